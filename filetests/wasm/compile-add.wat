@@ -0,0 +1,12 @@
+;; Translate a text module and compile it for Intel.
+;; test wasm-compile
+;; set is_64bit=1
+;; isa intel haswell
+
+(module
+  (func (param i32 i32) (result i32)
+    (i32.add (get_local 0) (get_local 1))))
+
+;; check: function u0:0(i32 [%rdi], i32 [%rsi], i64 vmctx [%rdx]
+;; check: iadd v0, v1
+;; check: ; code size: 36 bytes
@@ -33,3 +33,11 @@ jump_table = EntityRefKind(
 
 #: A reference to a heap declared in the function preamble.
 heap = EntityRefKind('heap', 'A heap.')
+
+#: A reference to a table declared in the function preamble.
+table = EntityRefKind('table', 'A table.')
+
+#: A reference to a vector lane mask.
+#: This is used by the vector shuffle instructions to select lanes.
+lane_mask = EntityRefKind(
+        'lane_mask', 'A vector lane mask.', default_member='mask')
@@ -10,8 +10,10 @@ from cdsl.formats import InstructionFormat
 from cdsl.operands import VALUE, VARIABLE_ARGS
 from .immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
 from .immediates import boolean, intcc, floatcc, memflags, regunit, trapcode
+from .immediates import ordering, atomic_rmw_op
 from . import entities
-from .entities import ebb, sig_ref, func_ref, stack_slot, heap
+from .entities import ebb, sig_ref, func_ref, stack_slot, heap, table
+from .entities import lane_mask
 
 Unary = InstructionFormat(VALUE)
 UnaryImm = InstructionFormat(imm64)
@@ -36,6 +38,8 @@ NullAry = InstructionFormat()
 
 InsertLane = InstructionFormat(VALUE, ('lane', uimm8), VALUE)
 ExtractLane = InstructionFormat(VALUE, ('lane', uimm8))
+Shuffle = InstructionFormat(VALUE, VALUE, lane_mask)
+Swizzle = InstructionFormat(VALUE, lane_mask)
 
 IntCompare = InstructionFormat(intcc, VALUE, VALUE)
 IntCompareImm = InstructionFormat(intcc, VALUE, imm64)
@@ -54,17 +58,42 @@ BranchTable = InstructionFormat(VALUE, entities.jump_table)
 
 Call = InstructionFormat(func_ref, VARIABLE_ARGS)
 IndirectCall = InstructionFormat(sig_ref, VALUE, VARIABLE_ARGS)
+TryCall = InstructionFormat(func_ref, ebb, VARIABLE_ARGS)
 FuncAddr = InstructionFormat(func_ref)
 
 Load = InstructionFormat(memflags, VALUE, offset32)
 Store = InstructionFormat(memflags, VALUE, VALUE, offset32)
 
+# Memory accesses with a base address and a scaled index. The store operands
+# don't fit in an `InstructionData`, so they are kept in a value list.
+LoadComplex = InstructionFormat(
+        memflags, VALUE, VALUE, ('scale', uimm8), offset32)
+StoreComplex = InstructionFormat(
+        memflags, VARIABLE_ARGS, ('scale', uimm8), offset32)
+
+# Atomic memory operations access exactly the address given, so they don't
+# have an offset. The `atomic_rmw` and `atomic_cas` instructions are controlled
+# by the type of the value operands, not the address.
+AtomicLoad = InstructionFormat(ordering, memflags, VALUE)
+AtomicStore = InstructionFormat(ordering, memflags, VALUE, VALUE)
+AtomicRmw = InstructionFormat(
+        atomic_rmw_op, ordering, memflags, VALUE, VALUE, typevar_operand=1)
+AtomicCas = InstructionFormat(
+        ordering, memflags, VALUE, VALUE, VALUE, typevar_operand=1)
+Fence = InstructionFormat(ordering)
+
+# Bulk memory operations take a destination address and two more values.
+BulkMemory = InstructionFormat(memflags, VALUE, VALUE, VALUE)
+
 StackLoad = InstructionFormat(stack_slot, offset32)
 StackStore = InstructionFormat(VALUE, stack_slot, offset32)
 
 # Accessing a WebAssembly heap.
 HeapAddr = InstructionFormat(heap, VALUE, uimm32)
 
+# Accessing a WebAssembly table.
+TableAddr = InstructionFormat(table, VALUE, offset32)
+
 RegMove = InstructionFormat(VALUE, ('src', regunit), ('dst', regunit))
 CopySpecial = InstructionFormat(('src', regunit), ('dst', regunit))
 RegSpill = InstructionFormat(
@@ -72,6 +101,10 @@ RegSpill = InstructionFormat(
 RegFill = InstructionFormat(
         VALUE, ('src', entities.stack_slot), ('dst', regunit))
 
+# Source-level debug information.
+DebugValue = InstructionFormat(VALUE, ('var', uimm32))
+DeoptPoint = InstructionFormat(('id', uimm32), VARIABLE_ARGS)
+
 Trap = InstructionFormat(trapcode)
 CondTrap = InstructionFormat(VALUE, trapcode)
 IntCondTrap = InstructionFormat(intcc, VALUE, trapcode)
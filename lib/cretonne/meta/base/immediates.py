@@ -118,6 +118,43 @@ trapcode = ImmediateKind(
         values={
             "stk_ovf": 'StackOverflow',
             "heap_oob": 'HeapOutOfBounds',
+            "table_oob": 'TableOutOfBounds',
             "int_ovf": 'IntegerOverflow',
             "int_divz": 'IntegerDivisionByZero',
         })
+
+#: A memory ordering constraint for atomic memory operations.
+#:
+#: This enumerated operand kind is used for the :cton:inst:`atomic_load`
+#: family of instructions and :cton:inst:`fence`. It corresponds to the
+#: `ir::AtomicOrdering` Rust type.
+ordering = ImmediateKind(
+        'ordering',
+        'A memory ordering constraint.',
+        default_member='ordering',
+        rust_type='ir::AtomicOrdering',
+        values={
+            'relaxed': 'Relaxed',
+            'acquire': 'Acquire',
+            'release': 'Release',
+            'acq_rel': 'AcqRel',
+            'seq_cst': 'SeqCst',
+        })
+
+#: The operation performed by an :cton:inst:`atomic_rmw` instruction.
+#:
+#: The operations are named after the base instructions that compute the new
+#: value.
+atomic_rmw_op = ImmediateKind(
+        'atomic_rmw_op',
+        'An atomic read-modify-write operation.',
+        default_member='op',
+        rust_type='ir::AtomicRmwOp',
+        values={
+            'iadd': 'Add',
+            'isub': 'Sub',
+            'band': 'And',
+            'bor':  'Or',
+            'bxor': 'Xor',
+            'xchg': 'Xchg',
+        })
@@ -8,10 +8,10 @@ from __future__ import absolute_import
 from cdsl.operands import Operand, VARIABLE_ARGS
 from cdsl.typevar import TypeVar
 from cdsl.instructions import Instruction, InstructionGroup
-from base.types import f32, f64, b1, iflags, fflags
+from base.types import i8, f32, f64, b1, iflags, fflags
 from base.immediates import imm64, uimm8, uimm32, ieee32, ieee64, offset32
 from base.immediates import boolean, intcc, floatcc, memflags, regunit
-from base.immediates import trapcode
+from base.immediates import trapcode, ordering, atomic_rmw_op
 from base import entities
 from cdsl.ti import WiderOrEq
 import base.formats  # noqa
@@ -135,9 +135,11 @@ br_table = Instruction(
         'br_table', r"""
         Indirect branch via jump table.
 
-        Use ``x`` as an unsigned index into the jump table ``JT``. If a jump
-        table entry is found, branch to the corresponding EBB. If no entry was
-        found fall through to the next instruction.
+        Subtract the minimum index of the jump table ``JT`` from ``x`` and use
+        the difference as an unsigned index into the table. If a jump table
+        entry is found, branch to the corresponding EBB. If no entry was found,
+        branch to the default EBB of the table, or fall through to the next
+        instruction if the table has no default EBB.
 
         Note that this branch instruction can't pass arguments to the targeted
         blocks. Split critical edges as needed to work around this.
@@ -212,6 +214,36 @@ call = Instruction(
         """,
         ins=(FN, args), outs=rvals, is_call=True)
 
+call_patchable = Instruction(
+        'call_patchable', r"""
+        Direct function call that can be repointed at run time.
+
+        Call a function like :inst:`call`, but emit a fixed-length call
+        sequence whose target displacement is naturally aligned, so it can be
+        overwritten atomically while other threads may be executing the code.
+        The code offset of each patchable call is reported to the embedder's
+        patch sink when the function is emitted.
+        """,
+        ins=(FN, args), outs=rvals, is_call=True)
+
+EH = Operand('EH', entities.ebb, doc='landing pad for exceptions')
+
+try_call = Instruction(
+        'try_call', r"""
+        Direct function call with an exceptional edge.
+
+        Call a function like :inst:`call`. If the callee returns normally,
+        execution continues with the next instruction. If the call unwinds
+        because of an exception, control is transferred to the landing pad
+        ``EH`` instead.
+
+        The landing pad can't take EBB arguments, and it can't use the return
+        values of the call. Values that are live into the landing pad are kept
+        on the stack across the call so the unwinder doesn't need to restore
+        any registers.
+        """,
+        ins=(FN, EH, args), outs=rvals, is_call=True, is_branch=True)
+
 SIG = Operand('SIG', entities.sig_ref, doc='function signature')
 callee = Operand('callee', iAddr, doc='address of function to call')
 
@@ -265,6 +297,32 @@ store = Instruction(
         """,
         ins=(Flags, x, p, Offset), can_store=True)
 
+iIndex = TypeVar('iIndex', 'An integer address index type', ints=(32, 64))
+Index = Operand(
+        'Index', iIndex, doc='Index to be scaled and added to ``p``')
+Scale = Operand(
+        'Scale', uimm8, doc='Number of bits to shift ``Index`` left by')
+
+load_complex = Instruction(
+        'load_complex', r"""
+        Load from memory at ``p + (Index << Scale) + Offset``.
+
+        This is a polymorphic instruction that can load any value type which
+        has a memory representation. It is formed by the post-legalization
+        optimizer on targets with complex addressing modes.
+        """,
+        ins=(Flags, p, Index, Scale, Offset), outs=a, can_load=True)
+
+store_complex = Instruction(
+        'store_complex', r"""
+        Store ``x`` to memory at ``p + (Index << Scale) + Offset``.
+
+        This is a polymorphic instruction that can store any value type with a
+        memory representation. It is formed by the post-legalization
+        optimizer on targets with complex addressing modes.
+        """,
+        ins=(Flags, x, p, Index, Scale, Offset), can_store=True)
+
 iExt8 = TypeVar(
         'iExt8', 'An integer type with more than 8 bits',
         ints=(16, 64))
@@ -355,6 +413,122 @@ istore32 = Instruction(
         """,
         ins=(Flags, x, p, Offset), can_store=True)
 
+#
+# Atomic memory operations.
+#
+
+iAtomic = TypeVar(
+        'iAtomic', 'An integer type that can be accessed atomically',
+        ints=(32, 64))
+x = Operand('x', iAtomic)
+a = Operand('a', iAtomic)
+Ordering = Operand('Ordering', ordering)
+
+atomic_load = Instruction(
+        'atomic_load', r"""
+        Atomically load from memory at ``p``.
+
+        The memory ordering must be one of ``relaxed``, ``acquire``, or
+        ``seq_cst``. The address must be aligned to the size of the loaded
+        type.
+        """,
+        ins=(Ordering, Flags, p), outs=a,
+        can_load=True, other_side_effects=True)
+
+atomic_store = Instruction(
+        'atomic_store', r"""
+        Atomically store ``x`` to memory at ``p``.
+
+        The memory ordering must be one of ``relaxed``, ``release``, or
+        ``seq_cst``. The address must be aligned to the size of the stored
+        type.
+        """,
+        ins=(Ordering, Flags, x, p),
+        can_store=True, other_side_effects=True)
+
+Op = Operand('Op', atomic_rmw_op)
+a = Operand('a', iAtomic, doc='The previous value in memory')
+
+atomic_rmw = Instruction(
+        'atomic_rmw', r"""
+        Atomically read-modify-write memory at ``p``.
+
+        Load the value at ``p``, combine it with ``x`` using ``Op``, and store
+        the result back to ``p`` as a single atomic operation. The ``xchg``
+        operation stores ``x`` unchanged.
+
+        Returns the value that was in memory before the operation.
+        """,
+        ins=(Op, Ordering, Flags, p, x), outs=a,
+        can_load=True, can_store=True, other_side_effects=True)
+
+e = Operand('e', iAtomic, doc='Expected value')
+r = Operand('r', iAtomic, doc='Replacement value')
+
+atomic_cas = Instruction(
+        'atomic_cas', r"""
+        Atomically compare and swap memory at ``p``.
+
+        If the value in memory at ``p`` is equal to ``e``, replace it with
+        ``r``. The comparison and the store happen as a single atomic
+        operation.
+
+        Returns the value that was in memory before the operation. The swap
+        happened if and only if that value is equal to ``e``.
+        """,
+        ins=(Ordering, Flags, p, e, r), outs=a,
+        can_load=True, can_store=True, other_side_effects=True)
+
+fence = Instruction(
+        'fence', r"""
+        A memory fence.
+
+        Order memory accesses before and after the fence as required by
+        ``Ordering``, which can't be ``relaxed``.
+        """,
+        ins=Ordering, other_side_effects=True)
+
+#
+# Bulk memory operations.
+#
+
+dest = Operand('dest', iAddr, doc='Destination address')
+src = Operand('src', iAddr, doc='Source address')
+Len = Operand('len', iAddr, doc='Number of bytes')
+byte = Operand('byte', i8, doc='Fill value')
+
+memcpy = Instruction(
+        'memcpy', r"""
+        Copy ``len`` bytes from ``src`` to ``dest``.
+
+        The source and destination regions must not overlap. Use
+        :inst:`memmove` for regions that may overlap.
+
+        Small constant lengths are expanded into loads and stores, and other
+        lengths call the ``Memcpy`` library routine. The ``notrap`` flag
+        applies to the expanded accesses.
+        """,
+        ins=(Flags, dest, src, Len), can_load=True, can_store=True)
+
+memmove = Instruction(
+        'memmove', r"""
+        Copy ``len`` bytes from ``src`` to ``dest``, where the regions may
+        overlap.
+
+        Small constant lengths are expanded into loads and stores, and other
+        lengths call the ``Memmove`` library routine.
+        """,
+        ins=(Flags, dest, src, Len), can_load=True, can_store=True)
+
+memset = Instruction(
+        'memset', r"""
+        Fill ``len`` bytes at ``dest`` with ``byte``.
+
+        Small constant lengths are expanded into stores, and other lengths
+        call the ``Memset`` library routine.
+        """,
+        ins=(Flags, dest, byte, Len), can_store=True)
+
 x = Operand('x', Mem, doc='Value to be stored')
 a = Operand('a', Mem, doc='Value loaded')
 Offset = Operand('Offset', offset32, 'In-bounds offset into stack slot')
@@ -415,6 +589,20 @@ globalsym_addr = Instruction(
         """,
         ins=GV, outs=addr)
 
+gotsym_addr = Instruction(
+        'gotsym_addr', r"""
+        Compute the address of global variable GV, which is a symbolic name,
+        by loading it from the global offset table.
+        """,
+        ins=GV, outs=addr)
+
+tlssym_addr = Instruction(
+        'tlssym_addr', r"""
+        Compute the address of global variable GV, which is a thread-local
+        symbolic name, in the current thread.
+        """,
+        ins=GV, outs=addr)
+
 #
 # WebAssembly bounds-checked heap accesses.
 #
@@ -439,6 +627,32 @@ heap_addr = Instruction(
         """,
         ins=(H, p, Size), outs=addr)
 
+#
+# WebAssembly bounds-checked table accesses.
+#
+
+TableOffset = TypeVar('TableOffset', 'An unsigned table offset', ints=(32, 64))
+
+T = Operand('T', entities.table)
+p = Operand('p', TableOffset)
+Offset = Operand('Offset', offset32, 'Byte offset from element address')
+
+table_addr = Instruction(
+        'table_addr', r"""
+        Bounds check and compute absolute address of a table entry.
+
+        Verify that the element index ``p`` is in bounds for the table T, and
+        generate an absolute address that is safe to dereference.
+
+        ``Offset`` must be less than the size of a table element.
+
+        1. If ``p`` is less than the table bound, return an absolute address
+           corresponding to a byte offset of ``p * element_size + Offset``
+           from the table's base address.
+        2. If ``p`` is not less than the table bound, generate a trap.
+        """,
+        ins=(T, p, Offset), outs=addr)
+
 #
 # Materializing constants.
 #
@@ -545,6 +759,46 @@ fill = Instruction(
         """,
         ins=x, outs=a, can_load=True)
 
+var = Operand('var', uimm32, doc='Frontend variable number')
+
+debug_value = Instruction(
+        'debug_value', r"""
+        Associate the source-level variable ``var`` with the value ``x``.
+
+        From this point on, a debugger should display the variable ``var`` by
+        reading the location of ``x``. The variable numbers are chosen by the
+        frontend, for example WebAssembly local indices.
+
+        This instruction generates no code and it is never given an encoding.
+        Optimization passes keep it in place, and after register allocation
+        the location of ``x`` at this point is resolved and reported in
+        ``Context::debug_values``.
+        """,
+        ins=(x, var),
+        other_side_effects=True)
+
+deopt_id = Operand('id', uimm32, doc='Deoptimization point number')
+deopt_args = Operand(
+        'args', VARIABLE_ARGS,
+        doc='Values needed to reconstruct the frontend state')
+
+deopt_point = Instruction(
+        'deopt_point', r"""
+        Record the locations of ``args`` at deoptimization point ``id``.
+
+        A speculative JIT compiler that bails out of optimized code needs to
+        reconstruct its interpreter state from the values live at the bailout.
+        The frontend lists those values here, and the point numbers are chosen
+        by the frontend.
+
+        This instruction generates no code and it is never given an encoding.
+        It keeps its arguments alive, and after register allocation the
+        locations of ``args`` at this point are resolved and reported in
+        ``Context::deopt_points``.
+        """,
+        ins=(deopt_id, deopt_args),
+        other_side_effects=True)
+
 src = Operand('src', regunit)
 dst = Operand('dst', regunit)
 
@@ -729,6 +983,39 @@ extractlane = Instruction(
         """,
         ins=(x, Idx), outs=a)
 
+x = Operand('x', TxN)
+y = Operand('y', TxN)
+a = Operand('a', TxN)
+Mask = Operand('Mask', entities.lane_mask, doc='Lane indexes')
+
+shuffle = Instruction(
+        'shuffle', r"""
+        Vector shuffle.
+
+        Select lanes from the concatenation of ``x`` and ``y``. Lane ``i`` of
+        the result is lane ``Mask[i]`` of ``x`` when ``Mask[i]`` is less than
+        the number of lanes ``n``, and lane ``Mask[i] - n`` of ``y``
+        otherwise.
+
+        The mask is an immediate list of lane indexes, not an SSA value. It
+        must have one index per lane in the result, and each index must be
+        less than ``2 * n``.
+        """,
+        ins=(x, y, Mask), outs=a)
+
+swizzle = Instruction(
+        'swizzle', r"""
+        Vector swizzle.
+
+        Rearrange the lanes of ``x``. Lane ``i`` of the result is lane
+        ``Mask[i]`` of ``x``.
+
+        The mask is an immediate list of lane indexes, not an SSA value. It
+        must have one index per lane in the result, and each index must be a
+        valid lane index for the type of ``x``.
+        """,
+        ins=(x, Mask), outs=a)
+
 #
 # Integer arithmetic
 #
@@ -810,7 +1097,7 @@ iadd = Instruction(
         This instruction does not depend on the signed/unsigned interpretation
         of the operands.
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 isub = Instruction(
         'isub', r"""
@@ -831,7 +1118,7 @@ imul = Instruction(
 
         Polymorphic over all integer types (vector and scalar).
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 umulhi = Instruction(
         'umulhi', r"""
@@ -841,7 +1128,7 @@ umulhi = Instruction(
         Polymorphic over all scalar integer types, but does not support vector
         types.
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 smulhi = Instruction(
         'smulhi', """
@@ -851,7 +1138,7 @@ smulhi = Instruction(
         Polymorphic over all scalar integer types, but does not support vector
         types.
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 udiv = Instruction(
         'udiv', r"""
@@ -1062,6 +1349,66 @@ isub_borrow = Instruction(
         """,
         ins=(x, y, b_in), outs=(a, b_out))
 
+#
+# Integer arithmetic with overflow detection.
+#
+of = Operand('of', b1, doc="Overflow flag")
+
+sadd_overflow = Instruction(
+        'sadd_overflow', r"""
+        Add signed integers with overflow detection.
+
+        Same as :inst:`iadd` with an additional overflow flag output which is
+        true when the sum of ``x`` and ``y`` interpreted as signed integers
+        doesn't fit in :math:`B` bits.
+
+        Use :inst:`iadd_cout` to detect unsigned overflow.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
+ssub_overflow = Instruction(
+        'ssub_overflow', r"""
+        Subtract signed integers with overflow detection.
+
+        Same as :inst:`isub` with an additional overflow flag output which is
+        true when the difference of ``x`` and ``y`` interpreted as signed
+        integers doesn't fit in :math:`B` bits.
+
+        Use :inst:`isub_bout` to detect unsigned overflow.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
+umul_overflow = Instruction(
+        'umul_overflow', r"""
+        Multiply unsigned integers with overflow detection.
+
+        Same as :inst:`imul` with an additional overflow flag output which is
+        true when :math:`x y >= 2^B`.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
+smul_overflow = Instruction(
+        'smul_overflow', r"""
+        Multiply signed integers with overflow detection.
+
+        Same as :inst:`imul` with an additional overflow flag output which is
+        true when the product of ``x`` and ``y`` interpreted as signed integers
+        doesn't fit in :math:`B` bits.
+
+        Polymorphic over all scalar integer types, but does not support vector
+        types.
+        """,
+        ins=(x, y), outs=(a, of))
+
 #
 # Bitwise operations.
 #
@@ -1079,19 +1426,19 @@ band = Instruction(
         'band', """
         Bitwise and.
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 bor = Instruction(
         'bor', """
         Bitwise or.
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 bxor = Instruction(
         'bxor', """
         Bitwise xor.
         """,
-        ins=(x, y), outs=a)
+        ins=(x, y), outs=a, is_commutative=True)
 
 bnot = Instruction(
         'bnot', """
@@ -1306,6 +1653,27 @@ popcnt = Instruction(
         """,
         ins=x, outs=a)
 
+#
+# Bit and byte reordering.
+#
+
+bswap = Instruction(
+        'bswap', r"""
+        Reverse the order of the bytes in ``x``.
+
+        This converts an integer between little-endian and big-endian
+        representations.
+        """,
+        ins=x, outs=a)
+
+bitrev = Instruction(
+        'bitrev', r"""
+        Reverse the order of the bits in ``x``.
+
+        The LSB of ``x`` becomes the MSB of ``a`` and vice versa.
+        """,
+        ins=x, outs=a)
+
 #
 # Floating point.
 #
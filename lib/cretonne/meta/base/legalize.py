@@ -12,14 +12,17 @@ from . import instructions as insts
 from . import types
 from .instructions import iadd, iadd_cout, iadd_cin, iadd_carry, iadd_imm
 from .instructions import isub, isub_bin, isub_bout, isub_borrow, irsub_imm
-from .instructions import imul, imul_imm
+from .instructions import imul, imul_imm, umulhi, smulhi
+from .instructions import sadd_overflow, ssub_overflow
+from .instructions import umul_overflow, smul_overflow
 from .instructions import sdiv, sdiv_imm, udiv, udiv_imm
 from .instructions import srem, srem_imm, urem, urem_imm
 from .instructions import band, bor, bxor, isplit, iconcat
 from .instructions import bnot, band_not, bor_not, bxor_not
 from .instructions import band_imm, bor_imm, bxor_imm
 from .instructions import icmp, icmp_imm, ifcmp, ifcmp_imm
-from .instructions import iconst, bint, select
+from .instructions import iconst, bint, select, uextend, sextend, ireduce
+from .instructions import bswap, bitrev
 from .instructions import ishl, ishl_imm, sshr, sshr_imm, ushr, ushr_imm
 from .instructions import rotl, rotl_imm, rotr, rotr_imm
 from .instructions import f32const, f64const
@@ -62,6 +65,21 @@ expand_flags = XFormGroup('expand_flags', """
 # Custom expansions for memory objects.
 expand.custom_legalize(insts.global_addr, 'expand_global_addr')
 expand.custom_legalize(insts.heap_addr, 'expand_heap_addr')
+expand.custom_legalize(insts.table_addr, 'expand_table_addr')
+expand.custom_legalize(insts.load_complex, 'expand_complex_addr')
+expand.custom_legalize(insts.store_complex, 'expand_complex_addr')
+
+# Custom expansions for atomic memory operations that the target can't encode
+# directly.
+expand.custom_legalize(insts.atomic_load, 'expand_atomic_load')
+expand.custom_legalize(insts.atomic_store, 'expand_atomic_store')
+expand.custom_legalize(insts.atomic_rmw, 'expand_atomic_rmw')
+
+# Custom expansions for bulk memory operations, which become loads and stores
+# or library calls.
+expand.custom_legalize(insts.memcpy, 'expand_bulk_memory')
+expand.custom_legalize(insts.memmove, 'expand_bulk_memory')
+expand.custom_legalize(insts.memset, 'expand_bulk_memory')
 
 # Custom expansions that need to change the CFG.
 # TODO: Add sufficient XForm syntax that we don't need to hand-code these.
@@ -70,6 +88,12 @@ expand.custom_legalize(insts.trapnz, 'expand_cond_trap')
 expand.custom_legalize(insts.br_table, 'expand_br_table')
 expand.custom_legalize(insts.select, 'expand_select')
 
+# The 32-bit extending loads only produce `i64`, so they are legalized as
+# monomorphic instructions. Split them into a 32-bit load and a constant or
+# sign bits for the high half.
+expand.custom_legalize(insts.uload32, 'narrow_load')
+expand.custom_legalize(insts.sload32, 'narrow_load')
+
 # Custom expansions for floating point constants.
 # These expansions require bit-casting or creating constant pool entries.
 expand.custom_legalize(insts.f32const, 'expand_fconst')
@@ -80,9 +104,12 @@ y = Var('y')
 a = Var('a')
 a1 = Var('a1')
 a2 = Var('a2')
+a3 = Var('a3')
 b = Var('b')
 b1 = Var('b1')
 b2 = Var('b2')
+b3 = Var('b3')
+b4 = Var('b4')
 b_in = Var('b_in')
 b_int = Var('b_int')
 c = Var('c')
@@ -97,6 +124,8 @@ yh = Var('yh')
 al = Var('al')
 ah = Var('ah')
 cc = Var('cc')
+o = Var('o')
+o1 = Var('o1')
 
 narrow.legalize(
         a << iadd(x, y),
@@ -139,6 +168,129 @@ narrow.legalize(
             a << iconcat(al, ah)
         ))
 
+narrow.legalize(
+        a << bnot(x),
+        Rtl(
+            (xl, xh) << isplit(x),
+            al << bnot(xl),
+            ah << bnot(xh),
+            a << iconcat(al, ah)
+        ))
+
+# Integer comparisons. Equality compares both halves, and the ordered
+# comparisons compare the high halves first, using an unsigned comparison of
+# the low halves to break a tie.
+narrow.legalize(
+        b << icmp(intcc.eq, x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            b1 << icmp(intcc.eq, xl, yl),
+            b2 << icmp(intcc.eq, xh, yh),
+            b << band(b1, b2)
+        ))
+
+narrow.legalize(
+        b << icmp(intcc.ne, x, y),
+        Rtl(
+            (xl, xh) << isplit(x),
+            (yl, yh) << isplit(y),
+            b1 << icmp(intcc.ne, xl, yl),
+            b2 << icmp(intcc.ne, xh, yh),
+            b << bor(b1, b2)
+        ))
+
+for cond,               cond_hi,    cond_lo in [
+        (intcc.slt, intcc.slt, intcc.ult),
+        (intcc.sle, intcc.slt, intcc.ule),
+        (intcc.sgt, intcc.sgt, intcc.ugt),
+        (intcc.sge, intcc.sgt, intcc.uge),
+        (intcc.ult, intcc.ult, intcc.ult),
+        (intcc.ule, intcc.ult, intcc.ule),
+        (intcc.ugt, intcc.ugt, intcc.ugt),
+        (intcc.uge, intcc.ugt, intcc.uge)]:
+    narrow.legalize(
+            b << icmp(cond, x, y),
+            Rtl(
+                (xl, xh) << isplit(x),
+                (yl, yh) << isplit(y),
+                b1 << icmp(cond_hi, xh, yh),
+                b2 << icmp(intcc.eq, xh, yh),
+                b3 << icmp(cond_lo, xl, yl),
+                b4 << band(b2, b3),
+                b << bor(b1, b4)
+            ))
+
+# Conditional traps test both halves.
+for inst in [insts.trapz, insts.trapnz]:
+    narrow.legalize(
+            inst(x, c),
+            Rtl(
+                (xl, xh) << isplit(x),
+                a << bor(xl, xh),
+                inst(a, c)
+            ))
+
+# Extensions and reductions.
+narrow.legalize(
+        a << uextend.i64.i32(x),
+        Rtl(
+            ah << iconst.i32(imm64(0)),
+            a << iconcat(x, ah)
+        ))
+
+narrow.legalize(
+        a << sextend.i64.i32(x),
+        Rtl(
+            ah << sshr_imm(x, imm64(31)),
+            a << iconcat(x, ah)
+        ))
+
+for ty in [types.i8, types.i16]:
+    narrow.legalize(
+            a << uextend.i64.bind(ty)(x),
+            Rtl(
+                al << uextend.i32(x),
+                ah << iconst.i32(imm64(0)),
+                a << iconcat(al, ah)
+            ))
+    narrow.legalize(
+            a << sextend.i64.bind(ty)(x),
+            Rtl(
+                al << sextend.i32(x),
+                ah << sshr_imm(al, imm64(31)),
+                a << iconcat(al, ah)
+            ))
+    narrow.legalize(
+            a << ireduce.bind(ty).i64(x),
+            Rtl(
+                (xl, xh) << isplit(x),
+                a << ireduce.bind(ty)(xl)
+            ))
+
+narrow.legalize(
+        a << bint.i64(x),
+        Rtl(
+            al << bint.i32(x),
+            ah << iconst.i32(imm64(0)),
+            a << iconcat(al, ah)
+        ))
+
+# Rotates are composed from shifts in opposite directions. The shift amounts
+# are taken modulo the type width, so the negated amount works for both
+# shifts.
+for inst,  inst1, inst2 in [
+        (rotl, ishl, ushr),
+        (rotr, ushr, ishl)]:
+    narrow.legalize(
+            a << inst(x, y),
+            Rtl(
+                a1 << inst1(x, y),
+                b_int << irsub_imm(y, imm64(0)),
+                a2 << inst2(x, b_int),
+                a << bor(a1, a2)
+            ))
+
 # Expand integer operations with carry for RISC architectures that don't have
 # the flags.
 expand.legalize(
@@ -189,7 +341,54 @@ expand.legalize(
             b << bor(b1, b2)
         ))
 
-# Expansions for immediate operands that are out of range.
+# Expand integer operations with overflow detection. Signed addition overflows
+# when the result has a different sign from both operands, and signed
+# subtraction overflows when the operands have different signs and the result
+# has a different sign from `x`.
+expand.legalize(
+        (a, o) << sadd_overflow(x, y),
+        Rtl(
+            a << iadd(x, y),
+            a1 << bxor(a, x),
+            a2 << bxor(a, y),
+            a3 << band(a1, a2),
+            o << icmp_imm(intcc.slt, a3, 0)
+        ))
+
+expand.legalize(
+        (a, o) << ssub_overflow(x, y),
+        Rtl(
+            a << isub(x, y),
+            a1 << bxor(x, y),
+            a2 << bxor(x, a),
+            a3 << band(a1, a2),
+            o << icmp_imm(intcc.slt, a3, 0)
+        ))
+
+# Multiplication overflows when the high half of the full product isn't the
+# extension of the low half. For the signed product, that means `hi + (a < 0)`
+# must be zero.
+expand.legalize(
+        (a, o) << umul_overflow(x, y),
+        Rtl(
+            a << imul(x, y),
+            a1 << umulhi(x, y),
+            o << icmp_imm(intcc.ne, a1, 0)
+        ))
+
+expand.legalize(
+        (a, o) << smul_overflow(x, y),
+        Rtl(
+            a << imul(x, y),
+            a1 << smulhi(x, y),
+            o1 << icmp_imm(intcc.slt, a, 0),
+            a2 << bint(o1),
+            a3 << iadd(a1, a2),
+            o << icmp_imm(intcc.ne, a3, 0)
+        ))
+
+# Expansions for immediate operands that are out of range. Wide integers are
+# narrowed the same way, by narrowing the materialized constant.
 for inst_imm,      inst in [
         (iadd_imm, iadd),
         (imul_imm, imul),
@@ -199,7 +398,7 @@ for inst_imm,      inst in [
         (urem_imm, urem),
         (band_imm, band),
         (bor_imm, bor),
-        (bxor_imm, bor),
+        (bxor_imm, bxor),
         (ifcmp_imm, ifcmp)]:
     expand.legalize(
             a << inst_imm(x, y),
@@ -207,12 +406,20 @@ for inst_imm,      inst in [
                 a1 << iconst(y),
                 a << inst(x, a1)
             ))
-expand.legalize(
-    a << irsub_imm(y, x),
-    Rtl(
-        a1 << iconst(x),
-        a << isub(a1, y)
-    ))
+    if inst_imm is not ifcmp_imm:
+        narrow.legalize(
+                a << inst_imm(x, y),
+                Rtl(
+                    a1 << iconst(y),
+                    a << inst(x, a1)
+                ))
+for group in [expand, narrow]:
+    group.legalize(
+        a << irsub_imm(y, x),
+        Rtl(
+            a1 << iconst(x),
+            a << isub(a1, y)
+        ))
 
 # Rotates and shifts.
 for inst_imm,      inst in [
@@ -221,18 +428,26 @@ for inst_imm,      inst in [
         (ishl_imm, ishl),
         (sshr_imm, sshr),
         (ushr_imm, ushr)]:
-    expand.legalize(
-            a << inst_imm(x, y),
+    for group in [expand, narrow]:
+        group.legalize(
+                a << inst_imm(x, y),
+                Rtl(
+                    a1 << iconst.i32(y),
+                    a << inst(x, a1)
+                ))
+
+for group in [expand, narrow]:
+    group.legalize(
+            a << icmp_imm(cc, x, y),
             Rtl(
-                a1 << iconst.i32(y),
-                a << inst(x, a1)
+                a1 << iconst(y),
+                a << icmp(cc, x, a1)
             ))
 
 expand.legalize(
-        a << icmp_imm(cc, x, y),
+        a << ireduce.i32.i64(x),
         Rtl(
-            a1 << iconst(y),
-            a << icmp(cc, x, a1)
+            (a, xh) << isplit(x)
         ))
 
 # Expansions for *_not variants of bitwise ops.
@@ -273,6 +488,102 @@ for ty,             minus_zero in [
             ))
 
 
+# Byte and bit reversal.
+#
+# Swap adjacent groups of bits with a shift-and-mask step for each group size,
+# and finish by swapping the two halves of the value. The 16-bit versions are
+# widened to 32 bits, and the 64-bit versions are narrowed to 32-bit halves
+# where needed.
+s1 = Var('s1')
+s2 = Var('s2')
+s3 = Var('s3')
+s4 = Var('s4')
+s5 = Var('s5')
+s6 = Var('s6')
+s7 = Var('s7')
+s8 = Var('s8')
+s9 = Var('s9')
+s10 = Var('s10')
+s11 = Var('s11')
+s12 = Var('s12')
+s13 = Var('s13')
+s14 = Var('s14')
+s15 = Var('s15')
+
+expand.legalize(
+        a << bswap.i32(x),
+        Rtl(
+            s1 << ushr_imm(x, imm64(8)),
+            s2 << band_imm(s1, imm64(0x00ff00ff)),
+            s3 << band_imm(x, imm64(0x00ff00ff)),
+            s4 << ishl_imm(s3, imm64(8)),
+            s5 << bor(s2, s4),
+            s6 << ushr_imm(s5, imm64(16)),
+            s7 << ishl_imm(s5, imm64(16)),
+            a << bor(s6, s7)
+        ))
+
+expand.legalize(
+        a << bswap.i64(x),
+        Rtl(
+            s1 << ushr_imm(x, imm64(8)),
+            s2 << band_imm(s1, imm64(0x00ff00ff00ff00ff)),
+            s3 << band_imm(x, imm64(0x00ff00ff00ff00ff)),
+            s4 << ishl_imm(s3, imm64(8)),
+            s5 << bor(s2, s4),
+            s6 << ushr_imm(s5, imm64(16)),
+            s7 << band_imm(s6, imm64(0x0000ffff0000ffff)),
+            s8 << band_imm(s5, imm64(0x0000ffff0000ffff)),
+            s9 << ishl_imm(s8, imm64(16)),
+            s10 << bor(s7, s9),
+            s11 << ushr_imm(s10, imm64(32)),
+            s12 << ishl_imm(s10, imm64(32)),
+            a << bor(s11, s12)
+        ))
+
+for ty, m1, m2, m4 in [
+        (types.i32, 0x55555555, 0x33333333, 0x0f0f0f0f),
+        (types.i64,
+         0x5555555555555555, 0x3333333333333333, 0x0f0f0f0f0f0f0f0f)]:
+    expand.legalize(
+            a << bitrev.bind(ty)(x),
+            Rtl(
+                s1 << ushr_imm(x, imm64(1)),
+                s2 << band_imm(s1, imm64(m1)),
+                s3 << band_imm(x, imm64(m1)),
+                s4 << ishl_imm(s3, imm64(1)),
+                s5 << bor(s2, s4),
+                s6 << ushr_imm(s5, imm64(2)),
+                s7 << band_imm(s6, imm64(m2)),
+                s8 << band_imm(s5, imm64(m2)),
+                s9 << ishl_imm(s8, imm64(2)),
+                s10 << bor(s7, s9),
+                s11 << ushr_imm(s10, imm64(4)),
+                s12 << band_imm(s11, imm64(m4)),
+                s13 << band_imm(s10, imm64(m4)),
+                s14 << ishl_imm(s13, imm64(4)),
+                s15 << bor(s12, s14),
+                a << bswap(s15)
+            ))
+
+for inst in [bswap, bitrev]:
+    widen.legalize(
+            a << inst.i16(x),
+            Rtl(
+                s1 << uextend.i32(x),
+                s2 << inst(s1),
+                s3 << ushr_imm(s2, imm64(16)),
+                a << ireduce.i16(s3)
+            ))
+    narrow.legalize(
+            a << inst.i64(x),
+            Rtl(
+                (xl, xh) << isplit(x),
+                al << inst(xh),
+                ah << inst(xl),
+                a << iconcat(al, ah)
+            ))
+
 # Expansions using CPU flags.
 expand_flags.custom_legalize(insts.stack_check, 'expand_stack_check')
 
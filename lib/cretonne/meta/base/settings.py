@@ -15,8 +15,12 @@ opt_level = EnumSetting(
         - default: Very profitable optimizations enabled, none slow.
         - best: Enable all optimizations
         - fastest: Optimize for compile time by disabling most optimizations.
+        - min_size: Minimize the size of the generated code. Optimizations
+          that grow the code like loop unrolling are disabled, library calls
+          are preferred over large inline expansions, alignment padding is
+          not inserted.
         """,
-        'default', 'best', 'fastest')
+        'default', 'best', 'fastest', 'min_size')
 
 enable_verifier = BoolSetting(
         """
@@ -53,13 +57,27 @@ avoid_div_traps = BoolSetting(
         this setting has no effect - explicit checks are always inserted.
         """)
 
+enable_heap_access_spectre_mitigation = BoolSetting(
+        """
+        Clamp the address computed by `heap_addr` to null when its bounds
+        check fails.
+
+        The bounds check branches to a trap, but the CPU can speculatively
+        execute the following memory access with an out-of-bounds address.
+        With this setting, the address is also selected with a conditional
+        move, so speculative accesses can't reach outside the heap.
+        """)
+
 is_compressed = BoolSetting("Enable compressed instructions")
 
 enable_float = BoolSetting(
         """
         Enable the use of floating-point instructions
 
-        Disabling use of floating-point instructions is not yet implemented.
+        When disabled, the legalizer represents floating point values as
+        integers and lowers floating point operations to runtime library
+        calls. This also passes floating point arguments and return values
+        like integers.
         """,
         default=True)
 
@@ -71,6 +89,55 @@ enable_atomics = BoolSetting(
         """Enable the use of atomic instructions""",
         default=True)
 
+enable_slp = BoolSetting(
+        """
+        Enable the experimental superword-level parallelism (SLP) vectorizer.
+
+        When `opt_level` is `best`, groups of adjacent scalar stores and the
+        isomorphic computations of the stored values are packed into vector
+        operations, provided the target can encode them.
+        """)
+
+unroll_budget = NumSetting(
+        """
+        Maximum number of instructions in a completely unrolled loop.
+
+        When `opt_level` is `best`, single-EBB loops with a constant trip
+        count are unrolled if the unrolled body has no more than this many
+        instructions. Zero disables loop unrolling.
+        """,
+        default=64)
+
+enable_ebb_counters = BoolSetting(
+        """
+        Insert an execution counter increment at the top of every EBB.
+
+        The counters live in a table addressed by a global variable that the
+        embedder supplies in `Context::ebb_counter_table`. This is useful for
+        collecting coverage information and execution profiles.
+        """)
+
+#
+# Code alignment.
+#
+function_align = NumSetting(
+        """
+        Minimum alignment in bytes of the start of a function.
+
+        The value is rounded up to a power of two. Zero means that the
+        function needs no more alignment than its instructions do.
+        """)
+
+loop_align = NumSetting(
+        """
+        Alignment in bytes of loop headers.
+
+        An EBB is considered a loop header when it is the target of a branch
+        from itself or a later EBB in the layout. Padding is inserted before
+        such EBBs so they start at an offset that is a multiple of this value,
+        rounded up to a power of two. Zero disables loop header alignment.
+        """)
+
 #
 # Settings specific to the `spiderwasm` calling convention.
 #
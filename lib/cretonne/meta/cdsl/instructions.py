@@ -95,6 +95,7 @@ class Instruction(object):
     :param can_load: This instruction can load from memory.
     :param can_store: This instruction can store to memory.
     :param other_side_effects: Instruction has other side effects.
+    :param is_commutative: The two value operands can be swapped.
     """
 
     # Boolean instruction attributes that can be passed as keyword arguments to
@@ -111,6 +112,7 @@ class Instruction(object):
             'other_side_effects':
             'Does this instruction have other side effects besides can_*',
             'writes_cpu_flags': 'Does this instruction write to CPU flags?',
+            'is_commutative': 'Can the two value operands be swapped?',
             }
 
     def __init__(self, name, doc, ins=(), outs=(), constraints=(), **kwargs):
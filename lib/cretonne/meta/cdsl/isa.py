@@ -469,7 +469,9 @@ class Encoding(object):
                 "Format {} must match recipe: {}".format(
                     self.inst.format, recipe.format))
 
-        if self.inst.is_branch:
+        # A `try_call` is also a branch, but its landing pad is reached by the
+        # unwinder, so the encoding doesn't need to reach it.
+        if self.inst.is_branch and not self.inst.is_call:
             assert recipe.branch_range, (
                     'Recipe {} for {} must have a branch_range'
                     .format(recipe, self.inst.name))
@@ -309,6 +309,40 @@ class IsUnsignedInt(FieldPredicate):
         assert scale >= 0 and scale < width
 
 
+
+class IsColocatedFunc(FieldPredicate):
+    """
+    Instruction predicate that checks if an external function referenced by a
+    `FuncRef` field is colocated with the function being compiled.
+
+    This predicate needs access to the whole function, so it can only be used
+    as an encoding predicate, not as a recipe predicate.
+
+    :param field: `FormatField` holding the function reference.
+    """
+
+    def __init__(self, field):
+        # type: (FormatField) -> None
+        super(IsColocatedFunc, self).__init__(
+                field, 'is_colocated_func', ('func',))
+
+
+class IsColocatedData(FieldPredicate):
+    """
+    Instruction predicate that checks if a symbolic global variable referenced
+    by a `GlobalVar` field is colocated with the function being compiled.
+
+    This predicate needs access to the whole function, so it can only be used
+    as an encoding predicate, not as a recipe predicate.
+
+    :param field: `FormatField` holding the global variable reference.
+    """
+
+    def __init__(self, field):
+        # type: (FormatField) -> None
+        super(IsColocatedData, self).__init__(
+                field, 'is_colocated_data', ('func',))
+
 class TypePredicate(object):
     """
     An instruction predicate that checks the type of an SSA argument value.
@@ -854,11 +854,11 @@ class TypeVar(object):
         """
         Get a Rust expression that computes the type of this type variable.
         """
-        if self.is_derived:
+        if self.singleton_type():
+            return self.singleton_type().rust_name()
+        elif self.is_derived:
             return '{}.{}()'.format(
                     self.base.rust_expr(), self.derived_func)
-        elif self.singleton_type():
-            return self.singleton_type().rust_name()
         else:
             return self.name
 
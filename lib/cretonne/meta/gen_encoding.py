@@ -53,7 +53,7 @@ instructions with different types for secondary type variables.
 from __future__ import absolute_import
 import srcgen
 from constant_hash import compute_quadratic
-from unique_table import UniqueSeqTable
+from unique_table import UniqueSeqTable, UniqueTable
 from collections import OrderedDict, defaultdict
 import math
 from itertools import groupby
@@ -74,7 +74,7 @@ except ImportError:
     pass
 
 
-def emit_instp(instp, fmt, has_dfg=False):
+def emit_instp(instp, fmt, has_func=False):
     # type: (PredNode, srcgen.Formatter, bool) -> None
     """
     Emit code for matching an instruction predicate against an
@@ -87,6 +87,7 @@ def emit_instp(instp, fmt, has_dfg=False):
 
     # Deal with pure type check predicates which apply to any instruction.
     if iform == instruction_context:
+        fmt.line('let dfg = &func.dfg;')
         fmt.line('let args = inst.arguments(&dfg.value_lists);')
         fmt.line(instp.rust_predicate(0))
         return
@@ -114,11 +115,12 @@ def emit_instp(instp, fmt, has_dfg=False):
             .format(iform.name, fields), '}'):
         if has_type_check:
             # We could implement this if we need to.
-            assert has_dfg, "Recipe predicates can't check type variables."
+            assert has_func, "Recipe predicates can't check type variables."
+            fmt.line('let dfg = &func.dfg;')
             fmt.line('let args = inst.arguments(&dfg.value_lists);')
-        elif has_dfg:
+        elif has_func:
             # Silence dead argument warning.
-            fmt.line('let _ = dfg;')
+            fmt.line('let _ = func;')
         fmt.format('return {};', instp.rust_predicate(0))
     fmt.line('unreachable!();')
 
@@ -132,9 +134,9 @@ def emit_inst_predicates(instps, fmt):
     for instp, number in instps.items():
         name = 'inst_predicate_{}'.format(number)
         with fmt.indented(
-                'fn {}(dfg: &ir::DataFlowGraph, inst: &ir::InstructionData)'
+                'fn {}(func: &ir::Function, inst: &ir::InstructionData)'
                 '-> bool {{'.format(name), '}'):
-            emit_instp(instp, fmt, has_dfg=True)
+            emit_instp(instp, fmt, has_func=True)
 
     # Generate the static table.
     with fmt.indented(
@@ -730,46 +732,153 @@ def offset_type(length):
         return 'u32'
 
 
-def emit_recipe_names(isa, fmt):
-    # type: (TargetISA, srcgen.Formatter) -> None
+class TextTable(object):
+    """
+    Collect names into a single string, removing duplicates.
+
+    Each name is mapped to a `TextRange` of byte offsets into the string.
+    """
+    def __init__(self):
+        # type: () -> None
+        self.names = list()  # type: List[str]
+        self.ranges = dict()  # type: Dict[str, Tuple[int, int]]
+        self.size = 0
+
+    def add(self, name):
+        # type: (str) -> Tuple[int, int]
+        if name not in self.ranges:
+            self.ranges[name] = (self.size, self.size + len(name))
+            self.names.append(name)
+            self.size += len(name)
+            assert self.size <= 0x10000, "Encoding names too long"
+        return self.ranges[name]
+
+
+def text_range(r):
+    # type: (Tuple[int, int]) -> str
+    return 'TextRange {{ start: {}, end: {} }}'.format(*r)
+
+
+def recipe_predicate_name(rcp):
+    # type: (EncRecipe) -> str
+    p = rcp.recipe_pred()
+    if p is None:
+        return ''
+    return ' && '.join(str(pred) for pred in p if pred)
+
+
+def emit_text(text, fmt):
+    # type: (TextTable, srcgen.Formatter) -> None
+    """
+    Emit the string holding all the recipe and predicate names.
+    """
+    with fmt.indented('static ENC_TEXT: &str = concat!(', ');'):
+        for name in text.names:
+            fmt.line('"{}",'.format(name))
+
+
+def emit_predicate_names(isa, text, fmt):
+    # type: (TargetISA, TextTable, srcgen.Formatter) -> None
     """
-    Emit a table of encoding recipe names keyed by recipe number.
+    Emit tables of instruction and ISA predicate names keyed by predicate
+    number.
 
-    This is used for pretty-printing encodings.
+    These are used to explain why an instruction can't be encoded.
     """
     with fmt.indented(
-            'static RECIPE_NAMES: [&str; {}] = ['
-            .format(len(isa.all_recipes)), '];'):
-        for r in isa.all_recipes:
-            fmt.line('"{}",'.format(r.name))
+            'static INST_PREDICATE_NAMES: [TextRange; {}] = ['
+            .format(len(isa.instp_number)), '];'):
+        for instp in isa.instp_number.keys():
+            fmt.comment(str(instp))
+            fmt.line(text_range(text.add(str(instp))) + ',')
+
+    sgrp = isa.settings
+    names = dict()  # type: Dict[PredNode, str]
+    for name, pred in sgrp.named_predicates.items():
+        names[pred] = '{}.{}'.format(sgrp.name, name)
+    with fmt.indented(
+            'static ISA_PREDICATE_NAMES: [TextRange; {}] = ['
+            .format(len(sgrp.predicate_number)), '];'):
+        for isap in sgrp.predicate_number.keys():
+            name = str(names.get(isap, isap))
+            fmt.comment(name)
+            fmt.line(text_range(text.add(name)) + ',')
 
 
-def emit_recipe_constraints(isa, fmt):
-    # type: (TargetISA, srcgen.Formatter) -> None
+def emit_recipe_infos(isa, text, fmt):
+    # type: (TargetISA, TextTable, srcgen.Formatter) -> None
     """
-    Emit a table of encoding recipe operand constraints keyed by recipe number.
+    Emit the packed per-recipe table along with the tables of unique operand
+    constraints and code size information it refers to.
 
-    These are used by the register allocator to pick registers that can be
-    properly encoded.
+    Many recipes share the same operand constraints and sizes, so they are
+    only emitted once.
     """
+    constraints = UniqueTable()
+    sizing = UniqueTable()
     with fmt.indented(
-            'static RECIPE_CONSTRAINTS: [RecipeConstraints; {}] = ['
+            'static RECIPE_INFOS: [RecipeInfo; {}] = ['
             .format(len(isa.all_recipes)), '];'):
         for r in isa.all_recipes:
-            fmt.comment('Constraints for recipe {}:'.format(r.name))
-            tied_i2o, tied_o2i = r.ties()
-            fixed_ins, fixed_outs = r.fixed_ops()
-            with fmt.indented('RecipeConstraints {', '},'):
-                emit_operand_constraints(
-                    r, r.ins, 'ins', tied_i2o, fixed_outs, fmt)
-                emit_operand_constraints(
-                    r, r.outs, 'outs', tied_o2i, fixed_ins, fmt)
-                fmt.format('fixed_ins: {},', str(bool(fixed_ins)).lower())
-                fmt.format('fixed_outs: {},', str(bool(fixed_outs)).lower())
-                fmt.format('tied_ops: {},', str(bool(tied_i2o)).lower())
+            fmt.comment('Recipe {}:'.format(r.name))
+            with fmt.indented('RecipeInfo {', '},'):
                 fmt.format(
-                        'clobbers_flags: {},',
-                        str(bool(r.clobbers_flags)).lower())
+                        'constraints: {},',
+                        constraints.add(recipe_constraints_source(r)))
+                fmt.format(
+                        'sizing: {},', sizing.add((r.size, r.branch_range)))
+                fmt.format('name: {},', text_range(text.add(r.name)))
+                fmt.format(
+                        'predicate_name: {},',
+                        text_range(text.add(recipe_predicate_name(r))))
+
+    with fmt.indented(
+            'static RECIPE_CONSTRAINTS: [RecipeConstraints; {}] = ['
+            .format(len(constraints.table)), '];'):
+        for src in constraints.table:
+            for line in src:
+                fmt.line(line)
+
+    with fmt.indented(
+            'static RECIPE_SIZING: [RecipeSizing; {}] = ['
+            .format(len(sizing.table)), '];'):
+        for size, branch_range in sizing.table:
+            with fmt.indented('RecipeSizing {', '},'):
+                fmt.format('bytes: {},', size)
+                if branch_range:
+                    fmt.format(
+                        'branch_range: '
+                        'Some(BranchRange {{ origin: {}, bits: {} }}),',
+                        *branch_range)
+                else:
+                    fmt.line('branch_range: None,')
+
+
+def recipe_constraints_source(r):
+    # type: (EncRecipe) -> Tuple[str, ...]
+    """
+    Generate the source lines of a `RecipeConstraints` initializer for the
+    operand constraints of recipe `r`.
+
+    These are used by the register allocator to pick registers that can be
+    properly encoded. Recipes with identical source lines share an entry in
+    the `RECIPE_CONSTRAINTS` table.
+    """
+    fmt = srcgen.Formatter()
+    tied_i2o, tied_o2i = r.ties()
+    fixed_ins, fixed_outs = r.fixed_ops()
+    with fmt.indented('RecipeConstraints {', '},'):
+        emit_operand_constraints(
+            r, r.ins, 'ins', tied_i2o, fixed_outs, fmt)
+        emit_operand_constraints(
+            r, r.outs, 'outs', tied_o2i, fixed_ins, fmt)
+        fmt.format('fixed_ins: {},', str(bool(fixed_ins)).lower())
+        fmt.format('fixed_outs: {},', str(bool(fixed_outs)).lower())
+        fmt.format('tied_ops: {},', str(bool(tied_i2o)).lower())
+        fmt.format(
+                'clobbers_flags: {},',
+                str(bool(r.clobbers_flags)).lower())
+    return tuple(line.rstrip('\n') for line in fmt.lines)
 
 
 def emit_operand_constraints(
@@ -821,27 +930,6 @@ def emit_operand_constraints(
                             'Unsupported constraint {}'.format(cons))
 
 
-def emit_recipe_sizing(isa, fmt):
-    # type: (TargetISA, srcgen.Formatter) -> None
-    """
-    Emit a table of encoding recipe code size information.
-    """
-    with fmt.indented(
-            'static RECIPE_SIZING: [RecipeSizing; {}] = ['
-            .format(len(isa.all_recipes)), '];'):
-        for r in isa.all_recipes:
-            fmt.comment('Code size information for recipe {}:'.format(r.name))
-            with fmt.indented('RecipeSizing {', '},'):
-                fmt.format('bytes: {},', r.size)
-                if r.branch_range:
-                    fmt.format(
-                        'branch_range: '
-                        'Some(BranchRange {{ origin: {}, bits: {} }}),',
-                        *r.branch_range)
-                else:
-                    fmt.line('branch_range: None,')
-
-
 def gen_isa(isa, fmt):
     # type: (TargetISA, srcgen.Formatter) -> None
 
@@ -880,15 +968,19 @@ def gen_isa(isa, fmt):
         emit_level1_hashtable(
                 cpumode, level1_tables[cpumode], level1_offt, fmt)
 
-    emit_recipe_names(isa, fmt)
-    emit_recipe_constraints(isa, fmt)
-    emit_recipe_sizing(isa, fmt)
+    text = TextTable()
+    emit_recipe_infos(isa, text, fmt)
+    emit_predicate_names(isa, text, fmt)
+    emit_text(text, fmt)
 
     # Finally, tie it all together in an `EncInfo`.
     with fmt.indented('pub static INFO: isa::EncInfo = isa::EncInfo {', '};'):
+        fmt.line('recipes: &RECIPE_INFOS,')
         fmt.line('constraints: &RECIPE_CONSTRAINTS,')
         fmt.line('sizing: &RECIPE_SIZING,')
-        fmt.line('names: &RECIPE_NAMES,')
+        fmt.line('text: ENC_TEXT,')
+        fmt.line('inst_predicate_names: &INST_PREDICATE_NAMES,')
+        fmt.line('isa_predicate_names: &ISA_PREDICATE_NAMES,')
 
 
 def generate(isas, out_dir):
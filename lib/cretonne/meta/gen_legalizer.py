@@ -103,18 +103,19 @@ def emit_runtime_typecheck(check, fmt, type_sets):
 
         base_exp = build_derived_expr(tv.base)
         if (tv.derived_func == TypeVar.LANEOF):
-            return "{}.map(|t: Type| -> t.lane_type())".format(base_exp)
+            return "{}.map(|t: ir::Type| t.lane_type())".format(base_exp)
         elif (tv.derived_func == TypeVar.ASBOOL):
-            return "{}.map(|t: Type| -> t.as_bool())".format(base_exp)
+            return "{}.map(|t: ir::Type| t.as_bool())".format(base_exp)
         elif (tv.derived_func == TypeVar.HALFWIDTH):
-            return "{}.and_then(|t: Type| -> t.half_width())".format(base_exp)
+            return "{}.and_then(|t: ir::Type| t.half_width())".format(base_exp)
         elif (tv.derived_func == TypeVar.DOUBLEWIDTH):
-            return "{}.and_then(|t: Type| -> t.double_width())"\
+            return "{}.and_then(|t: ir::Type| t.double_width())"\
                 .format(base_exp)
         elif (tv.derived_func == TypeVar.HALFVECTOR):
-            return "{}.and_then(|t: Type| -> t.half_vector())".format(base_exp)
+            return "{}.and_then(|t: ir::Type| t.half_vector())"\
+                .format(base_exp)
         elif (tv.derived_func == TypeVar.DOUBLEVECTOR):
-            return "{}.and_then(|t: Type| -> t.by(2))".format(base_exp)
+            return "{}.and_then(|t: ir::Type| t.by(2))".format(base_exp)
         else:
             assert False, "Unknown derived function {}".format(tv.derived_func)
 
@@ -267,13 +268,21 @@ def emit_dst_inst(node, fmt):
         # Split instructions are not emitted with the builder, but by calling
         # special functions in the `legalizer::split` module. These functions
         # will eliminate concat-split patterns.
+        # The split values may already exist, so source results can't be
+        # reused. Turn them into aliases of the split values instead.
+        names = ['{}_split'.format(d) if d.is_output() else str(d)
+                 for d in node.defs]
         fmt.line('let curpos = pos.position();')
         fmt.line('let srcloc = pos.srcloc();')
         fmt.format(
                 'let {} = split::{}(pos.func, cfg, curpos, srcloc, {});',
-                wrap_tup(node.defs),
+                wrap_tup(names),
                 node.expr.inst.snake_name(),
                 node.expr.args[0])
+        for d in node.defs:
+            if d.is_output():
+                fmt.format(
+                        'pos.func.dfg.change_to_alias({}, {}_split);', d, d)
     else:
         if len(node.defs) == 0:
             # This node doesn't define any values, so just insert the new
@@ -363,7 +372,9 @@ def gen_xform_group(xgrp, fmt, type_sets):
         fmt.line('cfg: &mut ::flowgraph::ControlFlowGraph,')
         fmt.line('isa: &::isa::TargetIsa,')
     with fmt.indented(') -> bool {', '}'):
-        fmt.line('use ir::InstBuilder;')
+        # Groups with only custom legalizations don't build instructions.
+        if xgrp.xforms:
+            fmt.line('use ir::InstBuilder;')
         fmt.line('use cursor::{Cursor, FuncCursor};')
         fmt.line('let mut pos = FuncCursor::new(func).at_inst(inst);')
         fmt.line('pos.use_srcloc(inst);')
@@ -101,6 +101,14 @@ def gen_getters(sgrp, fmt):
                 fmt.line(
                         'self.bytes[{} + p / 8] & (1 << (p % 8)) != 0'
                         .format(sgrp.boolean_offset))
+        fmt.doc_comment(
+                'Get the name, kind, default and current value of all the '
+                'settings.')
+        with fmt.indented(
+                'pub fn settings(&self) -> '
+                '::std::vec::Vec<::settings::Setting> {',
+                '}'):
+            fmt.line('TEMPLATE.settings(&self.bytes)')
         for setting in sgrp.settings:
             gen_getter(setting, sgrp, fmt)
         for name, pred in sgrp.named_predicates.items():
@@ -283,7 +291,7 @@ def gen_group(sgrp, fmt):
     """
     Generate a Flags struct representing `sgrp`.
     """
-    fmt.line('#[derive(Clone)]')
+    fmt.line('#[derive(Clone, PartialEq, Eq, Hash)]')
     fmt.doc_comment('Flags group `{}`.'.format(sgrp.name))
     with fmt.indented('pub struct Flags {', '}'):
         fmt.line('bytes: [u8; {}],'.format(sgrp.byte_size()))
@@ -6,10 +6,15 @@ Commonly used definitions.
 from __future__ import absolute_import
 from cdsl.isa import TargetISA, CPUMode
 import base.instructions
-from base.legalize import narrow
+from base.legalize import narrow, expand
 
 ISA = TargetISA('arm64', [base.instructions.GROUP])
 A64 = CPUMode('A64', ISA)
 
-# TODO: Refine these
-A64.legalize_type(narrow)
+A64.legalize_monomorphic(expand)
+A64.legalize_type(
+        default=narrow,
+        i32=expand,
+        i64=expand,
+        f32=expand,
+        f64=expand)
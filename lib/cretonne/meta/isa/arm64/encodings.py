@@ -0,0 +1,291 @@
+"""
+ARM64 Encodings.
+"""
+from __future__ import absolute_import
+from base import instructions as base
+from base.immediates import floatcc
+from base.types import i32, i64, f32, f64
+from cdsl.ast import Var
+from cdsl.predicates import IsUnsignedInt
+from base.formats import Load, Store
+from .defs import A64
+from .recipes import DPREG, ADDSUBIMM, MOVEWIDE, BITFIELD, LDST
+from .recipes import FP1, FPINT, BRIMM, BRREG
+from .recipes import R, Rrotl, Rudiv, Rsdiv, Rrem, R1, R1ctz, Rz, Rzrmov
+from .recipes import Ricmp, Ri, Riicmp, Adjsp, Adjsp24, Saddr
+from .recipes import Mz, Mn, Mz2, Mz4, BFshl, BFshr, BFext, null
+from .recipes import F2, F1, F1rmov, Ffcmp, Ffcmp2, FI, IF
+from .recipes import Ld, Ldur, fLd, fLdur, St, Stur, fSt, fStur
+from .recipes import GPsp, GPfi, GPrsp, GPrfi, FPsp, FPfi, FPrsp, FPrfi
+from .recipes import B, Bcall, CB, Bicmp, Bret, Bcallind, Udf
+
+# Dummies for instruction predicates.
+x = Var('x')
+y = Var('y')
+dest = Var('dest')
+args = Var('args')
+
+# The `sf` bit selects 64-bit operation in most integer instructions.
+SF = 1 << 31
+
+# Basic arithmetic and bitwise operations on shifted registers, with no shift.
+for inst,               template in [
+        (base.iadd,     0x0b000000),
+        (base.isub,     0x4b000000),
+        (base.band,     0x0a000000),
+        (base.bor,      0x2a000000),
+        (base.bxor,     0x4a000000),
+        (base.band_not, 0x0a200000),
+        (base.bor_not,  0x2a200000),
+        (base.bxor_not, 0x4a200000),
+        # `madd` with the zero register as addend.
+        (base.imul,     0x1b007c00)
+        ]:
+    A64.enc(inst.i32, R, DPREG(template))
+    A64.enc(inst.i64, R, DPREG(SF | template))
+
+A64.enc(base.umulhi.i64, R, DPREG(0x9bc07c00))
+A64.enc(base.smulhi.i64, R, DPREG(0x9b407c00))
+
+# Division and remainder need explicit checks to trap like the base
+# instructions.
+A64.enc(base.udiv.i32, Rudiv, DPREG(0x1ac00800))
+A64.enc(base.udiv.i64, Rudiv, DPREG(SF | 0x1ac00800))
+A64.enc(base.sdiv.i32, Rsdiv, DPREG(0x1ac00c00))
+A64.enc(base.sdiv.i64, Rsdiv, DPREG(SF | 0x1ac00c00))
+A64.enc(base.urem.i32, Rrem, DPREG(0x1ac00800))
+A64.enc(base.urem.i64, Rrem, DPREG(SF | 0x1ac00800))
+A64.enc(base.srem.i32, Rrem, DPREG(0x1ac00c00))
+A64.enc(base.srem.i64, Rrem, DPREG(SF | 0x1ac00c00))
+
+# Immediate add. Other immediate operations are expanded to use a register.
+A64.enc(base.iadd_imm.i32, Ri, ADDSUBIMM(0x11000000))
+A64.enc(base.iadd_imm.i64, Ri, ADDSUBIMM(SF | 0x11000000))
+
+# Dynamic shifts mask the shift amount like the cton base instructions.
+for inst,           template in [
+        (base.ishl, 0x1ac02000),
+        (base.ushr, 0x1ac02400),
+        (base.sshr, 0x1ac02800),
+        (base.rotr, 0x1ac02c00)
+        ]:
+    A64.enc(inst.i32.i32, R, DPREG(template))
+    A64.enc(inst.i64.i64, R, DPREG(SF | template))
+    # The shift amount is only read from the low bits of the register.
+    A64.enc(inst.i32.i64, R, DPREG(template))
+    A64.enc(inst.i64.i32, R, DPREG(SF | template))
+
+A64.enc(base.rotl.i32.i32, Rrotl, DPREG(0x1ac02c00))
+A64.enc(base.rotl.i64.i64, Rrotl, DPREG(SF | 0x1ac02c00))
+
+# Immediate shifts are aliases of the bitfield move instructions.
+A64.enc(base.ishl_imm.i32, BFshl, BITFIELD(0x53000000))
+A64.enc(base.ishl_imm.i64, BFshl, BITFIELD(0xd3400000))
+A64.enc(base.ushr_imm.i32, BFshr, BITFIELD(0x53000000))
+A64.enc(base.ushr_imm.i64, BFshr, BITFIELD(0xd3400000))
+A64.enc(base.sshr_imm.i32, BFshr, BITFIELD(0x13000000))
+A64.enc(base.sshr_imm.i64, BFshr, BITFIELD(0x93400000))
+
+# Bit counting.
+A64.enc(base.clz.i32, R1, DPREG(0x5ac01000))
+A64.enc(base.clz.i64, R1, DPREG(SF | 0x5ac01000))
+A64.enc(base.cls.i32, R1, DPREG(0x5ac01400))
+A64.enc(base.cls.i64, R1, DPREG(SF | 0x5ac01400))
+A64.enc(base.ctz.i32, R1ctz, DPREG(0x5ac01000))
+A64.enc(base.ctz.i64, R1ctz, DPREG(SF | 0x5ac01000))
+
+# Bitwise not is `orn` from the zero register.
+A64.enc(base.bnot.i32, Rz, DPREG(0x2a200000))
+A64.enc(base.bnot.i64, Rz, DPREG(SF | 0x2a200000))
+
+# Integer comparisons use `subs` to set the flags.
+A64.enc(base.icmp.i32, Ricmp, DPREG(0x6b000000))
+A64.enc(base.icmp.i64, Ricmp, DPREG(SF | 0x6b000000))
+A64.enc(base.icmp_imm.i32, Riicmp, ADDSUBIMM(0x71000000))
+A64.enc(base.icmp_imm.i64, Riicmp, ADDSUBIMM(SF | 0x71000000))
+
+# Integer constants. Small constants use a single `movz` or `movn`.
+for ty,  sf in [(i32, 0), (i64, SF)]:
+    A64.enc(base.iconst.bind(ty), Mz, MOVEWIDE(sf | 0x52800000))
+    A64.enc(base.iconst.bind(ty), Mn, MOVEWIDE(sf | 0x12800000))
+A64.enc(base.iconst.i32, Mz2, MOVEWIDE(0x52800000))
+A64.enc(base.iconst.i64, Mz4, MOVEWIDE(SF | 0x52800000))
+
+# Register copies are `orr` from the zero register.
+A64.enc(base.copy.i32, Rz, DPREG(0x2a000000))
+A64.enc(base.copy.i64, Rz, DPREG(SF | 0x2a000000))
+A64.enc(base.copy.b1, Rz, DPREG(0x2a000000))
+A64.enc(base.regmove.i32, Rzrmov, DPREG(0x2a000000))
+A64.enc(base.regmove.i64, Rzrmov, DPREG(SF | 0x2a000000))
+A64.enc(base.regmove.b1, Rzrmov, DPREG(0x2a000000))
+
+# Booleans are kept as 0 / 1 with the high bits clear.
+A64.enc(base.bint.i32.b1, Rz, DPREG(0x2a000000))
+A64.enc(base.bint.i64.b1, Rz, DPREG(0x2a000000))
+
+# Conversions. Writing a 32-bit register clears the high bits.
+A64.enc(base.uextend.i64.i32, Rz, DPREG(0x2a000000))
+A64.enc(base.ireduce.i32.i64, null, 0)
+A64.enc(base.sextend.i64.i32, BFext, BITFIELD(0x93407c00))
+
+# Stack pointer adjustments.
+A64.enc(base.adjust_sp_imm, Adjsp, ADDSUBIMM(SF | 0x11000000))
+A64.enc(base.adjust_sp_imm, Adjsp24, ADDSUBIMM(SF | 0x11000000))
+A64.enc(base.stack_addr.i64, Saddr, ADDSUBIMM(SF | 0x11000000))
+
+# Loads and stores. The unsigned offset is scaled by the access size.
+for inst,                   template,   ldst_size in [
+        (base.load.i32.i64,      0xb9400000, 2),
+        (base.load.i64.i64,      0xf9400000, 3),
+        (base.uload8.i32.i64,    0x39400000, 0),
+        (base.uload8.i64.i64,    0x39400000, 0),
+        (base.sload8.i32.i64,    0x39c00000, 0),
+        (base.sload8.i64.i64,    0x39800000, 0),
+        (base.uload16.i32.i64,   0x79400000, 1),
+        (base.uload16.i64.i64,   0x79400000, 1),
+        (base.sload16.i32.i64,   0x79c00000, 1),
+        (base.sload16.i64.i64,   0x79800000, 1),
+        (base.uload32.i64,       0xb9400000, 2),
+        (base.sload32.i64,       0xb9800000, 2)
+        ]:
+    A64.enc(
+            inst, Ld, LDST(template),
+            instp=IsUnsignedInt(Load.offset, 12 + ldst_size, ldst_size))
+    A64.enc(inst, Ldur, LDST(template))
+
+for inst,                   template,   ldst_size in [
+        (base.store.i32.i64,     0xb9000000, 2),
+        (base.store.i64.i64,     0xf9000000, 3),
+        (base.istore8.i32.i64,   0x39000000, 0),
+        (base.istore8.i64.i64,   0x39000000, 0),
+        (base.istore16.i32.i64,  0x79000000, 1),
+        (base.istore16.i64.i64,  0x79000000, 1),
+        (base.istore32.i64.i64,  0xb9000000, 2)
+        ]:
+    A64.enc(
+            inst, St, LDST(template),
+            instp=IsUnsignedInt(Store.offset, 12 + ldst_size, ldst_size))
+    A64.enc(inst, Stur, LDST(template))
+
+for ty,   ld,         st,         ldst_size in [
+        (f32,  0xbd400000, 0xbd000000, 2),
+        (f64,  0xfd400000, 0xfd000000, 3)
+        ]:
+    A64.enc(
+            base.load.bind(ty, i64), fLd, LDST(ld),
+            instp=IsUnsignedInt(Load.offset, 12 + ldst_size, ldst_size))
+    A64.enc(base.load.bind(ty, i64), fLdur, LDST(ld))
+    A64.enc(
+            base.store.bind(ty, i64), fSt, LDST(st),
+            instp=IsUnsignedInt(Store.offset, 12 + ldst_size, ldst_size))
+    A64.enc(base.store.bind(ty, i64), fStur, LDST(st))
+
+# Spill and fill.
+A64.enc(base.spill.i32, GPsp, LDST(0xb9000000))
+A64.enc(base.spill.i64, GPsp, LDST(0xf9000000))
+A64.enc(base.spill.b1, GPsp, LDST(0x39000000))
+A64.enc(base.fill.i32, GPfi, LDST(0xb9400000))
+A64.enc(base.fill.i64, GPfi, LDST(0xf9400000))
+A64.enc(base.fill.b1, GPfi, LDST(0x39400000))
+A64.enc(base.regspill.i32, GPrsp, LDST(0xb9000000))
+A64.enc(base.regspill.i64, GPrsp, LDST(0xf9000000))
+A64.enc(base.regspill.b1, GPrsp, LDST(0x39000000))
+A64.enc(base.regfill.i32, GPrfi, LDST(0xb9400000))
+A64.enc(base.regfill.i64, GPrfi, LDST(0xf9400000))
+A64.enc(base.regfill.b1, GPrfi, LDST(0x39400000))
+
+A64.enc(base.spill.f32, FPsp, LDST(0xbd000000))
+A64.enc(base.spill.f64, FPsp, LDST(0xfd000000))
+A64.enc(base.fill.f32, FPfi, LDST(0xbd400000))
+A64.enc(base.fill.f64, FPfi, LDST(0xfd400000))
+A64.enc(base.regspill.f32, FPrsp, LDST(0xbd000000))
+A64.enc(base.regspill.f64, FPrsp, LDST(0xfd000000))
+A64.enc(base.regfill.f32, FPrfi, LDST(0xbd400000))
+A64.enc(base.regfill.f64, FPrfi, LDST(0xfd400000))
+
+# Floating point arithmetic. The `ftype` field at bit 22 selects double
+# precision.
+FTYPE = 1 << 22
+
+for inst,           template in [
+        (base.fadd, 0x1e202800),
+        (base.fsub, 0x1e203800),
+        (base.fmul, 0x1e200800),
+        (base.fdiv, 0x1e201800),
+        (base.fmin, 0x1e205800),
+        (base.fmax, 0x1e204800)
+        ]:
+    A64.enc(inst.f32, F2, DPREG(template))
+    A64.enc(inst.f64, F2, DPREG(FTYPE | template))
+
+for inst,              template in [
+        (base.copy,    0x1e204000),
+        (base.fabs,    0x1e20c000),
+        (base.fneg,    0x1e214000),
+        (base.sqrt,    0x1e21c000),
+        (base.nearest, 0x1e244000),
+        (base.ceil,    0x1e24c000),
+        (base.floor,   0x1e254000),
+        (base.trunc,   0x1e25c000)
+        ]:
+    A64.enc(inst.f32, F1, FP1(template))
+    A64.enc(inst.f64, F1, FP1(FTYPE | template))
+
+A64.enc(base.regmove.f32, F1rmov, FP1(0x1e204000))
+A64.enc(base.regmove.f64, F1rmov, FP1(FTYPE | 0x1e204000))
+
+A64.enc(base.fpromote.f64.f32, F1, FP1(0x1e22c000))
+A64.enc(base.fdemote.f32.f64, F1, FP1(0x1e624000))
+
+# Floating point comparisons. Most conditions map to a single A64 condition
+# after `fcmp`.
+for cond in [
+        floatcc.ord, floatcc.uno, floatcc.eq, floatcc.ne,
+        floatcc.lt, floatcc.le, floatcc.gt, floatcc.ge,
+        floatcc.ult, floatcc.ule, floatcc.ugt, floatcc.uge]:
+    A64.enc(base.fcmp.f32(cond, x, y), Ffcmp, DPREG(0x1e202000))
+    A64.enc(base.fcmp.f64(cond, x, y), Ffcmp, DPREG(FTYPE | 0x1e202000))
+
+for cond in [floatcc.one, floatcc.ueq]:
+    A64.enc(base.fcmp.f32(cond, x, y), Ffcmp2, DPREG(0x1e202000))
+    A64.enc(base.fcmp.f64(cond, x, y), Ffcmp2, DPREG(FTYPE | 0x1e202000))
+
+# Conversions between integer and floating point registers.
+A64.enc(base.fcvt_from_sint.f32.i32, FI, FPINT(0x1e220000))
+A64.enc(base.fcvt_from_sint.f32.i64, FI, FPINT(SF | 0x1e220000))
+A64.enc(base.fcvt_from_sint.f64.i32, FI, FPINT(FTYPE | 0x1e220000))
+A64.enc(base.fcvt_from_sint.f64.i64, FI, FPINT(SF | FTYPE | 0x1e220000))
+A64.enc(base.fcvt_from_uint.f32.i32, FI, FPINT(0x1e230000))
+A64.enc(base.fcvt_from_uint.f32.i64, FI, FPINT(SF | 0x1e230000))
+A64.enc(base.fcvt_from_uint.f64.i32, FI, FPINT(FTYPE | 0x1e230000))
+A64.enc(base.fcvt_from_uint.f64.i64, FI, FPINT(SF | FTYPE | 0x1e230000))
+
+A64.enc(base.bitcast.f32.i32, FI, FPINT(0x1e270000))
+A64.enc(base.bitcast.f64.i64, FI, FPINT(SF | FTYPE | 0x1e270000))
+A64.enc(base.bitcast.i32.f32, IF, FPINT(0x1e260000))
+A64.enc(base.bitcast.i64.f64, IF, FPINT(SF | FTYPE | 0x1e260000))
+
+# Control flow.
+
+# Unconditional branches.
+A64.enc(base.jump, B, BRIMM(0x14000000))
+A64.enc(base.call, Bcall, BRIMM(0x94000000))
+
+# Conditional branches.
+for inst,           template in [
+        (base.brz,  0x34000000),
+        (base.brnz, 0x35000000)
+        ]:
+    A64.enc(inst.i32, CB, BRIMM(template))
+    A64.enc(inst.i64, CB, BRIMM(SF | template))
+    A64.enc(inst.b1, CB, BRIMM(template))
+
+A64.enc(base.br_icmp.i32, Bicmp, DPREG(0x6b000000))
+A64.enc(base.br_icmp.i64, Bicmp, DPREG(SF | 0x6b000000))
+
+# Returns jump to the address in %x30 which is provided by a special-purpose
+# `link` return value that is added by legalize_signature().
+A64.enc(base.x_return, Bret, BRREG(0xd65f0000))
+A64.enc(base.call_indirect.i64, Bcallind, BRREG(0xd63f0000))
+
+A64.enc(base.trap, Udf, 0)
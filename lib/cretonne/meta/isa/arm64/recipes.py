@@ -0,0 +1,492 @@
+"""
+ARM64 Encoding recipes.
+
+All A64 instructions are 32 bits wide. The recipes here correspond roughly to
+the instruction classes in the reference:
+
+    ARM Architecture Reference Manual
+    ARMv8, for ARMv8-A architecture profile
+    Chapter C4: A64 Instruction Set Encoding
+
+The encoding bits hold the parts of the instruction word that aren't operand
+fields. The functions below take an instruction template, which is the full
+instruction word with all operand fields zero, and compress it into encbits.
+The matching `put_*` functions in `binemit.rs` expand them again.
+"""
+from __future__ import absolute_import
+from cdsl.isa import EncRecipe
+from cdsl.predicates import IsSignedInt, IsUnsignedInt
+from cdsl.registers import Stack
+from base.formats import Unary, UnaryImm, Binary, BinaryImm, MultiAry
+from base.formats import IntCompare, IntCompareImm, FloatCompare
+from base.formats import Jump, Branch, BranchIcmp, Call, IndirectCall, Trap
+from base.formats import Load, Store, StackLoad, RegMove, RegSpill, RegFill
+from .registers import GPR, FPR
+
+# Register operand fields: Rm[20:16], Rn[9:5], Rd[4:0].
+RM = 0x1f << 16
+RN = 0x1f << 5
+RD = 0x1f
+
+
+def DPREG(template):
+    # type: (int) -> int
+    """
+    Data processing instructions with register operands, including the FP
+    data processing 2-source instructions.
+
+    Encbits: `template[31:21] | (template[14:10] << 11)`.
+    """
+    assert template & (RM | RN | RD | (1 << 15)) == 0
+    return (template >> 21) | (((template >> 10) & 0x1f) << 11)
+
+
+def ADDSUBIMM(template):
+    # type: (int) -> int
+    """
+    Add/subtract with a 12-bit immediate.
+
+    Encbits: `template[31:22]`.
+    """
+    assert template & 0x3fffff == 0
+    assert (template >> 24) & 0x1f == 0b10001
+    return template >> 22
+
+
+def MOVEWIDE(template):
+    # type: (int) -> int
+    """
+    Move wide with a 16-bit immediate.
+
+    Encbits: `template[31:23]`.
+    """
+    assert template & 0x7fffff == 0
+    assert (template >> 23) & 0x3f == 0b100101
+    return template >> 23
+
+
+def BITFIELD(template):
+    # type: (int) -> int
+    """
+    Bitfield moves. The `imms` field is part of the template, `immr` is not.
+
+    Encbits: `template[31:22] | (template[15:10] << 10)`.
+    """
+    assert template & ((0x3f << 16) | RN | RD) == 0
+    assert (template >> 23) & 0x3f == 0b100110
+    return (template >> 22) | (((template >> 10) & 0x3f) << 10)
+
+
+def LDST(template):
+    # type: (int) -> int
+    """
+    Loads and stores in the unsigned scaled 12-bit offset form. The same
+    encbits are used for the unscaled 9-bit offset form.
+
+    Encbits: `template[31:22]`.
+    """
+    assert template & 0x3fffff == 0
+    assert (template >> 24) & 0x3b == 0b111001
+    return template >> 22
+
+
+def FP1(template):
+    # type: (int) -> int
+    """
+    Floating point data processing with one source register.
+
+    Encbits: `template[23:15]`.
+    """
+    assert template & ~(0x1ff << 15) == 0x1e004000
+    return (template >> 15) & 0x1ff
+
+
+def FPINT(template):
+    # type: (int) -> int
+    """
+    Conversions between floating point and integer registers.
+
+    Encbits: `template[23:16] | (template[31] << 8)`.
+    """
+    assert template & 0x7f00ffff == 0x1e000000
+    return ((template >> 16) & 0xff) | ((template >> 31) << 8)
+
+
+def BRIMM(template):
+    # type: (int) -> int
+    """
+    Branches with an immediate displacement.
+
+    Encbits: `template[31:24]`.
+    """
+    assert template & 0xffffff == 0
+    return template >> 24
+
+
+def BRREG(template):
+    # type: (int) -> int
+    """
+    Unconditional branches to a register.
+
+    Encbits: `template[24:21]`.
+    """
+    assert template & ~(0xf << 21) == 0xd61f0000
+    return (template >> 21) & 0xf
+
+
+# Three-register data processing instruction.
+R = EncRecipe(
+        'R', Binary, size=4, ins=(GPR, GPR), outs=GPR,
+        emit='put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Rotate left is a rotate right by the negated amount in the %x16 scratch
+# register.
+Rrotl = EncRecipe(
+        'Rrotl', Binary, size=8, ins=(GPR, GPR), outs=GPR,
+        emit='put_rotl(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Unsigned division that traps on a zero divisor.
+Rudiv = EncRecipe(
+        'Rudiv', Binary, size=12, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_udiv(
+            bits, in_reg0, in_reg1, out_reg0, func.srclocs[inst], sink);
+        ''')
+
+# Signed division that traps on a zero divisor and on overflow.
+Rsdiv = EncRecipe(
+        'Rsdiv', Binary, size=28, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_sdiv(
+            bits, in_reg0, in_reg1, out_reg0, func.srclocs[inst], sink);
+        ''')
+
+# Remainder computed from the quotient in the %x16 scratch register.
+Rrem = EncRecipe(
+        'Rrem', Binary, size=16, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_rem(
+            bits, in_reg0, in_reg1, out_reg0, func.srclocs[inst], sink);
+        ''')
+
+# Data processing instruction with a single source register.
+R1 = EncRecipe(
+        'R1', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_rrr(bits, in_reg0, 0, out_reg0, sink);')
+
+# Count trailing zeros as a bit reversal followed by `clz`.
+R1ctz = EncRecipe(
+        'R1ctz', Unary, size=8, ins=GPR, outs=GPR,
+        emit='put_ctz(bits, in_reg0, out_reg0, sink);')
+
+# Register operation with the zero register as the first operand. This is used
+# for copies (`orr`) and bitwise not (`orn`).
+Rz = EncRecipe(
+        'Rz', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_rrr(bits, 31, in_reg0, out_reg0, sink);')
+
+# Same for a GPR regmove.
+Rzrmov = EncRecipe(
+        'Rzrmov', RegMove, size=4, ins=GPR, outs=(),
+        emit='put_rrr(bits, 31, src, dst, sink);')
+
+# Integer comparison setting a boolean register: `cmp` + `cset`.
+Ricmp = EncRecipe(
+        'Ricmp', IntCompare, size=8, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_rrr(bits, in_reg0, in_reg1, 31, sink);
+        put_cset(icc2cond(cond), out_reg0, sink);
+        ''')
+
+# Add/subtract immediate. Negative immediates flip the operation.
+Ri = EncRecipe(
+        'Ri', BinaryImm, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(BinaryImm.imm, 12),
+        emit='put_ri(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Integer comparison with an immediate: `cmp` + `cset`.
+Riicmp = EncRecipe(
+        'Riicmp', IntCompareImm, size=8, ins=GPR, outs=GPR,
+        instp=IsSignedInt(IntCompareImm.imm, 12),
+        emit='''
+        put_ri(bits, in_reg0, imm.into(), 31, sink);
+        put_cset(icc2cond(cond), out_reg0, sink);
+        ''')
+
+# Stack pointer adjustment with a 12-bit immediate.
+Adjsp = EncRecipe(
+        'Adjsp', UnaryImm, size=4, ins=(), outs=(),
+        instp=IsSignedInt(UnaryImm.imm, 12),
+        emit='put_ri(bits, 31, imm.into(), 31, sink);')
+
+# Stack pointer adjustment with a 24-bit immediate, split in two.
+Adjsp24 = EncRecipe(
+        'Adjsp24', UnaryImm, size=8, ins=(), outs=(),
+        instp=IsSignedInt(UnaryImm.imm, 24),
+        emit='put_ri24(bits, 31, imm.into(), 31, sink);')
+
+# Address of a stack slot relative to the stack pointer.
+Saddr = EncRecipe(
+        'Saddr', StackLoad, size=8, ins=(), outs=GPR,
+        emit='''
+        let sp = StackRef::sp(stack_slot, &func.stack_slots);
+        let base = stk_base(sp.base);
+        let offset: i64 = offset.into();
+        put_ri24(bits, base, i64::from(sp.offset) + offset, out_reg0, sink);
+        ''')
+
+# Move wide immediate: `movz`.
+Mz = EncRecipe(
+        'Mz', UnaryImm, size=4, ins=(), outs=GPR,
+        instp=IsUnsignedInt(UnaryImm.imm, 16),
+        emit='put_movw(bits, imm.into(), 0, out_reg0, sink);')
+
+# Move wide inverted immediate: `movn`.
+Mn = EncRecipe(
+        'Mn', UnaryImm, size=4, ins=(), outs=GPR,
+        instp=IsSignedInt(UnaryImm.imm, 17),
+        emit='''
+        let imm: i64 = imm.into();
+        put_movw(bits, !imm, 0, out_reg0, sink);
+        ''')
+
+# Arbitrary 32-bit constant: `movz` + `movk`.
+Mz2 = EncRecipe(
+        'Mz2', UnaryImm, size=8, ins=(), outs=GPR,
+        emit='put_movimm(bits, imm.into(), 2, out_reg0, sink);')
+
+# Arbitrary 64-bit constant: `movz` + 3 x `movk`.
+Mz4 = EncRecipe(
+        'Mz4', UnaryImm, size=16, ins=(), outs=GPR,
+        emit='put_movimm(bits, imm.into(), 4, out_reg0, sink);')
+
+# Left shift by an immediate: `ubfm`.
+BFshl = EncRecipe(
+        'BFshl', BinaryImm, size=4, ins=GPR, outs=GPR,
+        emit='put_shl_imm(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Right shift by an immediate: `ubfm` or `sbfm`.
+BFshr = EncRecipe(
+        'BFshr', BinaryImm, size=4, ins=GPR, outs=GPR,
+        emit='put_shr_imm(bits, in_reg0, imm.into(), out_reg0, sink);')
+
+# Integer extension: `ubfm` or `sbfm` with the `imms` field from the encbits.
+BFext = EncRecipe(
+        'BFext', Unary, size=4, ins=GPR, outs=GPR,
+        emit='put_bfm(bits, in_reg0, 0, u32::from(bits >> 10), out_reg0, sink);')
+
+# A null unary instruction that takes a GPR register. Can be used for identity
+# copies and no-op conversions.
+null = EncRecipe('null', Unary, size=0, ins=GPR, outs=0, emit='')
+
+# Floating point data processing with two source registers.
+F2 = EncRecipe(
+        'F2', Binary, size=4, ins=(FPR, FPR), outs=FPR,
+        emit='put_rrr(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Floating point data processing with one source register.
+F1 = EncRecipe(
+        'F1', Unary, size=4, ins=FPR, outs=FPR,
+        emit='put_fp1(bits, in_reg0, out_reg0, sink);')
+
+# FPR regmove: `fmov`.
+F1rmov = EncRecipe(
+        'F1rmov', RegMove, size=4, ins=FPR, outs=(),
+        emit='put_fp1(bits, src, dst, sink);')
+
+# Floating point comparison setting a boolean register: `fcmp` + `cset`.
+Ffcmp = EncRecipe(
+        'Ffcmp', FloatCompare, size=8, ins=(FPR, FPR), outs=GPR,
+        emit='''
+        put_rrr(bits, in_reg0, in_reg1, 0, sink);
+        put_cset(fcc2cond(cond), out_reg0, sink);
+        ''')
+
+# Floating point comparisons that need two A64 conditions: `one` and `ueq`.
+Ffcmp2 = EncRecipe(
+        'Ffcmp2', FloatCompare, size=12, ins=(FPR, FPR), outs=GPR,
+        emit='''
+        put_rrr(bits, in_reg0, in_reg1, 0, sink);
+        put_fcmp2(cond, out_reg0, sink);
+        ''')
+
+# Move from a GPR to an FPR, including int-to-float conversions.
+FI = EncRecipe(
+        'FI', Unary, size=4, ins=GPR, outs=FPR,
+        emit='put_fpint(bits, in_reg0, out_reg0, sink);')
+
+# Move from an FPR to a GPR.
+IF = EncRecipe(
+        'IF', Unary, size=4, ins=FPR, outs=GPR,
+        emit='put_fpint(bits, in_reg0, out_reg0, sink);')
+
+# Load with an unsigned scaled 12-bit offset. The encoding supplies an
+# instruction predicate for the access size.
+Ld = EncRecipe(
+        'Ld', Load, size=4, ins=GPR, outs=GPR,
+        emit='put_ldst(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# Load with a signed unscaled 9-bit offset.
+Ldur = EncRecipe(
+        'Ldur', Load, size=4, ins=GPR, outs=GPR,
+        instp=IsSignedInt(Load.offset, 9),
+        emit='put_ldur(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# Float load with an unsigned scaled 12-bit offset.
+fLd = EncRecipe(
+        'fLd', Load, size=4, ins=GPR, outs=FPR,
+        emit='put_ldst(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# Float load with a signed unscaled 9-bit offset.
+fLdur = EncRecipe(
+        'fLdur', Load, size=4, ins=GPR, outs=FPR,
+        instp=IsSignedInt(Load.offset, 9),
+        emit='put_ldur(bits, in_reg0, offset.into(), out_reg0, sink);')
+
+# Store with an unsigned scaled 12-bit offset.
+St = EncRecipe(
+        'St', Store, size=4, ins=(GPR, GPR), outs=(),
+        emit='put_ldst(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# Store with a signed unscaled 9-bit offset.
+Stur = EncRecipe(
+        'Stur', Store, size=4, ins=(GPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 9),
+        emit='put_ldur(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# Float store with an unsigned scaled 12-bit offset.
+fSt = EncRecipe(
+        'fSt', Store, size=4, ins=(FPR, GPR), outs=(),
+        emit='put_ldst(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# Float store with a signed unscaled 9-bit offset.
+fStur = EncRecipe(
+        'fStur', Store, size=4, ins=(FPR, GPR), outs=(),
+        instp=IsSignedInt(Store.offset, 9),
+        emit='put_ldur(bits, in_reg1, offset.into(), in_reg0, sink);')
+
+# Spill of a GPR. Stack references use the scaled offset form from SP.
+GPsp = EncRecipe(
+        'GPsp', Unary, size=4, ins=GPR, outs=Stack(GPR),
+        emit='''
+        let base = stk_base(out_stk0.base);
+        put_ldst(bits, base, out_stk0.offset.into(), in_reg0, sink);
+        ''')
+
+# Fill of a GPR.
+GPfi = EncRecipe(
+        'GPfi', Unary, size=4, ins=Stack(GPR), outs=GPR,
+        emit='''
+        let base = stk_base(in_stk0.base);
+        put_ldst(bits, base, in_stk0.offset.into(), out_reg0, sink);
+        ''')
+
+# Regspill of a GPR.
+GPrsp = EncRecipe(
+        'GPrsp', RegSpill, size=4, ins=GPR, outs=(),
+        emit='''
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        let base = stk_base(dst.base);
+        put_ldst(bits, base, dst.offset.into(), src, sink);
+        ''')
+
+# Regfill of a GPR.
+GPrfi = EncRecipe(
+        'GPrfi', RegFill, size=4, ins=Stack(GPR), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        let base = stk_base(src.base);
+        put_ldst(bits, base, src.offset.into(), dst, sink);
+        ''')
+
+# Spill of an FPR.
+FPsp = EncRecipe(
+        'FPsp', Unary, size=4, ins=FPR, outs=Stack(FPR),
+        emit='''
+        let base = stk_base(out_stk0.base);
+        put_ldst(bits, base, out_stk0.offset.into(), in_reg0, sink);
+        ''')
+
+# Fill of an FPR.
+FPfi = EncRecipe(
+        'FPfi', Unary, size=4, ins=Stack(FPR), outs=FPR,
+        emit='''
+        let base = stk_base(in_stk0.base);
+        put_ldst(bits, base, in_stk0.offset.into(), out_reg0, sink);
+        ''')
+
+# Regspill of an FPR.
+FPrsp = EncRecipe(
+        'FPrsp', RegSpill, size=4, ins=FPR, outs=(),
+        emit='''
+        let dst = StackRef::sp(dst, &func.stack_slots);
+        let base = stk_base(dst.base);
+        put_ldst(bits, base, dst.offset.into(), src, sink);
+        ''')
+
+# Regfill of an FPR.
+FPrfi = EncRecipe(
+        'FPrfi', RegFill, size=4, ins=Stack(FPR), outs=(),
+        emit='''
+        let src = StackRef::sp(src, &func.stack_slots);
+        let base = stk_base(src.base);
+        put_ldst(bits, base, src.offset.into(), dst, sink);
+        ''')
+
+# Unconditional branch with a 26-bit displacement.
+B = EncRecipe(
+        'B', Jump, size=4, ins=(), outs=(), branch_range=(0, 28),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_b(bits, disp, sink);
+        ''')
+
+# Direct call: `bl`.
+Bcall = EncRecipe(
+        'Bcall', Call, size=4, ins=(), outs=(),
+        emit='''
+        sink.reloc_external(Reloc::Arm64Call,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            0);
+        put_b(bits, 0, sink);
+        ''')
+
+# Compare against zero and branch: `cbz` / `cbnz`.
+CB = EncRecipe(
+        'CB', Branch, size=4, ins=GPR, outs=(), branch_range=(0, 21),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_cb(bits, disp, in_reg0, sink);
+        ''')
+
+# Integer compare and conditional branch: `cmp` + `b.cond`.
+Bicmp = EncRecipe(
+        'Bicmp', BranchIcmp, size=8, ins=(GPR, GPR), outs=(),
+        branch_range=(4, 21),
+        emit='''
+        put_rrr(bits, in_reg0, in_reg1, 31, sink);
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_bcond(icc2cond(cond), disp, sink);
+        ''')
+
+# Return through the link register in %x30.
+# The return address is provided as a special-purpose link argument.
+Bret = EncRecipe(
+        'Bret', MultiAry, size=4, ins=(), outs=(),
+        emit='put_br(bits, 30, sink);')
+
+# Indirect call: `blr`.
+Bcallind = EncRecipe(
+        'Bcallind', IndirectCall, size=4, ins=GPR, outs=(),
+        emit='put_br(bits, in_reg0, sink);')
+
+# Permanently undefined instruction.
+Udf = EncRecipe(
+        'Udf', Trap, size=4, ins=(), outs=(),
+        emit='''
+        sink.trap(code, func.srclocs[inst]);
+        sink.put4(UDF);
+        ''')
@@ -3,38 +3,52 @@ Intel Encodings.
 """
 from __future__ import absolute_import
 from cdsl.predicates import IsUnsignedInt, Not, And
+from cdsl.predicates import IsColocatedFunc, IsColocatedData
+from cdsl.ast import Var
 from base import instructions as base
-from base.formats import UnaryImm
+from base.formats import UnaryImm, FuncAddr, UnaryGlobalVar, Call, TryCall
+from base.immediates import ordering, atomic_rmw_op
+from base.types import i32, i64
 from .defs import X86_64, X86_32
 from . import recipes as r
 from . import settings as cfg
 from . import instructions as x86
-from .legalize import intel_expand
-from base.legalize import narrow, expand_flags
+from .legalize import intel_expand, intel_narrow, intel_vector
+from base.legalize import narrow, widen, expand_flags
 from base.settings import allones_funcaddrs, is_pic
 from .settings import use_sse41
 
 try:
     from typing import TYPE_CHECKING, Any  # noqa
     if TYPE_CHECKING:
-        from cdsl.instructions import MaybeBoundInst  # noqa
+        from cdsl.instructions import Instruction, MaybeBoundInst  # noqa
+        from cdsl.isa import InstSpec  # noqa
+        from cdsl.types import ValueType  # noqa
 except ImportError:
     pass
 
 
 X86_32.legalize_monomorphic(expand_flags)
+# Integer vectors in XMM registers.
+i32x4 = i32.by(4)
+
 X86_32.legalize_type(
     default=narrow,
+    i16=widen,
     b1=expand_flags,
     i32=intel_expand,
+    i64=intel_narrow,
+    i32x4=intel_vector,
     f32=intel_expand,
     f64=intel_expand)
 
 X86_64.legalize_monomorphic(expand_flags)
 X86_64.legalize_type(
     default=narrow,
+    i16=widen,
     b1=expand_flags,
     i32=intel_expand,
+    i32x4=intel_vector,
     i64=intel_expand,
     f32=intel_expand,
     f64=intel_expand)
@@ -116,6 +130,8 @@ enc_both(base.band.b1, r.rr, 0x21)
 enc_both(base.bor.b1,  r.rr, 0x09)
 enc_both(base.bxor.b1, r.rr, 0x31)
 
+enc_i32_i64(base.bnot, r.ur, 0xf7, rrr=2)
+
 enc_i32_i64(base.imul, r.rrx, 0x0f, 0xaf)
 enc_i32_i64(x86.sdivmodx, r.div, 0xf7, rrr=7)
 enc_i32_i64(x86.udivmodx, r.div, 0xf7, rrr=6)
@@ -169,6 +185,10 @@ for inst,           rrr in [
     X86_64.enc(inst.i32.any, *r.rc.rex(0xd3, rrr=rrr))
     X86_64.enc(inst.i32.any, *r.rc(0xd3, rrr=rrr))
 
+# Double precision shifts, used to shift register pairs.
+enc_i32_i64(x86.shld, r.dshift, 0x0f, 0xa5)
+enc_i32_i64(x86.shrd, r.dshift, 0x0f, 0xad)
+
 # Population count.
 X86_32.enc(base.popcnt.i32, *r.urm(0xf3, 0x0f, 0xb8), isap=cfg.use_popcnt)
 X86_64.enc(base.popcnt.i64, *r.urm.rex(0xf3, 0x0f, 0xb8, w=1),
@@ -190,6 +210,12 @@ X86_64.enc(base.ctz.i64, *r.urm.rex(0xf3, 0x0f, 0xbc, w=1),
 X86_64.enc(base.ctz.i32, *r.urm.rex(0xf3, 0x0f, 0xbc), isap=cfg.use_bmi1)
 X86_64.enc(base.ctz.i32, *r.urm(0xf3, 0x0f, 0xbc), isap=cfg.use_bmi1)
 
+# Byte swap.
+X86_32.enc(base.bswap.i32, *r.pur(0x0f, 0xc8))
+X86_64.enc(base.bswap.i64, *r.pur.rex(0x0f, 0xc8, w=1))
+X86_64.enc(base.bswap.i32, *r.pur.rex(0x0f, 0xc8))
+X86_64.enc(base.bswap.i32, *r.pur(0x0f, 0xc8))
+
 #
 # Loads and stores.
 #
@@ -198,6 +224,13 @@ for recipe in [r.st, r.stDisp8, r.stDisp32]:
     enc_x86_64(base.istore32.i64.any, recipe, 0x89)
     enc_i32_i64_ld_st(base.istore16, False, recipe, 0x66, 0x89)
 
+# Complex stores have two free type variables: the address and the index.
+for recipe in [r.stWithIndex, r.stWithIndexDisp8, r.stWithIndexDisp32]:
+    X86_32.enc(base.store_complex.i32.any.any, *recipe(0x89))
+    X86_64.enc(base.store_complex.i32.any.any, *recipe.rex(0x89))
+    X86_64.enc(base.store_complex.i32.any.any, *recipe(0x89))
+    X86_64.enc(base.store_complex.i64.any.any, *recipe.rex(0x89, w=1))
+
 # Byte stores are more complicated because the registers they can address
 # depends of the presence of a REX prefix. The st*_abcd recipes fall back to
 # the corresponding st* recipes when a REX prefix is applied.
@@ -223,6 +256,12 @@ for recipe in [r.ld, r.ldDisp8, r.ldDisp32]:
     enc_i32_i64_ld_st(base.uload8, True, recipe, 0x0f, 0xb6)
     enc_i32_i64_ld_st(base.sload8, True, recipe, 0x0f, 0xbe)
 
+for recipe in [r.ldWithIndex, r.ldWithIndexDisp8, r.ldWithIndexDisp32]:
+    X86_32.enc(base.load_complex.i32.any.any, *recipe(0x8b))
+    X86_64.enc(base.load_complex.i32.any.any, *recipe.rex(0x8b))
+    X86_64.enc(base.load_complex.i32.any.any, *recipe(0x8b))
+    X86_64.enc(base.load_complex.i64.any.any, *recipe.rex(0x8b, w=1))
+
 enc_i32_i64(base.fill, r.fillSib32, 0x8b)
 enc_i32_i64(base.regfill, r.regfill32, 0x8b)
 
@@ -277,10 +316,78 @@ enc_both(base.regspill.f32, r.fregspill32, 0x66, 0x0f, 0x7e)
 enc_both(base.spill.f64, r.fspillSib32, 0x66, 0x0f, 0xd6)
 enc_both(base.regspill.f64, r.fregspill32, 0x66, 0x0f, 0xd6)
 
+#
+# Atomic memory operations.
+#
+
+order = Var('order')
+flags = Var('flags')
+x = Var('x')
+p = Var('p')
+
+
+def enc_atomic(inst, recipe, *args, **kwargs):
+    # type: (Instruction, r.TailRecipe, *int, **Any) -> None
+    """
+    Add encodings for `inst.i32` to X86_32 and X86_64 with and without REX.
+    Add encodings for `inst.i64` to X86_64 with a REX.W prefix.
+
+    The address can have any type. If the `operands` keyword argument is given,
+    the encodings only apply to instructions whose immediate operands match.
+    """
+    operands = kwargs.get('operands')
+
+    def bind(ty):
+        # type: (ValueType) -> InstSpec
+        bound = inst.bind(ty).any
+        return bound(*operands) if operands else bound
+
+    X86_32.enc(bind(i32), *recipe(*args))
+    X86_64.enc(bind(i32), *recipe.rex(*args))
+    X86_64.enc(bind(i32), *recipe(*args))
+    X86_64.enc(bind(i64), *recipe.rex(*args, w=1))
+
+
+enc_atomic(base.atomic_load, r.ald, 0x8b)
+
+# Sequentially consistent stores need an `mfence` so they can't be reordered
+# with later loads.
+for weak in [ordering.relaxed, ordering.release]:
+    enc_atomic(
+            base.atomic_store, r.ast, 0x89,
+            operands=(weak, flags, x, p))
+enc_atomic(
+        base.atomic_store, r.ast_mfence, 0x89,
+        operands=(ordering.seq_cst, flags, x, p))
+
+# xadd and xchg return the old value in the operand register. The remaining
+# operations are expanded into compare-and-swap loops.
+enc_atomic(
+        base.atomic_rmw, r.lock_rmw, 0x0f, 0xc1,
+        operands=(atomic_rmw_op.iadd, order, flags, p, x))
+enc_atomic(
+        base.atomic_rmw, r.rmw, 0x87,
+        operands=(atomic_rmw_op.xchg, order, flags, p, x))
+
+enc_atomic(base.atomic_cas, r.lock_cas, 0x0f, 0xb1)
+
+# Only a sequentially consistent fence needs an `mfence`.
+X86_32.enc(base.fence(ordering.seq_cst), *r.fence(0x0f, 0xae, rrr=6))
+X86_64.enc(base.fence(ordering.seq_cst), *r.fence(0x0f, 0xae, rrr=6))
+for weak in [ordering.acquire, ordering.release, ordering.acq_rel]:
+    X86_32.enc(base.fence(weak), r.barrier, 0)
+    X86_64.enc(base.fence(weak), r.barrier, 0)
+
 #
 # Function addresses.
 #
 
+# Colocated functions are reached with a RIP-relative LEA. This is shorter
+# than a 64-bit absolute address, and it doesn't need the GOT in PIC mode.
+X86_64.enc(base.func_addr.i64, *r.pcrel_fnaddr8.rex(0x8d, w=1),
+           instp=IsColocatedFunc(FuncAddr.func_ref),
+           isap=Not(allones_funcaddrs))
+
 X86_32.enc(base.func_addr.i32, *r.fnaddr4(0xb8),
            isap=Not(allones_funcaddrs))
 X86_64.enc(base.func_addr.i64, *r.fnaddr8.rex(0xb8, w=1),
@@ -298,6 +405,9 @@ X86_64.enc(base.func_addr.i64, *r.got_fnaddr8.rex(0x8b, w=1),
 # Global addresses.
 #
 
+X86_64.enc(base.globalsym_addr.i64, *r.pcrel_gvaddr8.rex(0x8d, w=1),
+           instp=IsColocatedData(UnaryGlobalVar.global_var))
+
 X86_32.enc(base.globalsym_addr.i32, *r.gvaddr4(0xb8))
 X86_64.enc(base.globalsym_addr.i64, *r.gvaddr8.rex(0xb8, w=1),
            isap=Not(is_pic))
@@ -305,13 +415,33 @@ X86_64.enc(base.globalsym_addr.i64, *r.gvaddr8.rex(0xb8, w=1),
 X86_64.enc(base.globalsym_addr.i64, *r.got_gvaddr8.rex(0x8b, w=1),
            isap=is_pic)
 
+X86_64.enc(base.gotsym_addr.i64, *r.got_gvaddr8.rex(0x8b, w=1))
+
+X86_64.enc(base.tlssym_addr.i64, *r.tls_gvaddr8.rex(0x8b, w=1))
+
 #
 # Call/return
 #
 X86_32.enc(base.call, *r.call_id(0xe8))
 X86_64.enc(base.call, *r.call_id(0xe8), isap=Not(is_pic))
+# Colocated functions don't need to go through the PLT.
+X86_64.enc(base.call, *r.call_id(0xe8),
+           instp=IsColocatedFunc(Call.func_ref))
 X86_64.enc(base.call, *r.call_plt_id(0xe8), isap=is_pic)
 
+X86_32.enc(base.call_patchable, *r.call_patchable_id(0xe8))
+X86_64.enc(base.call_patchable, *r.call_patchable_id(0xe8),
+           isap=Not(is_pic))
+X86_64.enc(base.call_patchable, *r.call_patchable_id(0xe8),
+           instp=IsColocatedFunc(Call.func_ref))
+X86_64.enc(base.call_patchable, *r.call_patchable_plt_id(0xe8), isap=is_pic)
+
+X86_32.enc(base.try_call, *r.try_call_id(0xe8))
+X86_64.enc(base.try_call, *r.try_call_id(0xe8), isap=Not(is_pic))
+X86_64.enc(base.try_call, *r.try_call_id(0xe8),
+           instp=IsColocatedFunc(TryCall.func_ref))
+X86_64.enc(base.try_call, *r.try_call_plt_id(0xe8), isap=is_pic)
+
 X86_32.enc(base.call_indirect.i32, *r.call_r(0xff, rrr=2))
 X86_64.enc(base.call_indirect.i64, *r.call_r.rex(0xff, rrr=2))
 X86_64.enc(base.call_indirect.i64, *r.call_r(0xff, rrr=2))
@@ -513,6 +643,9 @@ for inst in [
 
 
 # Binary arithmetic ops.
+#
+# The non-destructive AVX forms come first so they are preferred when
+# available. They are only used in 64-bit mode.
 for inst,           opc in [
         (base.fadd, 0x58),
         (base.fsub, 0x5c),
@@ -520,6 +653,8 @@ for inst,           opc in [
         (base.fdiv, 0x5e),
         (x86.fmin,  0x5d),
         (x86.fmax,  0x5f)]:
+    X86_64.enc(inst.f32, *r.vfa.vex(0xf3, 0x0f, opc))
+    X86_64.enc(inst.f64, *r.vfa.vex(0xf2, 0x0f, opc))
     enc_both(inst.f32, r.fa, 0xf3, 0x0f, opc)
     enc_both(inst.f64, r.fa, 0xf2, 0x0f, opc)
 
@@ -528,6 +663,8 @@ for inst,               opc in [
         (base.band,     0x54),
         (base.bor,      0x56),
         (base.bxor,     0x57)]:
+    X86_64.enc(inst.f32, *r.vfa.vex(0x0f, opc))
+    X86_64.enc(inst.f64, *r.vfa.vex(0x0f, opc))
     enc_both(inst.f32, r.fa, 0x0f, opc)
     enc_both(inst.f64, r.fa, 0x0f, opc)
 
@@ -535,6 +672,12 @@ for inst,               opc in [
 enc_both(base.band_not.f32, r.fax, 0x0f, 0x55)
 enc_both(base.band_not.f64, r.fax, 0x0f, 0x55)
 
+# Variable blends, used to implement `select`.
+X86_64.enc(x86.blendv.f32, *r.vblend.vex(0x66, 0x0f, 0x3a, 0x4a))
+X86_64.enc(x86.blendv.f64, *r.vblend.vex(0x66, 0x0f, 0x3a, 0x4b))
+enc_both(x86.blendv.f32, r.blend, 0x66, 0x0f, 0x38, 0x14)
+enc_both(x86.blendv.f64, r.blend, 0x66, 0x0f, 0x38, 0x15)
+
 # Comparisons.
 #
 # This only covers the condition codes in `supported_floatccs`, the rest are
@@ -544,3 +687,47 @@ enc_both(base.fcmp.f64, r.fcscc, 0x66, 0x0f, 0x2e)
 
 enc_both(base.ffcmp.f32, r.fcmp, 0x0f, 0x2e)
 enc_both(base.ffcmp.f64, r.fcmp, 0x66, 0x0f, 0x2e)
+
+
+#
+# SIMD
+#
+# Only a few operations on 128-bit integer vectors are supported. Other vector
+# operations are split into scalars by the legalizer.
+
+# movdqu
+enc_both(base.load.bind(i32x4).any, r.fld, 0xf3, 0x0f, 0x6f)
+enc_both(base.load.bind(i32x4).any, r.fldDisp8, 0xf3, 0x0f, 0x6f)
+enc_both(base.load.bind(i32x4).any, r.fldDisp32, 0xf3, 0x0f, 0x6f)
+enc_both(base.store.bind(i32x4).any, r.fst, 0xf3, 0x0f, 0x7f)
+enc_both(base.store.bind(i32x4).any, r.fstDisp8, 0xf3, 0x0f, 0x7f)
+enc_both(base.store.bind(i32x4).any, r.fstDisp32, 0xf3, 0x0f, 0x7f)
+enc_both(base.fill.bind(i32x4), r.ffillSib32, 0xf3, 0x0f, 0x6f)
+enc_both(base.regfill.bind(i32x4), r.fregfill32, 0xf3, 0x0f, 0x6f)
+enc_both(base.spill.bind(i32x4), r.fspillSib32, 0xf3, 0x0f, 0x7f)
+enc_both(base.regspill.bind(i32x4), r.fregspill32, 0xf3, 0x0f, 0x7f)
+
+# movdqa
+enc_both(base.copy.bind(i32x4), r.furm, 0x66, 0x0f, 0x6f)
+enc_both(base.regmove.bind(i32x4), r.frmov, 0x66, 0x0f, 0x6f)
+
+# paddd, psubd
+for inst,           opc in [
+        (base.iadd, 0xfe),
+        (base.isub, 0xfa)]:
+    X86_64.enc(inst.bind(i32x4), *r.vfa.vex(0x66, 0x0f, opc))
+    enc_both(inst.bind(i32x4), r.fa, 0x66, 0x0f, opc)
+
+# pmulld
+X86_64.enc(base.imul.bind(i32x4), *r.vfa.vex(0x66, 0x0f, 0x38, 0x40))
+X86_32.enc(base.imul.bind(i32x4), *r.fa(0x66, 0x0f, 0x38, 0x40), isap=use_sse41)
+X86_64.enc(base.imul.bind(i32x4), *r.fa.rex(0x66, 0x0f, 0x38, 0x40), isap=use_sse41)
+X86_64.enc(base.imul.bind(i32x4), *r.fa(0x66, 0x0f, 0x38, 0x40), isap=use_sse41)
+
+# pshufd, shufps
+#
+# The generic `shuffle` and `swizzle` instructions are legalized into these.
+X86_64.enc(x86.pshufd.bind(i32x4), *r.vfshuf.vex(0x66, 0x0f, 0x70))
+enc_both(x86.pshufd.bind(i32x4), r.fshuf, 0x66, 0x0f, 0x70)
+X86_64.enc(x86.shufps.bind(i32x4), *r.vfshuf2.vex(0x0f, 0xc6))
+enc_both(x86.shufps.bind(i32x4), r.fshuf2, 0x0f, 0xc6)
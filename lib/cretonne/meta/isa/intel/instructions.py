@@ -6,6 +6,7 @@ target ISA.
 """
 
 from base.types import iflags
+from base.immediates import uimm8
 from cdsl.operands import Operand
 from cdsl.typevar import TypeVar
 from cdsl.instructions import Instruction, InstructionGroup
@@ -122,6 +123,17 @@ fmax = Instruction(
         """,
         ins=(x, y), outs=a)
 
+c = Operand('c', Float, doc='Selection mask')
+
+blendv = Instruction(
+        'x86_blendv', r"""
+        Variable blend.
+
+        Select each lane from `x` when the sign bit of the corresponding lane
+        in `c` is set, and from `y` otherwise.
+        """,
+        ins=(c, x, y), outs=a)
+
 
 x = Operand('x', iWord)
 
@@ -170,4 +182,58 @@ bsf = Instruction(
     """,
     ins=x, outs=(y, rflags))
 
+x = Operand('x', iWord, doc='Destination word')
+y = Operand('y', iWord, doc='Word supplying the shifted-in bits')
+c = Operand('c', iWord, doc='Shift amount')
+a = Operand('a', iWord)
+
+shld = Instruction(
+    'x86_shld', r"""
+    Double precision shift left.
+
+    Shift `x` left by `c` bits, filling the vacated low bits with the high
+    bits of `y`. The shift amount is masked to the number of bits in `x`.
+
+    This is used to shift the high half of a register pair.
+    """,
+    ins=(x, y, c), outs=a)
+
+shrd = Instruction(
+    'x86_shrd', r"""
+    Double precision shift right.
+
+    Shift `x` right by `c` bits, filling the vacated high bits with the low
+    bits of `y`. The shift amount is masked to the number of bits in `x`.
+
+    This is used to shift the low half of a register pair.
+    """,
+    ins=(x, y, c), outs=a)
+
+Vec32 = TypeVar(
+        'Vec32', 'A 128-bit vector with 32-bit lanes',
+        ints=(32, 32), floats=(32, 32), simd=(4, 4))
+
+x = Operand('x', Vec32)
+y = Operand('y', Vec32)
+a = Operand('a', Vec32)
+Order = Operand('Order', uimm8, doc='Two bits per result lane')
+
+pshufd = Instruction(
+    'x86_pshufd', r"""
+    Shuffle 32-bit lanes.
+
+    Lane ``i`` of the result is lane ``(Order >> 2*i) & 3`` of ``x``.
+    """,
+    ins=(x, Order), outs=a)
+
+shufps = Instruction(
+    'x86_shufps', r"""
+    Shuffle 32-bit lanes from two vectors.
+
+    The two low lanes of the result are selected from ``x`` and the two high
+    lanes are selected from ``y``. Lane ``i`` of the result is lane
+    ``(Order >> 2*i) & 3`` of the selected vector.
+    """,
+    ins=(x, Order, y), outs=a)
+
 GROUP.close()
@@ -19,6 +19,26 @@ intel_expand = XFormGroup(
         """,
         isa=ISA, chain=shared.expand_flags)
 
+intel_narrow = XFormGroup(
+        'intel_narrow',
+        """
+        Legalize 64-bit integer instructions on 32-bit Intel.
+
+        Use double precision shifts for the register pairs, and fall back to
+        the generic narrowing patterns.
+        """,
+        isa=ISA, chain=shared.narrow)
+
+intel_vector = XFormGroup(
+        'intel_vector',
+        """
+        Legalize 128-bit vector instructions.
+
+        Use Intel-specific shuffles if needed, and split other unsupported
+        vector operations.
+        """,
+        isa=ISA, chain=shared.narrow)
+
 a = Var('a')
 dead = Var('dead')
 x = Var('x')
@@ -37,6 +57,73 @@ intel_expand.custom_legalize(insts.srem, 'expand_sdivrem')
 intel_expand.custom_legalize(insts.udiv, 'expand_udivrem')
 intel_expand.custom_legalize(insts.urem, 'expand_udivrem')
 
+#
+# Register pairs.
+#
+# Instructions with immediates, variable arguments, or memory operands are
+# narrowed by the shared custom code. Division becomes a library call after
+# checking the trapping conditions explicitly.
+intel_narrow.custom_legalize(insts.iconst, 'narrow_iconst')
+for inst in [insts.brz, insts.brnz, insts.br_icmp]:
+    intel_narrow.custom_legalize(inst, 'narrow_branch')
+for inst in [insts.load, insts.uload8, insts.sload8, insts.uload16,
+             insts.sload16]:
+    intel_narrow.custom_legalize(inst, 'narrow_load')
+for inst in [insts.store, insts.istore8, insts.istore16, insts.istore32]:
+    intel_narrow.custom_legalize(inst, 'narrow_store')
+for inst in [insts.sdiv, insts.udiv, insts.srem, insts.urem]:
+    intel_narrow.custom_legalize(inst, 'narrow_divrem')
+
+# The shift amounts are taken modulo 64, so the custom code selects the halves
+# based on bit 5 of the amount.
+intel_narrow.custom_legalize(insts.ishl, 'narrow_shift')
+intel_narrow.custom_legalize(insts.ushr, 'narrow_shift')
+intel_narrow.custom_legalize(insts.sshr, 'narrow_shift')
+
+# Bit counting on register pairs combines the counts for the two halves.
+xl = Var('xl')
+xh = Var('xh')
+al = Var('al')
+ah = Var('ah')
+a3 = Var('a3')
+b = Var('b')
+intel_narrow.legalize(
+        a << insts.popcnt.i64(x),
+        Rtl(
+            (xl, xh) << insts.isplit(x),
+            a1 << insts.popcnt(xl),
+            a2 << insts.popcnt(xh),
+            al << insts.iadd(a1, a2),
+            ah << insts.iconst.i32(imm64(0)),
+            a << insts.iconcat(al, ah)
+        ))
+
+intel_narrow.legalize(
+        a << insts.clz.i64(x),
+        Rtl(
+            (xl, xh) << insts.isplit(x),
+            a1 << insts.clz(xh),
+            a2 << insts.clz(xl),
+            a3 << insts.iadd_imm(a2, imm64(32)),
+            b << insts.icmp_imm(intcc.ne, xh, imm64(0)),
+            al << insts.select(b, a1, a3),
+            ah << insts.iconst.i32(imm64(0)),
+            a << insts.iconcat(al, ah)
+        ))
+
+intel_narrow.legalize(
+        a << insts.ctz.i64(x),
+        Rtl(
+            (xl, xh) << insts.isplit(x),
+            a1 << insts.ctz(xl),
+            a2 << insts.ctz(xh),
+            a3 << insts.iadd_imm(a2, imm64(32)),
+            b << insts.icmp_imm(intcc.ne, xl, imm64(0)),
+            al << insts.select(b, a1, a3),
+            ah << insts.iconst.i32(imm64(0)),
+            a << insts.iconcat(al, ah)
+        ))
+
 #
 # Double length (widening) multiplication
 #
@@ -92,6 +179,9 @@ for cc,               rev_cc in [
 intel_expand.custom_legalize(insts.fmin, 'expand_minmax')
 intel_expand.custom_legalize(insts.fmax, 'expand_minmax')
 
+# Floating point selects can use a blend instead of a branch.
+intel_expand.custom_legalize(insts.select, 'expand_select')
+
 # Conversions from unsigned need special handling.
 intel_expand.custom_legalize(insts.fcvt_from_uint, 'expand_fcvt_from_uint')
 # Conversions from float to int can trap.
@@ -225,3 +315,9 @@ intel_expand.legalize(
         lv15 << insts.imul(lv14, lc01),
         lv16 << insts.ushr_imm(lv15, imm64(24))
     ))
+
+#
+# Vector shuffles.
+#
+intel_vector.custom_legalize(insts.shuffle, 'expand_shuffle')
+intel_vector.custom_legalize(insts.swizzle, 'expand_shuffle')
@@ -6,17 +6,20 @@ from cdsl.isa import EncRecipe
 from cdsl.predicates import IsSignedInt, IsEqual, Or
 from cdsl.registers import RegClass
 from base.formats import Unary, UnaryImm, Binary, BinaryImm, MultiAry, NullAry
-from base.formats import Trap, Call, IndirectCall, Store, Load
+from base.formats import Trap, Call, IndirectCall, TryCall, Store, Load
+from base.formats import StoreComplex, LoadComplex
 from base.formats import IntCompare, FloatCompare, IntCond, FloatCond
 from base.formats import IntSelect, IntCondTrap, FloatCondTrap
 from base.formats import Jump, Branch, BranchInt, BranchFloat
 from base.formats import Ternary, FuncAddr, UnaryGlobalVar
+from base.formats import InsertLane, ExtractLane
 from base.formats import RegMove, RegSpill, RegFill, CopySpecial
+from base.formats import AtomicLoad, AtomicStore, AtomicRmw, AtomicCas, Fence
 from .registers import GPR, ABCD, FPR, GPR_DEREF_SAFE, GPR_ZERO_DEREF_SAFE
 from .registers import GPR8, FPR8, GPR8_DEREF_SAFE, GPR8_ZERO_DEREF_SAFE, FLAG
 from .registers import StackGPR32, StackFPR32
 from .defs import supported_floatccs
-from .settings import use_sse41
+from .settings import use_sse41, use_avx
 
 try:
     from typing import Tuple, Dict, Sequence, Any  # noqa
@@ -54,8 +57,8 @@ OPCODE_PREFIX = {
         }
 
 # The table above does not include the REX prefix which goes after the
-# mandatory prefix. VEX/XOP and EVEX prefixes are not yet supported. Encodings
-# using any of these prefixes are represented by separate recipes.
+# mandatory prefix. Encodings using a VEX prefix are represented by separate
+# recipes, see `TailRecipe.vex()`. XOP and EVEX prefixes are not yet supported.
 #
 # The encoding bits are:
 #
@@ -140,6 +143,10 @@ class TailRecipe:
     The `requires_prefix` parameter indicates that the recipe can't be used
     without a REX prefix.
 
+    The `requires_vex` parameter indicates that the recipe can only be used
+    with a VEX prefix. The `emit` code for such a recipe passes the extra
+    `VEX.vvvv` register operand to `PUT_OP`.
+
     The `emit` parameter contains Rust code to actually emit an encoding, like
     `EncRecipe` does it. Additionally, the text `PUT_OP` is substituted with
     the proper `put_*` function from the `intel/binemit.rs` module.
@@ -158,6 +165,7 @@ class TailRecipe:
             isap=None,              # type: PredNode
             when_prefixed=None,     # type: TailRecipe
             requires_prefix=False,  # type: bool
+            requires_vex=False,     # type: bool
             emit=None               # type: str
             ):
         # type: (...) -> None
@@ -172,6 +180,7 @@ class TailRecipe:
         self.isap = isap
         self.when_prefixed = when_prefixed
         self.requires_prefix = requires_prefix
+        self.requires_vex = requires_vex
         self.emit = emit
 
         # Cached recipes, keyed by name prefix.
@@ -184,6 +193,7 @@ class TailRecipe:
         `ops`.
         """
         assert not self.requires_prefix, "Tail recipe requires REX prefix."
+        assert not self.requires_vex, "Tail recipe requires VEX prefix."
         rrr = kwargs.get('rrr', 0)
         w = kwargs.get('w', 0)
         name, bits = decode_ops(ops, rrr, w)
@@ -222,6 +232,8 @@ class TailRecipe:
         not. For instructions that don't require a REX prefix, two encodings
         should be added: One with REX and one without.
         """
+        assert not self.requires_vex, "Tail recipe requires VEX prefix."
+
         # Use the prefixed alternative recipe when applicable.
         if self.when_prefixed:
             return self.when_prefixed.rex(*ops, **kwargs)
@@ -253,6 +265,38 @@ class TailRecipe:
 
         return (self.recipes[name], bits)
 
+    def vex(self, *ops, **kwargs):
+        # type: (*int, **int) -> Tuple[EncRecipe, int]
+        """
+        Create a VEX encoding recipe and encoding bits for the opcode bytes in
+        `ops`.
+
+        The mandatory prefix and opcode map are folded into the VEX prefix.
+        The recipe always uses the three-byte VEX form so its size doesn't
+        depend on the registers chosen.
+        """
+        assert self.requires_vex, "Tail recipe doesn't support VEX prefix."
+        w = kwargs.get('w', 0)
+        name, bits = decode_ops(ops, 0, w)
+        assert bits & 0x0c00 != 0, "VEX prefix requires an opcode map."
+        name = 'Vex' + name
+        size = 4 + self.size
+
+        if name not in self.recipes:
+            recipe = EncRecipe(
+                name + self.name,
+                self.format,
+                size,
+                ins=self.ins,
+                outs=self.outs,
+                clobbers_flags=self.clobbers_flags,
+                instp=self.instp,
+                isap=self.isap,
+                emit=replace_put_op(self.emit, 'Vex'))
+            self.recipes[name] = recipe
+
+        return (self.recipes[name], bits)
+
     @staticmethod
     def check_names(globs):
         # type: (Dict[str, Any]) -> None
@@ -277,7 +321,10 @@ null = EncRecipe('null', Unary, size=0, ins=GPR, outs=0, emit='')
 # XX opcode, no ModR/M.
 trap = TailRecipe(
         'trap', Trap, size=0, ins=(), outs=(),
-        emit='PUT_OP(bits, BASE_REX, sink);')
+        emit='''
+        sink.trap(code, func.srclocs[inst]);
+        PUT_OP(bits, BASE_REX, sink);
+        ''')
 
 # Macro: conditional jump over a ud2.
 trapif = EncRecipe(
@@ -288,6 +335,7 @@ trapif = EncRecipe(
         sink.put1(0x70 | (icc2opc(cond.inverse()) as u8));
         sink.put1(2);
         // ud2.
+        sink.trap(code, func.srclocs[inst]);
         sink.put1(0x0f);
         sink.put1(0x0b);
         ''')
@@ -301,6 +349,7 @@ trapff = EncRecipe(
         sink.put1(0x70 | (fcc2opc(cond.inverse()) as u8));
         sink.put1(2);
         // ud2.
+        sink.trap(code, func.srclocs[inst]);
         sink.put1(0x0f);
         sink.put1(0x0b);
         ''')
@@ -338,6 +387,89 @@ fax = TailRecipe(
         modrm_rr(in_reg0, in_reg1, sink);
         ''')
 
+# XX /r with FPR ins and outs. Non-destructive VEX form where the first input
+# is in VEX.vvvv.
+vfa = TailRecipe(
+        'vfa', Binary, size=1, ins=(FPR, FPR), outs=FPR,
+        isap=use_avx, requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+        modrm_rr(in_reg1, out_reg0, sink);
+        ''')
+
+# XX /r variable blend with the selection mask implicitly in %xmm0.
+# The second input is selected where the mask is set, the third input is tied
+# to the output.
+blend = TailRecipe(
+        'blend', Ternary, size=1, ins=(FPR.xmm0, FPR, FPR), outs=2,
+        isap=use_sse41,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg2), sink);
+        modrm_rr(in_reg1, in_reg2, sink);
+        ''')
+
+# XX /r ib variable blend with the selection mask in the immediate byte.
+vblend = TailRecipe(
+        'vblend', Ternary, size=2, ins=(FPR, FPR, FPR), outs=FPR,
+        isap=use_avx, requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg2, sink);
+        modrm_rr(in_reg1, out_reg0, sink);
+        sink.put1(((in_reg0 & 0xf) << 4) as u8);
+        ''')
+
+# XX /r ib with FPR ins and outs. The immediate byte selects the lanes of the
+# input.
+fshuf = TailRecipe(
+        'fshuf', ExtractLane, size=2, ins=FPR, outs=FPR,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# Same as fshuf, with a VEX prefix.
+vfshuf = TailRecipe(
+        'vfshuf', ExtractLane, size=2, ins=FPR, outs=FPR,
+        clobbers_flags=False, isap=use_avx, requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), 0, sink);
+        modrm_rr(in_reg0, out_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# XX /r ib with FPR ins and outs. The immediate byte selects the low lanes from
+# the first input, which is tied to the output, and the high lanes from the
+# second input.
+fshuf2 = TailRecipe(
+        'fshuf2', InsertLane, size=2, ins=(FPR, FPR), outs=0,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rr(in_reg1, in_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# Non-destructive VEX form of fshuf2 where the first input is in VEX.vvvv.
+vfshuf2 = TailRecipe(
+        'vfshuf2', InsertLane, size=2, ins=(FPR, FPR), outs=FPR,
+        clobbers_flags=False, isap=use_avx, requires_vex=True,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, out_reg0), in_reg0, sink);
+        modrm_rr(in_reg1, out_reg0, sink);
+        sink.put1(lane);
+        ''')
+
+# XX /n for a unary operator with the output tied to the input.
+ur = TailRecipe(
+        'ur', Unary, size=1, ins=GPR, outs=0,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex1(in_reg0), sink);
+        modrm_r_bits(in_reg0, bits, sink);
+        ''')
+
 # XX /r, but for a unary operator with separate input/output register, like
 # copies. MR form, preserving flags.
 umr = TailRecipe(
@@ -444,11 +576,20 @@ rc = TailRecipe(
         modrm_r_bits(in_reg0, bits, sink);
         ''')
 
+# XX /r with the shift amount in %rcx, for double precision shifts.
+dshift = TailRecipe(
+        'dshift', Ternary, size=1, ins=(GPR, GPR, GPR.rcx), outs=0,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+        modrm_rr(in_reg0, in_reg1, sink);
+        ''')
+
 # XX /n for division: inputs in %rax, %rdx, r. Outputs in %rax, %rdx.
 div = TailRecipe(
         'div', Ternary, size=1,
         ins=(GPR.rax, GPR.rdx, GPR), outs=(GPR.rax, GPR.rdx),
         emit='''
+        sink.trap(TrapCode::IntegerDivisionByZero, func.srclocs[inst]);
         PUT_OP(bits, rex1(in_reg2), sink);
         modrm_r_bits(in_reg2, bits, sink);
         ''')
@@ -506,6 +647,16 @@ puid = TailRecipe(
         sink.put4(imm as u32);
         ''')
 
+# XX+rd unary operating in place, like `bswap`.
+pur = TailRecipe(
+        'pur', Unary, size=0, ins=GPR, outs=0,
+        clobbers_flags=False,
+        emit='''
+        // The register is encoded in the low bits of the opcode.
+        // No ModR/M.
+        PUT_OP(bits | (in_reg0 & 7), rex1(in_reg0), sink);
+        ''')
+
 # XX+rd iq unary with 64-bit immediate.
 puiq = TailRecipe(
         'puiq', UnaryImm, size=8, ins=(), outs=GPR,
@@ -618,6 +769,22 @@ got_fnaddr8 = TailRecipe(
         sink.put4(0);
         ''')
 
+# LEA with a RIP-relative displacement to a colocated function.
+pcrel_fnaddr8 = TailRecipe(
+        'pcrel_fnaddr8', FuncAddr, size=5, ins=(), outs=GPR,
+        # rex2 gets passed 0 for r/m register because the upper bit of
+        # r/m doesnt get decoded when in rip-relative addressing mode.
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        // The addend adjusts for the difference between the end of the
+        // instruction and the beginning of the immediate field.
+        sink.reloc_external(Reloc::IntelPCRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        ''')
+
 
 # XX+rd id with Abs4 globalsym relocation.
 gvaddr4 = TailRecipe(
@@ -655,6 +822,52 @@ got_gvaddr8 = TailRecipe(
         sink.put4(0);
         ''')
 
+# LEA with a RIP-relative displacement to a colocated globalsym.
+pcrel_gvaddr8 = TailRecipe(
+        'pcrel_gvaddr8', UnaryGlobalVar, size=5, ins=(), outs=GPR,
+        emit='''
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_riprel(out_reg0, sink);
+        // The addend adjusts for the difference between the end of the
+        // instruction and the beginning of the immediate field.
+        sink.reloc_external(Reloc::IntelPCRel4,
+                            &func.global_vars[global_var].symbol_name(),
+                            -4);
+        sink.put4(0);
+        ''')
+
+# Thread-local symbol address: mov %fs:0, %r followed by an add of the
+# variable's offset from the thread pointer, depending on the TLS model.
+tls_gvaddr8 = TailRecipe(
+        'tls_gvaddr8', UnaryGlobalVar, size=14, ins=(), outs=GPR,
+        emit='''
+        // mov %fs:0, out_reg0
+        sink.put1(0x64);
+        PUT_OP(bits, rex2(0, out_reg0), sink);
+        modrm_rm(0b100, out_reg0, sink);
+        sib_noindex(0b101, sink);
+        sink.put4(0);
+        match func.global_vars[global_var] {
+            GlobalVarData::TlsSym { ref name, model: TlsModel::InitialExec } => {
+                // add sym@gottpoff(%rip), out_reg0
+                sink.put1(rex2(0, out_reg0) | REX_W);
+                sink.put1(0x03);
+                modrm_riprel(out_reg0, sink);
+                sink.reloc_external(Reloc::IntelGOTTPOff4, name, -4);
+                sink.put4(0);
+            }
+            GlobalVarData::TlsSym { ref name, model: TlsModel::LocalExec } => {
+                // add $sym@tpoff, out_reg0
+                sink.put1(rex1(out_reg0) | REX_W);
+                sink.put1(0x81);
+                modrm_r_bits(out_reg0, 0, sink);
+                sink.reloc_external(Reloc::IntelTPOff4, name, 0);
+                sink.put4(0);
+            }
+            _ => panic!("tls_gvaddr8 requires a thread-local symbol"),
+        }
+        ''')
+
 
 #
 # Store recipes.
@@ -755,6 +968,45 @@ fstDisp32 = TailRecipe(
         sink.put4(offset as u32);
         ''')
 
+# XX /r store with a scaled index and no offset.
+stWithIndex = TailRecipe(
+        'stWithIndex', StoreComplex, size=2,
+        ins=(GPR, GPR_ZERO_DEREF_SAFE, GPR_DEREF_SAFE), outs=(),
+        instp=IsEqual(StoreComplex.offset, 0),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+        modrm_sib(in_reg0, sink);
+        sib(scale, in_reg2, in_reg1, sink);
+        ''')
+
+# XX /r store with a scaled index and an 8-bit offset.
+stWithIndexDisp8 = TailRecipe(
+        'stWithIndexDisp8', StoreComplex, size=3,
+        ins=(GPR, GPR, GPR_DEREF_SAFE), outs=(),
+        instp=IsSignedInt(StoreComplex.offset, 8),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+        modrm_sib_disp8(in_reg0, sink);
+        sib(scale, in_reg2, in_reg1, sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+        ''')
+
+# XX /r store with a scaled index and a 32-bit offset.
+stWithIndexDisp32 = TailRecipe(
+        'stWithIndexDisp32', StoreComplex, size=6,
+        ins=(GPR, GPR, GPR_DEREF_SAFE), outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg1, in_reg0, in_reg2), sink);
+        modrm_sib_disp32(in_reg0, sink);
+        sib(scale, in_reg2, in_reg1, sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+        ''')
+
 # Unary spill with SIB and 32-bit displacement.
 spillSib32 = TailRecipe(
         'spillSib32', Unary, size=6, ins=GPR, outs=StackGPR32,
@@ -877,6 +1129,45 @@ fldDisp32 = TailRecipe(
         sink.put4(offset as u32);
         ''')
 
+# XX /r load with a scaled index and no offset.
+ldWithIndex = TailRecipe(
+        'ldWithIndex', LoadComplex, size=2,
+        ins=(GPR_ZERO_DEREF_SAFE, GPR_DEREF_SAFE), outs=(GPR),
+        instp=IsEqual(LoadComplex.offset, 0),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+        modrm_sib(out_reg0, sink);
+        sib(scale, in_reg1, in_reg0, sink);
+        ''')
+
+# XX /r load with a scaled index and an 8-bit offset.
+ldWithIndexDisp8 = TailRecipe(
+        'ldWithIndexDisp8', LoadComplex, size=3,
+        ins=(GPR, GPR_DEREF_SAFE), outs=(GPR),
+        instp=IsSignedInt(LoadComplex.offset, 8),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+        modrm_sib_disp8(out_reg0, sink);
+        sib(scale, in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put1(offset as u8);
+        ''')
+
+# XX /r load with a scaled index and a 32-bit offset.
+ldWithIndexDisp32 = TailRecipe(
+        'ldWithIndexDisp32', LoadComplex, size=6,
+        ins=(GPR, GPR_DEREF_SAFE), outs=(GPR),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex3(in_reg0, out_reg0, in_reg1), sink);
+        modrm_sib_disp32(out_reg0, sink);
+        sib(scale, in_reg1, in_reg0, sink);
+        let offset: i32 = offset.into();
+        sink.put4(offset as u32);
+        ''')
+
 # Unary fill with SIB and 32-bit displacement.
 fillSib32 = TailRecipe(
         'fillSib32', Unary, size=6, ins=StackGPR32, outs=GPR,
@@ -927,6 +1218,92 @@ fregfill32 = TailRecipe(
         sink.put4(src.offset as u32);
         ''')
 
+#
+# Atomic memory operations.
+#
+# Aligned loads and stores are atomic on x86, and the memory model only allows
+# a store to be reordered after a later load. Only sequentially consistent
+# stores and fences need an `mfence`.
+#
+
+# XX /r atomic load with no offset.
+ald = TailRecipe(
+        'ald', AtomicLoad, size=1, ins=(GPR_ZERO_DEREF_SAFE), outs=(GPR),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, out_reg0), sink);
+        modrm_rm(in_reg0, out_reg0, sink);
+        ''')
+
+# XX /r atomic store with no offset.
+ast = TailRecipe(
+        'ast', AtomicStore, size=1, ins=(GPR, GPR_ZERO_DEREF_SAFE), outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rm(in_reg1, in_reg0, sink);
+        ''')
+
+# XX /r atomic store with no offset followed by an mfence.
+ast_mfence = TailRecipe(
+        'ast_mfence', AtomicStore, size=4,
+        ins=(GPR, GPR_ZERO_DEREF_SAFE), outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg1, in_reg0), sink);
+        modrm_rm(in_reg1, in_reg0, sink);
+        // mfence.
+        sink.put1(0x0f);
+        sink.put1(0xae);
+        sink.put1(0xf0);
+        ''')
+
+# XX /r read-modify-write with the old value returned in the operand
+# register. Instructions like `xchg` with a memory operand are always locked.
+rmw = TailRecipe(
+        'rmw', AtomicRmw, size=1, ins=(GPR_ZERO_DEREF_SAFE, GPR), outs=1,
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+        modrm_rm(in_reg0, in_reg1, sink);
+        ''')
+
+# LOCK XX /r read-modify-write with the old value returned in the operand
+# register.
+lock_rmw = TailRecipe(
+        'lock_rmw', AtomicRmw, size=2, ins=(GPR_ZERO_DEREF_SAFE, GPR), outs=1,
+        emit='''
+        // LOCK prefix.
+        sink.put1(0xf0);
+        PUT_OP(bits, rex2(in_reg0, in_reg1), sink);
+        modrm_rm(in_reg0, in_reg1, sink);
+        ''')
+
+# LOCK XX /r compare and swap. The expected value and the result are in %rax.
+lock_cas = TailRecipe(
+        'lock_cas', AtomicCas, size=2,
+        ins=(GPR_ZERO_DEREF_SAFE, GPR.rax, GPR), outs=GPR.rax,
+        emit='''
+        // LOCK prefix.
+        sink.put1(0xf0);
+        PUT_OP(bits, rex2(in_reg0, in_reg2), sink);
+        modrm_rm(in_reg0, in_reg2, sink);
+        ''')
+
+# XX /n with no register operands, like `mfence`.
+fence = TailRecipe(
+        'fence', Fence, size=1, ins=(), outs=(),
+        clobbers_flags=False,
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        modrm_r_bits(0, bits, sink);
+        ''')
+
+# A fence that only constrains the compiler. No code is needed.
+barrier = EncRecipe(
+        'barrier', Fence, size=0, ins=(), outs=(), clobbers_flags=False,
+        emit='')
+
 #
 # Call/return
 #
@@ -950,6 +1327,64 @@ call_plt_id = TailRecipe(
         sink.put4(0);
         ''')
 
+# A patchable call is padded with no-ops so the 4-byte displacement is
+# aligned and can be rewritten atomically. The sequence is always 8 bytes long.
+call_patchable_id = TailRecipe(
+        'call_patchable_id', Call, size=7, ins=(), outs=(),
+        emit='''
+        let pad = 3 - sink.offset() % 4;
+        for _ in 0..pad {
+            sink.put1(0x90);
+        }
+        sink.patchable_call(&func.dfg.ext_funcs[func_ref].name);
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::IntelPCRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            0);
+        sink.put4(0);
+        for _ in pad..3 {
+            sink.put1(0x90);
+        }
+        ''')
+
+call_patchable_plt_id = TailRecipe(
+        'call_patchable_plt_id', Call, size=7, ins=(), outs=(),
+        emit='''
+        let pad = 3 - sink.offset() % 4;
+        for _ in 0..pad {
+            sink.put1(0x90);
+        }
+        sink.patchable_call(&func.dfg.ext_funcs[func_ref].name);
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::IntelPLTRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        for _ in pad..3 {
+            sink.put1(0x90);
+        }
+        ''')
+
+try_call_id = TailRecipe(
+        'try_call_id', TryCall, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::IntelPCRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            0);
+        sink.put4(0);
+        ''')
+
+try_call_plt_id = TailRecipe(
+        'try_call_plt_id', TryCall, size=4, ins=(), outs=(),
+        emit='''
+        PUT_OP(bits, BASE_REX, sink);
+        sink.reloc_external(Reloc::IntelPLTRel4,
+                            &func.dfg.ext_funcs[func_ref].name,
+                            -4);
+        sink.put4(0);
+        ''')
+
 call_r = TailRecipe(
         'call_r', IndirectCall, size=1, ins=GPR, outs=(),
         emit='''
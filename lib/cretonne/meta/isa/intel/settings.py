@@ -32,6 +32,7 @@ has_lzcnt = BoolSetting("LZCNT: CPUID.EAX=80000001H:ECX.LZCNT[bit 5]")
 use_sse41 = And(has_sse41)
 use_sse42 = And(has_sse42, use_sse41)
 use_popcnt = And(has_popcnt, has_sse42)
+use_avx = And(has_avx, use_sse42)
 use_bmi1 = And(has_bmi1)
 use_lzcnt = And(has_lzcnt)
 
@@ -3,36 +3,52 @@ RISC-V Encodings.
 """
 from __future__ import absolute_import
 from base import instructions as base
-from base.immediates import intcc
+from base.immediates import intcc, floatcc, ordering, atomic_rmw_op
+from base.types import f32, f64
 from .defs import RV32, RV64
 from .recipes import OPIMM, OPIMM32, OP, OP32, LUI, BRANCH, JALR, JAL
-from .recipes import LOAD, STORE
+from .recipes import LOAD, STORE, MISCMEM, AMO, SYSTEM
+from .recipes import LOADFP, STOREFP, OPFP, OPFPU
 from .recipes import R, Rshamt, Ricmp, Ii, Iz, Iicmp, Iret, Icall, Icopy
 from .recipes import U, UJ, UJcall, SB, SBzero, GPsp, GPfi, Irmov
-from .settings import use_m
+from .recipes import Iald, Sast, Ramo, Ifence, Itrap
+from .recipes import CR, CI, CA, CB, CJ
+from .recipes import Crr, Crcopy, Crrmov, Crret, Crcall, Ci, Cli, Ca, Cbi
+from .recipes import Cbz, Cj
+from .recipes import Rf, Rfu, Rfi, Rif, Rfsgnj, Rfrmov, Rfcmp, FPsp, FPfi
+from .settings import use_m, use_a, use_f, use_d, use_c
+from . import instructions as riscv
+from .legalize import riscv_expand
 from cdsl.ast import Var
-from base.legalize import narrow, expand
+from base.legalize import narrow, widen, expand
 
-RV32.legalize_monomorphic(expand)
+RV32.legalize_monomorphic(riscv_expand)
 RV32.legalize_type(
         default=narrow,
-        i32=expand,
-        f32=expand,
-        f64=expand)
+        i16=widen,
+        b1=expand,
+        i32=riscv_expand,
+        f32=riscv_expand,
+        f64=riscv_expand)
 
-RV64.legalize_monomorphic(expand)
+RV64.legalize_monomorphic(riscv_expand)
 RV64.legalize_type(
         default=narrow,
-        i32=expand,
-        i64=expand,
-        f32=expand,
-        f64=expand)
+        i16=widen,
+        b1=expand,
+        i32=riscv_expand,
+        i64=riscv_expand,
+        f32=riscv_expand,
+        f64=riscv_expand)
 
 # Dummies for instruction predicates.
 x = Var('x')
 y = Var('y')
 dest = Var('dest')
 args = Var('args')
+order = Var('order')
+flags = Var('flags')
+p = Var('p')
 
 # Basic arithmetic binary instructions are encoded in an R-type instruction.
 for inst,           inst_imm,      f3,    f7 in [
@@ -102,6 +118,26 @@ RV32.enc(base.imul.i32, R, OP(0b000, 0b0000001), isap=use_m)
 RV64.enc(base.imul.i64, R, OP(0b000, 0b0000001), isap=use_m)
 RV64.enc(base.imul.i32, R, OP32(0b000, 0b0000001), isap=use_m)
 
+# There are no 'w' variants of the high multiplications.
+for inst,             f3 in [
+        (base.smulhi, 0b001),
+        (base.umulhi, 0b011)
+        ]:
+    RV32.enc(inst.i32, R, OP(f3, 0b0000001), isap=use_m)
+    RV64.enc(inst.i64, R, OP(f3, 0b0000001), isap=use_m)
+
+# The trapping `sdiv`, `udiv`, `srem`, and `urem` instructions are legalized
+# into explicit checks and these non-trapping instructions.
+for inst,              f3 in [
+        (riscv.div,  0b100),
+        (riscv.divu, 0b101),
+        (riscv.rem,  0b110),
+        (riscv.remu, 0b111)
+        ]:
+    RV32.enc(inst.i32, R, OP(f3, 0b0000001), isap=use_m)
+    RV64.enc(inst.i64, R, OP(f3, 0b0000001), isap=use_m)
+    RV64.enc(inst.i32, R, OP32(f3, 0b0000001), isap=use_m)
+
 # Control flow.
 
 # Unconditional branches.
@@ -131,6 +167,10 @@ for inst,           f3 in [
     RV32.enc(inst.b1, SBzero, BRANCH(f3))
     RV64.enc(inst.b1, SBzero, BRANCH(f3))
 
+# Traps.
+RV32.enc(base.trap, Itrap, SYSTEM())
+RV64.enc(base.trap, Itrap, SYSTEM())
+
 # Returns are a special case of JALR using %x1 to hold the return address.
 # The return address is provided by a special-purpose `link` return value that
 # is added by legalize_signature().
@@ -147,6 +187,41 @@ RV32.enc(base.fill.i32, GPfi, LOAD(0b010))
 RV64.enc(base.fill.i32, GPfi, LOAD(0b010))
 RV64.enc(base.fill.i64, GPfi, LOAD(0b011))
 
+# Relaxed atomic loads and stores. The legalizer adds fences for the stronger
+# orderings.
+RV32.enc(base.atomic_load.i32.i32(ordering.relaxed, flags, p), Iald,
+         LOAD(0b010))
+RV64.enc(base.atomic_load.i32.i64(ordering.relaxed, flags, p), Iald,
+         LOAD(0b010))
+RV64.enc(base.atomic_load.i64.i64(ordering.relaxed, flags, p), Iald,
+         LOAD(0b011))
+RV32.enc(base.atomic_store.i32.i32(ordering.relaxed, flags, x, p), Sast,
+         STORE(0b010))
+RV64.enc(base.atomic_store.i32.i64(ordering.relaxed, flags, x, p), Sast,
+         STORE(0b010))
+RV64.enc(base.atomic_store.i64.i64(ordering.relaxed, flags, x, p), Sast,
+         STORE(0b011))
+
+RV32.enc(base.fence, Ifence, MISCMEM(0b000))
+RV64.enc(base.fence, Ifence, MISCMEM(0b000))
+
+# "A" Standard Extension for Atomic Instructions.
+# Gated by the `use_a` flag. There is no AMO for subtraction or compare and
+# swap.
+for op,                   f5 in [
+        (atomic_rmw_op.iadd, 0b00000),
+        (atomic_rmw_op.xchg, 0b00001),
+        (atomic_rmw_op.bxor, 0b00100),
+        (atomic_rmw_op.bor,  0b01000),
+        (atomic_rmw_op.band, 0b01100)
+        ]:
+    RV32.enc(base.atomic_rmw.i32.i32(op, order, flags, p, x), Ramo,
+             AMO(0b010, f5), isap=use_a)
+    RV64.enc(base.atomic_rmw.i32.i64(op, order, flags, p, x), Ramo,
+             AMO(0b010, f5), isap=use_a)
+    RV64.enc(base.atomic_rmw.i64.i64(op, order, flags, p, x), Ramo,
+             AMO(0b011, f5), isap=use_a)
+
 # Register copies.
 RV32.enc(base.copy.i32, Icopy, OPIMM(0b000))
 RV64.enc(base.copy.i64, Icopy, OPIMM(0b000))
@@ -160,3 +235,146 @@ RV32.enc(base.copy.b1, Icopy, OPIMM(0b000))
 RV64.enc(base.copy.b1, Icopy, OPIMM(0b000))
 RV32.enc(base.regmove.b1, Irmov, OPIMM(0b000))
 RV64.enc(base.regmove.b1, Irmov, OPIMM(0b000))
+
+# "F" and "D" Standard Extensions for Single and Double-Precision Floating
+# Point. Gated by the `use_f` and `use_d` flags. The arithmetic instructions
+# use the round-to-nearest-even rounding mode. The `fmin` and `fmax`
+# instructions have different NaN semantics, so they become library calls. The
+# conversions to integers don't trap, so they are not encoded yet.
+for ty,  fmt, isap in [
+        (f32, 0b0, use_f),
+        (f64, 0b1, use_d)
+        ]:
+    for inst,           f7 in [
+            (base.fadd, 0b0000000),
+            (base.fsub, 0b0000100),
+            (base.fmul, 0b0001000),
+            (base.fdiv, 0b0001100)
+            ]:
+        RV32.enc(inst.bind(ty), Rf, OPFP(0b000, f7 | fmt), isap=isap)
+        RV64.enc(inst.bind(ty), Rf, OPFP(0b000, f7 | fmt), isap=isap)
+
+    RV32.enc(base.sqrt.bind(ty), Rfu, OPFPU(0b0101100 | fmt), isap=isap)
+    RV64.enc(base.sqrt.bind(ty), Rfu, OPFPU(0b0101100 | fmt), isap=isap)
+
+    # Sign injection: fsgnj, fsgnjn, and fsgnjx.
+    RV32.enc(base.fcopysign.bind(ty), Rf, OPFP(0b000, 0b0010000 | fmt),
+             isap=isap)
+    RV64.enc(base.fcopysign.bind(ty), Rf, OPFP(0b000, 0b0010000 | fmt),
+             isap=isap)
+    for inst,        f3 in [
+            (base.copy, 0b000),
+            (base.fneg, 0b001),
+            (base.fabs, 0b010)
+            ]:
+        RV32.enc(inst.bind(ty), Rfsgnj, OPFP(f3, 0b0010000 | fmt), isap=isap)
+        RV64.enc(inst.bind(ty), Rfsgnj, OPFP(f3, 0b0010000 | fmt), isap=isap)
+    RV32.enc(base.regmove.bind(ty), Rfrmov, OPFP(0b000, 0b0010000 | fmt),
+             isap=isap)
+    RV64.enc(base.regmove.bind(ty), Rfrmov, OPFP(0b000, 0b0010000 | fmt),
+             isap=isap)
+
+    # Comparisons: feq, flt, and fle.
+    for cond,        f3 in [
+            (floatcc.eq, 0b010),
+            (floatcc.lt, 0b001),
+            (floatcc.le, 0b000)
+            ]:
+        RV32.enc(base.fcmp.bind(ty)(cond, x, y), Rfcmp,
+                 OPFP(f3, 0b1010000 | fmt), isap=isap)
+        RV64.enc(base.fcmp.bind(ty)(cond, x, y), Rfcmp,
+                 OPFP(f3, 0b1010000 | fmt), isap=isap)
+
+    # Conversions from integers: fcvt.s.w, fcvt.s.wu, fcvt.s.l, and
+    # fcvt.s.lu.
+    RV32.enc(base.fcvt_from_sint.bind(ty).i32, Rfi, OPFPU(0b1101000 | fmt, 0),
+             isap=isap)
+    RV32.enc(base.fcvt_from_uint.bind(ty).i32, Rfi, OPFPU(0b1101000 | fmt, 1),
+             isap=isap)
+    RV64.enc(base.fcvt_from_sint.bind(ty).i32, Rfi, OPFPU(0b1101000 | fmt, 0),
+             isap=isap)
+    RV64.enc(base.fcvt_from_uint.bind(ty).i32, Rfi, OPFPU(0b1101000 | fmt, 1),
+             isap=isap)
+    RV64.enc(base.fcvt_from_sint.bind(ty).i64, Rfi, OPFPU(0b1101000 | fmt, 2),
+             isap=isap)
+    RV64.enc(base.fcvt_from_uint.bind(ty).i64, Rfi, OPFPU(0b1101000 | fmt, 3),
+             isap=isap)
+
+# Conversions between single and double precision: fcvt.d.s and fcvt.s.d.
+RV32.enc(base.fpromote.f64.f32, Rfu, OPFPU(0b0100001, 0), isap=use_d)
+RV64.enc(base.fpromote.f64.f32, Rfu, OPFPU(0b0100001, 0), isap=use_d)
+RV32.enc(base.fdemote.f32.f64, Rfu, OPFPU(0b0100000, 1), isap=use_d)
+RV64.enc(base.fdemote.f32.f64, Rfu, OPFPU(0b0100000, 1), isap=use_d)
+
+# Moves between integer and float registers: fmv.w.x, fmv.x.w, fmv.d.x, and
+# fmv.x.d. The 64-bit moves only exist in RV64.
+RV32.enc(base.bitcast.f32.i32, Rfi, OPFPU(0b1111000), isap=use_f)
+RV64.enc(base.bitcast.f32.i32, Rfi, OPFPU(0b1111000), isap=use_f)
+RV32.enc(base.bitcast.i32.f32, Rif, OPFPU(0b1110000), isap=use_f)
+RV64.enc(base.bitcast.i32.f32, Rif, OPFPU(0b1110000), isap=use_f)
+RV64.enc(base.bitcast.f64.i64, Rfi, OPFPU(0b1111001), isap=use_d)
+RV64.enc(base.bitcast.i64.f64, Rif, OPFPU(0b1110001), isap=use_d)
+
+# Spill and fill: fsw, fsd, flw, and fld.
+RV32.enc(base.spill.f32, FPsp, STOREFP(0b010), isap=use_f)
+RV64.enc(base.spill.f32, FPsp, STOREFP(0b010), isap=use_f)
+RV32.enc(base.spill.f64, FPsp, STOREFP(0b011), isap=use_d)
+RV64.enc(base.spill.f64, FPsp, STOREFP(0b011), isap=use_d)
+RV32.enc(base.fill.f32, FPfi, LOADFP(0b010), isap=use_f)
+RV64.enc(base.fill.f32, FPfi, LOADFP(0b010), isap=use_f)
+RV32.enc(base.fill.f64, FPfi, LOADFP(0b011), isap=use_d)
+RV64.enc(base.fill.f64, FPfi, LOADFP(0b011), isap=use_d)
+
+# "C" Standard Extension for Compressed Instructions.
+# Gated by the `use_c` flag. These encodings must come after the full-size
+# encodings above so they are only selected by instruction shrinking and branch
+# relaxation.
+RV32.enc(base.iadd.i32, Crr, CR(0b1001, 0b10), isap=use_c)
+RV64.enc(base.iadd.i64, Crr, CR(0b1001, 0b10), isap=use_c)
+RV32.enc(base.iadd_imm.i32, Ci, CI(0b000, 0b01), isap=use_c)
+RV64.enc(base.iadd_imm.i64, Ci, CI(0b000, 0b01), isap=use_c)
+# In RV32, the `c.addiw` encoding is `c.jal`.
+RV64.enc(base.iadd_imm.i32, Ci, CI(0b001, 0b01), isap=use_c)
+RV32.enc(base.iconst.i32, Cli, CI(0b010, 0b01), isap=use_c)
+RV64.enc(base.iconst.i32, Cli, CI(0b010, 0b01), isap=use_c)
+RV64.enc(base.iconst.i64, Cli, CI(0b010, 0b01), isap=use_c)
+
+for inst,           f2 in [
+        (base.isub, 0b00),
+        (base.bxor, 0b01),
+        (base.bor,  0b10),
+        (base.band, 0b11)
+        ]:
+    RV32.enc(inst.i32, Ca, CA(0b100011, f2, 0b01), isap=use_c)
+    RV64.enc(inst.i64, Ca, CA(0b100011, f2, 0b01), isap=use_c)
+RV64.enc(base.isub.i32, Ca, CA(0b100111, 0b00, 0b01), isap=use_c)
+RV64.enc(base.iadd.i32, Ca, CA(0b100111, 0b01, 0b01), isap=use_c)
+
+RV32.enc(base.band_imm.i32, Cbi, CB(0b100, 0b01, funct2=0b10), isap=use_c)
+RV64.enc(base.band_imm.i64, Cbi, CB(0b100, 0b01, funct2=0b10), isap=use_c)
+
+RV32.enc(base.jump, Cj, CJ(0b101, 0b01), isap=use_c)
+RV64.enc(base.jump, Cj, CJ(0b101, 0b01), isap=use_c)
+
+for inst,           f3 in [
+        (base.brz,  0b110),
+        (base.brnz, 0b111)
+        ]:
+    RV32.enc(inst.i32, Cbz, CB(f3, 0b01), isap=use_c)
+    RV64.enc(inst.i64, Cbz, CB(f3, 0b01), isap=use_c)
+    RV32.enc(inst.b1, Cbz, CB(f3, 0b01), isap=use_c)
+    RV64.enc(inst.b1, Cbz, CB(f3, 0b01), isap=use_c)
+
+RV32.enc(base.x_return, Crret, CR(0b1000, 0b10), isap=use_c)
+RV64.enc(base.x_return, Crret, CR(0b1000, 0b10), isap=use_c)
+RV32.enc(base.call_indirect.i32, Crcall, CR(0b1001, 0b10), isap=use_c)
+RV64.enc(base.call_indirect.i64, Crcall, CR(0b1001, 0b10), isap=use_c)
+
+RV32.enc(base.copy.i32, Crcopy, CR(0b1000, 0b10), isap=use_c)
+RV64.enc(base.copy.i64, Crcopy, CR(0b1000, 0b10), isap=use_c)
+RV32.enc(base.copy.b1, Crcopy, CR(0b1000, 0b10), isap=use_c)
+RV64.enc(base.copy.b1, Crcopy, CR(0b1000, 0b10), isap=use_c)
+RV32.enc(base.regmove.i32, Crrmov, CR(0b1000, 0b10), isap=use_c)
+RV64.enc(base.regmove.i64, Crrmov, CR(0b1000, 0b10), isap=use_c)
+RV32.enc(base.regmove.b1, Crrmov, CR(0b1000, 0b10), isap=use_c)
+RV64.enc(base.regmove.b1, Crrmov, CR(0b1000, 0b10), isap=use_c)
@@ -0,0 +1,58 @@
+"""
+Supplementary instruction definitions for RISC-V.
+
+This module defines additional instructions that are useful only to the RISC-V
+target ISA.
+"""
+
+from cdsl.operands import Operand
+from cdsl.typevar import TypeVar
+from cdsl.instructions import Instruction, InstructionGroup
+
+
+GROUP = InstructionGroup("riscv", "RISC-V-specific instruction set")
+
+iWord = TypeVar('iWord', 'A scalar integer machine word', ints=(32, 64))
+
+x = Operand('x', iWord, doc='Dividend')
+y = Operand('y', iWord, doc='Divisor')
+a = Operand('a', iWord)
+
+div = Instruction(
+        'riscv_div', r"""
+        Signed integer division with RISC-V semantics.
+
+        Unlike :inst:`sdiv`, this instruction never traps. Division by zero
+        returns -1, and dividing the smallest signed integer by -1 returns the
+        dividend unchanged.
+        """,
+        ins=(x, y), outs=a)
+
+divu = Instruction(
+        'riscv_divu', r"""
+        Unsigned integer division with RISC-V semantics.
+
+        Unlike :inst:`udiv`, this instruction never traps. Division by zero
+        returns the all-ones value.
+        """,
+        ins=(x, y), outs=a)
+
+rem = Instruction(
+        'riscv_rem', r"""
+        Signed integer remainder with RISC-V semantics.
+
+        Unlike :inst:`srem`, this instruction never traps. The remainder of a
+        division by zero is the dividend.
+        """,
+        ins=(x, y), outs=a)
+
+remu = Instruction(
+        'riscv_remu', r"""
+        Unsigned integer remainder with RISC-V semantics.
+
+        Unlike :inst:`urem`, this instruction never traps. The remainder of a
+        division by zero is the dividend.
+        """,
+        ins=(x, y), outs=a)
+
+GROUP.close()
@@ -0,0 +1,47 @@
+"""
+Custom legalization patterns for RISC-V.
+"""
+from __future__ import absolute_import
+from cdsl.ast import Var
+from cdsl.xform import Rtl, XFormGroup
+from base.immediates import floatcc
+from base import legalize as shared
+from base import instructions as insts
+from .defs import ISA
+
+riscv_expand = XFormGroup(
+        'riscv_expand',
+        """
+        Legalize instructions by expansion.
+
+        Use RISC-V-specific instructions if needed.
+        """,
+        isa=ISA, chain=shared.expand)
+
+a = Var('a')
+x = Var('x')
+y = Var('y')
+
+#
+# Division and remainder.
+#
+# The "M" extension division instructions never trap, so the trapping
+# conditions are checked explicitly. Without the "M" extension, the division
+# becomes a library call after the checks.
+riscv_expand.custom_legalize(insts.sdiv, 'expand_divrem')
+riscv_expand.custom_legalize(insts.srem, 'expand_divrem')
+riscv_expand.custom_legalize(insts.udiv, 'expand_divrem')
+riscv_expand.custom_legalize(insts.urem, 'expand_divrem')
+
+# Floating point comparisons.
+#
+# The `feq`, `flt`, and `fle` instructions implement the `eq`, `lt`, and `le`
+# condition codes directly. The `gt` and `ge` codes swap the operands.
+for cc,              rev_cc in [
+        (floatcc.gt, floatcc.lt),
+        (floatcc.ge, floatcc.le)]:
+    riscv_expand.legalize(
+            a << insts.fcmp(cc, x, y),
+            Rtl(
+                a << insts.fcmp(rev_cc, y, x)
+            ))
@@ -13,9 +13,11 @@ from cdsl.isa import EncRecipe
 from cdsl.predicates import IsSignedInt
 from cdsl.registers import Stack
 from base.formats import Binary, BinaryImm, MultiAry, IntCompare, IntCompareImm
+from base.formats import FloatCompare
 from base.formats import Unary, UnaryImm, BranchIcmp, Branch, Jump
 from base.formats import Call, IndirectCall, RegMove
-from .registers import GPR
+from base.formats import AtomicLoad, AtomicStore, AtomicRmw, Fence, Trap
+from .registers import GPR, GPRC, FPR
 
 # The low 7 bits of a RISC-V instruction is the base opcode. All 32-bit
 # instructions have 11 as the two low bits, with bits 6:2 determining the base
@@ -80,6 +82,55 @@ def OP32(funct3, funct7):
     return 0b01110 | (funct3 << 5) | (funct7 << 8)
 
 
+def LOADFP(funct3):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b00001 | (funct3 << 5)
+
+
+def STOREFP(funct3):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b01001 | (funct3 << 5)
+
+
+def OPFP(funct3, funct7):
+    # type: (int, int) -> int
+    assert funct3 <= 0b111
+    assert funct7 <= 0b1111111
+    return 0b10100 | (funct3 << 5) | (funct7 << 8)
+
+
+def OPFPU(funct7, rs2=0):
+    # type: (int, int) -> int
+    # Unary OP-FP instructions always use funct3 = 0, which is the
+    # round-to-nearest-even rounding mode where one applies. The rs2 field
+    # takes the place of funct3 in the encbits.
+    assert funct7 <= 0b1111111
+    assert rs2 <= 0b111
+    return 0b10100 | (rs2 << 5) | (funct7 << 8)
+
+
+def MISCMEM(funct3):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b00011 | (funct3 << 5)
+
+
+def AMO(funct3, funct5):
+    # type: (int, int) -> int
+    assert funct3 <= 0b111
+    assert funct5 <= 0b11111
+    # The `aq` and `rl` bits in funct7 are added by the recipe.
+    return 0b01011 | (funct3 << 5) | (funct5 << 10)
+
+
+def SYSTEM(funct3=0):
+    # type: (int) -> int
+    assert funct3 <= 0b111
+    return 0b11100 | (funct3 << 5)
+
+
 def AIUPC():
     # type: () -> int
     return 0b00101
@@ -90,6 +141,48 @@ def LUI():
     return 0b01101
 
 
+# The 16-bit compressed instructions from the "C" extension have one of the
+# three quadrants 00, 01, 10 in their two low bits. Their encbits are the
+# instruction itself with the register and immediate fields set to zero.
+
+
+def CR(funct4, op):
+    # type: (int, int) -> int
+    assert funct4 <= 0b1111
+    assert op <= 0b10
+    return (funct4 << 12) | op
+
+
+def CI(funct3, op):
+    # type: (int, int) -> int
+    assert funct3 <= 0b111
+    assert op <= 0b10
+    return (funct3 << 13) | op
+
+
+def CA(funct6, funct2, op):
+    # type: (int, int, int) -> int
+    assert funct6 <= 0b111111
+    assert funct2 <= 0b11
+    assert op <= 0b10
+    return (funct6 << 10) | (funct2 << 5) | op
+
+
+def CB(funct3, op, funct2=0):
+    # type: (int, int, int) -> int
+    assert funct3 <= 0b111
+    assert funct2 <= 0b11
+    assert op <= 0b10
+    return (funct3 << 13) | (funct2 << 10) | op
+
+
+def CJ(funct3, op):
+    # type: (int, int) -> int
+    assert funct3 <= 0b111
+    assert op <= 0b10
+    return (funct3 << 13) | op
+
+
 # R-type 32-bit instructions: These are mostly binary arithmetic instructions.
 # The encbits are `opcode[6:2] | (funct3 << 5) | (funct7 << 8)
 R = EncRecipe(
@@ -212,6 +305,152 @@ SBzero = EncRecipe(
         put_sb(bits, disp, in_reg0, 0, sink);
         ''')
 
+# I-type load with no offset.
+Iald = EncRecipe(
+        'Iald', AtomicLoad, size=4, ins=GPR, outs=GPR,
+        emit='put_i(bits, in_reg0, 0, out_reg0, sink);')
+
+# S-type store with no offset.
+Sast = EncRecipe(
+        'Sast', AtomicStore, size=4, ins=(GPR, GPR), outs=(),
+        emit='put_s(bits, 0, in_reg1, in_reg0, sink);')
+
+# R-type atomic memory operation with the ordering in the `aq` and `rl` bits.
+Ramo = EncRecipe(
+        'Ramo', AtomicRmw, size=4, ins=(GPR, GPR), outs=GPR,
+        emit='''
+        put_r(
+            amo_bits(bits, ordering),
+            in_reg0,
+            in_reg1,
+            out_reg0,
+            sink,
+        );
+        ''')
+
+# I-type encoding of a fence with the predecessor and successor sets in the
+# immediate.
+Ifence = EncRecipe(
+        'Ifence', Fence, size=4, ins=(), outs=(),
+        emit='put_i(bits, 0, fence_imm(ordering), 0, sink);')
+
+# Traps are an `ebreak` instruction, which is an I-type SYSTEM instruction with
+# an immediate of 1.
+Itrap = EncRecipe(
+        'Itrap', Trap, size=4, ins=(), outs=(),
+        emit='''
+        sink.trap(code, func.srclocs[inst]);
+        put_i(bits, 0, 1, 0, sink);
+        ''')
+
+# R-type floating point arithmetic.
+Rf = EncRecipe(
+        'Rf', Binary, size=4, ins=(FPR, FPR), outs=FPR,
+        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# R-type unary floating point instruction with a fixed rs2 field.
+Rfu = EncRecipe(
+        'Rfu', Unary, size=4, ins=FPR, outs=FPR,
+        emit='put_rfu(bits, in_reg0, out_reg0, sink);')
+
+# R-type conversion from an integer register to a float register.
+Rfi = EncRecipe(
+        'Rfi', Unary, size=4, ins=GPR, outs=FPR,
+        emit='put_rfu(bits, in_reg0, out_reg0, sink);')
+
+# R-type conversion from a float register to an integer register.
+Rif = EncRecipe(
+        'Rif', Unary, size=4, ins=FPR, outs=GPR,
+        emit='put_rfu(bits, in_reg0, out_reg0, sink);')
+
+# R-type sign injection with rs1 = rs2. This implements copies, `fneg`, and
+# `fabs`.
+Rfsgnj = EncRecipe(
+        'Rfsgnj', Unary, size=4, ins=FPR, outs=FPR,
+        emit='put_r(bits, in_reg0, in_reg0, out_reg0, sink);')
+
+# Same for an FPR regmove.
+Rfrmov = EncRecipe(
+        'Rfrmov', RegMove, size=4, ins=FPR, outs=(),
+        emit='put_r(bits, src, src, dst, sink);')
+
+# R-type encoding of a floating point comparison.
+Rfcmp = EncRecipe(
+        'Rfcmp', FloatCompare, size=4, ins=(FPR, FPR), outs=GPR,
+        emit='put_r(bits, in_reg0, in_reg1, out_reg0, sink);')
+
+# Compressed instructions from the "C" extension. These recipes are never
+# selected by the legalizer because the full-size encodings come first. After
+# register allocation, instruction shrinking picks them when the allocated
+# registers and the immediates fit.
+
+# CR-type binary instruction with a tied rd/rs1 operand.
+Crr = EncRecipe(
+        'Crr', Binary, size=2, ins=(GPR, GPR), outs=0,
+        emit='put_cr(bits, in_reg0, in_reg1, sink);')
+
+# CR-type register copy, `c.mv rd, rs2`.
+Crcopy = EncRecipe(
+        'Crcopy', Unary, size=2, ins=GPR, outs=GPR,
+        emit='put_cr(bits, out_reg0, in_reg0, sink);')
+
+# Same for a GPR regmove.
+Crrmov = EncRecipe(
+        'Crrmov', RegMove, size=2, ins=GPR, outs=(),
+        emit='put_cr(bits, dst, src, sink);')
+
+# CR-type `c.jr %x1` return.
+Crret = EncRecipe(
+        'Crret', MultiAry, size=2, ins=(), outs=(),
+        emit='put_cr(bits, 1, 0, sink);')
+
+# CR-type `c.jalr` indirect call with an implicit %x1 link register.
+Crcall = EncRecipe(
+        'Crcall', IndirectCall, size=2, ins=GPR, outs=(),
+        emit='put_cr(bits, in_reg0, 0, sink);')
+
+# CI-type instruction with a tied rd/rs1 operand and a 6-bit immediate.
+Ci = EncRecipe(
+        'Ci', BinaryImm, size=2, ins=GPR, outs=0,
+        instp=IsSignedInt(BinaryImm.imm, 6),
+        emit='put_ci(bits, in_reg0, imm.into(), sink);')
+
+# CI-type `c.li` materializing a 6-bit constant.
+Cli = EncRecipe(
+        'Cli', UnaryImm, size=2, ins=(), outs=GPR,
+        instp=IsSignedInt(UnaryImm.imm, 6),
+        emit='put_ci(bits, out_reg0, imm.into(), sink);')
+
+# CA-type binary instruction with a tied rd'/rs1' operand.
+Ca = EncRecipe(
+        'Ca', Binary, size=2, ins=(GPRC, GPRC), outs=0,
+        emit='put_ca(bits, in_reg0, in_reg1, sink);')
+
+# CB-type instruction with a tied rd'/rs1' operand and a 6-bit immediate.
+Cbi = EncRecipe(
+        'Cbi', BinaryImm, size=2, ins=GPRC, outs=0,
+        instp=IsSignedInt(BinaryImm.imm, 6),
+        emit='put_cbi(bits, in_reg0, imm.into(), sink);')
+
+# CB-type branch comparing rs1' with zero.
+Cbz = EncRecipe(
+        'Cbz', Branch, size=2, ins=GPRC, outs=(),
+        branch_range=(0, 9),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_cb(bits, disp, in_reg0, sink);
+        ''')
+
+# CJ-type unconditional branch.
+Cj = EncRecipe(
+        'Cj', Jump, size=2, ins=(), outs=(), branch_range=(0, 12),
+        emit='''
+        let dest = i64::from(func.offsets[destination]);
+        let disp = dest - i64::from(sink.offset());
+        put_cj(bits, disp, sink);
+        ''')
+
 # Spill of a GPR.
 GPsp = EncRecipe(
         'GPsp', Unary, size=4,
@@ -223,3 +462,15 @@ GPfi = EncRecipe(
         'GPfi', Unary, size=4,
         ins=Stack(GPR), outs=GPR,
         emit='unimplemented!();')
+
+# Spill of an FPR.
+FPsp = EncRecipe(
+        'FPsp', Unary, size=4,
+        ins=FPR, outs=Stack(FPR),
+        emit='unimplemented!();')
+
+# Fill of an FPR.
+FPfi = EncRecipe(
+        'FPfi', Unary, size=4,
+        ins=Stack(FPR), outs=FPR,
+        emit='unimplemented!();')
@@ -20,4 +20,8 @@ FloatRegs = RegBank(
 GPR = RegClass(IntRegs)
 FPR = RegClass(FloatRegs)
 
+# The registers %x8-%x15 that can be addressed by the 3-bit register fields of
+# the compressed instructions.
+GPRC = GPR[8:16]
+
 RegClass.extract_names(globals())
@@ -0,0 +1,374 @@
+//! Alias analysis.
+//!
+//! This module answers simple questions about which memory is accessed by loads, stores, and
+//! other instructions, and whether two instructions can access the same memory.
+//!
+//! The memory accessed through an address is classified into categories by following the address
+//! computation back to the instruction that produced the base pointer:
+//!
+//! - `heap_addr` addresses point into a heap. Distinct heaps never overlap.
+//! - `table_addr` addresses point into a table. Distinct tables never overlap.
+//! - `stack_addr` addresses and the `stack_load` and `stack_store` instructions access a stack
+//!   slot. Distinct stack slots don't overlap, except for the slots in the argument areas.
+//! - Global variable addresses point to the global variable, or into a heap for variables living
+//!   at a fixed heap offset.
+//!
+//! Memory in different categories never overlaps. Addresses that can't be traced to one of the
+//! above sources, like function arguments, can point anywhere.
+//!
+//! Loads with the `readonly` flag read memory that is never written, so they don't conflict with
+//! any other instruction.
+
+use ir::dfg::ValueDef;
+use ir::{Function, GlobalVar, GlobalVarData, Heap, Inst, InstructionData, Opcode, StackSlot,
+         StackSlotKind, Table, Value};
+
+/// A category of memory that doesn't overlap with other categories.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum MemoryCategory {
+    /// Memory in a heap.
+    Heap(Heap),
+    /// Memory in a table.
+    Table(Table),
+    /// Memory in a stack slot.
+    StackSlot(StackSlot),
+    /// The memory holding a global variable.
+    Global(GlobalVar),
+    /// Memory that could be anywhere.
+    Unknown,
+}
+
+/// Description of the memory accessed by an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MemoryAccess {
+    /// The category of the accessed memory.
+    pub category: MemoryCategory,
+    /// The address operand, if the instruction has one.
+    pub base: Option<Value>,
+    /// Byte offset of the access from `base`, or from the start of the stack slot.
+    pub offset: i64,
+    /// Number of bytes accessed, if known.
+    pub size: Option<u32>,
+    /// Does the instruction read memory?
+    pub reads: bool,
+    /// Does the instruction write memory?
+    pub writes: bool,
+    /// Is the accessed memory known to be read-only?
+    pub readonly: bool,
+}
+
+impl MemoryAccess {
+    /// Is this a read of memory that is never written?
+    pub fn is_readonly_load(&self) -> bool {
+        self.readonly && !self.writes
+    }
+}
+
+/// Get the category of the memory pointed to by `addr`.
+///
+/// The address computation is followed through additions of integers to the pointer.
+pub fn address_category(func: &Function, addr: Value) -> MemoryCategory {
+    let inst = match func.dfg.value_def(func.dfg.resolve_aliases(addr)) {
+        ValueDef::Result(inst, _) => inst,
+        ValueDef::Param(..) => return MemoryCategory::Unknown,
+    };
+    match func.dfg[inst] {
+        InstructionData::HeapAddr { heap, .. } => MemoryCategory::Heap(heap),
+        InstructionData::TableAddr { table, .. } => MemoryCategory::Table(table),
+        InstructionData::StackLoad {
+            opcode: Opcode::StackAddr,
+            stack_slot,
+            ..
+        } => MemoryCategory::StackSlot(stack_slot),
+        InstructionData::UnaryGlobalVar { global_var, .. } => global_category(func, global_var),
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            ..
+        } |
+        InstructionData::Unary {
+            opcode: Opcode::Copy,
+            arg,
+        } => address_category(func, arg),
+        InstructionData::Binary {
+            opcode: Opcode::Iadd,
+            args,
+        } => {
+            // One of the operands is the pointer. The other one is an integer offset which can't
+            // be traced to a memory object itself.
+            match (
+                address_category(func, args[0]),
+                address_category(func, args[1]),
+            ) {
+                (MemoryCategory::Unknown, cat) |
+                (cat, MemoryCategory::Unknown) => cat,
+                _ => MemoryCategory::Unknown,
+            }
+        }
+        _ => MemoryCategory::Unknown,
+    }
+}
+
+/// Get the category of the memory holding the global variable `gv`.
+fn global_category(func: &Function, gv: GlobalVar) -> MemoryCategory {
+    match func.global_vars[gv] {
+        GlobalVarData::HeapBase { heap, .. } => MemoryCategory::Heap(heap),
+        _ => MemoryCategory::Global(gv),
+    }
+}
+
+/// Get the memory accessed by `inst`, or `None` if it doesn't access memory.
+///
+/// Calls and other instructions that access memory in unknown ways are reported as reading and
+/// writing `MemoryCategory::Unknown`.
+pub fn memory_access(func: &Function, inst: Inst) -> Option<MemoryAccess> {
+    let opcode = func.dfg[inst].opcode();
+    let (base, offset, readonly) = match func.dfg[inst] {
+        InstructionData::Load {
+            flags, arg, offset, ..
+        } => (Some(arg), offset.into(), flags.readonly()),
+        InstructionData::Store {
+            args, offset, ..
+        } => (Some(args[1]), offset.into(), false),
+        InstructionData::StackLoad {
+            opcode: Opcode::StackLoad,
+            stack_slot,
+            offset,
+        } |
+        InstructionData::StackStore {
+            opcode: Opcode::StackStore,
+            stack_slot,
+            offset,
+            ..
+        } => {
+            return Some(MemoryAccess {
+                category: MemoryCategory::StackSlot(stack_slot),
+                base: None,
+                offset: offset.into(),
+                size: Some(access_size(func, inst)),
+                reads: opcode.can_load(),
+                writes: opcode.can_store(),
+                readonly: false,
+            })
+        }
+        _ => {
+            if !(opcode.can_load() || opcode.can_store() || opcode.is_call()) {
+                return None;
+            }
+            return Some(MemoryAccess {
+                category: MemoryCategory::Unknown,
+                base: None,
+                offset: 0,
+                size: None,
+                reads: opcode.can_load() || opcode.is_call(),
+                writes: opcode.can_store() || opcode.is_call(),
+                readonly: false,
+            });
+        }
+    };
+
+    Some(MemoryAccess {
+        category: base.map_or(MemoryCategory::Unknown, |b| address_category(func, b)),
+        base: base.map(|b| func.dfg.resolve_aliases(b)),
+        offset,
+        size: Some(access_size(func, inst)),
+        reads: opcode.can_load(),
+        writes: opcode.can_store(),
+        readonly,
+    })
+}
+
+/// Get the number of bytes accessed by the load or store `inst`.
+fn access_size(func: &Function, inst: Inst) -> u32 {
+    match func.dfg[inst].opcode() {
+        Opcode::Uload8 | Opcode::Sload8 | Opcode::Istore8 => 1,
+        Opcode::Uload16 | Opcode::Sload16 | Opcode::Istore16 => 2,
+        Opcode::Uload32 | Opcode::Sload32 | Opcode::Istore32 => 4,
+        _ => func.dfg.ctrl_typevar(inst).bytes(),
+    }
+}
+
+/// Can memory in the categories `a` and `b` overlap?
+pub fn categories_may_overlap(func: &Function, a: MemoryCategory, b: MemoryCategory) -> bool {
+    use self::MemoryCategory::*;
+    match (a, b) {
+        (Unknown, _) | (_, Unknown) => true,
+        (Heap(x), Heap(y)) => x == y,
+        (Table(x), Table(y)) => x == y,
+        (StackSlot(x), StackSlot(y)) => x == y || (is_arg_slot(func, x) && is_arg_slot(func, y)),
+        (Global(x), Global(y)) => x == y || !distinct_symbols(func, x, y),
+        _ => false,
+    }
+}
+
+/// Is `ss` a slot in one of the argument areas which may overlap other argument slots?
+fn is_arg_slot(func: &Function, ss: StackSlot) -> bool {
+    match func.stack_slots[ss].kind {
+        StackSlotKind::IncomingArg | StackSlotKind::OutgoingArg => true,
+        _ => false,
+    }
+}
+
+/// Are `x` and `y` global variables identified by different symbol names?
+fn distinct_symbols(func: &Function, x: GlobalVar, y: GlobalVar) -> bool {
+    match (&func.global_vars[x], &func.global_vars[y]) {
+        (&GlobalVarData::Sym { name: ref a, .. }, &GlobalVarData::Sym { name: ref b, .. }) => {
+            a != b
+        }
+        _ => false,
+    }
+}
+
+/// Can the memory accessed by `a` and `b` overlap?
+pub fn may_overlap(func: &Function, a: &MemoryAccess, b: &MemoryAccess) -> bool {
+    if !categories_may_overlap(func, a.category, b.category) {
+        return false;
+    }
+
+    // Accesses relative to the same base address, or to the same stack slot, only overlap if
+    // their byte ranges do.
+    let same_base = match (a.base, b.base) {
+        (Some(x), Some(y)) => x == y,
+        (None, None) => a.category != MemoryCategory::Unknown && a.category == b.category,
+        _ => false,
+    };
+    match (a.size, b.size) {
+        (Some(a_size), Some(b_size)) if same_base => {
+            a.offset < b.offset + i64::from(b_size) && b.offset < a.offset + i64::from(a_size)
+        }
+        _ => true,
+    }
+}
+
+/// Can the instructions `a` and `b` access the same memory, with at least one of them writing
+/// it?
+///
+/// If this returns false, the two instructions can be reordered without changing the memory
+/// they read and write.
+pub fn may_conflict(func: &Function, a: Inst, b: Inst) -> bool {
+    let (a, b) = match (memory_access(func, a), memory_access(func, b)) {
+        (Some(a), Some(b)) => (a, b),
+        _ => return false,
+    };
+    // Reads don't conflict with each other, and read-only memory is never written.
+    if (!a.writes && !b.writes) || a.is_readonly_load() || b.is_readonly_load() {
+        return false;
+    }
+    may_overlap(func, &a, &b)
+}
+
+/// Is `inst` a load from read-only memory?
+pub fn is_readonly_load(func: &Function, inst: Inst) -> bool {
+    memory_access(func, inst).map_or(false, |access| access.is_readonly_load())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cursor::{Cursor, FuncCursor};
+    use ir::types::{I32, I64};
+    use ir::{ExternalName, HeapBase, HeapData, HeapStyle, InstBuilder, MemFlags, StackSlotData};
+    use ir::immediates::Imm64;
+
+    fn heap(func: &mut Function) -> Heap {
+        func.create_heap(HeapData {
+            base: HeapBase::ReservedReg,
+            min_size: Imm64::new(0),
+            guard_size: Imm64::new(0x1000),
+            style: HeapStyle::Static { bound: Imm64::new(0x1_0000) },
+        })
+    }
+
+    #[test]
+    fn categories() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let arg = func.dfg.append_ebb_param(ebb0, I64);
+        let heap0 = heap(&mut func);
+        let heap1 = heap(&mut func);
+        let ss0 = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+        let ss1 = func.create_stack_slot(StackSlotData::new(StackSlotKind::ExplicitSlot, 8));
+        let gv0 = func.create_global_var(GlobalVarData::Sym {
+            name: ExternalName::testcase("a"),
+            colocated: false,
+        });
+        let gv1 = func.create_global_var(GlobalVarData::Sym {
+            name: ExternalName::testcase("b"),
+            colocated: false,
+        });
+        let gv2 = func.create_global_var(GlobalVarData::HeapBase {
+            heap: heap1,
+            offset: 16.into(),
+        });
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let idx = pos.ins().iconst(I32, 0);
+        let h0 = pos.ins().heap_addr(I64, heap0, idx, 8);
+        let h0x = pos.ins().iadd_imm(h0, 4);
+        let h1 = pos.ins().heap_addr(I64, heap1, idx, 8);
+        let s0 = pos.ins().stack_addr(I64, ss0, 0);
+        let s1 = pos.ins().stack_addr(I64, ss1, 0);
+        let g0 = pos.ins().global_addr(I64, gv0);
+        let g1 = pos.ins().global_addr(I64, gv1);
+        let g2 = pos.ins().global_addr(I64, gv2);
+        let offset = pos.ins().iconst(I64, 8);
+        let g2x = pos.ins().iadd(offset, g2);
+        let mixed = pos.ins().iadd(h0, s0);
+
+        let func = pos.func;
+        let cat = |v| address_category(func, v);
+        assert_eq!(cat(arg), MemoryCategory::Unknown);
+        assert_eq!(cat(h0), MemoryCategory::Heap(heap0));
+        assert_eq!(cat(h0x), MemoryCategory::Heap(heap0));
+        assert_eq!(cat(s0), MemoryCategory::StackSlot(ss0));
+        assert_eq!(cat(g0), MemoryCategory::Global(gv0));
+        assert_eq!(cat(g2x), MemoryCategory::Heap(heap1));
+        assert_eq!(cat(mixed), MemoryCategory::Unknown);
+
+        let overlap = |a, b| categories_may_overlap(func, cat(a), cat(b));
+        assert!(overlap(h0, h0x));
+        assert!(!overlap(h0, h1));
+        assert!(overlap(h1, g2));
+        assert!(!overlap(s0, s1));
+        assert!(!overlap(h0, s0));
+        assert!(!overlap(g0, g1));
+        assert!(!overlap(g0, s0));
+        assert!(overlap(arg, s0));
+    }
+
+    #[test]
+    fn conflicts() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let p = func.dfg.append_ebb_param(ebb0, I64);
+        let q = func.dfg.append_ebb_param(ebb0, I64);
+        let mut readonly = MemFlags::new();
+        readonly.set_readonly();
+
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+        let v0 = pos.ins().load(I32, MemFlags::new(), p, 0);
+        let v1 = pos.ins().load(I32, MemFlags::new(), p, 4);
+        let v2 = pos.ins().load(I32, readonly, q, 0);
+        let st4 = pos.ins().store(MemFlags::new(), v0, p, 4);
+        let st5 = pos.ins().istore8(MemFlags::new(), v1, p, 5);
+        let stq = pos.ins().store(MemFlags::new(), v2, q, 0);
+
+        let func = pos.func;
+        let def = |v| match func.dfg.value_def(v) {
+            ValueDef::Result(inst, _) => inst,
+            ValueDef::Param(..) => panic!("Expected instruction result"),
+        };
+        let (ld0, ld4, ldro) = (def(v0), def(v1), def(v2));
+        assert!(!may_conflict(func, ld0, ld4));
+        assert!(!may_conflict(func, ld0, st4));
+        assert!(may_conflict(func, ld4, st4));
+        assert!(may_conflict(func, ld4, st5));
+        assert!(may_conflict(func, st4, st5));
+        assert!(may_conflict(func, ld0, stq));
+        assert!(!may_conflict(func, ldro, stq));
+        assert!(is_readonly_load(func, ldro));
+        assert!(!is_readonly_load(func, ld0));
+    }
+}
@@ -0,0 +1,149 @@
+//! Code alignment.
+//!
+//! Emission normally starts every EBB at whatever offset falls out of the preceding code. Some
+//! EBBs benefit from being aligned, in particular loop headers on Intel where the instruction
+//! fetch and the branch predictor work on aligned blocks of code.
+//!
+//! The alignment of an EBB comes from `func.ebb_align` when it is set, or from the `loop_align`
+//! setting for loop headers. Branch relaxation inserts padding in front of the aligned EBBs, and
+//! the emitters fill the padding with no-op instructions. EBB offsets are relative to the start of
+//! the function, so the function itself must be at least as aligned as any of its EBBs.
+//!
+//! Patchable calls align their call displacement relative to the start of the function, so a
+//! function containing them must be at least 4-byte aligned.
+//!
+//! When `opt_level` is `min_size`, the `loop_align` and `function_align` settings are ignored so
+//! no padding is inserted. Explicit alignments in the function are still honored.
+
+use binemit::CodeOffset;
+use entity::EntityMap;
+use ir::{Ebb, Function, Inst, Opcode, ProgramOrder};
+use isa::TargetIsa;
+use settings::OptLevel;
+use std::cmp::Ordering;
+
+/// Round a setting or attribute value up to a valid alignment.
+fn valid_alignment(align: CodeOffset) -> CodeOffset {
+    if align <= 1 {
+        1
+    } else {
+        align.next_power_of_two()
+    }
+}
+
+/// Get the `loop_align` and `function_align` settings of `isa`, unless optimizing for size.
+fn alignment_settings(isa: &TargetIsa) -> (CodeOffset, CodeOffset) {
+    let flags = isa.flags();
+    if flags.opt_level() == OptLevel::MinSize {
+        (0, 0)
+    } else {
+        (
+            CodeOffset::from(flags.loop_align()),
+            CodeOffset::from(flags.function_align()),
+        )
+    }
+}
+
+/// Round `offset` up to a multiple of `align`, which must be a power of two.
+pub fn align_offset(offset: CodeOffset, align: CodeOffset) -> CodeOffset {
+    debug_assert!(align.is_power_of_two());
+    (offset + align - 1) & !(align - 1)
+}
+
+/// Is `ebb` a loop header, i.e. the target of a backwards branch in the layout?
+fn is_loop_header(func: &Function, ebb: Ebb, inst: Inst) -> bool {
+    func.layout.cmp(ebb, inst) == Ordering::Less
+}
+
+/// Compute the alignment of every EBB header in `func` into `align`.
+///
+/// EBBs that need no alignment are mapped to 1. The `align` map is cleared first, so it can be
+/// reused between functions without reallocating.
+pub fn ebb_alignments(func: &Function, isa: &TargetIsa, align: &mut EntityMap<Ebb, CodeOffset>) {
+    align.clear();
+    align.resize(func.dfg.num_ebbs());
+
+    let (loop_align, _) = alignment_settings(isa);
+    if loop_align > 1 {
+        let loop_align = valid_alignment(loop_align);
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let Some(dest) = func.dfg[inst].branch_destination() {
+                    if is_loop_header(func, dest, inst) {
+                        align[dest] = loop_align;
+                    }
+                }
+            }
+        }
+    }
+
+    // Explicit alignments take precedence.
+    for ebb in func.layout.ebbs() {
+        let explicit = func.ebb_align.get(ebb).cloned().unwrap_or(0);
+        if explicit != 0 {
+            align[ebb] = valid_alignment(explicit);
+        }
+    }
+}
+
+/// Get the required alignment of the start of `func`'s code in bytes.
+///
+/// The function alignment is taken from `func.align` or the `function_align` setting, and it is
+/// increased to cover the alignment of all the EBBs in the function. Embedders must place the
+/// code emitted for `func` at an address that is a multiple of this value.
+pub fn function_alignment(func: &Function, isa: &TargetIsa) -> CodeOffset {
+    let (loop_align, function_align) = alignment_settings(isa);
+    let mut align = valid_alignment(func.align.unwrap_or(function_align));
+    if align < 4 && has_patchable_calls(func) {
+        align = 4;
+    }
+
+    // This is the maximum of `ebb_alignments`, computed without allocating the map.
+    let loop_align = valid_alignment(loop_align);
+    for ebb in func.layout.ebbs() {
+        let explicit = func.ebb_align.get(ebb).cloned().unwrap_or(0);
+        if explicit != 0 {
+            align = align.max(valid_alignment(explicit));
+        }
+        if loop_align > align {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let Some(dest) = func.dfg[inst].branch_destination() {
+                    if is_loop_header(func, dest, inst) &&
+                        func.ebb_align.get(dest).cloned().unwrap_or(0) == 0
+                    {
+                        align = loop_align;
+                    }
+                }
+            }
+        }
+    }
+    align
+}
+
+/// Does `func` contain any patchable calls?
+fn has_patchable_calls(func: &Function) -> bool {
+    func.layout.ebbs().any(|ebb| {
+        func.layout.ebb_insts(ebb).any(|inst| {
+            func.dfg[inst].opcode() == Opcode::CallPatchable
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{align_offset, valid_alignment};
+
+    #[test]
+    fn rounding() {
+        assert_eq!(valid_alignment(0), 1);
+        assert_eq!(valid_alignment(1), 1);
+        assert_eq!(valid_alignment(12), 16);
+        assert_eq!(valid_alignment(16), 16);
+
+        assert_eq!(align_offset(0, 16), 0);
+        assert_eq!(align_offset(1, 16), 16);
+        assert_eq!(align_offset(16, 16), 16);
+        assert_eq!(align_offset(17, 4), 20);
+        assert_eq!(align_offset(5, 1), 5);
+    }
+}
@@ -0,0 +1,125 @@
+//! Annotation of disassembled machine code.
+//!
+//! When debugging the code generator, it is useful to know exactly which IR instruction produced
+//! which machine code bytes. An `InstRanges` sink collects the code range of every instruction
+//! while a function is emitted into memory, and `write_annotated_disassembly` interleaves the
+//! output of an external disassembler with the IR instructions.
+
+use binemit::{AnnotationSink, CodeOffset};
+use ir::{Ebb, Function, Inst};
+use isa::TargetIsa;
+use std::fmt::{Result, Write};
+use std::string::String;
+use std::vec::Vec;
+
+/// An `AnnotationSink` that collects the code range of every instruction in emission order.
+#[derive(Default)]
+pub struct InstRanges {
+    /// The emitted instructions and the start and end offsets of their machine code.
+    pub ranges: Vec<(Inst, CodeOffset, CodeOffset)>,
+}
+
+impl InstRanges {
+    /// Create an empty set of instruction ranges.
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl AnnotationSink for InstRanges {
+    fn inst_range(&mut self, inst: Inst, start: CodeOffset, end: CodeOffset) {
+        self.ranges.push((inst, start, end));
+    }
+}
+
+/// Write the output of a disassembler interleaved with the IR instructions of `func`.
+///
+/// The `ranges` are the instruction code ranges collected during emission, and `disasm` contains
+/// the disassembled lines along with the code offset they start at, both in increasing offset
+/// order. Every IR instruction is followed by the lines that fall in its code range. Lines that
+/// don't belong to any instruction, like alignment padding, are written where they occur.
+pub fn write_annotated_disassembly(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    ranges: &[(Inst, CodeOffset, CodeOffset)],
+    disasm: &[(CodeOffset, String)],
+) -> Result {
+    let mut lines = disasm.iter().peekable();
+    let mut cur_ebb: Option<Ebb> = None;
+
+    for &(inst, start, end) in ranges {
+        while let Some(&&(offset, ref text)) = lines.peek() {
+            if offset >= start {
+                break;
+            }
+            writeln!(w, "{:8x}: {}", offset, text)?;
+            lines.next();
+        }
+
+        let ebb = func.layout.inst_ebb(inst);
+        if ebb != cur_ebb {
+            if let Some(ebb) = ebb {
+                writeln!(w, "{}:", ebb)?;
+            }
+            cur_ebb = ebb;
+        }
+        writeln!(w, "    {}", func.dfg.display_inst(inst, isa))?;
+
+        while let Some(&&(offset, ref text)) = lines.peek() {
+            if offset >= end {
+                break;
+            }
+            writeln!(w, "{:8x}:     {}", offset, text)?;
+            lines.next();
+        }
+    }
+
+    for &(offset, ref text) in lines {
+        writeln!(w, "{:8x}: {}", offset, text)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::write_annotated_disassembly;
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder};
+    use ir::types::I32;
+    use std::string::{String, ToString};
+
+    #[test]
+    fn interleave() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let (iconst, ret) = {
+            let mut pos = FuncCursor::new(&mut func);
+            pos.insert_ebb(ebb0);
+            let v0 = pos.ins().iconst(I32, 1);
+            let iconst = pos.func.dfg.value_def(v0).unwrap_inst();
+            let ret = pos.ins().return_(&[]);
+            (iconst, ret)
+        };
+
+        let ranges = [(iconst, 0, 5), (ret, 8, 9)];
+        let disasm = [
+            (0, "mov $0x1,%eax".to_string()),
+            (5, "nop".to_string()),
+            (8, "retq".to_string()),
+        ];
+
+        let mut text = String::new();
+        write_annotated_disassembly(&mut text, &func, None, &ranges, &disasm).unwrap();
+        assert_eq!(
+            text,
+            "ebb0:\n    \
+             v0 = iconst.i32 1\n       \
+             0:     mov $0x1,%eax\n       \
+             5: nop\n    \
+             return\n       \
+             8:     retq\n"
+        );
+    }
+}
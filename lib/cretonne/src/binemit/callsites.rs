@@ -0,0 +1,46 @@
+//! Call-site tables for exception handling.
+//!
+//! A `try_call` instruction is a call with an exceptional edge to a landing pad. When the callee
+//! unwinds, the unwinder looks up the return address in a call-site table to find the landing pad
+//! where execution should continue. The table can be built once branch relaxation has computed
+//! the final EBB offsets.
+
+use binemit::CodeOffset;
+use ir::{Function, InstructionData};
+use isa::TargetIsa;
+use std::vec::Vec;
+
+/// An entry in the call-site table of a function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CallSite {
+    /// Code offset of the call instruction.
+    pub offset: CodeOffset,
+
+    /// Size of the call instruction in bytes. The return address is at `offset + size`.
+    pub size: CodeOffset,
+
+    /// Code offset of the landing pad.
+    pub landing_pad: CodeOffset,
+}
+
+/// Collect the call sites of all the `try_call` instructions in `func`.
+///
+/// Branch relaxation must have run so the EBB offsets are valid. The call sites are appended to
+/// `out` in order of increasing code offset.
+pub fn call_site_table(func: &Function, isa: &TargetIsa, out: &mut Vec<CallSite>) {
+    let encinfo = isa.encoding_info();
+    for ebb in func.layout.ebbs() {
+        let mut offset = func.offsets[ebb];
+        for inst in func.layout.ebb_insts(ebb) {
+            let size = encinfo.bytes(func.encodings[inst]);
+            if let InstructionData::TryCall { destination, .. } = func.dfg[inst] {
+                out.push(CallSite {
+                    offset,
+                    size,
+                    landing_pad: func.offsets[destination],
+                });
+            }
+            offset += size;
+        }
+    }
+}
@@ -11,10 +11,14 @@
 //! The `MemoryCodeSink` type fixes the performance problem because it is a type known to
 //! `TargetIsa` so it can specialize its machine code generation for the type. The trade-off is
 //! that a `MemoryCodeSink` will always write binary machine code to raw memory. It forwards any
-//! relocations to a `RelocSink` trait object. Relocations are less frequent than the
+//! relocations to a `RelocSink` trait object, any trap sites to a `TrapSink` trait object, and any
+//! patchable call sites to a `PatchSink` trait object. These are less frequent than the
 //! `CodeSink::put*` methods, so the performance impact of the virtual callbacks is less severe.
+//!
+//! For debugging, a `MemoryCodeSink` can also report the code range of every instruction to an
+//! `AnnotationSink` trait object.
 
-use ir::{ExternalName, JumpTable};
+use ir::{ExternalName, Inst, JumpTable, SourceLoc, TrapCode};
 use super::{CodeSink, CodeOffset, Reloc, Addend};
 use std::ptr::write_unaligned;
 
@@ -25,7 +29,9 @@ use std::ptr::write_unaligned;
 /// sure to allocate enough memory for the whole function. The number of bytes required is returned
 /// by the `Context::compile()` function.
 ///
-/// Any relocations in the function are forwarded to the `RelocSink` trait object.
+/// Any relocations in the function are forwarded to the `RelocSink` trait object, the
+/// instructions that can trap are reported to the `TrapSink` trait object, and the patchable call
+/// sites are reported to the `PatchSink` trait object.
 ///
 /// Note that `MemoryCodeSink` writes multi-byte values in the native byte order of the host. This
 /// is not the right thing to do for cross compilation.
@@ -33,15 +39,45 @@ pub struct MemoryCodeSink<'a> {
     data: *mut u8,
     offset: isize,
     relocs: &'a mut RelocSink,
+    traps: &'a mut TrapSink,
+    patches: &'a mut PatchSink,
+    annotations: Option<&'a mut AnnotationSink>,
 }
 
 impl<'a> MemoryCodeSink<'a> {
     /// Create a new memory code sink that writes a function to the memory pointed to by `data`.
-    pub fn new(data: *mut u8, relocs: &mut RelocSink) -> MemoryCodeSink {
+    pub fn new(
+        data: *mut u8,
+        relocs: &'a mut RelocSink,
+        traps: &'a mut TrapSink,
+        patches: &'a mut PatchSink,
+    ) -> MemoryCodeSink<'a> {
+        MemoryCodeSink {
+            data,
+            offset: 0,
+            relocs,
+            traps,
+            patches,
+            annotations: None,
+        }
+    }
+
+    /// Create a new memory code sink that also reports the code range of every emitted
+    /// instruction to `annotations`.
+    pub fn with_annotations(
+        data: *mut u8,
+        relocs: &'a mut RelocSink,
+        traps: &'a mut TrapSink,
+        patches: &'a mut PatchSink,
+        annotations: &'a mut AnnotationSink,
+    ) -> MemoryCodeSink<'a> {
         MemoryCodeSink {
             data,
             offset: 0,
             relocs,
+            traps,
+            patches,
+            annotations: Some(annotations),
         }
     }
 }
@@ -58,6 +94,48 @@ pub trait RelocSink {
     fn reloc_jt(&mut self, CodeOffset, Reloc, JumpTable);
 }
 
+/// A trait for receiving trap codes and offsets.
+///
+/// The `TrapTableSink` implementation collects the trap sites in a table that can be searched by
+/// code offset. If you don't need information about possible traps, you can use the
+/// `NullTrapSink` implementation.
+pub trait TrapSink {
+    /// Add trap information for a specific offset.
+    fn trap(&mut self, CodeOffset, SourceLoc, TrapCode);
+}
+
+/// A `TrapSink` implementation that does nothing, which is convenient when compiling code that
+/// does not rely on trapping semantics.
+pub struct NullTrapSink {}
+
+impl TrapSink for NullTrapSink {
+    fn trap(&mut self, _offset: CodeOffset, _srcloc: SourceLoc, _code: TrapCode) {}
+}
+
+/// A trait for receiving the offsets of patchable call sites.
+///
+/// The offset is that of the call instruction itself. The 4-byte call displacement that follows
+/// the opcode is aligned, so an embedder can repoint the call by overwriting it atomically.
+///
+/// If the function has no patchable calls, you can use the `NullPatchSink` implementation.
+pub trait PatchSink {
+    /// Add a patchable call to the function `name` at a specific offset.
+    fn patchable_call(&mut self, CodeOffset, &ExternalName);
+}
+
+/// A `PatchSink` implementation that does nothing.
+pub struct NullPatchSink {}
+
+impl PatchSink for NullPatchSink {
+    fn patchable_call(&mut self, _offset: CodeOffset, _name: &ExternalName) {}
+}
+
+/// A trait for receiving the code ranges of the instructions emitted into memory.
+pub trait AnnotationSink {
+    /// The machine code for `inst` occupies the range from `start` to `end`.
+    fn inst_range(&mut self, inst: Inst, start: CodeOffset, end: CodeOffset);
+}
+
 impl<'a> CodeSink for MemoryCodeSink<'a> {
     fn offset(&self) -> CodeOffset {
         self.offset as CodeOffset
@@ -105,4 +183,21 @@ impl<'a> CodeSink for MemoryCodeSink<'a> {
         let ofs = self.offset();
         self.relocs.reloc_jt(ofs, rel, jt);
     }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        let ofs = self.offset();
+        self.traps.trap(ofs, srcloc, code);
+    }
+
+    fn patchable_call(&mut self, name: &ExternalName) {
+        let ofs = self.offset();
+        self.patches.patchable_call(ofs, name);
+    }
+
+    fn annotate_inst(&mut self, inst: Inst, start: CodeOffset) {
+        let end = self.offset();
+        if let Some(ref mut annotations) = self.annotations {
+            annotations.inst_range(inst, start, end);
+        }
+    }
 }
@@ -3,14 +3,25 @@
 //! The `binemit` module contains code for translating Cretonne's intermediate representation into
 //! binary machine code.
 
+mod alignment;
+mod annotate;
+mod callsites;
 mod relaxation;
 mod memorysink;
+mod shrink;
+mod traptable;
 
 pub use regalloc::RegDiversions;
-pub use self::relaxation::relax_branches;
-pub use self::memorysink::{MemoryCodeSink, RelocSink};
-
-use ir::{ExternalName, JumpTable, Function, Inst};
+pub use self::alignment::function_alignment;
+pub use self::callsites::{call_site_table, CallSite};
+pub use self::relaxation::{relax_branches, Relaxation};
+pub use self::shrink::shrink_instructions;
+pub use self::traptable::{TrapTableSink, TrapSite};
+pub use self::annotate::{InstRanges, write_annotated_disassembly};
+pub use self::memorysink::{MemoryCodeSink, RelocSink, TrapSink, NullTrapSink, PatchSink,
+                           NullPatchSink, AnnotationSink};
+
+use ir::{ExternalName, JumpTable, Function, Inst, SourceLoc, TrapCode};
 use std::fmt;
 
 /// Offset in bytes from the beginning of the function.
@@ -35,6 +46,11 @@ pub enum Reloc {
     IntelGOTPCRel4,
     /// Intel PLT-relative 4-byte
     IntelPLTRel4,
+    /// Intel PC-relative 4-byte offset of the GOT entry holding a TLS variable's offset from the
+    /// thread pointer (initial-exec TLS model)
+    IntelGOTTPOff4,
+    /// Intel 4-byte offset of a TLS variable from the thread pointer (local-exec TLS model)
+    IntelTPOff4,
     /// Arm32 call target
     Arm32Call,
     /// Arm64 call target
@@ -53,6 +69,8 @@ impl fmt::Display for Reloc {
             Reloc::IntelAbs8 => write!(f, "{}", "Abs8"),
             Reloc::IntelGOTPCRel4 => write!(f, "{}", "GOTPCRel4"),
             Reloc::IntelPLTRel4 => write!(f, "{}", "PLTRel4"),
+            Reloc::IntelGOTTPOff4 => write!(f, "{}", "GOTTPOff4"),
+            Reloc::IntelTPOff4 => write!(f, "{}", "TPOff4"),
             Reloc::Arm32Call | Reloc::Arm64Call | Reloc::RiscvCall => write!(f, "{}", "Call"),
         }
     }
@@ -86,6 +104,18 @@ pub trait CodeSink {
 
     /// Add a relocation referencing a jump table.
     fn reloc_jt(&mut self, Reloc, JumpTable);
+
+    /// Add trap information for the instruction at the current offset.
+    fn trap(&mut self, TrapCode, SourceLoc);
+
+    /// Note that a patchable call to the function `name` starts at the current offset.
+    fn patchable_call(&mut self, &ExternalName);
+
+    /// Note that the machine code for `inst` occupies the range from `start` to the current
+    /// offset.
+    ///
+    /// This is only used for debugging annotations, so the default implementation ignores it.
+    fn annotate_inst(&mut self, _inst: Inst, _start: CodeOffset) {}
 }
 
 /// Report a bad encoding error.
@@ -98,21 +128,30 @@ pub fn bad_encoding(func: &Function, inst: Inst) -> ! {
     );
 }
 
-/// Emit a function to `sink`, given an instruction emitter function.
+/// Emit a function to `sink`, given an instruction emitter function and a padding emitter
+/// function.
 ///
 /// This function is called from the `TargetIsa::emit_function()` implementations with the
-/// appropriate instruction emitter.
-pub fn emit_function<CS, EI>(func: &Function, emit_inst: EI, sink: &mut CS)
+/// appropriate emitters. The padding emitter is used to fill the gaps in front of aligned EBBs with
+/// no-op instructions.
+pub fn emit_function<CS, EI, EP>(func: &Function, emit_inst: EI, emit_padding: EP, sink: &mut CS)
 where
     CS: CodeSink,
     EI: Fn(&Function, Inst, &mut RegDiversions, &mut CS),
+    EP: Fn(CodeOffset, &mut CS),
 {
     let mut divert = RegDiversions::new();
     for ebb in func.layout.ebbs() {
         divert.clear();
+        let padding = func.offsets[ebb] - sink.offset();
+        if padding > 0 {
+            emit_padding(padding, sink);
+        }
         debug_assert_eq!(func.offsets[ebb], sink.offset());
         for inst in func.layout.ebb_insts(ebb) {
+            let start = sink.offset();
             emit_inst(func, inst, &mut divert, sink);
+            sink.annotate_inst(inst, start);
         }
     }
 }
@@ -10,7 +10,10 @@
 //!
 //! Branch relaxation is the process of ensuring that all branches in the function have enough
 //! range to encode their destination. It is common to have multiple branch encodings in an ISA.
-//! For example, Intel branches can have either an 8-bit or a 32-bit displacement.
+//! For example, Intel branches can have either an 8-bit or a 32-bit displacement, and RISC-V
+//! branches can use a 16-bit compressed encoding with a short range. A branch can be relaxed to an
+//! encoding whose register constraints are less strict than its current ones, like a compressed
+//! RISC-V branch which can only test the registers `%x8-%x15`.
 //!
 //! On RISC architectures, it can happen that conditional branches have a shorter range than
 //! unconditional branches:
@@ -26,73 +29,213 @@
 //!     jump ebb17
 //! ebb23:
 //! ```
+//!
+//! # EBB alignment
+//!
+//! EBBs that require alignment are padded so their header offset is a multiple of the alignment.
+//! See the `alignment` module.
+//!
+//! # Algorithm
+//!
+//! Functions translated from WebAssembly can have tens of thousands of EBBs, so relaxation must
+//! not rescan the whole function until it converges. Instead, a single scan of the instructions
+//! measures the size of every EBB and collects the branches along with their offset into their
+//! EBB. Each branch starts out with its shortest encoding, which also shrinks any long branches
+//! selected earlier.
+//!
+//! The relaxation rounds then only visit the EBB headers and the cached branches. Branches are
+//! visited in layout order, so all the EBB headers before a branch are exact when it is checked.
+//! When a branch is relaxed, its EBB grows and the rest of the EBB shifts, and another round is
+//! needed to check the branches against the updated offsets of the EBBs that follow. Encodings
+//! only ever grow during the rounds, so the process converges.
 
 use binemit::CodeOffset;
-use cursor::{Cursor, FuncCursor};
-use ir::{Function, InstructionData, Opcode};
-use isa::{TargetIsa, EncInfo};
+use binemit::alignment::{align_offset, ebb_alignments};
+use entity::EntityMap;
+use ir::{Ebb, Function, Inst, InstructionData, Opcode};
+use isa::{Encoding, TargetIsa, EncInfo};
 use iterators::IteratorExtras;
 use result::CtonError;
+use std::vec::Vec;
+
+/// A branch instruction that may need relaxing.
+struct Branch {
+    /// The branch instruction.
+    inst: Inst,
+    /// The EBB containing the branch.
+    ebb: Ebb,
+    /// Offset of the branch relative to the header of `ebb`.
+    offset: CodeOffset,
+}
 
 /// Relax branches and compute the final layout of EBB headers in `func`.
 ///
 /// Fill in the `func.offsets` table so the function is ready for binary emission.
 pub fn relax_branches(func: &mut Function, isa: &TargetIsa) -> Result<CodeOffset, CtonError> {
-    let encinfo = isa.encoding_info();
-
-    // Clear all offsets so we can recognize EBBs that haven't been visited yet.
-    func.offsets.clear();
-    func.offsets.resize(func.dfg.num_ebbs());
-
-    // Start by inserting fall through instructions.
-    fallthroughs(func);
-
-    let mut offset = 0;
-
-    // The relaxation algorithm iterates to convergence.
-    let mut go_again = true;
-    while go_again {
-        go_again = false;
-        offset = 0;
-
-        // Visit all instructions in layout order
-        let mut cur = FuncCursor::new(func);
-        while let Some(ebb) = cur.next_ebb() {
-            // Record the offset for `ebb` and make sure we iterate until offsets are stable.
-            if cur.func.offsets[ebb] != offset {
-                debug_assert!(
-                    cur.func.offsets[ebb] < offset,
-                    "Code shrinking during relaxation"
-                );
-                cur.func.offsets[ebb] = offset;
-                go_again = true;
-            }
+    Relaxation::new().run(func, isa)
+}
+
+/// Persistent data structures for branch relaxation.
+///
+/// This is the same as `relax_branches`, but the data structures are kept around so they can be
+/// reused for the next function.
+pub struct Relaxation {
+    /// Alignment of every EBB header.
+    align: EntityMap<Ebb, CodeOffset>,
+    /// Size of every EBB, not including the alignment padding in front of it.
+    sizes: EntityMap<Ebb, CodeOffset>,
+    /// Branches that may need relaxing, in layout order.
+    branches: Vec<Branch>,
+}
+
+impl Relaxation {
+    /// Create a new relaxation context.
+    pub fn new() -> Self {
+        Self {
+            align: EntityMap::with_default(1),
+            sizes: EntityMap::new(),
+            branches: Vec::new(),
+        }
+    }
+
+    /// Clear all data structures in this context.
+    pub fn clear(&mut self) {
+        self.align.clear();
+        self.sizes.clear();
+        self.branches.clear();
+    }
+
+    /// Relax branches and compute the final layout of EBB headers in `func`.
+    ///
+    /// Fill in the `func.offsets` table so the function is ready for binary emission.
+    pub fn run(&mut self, func: &mut Function, isa: &TargetIsa) -> Result<CodeOffset, CtonError> {
+        let encinfo = isa.encoding_info();
+
+        // Clear all offsets so we can recognize EBBs that haven't been visited yet.
+        func.offsets.clear();
+        func.offsets.resize(func.dfg.num_ebbs());
+
+        // Start by inserting fall through instructions.
+        fallthroughs(func);
+
+        ebb_alignments(func, isa, &mut self.align);
+        self.collect_branches(func, &encinfo, isa);
+
+        // Compute the initial EBB header offsets so forward branches have a destination.
+        let mut offset = self.layout_ebbs(func);
+
+        // The relaxation rounds iterate to convergence.
+        let mut go_again = !self.branches.is_empty();
+        while go_again {
+            go_again = false;
+            offset = 0;
+
+            let mut next = 0;
+            let mut ebb_cursor = func.layout.entry_block();
+            while let Some(ebb) = ebb_cursor {
+                offset = align_offset(offset, self.align[ebb]);
+                func.offsets[ebb] = offset;
 
-            while let Some(inst) = cur.next_inst() {
-                let enc = cur.func.encodings[inst];
-                let size = encinfo.bytes(enc);
-
-                // See if this might be a branch that is out of range.
-                if let Some(range) = encinfo.branch_range(enc) {
-                    if let Some(dest) = cur.func.dfg[inst].branch_destination() {
-                        let dest_offset = cur.func.offsets[dest];
-                        // This could be an out-of-range branch.
-                        // Relax it unless the destination offset has not been computed yet.
-                        if !range.contains(offset, dest_offset) &&
-                            (dest_offset != 0 || Some(dest) == cur.func.layout.entry_block())
-                        {
-                            offset += relax_branch(&mut cur, offset, dest_offset, &encinfo, isa);
-                            continue;
-                        }
+                // Growth of the branches in `ebb` that have been relaxed so far.
+                let mut shift = 0;
+                while next < self.branches.len() && self.branches[next].ebb == ebb {
+                    let br = &self.branches[next];
+                    next += 1;
+
+                    let inst = br.inst;
+                    let branch_offset = offset + br.offset + shift;
+                    let enc = func.encodings[inst];
+                    let range = encinfo.branch_range(enc).expect("Branch with no range");
+                    let dest = func.dfg[inst].branch_destination().expect(
+                        "Branch with no destination",
+                    );
+                    let dest_offset = func.offsets[dest];
+                    if !range.contains(branch_offset, dest_offset) {
+                        let before = encinfo.bytes(enc);
+                        let after =
+                            relax_branch(func, inst, branch_offset, dest_offset, &encinfo, isa);
+                        shift += after - before;
+                        go_again = true;
                     }
                 }
 
-                offset += size;
+                self.sizes[ebb] += shift;
+                offset += self.sizes[ebb];
+                ebb_cursor = func.layout.next_ebb(ebb);
+            }
+
+            // Cache the new offsets of the shifted branches for the next round.
+            if go_again {
+                self.update_branch_offsets(func, &encinfo);
+            }
+        }
+
+        Ok(offset)
+    }
+
+    /// Measure the EBBs in `func` and collect the branches that may need relaxing, in layout
+    /// order.
+    ///
+    /// Every branch is given its shortest encoding.
+    fn collect_branches(&mut self, func: &mut Function, encinfo: &EncInfo, isa: &TargetIsa) {
+        self.sizes.clear();
+        self.sizes.resize(func.dfg.num_ebbs());
+        self.branches.clear();
+
+        let mut ebb_cursor = func.layout.entry_block();
+        while let Some(ebb) = ebb_cursor {
+            let mut size = 0;
+            let mut inst_cursor = func.layout.first_inst(ebb);
+            while let Some(inst) = inst_cursor {
+                if encinfo.branch_range(func.encodings[inst]).is_some() &&
+                    func.dfg[inst].branch_destination().is_some()
+                {
+                    shrink_branch(func, inst, encinfo, isa);
+                    self.branches.push(Branch {
+                        inst,
+                        ebb,
+                        offset: size,
+                    });
+                }
+                size += encinfo.bytes(func.encodings[inst]);
+                inst_cursor = func.layout.next_inst(inst);
             }
+            self.sizes[ebb] = size;
+            ebb_cursor = func.layout.next_ebb(ebb);
+        }
+    }
+
+    /// Compute the EBB header offsets from the EBB sizes, and return the total code size.
+    fn layout_ebbs(&self, func: &mut Function) -> CodeOffset {
+        let mut offset = 0;
+        let mut ebb_cursor = func.layout.entry_block();
+        while let Some(ebb) = ebb_cursor {
+            offset = align_offset(offset, self.align[ebb]);
+            func.offsets[ebb] = offset;
+            offset += self.sizes[ebb];
+            ebb_cursor = func.layout.next_ebb(ebb);
         }
+        offset
     }
 
-    Ok(offset)
+    /// Recompute the offsets of the branches relative to their EBB headers.
+    ///
+    /// Only the EBBs containing branches are scanned.
+    fn update_branch_offsets(&mut self, func: &Function, encinfo: &EncInfo) {
+        let branches = &mut self.branches;
+        let mut i = 0;
+        while i < branches.len() {
+            let ebb = branches[i].ebb;
+            let mut size = 0;
+            for inst in func.layout.ebb_insts(ebb) {
+                if i < branches.len() && branches[i].inst == inst {
+                    branches[i].offset = size;
+                    i += 1;
+                }
+                size += encinfo.bytes(func.encodings[inst]);
+            }
+        }
+    }
 }
 
 /// Convert `jump` instructions to `fallthrough` instructions where possible and verify that any
@@ -125,43 +268,76 @@ fn fallthroughs(func: &mut Function) {
     }
 }
 
-/// Relax the branch instruction at `pos` so it can cover the range `offset - dest_offset`.
+/// Give the branch instruction `inst` its shortest encoding with the same operand constraints.
+fn shrink_branch(func: &mut Function, inst: Inst, encinfo: &EncInfo, isa: &TargetIsa) {
+    let cur = func.encodings[inst];
+    let shortest = {
+        let dfg = &func.dfg;
+        let ctrl_type = dfg.ctrl_typevar(inst);
+        isa.legal_encodings(func, &dfg[inst], ctrl_type)
+            .filter(|&enc| {
+                encinfo.branch_range(enc).is_some() &&
+                    encinfo.operand_constraints(enc) == encinfo.operand_constraints(cur)
+            })
+            .min_by_key(|&enc| encinfo.bytes(enc))
+    };
+    if let Some(enc) = shortest {
+        if encinfo.bytes(enc) < encinfo.bytes(cur) {
+            func.encodings[inst] = enc;
+        }
+    }
+}
+
+/// Do the operand constraints of `enc` accept all the operands that satisfy the constraints of
+/// `cur`?
+fn subsumes(encinfo: &EncInfo, enc: Encoding, cur: Encoding) -> bool {
+    match (
+        encinfo.operand_constraints(enc),
+        encinfo.operand_constraints(cur),
+    ) {
+        (Some(a), Some(b)) => a.subsumes(b),
+        (a, b) => a == b,
+    }
+}
+
+/// Relax the branch instruction `inst` at `offset` so it can cover the range `offset -
+/// dest_offset`.
 ///
-/// Return the size of the replacement instructions up to and including the location where `pos` is
-/// left.
+/// Return the size of the new encoding.
 fn relax_branch(
-    cur: &mut FuncCursor,
+    func: &mut Function,
+    inst: Inst,
     offset: CodeOffset,
     dest_offset: CodeOffset,
     encinfo: &EncInfo,
     isa: &TargetIsa,
 ) -> CodeOffset {
-    let inst = cur.current_inst().unwrap();
     dbg!(
         "Relaxing [{}] {} for {:#x}-{:#x} range",
-        encinfo.display(cur.func.encodings[inst]),
-        cur.func.dfg.display_inst(inst, isa),
+        encinfo.display(func.encodings[inst]),
+        func.dfg.display_inst(inst, isa),
         offset,
         dest_offset
     );
 
     // Pick the first encoding that can handle the branch range.
-    let dfg = &cur.func.dfg;
+    let dfg = &func.dfg;
     let ctrl_type = dfg.ctrl_typevar(inst);
-    if let Some(enc) = isa.legal_encodings(dfg, &dfg[inst], ctrl_type).find(
+    if let Some(enc) = isa.legal_encodings(func, &dfg[inst], ctrl_type).find(
         |&enc| {
             let range = encinfo.branch_range(enc).expect("Branch with no range");
             if !range.contains(offset, dest_offset) {
                 dbg!("  trying [{}]: out of range", encinfo.display(enc));
                 false
-            } else if encinfo.operand_constraints(enc) !=
-                       encinfo.operand_constraints(cur.func.encodings[inst])
-            {
-                // Conservatively give up if the encoding has different constraints
+            } else if !subsumes(encinfo, enc, func.encodings[inst]) {
+                // Conservatively give up if the encoding has stricter constraints
                 // than the original, so that we don't risk picking a new encoding
                 // which the existing operands don't satisfy. We can't check for
                 // validity directly because we don't have a RegDiversions active so
                 // we don't know which registers are actually in use.
+                //
+                // Compressed branches usually have stricter constraints than the
+                // full-size encodings they can be relaxed to.
                 dbg!("  trying [{}]: constraints differ", encinfo.display(enc));
                 false
             } else {
@@ -171,7 +347,7 @@ fn relax_branch(
         },
     )
     {
-        cur.func.encodings[inst] = enc;
+        func.encodings[inst] = enc;
         return encinfo.bytes(enc);
     }
 
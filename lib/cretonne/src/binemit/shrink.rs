@@ -0,0 +1,57 @@
+//! Instruction shrinking.
+//!
+//! Some ISAs have multiple encodings of the same instruction that only differ in the registers
+//! they can address. For example, Intel instructions need a REX prefix to access the registers
+//! `%r8`-`%r15`, and the encodings with a REX prefix are selected by default so the register
+//! allocator can use all the registers.
+//!
+//! After register allocation, the operand locations are known, and each instruction can be
+//! changed to its shortest encoding whose constraints are satisfied by the allocated registers.
+//! This must run before branch relaxation, which only switches between branch encodings with the
+//! same operand constraints.
+
+use ir::Function;
+use isa::TargetIsa;
+use regalloc::RegDiversions;
+use timing;
+
+/// Pick the shortest encoding of every instruction in `func` that is compatible with its
+/// register assignment.
+pub fn shrink_instructions(func: &mut Function, isa: &TargetIsa) {
+    let _tt = timing::shrink_instructions();
+    let encinfo = isa.encoding_info();
+    let mut divert = RegDiversions::new();
+
+    let mut ebb = func.layout.entry_block();
+    while let Some(e) = ebb {
+        divert.clear();
+        let mut cur = func.layout.first_inst(e);
+        while let Some(inst) = cur {
+            let enc = func.encodings[inst];
+            if enc.is_legal() {
+                let ctrl_type = func.dfg.ctrl_typevar(inst);
+                let best = isa.legal_encodings(func, &func.dfg[inst], ctrl_type)
+                    .filter(|&e| {
+                        encinfo.operand_constraints(e).map_or(false, |c| {
+                            c.satisfied(inst, &divert, func)
+                        })
+                    })
+                    .min_by_key(|&e| encinfo.bytes(e));
+                if let Some(best) = best {
+                    if encinfo.bytes(best) < encinfo.bytes(enc) {
+                        dbg!(
+                            "Shrinking {} from {} to {}",
+                            func.dfg.display_inst(inst, isa),
+                            encinfo.display(enc),
+                            encinfo.display(best)
+                        );
+                        func.encodings[inst] = best;
+                    }
+                }
+            }
+            divert.apply(&func.dfg[inst]);
+            cur = func.layout.next_inst(inst);
+        }
+        ebb = func.layout.next_ebb(e);
+    }
+}
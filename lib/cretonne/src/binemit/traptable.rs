@@ -0,0 +1,129 @@
+//! Trap tables mapping code offsets to trap codes.
+//!
+//! When a trapping instruction faults, the runtime gets the faulting code address from the signal
+//! handler. The `TrapTableSink` collects the trap sites reported while emitting a function so the
+//! runtime can map the code offset back to the trap code and the source location of the
+//! instruction, like a WebAssembly bytecode offset.
+
+use binemit::{CodeOffset, TrapSink};
+use ir::{SourceLoc, TrapCode};
+use std::vec::Vec;
+
+/// A trapping instruction in the trap table of a function.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TrapSite {
+    /// Code offset of the trapping instruction.
+    pub offset: CodeOffset,
+
+    /// Source location of the instruction.
+    pub srcloc: SourceLoc,
+
+    /// The trap code reported by the instruction.
+    pub code: TrapCode,
+}
+
+/// A `TrapSink` that collects the trap sites of a function in a table sorted by code offset.
+///
+/// The same sink can be used for multiple functions, as long as the functions are emitted in order
+/// of increasing code offsets, like when they are placed one after another in memory. Use
+/// `set_base()` to set the code offset of the next function.
+#[derive(Clone, Debug, Default)]
+pub struct TrapTableSink {
+    base: CodeOffset,
+    sites: Vec<TrapSite>,
+}
+
+impl TrapTableSink {
+    /// Create a new empty trap table.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the code offset of the function being emitted.
+    ///
+    /// The trap offsets reported by the function are relative to this offset in the table.
+    pub fn set_base(&mut self, base: CodeOffset) {
+        self.base = base;
+    }
+
+    /// Remove all trap sites and reset the base offset.
+    pub fn clear(&mut self) {
+        self.base = 0;
+        self.sites.clear();
+    }
+
+    /// Get all the trap sites in order of increasing code offset.
+    pub fn sites(&self) -> &[TrapSite] {
+        &self.sites
+    }
+
+    /// Get the number of trap sites in the table.
+    pub fn len(&self) -> usize {
+        self.sites.len()
+    }
+
+    /// Is the table empty?
+    pub fn is_empty(&self) -> bool {
+        self.sites.is_empty()
+    }
+
+    /// Find the trap site at `offset`, if any.
+    pub fn lookup(&self, offset: CodeOffset) -> Option<&TrapSite> {
+        self.sites
+            .binary_search_by_key(&offset, |site| site.offset)
+            .ok()
+            .map(|idx| &self.sites[idx])
+    }
+}
+
+impl TrapSink for TrapTableSink {
+    fn trap(&mut self, offset: CodeOffset, srcloc: SourceLoc, code: TrapCode) {
+        let site = TrapSite {
+            offset: self.base + offset,
+            srcloc,
+            code,
+        };
+        // Trap sites are normally reported in order, but keep the table sorted if they aren't.
+        match self.sites.last() {
+            Some(last) if last.offset > site.offset => {
+                let idx = self.sites
+                    .binary_search_by_key(&site.offset, |s| s.offset)
+                    .unwrap_or_else(|idx| idx);
+                self.sites.insert(idx, site);
+            }
+            _ => self.sites.push(site),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let mut table = TrapTableSink::new();
+        assert!(table.is_empty());
+        assert_eq!(table.lookup(0), None);
+
+        table.trap(4, SourceLoc::new(10), TrapCode::HeapOutOfBounds);
+        table.trap(12, SourceLoc::new(20), TrapCode::IntegerDivisionByZero);
+        table.set_base(100);
+        table.trap(2, SourceLoc::new(30), TrapCode::StackOverflow);
+
+        // An out of order site is inserted in the right place.
+        table.set_base(0);
+        table.trap(8, SourceLoc::default(), TrapCode::User(1));
+
+        assert_eq!(table.len(), 4);
+        let offsets: Vec<_> = table.sites().iter().map(|s| s.offset).collect();
+        assert_eq!(offsets, [4, 8, 12, 102]);
+
+        assert_eq!(table.lookup(12).unwrap().srcloc, SourceLoc::new(20));
+        assert_eq!(table.lookup(102).unwrap().code, TrapCode::StackOverflow);
+        assert_eq!(table.lookup(5), None);
+
+        table.clear();
+        assert!(table.is_empty());
+    }
+}
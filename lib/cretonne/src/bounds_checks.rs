@@ -0,0 +1,335 @@
+//! Heap bounds check optimization.
+//!
+//! Every `heap_addr` instruction becomes a compare and a conditional trap when it is legalized, so
+//! memory-heavy code spends many branches on bounds checks. This pass reduces their number in two
+//! ways:
+//!
+//! - Checks of the same heap in an EBB are combined when their indexes are the same value, or the
+//!   same base value plus small constants added with `iadd_imm`. The first check of the group is
+//!   widened to cover all the accesses, and the other addresses are computed from it:
+//!
+//!   ```cton
+//!   v2 = heap_addr.i64 heap0, v1, 4
+//!   v3 = iadd_imm v1, 8
+//!   v4 = heap_addr.i64 heap0, v3, 4
+//!   ```
+//!
+//!   becomes:
+//!
+//!   ```cton
+//!   v5 = heap_addr.i64 heap0, v1, 12
+//!   v2 -> v5
+//!   v3 = iadd_imm v1, 8
+//!   v4 = iadd_imm v5, 8
+//!   ```
+//!
+//! - Checks at the top of a loop header whose index is defined outside the loop are hoisted to
+//!   the loop preheader.
+//!
+//! Both transformations move checks up, so they must not move a check across an instruction with
+//! side effects, or to a place where it wasn't executed before. Checks can be moved across other
+//! bounds checks since they all trap with the same code.
+//!
+//! Adding a constant to the index can wrap around, so checks with different indexes are only
+//! combined for static heaps where the combined check can't fail unless one of the original
+//! checks fails. Heaps never shrink, so a check that succeeds once keeps succeeding for the same
+//! index.
+
+use cursor::{Cursor, FuncCursor};
+use dominator_tree::DominatorTree;
+use flowgraph::ControlFlowGraph;
+use ir::{Ebb, Function, Heap, HeapStyle, Inst, InstBuilder, InstructionData, Opcode, ProgramOrder,
+         Type, Value, ValueDef};
+use loop_analysis::LoopAnalysis;
+use std::cmp::Ordering;
+use std::vec::Vec;
+use timing;
+
+/// A bounds check which is part of a group of combined checks.
+struct Member {
+    /// The `heap_addr` instruction.
+    inst: Inst,
+    /// The index checked by `inst`.
+    index: Value,
+    /// Constant offset of `index` from the base value of the group.
+    offset: i64,
+    /// Number of bytes checked by `inst`.
+    size: i64,
+}
+
+/// A group of bounds checks of the same heap and the same base value in an EBB.
+struct CheckGroup {
+    heap: Heap,
+    base: Value,
+    /// The type of the computed addresses.
+    addr_ty: Type,
+    /// The checks in the group, in layout order. The combined check is inserted before the first.
+    members: Vec<Member>,
+}
+
+/// Combine and hoist the heap bounds checks in `func`.
+///
+/// The control flow graph, dominator tree, and loop analysis must be valid. They are updated when
+/// loop preheaders are created.
+///
+/// Returns true if any bounds checks were changed.
+pub fn do_bounds_checks(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
+    loop_analysis: &mut LoopAnalysis,
+) -> bool {
+    let _tt = timing::bounds_checks();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    let mut changed = false;
+    let mut ebb = func.layout.entry_block();
+    while let Some(current) = ebb {
+        changed |= combine_checks(func, current);
+        ebb = func.layout.next_ebb(current);
+    }
+    changed | hoist_checks(func, cfg, domtree, loop_analysis)
+}
+
+/// Can a bounds check be moved up across `inst`?
+///
+/// Checks must not be moved across instructions with side effects, or across branches since the
+/// check may not be executed at all when the branch is taken.
+fn is_barrier(func: &Function, inst: Inst) -> bool {
+    let opcode = func.dfg[inst].opcode();
+    opcode.is_branch() || opcode.is_terminator() || opcode.is_call() || opcode.can_store() ||
+        opcode.other_side_effects() || (opcode.can_trap() && opcode != Opcode::HeapAddr)
+}
+
+/// Unpack a `heap_addr` instruction.
+fn heap_addr(func: &Function, inst: Inst) -> Option<(Heap, Value, i64)> {
+    match func.dfg[inst] {
+        InstructionData::HeapAddr {
+            opcode: Opcode::HeapAddr,
+            heap,
+            arg,
+            imm,
+        } => Some((heap, func.dfg.resolve_aliases(arg), imm.into())),
+        _ => None,
+    }
+}
+
+/// Split `index` into a base value and a constant offset.
+///
+/// Only non-negative offsets that are added with `iadd_imm` are recognized.
+fn split_index(func: &Function, index: Value) -> (Value, i64) {
+    if let ValueDef::Result(inst, _) = func.dfg.value_def(index) {
+        if let InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } = func.dfg[inst]
+        {
+            let offset: i64 = imm.into();
+            let bits = func.dfg.value_type(index).bits();
+            if offset >= 0 && offset <= i64::from(u32::max_value()) &&
+                (bits >= 64 || offset < 1 << bits)
+            {
+                return (func.dfg.resolve_aliases(arg), offset);
+            }
+        }
+    }
+    (index, 0)
+}
+
+/// Is `value` available before `inst`?
+///
+/// Values defined in other EBBs are available since they dominate their uses in this EBB.
+fn available_before(func: &Function, value: Value, inst: Inst) -> bool {
+    match func.dfg.value_def(value) {
+        ValueDef::Result(def, _) => {
+            func.layout.inst_ebb(def) != func.layout.inst_ebb(inst) ||
+                func.layout.cmp(def, inst) == Ordering::Less
+        }
+        ValueDef::Param(..) => true,
+    }
+}
+
+impl CheckGroup {
+    /// Get the smallest offset and the number of bytes checked from there.
+    fn range(&self) -> (i64, i64) {
+        let min = self.members.iter().map(|m| m.offset).min().unwrap_or(0);
+        let max = self.members.iter().map(|m| m.offset + m.size).max().unwrap_or(0);
+        (min, max - min)
+    }
+
+    /// Find the index with the smallest offset in the group. It must be available before the
+    /// first check where the combined check is inserted.
+    fn min_index(&self, func: &Function) -> Option<(Value, i64, i64)> {
+        let (min, size) = self.range();
+        let leader = self.members[0].inst;
+        self.members
+            .iter()
+            .find(|m| m.offset == min && available_before(func, m.index, leader))
+            .map(|m| (m.index, min, size))
+    }
+
+    /// Try to add a check to this group. Returns false if it can't be combined with the others.
+    fn try_add(&mut self, func: &Function, member: Member) -> bool {
+        self.members.push(member);
+        let ok = match self.min_index(func) {
+            Some((_, min, size)) => {
+                size <= i64::from(u32::max_value()) &&
+                    (self.members.iter().all(|m| m.offset == min) ||
+                         self.is_safe_to_offset(func, size))
+            }
+            None => false,
+        };
+        if !ok {
+            self.members.pop();
+        }
+        ok
+    }
+
+    /// Can checks with different offsets be combined into one check of `size` bytes?
+    ///
+    /// The index computations wrap around, so the combined check could fail when none of the
+    /// original checks fail. That can't happen if the heap bound plus `size` fits in the index
+    /// type.
+    fn is_safe_to_offset(&self, func: &Function, size: i64) -> bool {
+        match func.heaps[self.heap].style {
+            HeapStyle::Static { bound } => {
+                let bits = func.dfg.value_type(self.base).bits();
+                let bound: i64 = bound.into();
+                bits < 64 && bound >= 0 && bound + size <= 1 << bits
+            }
+            HeapStyle::Dynamic { .. } => false,
+        }
+    }
+
+    /// Replace the checks in the group with one combined check.
+    fn combine(&self, func: &mut Function) {
+        let (index, min, size) = self.min_index(func).expect("no index available for the check");
+
+        let mut pos = FuncCursor::new(func).at_inst(self.members[0].inst);
+        pos.use_srcloc(self.members[0].inst);
+        let addr = pos.ins().heap_addr(self.addr_ty, self.heap, index, size as u32);
+        let check = pos.func.dfg.value_def(addr).unwrap_inst();
+        dbg!(
+            "Combining {} checks of {} into {}",
+            self.members.len(),
+            self.heap,
+            addr
+        );
+        for member in &self.members {
+            let delta = member.offset - min;
+            if delta == 0 {
+                pos.func.dfg.replace_with_aliases(member.inst, check);
+                pos.func.layout.remove_inst(member.inst);
+            } else {
+                pos.func.dfg.replace(member.inst).iadd_imm(addr, delta);
+            }
+        }
+    }
+}
+
+/// Combine the bounds checks in `ebb`. Returns true if any checks were combined.
+fn combine_checks(func: &mut Function, ebb: Ebb) -> bool {
+    let mut open: Vec<CheckGroup> = Vec::new();
+    let mut done: Vec<CheckGroup> = Vec::new();
+
+    for inst in func.layout.ebb_insts(ebb) {
+        let (heap, index, size) = match heap_addr(func, inst) {
+            Some(check) => check,
+            None => {
+                if is_barrier(func, inst) {
+                    done.extend(open.drain(..).filter(|g| g.members.len() > 1));
+                }
+                continue;
+            }
+        };
+        let (base, offset) = split_index(func, index);
+        let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+        let mut added = false;
+        for group in open.iter_mut().filter(|g| {
+            g.heap == heap && g.base == base && g.addr_ty == addr_ty
+        })
+        {
+            if group.try_add(
+                func,
+                Member {
+                    inst,
+                    index,
+                    offset,
+                    size,
+                },
+            )
+            {
+                added = true;
+                break;
+            }
+        }
+        if !added {
+            open.push(CheckGroup {
+                heap,
+                base,
+                addr_ty,
+                members: vec![
+                    Member {
+                        inst,
+                        index,
+                        offset,
+                        size,
+                    },
+                ],
+            });
+        }
+    }
+    done.extend(open.drain(..).filter(|g| g.members.len() > 1));
+
+    for group in &done {
+        group.combine(func);
+    }
+    !done.is_empty()
+}
+
+/// Hoist the loop-invariant bounds checks at the top of loop headers to the preheaders. Returns
+/// true if any checks were hoisted.
+fn hoist_checks(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
+    loop_analysis: &mut LoopAnalysis,
+) -> bool {
+    let mut changed = false;
+
+    // Inner loops are created after their parents, so visiting the loops in reverse lets checks
+    // hoisted into an outer loop header move further out.
+    for lp in loop_analysis.loops().rev() {
+        let header = loop_analysis.loop_header(lp);
+        let mut invariant = Vec::new();
+        for inst in func.layout.ebb_insts(header) {
+            if let Some((_, index, _)) = heap_addr(func, inst) {
+                let def_ebb = match func.dfg.value_def(index) {
+                    ValueDef::Result(def, _) => func.layout.inst_ebb(def),
+                    ValueDef::Param(ebb, _) => Some(ebb),
+                };
+                if def_ebb.map_or(false, |ebb| !loop_analysis.is_in_loop(ebb, lp)) {
+                    invariant.push(inst);
+                }
+            } else if is_barrier(func, inst) {
+                break;
+            }
+        }
+        if invariant.is_empty() {
+            continue;
+        }
+
+        dbg!("Hoisting {} checks out of {}", invariant.len(), header);
+        let (_, branch) = loop_analysis.ensure_preheader(lp, func, cfg, domtree);
+        let mut pos = FuncCursor::new(func).at_inst(branch);
+        for inst in invariant {
+            pos.func.layout.remove_inst(inst);
+            pos.insert_inst(inst);
+        }
+        changed = true;
+    }
+    changed
+}
@@ -1,15 +1,26 @@
 //! The `CFGPrinter` utility.
+//!
+//! The CFG is printed in the Graphviz DOT format. By default, the nodes only show the branch
+//! instructions of each EBB. The printer can optionally include the instructions of the EBBs,
+//! draw the edges of the dominator tree, and group the EBBs of each loop in nested clusters.
 
 use std::fmt::{Result, Write, Display, Formatter};
+use std::string::String;
+use std::vec::Vec;
 
+use dominator_tree::DominatorTree;
 use flowgraph::ControlFlowGraph;
-use ir::Function;
+use ir::{Ebb, Function};
 use ir::instructions::BranchInfo;
+use loop_analysis::{Loop, LoopAnalysis};
 
 /// A utility for pretty-printing the CFG of a `Function`.
 pub struct CFGPrinter<'a> {
     func: &'a Function,
     cfg: ControlFlowGraph,
+    max_insts: Option<usize>,
+    domtree: bool,
+    loops: bool,
 }
 
 /// A utility for pretty-printing the CFG of a `Function`.
@@ -19,14 +30,50 @@ impl<'a> CFGPrinter<'a> {
         CFGPrinter {
             func,
             cfg: ControlFlowGraph::with_function(func),
+            max_insts: None,
+            domtree: false,
+            loops: false,
         }
     }
 
+    /// Show the instructions of each EBB in its node.
+    ///
+    /// Only the first `max_insts` instructions of an EBB are shown. The branches are always shown
+    /// since the CFG edges start from them.
+    pub fn with_insts(mut self, max_insts: usize) -> Self {
+        self.max_insts = Some(max_insts);
+        self
+    }
+
+    /// Draw the edges of the dominator tree as dashed lines from each EBB to the EBB containing
+    /// its immediate dominator.
+    pub fn with_domtree(mut self) -> Self {
+        self.domtree = true;
+        self
+    }
+
+    /// Group the EBBs of each loop in a cluster, nested according to the loop tree.
+    pub fn with_loops(mut self) -> Self {
+        self.loops = true;
+        self
+    }
+
     /// Write the CFG for this function to `w`.
     pub fn write(&self, w: &mut Write) -> Result {
         self.header(w)?;
         self.ebb_nodes(w)?;
         self.cfg_connections(w)?;
+        if self.domtree || self.loops {
+            let domtree = DominatorTree::with_function(self.func, &self.cfg);
+            if self.domtree {
+                self.domtree_connections(w, &domtree)?;
+            }
+            if self.loops {
+                let mut loop_analysis = LoopAnalysis::new();
+                loop_analysis.compute(self.func, &self.cfg, &domtree);
+                self.loop_clusters(w, &loop_analysis)?;
+            }
+        }
         writeln!(w, "}}")
     }
 
@@ -40,25 +87,77 @@ impl<'a> CFGPrinter<'a> {
 
     fn ebb_nodes(&self, w: &mut Write) -> Result {
         for ebb in &self.func.layout {
-            write!(w, "    {} [shape=record, label=\"{{{}", ebb, ebb)?;
-            // Add all outgoing branch instructions to the label.
-            for inst in self.func.layout.ebb_insts(ebb) {
-                let idata = &self.func.dfg[inst];
-                match idata.analyze_branch(&self.func.dfg.value_lists) {
-                    BranchInfo::SingleDest(dest, _) => {
-                        write!(w, " | <{}>{} {}", inst, idata.opcode(), dest)?
-                    }
-                    BranchInfo::Table(table) => {
-                        write!(w, " | <{}>{} {}", inst, idata.opcode(), table)?
-                    }
-                    BranchInfo::NotABranch => {}
-                }
+            write!(w, "    {} [shape=record, label=\"{{", ebb)?;
+            match self.max_insts {
+                None => self.ebb_branches(w, ebb)?,
+                Some(max_insts) => self.ebb_insts(w, ebb, max_insts)?,
             }
             writeln!(w, "}}\"]")?
         }
         Ok(())
     }
 
+    /// Write a label with the EBB name and the outgoing branch instructions.
+    fn ebb_branches(&self, w: &mut Write, ebb: Ebb) -> Result {
+        write!(w, "{}", ebb)?;
+        for inst in self.func.layout.ebb_insts(ebb) {
+            let idata = &self.func.dfg[inst];
+            match idata.analyze_branch(&self.func.dfg.value_lists) {
+                BranchInfo::SingleDest(dest, _) => {
+                    write!(w, " | <{}>{} {}", inst, idata.opcode(), dest)?
+                }
+                BranchInfo::Table(table) => {
+                    write!(w, " | <{}>{} {}", inst, idata.opcode(), table)?
+                }
+                BranchInfo::NotABranch => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Write a label with the EBB header and its first `max_insts` instructions, followed by any
+    /// remaining branches.
+    fn ebb_insts(&self, w: &mut Write, ebb: Ebb, max_insts: usize) -> Result {
+        let mut header = format!("{}", ebb);
+        let params = self.func.dfg.ebb_params(ebb);
+        if !params.is_empty() {
+            header.push('(');
+            for (i, &param) in params.iter().enumerate() {
+                if i != 0 {
+                    header.push_str(", ");
+                }
+                write!(header, "{}: {}", param, self.func.dfg.value_type(param))?;
+            }
+            header.push(')');
+        }
+        write!(w, "{}", escape(&header))?;
+
+        let mut truncated = false;
+        for (idx, inst) in self.func.layout.ebb_insts(ebb).enumerate() {
+            let is_branch = self.func.dfg[inst].opcode().is_branch();
+            if idx >= max_insts {
+                if !is_branch {
+                    truncated = true;
+                    continue;
+                }
+                if truncated {
+                    write!(w, " | ...")?;
+                    truncated = false;
+                }
+            }
+            let text = format!("{}", self.func.dfg.display_inst(inst, None));
+            if is_branch {
+                write!(w, " | <{}>{}\\l", inst, escape(&text))?;
+            } else {
+                write!(w, " | {}\\l", escape(&text))?;
+            }
+        }
+        if truncated {
+            write!(w, " | ...")?;
+        }
+        Ok(())
+    }
+
     fn cfg_connections(&self, w: &mut Write) -> Result {
         for ebb in &self.func.layout {
             for (parent, inst) in self.cfg.pred_iter(ebb) {
@@ -67,6 +166,74 @@ impl<'a> CFGPrinter<'a> {
         }
         Ok(())
     }
+
+    fn domtree_connections(&self, w: &mut Write, domtree: &DominatorTree) -> Result {
+        for ebb in &self.func.layout {
+            if let Some(idom) = domtree.idom(ebb) {
+                let parent = self.func.layout.inst_ebb(idom).expect("idom in layout");
+                writeln!(
+                    w,
+                    "    {} -> {} [style=dashed, color=blue, constraint=false]",
+                    parent,
+                    ebb
+                )?;
+            }
+        }
+        Ok(())
+    }
+
+    fn loop_clusters(&self, w: &mut Write, loop_analysis: &LoopAnalysis) -> Result {
+        for lp in loop_analysis.loops() {
+            if loop_analysis.loop_parent(lp).is_none() {
+                self.loop_cluster(w, loop_analysis, lp, 1)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the cluster for `lp` containing the EBBs of the loop and its child loops.
+    fn loop_cluster(
+        &self,
+        w: &mut Write,
+        loop_analysis: &LoopAnalysis,
+        lp: Loop,
+        depth: usize,
+    ) -> Result {
+        let indent = depth * 4;
+        writeln!(w, "{:2$}subgraph cluster_{} {{", "", lp, indent)?;
+        writeln!(w, "{:2$}    label=\"{}\"", "", lp, indent)?;
+        let ebbs = self.func
+            .layout
+            .ebbs()
+            .filter(|&ebb| loop_analysis.innermost_loop(ebb) == Some(lp))
+            .collect::<Vec<_>>();
+        if !ebbs.is_empty() {
+            write!(w, "{:1$}   ", "", indent)?;
+            for ebb in ebbs {
+                write!(w, " {};", ebb)?;
+            }
+            writeln!(w)?;
+        }
+        for child in loop_analysis.loops() {
+            if loop_analysis.loop_parent(child) == Some(lp) {
+                self.loop_cluster(w, loop_analysis, child, depth + 1)?;
+            }
+        }
+        writeln!(w, "{:1$}}}", "", indent)
+    }
+}
+
+/// Escape the characters that have a special meaning in a record label.
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '{' | '}' | '|' | '<' | '>' | '"' | '\\' => escaped.push('\\'),
+            _ => {}
+        }
+        escaped.push(c);
+    }
+    escaped
 }
 
 impl<'a> Display for CFGPrinter<'a> {
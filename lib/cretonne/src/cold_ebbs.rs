@@ -0,0 +1,46 @@
+//! Cold code layout.
+//!
+//! EBBs that call a function marked `cold` or `noreturn` are unlikely to execute. This pass moves
+//! them to the end of the layout so the hot code stays contiguous.
+
+use ir::{Ebb, Function, Opcode};
+use std::vec::Vec;
+use timing;
+
+/// Move EBBs containing calls to cold functions to the end of the layout.
+///
+/// The relative order of the moved EBBs is preserved. The entry block is never moved, and EBBs
+/// that are involved in a `fallthrough` are left where they are.
+pub fn move_cold_ebbs(func: &mut Function) {
+    let _tt = timing::cold_ebbs();
+    let cold: Vec<Ebb> = func.layout
+        .ebbs()
+        .skip(1)
+        .filter(|&ebb| is_cold(func, ebb) && !has_fallthrough(func, ebb))
+        .collect();
+
+    for ebb in cold {
+        dbg!("Moving cold {} out of line", ebb);
+        func.layout.move_ebb_to_end(ebb);
+    }
+}
+
+/// Does `ebb` call a cold function?
+fn is_cold(func: &Function, ebb: Ebb) -> bool {
+    func.layout.ebb_insts(ebb).any(|inst| {
+        func.dfg.call_ext_func(inst).map_or(
+            false,
+            |f| f.cold || f.noreturn,
+        )
+    })
+}
+
+/// Does `ebb` fall through to the next EBB, or does the previous EBB fall through to it?
+fn has_fallthrough(func: &Function, ebb: Ebb) -> bool {
+    let ends_in_fallthrough = |e: Ebb| {
+        func.layout.last_inst(e).map_or(false, |inst| {
+            func.dfg[inst].opcode() == Opcode::Fallthrough
+        })
+    };
+    ends_in_fallthrough(ebb) || func.layout.prev_ebb(ebb).map_or(false, ends_in_fallthrough)
+}
@@ -0,0 +1,90 @@
+//! Evaluation of integer operations on constants.
+//!
+//! Constants are represented as `i64` values holding the low `bits` bits of a `bits`-wide
+//! integer. The upper bits are ignored, so the same value can be interpreted as signed or
+//! unsigned depending on the operation.
+
+use ir::Opcode;
+use ir::condcodes::IntCC;
+
+/// Sign-extend the low `bits` bits of `x`.
+pub fn sign_extend(x: i64, bits: u16) -> i64 {
+    let shift = 64 - u32::from(bits);
+    x.wrapping_shl(shift).wrapping_shr(shift)
+}
+
+/// Zero-extend the low `bits` bits of `x`.
+pub fn zero_extend(x: i64, bits: u16) -> i64 {
+    let shift = 64 - u32::from(bits);
+    ((x as u64).wrapping_shl(shift).wrapping_shr(shift)) as i64
+}
+
+/// Evaluate the integer comparison `cc` of `bits`-wide integers `x` and `y`.
+pub fn icmp(cc: IntCC, x: i64, y: i64, bits: u16) -> bool {
+    let (sx, sy) = (sign_extend(x, bits), sign_extend(y, bits));
+    let (ux, uy) = (zero_extend(x, bits) as u64, zero_extend(y, bits) as u64);
+    match cc {
+        IntCC::Equal => ux == uy,
+        IntCC::NotEqual => ux != uy,
+        IntCC::SignedLessThan => sx < sy,
+        IntCC::SignedGreaterThanOrEqual => sx >= sy,
+        IntCC::SignedGreaterThan => sx > sy,
+        IntCC::SignedLessThanOrEqual => sx <= sy,
+        IntCC::UnsignedLessThan => ux < uy,
+        IntCC::UnsignedGreaterThanOrEqual => ux >= uy,
+        IntCC::UnsignedGreaterThan => ux > uy,
+        IntCC::UnsignedLessThanOrEqual => ux <= uy,
+    }
+}
+
+/// Evaluate the binary operation `opcode` on `bits`-wide integers.
+///
+/// Returns `None` if `opcode` isn't a binary integer operation that can be evaluated.
+pub fn fold_binary(opcode: Opcode, bits: u16, x: i64, y: i64) -> Option<i64> {
+    // Shift amounts are taken modulo the number of bits.
+    let amount = (y as u32) & (u32::from(bits) - 1);
+    Some(match opcode {
+        Opcode::Iadd | Opcode::IaddImm => x.wrapping_add(y),
+        Opcode::Isub => x.wrapping_sub(y),
+        Opcode::IrsubImm => y.wrapping_sub(x),
+        Opcode::Imul | Opcode::ImulImm => x.wrapping_mul(y),
+        Opcode::Band | Opcode::BandImm => x & y,
+        Opcode::Bor | Opcode::BorImm => x | y,
+        Opcode::Bxor | Opcode::BxorImm => x ^ y,
+        Opcode::Ishl | Opcode::IshlImm => x.wrapping_shl(amount),
+        Opcode::Ushr | Opcode::UshrImm => zero_extend(x, bits).wrapping_shr(amount),
+        Opcode::Sshr | Opcode::SshrImm => sign_extend(x, bits).wrapping_shr(amount),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn extend() {
+        assert_eq!(sign_extend(0xff, 8), -1);
+        assert_eq!(zero_extend(-1, 8), 0xff);
+        assert_eq!(sign_extend(-1, 64), -1);
+        assert_eq!(zero_extend(-1, 64), -1);
+    }
+
+    #[test]
+    fn compare() {
+        assert!(icmp(IntCC::Equal, 0x1ff, -1, 8));
+        assert!(icmp(IntCC::SignedLessThan, 0xff, 0, 8));
+        assert!(icmp(IntCC::UnsignedGreaterThan, 0xff, 0, 8));
+        assert!(!icmp(IntCC::UnsignedLessThan, -1, 1, 64));
+    }
+
+    #[test]
+    fn binary() {
+        assert_eq!(fold_binary(Opcode::Iadd, 32, 1, 2), Some(3));
+        assert_eq!(fold_binary(Opcode::IrsubImm, 32, 1, 2), Some(1));
+        assert_eq!(fold_binary(Opcode::IshlImm, 8, 1, 9), Some(2));
+        assert_eq!(fold_binary(Opcode::Ushr, 8, -128, 7), Some(1));
+        assert_eq!(fold_binary(Opcode::Sshr, 8, 0x80, 7), Some(-1));
+        assert_eq!(fold_binary(Opcode::Udiv, 32, 4, 2), None);
+    }
+}
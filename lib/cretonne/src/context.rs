@@ -9,22 +9,39 @@
 //! contexts concurrently. Typically, you would have one context per compilation thread and only a
 //! single ISA instance.
 
-use binemit::{CodeOffset, relax_branches, MemoryCodeSink, RelocSink};
+use binemit::{CodeOffset, Relaxation, function_alignment, shrink_instructions, call_site_table,
+              CallSite, MemoryCodeSink, RelocSink, TrapSink, PatchSink, AnnotationSink};
+use bounds_checks::do_bounds_checks;
+use cold_ebbs::move_cold_ebbs;
+use copy_propagation::CopyPropagation;
+use critical_edges::split_critical_edges;
+use debug_values::{resolve_debug_values, DebugValueLoc};
+use deopt::{resolve_deopt_points, DeoptPoint};
 use dominator_tree::DominatorTree;
+use dump::IrDump;
+use ebb_counters::insert_ebb_counters;
 use flowgraph::ControlFlowGraph;
-use ir::Function;
+use ir::{Ebb, Function, GlobalVar};
+use ir::stackslot::StackSize;
 use loop_analysis::LoopAnalysis;
 use isa::TargetIsa;
-use legalize_function;
+use legalizer::{legalize_function_with, CustomLegalizations};
 use regalloc;
 use result::{CtonError, CtonResult};
+use sccp::Sccp;
 use settings::{FlagsOrIsa, OptLevel};
 use unreachable_code::eliminate_unreachable_code;
 use verifier;
-use simple_gvn::do_simple_gvn;
+use simple_gvn::SimpleGvn;
+use stack_layout::FrameLayout;
 use licm::do_licm;
+use postopt::do_postopt;
 use preopt::do_preopt;
+use slp::do_slp;
+use strength_reduction::do_strength_reduction;
+use unroll::do_unroll;
 use timing;
+use std::vec::Vec;
 
 /// Persistent data structures and compilation pipeline.
 pub struct Context {
@@ -42,6 +59,74 @@ pub struct Context {
 
     /// Loop analysis of `func`.
     pub loop_analysis: LoopAnalysis,
+
+    /// The global variable whose address is the start of the EBB execution counter table.
+    ///
+    /// This must be set by the embedder when the `enable_ebb_counters` setting is enabled. It
+    /// refers to a global variable in `func`, so it is reset by `clear()`.
+    pub ebb_counter_table: Option<GlobalVar>,
+
+    /// The layout of the EBB execution counter table: counter `i` counts the executions of
+    /// `ebb_counters[i]`, and it is found at `i` times the pointer size from the start of the
+    /// table.
+    ///
+    /// This is computed by `compile` when the `enable_ebb_counters` setting is enabled.
+    pub ebb_counters: Vec<Ebb>,
+
+    /// Locations of the source-level variables described by `debug_value` instructions in `func`.
+    ///
+    /// This is computed by `compile`, and it is empty until then.
+    pub debug_values: Vec<DebugValueLoc>,
+
+    /// Value locations at the `deopt_point` instructions in `func`.
+    ///
+    /// This is computed by `compile`, and it is empty until then.
+    pub deopt_points: Vec<DeoptPoint>,
+
+    /// Call-site table for the `try_call` instructions in `func`.
+    ///
+    /// This is computed by `compile`, and it is empty until then.
+    pub call_sites: Vec<CallSite>,
+
+    /// Legalization actions that take precedence over the ones defined by the target ISA.
+    ///
+    /// This is not affected by `clear()`.
+    pub custom_legalizations: CustomLegalizations,
+
+    /// Optional dumping of the IL after the compilation passes.
+    ///
+    /// This is not affected by `clear()`, so it applies to all the functions compiled with this
+    /// context.
+    pub dump: Option<IrDump>,
+
+    /// GVN context.
+    gvn: SimpleGvn,
+
+    /// Constant propagation context.
+    sccp: Sccp,
+
+    /// Branch relaxation context.
+    relaxation: Relaxation,
+
+    /// Copy propagation context.
+    copy_propagation: CopyPropagation,
+}
+
+/// Information about a function compiled by `Context::compile_and_emit`.
+///
+/// There are no jump table offsets because jump tables are not emitted with the code. The
+/// legalizer expands `br_table` instructions into a sequence of conditional branches, so the
+/// machine code never refers to a jump table.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CompiledFunctionInfo {
+    /// Size of the function's machine code in bytes.
+    pub code_size: CodeOffset,
+
+    /// Required alignment of the start of the machine code in bytes.
+    pub alignment: CodeOffset,
+
+    /// Size of the function's stack frame in bytes, not including the return address.
+    pub frame_size: StackSize,
 }
 
 impl Context {
@@ -64,6 +149,17 @@ impl Context {
             domtree: DominatorTree::new(),
             regalloc: regalloc::Context::new(),
             loop_analysis: LoopAnalysis::new(),
+            ebb_counter_table: None,
+            ebb_counters: Vec::new(),
+            debug_values: Vec::new(),
+            deopt_points: Vec::new(),
+            call_sites: Vec::new(),
+            custom_legalizations: CustomLegalizations::new(),
+            dump: None,
+            gvn: SimpleGvn::new(),
+            sccp: Sccp::new(),
+            relaxation: Relaxation::new(),
+            copy_propagation: CopyPropagation::new(),
         }
     }
 
@@ -74,6 +170,15 @@ impl Context {
         self.domtree.clear();
         self.regalloc.clear();
         self.loop_analysis.clear();
+        self.ebb_counter_table = None;
+        self.ebb_counters.clear();
+        self.debug_values.clear();
+        self.deopt_points.clear();
+        self.call_sites.clear();
+        self.gvn.clear();
+        self.sccp.clear();
+        self.relaxation.clear();
+        self.copy_propagation.clear();
     }
 
     /// Compile the function.
@@ -87,10 +192,28 @@ impl Context {
         let _tt = timing::compile();
         self.verify_if(isa)?;
 
+        if isa.flags().enable_ebb_counters() {
+            self.insert_ebb_counters(isa)?;
+        }
+
+        let opt_level = isa.flags().opt_level();
         self.compute_cfg();
         self.preopt(isa)?;
+        if opt_level == OptLevel::Best {
+            self.sccp(isa)?;
+            self.compute_domtree();
+            self.compute_loop_analysis();
+            if isa.flags().unroll_budget() > 0 {
+                self.unroll(isa)?;
+            }
+            self.strength_reduction(isa)?;
+            self.bounds_checks(isa)?;
+        }
+        if opt_level == OptLevel::Best && isa.flags().enable_slp() {
+            self.slp(isa)?;
+        }
         self.legalize(isa)?;
-        if isa.flags().opt_level() == OptLevel::Best {
+        if opt_level == OptLevel::Best || opt_level == OptLevel::MinSize {
             self.compute_domtree();
             /* TODO: Re-enable LICM.
             self.compute_loop_analysis();
@@ -98,11 +221,51 @@ impl Context {
             */
             self.simple_gvn(isa)?;
         }
+        if opt_level != OptLevel::Fastest {
+            self.postopt(isa)?;
+        }
         self.compute_domtree();
         self.eliminate_unreachable_code(isa)?;
+        self.move_cold_ebbs(isa)?;
         self.regalloc(isa)?;
+        if opt_level != OptLevel::Fastest {
+            self.propagate_copies(isa)?;
+        }
         self.prologue_epilogue(isa)?;
-        self.relax_branches(isa)
+        if opt_level != OptLevel::Fastest {
+            self.shrink_instructions(isa)?;
+        }
+        let code_size = self.relax_branches(isa)?;
+        self.resolve_debug_values(isa);
+        self.resolve_deopt_points(isa);
+        self.build_call_site_table(isa);
+        Ok(code_size)
+    }
+
+    /// Compile the function and emit its machine code.
+    ///
+    /// This runs `compile` followed by `emit_to_memory`. The machine code is appended to `code`
+    /// which grows as needed, and relocations, trap sites, and patchable call sites are emitted
+    /// into `relocs`, `traps`, and `patches` with offsets relative to the start of the function.
+    /// Embedders must place the code at an address that is a multiple of the returned alignment.
+    pub fn compile_and_emit(
+        &mut self,
+        isa: &TargetIsa,
+        code: &mut Vec<u8>,
+        relocs: &mut RelocSink,
+        traps: &mut TrapSink,
+        patches: &mut PatchSink,
+    ) -> Result<CompiledFunctionInfo, CtonError> {
+        let code_size = self.compile(isa)?;
+        let start = code.len();
+        code.resize(start + code_size as usize, 0);
+        self.emit_to_memory(code[start..].as_mut_ptr(), relocs, traps, patches, isa);
+
+        Ok(CompiledFunctionInfo {
+            code_size,
+            alignment: function_alignment(&self.func, isa),
+            frame_size: self.func.stack_slots.frame_size.unwrap_or(0),
+        })
     }
 
     /// Emit machine code directly into raw memory.
@@ -110,10 +273,48 @@ impl Context {
     /// Write all of the function's machine code to the memory at `mem`. The size of the machine
     /// code is returned by `compile` above.
     ///
-    /// The machine code is not relocated. Instead, any relocations are emitted into `relocs`.
-    pub fn emit_to_memory(&self, mem: *mut u8, relocs: &mut RelocSink, isa: &TargetIsa) {
+    /// The machine code is not relocated. Instead, any relocations are emitted into `relocs`, and
+    /// the offsets and trap codes of instructions that can trap are emitted into `traps`. The
+    /// offsets of patchable calls are emitted into `patches`.
+    pub fn emit_to_memory(
+        &self,
+        mem: *mut u8,
+        relocs: &mut RelocSink,
+        traps: &mut TrapSink,
+        patches: &mut PatchSink,
+        isa: &TargetIsa,
+    ) {
         let _tt = timing::binemit();
-        isa.emit_function(&self.func, &mut MemoryCodeSink::new(mem, relocs));
+        isa.emit_function(&self.func, &mut MemoryCodeSink::new(mem, relocs, traps, patches));
+    }
+
+    /// Emit machine code directly into raw memory, and report the code range of every instruction
+    /// to `annotations`.
+    ///
+    /// This is the same as `emit_to_memory`, but the code ranges make it possible to match the
+    /// machine code up with the IR instructions when debugging.
+    pub fn emit_to_memory_annotated(
+        &self,
+        mem: *mut u8,
+        relocs: &mut RelocSink,
+        traps: &mut TrapSink,
+        patches: &mut PatchSink,
+        annotations: &mut AnnotationSink,
+        isa: &TargetIsa,
+    ) {
+        let _tt = timing::binemit();
+        isa.emit_function(
+            &self.func,
+            &mut MemoryCodeSink::with_annotations(mem, relocs, traps, patches, annotations),
+        );
+    }
+
+    /// Get the final layout of the function's stack frame.
+    ///
+    /// This describes the frame size, the registers saved by the prologue, and the offsets of all
+    /// the stack slots. Returns `None` if the function hasn't been compiled yet.
+    pub fn frame_layout(&self) -> Option<FrameLayout> {
+        FrameLayout::new(&self.func)
     }
 
     /// Run the verifier on the function.
@@ -138,6 +339,17 @@ impl Context {
         verifier::verify_locations(isa, &self.func, None)
     }
 
+    /// Run the locations verifier on the function with a freshly computed liveness analysis.
+    ///
+    /// In addition to the checks done by `verify_locations`, this checks that no two values
+    /// assigned to overlapping registers or the same spill slot are live at the same time.
+    pub fn verify_locations_with_liveness(&mut self, isa: &TargetIsa) -> verifier::Result {
+        self.compute_cfg();
+        let mut liveness = regalloc::liveness::Liveness::new();
+        liveness.compute(isa, &mut self.func, &self.cfg);
+        verifier::verify_locations(isa, &self.func, Some(&liveness))
+    }
+
     /// Run the locations verifier only if the `enable_verifier` setting is true.
     pub fn verify_locations_if(&self, isa: &TargetIsa) -> CtonResult {
         if isa.flags().enable_verifier() {
@@ -147,20 +359,125 @@ impl Context {
         }
     }
 
+    /// Insert an execution counter increment at the top of every EBB.
+    ///
+    /// The counters are addressed off `ebb_counter_table`, and their layout is written to
+    /// `ebb_counters`. Returns `CtonError::InvalidInput` if no counter table was supplied.
+    pub fn insert_ebb_counters(&mut self, isa: &TargetIsa) -> CtonResult {
+        let table = self.ebb_counter_table.ok_or(CtonError::InvalidInput)?;
+        self.ebb_counters.clear();
+        insert_ebb_counters(&mut self.func, isa, table, &mut self.ebb_counters);
+        self.dump_after("ebb_counters", Some(isa));
+        self.verify_if(isa)
+    }
+
     /// Perform pre-legalization rewrites on the function.
     pub fn preopt(&mut self, isa: &TargetIsa) -> CtonResult {
         do_preopt(&mut self.func);
+        self.dump_after("preopt", Some(isa));
+        self.verify_if(isa)?;
+        Ok(())
+    }
+
+    /// Perform post-legalization rewrites on the function.
+    pub fn postopt(&mut self, isa: &TargetIsa) -> CtonResult {
+        do_postopt(&mut self.func, &self.cfg, isa);
+        self.dump_after("postopt", Some(isa));
         self.verify_if(isa)?;
         Ok(())
     }
 
+    /// Pack adjacent scalar operations into vector operations supported by `isa`.
+    pub fn slp(&mut self, isa: &TargetIsa) -> CtonResult {
+        do_slp(&mut self.func, isa);
+        self.dump_after("slp", Some(isa));
+        self.verify_if(isa)
+    }
+
+    /// Perform sparse conditional constant propagation.
+    ///
+    /// The control flow graph is recomputed if any branches are folded or EBBs removed. The
+    /// dominator tree and loop analysis are cleared in that case.
+    pub fn sccp<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
+        let fisa = fisa.into();
+        if self.sccp.run(&mut self.func) {
+            self.compute_cfg();
+            self.domtree.clear();
+            self.loop_analysis.clear();
+        }
+        self.dump_after("sccp", fisa.isa);
+        self.verify_if(fisa)
+    }
+
+    /// Completely unroll small loops with a constant trip count.
+    ///
+    /// The control flow graph, dominator tree, and loop analysis must be valid. They are
+    /// recomputed if any loops are unrolled.
+    pub fn unroll<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
+        let fisa = fisa.into();
+        if do_unroll(
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+            u32::from(fisa.flags.unroll_budget()),
+        )
+        {
+            self.compute_cfg();
+            self.compute_domtree();
+            self.compute_loop_analysis();
+        }
+        self.dump_after("unroll", fisa.isa);
+        self.verify_if(fisa)
+    }
+
+    /// Replace multiplications of induction variables with new induction variables.
+    ///
+    /// The control flow graph, dominator tree, and loop analysis must be valid.
+    pub fn strength_reduction<'a, FOI>(&mut self, fisa: FOI) -> CtonResult
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        let fisa = fisa.into();
+        do_strength_reduction(
+            &mut self.func,
+            &self.cfg,
+            &self.domtree,
+            &self.loop_analysis,
+        );
+        self.dump_after("strength_reduction", fisa.isa);
+        self.verify_if(fisa)
+    }
+
+    /// Combine heap bounds checks and hoist loop-invariant ones out of loops.
+    ///
+    /// The control flow graph, dominator tree, and loop analysis must be valid. They are updated
+    /// when loop preheaders are created.
+    pub fn bounds_checks<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
+        let fisa = fisa.into();
+        do_bounds_checks(
+            &mut self.func,
+            &mut self.cfg,
+            &mut self.domtree,
+            &mut self.loop_analysis,
+        );
+        self.dump_after("bounds_checks", fisa.isa);
+        self.verify_if(fisa)
+    }
+
     /// Run the legalizer for `isa` on the function.
     pub fn legalize(&mut self, isa: &TargetIsa) -> CtonResult {
         // Legalization invalidates the domtree and loop_analysis by mutating the CFG.
         // TODO: Avoid doing this when legalization doesn't actually mutate the CFG.
         self.domtree.clear();
         self.loop_analysis.clear();
-        legalize_function(&mut self.func, &mut self.cfg, isa);
+        legalize_function_with(
+            &mut self.func,
+            &mut self.cfg,
+            isa,
+            &self.custom_legalizations,
+        );
+        self.dump_after("legalize", Some(isa));
         self.verify_if(isa)
     }
 
@@ -191,18 +508,37 @@ impl Context {
 
     /// Perform simple GVN on the function.
     pub fn simple_gvn<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
-        do_simple_gvn(&mut self.func, &mut self.cfg, &mut self.domtree);
+        let fisa = fisa.into();
+        self.gvn.run(&mut self.func, &mut self.cfg, &mut self.domtree);
+        self.dump_after("gvn", fisa.isa);
         self.verify_if(fisa)
     }
 
     /// Perform LICM on the function.
     pub fn licm<'a, FOI: Into<FlagsOrIsa<'a>>>(&mut self, fisa: FOI) -> CtonResult {
+        let fisa = fisa.into();
         do_licm(
             &mut self.func,
             &mut self.cfg,
             &mut self.domtree,
             &mut self.loop_analysis,
         );
+        self.dump_after("licm", fisa.isa);
+        self.verify_if(fisa)
+    }
+
+    /// Split the critical edges in the CFG.
+    ///
+    /// The control flow graph and dominator tree must be valid, and they are updated as the edges
+    /// are split. The loop analysis is invalidated.
+    pub fn split_critical_edges<'a, FOI>(&mut self, fisa: FOI) -> CtonResult
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        let fisa = fisa.into();
+        split_critical_edges(&mut self.func, &mut self.cfg, &mut self.domtree);
+        self.loop_analysis.clear();
+        self.dump_after("critical_edges", fisa.isa);
         self.verify_if(fisa)
     }
 
@@ -211,7 +547,20 @@ impl Context {
     where
         FOI: Into<FlagsOrIsa<'a>>,
     {
+        let fisa = fisa.into();
         eliminate_unreachable_code(&mut self.func, &mut self.cfg, &self.domtree);
+        self.dump_after("unreachable_code", fisa.isa);
+        self.verify_if(fisa)
+    }
+
+    /// Move EBBs calling cold functions to the end of the layout.
+    pub fn move_cold_ebbs<'a, FOI>(&mut self, fisa: FOI) -> CtonResult
+    where
+        FOI: Into<FlagsOrIsa<'a>>,
+    {
+        let fisa = fisa.into();
+        move_cold_ebbs(&mut self.func);
+        self.dump_after("cold_ebbs", fisa.isa);
         self.verify_if(fisa)
     }
 
@@ -222,23 +571,77 @@ impl Context {
             &mut self.func,
             &self.cfg,
             &mut self.domtree,
-        )
+        )?;
+        self.dump_after("regalloc", Some(isa));
+        Ok(())
+    }
+
+    /// Remove register copies made redundant by the register assignment.
+    pub fn propagate_copies(&mut self, isa: &TargetIsa) -> CtonResult {
+        self.copy_propagation.run(&mut self.func, isa);
+        self.dump_after("copy_propagation", Some(isa));
+        self.verify_if(isa)?;
+        self.verify_locations_if(isa)
     }
 
     /// Insert prologue and epilogues after computing the stack frame layout.
     pub fn prologue_epilogue(&mut self, isa: &TargetIsa) -> CtonResult {
         isa.prologue_epilogue(&mut self.func)?;
+        self.dump_after("prologue_epilogue", Some(isa));
         self.verify_if(isa)?;
         self.verify_locations_if(isa)?;
         Ok(())
     }
 
+    /// Pick the shortest instruction encodings allowed by the register assignment.
+    pub fn shrink_instructions(&mut self, isa: &TargetIsa) -> CtonResult {
+        shrink_instructions(&mut self.func, isa);
+        self.dump_after("shrink_instructions", Some(isa));
+        self.verify_if(isa)?;
+        self.verify_locations_if(isa)
+    }
+
     /// Run the branch relaxation pass and return the final code size.
     pub fn relax_branches(&mut self, isa: &TargetIsa) -> Result<CodeOffset, CtonError> {
-        let code_size = relax_branches(&mut self.func, isa)?;
+        let code_size = self.relaxation.run(&mut self.func, isa)?;
+        self.dump_after("relax_branches", Some(isa));
         self.verify_if(isa)?;
         self.verify_locations_if(isa)?;
 
         Ok(code_size)
     }
+
+    /// Resolve the locations of the `debug_value` instructions in the function into
+    /// `debug_values`.
+    ///
+    /// This must run after register allocation and branch relaxation.
+    pub fn resolve_debug_values(&mut self, isa: &TargetIsa) {
+        self.debug_values.clear();
+        resolve_debug_values(&self.func, isa, &mut self.debug_values);
+    }
+
+    /// Resolve the value locations at the `deopt_point` instructions in the function into
+    /// `deopt_points`.
+    ///
+    /// This must run after register allocation and branch relaxation.
+    pub fn resolve_deopt_points(&mut self, isa: &TargetIsa) {
+        self.deopt_points.clear();
+        resolve_deopt_points(&self.func, isa, &mut self.deopt_points);
+    }
+
+    /// Build the call-site table for the `try_call` instructions in the function into
+    /// `call_sites`.
+    ///
+    /// This must run after branch relaxation.
+    pub fn build_call_site_table(&mut self, isa: &TargetIsa) {
+        self.call_sites.clear();
+        call_site_table(&self.func, isa, &mut self.call_sites);
+    }
+
+    /// Dump the function after `pass` if requested.
+    fn dump_after(&mut self, pass: &str, isa: Option<&TargetIsa>) {
+        if let Some(ref mut dump) = self.dump {
+            dump.after_pass(pass, &self.func, isa);
+        }
+    }
 }
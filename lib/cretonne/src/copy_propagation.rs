@@ -0,0 +1,289 @@
+//! Copy propagation after register allocation.
+//!
+//! The register allocator sometimes leaves behind copies that don't need to move any bits:
+//!
+//! - A `copy` or `regmove` whose source and destination registers are the same. These self-moves
+//!   are simply removed.
+//! - A `copy` whose argument dies at the copy, while the source register stays free for the rest
+//!   of the copy's live range. The copied value is moved into the source register, and all its
+//!   uses read the argument instead.
+//!
+//! Both cases replace the copied value with its argument, so the copy can be removed.
+//!
+//! The pass works on the final register assignment. Liveness is computed from the instruction
+//! operands and value locations directly, so forward propagation is limited to values that are
+//! local to an EBB.
+
+use cursor::{Cursor, FuncCursor};
+use entity::EntityMap;
+use ir::{Ebb, Function, Inst, InstructionData, Opcode, Value, ValueDef, ValueLoc};
+use isa::{regs_overlap, EncInfo, RegUnit, TargetIsa};
+use packed_option::PackedOption;
+use regalloc::RegDiversions;
+use timing;
+
+/// Remove register copies that are made redundant by the register assignment in `func`.
+pub fn propagate_copies(func: &mut Function, isa: &TargetIsa) {
+    CopyPropagation::new().run(func, isa)
+}
+
+/// Persistent data structures for the copy propagation pass.
+///
+/// This is the same as `propagate_copies`, but the data structures are kept around so they can be
+/// reused for the next function.
+pub struct CopyPropagation {
+    /// The uses of each value.
+    uses: Uses,
+    /// Diversions at the current instruction.
+    divert: RegDiversions,
+    /// Diversions used when scanning ahead of the current instruction.
+    scratch: RegDiversions,
+}
+
+impl CopyPropagation {
+    /// Create a new copy propagation context.
+    pub fn new() -> Self {
+        Self {
+            uses: Uses::new(),
+            divert: RegDiversions::new(),
+            scratch: RegDiversions::new(),
+        }
+    }
+
+    /// Clear all data structures in this context.
+    pub fn clear(&mut self) {
+        self.uses.clear();
+        self.divert.clear();
+        self.scratch.clear();
+    }
+
+    /// Remove register copies that are made redundant by the register assignment in `func`.
+    pub fn run(&mut self, func: &mut Function, isa: &TargetIsa) {
+        let _tt = timing::copy_propagation();
+        let encinfo = isa.encoding_info();
+        self.uses.compute(func);
+        let mut changed = false;
+
+        let mut pos = FuncCursor::new(func);
+        while let Some(_ebb) = pos.next_ebb() {
+            self.divert.clear();
+            while let Some(inst) = pos.next_inst() {
+                let removed = match pos.func.dfg[inst] {
+                    InstructionData::RegMove {
+                        opcode: Opcode::Regmove,
+                        src,
+                        dst,
+                        ..
+                    } => src == dst,
+                    InstructionData::Unary {
+                        opcode: Opcode::Copy,
+                        arg,
+                    } => {
+                        let propagated = self.propagate_copy(pos.func, &encinfo, inst, arg);
+                        if propagated {
+                            let result = pos.func.dfg.first_result(inst);
+                            pos.func.dfg.clear_results(inst);
+                            pos.func.dfg.change_to_alias(result, arg);
+                            changed = true;
+                        }
+                        propagated
+                    }
+                    _ => false,
+                };
+                if removed {
+                    dbg!("Removing {}", pos.func.dfg.display_inst(inst, isa));
+                    pos.remove_inst_and_step_back();
+                } else {
+                    self.divert.apply(&pos.func.dfg[inst]);
+                }
+            }
+        }
+
+        if !changed {
+            return;
+        }
+        // The later passes look up the locations of instruction arguments directly, so make sure
+        // they don't see the removed values.
+        let mut pos = FuncCursor::new(func);
+        while let Some(_ebb) = pos.next_ebb() {
+            while let Some(inst) = pos.next_inst() {
+                pos.func.dfg.resolve_aliases_in_arguments(inst);
+            }
+        }
+    }
+
+    /// Check if the copy `inst` of `arg` can be removed.
+    ///
+    /// This changes the location of the copied value to the register holding `arg` when needed.
+    fn propagate_copy(
+        &mut self,
+        func: &mut Function,
+        encinfo: &EncInfo,
+        inst: Inst,
+        arg: Value,
+    ) -> bool {
+        let arg = func.dfg.resolve_aliases(arg);
+        let result = func.dfg.first_result(inst);
+        if !func.encodings[inst].is_legal() || self.divert.diversion(arg).is_some() ||
+            self.divert.diversion(result).is_some()
+        {
+            return false;
+        }
+        let (src, dst) = match (func.locations[arg], func.locations[result]) {
+            (ValueLoc::Reg(src), ValueLoc::Reg(dst)) => (src, dst),
+            _ => return false,
+        };
+        if src == dst {
+            return true;
+        }
+
+        // Forward propagation: `arg` must die at the copy, and `result` must be able to live in
+        // `src` for the rest of its live range.
+        if !self.uses.is_local(arg) || self.uses.last_use(arg) != Some(inst) ||
+            !self.uses.is_local(result)
+        {
+            return false;
+        }
+        let end = match self.uses.last_use(result) {
+            Some(end) => end,
+            None => return false,
+        };
+        func.locations[result] = ValueLoc::Reg(src);
+        if self.reaches_end_in_reg(func, encinfo, inst, end, result, src) {
+            true
+        } else {
+            func.locations[result] = ValueLoc::Reg(dst);
+            false
+        }
+    }
+
+    /// Check that `value` defined by the copy `inst` can stay in `reg` until its last use at
+    /// `end`.
+    ///
+    /// No other value can be placed in `reg` in the meantime, and all uses of `value` must accept
+    /// `reg` as the location of `value`.
+    fn reaches_end_in_reg(
+        &mut self,
+        func: &Function,
+        encinfo: &EncInfo,
+        inst: Inst,
+        end: Inst,
+        value: Value,
+        reg: RegUnit,
+    ) -> bool {
+        let rc = match encinfo.operand_constraints(func.encodings[inst]) {
+            Some(constraints) => constraints.outs[0].regclass,
+            None => return false,
+        };
+        self.scratch.clone_from(&self.divert);
+        let mut cur = func.layout.next_inst(inst);
+        while let Some(inst) = cur {
+            let opcode = func.dfg[inst].opcode();
+            // All registers are clobbered by a call.
+            if opcode.is_call() {
+                return false;
+            }
+
+            let is_use = func.dfg.inst_args(inst).iter().any(|&arg| {
+                func.dfg.resolve_aliases(arg) == value
+            });
+            if is_use {
+                // Branch and return arguments are assigned to fixed registers, and moving `value`
+                // would invalidate a diversion.
+                if opcode.is_branch() || opcode.is_return() || opcode == Opcode::Regmove ||
+                    opcode == Opcode::Regspill
+                {
+                    return false;
+                }
+                match encinfo.operand_constraints(func.encodings[inst]) {
+                    Some(constraints) if constraints.satisfied(inst, &self.scratch, func) => {}
+                    _ => return false,
+                }
+            }
+            if inst == end {
+                return true;
+            }
+
+            // Values defined at the last use can reuse the register, but nothing else can.
+            match func.dfg[inst] {
+                InstructionData::RegMove { dst, .. } |
+                InstructionData::RegFill { dst, .. } if regs_overlap(rc, reg, rc, dst) => {
+                    return false;
+                }
+                _ => {}
+            }
+            if let Some(constraints) = encinfo.operand_constraints(func.encodings[inst]) {
+                for (&def, op) in func.dfg.inst_results(inst).iter().zip(constraints.outs) {
+                    if let ValueLoc::Reg(def_reg) = func.locations[def] {
+                        if regs_overlap(rc, reg, op.regclass, def_reg) {
+                            return false;
+                        }
+                    }
+                }
+            }
+
+            self.scratch.apply(&func.dfg[inst]);
+            cur = func.layout.next_inst(inst);
+        }
+        false
+    }
+}
+
+/// The uses of each value in a function.
+struct Uses {
+    /// The last use of each value in layout order, if all its uses are in the EBB where the value
+    /// is defined.
+    last_local: EntityMap<Value, PackedOption<Inst>>,
+
+    /// Values that are used outside the EBB where they are defined.
+    global: EntityMap<Value, bool>,
+}
+
+impl Uses {
+    fn new() -> Self {
+        Self {
+            last_local: EntityMap::new(),
+            global: EntityMap::new(),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.last_local.clear();
+        self.global.clear();
+    }
+
+    /// Collect the uses of all values in `func`.
+    fn compute(&mut self, func: &Function) {
+        self.clear();
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                for &arg in func.dfg.inst_args(inst) {
+                    let arg = func.dfg.resolve_aliases(arg);
+                    if def_ebb(func, arg) == Some(ebb) {
+                        self.last_local[arg] = inst.into();
+                    } else {
+                        self.global[arg] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Are all the uses of `value` in the EBB where it is defined?
+    fn is_local(&self, value: Value) -> bool {
+        !self.global[value]
+    }
+
+    /// Get the last use of a local value.
+    fn last_use(&self, value: Value) -> Option<Inst> {
+        self.last_local[value].expand()
+    }
+}
+
+/// Get the EBB where `value` is defined.
+fn def_ebb(func: &Function, value: Value) -> Option<Ebb> {
+    match func.dfg.value_def(value) {
+        ValueDef::Result(inst, _) => func.layout.inst_ebb(inst),
+        ValueDef::Param(ebb, _) => Some(ebb),
+    }
+}
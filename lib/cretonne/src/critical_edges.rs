@@ -0,0 +1,228 @@
+//! Critical edge splitting.
+//!
+//! A critical edge goes from an EBB with several outgoing edges to an EBB with several incoming
+//! edges. Code can't be placed on such an edge, neither at the end of the predecessor nor at the
+//! top of the successor, without also executing it on other paths. Passes that need to place code
+//! on CFG edges, like spill code, safepoint polls, or profiling counters, can split the edge first
+//! by redirecting the branch to a new EBB which jumps to the original destination.
+//!
+//! The edges from a `try_call` to its landing pad are never split because the unwinder transfers
+//! control to the landing pad directly.
+
+use cursor::{Cursor, FuncCursor};
+use dominator_tree::DominatorTree;
+use flowgraph::{BasicBlock, ControlFlowGraph};
+use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, JumpTable, Opcode, Type, Value};
+use ir::instructions::BranchInfo;
+use std::vec::Vec;
+use timing;
+
+/// Split all the critical edges in `func`.
+///
+/// The control flow graph and dominator tree must be valid. They are updated incrementally as the
+/// edges are split. Returns the number of edges split.
+pub fn split_critical_edges(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
+) -> usize {
+    let _tt = timing::critical_edges();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+
+    let mut edges = Vec::new();
+    for ebb in func.layout.ebbs() {
+        if num_edges_from(func, ebb) < 2 {
+            continue;
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            if func.dfg[inst].opcode() == Opcode::TryCall {
+                continue;
+            }
+            match func.dfg.analyze_branch(inst) {
+                BranchInfo::NotABranch => {}
+                BranchInfo::SingleDest(dest, _) => edges.push(((ebb, inst), dest)),
+                BranchInfo::Table(jt) => {
+                    for dest in table_destinations(func, jt) {
+                        edges.push(((ebb, inst), dest));
+                    }
+                }
+            }
+        }
+    }
+
+    // Splitting an edge doesn't change the number of predecessors of its destination.
+    let mut split = 0;
+    for (pred, succ) in edges {
+        if cfg.pred_iter(succ).nth(1).is_some() {
+            dbg!("Splitting critical edge from {} to {}", pred.1, succ);
+            split_edge(func, cfg, domtree, pred, succ);
+            split += 1;
+        }
+    }
+    split
+}
+
+/// Is the CFG edge from the branch `pred` to `succ` a critical edge?
+///
+/// The edge from a `try_call` to its landing pad is never considered critical since it can't be
+/// split.
+pub fn is_critical_edge(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    pred: BasicBlock,
+    succ: Ebb,
+) -> bool {
+    func.dfg[pred.1].opcode() != Opcode::TryCall && cfg.pred_iter(succ).nth(1).is_some() &&
+        num_edges_from(func, pred.0) > 1
+}
+
+/// Split the CFG edge from the branch `pred` to `succ` by inserting a new EBB.
+///
+/// The branch is redirected to a new EBB which jumps to `succ`, see `insert_forwarding_ebb()`.
+/// If `pred` is a `br_table`, all the table entries branching to `succ` are redirected. A jump
+/// table shared with other `br_table` instructions is copied first, so their destinations don't
+/// change. The control flow graph and dominator tree must be valid, and they are updated
+/// incrementally.
+///
+/// Returns the new EBB.
+pub fn split_edge(
+    func: &mut Function,
+    cfg: &mut ControlFlowGraph,
+    domtree: &mut DominatorTree,
+    pred: BasicBlock,
+    succ: Ebb,
+) -> Ebb {
+    debug_assert!(
+        func.dfg[pred.1].opcode() != Opcode::TryCall,
+        "Can't split the edge to a landing pad"
+    );
+    if let BranchInfo::Table(jt) = func.dfg.analyze_branch(pred.1) {
+        unshare_jump_table(func, pred.1, jt);
+    }
+    let (new_ebb, _) = insert_forwarding_ebb(func, succ);
+    func.change_branch_destination(pred.1, succ, new_ebb);
+    cfg.split_edge(func, pred, succ, new_ebb);
+    domtree.recompute_split_edge(func, cfg, pred, new_ebb);
+    new_ebb
+}
+
+/// Give the `br_table` instruction `inst` its own copy of the jump table `jt` if other `br_table`
+/// instructions use it too.
+pub(crate) fn unshare_jump_table(func: &mut Function, inst: Inst, jt: JumpTable) {
+    let shared = func.layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .any(|other| {
+            other != inst &&
+                match func.dfg[other] {
+                    InstructionData::BranchTable { table, .. } => table == jt,
+                    _ => false,
+                }
+        });
+    if !shared {
+        return;
+    }
+    let copy = func.jump_tables[jt].clone();
+    let new_jt = func.create_jump_table(copy);
+    if let InstructionData::BranchTable { ref mut table, .. } = func.dfg[inst] {
+        *table = new_jt;
+    }
+}
+
+/// Insert a new EBB just before `dest` in the layout which passes its parameters on to `dest`.
+///
+/// The new EBB has the same parameter types as `dest`, and it contains a single jump to `dest`.
+/// Nothing branches to it yet, and the control flow graph and dominator tree are not updated.
+///
+/// Returns the new EBB and its jump instruction.
+pub fn insert_forwarding_ebb(func: &mut Function, dest: Ebb) -> (Ebb, Inst) {
+    let new_ebb = func.dfg.make_ebb();
+    let types: Vec<Type> = func.dfg
+        .ebb_params(dest)
+        .iter()
+        .map(|&param| func.dfg.value_type(param))
+        .collect();
+    let args: Vec<Value> = types
+        .into_iter()
+        .map(|ty| func.dfg.append_ebb_param(new_ebb, ty))
+        .collect();
+    let mut pos = FuncCursor::new(func).at_top(dest);
+    pos.insert_ebb(new_ebb);
+    let jump = pos.ins().jump(dest, &args);
+    (new_ebb, jump)
+}
+
+/// Count the CFG edges leaving `ebb`, with one edge per destination of a `br_table`.
+fn num_edges_from(func: &Function, ebb: Ebb) -> usize {
+    func.layout
+        .ebb_insts(ebb)
+        .map(|inst| match func.dfg.analyze_branch(inst) {
+            BranchInfo::NotABranch => 0,
+            BranchInfo::SingleDest(..) => 1,
+            BranchInfo::Table(jt) => table_destinations(func, jt).len(),
+        })
+        .sum()
+}
+
+/// Get the distinct destinations of a jump table, including the default destination.
+fn table_destinations(func: &Function, jt: JumpTable) -> Vec<Ebb> {
+    let mut dests: Vec<Ebb> = func.jump_tables[jt].destinations().collect();
+    dests.sort();
+    dests.dedup();
+    dests
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_critical_edge, split_critical_edges};
+    use cursor::{Cursor, FuncCursor};
+    use dominator_tree::DominatorTree;
+    use flowgraph::ControlFlowGraph;
+    use ir::{AbiParam, Function, InstBuilder, types};
+    use settings;
+    use verifier::verify_context;
+
+    #[test]
+    fn diamond_with_shortcut() {
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        func.dfg.append_ebb_param(ebb2, types::I32);
+
+        let (br0, jmp0) = {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            let br0 = cur.ins().brnz(cond, ebb2, &[cond]);
+            let jmp0 = cur.ins().jump(ebb1, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb2, &[cond]);
+
+            cur.insert_ebb(ebb2);
+            cur.ins().return_(&[]);
+            (br0, jmp0)
+        };
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+        let mut domtree = DominatorTree::with_function(&func, &cfg);
+        assert!(is_critical_edge(&func, &cfg, (ebb0, br0), ebb2));
+        assert!(!is_critical_edge(&func, &cfg, (ebb0, jmp0), ebb1));
+
+        assert_eq!(split_critical_edges(&mut func, &mut cfg, &mut domtree), 1);
+        let new_ebb = func.layout.prev_ebb(ebb2).unwrap();
+        assert!(new_ebb != ebb1);
+        assert_eq!(func.dfg[br0].branch_destination(), Some(new_ebb));
+        assert_eq!(func.dfg.ebb_params(new_ebb).len(), 1);
+        assert_eq!(domtree.idom(new_ebb), Some(br0));
+        assert!(!is_critical_edge(&func, &cfg, (ebb0, br0), new_ebb));
+        assert_eq!(split_critical_edges(&mut func, &mut cfg, &mut domtree), 0);
+
+        let flags = settings::Flags::new(&settings::builder());
+        verify_context(&func, &cfg, &domtree, &flags).unwrap();
+    }
+}
@@ -4,6 +4,7 @@
 
 use ir;
 use isa::TargetIsa;
+use std::vec::Vec;
 
 /// The possible positions of a cursor.
 #[derive(Clone, Copy, PartialEq, Eq, Debug)]
@@ -591,6 +592,62 @@ impl<'f> FuncCursor<'f> {
     pub fn ins(&mut self) -> ir::InsertBuilder<&mut FuncCursor<'f>> {
         ir::InsertBuilder::new(self)
     }
+
+    /// Replace `inst` with a sequence of instructions inserted by `build`.
+    ///
+    /// This is the multi-instruction counterpart of `dfg.replace(inst)`:
+    ///
+    /// 1. The cursor is moved to `inst`, and `build` is called to insert the new instructions
+    ///    before it. The new instructions get the source location of `inst`.
+    /// 2. `build` returns the values replacing the results of `inst`, in order. The original
+    ///    result values are changed to aliases of them, so the existing uses don't need to be
+    ///    rewritten.
+    /// 3. `inst` is removed, and the cursor is left at the instruction that followed it.
+    ///
+    /// The source location of the cursor is restored afterwards.
+    ///
+    /// ```
+    /// # use cretonne::ir::{Function, Inst, InstBuilder};
+    /// # use cretonne::cursor::FuncCursor;
+    /// // Expand `v1 = imul_imm v0, 3` as `v1 = iadd (ishl_imm v0, 1), v0`.
+    /// fn expand_imul3(func: &mut Function, inst: Inst) {
+    ///     let arg = func.dfg.inst_args(inst)[0];
+    ///     FuncCursor::new(func).replace_with_multiple(inst, |pos| {
+    ///         let double = pos.ins().ishl_imm(arg, 1);
+    ///         [pos.ins().iadd(double, arg)]
+    ///     });
+    /// }
+    /// ```
+    pub fn replace_with_multiple<F, R>(&mut self, inst: ir::Inst, build: F)
+    where
+        F: FnOnce(&mut Self) -> R,
+        R: AsRef<[ir::Value]>,
+    {
+        let saved_srcloc = self.srcloc;
+        self.goto_inst(inst);
+        self.use_srcloc(inst);
+        let values = build(self);
+        let values = values.as_ref();
+
+        let results: Vec<_> = self.func.dfg.inst_results(inst).to_vec();
+        assert_eq!(
+            results.len(),
+            values.len(),
+            "{} results of {} replaced by {} values",
+            results.len(),
+            inst,
+            values.len()
+        );
+        self.func.dfg.clear_results(inst);
+        for (&old, &new) in results.iter().zip(values) {
+            self.func.dfg.change_to_alias(old, new);
+        }
+
+        // `build` may have moved the cursor.
+        self.goto_inst(inst);
+        self.remove_inst();
+        self.srcloc = saved_srcloc;
+    }
 }
 
 impl<'f> Cursor for FuncCursor<'f> {
@@ -643,11 +700,17 @@ impl<'c, 'f> ir::InstInserterBase<'c> for &'c mut FuncCursor<'f> {
 /// An `EncCursor` can be used to insert instructions that are immediately assigned an encoding.
 /// The cursor holds a mutable reference to the whole function which can be re-borrowed from the
 /// public `pos.func` member.
+///
+/// Sequences of instructions that may not be encodable can be inserted with `encode_batch()`
+/// instead, which encodes them all at the end or leaves the function unchanged.
 pub struct EncCursor<'f> {
     pos: CursorPosition,
     srcloc: ir::SourceLoc,
     built_inst: Option<ir::Inst>,
 
+    // Instructions changed by the current `encode_batch()`, if any.
+    batch: Option<Batch>,
+
     /// The referenced function.
     pub func: &'f mut ir::Function,
 
@@ -662,6 +725,7 @@ impl<'f> EncCursor<'f> {
             pos: CursorPosition::Nowhere,
             srcloc: Default::default(),
             built_inst: None,
+            batch: None,
             func,
             isa,
         }
@@ -695,6 +759,74 @@ impl<'f> EncCursor<'f> {
     pub fn display_inst(&self, inst: ir::Inst) -> ir::dfg::DisplayInst {
         self.func.dfg.display_inst(inst, self.isa)
     }
+
+    /// Insert and replace a batch of instructions with `build`, and then encode them all.
+    ///
+    /// Inside `build`, the instructions inserted with `pos.ins()` are tagged with the cursor's
+    /// source location as usual, but they aren't encoded right away. Existing instructions can be
+    /// rewritten in place with `pos.batch_replace()`. When `build` returns, all the inserted and
+    /// replaced instructions are encoded for `self.isa`.
+    ///
+    /// If any of them can't be encoded, the batch is rolled back: the inserted instructions are
+    /// removed from the layout, the replaced instructions are restored, the cursor is moved back
+    /// to where it was, and `None` is returned. This lets post-legalization passes try a rewrite
+    /// without checking every instruction themselves.
+    pub fn encode_batch<F, R>(&mut self, build: F) -> Option<R>
+    where
+        F: FnOnce(&mut Self) -> R,
+    {
+        assert!(self.batch.is_none(), "encode_batch() can't be nested");
+        let start = self.position();
+        self.batch = Some(Batch::default());
+        let result = build(self);
+        let batch = self.batch.take().expect("batch disappeared");
+
+        let mut insts = batch.inserted.clone();
+        for &(inst, _) in &batch.replaced {
+            insts.push((inst, self.func.dfg.ctrl_typevar(inst)));
+        }
+        let mut encodings = Vec::new();
+        for (inst, ctrl_typevar) in insts {
+            match self.isa.encode(self.func, &self.func.dfg[inst], ctrl_typevar) {
+                Ok(enc) => encodings.push((inst, enc)),
+                Err(_) => {
+                    for &(inst, _) in &batch.inserted {
+                        self.func.layout.remove_inst(inst);
+                    }
+                    for (inst, data) in batch.replaced {
+                        self.func.dfg[inst] = data;
+                    }
+                    self.set_position(start);
+                    return None;
+                }
+            }
+        }
+        for (inst, enc) in encodings {
+            self.func.encodings[inst] = enc;
+        }
+        Some(result)
+    }
+
+    /// Create a builder that rewrites `inst` in place as part of the current `encode_batch()`.
+    ///
+    /// The original instruction is restored if the batch can't be encoded.
+    pub fn batch_replace(&mut self, inst: ir::Inst) -> ir::ReplaceBuilder {
+        self.batch
+            .as_mut()
+            .expect("batch_replace() must be called inside encode_batch()")
+            .replaced
+            .push((inst, self.func.dfg[inst].clone()));
+        self.func.dfg.replace(inst)
+    }
+}
+
+/// Instructions changed by `EncCursor::encode_batch()`.
+#[derive(Default)]
+struct Batch {
+    // Inserted instructions and their controlling type variables.
+    inserted: Vec<(ir::Inst, ir::Type)>,
+    // Instructions replaced in place, and their original data.
+    replaced: Vec<(ir::Inst, ir::InstructionData)>,
 }
 
 impl<'f> Cursor for EncCursor<'f> {
@@ -744,16 +876,32 @@ impl<'c, 'f> ir::InstInserterBase<'c> for &'c mut EncCursor<'f> {
         if !self.srcloc.is_default() {
             self.func.srclocs[inst] = self.srcloc;
         }
+
+        // In a batch, the encoding is assigned when the batch is done.
+        if let Some(ref mut batch) = self.batch {
+            batch.inserted.push((inst, ctrl_typevar));
+            return &mut self.func.dfg;
+        }
+
         // Assign an encoding.
-        // XXX Is there a way to describe this error to the user?
         #[cfg_attr(feature = "cargo-clippy", allow(match_wild_err_arm))]
         match self.isa.encode(
-            &self.func.dfg,
+            self.func,
             &self.func.dfg[inst],
             ctrl_typevar,
         ) {
             Ok(e) => self.func.encodings[inst] = e,
-            Err(_) => panic!("can't encode {}", self.display_inst(inst)),
+            Err(_) => {
+                panic!(
+                    "can't encode {}: {}",
+                    self.display_inst(inst),
+                    self.isa.explain_encoding(
+                        self.func,
+                        &self.func.dfg[inst],
+                        ctrl_typevar,
+                    )
+                )
+            }
         }
 
         &mut self.func.dfg
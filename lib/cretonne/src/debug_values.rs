@@ -0,0 +1,60 @@
+//! Source-level debug values.
+//!
+//! The `debug_value` instruction associates a frontend variable with an SSA value. The instruction
+//! generates no code, but once register allocation and branch relaxation have run, the location
+//! of each such value and the code offset where it becomes valid are known. A debugger can use
+//! this mapping to display source-level variables such as WebAssembly locals.
+
+use binemit::CodeOffset;
+use ir::{Function, InstructionData, Value, ValueLoc};
+use isa::TargetIsa;
+use regalloc::RegDiversions;
+use std::vec::Vec;
+use timing;
+
+/// The location of a source-level variable, as described by a `debug_value` instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugValueLoc {
+    /// The frontend variable number.
+    pub var: u32,
+
+    /// The value holding the variable.
+    pub value: Value,
+
+    /// Code offset from the start of the function where the variable is found in `loc`.
+    pub offset: CodeOffset,
+
+    /// The location of `value` at `offset`, taking register diversions into account.
+    ///
+    /// This is `ValueLoc::Unassigned` when the value doesn't exist in the generated code, for
+    /// example when it was split into multiple values by the legalizer.
+    pub loc: ValueLoc,
+}
+
+/// Resolve the locations of all the `debug_value` instructions in `func`.
+///
+/// The function must have been through register allocation and branch relaxation so that value
+/// locations and EBB offsets are valid. The locations are appended to `out` in layout order.
+pub fn resolve_debug_values(func: &Function, isa: &TargetIsa, out: &mut Vec<DebugValueLoc>) {
+    let _tt = timing::debug_values();
+    let encinfo = isa.encoding_info();
+    let mut divert = RegDiversions::new();
+
+    for ebb in func.layout.ebbs() {
+        divert.clear();
+        let mut offset = func.offsets[ebb];
+        for inst in func.layout.ebb_insts(ebb) {
+            if let InstructionData::DebugValue { arg, var, .. } = func.dfg[inst] {
+                let value = func.dfg.resolve_aliases(arg);
+                out.push(DebugValueLoc {
+                    var: var.into(),
+                    value,
+                    offset,
+                    loc: divert.get(value, &func.locations),
+                });
+            }
+            divert.apply(&func.dfg[inst]);
+            offset += encinfo.bytes(func.encodings[inst]);
+        }
+    }
+}
@@ -0,0 +1,64 @@
+//! Deoptimization metadata.
+//!
+//! A speculative JIT compiler emits optimized code that is only valid while some assumptions
+//! hold. When an assumption fails, the optimized code bails out to an interpreter or to less
+//! optimized code, which must be given the frontend state at the bailout. The `deopt_point`
+//! instruction lists the values needed to reconstruct that state. It generates no code, but once
+//! register allocation and branch relaxation have run, the locations of the values and the code
+//! offset of the point are known and can be recorded in a side table.
+
+use binemit::CodeOffset;
+use ir::{Function, InstructionData, ValueLoc};
+use isa::TargetIsa;
+use regalloc::RegDiversions;
+use std::vec::Vec;
+use timing;
+
+/// The value locations recorded at a `deopt_point` instruction.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeoptPoint {
+    /// The deoptimization point number chosen by the frontend.
+    pub id: u32,
+
+    /// Code offset from the start of the function where the values are found in `locations`.
+    pub offset: CodeOffset,
+
+    /// The location of each `deopt_point` argument, in order, taking register diversions into
+    /// account.
+    ///
+    /// A location is `ValueLoc::Unassigned` when the value doesn't exist in the generated code,
+    /// for example when it was split into multiple values by the legalizer.
+    pub locations: Vec<ValueLoc>,
+}
+
+/// Resolve the value locations of all the `deopt_point` instructions in `func`.
+///
+/// The function must have been through register allocation and branch relaxation so that value
+/// locations and EBB offsets are valid. The points are appended to `out` in layout order.
+pub fn resolve_deopt_points(func: &Function, isa: &TargetIsa, out: &mut Vec<DeoptPoint>) {
+    let _tt = timing::deopt_points();
+    let encinfo = isa.encoding_info();
+    let mut divert = RegDiversions::new();
+
+    for ebb in func.layout.ebbs() {
+        divert.clear();
+        let mut offset = func.offsets[ebb];
+        for inst in func.layout.ebb_insts(ebb) {
+            if let InstructionData::DeoptPoint { id, ref args, .. } = func.dfg[inst] {
+                let locations = args.as_slice(&func.dfg.value_lists)
+                    .iter()
+                    .map(|&arg| {
+                        divert.get(func.dfg.resolve_aliases(arg), &func.locations)
+                    })
+                    .collect();
+                out.push(DeoptPoint {
+                    id: id.into(),
+                    offset,
+                    locations,
+                });
+            }
+            divert.apply(&func.dfg[inst]);
+            offset += encinfo.bytes(func.encodings[inst]);
+        }
+    }
+}
@@ -0,0 +1,246 @@
+//! Structural differences between two functions.
+//!
+//! A `FunctionDiff` compares two versions of the same function, typically before and after a
+//! pass. EBBs and instructions are matched by their entity numbers, so the diff only lists the
+//! entities that were added, removed, or changed instead of a line-based diff of the text.
+
+use ir::{Ebb, Function, Inst};
+use isa::TargetIsa;
+use std::fmt;
+use std::string::{String, ToString};
+use std::vec::Vec;
+use write::{write_ebb_header, write_instruction};
+
+/// A change to a single entity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// The EBB and its instructions only exist in the new function.
+    AddedEbb(Ebb),
+    /// The EBB and its instructions only exist in the old function.
+    RemovedEbb(Ebb),
+    /// The parameters of the EBB changed.
+    ChangedEbb(Ebb),
+    /// The instruction was inserted in its EBB in the new function.
+    AddedInst(Inst),
+    /// The instruction was removed from its EBB in the old function.
+    RemovedInst(Inst),
+    /// The opcode, operands, or results of the instruction changed.
+    ChangedInst(Inst),
+    /// Only the encoding, source location, or result locations of the instruction changed.
+    ChangedEncoding(Inst),
+}
+
+/// The differences between two versions of a function.
+///
+/// Displaying a `FunctionDiff` prints the old version of the changed entities prefixed with `-`
+/// and the new version prefixed with `+`, grouped by EBB.
+pub struct FunctionDiff<'a> {
+    old: &'a Function,
+    new: &'a Function,
+    isa: Option<&'a TargetIsa>,
+    changes: Vec<Change>,
+}
+
+impl<'a> FunctionDiff<'a> {
+    /// Compare `old` and `new`.
+    ///
+    /// Use `isa` to compare and print the encodings and value locations.
+    pub fn new<I: Into<Option<&'a TargetIsa>>>(
+        old: &'a Function,
+        new: &'a Function,
+        isa: I,
+    ) -> FunctionDiff<'a> {
+        let mut diff = FunctionDiff {
+            old,
+            new,
+            isa: isa.into(),
+            changes: Vec::new(),
+        };
+        diff.compute();
+        diff
+    }
+
+    /// Get the list of changes, grouped by EBB.
+    pub fn changes(&self) -> &[Change] {
+        &self.changes
+    }
+
+    /// Are the two functions the same?
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn compute(&mut self) {
+        for ebb in self.new.layout.ebbs() {
+            if !self.old.layout.is_ebb_inserted(ebb) {
+                self.changes.push(Change::AddedEbb(ebb));
+                continue;
+            }
+
+            if self.ebb_header(self.old, ebb) != self.ebb_header(self.new, ebb) {
+                self.changes.push(Change::ChangedEbb(ebb));
+            }
+            for inst in self.old.layout.ebb_insts(ebb) {
+                if self.new.layout.inst_ebb(inst) != Some(ebb) {
+                    self.changes.push(Change::RemovedInst(inst));
+                }
+            }
+            for inst in self.new.layout.ebb_insts(ebb) {
+                if self.old.layout.inst_ebb(inst) != Some(ebb) {
+                    self.changes.push(Change::AddedInst(inst));
+                } else if self.instruction(self.old, inst) != self.instruction(self.new, inst) {
+                    let old_text = self.old.dfg.display_inst(inst, None).to_string();
+                    let new_text = self.new.dfg.display_inst(inst, None).to_string();
+                    self.changes.push(if old_text != new_text {
+                        Change::ChangedInst(inst)
+                    } else {
+                        Change::ChangedEncoding(inst)
+                    });
+                }
+            }
+        }
+
+        for ebb in self.old.layout.ebbs() {
+            if !self.new.layout.is_ebb_inserted(ebb) {
+                self.changes.push(Change::RemovedEbb(ebb));
+            }
+        }
+    }
+
+    fn ebb_header(&self, func: &Function, ebb: Ebb) -> String {
+        let mut s = String::new();
+        write_ebb_header(&mut s, func, self.isa, ebb, 4).expect("writing to a string");
+        s
+    }
+
+    fn instruction(&self, func: &Function, inst: Inst) -> String {
+        let mut s = String::new();
+        write_instruction(&mut s, func, self.isa, inst, 4).expect("writing to a string");
+        s
+    }
+
+    /// Write the lines in `text` to `f`, each prefixed with `marker`.
+    fn write_lines(f: &mut fmt::Formatter, marker: char, text: &str) -> fmt::Result {
+        for line in text.lines() {
+            writeln!(f, "{}{}", marker, line)?;
+        }
+        Ok(())
+    }
+
+    /// Write all of `ebb` in `func`, prefixed with `marker`.
+    fn write_ebb(
+        &self,
+        f: &mut fmt::Formatter,
+        marker: char,
+        func: &Function,
+        ebb: Ebb,
+    ) -> fmt::Result {
+        Self::write_lines(f, marker, &self.ebb_header(func, ebb))?;
+        for inst in func.layout.ebb_insts(ebb) {
+            Self::write_lines(f, marker, &self.instruction(func, inst))?;
+        }
+        Ok(())
+    }
+}
+
+impl<'a> fmt::Display for FunctionDiff<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The EBB whose header was printed last.
+        let mut context = None;
+        for &change in &self.changes {
+            let inst = match change {
+                Change::AddedEbb(ebb) => {
+                    self.write_ebb(f, '+', self.new, ebb)?;
+                    context = Some(ebb);
+                    continue;
+                }
+                Change::RemovedEbb(ebb) => {
+                    self.write_ebb(f, '-', self.old, ebb)?;
+                    context = Some(ebb);
+                    continue;
+                }
+                Change::ChangedEbb(ebb) => {
+                    Self::write_lines(f, '-', &self.ebb_header(self.old, ebb))?;
+                    Self::write_lines(f, '+', &self.ebb_header(self.new, ebb))?;
+                    context = Some(ebb);
+                    continue;
+                }
+                Change::AddedInst(inst) |
+                Change::RemovedInst(inst) |
+                Change::ChangedInst(inst) |
+                Change::ChangedEncoding(inst) => inst,
+            };
+
+            // Removed instructions are no longer in the new layout.
+            let ebb = match change {
+                Change::RemovedInst(_) => self.old.layout.pp_ebb(inst),
+                _ => self.new.layout.pp_ebb(inst),
+            };
+            if context != Some(ebb) {
+                Self::write_lines(f, ' ', &self.ebb_header(self.new, ebb))?;
+                context = Some(ebb);
+            }
+            if change != Change::AddedInst(inst) {
+                Self::write_lines(f, '-', &self.instruction(self.old, inst))?;
+            }
+            if change != Change::RemovedInst(inst) {
+                Self::write_lines(f, '+', &self.instruction(self.new, inst))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Change, FunctionDiff};
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder};
+    use ir::types::I32;
+    use std::string::ToString;
+
+    #[test]
+    fn changes() {
+        let mut old = Function::new();
+        let (ebb0, add, ret) = {
+            let mut pos = FuncCursor::new(&mut old);
+            let ebb0 = pos.func.dfg.make_ebb();
+            pos.insert_ebb(ebb0);
+            let arg = pos.func.dfg.append_ebb_param(ebb0, I32);
+            let v1 = pos.ins().iadd(arg, arg);
+            let add = pos.func.dfg.value_def(v1).unwrap_inst();
+            let ret = pos.ins().return_(&[v1]);
+            (ebb0, add, ret)
+        };
+        assert!(FunctionDiff::new(&old, &old, None).is_empty());
+
+        // Replace the `iadd` and insert an instruction before the return.
+        let mut new = old.clone();
+        let konst = {
+            let mut pos = FuncCursor::new(&mut new).at_inst(ret);
+            let arg = pos.func.dfg.ebb_params(ebb0)[0];
+            pos.func.dfg.replace(add).iadd_imm(arg, 1);
+            let v2 = pos.ins().iconst(I32, 0);
+            pos.func.dfg.value_def(v2).unwrap_inst()
+        };
+
+        let diff = FunctionDiff::new(&old, &new, None);
+        assert_eq!(
+            diff.changes(),
+            &[Change::ChangedInst(add), Change::AddedInst(konst)]
+        );
+        assert_eq!(
+            diff.to_string(),
+            " ebb0(v0: i32):\n\
+             -    v1 = iadd v0, v0\n\
+             +    v1 = iadd_imm v0, 1\n\
+             +    v2 = iconst.i32 0\n"
+        );
+
+        let diff = FunctionDiff::new(&new, &old, None);
+        assert_eq!(
+            diff.changes(),
+            &[Change::RemovedInst(konst), Change::ChangedInst(add)]
+        );
+    }
+}
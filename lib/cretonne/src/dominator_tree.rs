@@ -231,6 +231,8 @@ impl DominatorTree {
     pub fn compute(&mut self, func: &Function, cfg: &ControlFlowGraph) {
         let _tt = timing::domtree();
         debug_assert!(cfg.is_valid());
+        self.clear();
+        self.nodes.resize(func.dfg.num_ebbs());
         self.compute_postorder(func);
         self.compute_domtree(func, cfg);
         self.valid = true;
@@ -254,13 +256,11 @@ impl DominatorTree {
         self.valid
     }
 
-    /// Reset all internal data structures and compute a post-order of the control flow graph.
+    /// Compute a post-order of the control flow graph.
     ///
-    /// This leaves `rpo_number == 1` for all reachable EBBs, 0 for unreachable ones.
+    /// This expects an empty post-order and `rpo_number == 0` for all EBBs. It leaves
+    /// `rpo_number == 1` for all reachable EBBs, 0 for unreachable ones.
     fn compute_postorder(&mut self, func: &Function) {
-        self.clear();
-        self.nodes.resize(func.dfg.num_ebbs());
-
         // This algorithm is a depth first traversal (DFT) of the control flow graph, computing a
         // post-order of the EBBs that are reachable form the entry block. A DFT post-order is not
         // unique. The specific order we get is controlled by two factors:
@@ -348,14 +348,19 @@ impl DominatorTree {
                     }
                 }
                 BranchInfo::Table(jt) => {
-                    for (_, succ) in func.jump_tables[jt].entries() {
+                    for succ in func.jump_tables[jt].destinations() {
                         if self.nodes[succ].rpo_number == 0 {
                             self.nodes[succ].rpo_number = SEEN;
                             self.stack.push(succ);
                         }
                     }
                 }
-                BranchInfo::NotABranch => {}
+                BranchInfo::NotABranch => {
+                    // Like the CFG, ignore the branches following a call that never returns.
+                    if func.dfg.is_noreturn_call(inst) {
+                        break;
+                    }
+                }
             }
         }
     }
@@ -463,6 +468,105 @@ impl DominatorTree {
 
     }
 
+    /// Update the dominator tree after splitting the CFG edge from `pred` to its destination.
+    ///
+    /// `new_ebb` is a new EBB whose only predecessor is the branch `pred`, and which ends in a
+    /// jump to the old destination of the branch. The CFG must already have been updated, see
+    /// `ControlFlowGraph::split_edge`.
+    ///
+    /// Splitting an edge only changes the immediate dominator of `new_ebb` and possibly of the old
+    /// destination, so they are updated directly. The post-order can change in non-local ways, so
+    /// it is recomputed with a linear traversal of the CFG.
+    pub fn recompute_split_edge(
+        &mut self,
+        func: &Function,
+        cfg: &ControlFlowGraph,
+        pred: BasicBlock,
+        new_ebb: Ebb,
+    ) {
+        debug_assert!(self.is_valid() && cfg.is_valid());
+        if !self.is_reachable(pred.0) {
+            // The edge is unreachable, and so is `new_ebb`.
+            self.nodes[new_ebb] = Default::default();
+            return;
+        }
+
+        // The old destination is now dominated by the jump in `new_ebb` if that is the only way
+        // to reach it. Otherwise, its dominators are unchanged.
+        let jump = func.layout.last_inst(new_ebb).expect("Empty EBB after split edge");
+        let mut succs = cfg.succ_iter(new_ebb);
+        let succ = succs.next().expect("No jump out of the split edge");
+        debug_assert!(succs.next().is_none(), "Split edge must have one successor");
+        if cfg.pred_iter(succ).all(|(ebb, _)| {
+            ebb == new_ebb || !self.is_reachable(ebb)
+        })
+        {
+            self.nodes[succ].idom = jump.into();
+        }
+        self.nodes[new_ebb].idom = pred.1.into();
+        self.renumber(func);
+    }
+
+    /// Update the dominator tree after inserting `ebb` into the CFG.
+    ///
+    /// The branches to `ebb` and from it must already be in the CFG. The dominators of the
+    /// successors of `ebb` must not change, which is the case when `ebb` doesn't branch anywhere,
+    /// or when all its successors are dominated by one of its predecessors.
+    pub fn recompute_insert_ebb(&mut self, func: &Function, cfg: &ControlFlowGraph, ebb: Ebb) {
+        debug_assert!(self.is_valid() && cfg.is_valid());
+        let mut preds = cfg.pred_iter(ebb).filter(|&(pred, _)| self.is_reachable(pred));
+        let idom = match preds.next() {
+            Some(first) => {
+                preds
+                    .fold(first, |idom, pred| self.common_dominator(idom, pred, &func.layout))
+                    .1
+            }
+            None => {
+                // Nothing reachable branches to `ebb`.
+                self.nodes[ebb] = Default::default();
+                return;
+            }
+        };
+        self.nodes[ebb].idom = idom.into();
+        self.renumber(func);
+
+        if cfg!(debug_assertions) {
+            for succ in cfg.succ_iter(ebb) {
+                debug_assert_eq!(
+                    self.idom(succ),
+                    Some(self.compute_idom(succ, cfg, &func.layout)),
+                    "Inserting {} changed the dominators of {}",
+                    ebb,
+                    succ
+                );
+            }
+        }
+    }
+
+    /// Update the dominator tree after removing the unreachable `ebb` from the layout.
+    ///
+    /// Unreachable EBBs are not part of the post-order, and they don't dominate anything, so only
+    /// the node of `ebb` itself needs to be reset.
+    pub fn remove_unreachable_ebb(&mut self, ebb: Ebb) {
+        debug_assert!(!self.is_reachable(ebb), "{} is reachable", ebb);
+        self.nodes[ebb] = Default::default();
+    }
+
+    /// Recompute the CFG post-order and the RPO numbers while keeping the immediate dominators.
+    ///
+    /// This is a linear traversal of the CFG, much cheaper than computing the dominators.
+    fn renumber(&mut self, func: &Function) {
+        self.nodes.resize(func.dfg.num_ebbs());
+        for ebb in self.nodes.keys() {
+            self.nodes[ebb].rpo_number = 0;
+        }
+        self.postorder.clear();
+        self.compute_postorder(func);
+        for (rpo_idx, &ebb) in self.postorder.iter().rev().enumerate() {
+            self.nodes[ebb].rpo_number = (rpo_idx as u32 + 2) * STRIDE;
+        }
+    }
+
     // Insert new_ebb just after ebb in the RPO. This function checks
     // if there is a gap in rpo numbers; if yes it returns the number in the gap and if
     // not it renumbers.
@@ -670,7 +774,7 @@ mod test {
     use cursor::{Cursor, FuncCursor};
     use flowgraph::ControlFlowGraph;
     use ir::types::*;
-    use ir::{Function, InstBuilder, TrapCode};
+    use ir::{AbiParam, Function, InstBuilder, TrapCode};
     use settings;
     use super::*;
     use verifier::verify_context;
@@ -933,4 +1037,73 @@ mod test {
         let flags = settings::Flags::new(&settings::builder());
         verify_context(cur.func, &cfg, &dt, &flags).unwrap();
     }
+
+    #[test]
+    fn incremental_updates() {
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(I32));
+        let ebb0 = func.dfg.make_ebb();
+        let v0 = func.dfg.append_ebb_param(ebb0, I32);
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+
+        let mut cur = FuncCursor::new(&mut func);
+
+        cur.insert_ebb(ebb0);
+        let br02 = cur.ins().brnz(v0, ebb2, &[]);
+        cur.ins().jump(ebb1, &[]);
+
+        cur.insert_ebb(ebb1);
+        let br13 = cur.ins().brnz(v0, ebb3, &[]);
+        cur.ins().jump(ebb2, &[]);
+
+        cur.insert_ebb(ebb2);
+        cur.ins().return_(&[]);
+
+        cur.insert_ebb(ebb3);
+        let ret3 = cur.ins().return_(&[]);
+
+        let mut cfg = ControlFlowGraph::with_function(cur.func);
+        let mut dt = DominatorTree::with_function(cur.func, &cfg);
+        let flags = settings::Flags::new(&settings::builder());
+
+        // Split the critical edge from ebb0 to ebb2. It still has two predecessors, so its
+        // dominator doesn't change.
+        let ebb4 = cur.func.dfg.make_ebb();
+        cur.func.dfg.replace(br02).brnz(v0, ebb4, &[]);
+        cur.insert_ebb(ebb4);
+        let jmp42 = cur.ins().jump(ebb2, &[]);
+        cfg.split_edge(cur.func, (ebb0, br02), ebb2, ebb4);
+        dt.recompute_split_edge(cur.func, &cfg, (ebb0, br02), ebb4);
+        verify_context(cur.func, &cfg, &dt, &flags).unwrap();
+        assert_eq!(dt.idom(ebb4), Some(br02));
+        assert_eq!(dt.idom(ebb2), Some(br02));
+
+        // Split the only edge to ebb3, which is now dominated by the new EBB.
+        let ebb5 = cur.func.dfg.make_ebb();
+        cur.func.dfg.replace(br13).brnz(v0, ebb5, &[]);
+        cur.insert_ebb(ebb5);
+        let jmp53 = cur.ins().jump(ebb3, &[]);
+        cfg.split_edge(cur.func, (ebb1, br13), ebb3, ebb5);
+        dt.recompute_split_edge(cur.func, &cfg, (ebb1, br13), ebb5);
+        verify_context(cur.func, &cfg, &dt, &flags).unwrap();
+        assert_eq!(dt.idom(ebb5), Some(br13));
+        assert_eq!(dt.idom(ebb3), Some(jmp53));
+        assert!(dt.dominates(ebb5, ebb3, &cur.func.layout));
+        assert!(!dt.dominates(jmp42, ebb2, &cur.func.layout));
+
+        // Insert a trap EBB branched to from ebb3.
+        let ebb6 = cur.func.dfg.make_ebb();
+        cur.goto_inst(ret3);
+        let br36 = cur.ins().brz(v0, ebb6, &[]);
+        cur.goto_bottom(ebb5);
+        cur.insert_ebb(ebb6);
+        cur.ins().trap(TrapCode::User(0));
+        cfg.recompute_ebb(cur.func, ebb3);
+        cfg.insert_ebb(cur.func, ebb6);
+        dt.recompute_insert_ebb(cur.func, &cfg, ebb6);
+        verify_context(cur.func, &cfg, &dt, &flags).unwrap();
+        assert_eq!(dt.idom(ebb6), Some(br36));
+    }
 }
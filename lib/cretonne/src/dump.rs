@@ -0,0 +1,214 @@
+//! Dumping the IL after compilation passes.
+//!
+//! When investigating a miscompile, it helps to see how the IL of the function changes through
+//! the compilation pipeline. An `IrDump` attached to a `Context` writes the IL after selected
+//! passes, either to files or to a callback. It is configured through the `Configurable` trait
+//! like the settings:
+//!
+//! - `dump_ir=legalize,regalloc` selects the passes to dump after, or `all` of them.
+//! - `dump_filter=%name` only dumps the function with the given name.
+//! - `dump_dir=path` is the directory where the dump files are written.
+//!
+//! A dump file is named after the function, the position of the pass in the pipeline, and the
+//! pass, like `foo.05.regalloc.cton`.
+
+use ir::Function;
+use isa::TargetIsa;
+use settings::{Configurable, Error, Result};
+use std::boxed::Box;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::string::{String, ToString};
+use std::vec::Vec;
+
+/// The passes that can be dumped, in pipeline order.
+///
+/// These are the names used by `dump_ir`.
+pub const PASSES: [&str; 9] = [
+    "preopt",
+    "legalize",
+    "gvn",
+    "licm",
+    "unreachable_code",
+    "regalloc",
+    "copy_propagation",
+    "prologue_epilogue",
+    "relax_branches",
+];
+
+/// A function receiving the name of the pass, the function, and its text after the pass.
+pub type DumpCallback = Box<FnMut(&str, &Function, &str) + Send>;
+
+/// Configuration and destination of IL dumps.
+pub struct IrDump {
+    /// Indexes into `PASSES` of the selected passes.
+    passes: Vec<usize>,
+    filter: Option<String>,
+    dir: PathBuf,
+    callback: Option<DumpCallback>,
+    error: Option<io::Error>,
+}
+
+impl IrDump {
+    /// Create a dump that writes files to the current directory.
+    ///
+    /// No passes are selected until `dump_ir` is set.
+    pub fn new() -> Self {
+        Self {
+            passes: Vec::new(),
+            filter: None,
+            dir: PathBuf::new(),
+            callback: None,
+            error: None,
+        }
+    }
+
+    /// Create a dump that passes the IL to `callback` instead of writing files.
+    pub fn with_callback(callback: DumpCallback) -> Self {
+        Self {
+            callback: Some(callback),
+            ..Self::new()
+        }
+    }
+
+    /// Take the first error that happened while writing a dump file.
+    ///
+    /// Writing the dumps doesn't interrupt the compilation, so errors are kept here.
+    pub fn take_error(&mut self) -> Option<io::Error> {
+        self.error.take()
+    }
+
+    /// Dump `func` if `pass` is selected and the function matches the filter.
+    ///
+    /// This is called by `Context` after running each pass in `PASSES`.
+    pub fn after_pass(&mut self, pass: &str, func: &Function, isa: Option<&TargetIsa>) {
+        let index = match PASSES.iter().position(|&p| p == pass) {
+            Some(index) => index,
+            None => return,
+        };
+        if !self.passes.contains(&index) {
+            return;
+        }
+        let name = func.name.to_string();
+        if let Some(ref filter) = self.filter {
+            if *filter != name {
+                return;
+            }
+        }
+
+        let text = func.display(isa).to_string();
+        if let Some(ref mut callback) = self.callback {
+            return callback(pass, func, &text);
+        }
+
+        let path = self.dir.join(format!(
+            "{}.{:02}.{}.cton",
+            file_stem(&name),
+            index,
+            pass
+        ));
+        let result = File::create(path).and_then(|mut file| {
+            writeln!(file, "; {} after {}", name, pass)?;
+            file.write_all(text.as_bytes())
+        });
+        if let Err(err) = result {
+            if self.error.is_none() {
+                self.error = Some(err);
+            }
+        }
+    }
+}
+
+/// Turn a function name into something that can be used in a file name.
+fn file_stem(name: &str) -> String {
+    name.trim_left_matches('%')
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '_' || c == '-' {
+            c
+        } else {
+            '_'
+        })
+        .collect()
+}
+
+impl Configurable for IrDump {
+    fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        match name {
+            "dump_ir" => {
+                let mut passes = Vec::new();
+                for pass in value.split(',') {
+                    if pass == "all" {
+                        passes.extend(0..PASSES.len());
+                    } else {
+                        let index = PASSES.iter().position(|&p| p == pass).ok_or(
+                            Error::BadValue,
+                        )?;
+                        passes.push(index);
+                    }
+                }
+                self.passes = passes;
+            }
+            "dump_filter" => self.filter = Some(value.to_string()),
+            "dump_dir" => self.dir = PathBuf::from(value),
+            _ => return Err(Error::BadName),
+        }
+        Ok(())
+    }
+
+    fn enable(&mut self, name: &str) -> Result<()> {
+        match name {
+            "dump_ir" | "dump_filter" | "dump_dir" => Err(Error::BadType),
+            _ => Err(Error::BadName),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{IrDump, file_stem};
+    use ir::{ExternalName, Function};
+    use settings::{Configurable, Error};
+    use std::boxed::Box;
+    use std::string::String;
+    use std::sync::{Arc, Mutex};
+    use std::vec::Vec;
+
+    #[test]
+    fn config() {
+        let mut dump = IrDump::new();
+        assert_eq!(dump.set("dump_ir", "legalize,regalloc"), Ok(()));
+        assert_eq!(dump.passes, [1, 5]);
+        assert_eq!(dump.set("dump_ir", "all"), Ok(()));
+        assert_eq!(dump.passes.len(), 9);
+        assert_eq!(dump.set("dump_ir", "legalize,bogus"), Err(Error::BadValue));
+        assert_eq!(dump.set("dump_bogus", "1"), Err(Error::BadName));
+        assert_eq!(dump.enable("dump_ir"), Err(Error::BadType));
+    }
+
+    #[test]
+    fn callback() {
+        let dumped = Arc::new(Mutex::new(Vec::new()));
+        let sink = dumped.clone();
+        let mut dump = IrDump::with_callback(Box::new(move |pass, func, _| {
+            sink.lock().unwrap().push(format!("{} {}", func.name, pass))
+        }));
+        dump.set("dump_ir", "legalize,regalloc").unwrap();
+        dump.set("dump_filter", "%foo").unwrap();
+
+        let mut func = Function::new();
+        func.name = ExternalName::testcase("foo");
+        dump.after_pass("preopt", &func, None);
+        dump.after_pass("legalize", &func, None);
+        func.name = ExternalName::testcase("bar");
+        dump.after_pass("regalloc", &func, None);
+
+        assert_eq!(*dumped.lock().unwrap(), [String::from("%foo legalize")]);
+    }
+
+    #[test]
+    fn file_names() {
+        assert_eq!(file_stem("%foo"), "foo");
+        assert_eq!(file_stem("u0:12"), "u0_12");
+    }
+}
@@ -0,0 +1,49 @@
+//! EBB execution counters.
+//!
+//! This instrumentation pass inserts code at the top of every EBB that increments a counter in a
+//! table supplied by the embedder. The counters can be read back after running the code to
+//! collect coverage information or an execution profile for profile-guided optimization.
+//!
+//! The table is addressed by a global variable, and it holds one pointer-sized counter per EBB.
+//! The counters are numbered in layout order when the pass runs, and counter `i` is found at a
+//! byte offset of `i` times the pointer size from the start of the table.
+
+use cursor::{Cursor, FuncCursor};
+use ir::{Ebb, Function, GlobalVar, InstBuilder, MemFlags};
+use ir::types::{I32, I64};
+use isa::TargetIsa;
+use std::vec::Vec;
+use timing;
+
+/// Insert an execution counter increment at the top of every EBB in `func`.
+///
+/// The address of the counter table is the address of the global variable `table`. The EBB
+/// counted by each counter is appended to `counters`, so counter `i` counts the executions of
+/// `counters[i]`.
+pub fn insert_ebb_counters(
+    func: &mut Function,
+    isa: &TargetIsa,
+    table: GlobalVar,
+    counters: &mut Vec<Ebb>,
+) {
+    let _tt = timing::ebb_counters();
+    let (ptr_ty, ptr_bytes) = if isa.flags().is_64bit() {
+        (I64, 8)
+    } else {
+        (I32, 4)
+    };
+    let mut flags = MemFlags::new();
+    flags.set_notrap();
+    flags.set_aligned();
+
+    let mut pos = FuncCursor::new(func);
+    while let Some(ebb) = pos.next_ebb() {
+        let offset = (counters.len() * ptr_bytes) as i32;
+        counters.push(ebb);
+        pos.goto_first_inst(ebb);
+        let base = pos.ins().global_addr(ptr_ty, table);
+        let count = pos.ins().load(ptr_ty, flags, base, offset);
+        let count = pos.ins().iadd_imm(count, 1);
+        pos.ins().store(flags, count, base, offset);
+    }
+}
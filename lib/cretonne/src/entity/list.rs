@@ -140,6 +140,32 @@ impl<T: EntityRef> ListPool<T> {
         self.free.clear();
     }
 
+    /// Get the number of elements in the pool's memory.
+    ///
+    /// This includes the length fields, the unused space in each list block, and the blocks on the
+    /// free lists.
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Is the pool's memory empty?
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Get the number of elements the pool can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.data.capacity()
+    }
+
+    /// Release the memory that isn't needed for the currently allocated blocks.
+    ///
+    /// Free blocks are kept in the pool since moving the lists would invalidate them.
+    pub fn shrink_to_fit(&mut self) {
+        self.data.shrink_to_fit();
+        self.free.shrink_to_fit();
+    }
+
     /// Read the length of a list field, if it exists.
     fn len_of(&self, list: &EntityList<T>) -> Option<usize> {
         let idx = list.index as usize;
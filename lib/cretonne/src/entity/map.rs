@@ -63,6 +63,23 @@ where
         self.elems.is_empty()
     }
 
+    /// Get the number of entries stored in the map.
+    ///
+    /// Keys beyond this have the default value without using any memory.
+    pub fn len(&self) -> usize {
+        self.elems.len()
+    }
+
+    /// Get the number of entries the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.elems.capacity()
+    }
+
+    /// Release the memory that isn't needed for the current entries.
+    pub fn shrink_to_fit(&mut self) {
+        self.elems.shrink_to_fit()
+    }
+
     /// Remove all entries from this map.
     pub fn clear(&mut self) {
         self.elems.clear()
@@ -152,4 +169,20 @@ mod tests {
         assert_eq!(shared[r1], 5);
         assert_eq!(shared[r2], 3);
     }
+
+    #[test]
+    fn capacity() {
+        let mut m = EntityMap::<E, u32>::new();
+        assert_eq!(m.len(), 0);
+
+        m[E(9)] = 1;
+        assert_eq!(m.len(), 10);
+        assert!(m.capacity() >= 10);
+
+        m.clear();
+        assert_eq!(m.len(), 0);
+        assert!(m.capacity() >= 10);
+        m.shrink_to_fit();
+        assert_eq!(m.capacity(), 0);
+    }
 }
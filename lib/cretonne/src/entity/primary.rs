@@ -54,6 +54,16 @@ where
         self.elems.len()
     }
 
+    /// Get the number of entities the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.elems.capacity()
+    }
+
+    /// Release the memory that isn't needed for the current entities.
+    pub fn shrink_to_fit(&mut self) {
+        self.elems.shrink_to_fit()
+    }
+
     /// Iterate over all the keys in this map.
     pub fn keys(&self) -> Keys<K> {
         Keys::new(self.elems.len())
@@ -64,6 +74,11 @@ where
         self.elems.clear()
     }
 
+    /// Remove the entries from `k` onwards, so `k` becomes the next key.
+    pub fn truncate(&mut self, k: K) {
+        self.elems.truncate(k.index())
+    }
+
     /// Get the key that will be assigned to the next pushed value.
     pub fn next_key(&self) -> K {
         K::new(self.elems.len())
@@ -142,4 +157,17 @@ mod tests {
         let v: Vec<E> = m.keys().collect();
         assert_eq!(v, [k1, k2]);
     }
+
+    #[test]
+    fn truncate() {
+        let mut m = PrimaryMap::new();
+        let k1: E = m.push(12);
+        let k2 = m.push(33);
+        m.truncate(k2);
+
+        assert!(m.is_valid(k1));
+        assert!(!m.is_valid(k2));
+        assert_eq!(m.next_key(), k2);
+        assert_eq!(m.push(7), k2);
+    }
 }
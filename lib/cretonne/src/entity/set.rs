@@ -47,6 +47,16 @@ where
         self.len == 0
     }
 
+    /// Get the number of keys the set can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.elems.capacity() * 8
+    }
+
+    /// Release the memory that isn't needed for the current keys.
+    pub fn shrink_to_fit(&mut self) {
+        self.elems.shrink_to_fit()
+    }
+
     /// Remove all entries from this set.
     pub fn clear(&mut self) {
         self.len = 0;
@@ -83,6 +83,17 @@ where
         self.dense.is_empty()
     }
 
+    /// Returns the number of elements the map can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.dense.capacity()
+    }
+
+    /// Release the memory that isn't needed for the current elements.
+    pub fn shrink_to_fit(&mut self) {
+        self.sparse.shrink_to_fit();
+        self.dense.shrink_to_fit();
+    }
+
     /// Remove all elements from the mapping.
     pub fn clear(&mut self) {
         self.dense.clear();
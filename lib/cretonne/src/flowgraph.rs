@@ -113,11 +113,17 @@ impl ControlFlowGraph {
                     self.add_edge((ebb, inst), dest);
                 }
                 BranchInfo::Table(jt) => {
-                    for (_, dest) in func.jump_tables[jt].entries() {
+                    for dest in func.jump_tables[jt].destinations() {
                         self.add_edge((ebb, inst), dest);
                     }
                 }
-                BranchInfo::NotABranch => {}
+                BranchInfo::NotABranch => {
+                    // A call that never returns terminates the EBB. The instructions following
+                    // it are unreachable.
+                    if func.dfg.is_noreturn_call(inst) {
+                        break;
+                    }
+                }
             }
         }
     }
@@ -148,6 +154,66 @@ impl ControlFlowGraph {
         self.compute_ebb(func, ebb);
     }
 
+    /// Update the control flow graph after splitting the edge from `pred` to `succ`.
+    ///
+    /// The branch `pred` has been redirected to `new_ebb`, a new EBB which jumps to `succ`. This
+    /// only changes the edges from `pred` and `new_ebb`, so it is cheaper than recomputing all the
+    /// edges from the EBB containing `pred`.
+    pub fn split_edge(&mut self, func: &Function, pred: BasicBlock, succ: Ebb, new_ebb: Ebb) {
+        debug_assert!(self.is_valid());
+
+        // A jump table can still have other entries branching to `succ`.
+        let still_branches = match func.dfg.analyze_branch(pred.1) {
+            BranchInfo::SingleDest(dest, _) => dest == succ,
+            BranchInfo::Table(jt) => func.jump_tables[jt].destinations().any(|dest| dest == succ),
+            BranchInfo::NotABranch => false,
+        };
+        if !still_branches {
+            self.data[succ].predecessors.remove(
+                pred.1,
+                &mut self.pred_forest,
+                &(),
+            );
+            if self.pred_iter(succ).all(|(ebb, _)| ebb != pred.0) {
+                self.data[pred.0].successors.remove(
+                    succ,
+                    &mut self.succ_forest,
+                    &(),
+                );
+            }
+        }
+
+        self.add_edge(pred, new_ebb);
+        self.insert_ebb(func, new_ebb);
+    }
+
+    /// Add the edges from `ebb` which has just been inserted into the layout.
+    ///
+    /// The edges to `ebb` are added by `recompute_ebb()` or `split_edge()` for the EBBs that branch
+    /// to it.
+    pub fn insert_ebb(&mut self, func: &Function, ebb: Ebb) {
+        debug_assert!(self.is_valid());
+        debug_assert!(self.data[ebb].successors.is_empty());
+        self.compute_ebb(func, ebb);
+    }
+
+    /// Remove all edges to and from `ebb` which is about to be removed from the layout.
+    ///
+    /// The EBBs branching to `ebb` must be unreachable, or they must be removed too.
+    pub fn remove_ebb(&mut self, ebb: Ebb) {
+        debug_assert!(self.is_valid());
+        self.invalidate_ebb_successors(ebb);
+        let mut predecessors = mem::replace(&mut self.data[ebb].predecessors, Default::default());
+        for (_, pred) in predecessors.iter(&self.pred_forest) {
+            self.data[pred].successors.remove(
+                ebb,
+                &mut self.succ_forest,
+                &(),
+            );
+        }
+        predecessors.clear(&mut self.pred_forest);
+    }
+
     fn add_edge(&mut self, from: BasicBlock, to: Ebb) {
         self.data[from.0].successors.insert(
             to,
@@ -203,7 +269,8 @@ pub type SuccIter<'a> = bforest::SetIter<'a, Ebb, ()>;
 mod tests {
     use super::*;
     use cursor::{Cursor, FuncCursor};
-    use ir::{Function, InstBuilder, types};
+    use ir::{AbiParam, CallConv, ExtFuncData, ExternalName, Function, InstBuilder, Signature,
+             types};
     use std::vec::Vec;
 
     #[test]
@@ -313,4 +380,103 @@ mod tests {
             assert_eq!(ebb2_successors.collect::<Vec<_>>(), []);
         }
     }
+
+    #[test]
+    fn split_edge_and_remove_ebb() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+
+        let br_ebb0_ebb1;
+        let jmp_ebb0_ebb1;
+        let jmp_ebb2_ebb1;
+
+        {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            br_ebb0_ebb1 = cur.ins().brnz(cond, ebb1, &[]);
+            jmp_ebb0_ebb1 = cur.ins().jump(ebb1, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().return_(&[]);
+
+            cur.insert_ebb(ebb2);
+            jmp_ebb2_ebb1 = cur.ins().jump(ebb1, &[]);
+        }
+
+        let mut cfg = ControlFlowGraph::with_function(&func);
+
+        // Split the edge from the `brnz`. The `jump` still goes from ebb0 to ebb1.
+        func.dfg.replace(br_ebb0_ebb1).brnz(cond, ebb3, &[]);
+        let jmp_ebb3_ebb1 = {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb3);
+            cur.ins().jump(ebb1, &[])
+        };
+        cfg.split_edge(&func, (ebb0, br_ebb0_ebb1), ebb1, ebb3);
+
+        assert_eq!(cfg.succ_iter(ebb0).collect::<Vec<_>>(), [ebb1, ebb3]);
+        assert_eq!(cfg.succ_iter(ebb3).collect::<Vec<_>>(), [ebb1]);
+        assert_eq!(
+            cfg.pred_iter(ebb3).collect::<Vec<_>>(),
+            [(ebb0, br_ebb0_ebb1)]
+        );
+        let ebb1_predecessors = cfg.pred_iter(ebb1).collect::<Vec<_>>();
+        assert_eq!(ebb1_predecessors.len(), 3);
+        assert!(ebb1_predecessors.contains(&(ebb0, jmp_ebb0_ebb1)));
+        assert!(ebb1_predecessors.contains(&(ebb2, jmp_ebb2_ebb1)));
+        assert!(ebb1_predecessors.contains(&(ebb3, jmp_ebb3_ebb1)));
+
+        // Remove the unreachable ebb2.
+        cfg.remove_ebb(ebb2);
+        func.layout.remove_inst(jmp_ebb2_ebb1);
+        func.layout.remove_ebb(ebb2);
+
+        assert_eq!(cfg.pred_iter(ebb1).count(), 2);
+        assert_eq!(cfg.succ_iter(ebb2).count(), 0);
+    }
+
+    #[test]
+    fn noreturn_call() {
+        let mut func = Function::new();
+        let mut sig = Signature::new(CallConv::Native);
+        sig.params.push(AbiParam::new(types::I32));
+        let signature = func.import_signature(sig);
+        let abort = func.import_function(ExtFuncData {
+            name: ExternalName::testcase("abort"),
+            signature,
+            noreturn: true,
+            cold: false,
+            colocated: false,
+        });
+
+        let ebb0 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+
+        {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().call(abort, &[cond]);
+            cur.ins().jump(ebb2, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().return_(&[]);
+
+            cur.insert_ebb(ebb2);
+            cur.ins().return_(&[]);
+        }
+
+        // The jump following the call to `abort` is unreachable.
+        let cfg = ControlFlowGraph::with_function(&func);
+        assert_eq!(cfg.succ_iter(ebb0).collect::<Vec<_>>(), [ebb1]);
+        assert_eq!(cfg.pred_iter(ebb2).count(), 0);
+    }
 }
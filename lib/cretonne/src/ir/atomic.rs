@@ -0,0 +1,168 @@
+//! Immediate operands for atomic memory operations.
+
+use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+/// A memory ordering constraint for an atomic memory operation or a fence.
+///
+/// The orderings have the same meaning as in the C++11 memory model.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum AtomicOrdering {
+    /// Only the atomicity of the access itself is guaranteed.
+    Relaxed,
+
+    /// No later memory access can be reordered before this one.
+    Acquire,
+
+    /// No earlier memory access can be reordered after this one.
+    Release,
+
+    /// Both `Acquire` and `Release`.
+    AcqRel,
+
+    /// Like `AcqRel`, and all sequentially consistent operations are observed in a single total
+    /// order.
+    SeqCst,
+}
+
+impl AtomicOrdering {
+    /// Does this ordering prevent later memory accesses from moving before the operation?
+    pub fn is_acquire(self) -> bool {
+        match self {
+            AtomicOrdering::Acquire | AtomicOrdering::AcqRel | AtomicOrdering::SeqCst => true,
+            AtomicOrdering::Relaxed | AtomicOrdering::Release => false,
+        }
+    }
+
+    /// Does this ordering prevent earlier memory accesses from moving after the operation?
+    pub fn is_release(self) -> bool {
+        match self {
+            AtomicOrdering::Release | AtomicOrdering::AcqRel | AtomicOrdering::SeqCst => true,
+            AtomicOrdering::Relaxed | AtomicOrdering::Acquire => false,
+        }
+    }
+}
+
+impl Display for AtomicOrdering {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::AtomicOrdering::*;
+        f.write_str(match *self {
+            Relaxed => "relaxed",
+            Acquire => "acquire",
+            Release => "release",
+            AcqRel => "acq_rel",
+            SeqCst => "seq_cst",
+        })
+    }
+}
+
+impl FromStr for AtomicOrdering {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::AtomicOrdering::*;
+        match s {
+            "relaxed" => Ok(Relaxed),
+            "acquire" => Ok(Acquire),
+            "release" => Ok(Release),
+            "acq_rel" => Ok(AcqRel),
+            "seq_cst" => Ok(SeqCst),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The operation performed by an `atomic_rmw` instruction.
+///
+/// The text form of each operation is the name of the base instruction that computes the new
+/// value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum AtomicRmwOp {
+    /// Wrapping addition.
+    Add,
+    /// Wrapping subtraction of the operand from the value in memory.
+    Sub,
+    /// Bitwise and.
+    And,
+    /// Bitwise or.
+    Or,
+    /// Bitwise exclusive or.
+    Xor,
+    /// Replace the value in memory with the operand.
+    Xchg,
+}
+
+impl Display for AtomicRmwOp {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        use self::AtomicRmwOp::*;
+        f.write_str(match *self {
+            Add => "iadd",
+            Sub => "isub",
+            And => "band",
+            Or => "bor",
+            Xor => "bxor",
+            Xchg => "xchg",
+        })
+    }
+}
+
+impl FromStr for AtomicRmwOp {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::AtomicRmwOp::*;
+        match s {
+            "iadd" => Ok(Add),
+            "isub" => Ok(Sub),
+            "band" => Ok(And),
+            "bor" => Ok(Or),
+            "bxor" => Ok(Xor),
+            "xchg" => Ok(Xchg),
+            _ => Err(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::string::ToString;
+
+    #[test]
+    fn ordering() {
+        let all = [
+            AtomicOrdering::Relaxed,
+            AtomicOrdering::Acquire,
+            AtomicOrdering::Release,
+            AtomicOrdering::AcqRel,
+            AtomicOrdering::SeqCst,
+        ];
+        for &o in &all {
+            assert_eq!(o.to_string().parse(), Ok(o));
+        }
+        assert_eq!("bogus".parse::<AtomicOrdering>(), Err(()));
+
+        assert!(!AtomicOrdering::Relaxed.is_acquire());
+        assert!(!AtomicOrdering::Relaxed.is_release());
+        assert!(AtomicOrdering::Acquire.is_acquire());
+        assert!(!AtomicOrdering::Acquire.is_release());
+        assert!(AtomicOrdering::AcqRel.is_release());
+        assert!(AtomicOrdering::SeqCst.is_acquire());
+    }
+
+    #[test]
+    fn rmw_op() {
+        let all = [
+            AtomicRmwOp::Add,
+            AtomicRmwOp::Sub,
+            AtomicRmwOp::And,
+            AtomicRmwOp::Or,
+            AtomicRmwOp::Xor,
+            AtomicRmwOp::Xchg,
+        ];
+        for &op in &all {
+            assert_eq!(op.to_string().parse(), Ok(op));
+        }
+        assert_eq!("nand".parse::<AtomicRmwOp>(), Err(()));
+    }
+}
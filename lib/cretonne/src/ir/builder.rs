@@ -215,7 +215,7 @@ impl<'f> InstBuilderBase<'f> for ReplaceBuilder<'f> {
 #[cfg(test)]
 mod tests {
     use cursor::{Cursor, FuncCursor};
-    use ir::{Function, InstBuilder, ValueDef};
+    use ir::{Function, InstBuilder, ValueDef, SourceLoc};
     use ir::types::*;
     use ir::condcodes::*;
 
@@ -262,4 +262,43 @@ mod tests {
         assert!(iadd != iconst);
         assert_eq!(pos.func.dfg.value_def(v0), ValueDef::Result(iconst, 0));
     }
+
+    #[test]
+    fn replace_with_multiple() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let arg0 = func.dfg.append_ebb_param(ebb0, I32);
+        let mut pos = FuncCursor::new(&mut func);
+        pos.insert_ebb(ebb0);
+
+        let (lo, hi) = pos.ins().isplit(arg0);
+        let sum = pos.ins().iadd(lo, hi);
+        let split = pos.func.dfg.value_def(lo).unwrap_inst();
+        let add = pos.func.dfg.value_def(sum).unwrap_inst();
+        pos.func.srclocs[split] = SourceLoc::new(7);
+
+        // Replace both results of `isplit` with a shift sequence.
+        pos.set_srcloc(SourceLoc::new(3));
+        let mut new_lo = None;
+        pos.replace_with_multiple(split, |pos| {
+            let lo = pos.ins().ireduce(I16, arg0);
+            let shifted = pos.ins().ushr_imm(arg0, 16);
+            let hi = pos.ins().ireduce(I16, shifted);
+            new_lo = Some(lo);
+            [lo, hi]
+        });
+        assert_eq!(pos.current_inst(), Some(add));
+        assert_eq!(pos.srcloc(), SourceLoc::new(3));
+
+        let dfg = &pos.func.dfg;
+        assert_eq!(pos.func.layout.inst_ebb(split), None);
+        assert_eq!(dfg.resolve_aliases(lo), new_lo.unwrap());
+        assert_eq!(dfg.inst_args(add), &[lo, hi]);
+        let insts: Vec<_> = pos.func.layout.ebb_insts(ebb0).collect();
+        assert_eq!(insts.len(), 4);
+        for &inst in &insts[0..3] {
+            assert_eq!(pos.func.srclocs[inst], SourceLoc::new(7));
+        }
+        assert_eq!(dfg.value_type(sum), I16);
+    }
 }
@@ -7,7 +7,8 @@ use ir::builder::ReplaceBuilder;
 use ir::extfunc::ExtFuncData;
 use ir::instructions::{InstructionData, CallInfo, BranchInfo};
 use ir::types;
-use ir::{Ebb, Inst, Value, Type, SigRef, Signature, FuncRef, ValueList, ValueListPool};
+use ir::{Ebb, Inst, Value, Type, SigRef, Signature, FuncRef, ValueList, ValueListPool, LaneMask,
+         LaneMaskData, FunctionMemory, TableMemory};
 use packed_option::ReservedValue;
 use write::write_operands;
 use std::fmt;
@@ -60,6 +61,9 @@ pub struct DataFlowGraph {
 
     /// External function references. These are functions that can be called directly.
     pub ext_funcs: PrimaryMap<FuncRef, ExtFuncData>,
+
+    /// Lane masks referenced by vector shuffle instructions.
+    pub lane_masks: PrimaryMap<LaneMask, LaneMaskData>,
 }
 
 impl DataFlowGraph {
@@ -73,6 +77,7 @@ impl DataFlowGraph {
             values: PrimaryMap::new(),
             signatures: PrimaryMap::new(),
             ext_funcs: PrimaryMap::new(),
+            lane_masks: PrimaryMap::new(),
         }
     }
 
@@ -85,6 +90,7 @@ impl DataFlowGraph {
         self.values.clear();
         self.signatures.clear();
         self.ext_funcs.clear();
+        self.lane_masks.clear();
     }
 
     /// Get the total number of instructions created in this function, whether they are currently
@@ -117,6 +123,54 @@ impl DataFlowGraph {
     pub fn num_values(&self) -> usize {
         self.values.len()
     }
+
+    /// Add the memory used by the data flow graph tables to `mem`.
+    pub fn memory_usage(&self, mem: &mut FunctionMemory) {
+        mem.insts.add(TableMemory::new::<InstructionData>(
+            self.insts.len(),
+            self.insts.capacity(),
+        ));
+        mem.insts.add(TableMemory::new::<ValueList>(
+            self.results.len(),
+            self.results.capacity(),
+        ));
+        mem.values.add(TableMemory::new::<ValueData>(
+            self.values.len(),
+            self.values.capacity(),
+        ));
+        mem.ebbs.add(TableMemory::new::<EbbData>(
+            self.ebbs.len(),
+            self.ebbs.capacity(),
+        ));
+        mem.value_lists.add(TableMemory::new::<Value>(
+            self.value_lists.len(),
+            self.value_lists.capacity(),
+        ));
+        mem.other.add(TableMemory::new::<Signature>(
+            self.signatures.len(),
+            self.signatures.capacity(),
+        ));
+        mem.other.add(TableMemory::new::<ExtFuncData>(
+            self.ext_funcs.len(),
+            self.ext_funcs.capacity(),
+        ));
+        mem.other.add(TableMemory::new::<LaneMaskData>(
+            self.lane_masks.len(),
+            self.lane_masks.capacity(),
+        ));
+    }
+
+    /// Release the unused capacity of the data flow graph tables.
+    pub fn shrink_to_fit(&mut self) {
+        self.insts.shrink_to_fit();
+        self.results.shrink_to_fit();
+        self.ebbs.shrink_to_fit();
+        self.value_lists.shrink_to_fit();
+        self.values.shrink_to_fit();
+        self.signatures.shrink_to_fit();
+        self.ext_funcs.shrink_to_fit();
+        self.lane_masks.shrink_to_fit();
+    }
 }
 
 /// Resolve value aliases.
@@ -159,6 +213,18 @@ impl DataFlowGraph {
         }
     }
 
+    /// Change the type of a value.
+    ///
+    /// This doesn't update the instructions and EBBs using `v`, so the caller is responsible for
+    /// changing all of them consistently.
+    pub fn change_value_type(&mut self, v: Value, t: Type) {
+        match self.values[v] {
+            ValueData::Inst { ref mut ty, .. } |
+            ValueData::Param { ref mut ty, .. } |
+            ValueData::Alias { ref mut ty, .. } => *ty = t,
+        }
+    }
+
     /// Get the definition of a value.
     ///
     /// This is either the instruction that defined it or the Ebb that has the value as an
@@ -595,6 +661,20 @@ impl DataFlowGraph {
         }
     }
 
+    /// Get the external function called by a direct call instruction.
+    /// Returns `None` if `inst` is not a direct call.
+    pub fn call_ext_func(&self, inst: Inst) -> Option<&ExtFuncData> {
+        match self.insts[inst].analyze_call(&self.value_lists) {
+            CallInfo::Direct(f, _) => Some(&self.ext_funcs[f]),
+            _ => None,
+        }
+    }
+
+    /// Is `inst` a direct call to a function that never returns?
+    pub fn is_noreturn_call(&self, inst: Inst) -> bool {
+        self.call_ext_func(inst).map_or(false, |f| f.noreturn)
+    }
+
     /// Check if `inst` is a branch.
     pub fn analyze_branch(&self, inst: Inst) -> BranchInfo {
         self.insts[inst].analyze_branch(&self.value_lists)
@@ -156,6 +156,28 @@ impl Heap {
     }
 }
 
+/// A reference to a table.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct Table(u32);
+entity_impl!(Table, "table");
+
+impl Table {
+    /// Create a new table reference from its number.
+    ///
+    /// This method is for use by the parser.
+    pub fn with_number(n: u32) -> Option<Table> {
+        if n < u32::MAX { Some(Table(n)) } else { None }
+    }
+}
+
+/// An opaque reference to a vector lane mask.
+///
+/// Lane masks are stored in the data flow graph, and they are printed inline with the
+/// instructions using them.
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub struct LaneMask(u32);
+entity_impl!(LaneMask, "mask");
+
 /// A reference to any of the entities defined in this module.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum AnyEntity {
@@ -179,6 +201,8 @@ pub enum AnyEntity {
     SigRef(SigRef),
     /// A heap.
     Heap(Heap),
+    /// A table.
+    Table(Table),
 }
 
 impl fmt::Display for AnyEntity {
@@ -194,6 +218,7 @@ impl fmt::Display for AnyEntity {
             AnyEntity::FuncRef(r) => r.fmt(f),
             AnyEntity::SigRef(r) => r.fmt(f),
             AnyEntity::Heap(r) => r.fmt(f),
+            AnyEntity::Table(r) => r.fmt(f),
         }
     }
 }
@@ -258,6 +283,12 @@ impl From<Heap> for AnyEntity {
     }
 }
 
+impl From<Table> for AnyEntity {
+    fn from(r: Table) -> AnyEntity {
+        AnyEntity::Table(r)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -327,10 +327,33 @@ pub struct ExtFuncData {
     pub name: ExternalName,
     /// Call signature of function.
     pub signature: SigRef,
+    /// The function never returns to its caller.
+    ///
+    /// A direct call to this function ends its EBB. Any instructions following the call are
+    /// unreachable.
+    pub noreturn: bool,
+    /// The function is rarely called.
+    ///
+    /// EBBs calling this function, or a `noreturn` function, are laid out after the other EBBs.
+    pub cold: bool,
+    /// The function is defined in the same module as the function being compiled.
+    ///
+    /// Calls and address computations can then reach it with a PC-relative offset instead of
+    /// going through an absolute address or the procedure linkage table.
+    pub colocated: bool,
 }
 
 impl fmt::Display for ExtFuncData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.noreturn {
+            write!(f, "noreturn ")?;
+        }
+        if self.cold {
+            write!(f, "cold ")?;
+        }
+        if self.colocated {
+            write!(f, "colocated ")?;
+        }
         write!(f, "{} {}", self.signature, self.name)
     }
 }
@@ -350,6 +373,11 @@ pub enum CallConv {
 
     /// A JIT-compiled WebAssembly function in the SpiderMonkey VM.
     SpiderWASM,
+
+    /// The Windows x64 calling convention.
+    ///
+    /// This is the C calling convention used by Microsoft's compilers for 64-bit code.
+    WindowsFastcall,
 }
 
 impl fmt::Display for CallConv {
@@ -358,6 +386,7 @@ impl fmt::Display for CallConv {
         f.write_str(match *self {
             Native => "native",
             SpiderWASM => "spiderwasm",
+            WindowsFastcall => "windows_fastcall",
         })
     }
 }
@@ -370,6 +399,7 @@ impl FromStr for CallConv {
         match s {
             "native" => Ok(Native),
             "spiderwasm" => Ok(SpiderWASM),
+            "windows_fastcall" => Ok(WindowsFastcall),
             _ => Err(()),
         }
     }
@@ -410,7 +440,12 @@ mod tests {
 
     #[test]
     fn call_conv() {
-        for &cc in &[CallConv::Native, CallConv::SpiderWASM] {
+        for &cc in &[
+            CallConv::Native,
+            CallConv::SpiderWASM,
+            CallConv::WindowsFastcall,
+        ]
+        {
             assert_eq!(Ok(cc), cc.to_string().parse())
         }
     }
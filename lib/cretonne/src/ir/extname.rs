@@ -12,8 +12,8 @@ use std::str::FromStr;
 const TESTCASE_NAME_LENGTH: usize = 16;
 
 /// The name of an external is either a reference to a user-defined symbol
-/// table, or a short sequence of ascii bytes so that test cases do not have
-/// to keep track of a sy mbol table.
+/// table, an arbitrary symbol name, or a short sequence of ascii bytes so that
+/// test cases do not have to keep track of a sy mbol table.
 ///
 /// External names are primarily used as keys by code using Cretonne to map
 /// from a `cretonne::ir::FuncRef` or similar to additional associated data.
@@ -41,6 +41,9 @@ pub enum ExternalName {
     },
     /// A well-known runtime library function.
     LibCall(LibCall),
+    /// A symbol name made of arbitrary bytes, such as a mangled C++ or Rust
+    /// symbol. It is written as a quoted string with escapes: `%"_ZN3foo3barE"`.
+    Symbol(Vec<u8>),
 }
 
 impl ExternalName {
@@ -82,6 +85,19 @@ impl ExternalName {
             index: index,
         }
     }
+
+    /// Create a new external name from an arbitrary symbol name.
+    ///
+    /// # Examples
+    /// ```rust
+    /// # use cretonne::ir::ExternalName;
+    /// // Bytes that can't appear in a test case name are escaped when printed.
+    /// let name = ExternalName::symbol("foo::bar \"baz\"\n");
+    /// assert_eq!(name.to_string(), r#"%"foo::bar \"baz\"\0a""#);
+    /// ```
+    pub fn symbol<T: AsRef<[u8]>>(v: T) -> ExternalName {
+        ExternalName::Symbol(v.as_ref().to_vec())
+    }
 }
 
 impl Default for ExternalName {
@@ -102,6 +118,17 @@ impl fmt::Display for ExternalName {
                 Ok(())
             }
             ExternalName::LibCall(lc) => write!(f, "%{}", lc),
+            ExternalName::Symbol(ref bytes) => {
+                f.write_str("%\"")?;
+                for &byte in bytes {
+                    match byte {
+                        b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+                        _ if b' ' <= byte && byte <= b'~' => f.write_char(byte as char)?,
+                        _ => write!(f, "\\{:02x}", byte)?,
+                    }
+                }
+                f.write_char('"')
+            }
         }
     }
 }
@@ -150,6 +177,23 @@ mod tests {
         );
     }
 
+    #[test]
+    fn display_symbol() {
+        assert_eq!(ExternalName::symbol("").to_string(), r#"%"""#);
+        assert_eq!(
+            ExternalName::symbol("_ZN3foo3barE").to_string(),
+            r#"%"_ZN3foo3barE""#
+        );
+        assert_eq!(
+            ExternalName::symbol("a b\\c").to_string(),
+            r#"%"a b\\c""#
+        );
+        assert_eq!(
+            ExternalName::symbol(&[0u8, 0x7f, 0xff, b'"'][..]).to_string(),
+            r#"%"\00\7f\ff\"""#
+        );
+    }
+
     #[test]
     fn parsing() {
         assert_eq!(
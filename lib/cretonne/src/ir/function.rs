@@ -8,11 +8,13 @@ use entity::{PrimaryMap, EntityMap};
 use ir;
 use ir::{ExternalName, CallConv, Signature, DataFlowGraph, Layout};
 use ir::{InstEncodings, ValueLocations, JumpTables, StackSlots, EbbOffsets, SourceLocs};
-use ir::{Ebb, JumpTableData, JumpTable, StackSlotData, StackSlot, SigRef, ExtFuncData, FuncRef,
-         GlobalVarData, GlobalVar, HeapData, Heap};
+use ir::instructions::BranchInfo;
+use ir::{Ebb, Inst, JumpTableData, JumpTable, StackSlotData, StackSlot, SigRef, ExtFuncData,
+         FuncRef, GlobalVarData, GlobalVar, HeapData, Heap, TableData, Table, FunctionStats,
+         FunctionMemory};
 use isa::{TargetIsa, EncInfo};
 use std::fmt;
-use write::write_function;
+use write::{write_function, write_function_with, WriteOptions};
 
 /// A function.
 ///
@@ -35,6 +37,9 @@ pub struct Function {
     /// Heaps referenced.
     pub heaps: PrimaryMap<ir::Heap, ir::HeapData>,
 
+    /// Tables referenced.
+    pub tables: PrimaryMap<ir::Table, ir::TableData>,
+
     /// Jump tables used in this function.
     pub jump_tables: JumpTables,
 
@@ -58,6 +63,19 @@ pub struct Function {
     /// in the textual IL format.
     pub offsets: EbbOffsets,
 
+    /// Minimum alignment in bytes of the function's code.
+    ///
+    /// When set, this overrides the `function_align` setting. It is not included in the textual IL
+    /// format.
+    pub align: Option<CodeOffset>,
+
+    /// Minimum alignment in bytes of individual EBB headers.
+    ///
+    /// A non-zero entry overrides the `loop_align` setting for that EBB. Branch relaxation inserts
+    /// padding so the EBB starts at a multiple of its alignment. It is not included in the textual
+    /// IL format.
+    pub ebb_align: EntityMap<Ebb, CodeOffset>,
+
     /// Source locations.
     ///
     /// Track the original source location for each instruction. The source locations are not
@@ -74,12 +92,15 @@ impl Function {
             stack_slots: StackSlots::new(),
             global_vars: PrimaryMap::new(),
             heaps: PrimaryMap::new(),
+            tables: PrimaryMap::new(),
             jump_tables: PrimaryMap::new(),
             dfg: DataFlowGraph::new(),
             layout: Layout::new(),
             encodings: EntityMap::new(),
             locations: EntityMap::new(),
             offsets: EntityMap::new(),
+            align: None,
+            ebb_align: EntityMap::new(),
             srclocs: EntityMap::new(),
         }
     }
@@ -90,12 +111,15 @@ impl Function {
         self.stack_slots.clear();
         self.global_vars.clear();
         self.heaps.clear();
+        self.tables.clear();
         self.jump_tables.clear();
         self.dfg.clear();
         self.layout.clear();
         self.encodings.clear();
         self.locations.clear();
         self.offsets.clear();
+        self.align = None;
+        self.ebb_align.clear();
         self.srclocs.clear();
     }
 
@@ -114,6 +138,29 @@ impl Function {
         self.jump_tables[jt].set_entry(index, ebb);
     }
 
+    /// Change the destination of the branch `inst` from `old_dest` to `new_dest`.
+    ///
+    /// For a `br_table`, all the entries of the jump table that branch to `old_dest` are changed,
+    /// including the default destination. This affects any other `br_table` using the same table.
+    pub fn change_branch_destination(&mut self, inst: Inst, old_dest: Ebb, new_dest: Ebb) {
+        if let Some(dest) = self.dfg[inst].branch_destination_mut() {
+            debug_assert_eq!(*dest, old_dest);
+            *dest = new_dest;
+            return;
+        }
+        if let BranchInfo::Table(jt) = self.dfg.analyze_branch(inst) {
+            let table = &mut self.jump_tables[jt];
+            for entry in table.as_mut_slice() {
+                if entry.expand() == Some(old_dest) {
+                    *entry = new_dest.into();
+                }
+            }
+            if table.default_dest() == Some(old_dest) {
+                table.set_default_dest(Some(new_dest));
+            }
+        }
+    }
+
     /// Creates a stack slot in the function, to be used by `stack_load`, `stack_store` and
     /// `stack_addr` instructions.
     pub fn create_stack_slot(&mut self, data: StackSlotData) -> StackSlot {
@@ -140,9 +187,57 @@ impl Function {
         self.heaps.push(data)
     }
 
+    /// Declares a table accessible to the function.
+    pub fn create_table(&mut self, data: TableData) -> Table {
+        self.tables.push(data)
+    }
+
     /// Return an object that can display this function with correct ISA-specific annotations.
     pub fn display<'a, I: Into<Option<&'a TargetIsa>>>(&'a self, isa: I) -> DisplayFunction<'a> {
-        DisplayFunction(self, isa.into())
+        DisplayFunction(self, isa.into(), WriteOptions::default())
+    }
+
+    /// Return an object that can display this function using `options`.
+    ///
+    /// This can be used to renumber the EBBs and values in the text without changing the
+    /// function.
+    pub fn display_with<'a, I: Into<Option<&'a TargetIsa>>>(
+        &'a self,
+        isa: I,
+        options: WriteOptions,
+    ) -> DisplayFunction<'a> {
+        DisplayFunction(self, isa.into(), options)
+    }
+
+    /// Compute size statistics for this function.
+    ///
+    /// The machine code size is only included when an ISA is given and the function has been
+    /// compiled.
+    pub fn stats<'a, I: Into<Option<&'a TargetIsa>>>(&self, isa: I) -> FunctionStats {
+        FunctionStats::new(self, isa.into())
+    }
+
+    /// Get the heap memory used by the tables of this function.
+    pub fn memory_usage(&self) -> FunctionMemory {
+        FunctionMemory::new(self)
+    }
+
+    /// Release the unused capacity of all the tables in this function.
+    ///
+    /// This is useful for functions that are kept in memory after they have been compiled.
+    pub fn shrink_to_fit(&mut self) {
+        self.stack_slots.shrink_to_fit();
+        self.global_vars.shrink_to_fit();
+        self.heaps.shrink_to_fit();
+        self.tables.shrink_to_fit();
+        self.jump_tables.shrink_to_fit();
+        self.dfg.shrink_to_fit();
+        self.layout.shrink_to_fit();
+        self.encodings.shrink_to_fit();
+        self.locations.shrink_to_fit();
+        self.offsets.shrink_to_fit();
+        self.ebb_align.shrink_to_fit();
+        self.srclocs.shrink_to_fit();
     }
 
     /// Find a presumed unique special-purpose function parameter value.
@@ -179,11 +274,11 @@ impl Function {
 }
 
 /// Wrapper type capable of displaying a `Function` with correct ISA annotations.
-pub struct DisplayFunction<'a>(&'a Function, Option<&'a TargetIsa>);
+pub struct DisplayFunction<'a>(&'a Function, Option<&'a TargetIsa>, WriteOptions);
 
 impl<'a> fmt::Display for DisplayFunction<'a> {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
-        write_function(fmt, self.0, self.1)
+        write_function_with(fmt, self.0, self.1, self.2)
     }
 }
 
@@ -1,8 +1,9 @@
 //! Global variables.
 
-use ir::{ExternalName, GlobalVar};
+use ir::{ExternalName, GlobalVar, Heap};
 use ir::immediates::Offset32;
 use std::fmt;
+use std::str::FromStr;
 
 /// Information about a global variable declaration.
 #[derive(Clone)]
@@ -26,31 +27,115 @@ pub enum GlobalVarData {
         offset: Offset32,
     },
 
+    /// Variable is at a constant offset from the base address of a heap.
+    ///
+    /// This describes data that lives at a fixed location in a heap, like the globals of a wasm
+    /// module that are placed in its linear memory.
+    HeapBase {
+        /// The heap containing the variable.
+        heap: Heap,
+
+        /// Byte offset from the heap base address to this global.
+        offset: Offset32,
+    },
+
     /// Variable is at an address identified by a symbolic name. Cretonne itself
     /// does not interpret this name; it's used by embedders to link with other
     /// data structures.
     Sym {
         /// The symbolic name.
         name: ExternalName,
+
+        /// The symbol is defined in the same module as the function being compiled, so its
+        /// address can be computed with a PC-relative offset.
+        colocated: bool,
+    },
+
+    /// Variable is at an address identified by a symbolic name, and the address is loaded from
+    /// the symbol's entry in the global offset table.
+    ///
+    /// This allows position-independent code to access symbols defined in other modules.
+    GotSym {
+        /// The symbolic name.
+        name: ExternalName,
+    },
+
+    /// Variable is a thread-local variable identified by a symbolic name.
+    ///
+    /// Its address is computed relative to the thread pointer, as described by the TLS access
+    /// model.
+    TlsSym {
+        /// The symbolic name.
+        name: ExternalName,
+
+        /// How the offset from the thread pointer is found.
+        model: TlsModel,
     },
 }
 
 impl GlobalVarData {
-    /// Assume that `self` is an `GlobalVarData::Sym` and return its name.
+    /// Assume that `self` is a symbolic global variable and return its name.
     pub fn symbol_name(&self) -> &ExternalName {
         match *self {
-            GlobalVarData::Sym { ref name } => name,
+            GlobalVarData::Sym { ref name, .. } |
+            GlobalVarData::GotSym { ref name } |
+            GlobalVarData::TlsSym { ref name, .. } => name,
             _ => panic!("only symbols have names"),
         }
     }
 }
 
+/// Thread-local storage access models.
+///
+/// The access model determines which relocations are used to find the offset of a thread-local
+/// variable from the thread pointer.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum TlsModel {
+    /// The variable may be defined in another module that is loaded at startup. Its offset from
+    /// the thread pointer is loaded from the global offset table.
+    InitialExec,
+
+    /// The variable is defined in the executable being linked, so its offset from the thread
+    /// pointer is a link-time constant.
+    LocalExec,
+}
+
+impl fmt::Display for TlsModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            TlsModel::InitialExec => "initial_exec",
+            TlsModel::LocalExec => "local_exec",
+        })
+    }
+}
+
+impl FromStr for TlsModel {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "initial_exec" => Ok(TlsModel::InitialExec),
+            "local_exec" => Ok(TlsModel::LocalExec),
+            _ => Err(()),
+        }
+    }
+}
+
 impl fmt::Display for GlobalVarData {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match *self {
             GlobalVarData::VmCtx { offset } => write!(f, "vmctx{}", offset),
             GlobalVarData::Deref { base, offset } => write!(f, "deref({}){}", base, offset),
-            GlobalVarData::Sym { ref name } => write!(f, "globalsym {}", name),
+            GlobalVarData::HeapBase { heap, offset } => write!(f, "heap_base({}){}", heap, offset),
+            GlobalVarData::Sym { ref name, colocated } => {
+                if colocated {
+                    write!(f, "globalsym colocated {}", name)
+                } else {
+                    write!(f, "globalsym {}", name)
+                }
+            }
+            GlobalVarData::GotSym { ref name } => write!(f, "gotsym {}", name),
+            GlobalVarData::TlsSym { ref name, model } => write!(f, "tlssym {} {}", model, name),
         }
     }
 }
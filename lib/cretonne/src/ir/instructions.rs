@@ -193,6 +193,10 @@ impl InstructionData {
                 ref args,
                 ..
             } => BranchInfo::SingleDest(destination, &args.as_slice(pool)[2..]),
+            // The variable arguments are passed to the callee, not the landing pad.
+            InstructionData::TryCall { destination, .. } => {
+                BranchInfo::SingleDest(destination, &[])
+            }
             InstructionData::BranchTable { table, .. } => BranchInfo::Table(table),
             _ => {
                 debug_assert!(!self.opcode().is_branch());
@@ -211,7 +215,8 @@ impl InstructionData {
             InstructionData::Branch { destination, .. } |
             InstructionData::BranchInt { destination, .. } |
             InstructionData::BranchFloat { destination, .. } |
-            InstructionData::BranchIcmp { destination, .. } => Some(destination),
+            InstructionData::BranchIcmp { destination, .. } |
+            InstructionData::TryCall { destination, .. } => Some(destination),
             InstructionData::BranchTable { .. } => None,
             _ => {
                 debug_assert!(!self.opcode().is_branch());
@@ -230,7 +235,8 @@ impl InstructionData {
             InstructionData::Branch { ref mut destination, .. } |
             InstructionData::BranchInt { ref mut destination, .. } |
             InstructionData::BranchFloat { ref mut destination, .. } |
-            InstructionData::BranchIcmp { ref mut destination, .. } => Some(destination),
+            InstructionData::BranchIcmp { ref mut destination, .. } |
+            InstructionData::TryCall { ref mut destination, .. } => Some(destination),
             InstructionData::BranchTable { .. } => None,
             _ => {
                 debug_assert!(!self.opcode().is_branch());
@@ -244,7 +250,8 @@ impl InstructionData {
     /// Any instruction that can call another function reveals its call signature here.
     pub fn analyze_call<'a>(&'a self, pool: &'a ValueListPool) -> CallInfo<'a> {
         match *self {
-            InstructionData::Call { func_ref, ref args, .. } => {
+            InstructionData::Call { func_ref, ref args, .. } |
+            InstructionData::TryCall { func_ref, ref args, .. } => {
                 CallInfo::Direct(func_ref, args.as_slice(pool))
             }
             InstructionData::IndirectCall { sig_ref, ref args, .. } => {
@@ -12,8 +12,11 @@ use std::vec::Vec;
 
 /// Contents of a jump table.
 ///
-/// All jump tables use 0-based indexing and are expected to be densely populated. They don't need
-/// to be completely populated, though. Individual entries can be missing.
+/// Jump tables are expected to be densely populated. They don't need to be completely populated,
+/// though. Individual entries can be missing.
+///
+/// The first entry in the table corresponds to the index `min_index`, which is 0 by default. An
+/// optional default destination is taken when the index doesn't have a table entry.
 #[derive(Clone)]
 pub struct JumpTableData {
     // Table entries, using `None` as a placeholder for missing entries.
@@ -21,6 +24,12 @@ pub struct JumpTableData {
 
     // How many `None` holes in table?
     holes: usize,
+
+    // The index corresponding to the first table entry.
+    min_index: i64,
+
+    // Destination when no table entry is found, or `None` to fall through.
+    default: PackedOption<Ebb>,
 }
 
 impl JumpTableData {
@@ -29,6 +38,8 @@ impl JumpTableData {
         Self {
             table: Vec::new(),
             holes: 0,
+            min_index: 0,
+            default: None.into(),
         }
     }
 
@@ -37,6 +48,8 @@ impl JumpTableData {
         Self {
             table: Vec::with_capacity(capacity),
             holes: 0,
+            min_index: 0,
+            default: None.into(),
         }
     }
 
@@ -45,6 +58,30 @@ impl JumpTableData {
         self.table.len()
     }
 
+    /// Get the index corresponding to the first table entry.
+    pub fn min_index(&self) -> i64 {
+        self.min_index
+    }
+
+    /// Set the index corresponding to the first table entry.
+    ///
+    /// The `br_table` instruction subtracts this from its argument before looking up an entry.
+    pub fn set_min_index(&mut self, min_index: i64) {
+        self.min_index = min_index;
+    }
+
+    /// Get the default destination, if any.
+    pub fn default_dest(&self) -> Option<Ebb> {
+        self.default.expand()
+    }
+
+    /// Set the default destination taken when the index has no table entry.
+    ///
+    /// Without a default destination, `br_table` falls through instead.
+    pub fn set_default_dest(&mut self, dest: Option<Ebb>) {
+        self.default = dest.into();
+    }
+
     /// Set a table entry.
     ///
     /// The table will grow as needed to fit `idx`.
@@ -67,8 +104,8 @@ impl JumpTableData {
 
     /// Clear a table entry.
     ///
-    /// The `br_table` instruction will fall through if given an index corresponding to a cleared
-    /// table entry.
+    /// The `br_table` instruction will branch to the default destination or fall through if given
+    /// an index corresponding to a cleared table entry.
     pub fn clear_entry(&mut self, idx: usize) {
         if idx < self.table.len() && self.table[idx].is_some() {
             self.holes += 1;
@@ -88,11 +125,22 @@ impl JumpTableData {
         Entries(self.table.iter().cloned().enumerate())
     }
 
-    /// Checks if any of the entries branch to `ebb`.
+    /// Enumerate all the destinations in the table, including the default destination.
+    ///
+    /// The same EBB may appear more than once.
+    pub fn destinations(&self) -> Destinations {
+        Destinations {
+            entries: self.entries(),
+            default: self.default.expand(),
+        }
+    }
+
+    /// Checks if any of the entries or the default destination branch to `ebb`.
     pub fn branches_to(&self, ebb: Ebb) -> bool {
-        self.table.iter().any(|target_ebb| {
-            target_ebb.expand() == Some(ebb)
-        })
+        self.default.expand() == Some(ebb) ||
+            self.table.iter().any(|target_ebb| {
+                target_ebb.expand() == Some(ebb)
+            })
     }
 
     /// Access the whole table as a mutable slice.
@@ -120,6 +168,23 @@ impl<'a> Iterator for Entries<'a> {
     }
 }
 
+/// Enumerate all destinations of a jump table, ending with the default destination.
+pub struct Destinations<'a> {
+    entries: Entries<'a>,
+    default: Option<Ebb>,
+}
+
+impl<'a> Iterator for Destinations<'a> {
+    type Item = Ebb;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.entries.next() {
+            Some((_, ebb)) => Some(ebb),
+            None => self.default.take(),
+        }
+    }
+}
+
 impl Display for JumpTableData {
     fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
         match self.table.first().and_then(|e| e.expand()) {
@@ -133,6 +198,12 @@ impl Display for JumpTableData {
                 Some(ebb) => write!(fmt, ", {}", ebb)?,
             }
         }
+        if self.min_index != 0 {
+            write!(fmt, ", min {}", self.min_index)?;
+        }
+        if let Some(ebb) = self.default.expand() {
+            write!(fmt, ", default {}", ebb)?;
+        }
         Ok(())
     }
 }
@@ -177,4 +248,31 @@ mod tests {
         let v: Vec<(usize, Ebb)> = jt.entries().collect();
         assert_eq!(v, [(0, e2), (10, e1)]);
     }
+
+    #[test]
+    fn min_index_and_default() {
+        let e1 = Ebb::new(1);
+        let e2 = Ebb::new(2);
+        let e3 = Ebb::new(3);
+
+        let mut jt = JumpTableData::new();
+
+        jt.set_entry(0, e1);
+        jt.set_entry(2, e2);
+        jt.set_min_index(-4);
+        assert_eq!(jt.to_string(), "jump_table ebb1, 0, ebb2, min -4");
+        assert!(!jt.branches_to(e3));
+
+        jt.set_default_dest(Some(e3));
+        assert_eq!(jt.min_index(), -4);
+        assert_eq!(jt.default_dest(), Some(e3));
+        assert_eq!(
+            jt.to_string(),
+            "jump_table ebb1, 0, ebb2, min -4, default ebb3"
+        );
+        assert!(jt.branches_to(e3));
+
+        let v: Vec<Ebb> = jt.destinations().collect();
+        assert_eq!(v, [e1, e2, e3]);
+    }
 }
@@ -0,0 +1,82 @@
+//! Vector lane masks.
+//!
+//! The vector shuffle instructions select lanes according to an immediate list of lane indexes.
+//! The list is too large to fit in an `InstructionData`, so it is stored in the data flow graph
+//! and referenced by an `ir::entities::LaneMask`.
+
+use std::fmt::{self, Display, Formatter};
+use std::slice;
+use std::vec::Vec;
+
+/// Contents of a lane mask: The source lane index for each lane in the result.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct LaneMaskData {
+    lanes: Vec<u8>,
+}
+
+impl LaneMaskData {
+    /// Create a new empty lane mask.
+    pub fn new() -> Self {
+        Self { lanes: Vec::new() }
+    }
+
+    /// Create a lane mask with the given lane indexes.
+    pub fn with_lanes(lanes: &[u8]) -> Self {
+        Self { lanes: lanes.to_vec() }
+    }
+
+    /// Append the source lane index for the next result lane.
+    pub fn push(&mut self, lane: u8) {
+        self.lanes.push(lane)
+    }
+
+    /// Get the number of lanes in the mask.
+    pub fn len(&self) -> usize {
+        self.lanes.len()
+    }
+
+    /// Check if the mask has no lanes.
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+    }
+
+    /// Get the source lane indexes as a slice.
+    pub fn as_slice(&self) -> &[u8] {
+        &self.lanes
+    }
+
+    /// Iterate over the source lane indexes.
+    pub fn iter(&self) -> slice::Iter<u8> {
+        self.lanes.iter()
+    }
+}
+
+impl Display for LaneMaskData {
+    fn fmt(&self, fmt: &mut Formatter) -> fmt::Result {
+        write!(fmt, "[")?;
+        for (i, lane) in self.lanes.iter().enumerate() {
+            if i > 0 {
+                write!(fmt, ", ")?;
+            }
+            write!(fmt, "{}", lane)?;
+        }
+        write!(fmt, "]")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LaneMaskData;
+    use std::string::ToString;
+
+    #[test]
+    fn display() {
+        assert_eq!(LaneMaskData::new().to_string(), "[]");
+
+        let mut mask = LaneMaskData::with_lanes(&[0, 5]);
+        mask.push(2);
+        assert_eq!(mask.len(), 3);
+        assert_eq!(mask.as_slice(), &[0, 5, 2]);
+        assert_eq!(mask.to_string(), "[0, 5, 2]");
+    }
+}
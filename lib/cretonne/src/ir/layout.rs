@@ -4,7 +4,7 @@
 //! determined by the `Layout` data structure defined in this module.
 
 use entity::EntityMap;
-use ir::{Ebb, Inst};
+use ir::{Ebb, Inst, TableMemory};
 use ir::progpoint::{ProgramOrder, ExpandedProgramPoint};
 use packed_option::PackedOption;
 use std::cmp;
@@ -59,6 +59,22 @@ impl Layout {
         self.first_ebb = None;
         self.last_ebb = None;
     }
+
+    /// Get the memory used by the layout.
+    pub fn memory_usage(&self) -> TableMemory {
+        let mut mem = TableMemory::new::<EbbNode>(self.ebbs.len(), self.ebbs.capacity());
+        mem.add(TableMemory::new::<InstNode>(
+            self.insts.len(),
+            self.insts.capacity(),
+        ));
+        mem
+    }
+
+    /// Release the unused capacity of the layout tables.
+    pub fn shrink_to_fit(&mut self) {
+        self.ebbs.shrink_to_fit();
+        self.insts.shrink_to_fit();
+    }
 }
 
 // Sequence numbers.
@@ -427,6 +443,40 @@ impl Layout {
         }
     }
 
+    /// Move `ebb` and all of its instructions to the end of the layout.
+    pub fn move_ebb_to_end(&mut self, ebb: Ebb) {
+        debug_assert!(self.is_ebb_inserted(ebb), "EBB not in the layout");
+        let last = self.last_ebb.expect("EBB in empty layout");
+        if last == ebb {
+            return;
+        }
+
+        // Unlink `ebb` from its current position. It isn't the last EBB, so it has a successor.
+        let prev = self.ebbs[ebb].prev;
+        let next = self.ebbs[ebb].next.unwrap();
+        match prev.expand() {
+            None => self.first_ebb = Some(next),
+            Some(p) => self.ebbs[p].next = next.into(),
+        }
+        self.ebbs[next].prev = prev;
+
+        // Append it after `last`.
+        self.ebbs[ebb].prev = last.into();
+        self.ebbs[ebb].next = None.into();
+        self.ebbs[last].next = ebb.into();
+        self.last_ebb = Some(ebb);
+
+        // Nothing follows `ebb` now, so it can be numbered with major strides.
+        let mut seq = self.last_ebb_seq(last) + MAJOR_STRIDE;
+        self.ebbs[ebb].seq = seq;
+        let mut next_inst = self.ebbs[ebb].first_inst.expand();
+        while let Some(inst) = next_inst {
+            seq += MAJOR_STRIDE;
+            self.insts[inst].seq = seq;
+            next_inst = self.insts[inst].next.expand();
+        }
+    }
+
     /// Return an iterator over all EBBs in layout order.
     pub fn ebbs(&self) -> Ebbs {
         Ebbs {
@@ -949,6 +999,34 @@ mod tests {
         verify(&mut layout, &[(e1, &[]), (e0, &[]), (e2, &[])]);
     }
 
+    #[test]
+    fn move_ebb_to_end() {
+        let mut layout = Layout::new();
+        let e0 = Ebb::new(0);
+        let e1 = Ebb::new(1);
+        let e2 = Ebb::new(2);
+        let i0 = Inst::new(0);
+        let i1 = Inst::new(1);
+        let i2 = Inst::new(2);
+
+        layout.append_ebb(e0);
+        layout.append_inst(i0, e0);
+        layout.append_ebb(e1);
+        layout.append_inst(i1, e1);
+        layout.append_inst(i2, e1);
+        layout.append_ebb(e2);
+
+        layout.move_ebb_to_end(e1);
+        verify(&mut layout, &[(e0, &[i0]), (e2, &[]), (e1, &[i1, i2])]);
+
+        layout.move_ebb_to_end(e0);
+        verify(&mut layout, &[(e2, &[]), (e1, &[i1, i2]), (e0, &[i0])]);
+
+        // Moving the last EBB is a no-op.
+        layout.move_ebb_to_end(e0);
+        verify(&mut layout, &[(e2, &[]), (e1, &[i1, i2]), (e0, &[i0])]);
+    }
+
     #[test]
     fn append_inst() {
         let mut layout = Layout::new();
@@ -1,8 +1,9 @@
 //! Naming well-known routines in the runtime library.
 
-use ir::{types, Opcode, Type};
+use ir::{types, CallConv, ExternalName, Opcode, Type};
 use std::fmt;
 use std::str::FromStr;
+use std::vec::Vec;
 
 /// The name of a runtime library routine.
 ///
@@ -30,9 +31,139 @@ pub enum LibCall {
     NearestF32,
     /// nearest.f64
     NearestF64,
+    /// Copy a block of memory: `memcpy(dest, src, size)`.
+    Memcpy,
+    /// fadd.f32 (`__addsf3`)
+    FaddF32,
+    /// fsub.f32 (`__subsf3`)
+    FsubF32,
+    /// fmul.f32 (`__mulsf3`)
+    FmulF32,
+    /// fdiv.f32 (`__divsf3`)
+    FdivF32,
+    /// sqrt.f32 (`sqrtf`)
+    SqrtF32,
+    /// fma.f32 (`fmaf`)
+    FmaF32,
+    /// fmin.f32
+    FminF32,
+    /// fmax.f32
+    FmaxF32,
+    /// fadd.f64 (`__adddf3`)
+    FaddF64,
+    /// fsub.f64 (`__subdf3`)
+    FsubF64,
+    /// fmul.f64 (`__muldf3`)
+    FmulF64,
+    /// fdiv.f64 (`__divdf3`)
+    FdivF64,
+    /// sqrt.f64 (`sqrt`)
+    SqrtF64,
+    /// fma.f64 (`fma`)
+    FmaF64,
+    /// fmin.f64
+    FminF64,
+    /// fmax.f64
+    FmaxF64,
+    /// Compare two f32 values, returning zero if equal and ordered (`__eqsf2`)
+    EqF32,
+    /// Compare two f32 values, returning negative if less and ordered (`__ltsf2`)
+    LtF32,
+    /// Compare two f32 values, returning non-positive if less or equal and ordered (`__lesf2`)
+    LeF32,
+    /// Compare two f32 values, returning positive if greater and ordered (`__gtsf2`)
+    GtF32,
+    /// Compare two f32 values, returning non-negative if greater or equal and ordered (`__gesf2`)
+    GeF32,
+    /// Compare two f32 values, returning non-zero if unordered (`__unordsf2`)
+    UnordF32,
+    /// Compare two f64 values, returning zero if equal and ordered (`__eqdf2`)
+    EqF64,
+    /// Compare two f64 values, returning negative if less and ordered (`__ltdf2`)
+    LtF64,
+    /// Compare two f64 values, returning non-positive if less or equal and ordered (`__ledf2`)
+    LeF64,
+    /// Compare two f64 values, returning positive if greater and ordered (`__gtdf2`)
+    GtF64,
+    /// Compare two f64 values, returning non-negative if greater or equal and ordered (`__gedf2`)
+    GeF64,
+    /// Compare two f64 values, returning non-zero if unordered (`__unorddf2`)
+    UnordF64,
+    /// fcvt_from_sint.f32 from i32 (`__floatsisf`)
+    F32FromI32,
+    /// fcvt_from_uint.f32 from i32 (`__floatunsisf`)
+    F32FromU32,
+    /// fcvt_from_sint.f32 from i64 (`__floatdisf`)
+    F32FromI64,
+    /// fcvt_from_uint.f32 from i64 (`__floatundisf`)
+    F32FromU64,
+    /// fcvt_from_sint.f64 from i32 (`__floatsidf`)
+    F64FromI32,
+    /// fcvt_from_uint.f64 from i32 (`__floatunsidf`)
+    F64FromU32,
+    /// fcvt_from_sint.f64 from i64 (`__floatdidf`)
+    F64FromI64,
+    /// fcvt_from_uint.f64 from i64 (`__floatundidf`)
+    F64FromU64,
+    /// fcvt_to_sint.i32 from f32, without the range checks (`__fixsfsi`)
+    I32FromF32,
+    /// fcvt_to_uint.i32 from f32, without the range checks (`__fixunssfsi`)
+    U32FromF32,
+    /// fcvt_to_sint.i64 from f32, without the range checks (`__fixsfdi`)
+    I64FromF32,
+    /// fcvt_to_uint.i64 from f32, without the range checks (`__fixunssfdi`)
+    U64FromF32,
+    /// fcvt_to_sint.i32 from f64, without the range checks (`__fixdfsi`)
+    I32FromF64,
+    /// fcvt_to_uint.i32 from f64, without the range checks (`__fixunsdfsi`)
+    U32FromF64,
+    /// fcvt_to_sint.i64 from f64, without the range checks (`__fixdfdi`)
+    I64FromF64,
+    /// fcvt_to_uint.i64 from f64, without the range checks (`__fixunsdfdi`)
+    U64FromF64,
+    /// fpromote.f64 from f32 (`__extendsfdf2`)
+    F64FromF32,
+    /// fdemote.f32 from f64 (`__truncdfsf2`)
+    F32FromF64,
+    /// popcnt.i32 (`__popcountsi2`)
+    PopcntI32,
+    /// popcnt.i64 (`__popcountdi2`)
+    PopcntI64,
+    /// clz.i32, returning 32 for zero
+    ClzI32,
+    /// clz.i64, returning 64 for zero
+    ClzI64,
+    /// ctz.i32, returning 32 for zero
+    CtzI32,
+    /// ctz.i64, returning 64 for zero
+    CtzI64,
+    /// Copy a block of memory that may overlap: `memmove(dest, src, size)`.
+    Memmove,
+    /// Fill a block of memory: `memset(dest, byte, size)`, with `byte` passed as an `i32`.
+    Memset,
+    /// imul.i32 (`__mulsi3`)
+    ImulI32,
+    /// imul.i64 (`__muldi3`)
+    ImulI64,
+    /// sdiv.i32, without the trap checks (`__divsi3`)
+    SdivI32,
+    /// sdiv.i64, without the trap checks (`__divdi3`)
+    SdivI64,
+    /// udiv.i32, without the trap checks (`__udivsi3`)
+    UdivI32,
+    /// udiv.i64, without the trap checks (`__udivdi3`)
+    UdivI64,
+    /// srem.i32, without the trap checks (`__modsi3`)
+    SremI32,
+    /// srem.i64, without the trap checks (`__moddi3`)
+    SremI64,
+    /// urem.i32, without the trap checks (`__umodsi3`)
+    UremI32,
+    /// urem.i64, without the trap checks (`__umoddi3`)
+    UremI64,
 }
 
-const NAME: [&str; 8] = [
+const NAME: [&str; 73] = [
     "CeilF32",
     "CeilF64",
     "FloorF32",
@@ -41,6 +172,71 @@ const NAME: [&str; 8] = [
     "TruncF64",
     "NearestF32",
     "NearestF64",
+    "Memcpy",
+    "FaddF32",
+    "FsubF32",
+    "FmulF32",
+    "FdivF32",
+    "SqrtF32",
+    "FmaF32",
+    "FminF32",
+    "FmaxF32",
+    "FaddF64",
+    "FsubF64",
+    "FmulF64",
+    "FdivF64",
+    "SqrtF64",
+    "FmaF64",
+    "FminF64",
+    "FmaxF64",
+    "EqF32",
+    "LtF32",
+    "LeF32",
+    "GtF32",
+    "GeF32",
+    "UnordF32",
+    "EqF64",
+    "LtF64",
+    "LeF64",
+    "GtF64",
+    "GeF64",
+    "UnordF64",
+    "F32FromI32",
+    "F32FromU32",
+    "F32FromI64",
+    "F32FromU64",
+    "F64FromI32",
+    "F64FromU32",
+    "F64FromI64",
+    "F64FromU64",
+    "I32FromF32",
+    "U32FromF32",
+    "I64FromF32",
+    "U64FromF32",
+    "I32FromF64",
+    "U32FromF64",
+    "I64FromF64",
+    "U64FromF64",
+    "F64FromF32",
+    "F32FromF64",
+    "PopcntI32",
+    "PopcntI64",
+    "ClzI32",
+    "ClzI64",
+    "CtzI32",
+    "CtzI64",
+    "Memmove",
+    "Memset",
+    "ImulI32",
+    "ImulI64",
+    "SdivI32",
+    "SdivI64",
+    "UdivI32",
+    "UdivI64",
+    "SremI32",
+    "SremI64",
+    "UremI32",
+    "UremI64",
 ];
 
 impl fmt::Display for LibCall {
@@ -62,6 +258,71 @@ impl FromStr for LibCall {
             "TruncF64" => Ok(LibCall::TruncF64),
             "NearestF32" => Ok(LibCall::NearestF32),
             "NearestF64" => Ok(LibCall::NearestF64),
+            "Memcpy" => Ok(LibCall::Memcpy),
+            "FaddF32" => Ok(LibCall::FaddF32),
+            "FsubF32" => Ok(LibCall::FsubF32),
+            "FmulF32" => Ok(LibCall::FmulF32),
+            "FdivF32" => Ok(LibCall::FdivF32),
+            "SqrtF32" => Ok(LibCall::SqrtF32),
+            "FmaF32" => Ok(LibCall::FmaF32),
+            "FminF32" => Ok(LibCall::FminF32),
+            "FmaxF32" => Ok(LibCall::FmaxF32),
+            "FaddF64" => Ok(LibCall::FaddF64),
+            "FsubF64" => Ok(LibCall::FsubF64),
+            "FmulF64" => Ok(LibCall::FmulF64),
+            "FdivF64" => Ok(LibCall::FdivF64),
+            "SqrtF64" => Ok(LibCall::SqrtF64),
+            "FmaF64" => Ok(LibCall::FmaF64),
+            "FminF64" => Ok(LibCall::FminF64),
+            "FmaxF64" => Ok(LibCall::FmaxF64),
+            "EqF32" => Ok(LibCall::EqF32),
+            "LtF32" => Ok(LibCall::LtF32),
+            "LeF32" => Ok(LibCall::LeF32),
+            "GtF32" => Ok(LibCall::GtF32),
+            "GeF32" => Ok(LibCall::GeF32),
+            "UnordF32" => Ok(LibCall::UnordF32),
+            "EqF64" => Ok(LibCall::EqF64),
+            "LtF64" => Ok(LibCall::LtF64),
+            "LeF64" => Ok(LibCall::LeF64),
+            "GtF64" => Ok(LibCall::GtF64),
+            "GeF64" => Ok(LibCall::GeF64),
+            "UnordF64" => Ok(LibCall::UnordF64),
+            "F32FromI32" => Ok(LibCall::F32FromI32),
+            "F32FromU32" => Ok(LibCall::F32FromU32),
+            "F32FromI64" => Ok(LibCall::F32FromI64),
+            "F32FromU64" => Ok(LibCall::F32FromU64),
+            "F64FromI32" => Ok(LibCall::F64FromI32),
+            "F64FromU32" => Ok(LibCall::F64FromU32),
+            "F64FromI64" => Ok(LibCall::F64FromI64),
+            "F64FromU64" => Ok(LibCall::F64FromU64),
+            "I32FromF32" => Ok(LibCall::I32FromF32),
+            "U32FromF32" => Ok(LibCall::U32FromF32),
+            "I64FromF32" => Ok(LibCall::I64FromF32),
+            "U64FromF32" => Ok(LibCall::U64FromF32),
+            "I32FromF64" => Ok(LibCall::I32FromF64),
+            "U32FromF64" => Ok(LibCall::U32FromF64),
+            "I64FromF64" => Ok(LibCall::I64FromF64),
+            "U64FromF64" => Ok(LibCall::U64FromF64),
+            "F64FromF32" => Ok(LibCall::F64FromF32),
+            "F32FromF64" => Ok(LibCall::F32FromF64),
+            "PopcntI32" => Ok(LibCall::PopcntI32),
+            "PopcntI64" => Ok(LibCall::PopcntI64),
+            "ClzI32" => Ok(LibCall::ClzI32),
+            "ClzI64" => Ok(LibCall::ClzI64),
+            "CtzI32" => Ok(LibCall::CtzI32),
+            "CtzI64" => Ok(LibCall::CtzI64),
+            "Memmove" => Ok(LibCall::Memmove),
+            "Memset" => Ok(LibCall::Memset),
+            "ImulI32" => Ok(LibCall::ImulI32),
+            "ImulI64" => Ok(LibCall::ImulI64),
+            "SdivI32" => Ok(LibCall::SdivI32),
+            "SdivI64" => Ok(LibCall::SdivI64),
+            "UdivI32" => Ok(LibCall::UdivI32),
+            "UdivI64" => Ok(LibCall::UdivI64),
+            "SremI32" => Ok(LibCall::SremI32),
+            "SremI64" => Ok(LibCall::SremI64),
+            "UremI32" => Ok(LibCall::UremI32),
+            "UremI64" => Ok(LibCall::UremI64),
             _ => Err(()),
         }
     }
@@ -80,6 +341,14 @@ impl LibCall {
                     Opcode::Floor => LibCall::FloorF32,
                     Opcode::Trunc => LibCall::TruncF32,
                     Opcode::Nearest => LibCall::NearestF32,
+                    Opcode::Fadd => LibCall::FaddF32,
+                    Opcode::Fsub => LibCall::FsubF32,
+                    Opcode::Fmul => LibCall::FmulF32,
+                    Opcode::Fdiv => LibCall::FdivF32,
+                    Opcode::Sqrt => LibCall::SqrtF32,
+                    Opcode::Fma => LibCall::FmaF32,
+                    Opcode::Fmin => LibCall::FminF32,
+                    Opcode::Fmax => LibCall::FmaxF32,
                     _ => return None,
                 }
             }
@@ -89,12 +358,131 @@ impl LibCall {
                     Opcode::Floor => LibCall::FloorF64,
                     Opcode::Trunc => LibCall::TruncF64,
                     Opcode::Nearest => LibCall::NearestF64,
+                    Opcode::Fadd => LibCall::FaddF64,
+                    Opcode::Fsub => LibCall::FsubF64,
+                    Opcode::Fmul => LibCall::FmulF64,
+                    Opcode::Fdiv => LibCall::FdivF64,
+                    Opcode::Sqrt => LibCall::SqrtF64,
+                    Opcode::Fma => LibCall::FmaF64,
+                    Opcode::Fmin => LibCall::FminF64,
+                    Opcode::Fmax => LibCall::FmaxF64,
+                    _ => return None,
+                }
+            }
+            types::I32 => {
+                match opcode {
+                    Opcode::Popcnt => LibCall::PopcntI32,
+                    Opcode::Clz => LibCall::ClzI32,
+                    Opcode::Ctz => LibCall::CtzI32,
+                    Opcode::Imul => LibCall::ImulI32,
+                    Opcode::Sdiv => LibCall::SdivI32,
+                    Opcode::Udiv => LibCall::UdivI32,
+                    Opcode::Srem => LibCall::SremI32,
+                    Opcode::Urem => LibCall::UremI32,
+                    _ => return None,
+                }
+            }
+            types::I64 => {
+                match opcode {
+                    Opcode::Popcnt => LibCall::PopcntI64,
+                    Opcode::Clz => LibCall::ClzI64,
+                    Opcode::Ctz => LibCall::CtzI64,
+                    Opcode::Imul => LibCall::ImulI64,
+                    Opcode::Sdiv => LibCall::SdivI64,
+                    Opcode::Udiv => LibCall::UdivI64,
+                    Opcode::Srem => LibCall::SremI64,
+                    Opcode::Urem => LibCall::UremI64,
                     _ => return None,
                 }
             }
             _ => return None,
         })
     }
+
+    /// Get the well-known library call name to use as a replacement for a conversion instruction
+    /// with the given opcode from type `from` to type `to`.
+    ///
+    /// Returns `None` if no well-known library routine name exists for that conversion.
+    pub fn for_conversion(opcode: Opcode, from: Type, to: Type) -> Option<LibCall> {
+        use ir::types::{F32, F64, I32, I64};
+        Some(match (opcode, from, to) {
+            (Opcode::FcvtFromSint, I32, F32) => LibCall::F32FromI32,
+            (Opcode::FcvtFromUint, I32, F32) => LibCall::F32FromU32,
+            (Opcode::FcvtFromSint, I64, F32) => LibCall::F32FromI64,
+            (Opcode::FcvtFromUint, I64, F32) => LibCall::F32FromU64,
+            (Opcode::FcvtFromSint, I32, F64) => LibCall::F64FromI32,
+            (Opcode::FcvtFromUint, I32, F64) => LibCall::F64FromU32,
+            (Opcode::FcvtFromSint, I64, F64) => LibCall::F64FromI64,
+            (Opcode::FcvtFromUint, I64, F64) => LibCall::F64FromU64,
+            (Opcode::FcvtToSint, F32, I32) => LibCall::I32FromF32,
+            (Opcode::FcvtToUint, F32, I32) => LibCall::U32FromF32,
+            (Opcode::FcvtToSint, F32, I64) => LibCall::I64FromF32,
+            (Opcode::FcvtToUint, F32, I64) => LibCall::U64FromF32,
+            (Opcode::FcvtToSint, F64, I32) => LibCall::I32FromF64,
+            (Opcode::FcvtToUint, F64, I32) => LibCall::U32FromF64,
+            (Opcode::FcvtToSint, F64, I64) => LibCall::I64FromF64,
+            (Opcode::FcvtToUint, F64, I64) => LibCall::U64FromF64,
+            (Opcode::Fpromote, F32, F64) => LibCall::F64FromF32,
+            (Opcode::Fdemote, F64, F32) => LibCall::F32FromF64,
+            _ => return None,
+        })
+    }
+}
+
+/// The symbol names and calling conventions used for runtime library calls.
+///
+/// By default, a library call refers to its routine with an `ExternalName::LibCall` name, and uses
+/// the native calling convention. Embedders whose runtime library uses other names or calling
+/// conventions can override them for the `TargetIsa` with `isa::Builder::set_libcall_names()`.
+#[derive(Clone, Debug)]
+pub struct LibCallNames {
+    call_conv: CallConv,
+    overrides: Vec<(LibCall, ExternalName, CallConv)>,
+}
+
+impl LibCallNames {
+    /// Create a configuration using the default names and the native calling convention.
+    pub fn new() -> Self {
+        Self {
+            call_conv: CallConv::Native,
+            overrides: Vec::new(),
+        }
+    }
+
+    /// Set the calling convention used by the library calls that don't have an override.
+    pub fn set_call_conv(&mut self, call_conv: CallConv) {
+        self.call_conv = call_conv;
+    }
+
+    /// Call `libcall` as `name` with the calling convention `call_conv`.
+    pub fn set(&mut self, libcall: LibCall, name: ExternalName, call_conv: CallConv) {
+        self.overrides.retain(|&(lc, _, _)| lc != libcall);
+        self.overrides.push((libcall, name, call_conv));
+    }
+
+    /// Get the external name used to call `libcall`.
+    pub fn name(&self, libcall: LibCall) -> ExternalName {
+        self.overrides
+            .iter()
+            .find(|&&(lc, _, _)| lc == libcall)
+            .map_or(ExternalName::LibCall(libcall), |&(_, ref name, _)| {
+                name.clone()
+            })
+    }
+
+    /// Get the calling convention used to call `libcall`.
+    pub fn call_conv(&self, libcall: LibCall) -> CallConv {
+        self.overrides
+            .iter()
+            .find(|&&(lc, _, _)| lc == libcall)
+            .map_or(self.call_conv, |&(_, _, call_conv)| call_conv)
+    }
+}
+
+impl Default for LibCallNames {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[cfg(test)]
@@ -106,10 +494,49 @@ mod test {
     fn display() {
         assert_eq!(LibCall::CeilF32.to_string(), "CeilF32");
         assert_eq!(LibCall::NearestF64.to_string(), "NearestF64");
+        assert_eq!(LibCall::CtzI64.to_string(), "CtzI64");
+        assert_eq!(LibCall::UremI64.to_string(), "UremI64");
     }
 
     #[test]
     fn parsing() {
         assert_eq!("FloorF32".parse(), Ok(LibCall::FloorF32));
+        assert_eq!("F32FromF64".parse(), Ok(LibCall::F32FromF64));
+        assert_eq!("PopcntI32".parse(), Ok(LibCall::PopcntI32));
+        assert_eq!("Memset".parse(), Ok(LibCall::Memset));
+        assert_eq!("SdivI32".parse(), Ok(LibCall::SdivI32));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(
+            LibCall::for_conversion(Opcode::FcvtFromUint, types::I64, types::F32),
+            Some(LibCall::F32FromU64)
+        );
+        assert_eq!(
+            LibCall::for_conversion(Opcode::Fpromote, types::F32, types::F64),
+            Some(LibCall::F64FromF32)
+        );
+        assert_eq!(
+            LibCall::for_conversion(Opcode::FcvtToSint, types::F32, types::I16),
+            None
+        );
+    }
+
+    #[test]
+    fn names() {
+        let mut names = LibCallNames::new();
+        assert_eq!(names.name(LibCall::CeilF32), ExternalName::LibCall(LibCall::CeilF32));
+        assert_eq!(names.call_conv(LibCall::CeilF32), CallConv::Native);
+
+        names.set_call_conv(CallConv::SpiderWASM);
+        names.set(
+            LibCall::FloorF64,
+            ExternalName::testcase("floor"),
+            CallConv::WindowsFastcall,
+        );
+        assert_eq!(names.name(LibCall::FloorF64), ExternalName::testcase("floor"));
+        assert_eq!(names.call_conv(LibCall::FloorF64), CallConv::WindowsFastcall);
+        assert_eq!(names.call_conv(LibCall::CeilF32), CallConv::SpiderWASM);
     }
 }
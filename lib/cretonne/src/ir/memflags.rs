@@ -5,9 +5,10 @@ use std::fmt;
 enum FlagBit {
     Notrap,
     Aligned,
+    Readonly,
 }
 
-const NAMES: [&str; 2] = ["notrap", "aligned"];
+const NAMES: [&str; 3] = ["notrap", "aligned", "readonly"];
 
 /// Flags for memory operations like load/store.
 ///
@@ -35,6 +36,10 @@ impl MemFlags {
         self.bits |= 1 << bit as usize
     }
 
+    fn clear(&mut self, bit: FlagBit) {
+        self.bits &= !(1 << bit as usize)
+    }
+
     /// Set a flag bit by name.
     ///
     /// Returns true if the flag was found and set, false for an unknown flag name.
@@ -79,6 +84,25 @@ impl MemFlags {
     pub fn set_aligned(&mut self) {
         self.set(FlagBit::Aligned)
     }
+
+    /// Clear the `aligned` flag.
+    pub fn clear_aligned(&mut self) {
+        self.clear(FlagBit::Aligned)
+    }
+
+    /// Test if the `readonly` flag is set.
+    ///
+    /// Loads with this flag read memory that is not written while the function is executing. This
+    /// makes it possible to treat the load like a pure computation of its address, so it can be
+    /// merged with identical loads and moved across stores and calls.
+    pub fn readonly(self) -> bool {
+        self.read(FlagBit::Readonly)
+    }
+
+    /// Set the `readonly` flag.
+    pub fn set_readonly(&mut self) {
+        self.set(FlagBit::Readonly)
+    }
 }
 
 impl fmt::Display for MemFlags {
@@ -0,0 +1,212 @@
+//! Function memory usage.
+//!
+//! A `FunctionMemory` reports the heap memory used by the entity tables of a function, both the
+//! bytes holding entries and the bytes allocated. The tables grow as a function is compiled, and
+//! `Function::shrink_to_fit()` releases the unused capacity of a function that is kept around.
+//! The reports for multiple functions can be merged to get the totals for a whole module.
+
+use entity::{EntityMap, EntityRef};
+use ir::{Function, GlobalVarData, HeapData, JumpTableData, TableData};
+use std::fmt;
+use std::mem;
+
+/// Memory used by a table, in bytes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TableMemory {
+    /// Bytes holding entries.
+    pub used: usize,
+
+    /// Bytes allocated, including the unused capacity.
+    pub allocated: usize,
+}
+
+impl TableMemory {
+    /// Get the memory used by a table of `len` entries of type `T` with room for `capacity`.
+    pub fn new<T>(len: usize, capacity: usize) -> Self {
+        Self {
+            used: len * mem::size_of::<T>(),
+            allocated: capacity * mem::size_of::<T>(),
+        }
+    }
+
+    /// Add the memory used by another table.
+    pub fn add(&mut self, other: Self) {
+        self.used += other.used;
+        self.allocated += other.allocated;
+    }
+}
+
+/// Memory used by the tables of one function, or a collection of functions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionMemory {
+    /// Number of functions included in this report.
+    pub functions: usize,
+
+    /// Instruction data and result lists.
+    pub insts: TableMemory,
+
+    /// Value definitions.
+    pub values: TableMemory,
+
+    /// EBB parameter lists.
+    pub ebbs: TableMemory,
+
+    /// The pool holding instruction arguments, results, and EBB parameters.
+    pub value_lists: TableMemory,
+
+    /// The layout order of EBBs and instructions.
+    pub layout: TableMemory,
+
+    /// Tables filled in by compilation: encodings, value locations, EBB offsets and alignments,
+    /// and source locations.
+    pub side_tables: TableMemory,
+
+    /// Stack slots, global variables, heaps, tables, jump tables, signatures, external functions,
+    /// and lane masks.
+    pub other: TableMemory,
+}
+
+impl FunctionMemory {
+    /// Compute the memory used by `func`.
+    pub fn new(func: &Function) -> Self {
+        let mut mem = Self {
+            functions: 1,
+            layout: func.layout.memory_usage(),
+            ..Self::default()
+        };
+        func.dfg.memory_usage(&mut mem);
+
+        for table in &[
+            map_memory(&func.encodings),
+            map_memory(&func.locations),
+            map_memory(&func.offsets),
+            map_memory(&func.ebb_align),
+            map_memory(&func.srclocs),
+        ]
+        {
+            mem.side_tables.add(*table);
+        }
+
+        mem.other.add(func.stack_slots.memory_usage());
+        mem.other.add(TableMemory::new::<GlobalVarData>(
+            func.global_vars.len(),
+            func.global_vars.capacity(),
+        ));
+        mem.other.add(TableMemory::new::<HeapData>(
+            func.heaps.len(),
+            func.heaps.capacity(),
+        ));
+        mem.other.add(TableMemory::new::<TableData>(
+            func.tables.len(),
+            func.tables.capacity(),
+        ));
+        mem.other.add(TableMemory::new::<JumpTableData>(
+            func.jump_tables.len(),
+            func.jump_tables.capacity(),
+        ));
+        mem
+    }
+
+    /// Get the total memory used by all the tables.
+    pub fn total(&self) -> TableMemory {
+        let mut total = TableMemory::default();
+        for table in &[
+            self.insts,
+            self.values,
+            self.ebbs,
+            self.value_lists,
+            self.layout,
+            self.side_tables,
+            self.other,
+        ]
+        {
+            total.add(*table);
+        }
+        total
+    }
+
+    /// Add the memory used in `other` to this report.
+    pub fn merge(&mut self, other: &Self) {
+        self.functions += other.functions;
+        self.insts.add(other.insts);
+        self.values.add(other.values);
+        self.ebbs.add(other.ebbs);
+        self.value_lists.add(other.value_lists);
+        self.layout.add(other.layout);
+        self.side_tables.add(other.side_tables);
+        self.other.add(other.other);
+    }
+}
+
+/// Get the memory used by a secondary entity map.
+fn map_memory<K: EntityRef, V: Clone>(map: &EntityMap<K, V>) -> TableMemory {
+    TableMemory::new::<V>(map.len(), map.capacity())
+}
+
+impl fmt::Display for FunctionMemory {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "functions: {}", self.functions)?;
+        for &(name, table) in &[
+            ("insts", self.insts),
+            ("values", self.values),
+            ("ebbs", self.ebbs),
+            ("value_lists", self.value_lists),
+            ("layout", self.layout),
+            ("side_tables", self.side_tables),
+            ("other", self.other),
+            ("total", self.total()),
+        ]
+        {
+            writeln!(
+                f,
+                "{}: {} bytes used, {} allocated",
+                name,
+                table.used,
+                table.allocated
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder};
+    use ir::types::I32;
+    use std::string::ToString;
+
+    #[test]
+    fn shrink() {
+        let mut func = Function::new();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            let ebb0 = pos.func.dfg.make_ebb();
+            pos.insert_ebb(ebb0);
+            let arg = pos.func.dfg.append_ebb_param(ebb0, I32);
+            let v1 = pos.ins().iadd(arg, arg);
+            let v2 = pos.ins().iadd_imm(v1, 1);
+            pos.ins().return_(&[v2]);
+        }
+
+        let mem = func.memory_usage();
+        assert_eq!(mem.functions, 1);
+        assert!(mem.insts.used > 0);
+        assert!(mem.values.used > 0);
+        assert!(mem.layout.used > 0);
+        assert_eq!(mem.side_tables.used, 0);
+        let total = mem.total();
+        assert!(total.used <= total.allocated);
+
+        func.shrink_to_fit();
+        let shrunk = func.memory_usage();
+        assert_eq!(shrunk.total().used, total.used);
+        assert!(shrunk.total().allocated <= total.allocated);
+
+        let mut merged = shrunk.clone();
+        merged.merge(&shrunk);
+        assert_eq!(merged.functions, 2);
+        assert_eq!(merged.insts.used, 2 * shrunk.insts.used);
+        assert!(merged.to_string().starts_with("functions: 2\ninsts: "));
+    }
+}
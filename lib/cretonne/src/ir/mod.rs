@@ -10,35 +10,47 @@ pub mod jumptable;
 pub mod dfg;
 pub mod layout;
 pub mod function;
+mod atomic;
 mod builder;
 mod extfunc;
 mod extname;
 mod globalvar;
 mod heap;
+mod lanemask;
 mod libcall;
+mod memory;
 mod memflags;
 mod progpoint;
 mod sourceloc;
+mod stats;
+mod table;
 mod trapcode;
 mod valueloc;
 
-pub use ir::builder::{InstBuilder, InstBuilderBase, InstInserterBase, InsertBuilder};
+pub use ir::atomic::{AtomicOrdering, AtomicRmwOp};
+pub use ir::builder::{InstBuilder, InstBuilderBase, InstInserterBase, InsertBuilder,
+                      ReplaceBuilder};
 pub use ir::dfg::{DataFlowGraph, ValueDef};
-pub use ir::entities::{Ebb, Inst, Value, StackSlot, GlobalVar, JumpTable, FuncRef, SigRef, Heap};
+pub use ir::entities::{Ebb, Inst, Value, StackSlot, GlobalVar, JumpTable, FuncRef, SigRef, Heap,
+                       Table, LaneMask};
 pub use ir::extfunc::{Signature, CallConv, AbiParam, ArgumentExtension, ArgumentPurpose,
                       ExtFuncData};
 pub use ir::extname::ExternalName;
 pub use ir::function::Function;
-pub use ir::globalvar::GlobalVarData;
+pub use ir::globalvar::{GlobalVarData, TlsModel};
 pub use ir::heap::{HeapData, HeapStyle, HeapBase};
 pub use ir::instructions::{Opcode, InstructionData, VariableArgs, ValueList, ValueListPool};
 pub use ir::jumptable::JumpTableData;
+pub use ir::lanemask::LaneMaskData;
 pub use ir::layout::Layout;
-pub use ir::libcall::LibCall;
+pub use ir::libcall::{LibCall, LibCallNames};
 pub use ir::memflags::MemFlags;
+pub use ir::memory::{FunctionMemory, TableMemory};
 pub use ir::progpoint::{ProgramPoint, ProgramOrder, ExpandedProgramPoint};
 pub use ir::sourceloc::SourceLoc;
 pub use ir::stackslot::{StackSlots, StackSlotKind, StackSlotData};
+pub use ir::stats::FunctionStats;
+pub use ir::table::TableData;
 pub use ir::trapcode::TrapCode;
 pub use ir::types::Type;
 pub use ir::valueloc::{ValueLoc, ArgumentLoc};
@@ -3,8 +3,8 @@
 //! The `StackSlotData` struct keeps track of a single stack slot in a function.
 //!
 
-use entity::{PrimaryMap, Keys};
-use ir::{Type, StackSlot};
+use entity::{EntityRef, PrimaryMap, Keys};
+use ir::{Type, StackSlot, TableMemory};
 use packed_option::PackedOption;
 use std::cmp;
 use std::fmt;
@@ -190,6 +190,23 @@ impl StackSlots {
         self.frame_size = None;
     }
 
+    /// Get the memory used by the stack slot tables.
+    pub fn memory_usage(&self) -> TableMemory {
+        let mut mem = TableMemory::new::<StackSlotData>(self.slots.len(), self.slots.capacity());
+        mem.add(TableMemory::new::<StackSlot>(
+            self.outgoing.len() + self.emergency.len(),
+            self.outgoing.capacity() + self.emergency.capacity(),
+        ));
+        mem
+    }
+
+    /// Release the unused capacity of the stack slot tables.
+    pub fn shrink_to_fit(&mut self) {
+        self.slots.shrink_to_fit();
+        self.outgoing.shrink_to_fit();
+        self.emergency.shrink_to_fit();
+    }
+
     /// Allocate a new stack slot.
     ///
     /// This function should be primarily used by the text format parser. There are more convenient
@@ -213,6 +230,17 @@ impl StackSlots {
         self.slots.keys()
     }
 
+    /// Remove the stack slots from `ss` onwards, so `ss` becomes the next stack slot created.
+    ///
+    /// The removed slots can't be outgoing argument or emergency slots.
+    pub fn truncate(&mut self, ss: StackSlot) {
+        debug_assert!(
+            self.outgoing.iter().chain(&self.emergency).all(|s| s.index() < ss.index()),
+            "Can't remove an outgoing argument or emergency slot"
+        );
+        self.slots.truncate(ss)
+    }
+
     /// Get a reference to the next stack slot that would be created by `push()`.
     ///
     /// This should just be used by the parser.
@@ -0,0 +1,179 @@
+//! Function statistics.
+//!
+//! A `FunctionStats` summarizes the size of a function: the number of instructions of each
+//! opcode, EBBs, and values, and after compilation, the size of its stack frame and machine code.
+//! The statistics of multiple functions can be merged to get the totals for a whole module.
+
+use binemit::CodeOffset;
+use ir::{Function, Opcode};
+use ir::stackslot::StackSize;
+use isa::TargetIsa;
+use std::fmt;
+use std::vec::Vec;
+
+/// Size statistics for one function, or a collection of functions.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FunctionStats {
+    /// Number of functions included in these statistics.
+    pub functions: usize,
+
+    /// Number of EBBs in the layout.
+    pub ebbs: usize,
+
+    /// Number of instructions in the layout.
+    pub insts: usize,
+
+    /// Number of values defined by the EBBs and instructions in the layout.
+    pub values: usize,
+
+    /// Number of instructions with each opcode, sorted by opcode name.
+    pub opcodes: Vec<(Opcode, usize)>,
+
+    /// Size of the stack frame, once it has been laid out.
+    ///
+    /// For merged statistics, this is the largest frame size.
+    pub frame_size: Option<StackSize>,
+
+    /// Size of the machine code, once branches have been relaxed.
+    ///
+    /// For merged statistics, this is the total code size of the functions that have been
+    /// compiled.
+    pub code_size: Option<CodeOffset>,
+}
+
+impl FunctionStats {
+    /// Compute the statistics for `func`.
+    ///
+    /// The code size is only available when an `isa` is given and the function has been through
+    /// branch relaxation.
+    pub fn new(func: &Function, isa: Option<&TargetIsa>) -> Self {
+        let mut stats = Self {
+            functions: 1,
+            frame_size: func.stack_slots.frame_size,
+            ..Self::default()
+        };
+
+        for ebb in func.layout.ebbs() {
+            stats.ebbs += 1;
+            stats.values += func.dfg.num_ebb_params(ebb);
+            for inst in func.layout.ebb_insts(ebb) {
+                stats.insts += 1;
+                stats.values += func.dfg.inst_results(inst).len();
+                stats.count_opcode(func.dfg[inst].opcode(), 1);
+            }
+        }
+
+        if let Some(isa) = isa {
+            if !func.offsets.is_empty() {
+                let encinfo = isa.encoding_info();
+                stats.code_size = Some(func.layout.last_ebb().map_or(0, |ebb| {
+                    func.inst_offsets(ebb, &encinfo).fold(
+                        func.offsets[ebb],
+                        |_, (offset, _, size)| offset + size,
+                    )
+                }));
+            }
+        }
+
+        stats
+    }
+
+    /// Add `count` instructions with `opcode`, keeping the opcodes sorted by name.
+    fn count_opcode(&mut self, opcode: Opcode, count: usize) {
+        let name = opcode.to_string();
+        match self.opcodes.binary_search_by(|&(op, _)| op.to_string().cmp(&name)) {
+            Ok(idx) => self.opcodes[idx].1 += count,
+            Err(idx) => self.opcodes.insert(idx, (opcode, count)),
+        }
+    }
+
+    /// Get the number of instructions with `opcode`.
+    pub fn opcode_count(&self, opcode: Opcode) -> usize {
+        self.opcodes
+            .iter()
+            .find(|&&(op, _)| op == opcode)
+            .map_or(0, |&(_, count)| count)
+    }
+
+    /// Add the statistics in `other` to these statistics.
+    pub fn merge(&mut self, other: &Self) {
+        self.functions += other.functions;
+        self.ebbs += other.ebbs;
+        self.insts += other.insts;
+        self.values += other.values;
+        for &(opcode, count) in &other.opcodes {
+            self.count_opcode(opcode, count);
+        }
+        self.frame_size = match (self.frame_size, other.frame_size) {
+            (Some(a), Some(b)) => Some(a.max(b)),
+            (a, b) => a.or(b),
+        };
+        self.code_size = match (self.code_size, other.code_size) {
+            (Some(a), Some(b)) => Some(a + b),
+            (a, b) => a.or(b),
+        };
+    }
+}
+
+impl fmt::Display for FunctionStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "functions: {}", self.functions)?;
+        writeln!(f, "ebbs: {}", self.ebbs)?;
+        writeln!(f, "insts: {}", self.insts)?;
+        writeln!(f, "values: {}", self.values)?;
+        if let Some(size) = self.frame_size {
+            writeln!(f, "frame_size: {}", size)?;
+        }
+        if let Some(size) = self.code_size {
+            writeln!(f, "code_size: {}", size)?;
+        }
+        for &(opcode, count) in &self.opcodes {
+            writeln!(f, "opcode {}: {}", opcode, count)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FunctionStats;
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder, Opcode};
+    use ir::types::I32;
+    use std::string::ToString;
+
+    #[test]
+    fn counts() {
+        let mut func = Function::new();
+        {
+            let mut pos = FuncCursor::new(&mut func);
+            let ebb0 = pos.func.dfg.make_ebb();
+            pos.insert_ebb(ebb0);
+            let arg = pos.func.dfg.append_ebb_param(ebb0, I32);
+            let v1 = pos.ins().iadd(arg, arg);
+            let v2 = pos.ins().iadd_imm(v1, 1);
+            let v3 = pos.ins().iadd(v2, v1);
+            pos.ins().return_(&[v3]);
+        }
+
+        let mut stats = FunctionStats::new(&func, None);
+        assert_eq!(stats.functions, 1);
+        assert_eq!(stats.ebbs, 1);
+        assert_eq!(stats.insts, 4);
+        assert_eq!(stats.values, 4);
+        assert_eq!(stats.opcode_count(Opcode::Iadd), 2);
+        assert_eq!(stats.opcode_count(Opcode::Isub), 0);
+        assert_eq!(stats.code_size, None);
+        assert_eq!(
+            stats.to_string(),
+            "functions: 1\nebbs: 1\ninsts: 4\nvalues: 4\n\
+             opcode iadd: 2\nopcode iadd_imm: 1\nopcode return: 1\n"
+        );
+
+        let copy = stats.clone();
+        stats.merge(&copy);
+        assert_eq!(stats.functions, 2);
+        assert_eq!(stats.insts, 8);
+        assert_eq!(stats.opcode_count(Opcode::Iadd), 4);
+    }
+}
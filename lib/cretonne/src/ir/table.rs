@@ -0,0 +1,39 @@
+//! Tables.
+
+use ir::immediates::Imm64;
+use ir::GlobalVar;
+use std::fmt;
+
+/// Information about a table declaration.
+///
+/// A table is an array of fixed-size elements, such as the function pointers called by
+/// WebAssembly's `call_indirect`. Unlike a heap, a table has no guard pages, so every
+/// `table_addr` access is bounds checked against the current number of elements.
+#[derive(Clone)]
+pub struct TableData {
+    /// Global variable holding the table base address.
+    pub base_gv: GlobalVar,
+
+    /// Guaranteed minimum table size in elements. Table accesses before `min_size` don't need
+    /// bounds checking.
+    pub min_size: Imm64,
+
+    /// Global variable holding the current bound of the table in elements.
+    pub bound_gv: GlobalVar,
+
+    /// The size of a table element, in bytes.
+    pub element_size: Imm64,
+}
+
+impl fmt::Display for TableData {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "dynamic {}, min {}, bound {}, element_size {}",
+            self.base_gv,
+            self.min_size,
+            self.bound_gv,
+            self.element_size
+        )
+    }
+}
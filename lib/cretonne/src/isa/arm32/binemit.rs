@@ -1,7 +1,22 @@
 //! Emitting binary ARM32 machine code.
 
-use binemit::{CodeSink, bad_encoding};
+use binemit::{CodeOffset, CodeSink, bad_encoding};
 use ir::{Function, Inst};
 use regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm32.rs"));
+
+/// Emit `bytes` of padding as NOP instructions in either the T32 or the A32 instruction set.
+pub fn emit_padding<CS: CodeSink + ?Sized>(bytes: CodeOffset, thumb: bool, sink: &mut CS) {
+    if thumb {
+        debug_assert_eq!(bytes % 2, 0, "Misaligned T32 padding");
+        for _ in 0..bytes / 2 {
+            sink.put2(0xbf00);
+        }
+    } else {
+        debug_assert_eq!(bytes % 4, 0, "Misaligned A32 padding");
+        for _ in 0..bytes / 4 {
+            sink.put4(0xe320_f000);
+        }
+    }
+}
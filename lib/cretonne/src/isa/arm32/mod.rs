@@ -6,20 +6,24 @@ mod binemit;
 mod enc_tables;
 mod registers;
 
-use binemit::{CodeSink, MemoryCodeSink, emit_function};
+use binemit::{CodeOffset, CodeSink, MemoryCodeSink, emit_function};
 use super::super::settings as shared_settings;
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
-use isa::{TargetIsa, RegInfo, RegClass, EncInfo};
+use isa::{TargetIsa, RegInfo, RegClass, EncInfo, FrameConfig};
 use ir;
 use regalloc;
 use std::fmt;
 use std::boxed::Box;
+use std::hash::{Hash, Hasher};
+use std::vec::Vec;
 
 #[allow(dead_code)]
 struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
 }
 
@@ -27,6 +31,8 @@ struct Isa {
 pub fn isa_builder() -> IsaBuilder {
     IsaBuilder {
         setup: settings::builder(),
+        frame: FrameConfig::default(),
+        libcall_names: ir::LibCallNames::new(),
         constructor: isa_constructor,
     }
 }
@@ -34,6 +40,8 @@ pub fn isa_builder() -> IsaBuilder {
 fn isa_constructor(
     shared_flags: shared_settings::Flags,
     builder: &shared_settings::Builder,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
 ) -> Box<TargetIsa> {
     let level1 = if shared_flags.is_compressed() {
         &enc_tables::LEVEL1_T32[..]
@@ -43,6 +51,8 @@ fn isa_constructor(
     Box::new(Isa {
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        frame,
+        libcall_names,
         cpumode: level1,
     })
 }
@@ -56,6 +66,24 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn hash_flags(&self, mut state: &mut Hasher) {
+        self.name().hash(&mut state);
+        self.shared_flags.hash(&mut state);
+        self.isa_flags.hash(&mut state);
+    }
+
+    fn isa_settings(&self) -> Vec<shared_settings::Setting> {
+        self.isa_flags.settings()
+    }
+
+    fn frame_config(&self) -> &FrameConfig {
+        &self.frame
+    }
+
+    fn libcall_names(&self) -> &ir::LibCallNames {
+        &self.libcall_names
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -66,14 +94,14 @@ impl TargetIsa for Isa {
 
     fn legal_encodings<'a>(
         &'a self,
-        dfg: &'a ir::DataFlowGraph,
+        func: &'a ir::Function,
         inst: &'a ir::InstructionData,
         ctrl_typevar: ir::Type,
     ) -> Encodings<'a> {
         lookup_enclist(
             ctrl_typevar,
             inst,
-            dfg,
+            func,
             self.cpumode,
             &enc_tables::LEVEL2[..],
             &enc_tables::ENCLISTS[..],
@@ -106,8 +134,18 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    fn emit_padding(&self, bytes: CodeOffset, sink: &mut CodeSink) {
+        binemit::emit_padding(bytes, self.shared_flags.is_compressed(), sink)
+    }
+
     fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        let thumb = self.shared_flags.is_compressed();
+        emit_function(
+            func,
+            binemit::emit_inst,
+            |bytes, sink| binemit::emit_padding(bytes, thumb, sink),
+            sink,
+        )
     }
 }
 
@@ -1,26 +1,319 @@
 //! ARM 64 ABI implementation.
+//!
+//! This module implements the AAPCS64 calling convention through the primary
+//! `legalize_signature()` entry point, and the matching stack frame through `prologue_epilogue()`.
 
-use ir;
-use isa::RegClass;
+use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args};
+use cursor::{Cursor, CursorPosition, EncCursor};
+use ir::{self, Type, AbiParam, ArgumentLoc, ArgumentExtension, ArgumentPurpose, InstBuilder};
+use ir::immediates::Imm64;
+use ir::stackslot::{StackOffset, StackSlotKind};
+use isa::{RegClass, RegUnit, StackRef, TargetIsa};
 use regalloc::AllocatableSet;
+use result::{CtonError, CtonResult};
 use settings as shared_settings;
-use super::registers::{GPR, FPR};
+use stack_layout::layout_stack;
+use super::registers::{GPR, FPR, RU};
+use std::i32;
+use std::vec::Vec;
+
+/// Number of argument registers in each of the integer and floating point banks.
+const ARG_REGS: usize = 8;
+
+/// Callee-saved integer registers.
+static CSR_GPRS: [RU; 10] = [
+    RU::x19,
+    RU::x20,
+    RU::x21,
+    RU::x22,
+    RU::x23,
+    RU::x24,
+    RU::x25,
+    RU::x26,
+    RU::x27,
+    RU::x28,
+];
+
+/// Callee-saved floating point registers. Only the low 64 bits are preserved.
+static CSR_FPRS: [RU; 8] = [
+    RU::v8,
+    RU::v9,
+    RU::v10,
+    RU::v11,
+    RU::v12,
+    RU::v13,
+    RU::v14,
+    RU::v15,
+];
+
+struct Args {
+    pointer_bits: u16,
+    pointer_bytes: u32,
+    pointer_type: Type,
+    gpr_used: usize,
+    fpr_used: usize,
+    offset: u32,
+}
+
+impl Args {
+    fn new(bits: u16) -> Args {
+        Args {
+            pointer_bits: bits,
+            pointer_bytes: u32::from(bits) / 8,
+            pointer_type: Type::int(bits).unwrap(),
+            gpr_used: 0,
+            fpr_used: 0,
+            offset: 0,
+        }
+    }
+}
+
+impl ArgAssigner for Args {
+    fn assign(&mut self, arg: &AbiParam) -> ArgAction {
+        fn align(value: u32, to: u32) -> u32 {
+            (value + to - 1) & !(to - 1)
+        }
+
+        let ty = arg.value_type;
+
+        // Check for a legal type.
+        // We don't support SIMD yet, so break all vectors down.
+        if ty.is_vector() {
+            return ValueConversion::VectorSplit.into();
+        }
+
+        // Large integers and booleans are broken down to fit in a register.
+        if !ty.is_float() && ty.bits() > self.pointer_bits {
+            // The halves of a 128-bit integer go in an even-numbered register pair, or a 16-byte
+            // aligned stack location.
+            self.gpr_used = align(self.gpr_used as u32, 2) as usize;
+            self.offset = align(self.offset, 2 * self.pointer_bytes);
+            return ValueConversion::IntSplit.into();
+        }
+
+        // Small integers are extended to the size of a pointer register.
+        if ty.is_int() && ty.bits() < self.pointer_bits {
+            match arg.extension {
+                ArgumentExtension::None => {}
+                ArgumentExtension::Uext => return ValueConversion::Uext(self.pointer_type).into(),
+                ArgumentExtension::Sext => return ValueConversion::Sext(self.pointer_type).into(),
+            }
+        }
+
+        // The address of a returned structure is passed in the indirect result register.
+        if arg.purpose == ArgumentPurpose::StructReturn {
+            return ArgumentLoc::Reg(RU::x8 as RegUnit).into();
+        }
+
+        if ty.is_float() {
+            if self.fpr_used < ARG_REGS {
+                let reg = FPR.unit(self.fpr_used);
+                self.fpr_used += 1;
+                return ArgumentLoc::Reg(reg).into();
+            }
+        } else if self.gpr_used < ARG_REGS {
+            let reg = GPR.unit(self.gpr_used);
+            self.gpr_used += 1;
+            return ArgumentLoc::Reg(reg).into();
+        }
+
+        // Assign a stack location.
+        let loc = ArgumentLoc::Stack(self.offset as i32);
+        self.offset += self.pointer_bytes;
+        debug_assert!(self.offset <= i32::MAX as u32);
+        loc.into()
+    }
+}
 
 /// Legalize `sig`.
 pub fn legalize_signature(
-    _sig: &mut ir::Signature,
+    sig: &mut ir::Signature,
     _flags: &shared_settings::Flags,
-    _current: bool,
+    current: bool,
 ) {
-    unimplemented!()
+    let bits = 64;
+
+    let mut args = Args::new(bits);
+    legalize_args(&mut sig.params, &mut args);
+
+    let mut rets = Args::new(bits);
+    legalize_args(&mut sig.returns, &mut rets);
+
+    if current {
+        let ptr = Type::int(bits).unwrap();
+
+        // Add the link register as an argument and return value.
+        //
+        // The `ret` instruction can return through any register, but the return address
+        // predictor expects `x30` which is also where `bl` leaves it.
+        let link = AbiParam::special_reg(ptr, ArgumentPurpose::Link, RU::x30 as RegUnit);
+        sig.params.push(link);
+        sig.returns.push(link);
+    }
 }
 
 /// Get register class for a type appearing in a legalized signature.
 pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
-    if ty.is_int() { GPR } else { FPR }
+    if ty.is_float() { FPR } else { GPR }
+}
+
+/// Get the callee-saved registers of the AAPCS64 calling convention.
+pub fn callee_saved_registers() -> Vec<RegUnit> {
+    CSR_GPRS
+        .iter()
+        .chain(CSR_FPRS.iter())
+        .map(|&ru| ru as RegUnit)
+        .collect()
 }
 
 /// Get the set of allocatable registers for `func`.
-pub fn allocatable_registers(_func: &ir::Function) -> AllocatableSet {
-    unimplemented!()
+pub fn allocatable_registers(func: &ir::Function, isa: &TargetIsa) -> AllocatableSet {
+    let mut regs = AllocatableSet::new();
+    // %x16 and %x17 are the intra-procedure-call scratch registers. We use %x16 as a scratch
+    // register in multi-instruction encodings, and linker veneers may clobber both.
+    regs.take(GPR, RU::x16 as RegUnit);
+    regs.take(GPR, RU::x17 as RegUnit);
+    regs.take(GPR, RU::x18 as RegUnit); // Platform register.
+    regs.take(GPR, RU::x29 as RegUnit); // Frame pointer.
+    regs.take(GPR, RU::x31 as RegUnit); // Stack pointer or zero register.
+
+    // Without a prologue, nothing saves the callee-saved registers.
+    if isa.frame_config().external_prologue_bytes.is_some() {
+        for reg in isa.callee_saved_registers(func.signature.call_conv) {
+            let rc = if GPR.contains(reg) { GPR } else { FPR };
+            if regs.is_avail(rc, reg) {
+                regs.take(rc, reg);
+            }
+        }
+    }
+
+    regs
+}
+
+/// Find the callee-saved registers in `csrs` that are written by `func`.
+fn used_callee_saved(func: &ir::Function, csrs: Vec<RegUnit>) -> Vec<RegUnit> {
+    let mut used = AllocatableSet::new();
+    let mut mark = |reg: RegUnit| {
+        let rc = if GPR.contains(reg) { GPR } else { FPR };
+        if used.is_avail(rc, reg) {
+            used.take(rc, reg);
+        }
+    };
+
+    for value in func.locations.keys() {
+        if let ir::ValueLoc::Reg(reg) = func.locations[value] {
+            mark(reg);
+        }
+    }
+
+    // Register diversions can also move values into callee-saved registers.
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            match func.dfg[inst] {
+                ir::InstructionData::RegMove { dst, .. } |
+                ir::InstructionData::RegFill { dst, .. } => mark(dst),
+                _ => {}
+            }
+        }
+    }
+
+    csrs.into_iter()
+        .filter(|&reg| {
+            let rc = if GPR.contains(reg) { GPR } else { FPR };
+            !used.is_avail(rc, reg)
+        })
+        .collect()
+}
+
+/// Check that the stack slots accessed by spill and fill instructions can be reached with a
+/// scaled 12-bit offset from the stack pointer.
+fn check_spill_offsets(func: &ir::Function) -> CtonResult {
+    for ss in func.stack_slots.keys() {
+        let slot = &func.stack_slots[ss];
+        match slot.kind {
+            StackSlotKind::SpillSlot |
+            StackSlotKind::IncomingArg |
+            StackSlotKind::OutgoingArg => {
+                let offset = StackRef::sp(ss, &func.stack_slots).offset;
+                if offset > 4095 * slot.size as StackOffset {
+                    return Err(CtonError::ImplLimitExceeded);
+                }
+            }
+            StackSlotKind::ExplicitSlot |
+            StackSlotKind::EmergencySlot => {}
+        }
+    }
+    Ok(())
+}
+
+/// Compute the stack frame layout and insert the prologue and epilogues.
+///
+/// Code generated by Cretonne never modifies the frame pointer, and the link register is
+/// preserved as a special-purpose argument, so only the callee-saved registers that are actually
+/// used need saving. They are spilled to spill slots after allocating the frame, and filled again
+/// before every return.
+pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> CtonResult {
+    if let Some(bytes) = isa.frame_config().external_prologue_bytes {
+        let mut ss = ir::StackSlotData::new(StackSlotKind::IncomingArg, bytes);
+        ss.offset = Some(-(bytes as StackOffset));
+        func.stack_slots.push(ss);
+        layout_stack(&mut func.stack_slots, 16)?;
+        return check_spill_offsets(func);
+    }
+
+    let mut csrs = Vec::new();
+    let candidates = isa.callee_saved_registers(func.signature.call_conv);
+    for reg in used_callee_saved(func, candidates) {
+        let ty = if GPR.contains(reg) {
+            ir::types::I64
+        } else {
+            ir::types::F64
+        };
+        let csr_arg = AbiParam::special_reg(ty, ArgumentPurpose::CalleeSaved, reg);
+        func.signature.params.push(csr_arg);
+        func.signature.returns.push(csr_arg);
+        csrs.push((reg, ty, func.stack_slots.make_spill_slot(ty)));
+    }
+
+    // The stack pointer must always be 16-byte aligned.
+    let frame_size = i64::from(layout_stack(&mut func.stack_slots, 16)?);
+    if frame_size >= 1 << 23 {
+        return Err(CtonError::ImplLimitExceeded);
+    }
+    check_spill_offsets(func)?;
+
+    // Allocate the frame and save the callee-saved registers.
+    let entry_ebb = func.layout.entry_block().expect("missing entry block");
+    let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
+    if frame_size > 0 {
+        pos.ins().adjust_sp_imm(Imm64::new(-frame_size));
+    }
+    let mut saved = Vec::with_capacity(csrs.len());
+    for &(reg, ty, ss) in &csrs {
+        let csr_arg = pos.func.dfg.append_ebb_param(entry_ebb, ty);
+        pos.func.locations[csr_arg] = ir::ValueLoc::Reg(reg);
+        let spill = pos.ins().spill(csr_arg);
+        pos.func.locations[spill] = ir::ValueLoc::Stack(ss);
+        saved.push(spill);
+    }
+
+    // Restore them and free the frame before every return.
+    let mut pos = pos.at_position(CursorPosition::Nowhere);
+    while let Some(ebb) = pos.next_ebb() {
+        pos.goto_last_inst(ebb);
+        let inst = match pos.current_inst() {
+            Some(inst) if pos.func.dfg[inst].opcode().is_return() => inst,
+            _ => continue,
+        };
+        for (&(reg, _, _), &spill) in csrs.iter().zip(&saved) {
+            let csr_ret = pos.ins().fill(spill);
+            pos.func.locations[csr_ret] = ir::ValueLoc::Reg(reg);
+            pos.func.dfg.append_inst_arg(inst, csr_ret);
+        }
+        if frame_size > 0 {
+            pos.ins().adjust_sp_imm(Imm64::new(frame_size));
+        }
+    }
+
+    Ok(())
 }
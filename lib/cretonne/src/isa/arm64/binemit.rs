@@ -1,7 +1,556 @@
 //! Emitting binary ARM64 machine code.
 
-use binemit::{CodeSink, bad_encoding};
-use ir::{Function, Inst};
+use binemit::{CodeOffset, CodeSink, Reloc, bad_encoding};
+use ir::condcodes::{IntCC, FloatCC};
+use ir::{Function, Inst, InstructionData, SourceLoc, TrapCode};
+use isa::{RegUnit, StackRef, StackBase, StackBaseMask};
+use predicates::{is_signed_int, is_unsigned_int};
 use regalloc::RegDiversions;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-arm64.rs"));
+
+/// The `udf #0` permanently undefined instruction used for traps.
+const UDF: u32 = 0;
+
+const NOP: u32 = 0xd503_201f;
+
+/// Emit `bytes` of padding as NOP instructions.
+pub fn emit_padding<CS: CodeSink + ?Sized>(bytes: CodeOffset, sink: &mut CS) {
+    debug_assert_eq!(bytes % 4, 0, "Misaligned ARM64 padding");
+    for _ in 0..bytes / 4 {
+        sink.put4(NOP);
+    }
+}
+
+/// Scratch register used by multi-instruction recipes. This is `ip0` in the AAPCS64 which is not
+/// allocatable.
+const SCRATCH: u32 = 16;
+
+// A64 condition codes.
+const COND_EQ: u32 = 0x0;
+const COND_NE: u32 = 0x1;
+const COND_HS: u32 = 0x2;
+const COND_LO: u32 = 0x3;
+const COND_MI: u32 = 0x4;
+const COND_VS: u32 = 0x6;
+const COND_VC: u32 = 0x7;
+const COND_HI: u32 = 0x8;
+const COND_LS: u32 = 0x9;
+const COND_GE: u32 = 0xa;
+const COND_LT: u32 = 0xb;
+const COND_GT: u32 = 0xc;
+const COND_LE: u32 = 0xd;
+
+/// Convert a stack base to the corresponding register.
+fn stk_base(base: StackBase) -> RegUnit {
+    match base {
+        StackBase::SP => 31,
+        StackBase::FP => 29,
+        StackBase::Zone => unimplemented!(),
+    }
+}
+
+/// Get the A64 condition that is true after `cmp x, y` when `x cond y`.
+fn icc2cond(cond: IntCC) -> u32 {
+    use ir::condcodes::IntCC::*;
+    match cond {
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        SignedLessThan => COND_LT,
+        SignedGreaterThanOrEqual => COND_GE,
+        SignedGreaterThan => COND_GT,
+        SignedLessThanOrEqual => COND_LE,
+        UnsignedLessThan => COND_LO,
+        UnsignedGreaterThanOrEqual => COND_HS,
+        UnsignedGreaterThan => COND_HI,
+        UnsignedLessThanOrEqual => COND_LS,
+    }
+}
+
+/// Get the A64 condition that is true after `fcmp x, y` when `x cond y`.
+///
+/// The `fcmp` instruction sets NZCV to 0110 for equal, 1000 for less than, 0010 for greater than,
+/// and 0011 for unordered operands. The `one` and `ueq` conditions can't be tested with a single
+/// A64 condition. They are handled by `put_fcmp2`.
+fn fcc2cond(cond: FloatCC) -> u32 {
+    use ir::condcodes::FloatCC::*;
+    match cond {
+        Ordered => COND_VC,
+        Unordered => COND_VS,
+        Equal => COND_EQ,
+        NotEqual => COND_NE,
+        LessThan => COND_MI,
+        LessThanOrEqual => COND_LS,
+        GreaterThan => COND_GT,
+        GreaterThanOrEqual => COND_GE,
+        UnorderedOrLessThan => COND_LT,
+        UnorderedOrLessThanOrEqual => COND_LE,
+        UnorderedOrGreaterThan => COND_HI,
+        UnorderedOrGreaterThanOrEqual => COND_HS,
+        OrderedNotEqual | UnorderedOrEqual => panic!("{} needs two A64 conditions", cond),
+    }
+}
+
+/// Data processing instructions with register operands.
+///
+///   31       20 15     9  4
+///   template rm tmpl rn rd
+///         21 16   10  5  0
+///
+/// Encoding bits: `template[31:21] | (template[14:10] << 11)`.
+fn put_rrr<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rm: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = (bits & 0x7ff) << 21;
+    i |= (bits >> 11) << 10;
+    i |= rm << 16;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Get the `sf` bit from `put_rrr` encoding bits.
+fn rrr_sf(bits: u16) -> u32 {
+    (u32::from(bits) >> 10) & 1
+}
+
+/// Trap if `rm` is zero: `cbnz rm, #8` + `udf`.
+fn put_trap_if_zero<CS: CodeSink + ?Sized>(
+    sf: u32,
+    rm: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    let rm = u32::from(rm) & 0x1f;
+    sink.put4((sf << 31) | 0x3500_0000 | (2 << 5) | rm);
+    sink.trap(TrapCode::IntegerDivisionByZero, srcloc);
+    sink.put4(UDF);
+}
+
+/// Rotate left: `neg x16, rm` + `rorv rd, rn, x16`.
+///
+/// Encoding bits are those of `rorv` for `put_rrr`.
+fn put_rotl<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let rm = u32::from(rm) & 0x1f;
+    sink.put4((rrr_sf(bits) << 31) | 0x4b00_03e0 | (rm << 16) | SCRATCH);
+    put_rrr(bits, rn, SCRATCH as RegUnit, rd, sink);
+}
+
+/// Unsigned division that traps on a zero divisor.
+///
+/// Encoding bits are those of `udiv` for `put_rrr`.
+fn put_udiv<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    put_trap_if_zero(rrr_sf(bits), rm, srcloc, sink);
+    put_rrr(bits, rn, rm, rd, sink);
+}
+
+/// Signed division that traps on a zero divisor and on `INT_MIN / -1`.
+///
+/// The overflow check compares `rm` with -1, and if equal compares `rn` with 1 which overflows
+/// only when `rn` is `INT_MIN`:
+///
+///   cmn rm, #1
+///   ccmp rn, #1, #0, eq
+///   b.vc #8
+///   udf
+///
+/// Encoding bits are those of `sdiv` for `put_rrr`.
+fn put_sdiv<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    let sf = rrr_sf(bits);
+    put_trap_if_zero(sf, rm, srcloc, sink);
+    let rn5 = u32::from(rn) & 0x1f;
+    let rm5 = u32::from(rm) & 0x1f;
+    sink.put4((sf << 31) | 0x3100_0000 | (1 << 10) | (rm5 << 5) | 31);
+    sink.put4((sf << 31) | 0x7a40_0800 | (1 << 16) | (COND_EQ << 12) | (rn5 << 5));
+    put_bcond(COND_VC, 8, sink);
+    sink.trap(TrapCode::IntegerOverflow, srcloc);
+    sink.put4(UDF);
+    put_rrr(bits, rn, rm, rd, sink);
+}
+
+/// Remainder that traps on a zero divisor: `{u,s}div x16, rn, rm` + `msub rd, x16, rm, rn`.
+///
+/// The signed remainder of `INT_MIN % -1` correctly computes as 0.
+///
+/// Encoding bits are those of the division for `put_rrr`.
+fn put_rem<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    rm: RegUnit,
+    rd: RegUnit,
+    srcloc: SourceLoc,
+    sink: &mut CS,
+) {
+    let sf = rrr_sf(bits);
+    put_trap_if_zero(sf, rm, srcloc, sink);
+    put_rrr(bits, rn, rm, SCRATCH as RegUnit, sink);
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+    sink.put4((sf << 31) | 0x1b00_8000 | (rm << 16) | (rn << 10) | (SCRATCH << 5) | rd);
+}
+
+/// Count trailing zeros: `rbit rd, rn` + `clz rd, rd`.
+///
+/// Encoding bits are those of `clz` for `put_rrr`. Clearing the opcode in `template[14:10]` gives
+/// `rbit`.
+fn put_ctz<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    put_rrr(bits & 0x7ff, rn, 0, rd, sink);
+    put_rrr(bits, rd, 0, rd, sink);
+}
+
+/// Conditional select increment: `csinc wd, wn, wm, cond`.
+fn put_csinc<CS: CodeSink + ?Sized>(
+    rn: RegUnit,
+    rm: RegUnit,
+    cond: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let rn = u32::from(rn) & 0x1f;
+    let rm = u32::from(rm) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+    sink.put4(0x1a80_0400 | (rm << 16) | (cond << 12) | (rn << 5) | rd);
+}
+
+/// Materialize a condition as 0 or 1: `cset wd, cond`.
+fn put_cset<CS: CodeSink + ?Sized>(cond: u32, rd: RegUnit, sink: &mut CS) {
+    put_csinc(31, 31, cond ^ 1, rd, sink);
+}
+
+/// Materialize the floating point conditions that need two A64 conditions.
+fn put_fcmp2<CS: CodeSink + ?Sized>(cond: FloatCC, rd: RegUnit, sink: &mut CS) {
+    match cond {
+        // Less than, or greater than.
+        FloatCC::OrderedNotEqual => {
+            put_cset(COND_MI, rd, sink);
+            put_csinc(rd, 31, COND_LE, rd, sink);
+        }
+        // Equal, or unordered.
+        FloatCC::UnorderedOrEqual => {
+            put_cset(COND_EQ, rd, sink);
+            put_csinc(rd, 31, COND_VC, rd, sink);
+        }
+        _ => panic!("{} doesn't need two A64 conditions", cond),
+    }
+}
+
+/// Add/subtract with a 12-bit immediate.
+///
+///   31       21    9  4
+///   template imm12 rn rd
+///         22    10  5  0
+///
+/// A negative immediate flips the operation between add and subtract.
+///
+/// Encoding bits: `template[31:22]`.
+fn put_ri<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let (op, imm) = if imm < 0 { (1 << 30, -imm) } else { (0, imm) };
+    debug_assert!(is_unsigned_int(imm, 12, 0), "imm12 out of range {:#x}", imm);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = (bits << 22) ^ op;
+    i |= (imm as u32) << 10;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Add/subtract with a 24-bit immediate as two instructions. The first adds the high 12 bits
+/// shifted by 12.
+///
+/// Encoding bits: `template[31:22]`.
+fn put_ri24<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, imm: i64, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let (op, imm) = if imm < 0 { (1 << 30, -imm) } else { (0, imm) };
+    debug_assert!(is_unsigned_int(imm, 24, 0), "imm24 out of range {:#x}", imm);
+    let imm = imm as u32;
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let i = (bits << 22) ^ op;
+    sink.put4(i | (1 << 22) | ((imm >> 12) << 10) | (rn << 5) | rd);
+    sink.put4(i | ((imm & 0xfff) << 10) | (rd << 5) | rd);
+}
+
+/// Move wide immediate.
+///
+///   31       22 20    4
+///   template hw imm16 rd
+///         23 21     5  0
+///
+/// Encoding bits: `template[31:23]`.
+fn put_movw<CS: CodeSink + ?Sized>(bits: u16, imm: i64, hw: u32, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = bits << 23;
+    i |= hw << 21;
+    i |= (imm as u32 & 0xffff) << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Materialize the low `chunks` 16-bit chunks of `imm` with `movz` followed by `movk`
+/// instructions.
+///
+/// Encoding bits are those of `movz` for `put_movw`. Setting bit 29 of the template gives `movk`.
+fn put_movimm<CS: CodeSink + ?Sized>(bits: u16, imm: i64, chunks: u32, rd: RegUnit, sink: &mut CS) {
+    put_movw(bits, imm, 0, rd, sink);
+    for hw in 1..chunks {
+        put_movw(bits | (1 << 6), imm >> (16 * hw), hw, rd, sink);
+    }
+}
+
+/// Bitfield move.
+///
+///   31       21   15   9  4
+///   template immr imms rn rd
+///         22   16   10  5  0
+///
+/// Encoding bits: `template[31:22] | (template[15:10] << 10)`. Only the low 10 bits are used
+/// here, the caller decides the `immr` and `imms` fields.
+fn put_bfm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    immr: u32,
+    imms: u32,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = (bits & 0x3ff) << 22;
+    i |= (immr & 0x3f) << 16;
+    i |= (imms & 0x3f) << 10;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Get the operand size in bits from `put_bfm` encoding bits.
+fn bfm_size(bits: u16) -> u32 {
+    if bits & 0x200 != 0 { 64 } else { 32 }
+}
+
+/// Left shift by an immediate: `lsl rd, rn, #s` is `ubfm rd, rn, #(-s % size), #(size - 1 - s)`.
+fn put_shl_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    imm: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let size = bfm_size(bits);
+    let s = imm as u32 & (size - 1);
+    put_bfm(bits, rn, (size - s) & (size - 1), size - 1 - s, rd, sink);
+}
+
+/// Right shift by an immediate: `lsr/asr rd, rn, #s` is `{u,s}bfm rd, rn, #s, #(size - 1)`.
+fn put_shr_imm<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    imm: i64,
+    rd: RegUnit,
+    sink: &mut CS,
+) {
+    let size = bfm_size(bits);
+    let s = imm as u32 & (size - 1);
+    put_bfm(bits, rn, s, size - 1, rd, sink);
+}
+
+/// Floating point data processing with one source register.
+///
+///   31       23   21 20     14    9  4
+///   00011110 type 1  opcode 10000 rn rd
+///         24   22 21     15    10  5  0
+///
+/// Encoding bits: `template[23:15]`.
+fn put_fp1<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = 0x1e00_4000;
+    i |= bits << 15;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Conversions between floating point and integer registers.
+///
+///   31 30      23   21 20    18     15     9  4
+///   sf 0011110 type 1  rmode opcode 000000 rn rd
+///   31      24   22 21    19     16     10  5  0
+///
+/// Encoding bits: `template[23:16] | (template[31] << 8)`.
+fn put_fpint<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+    let rd = u32::from(rd) & 0x1f;
+
+    let mut i = 0x1e00_0000;
+    i |= (bits & 0xff) << 16;
+    i |= (bits >> 8) << 31;
+    i |= rn << 5;
+    i |= rd;
+
+    sink.put4(i);
+}
+
+/// Loads and stores with an unsigned offset scaled by the access size.
+///
+///   31   29  26 25 23  21    9  4
+///   size 111 V  01 opc imm12 rn rt
+///     30  27 26 24  22    10  5  0
+///
+/// Encoding bits: `template[31:22]`.
+fn put_ldst<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i64,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    let scale = bits >> 8;
+    debug_assert!(
+        is_unsigned_int(offset, 12 + scale as u8, scale as u8),
+        "scaled offset out of range {:#x}",
+        offset
+    );
+    let rn = u32::from(rn) & 0x1f;
+    let rt = u32::from(rt) & 0x1f;
+
+    let mut i = bits << 22;
+    i |= ((offset >> scale) as u32 & 0xfff) << 10;
+    i |= rn << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Loads and stores with a signed unscaled 9-bit offset.
+///
+///   31   29  26 25 23  21 20   11 9  4
+///   size 111 V  00 opc 0  imm9 00 rn rt
+///     30  27 26 24  22 21   12 10  5  0
+///
+/// Encoding bits: `template[31:22]` of the scaled form for `put_ldst`.
+fn put_ldur<CS: CodeSink + ?Sized>(
+    bits: u16,
+    rn: RegUnit,
+    offset: i64,
+    rt: RegUnit,
+    sink: &mut CS,
+) {
+    let bits = u32::from(bits);
+    debug_assert!(is_signed_int(offset, 9, 0), "offset out of range {:#x}", offset);
+    let rn = u32::from(rn) & 0x1f;
+    let rt = u32::from(rt) & 0x1f;
+
+    let mut i = (bits << 22) & !(1 << 24);
+    i |= (offset as u32 & 0x1ff) << 12;
+    i |= rn << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Unconditional branches with a 26-bit displacement.
+///
+///   31     25
+///   opcode imm26
+///       26     0
+///
+/// Encoding bits: `template[31:24]`.
+fn put_b<CS: CodeSink + ?Sized>(bits: u16, disp: i64, sink: &mut CS) {
+    let bits = u32::from(bits);
+    debug_assert!(is_signed_int(disp, 28, 2), "B out of range {:#x}", disp);
+
+    let mut i = bits << 24;
+    i |= (disp >> 2) as u32 & 0x3ff_ffff;
+
+    sink.put4(i);
+}
+
+/// Compare and branch on zero with a 19-bit displacement.
+///
+///   31       23    4
+///   template imm19 rt
+///         24     5  0
+///
+/// Encoding bits: `template[31:24]`.
+fn put_cb<CS: CodeSink + ?Sized>(bits: u16, disp: i64, rt: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    debug_assert!(is_signed_int(disp, 21, 2), "CB out of range {:#x}", disp);
+    let rt = u32::from(rt) & 0x1f;
+
+    let mut i = bits << 24;
+    i |= ((disp >> 2) as u32 & 0x7_ffff) << 5;
+    i |= rt;
+
+    sink.put4(i);
+}
+
+/// Conditional branch: `b.cond`.
+fn put_bcond<CS: CodeSink + ?Sized>(cond: u32, disp: i64, sink: &mut CS) {
+    debug_assert!(is_signed_int(disp, 21, 2), "B.cond out of range {:#x}", disp);
+
+    let mut i = 0x5400_0000;
+    i |= ((disp >> 2) as u32 & 0x7_ffff) << 5;
+    i |= cond;
+
+    sink.put4(i);
+}
+
+/// Unconditional branches to a register.
+///
+///   31      24  20    15     9  4
+///   1101011 opc 11111 000000 rn 00000
+///        25  21    16     10  5     0
+///
+/// Encoding bits: `template[24:21]`.
+fn put_br<CS: CodeSink + ?Sized>(bits: u16, rn: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let rn = u32::from(rn) & 0x1f;
+
+    let mut i = 0xd61f_0000;
+    i |= bits << 21;
+    i |= rn << 5;
+
+    sink.put4(i);
+}
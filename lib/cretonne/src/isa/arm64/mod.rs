@@ -6,26 +6,34 @@ mod binemit;
 mod enc_tables;
 mod registers;
 
-use binemit::{CodeSink, MemoryCodeSink, emit_function};
+use binemit::{CodeOffset, CodeSink, MemoryCodeSink, emit_function};
 use super::super::settings as shared_settings;
 use isa::enc_tables::{lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
-use isa::{TargetIsa, RegInfo, RegClass, EncInfo};
+use isa::{TargetIsa, RegInfo, RegClass, RegUnit, EncInfo, FrameConfig};
 use ir;
 use regalloc;
+use result;
+use timing;
 use std::fmt;
 use std::boxed::Box;
+use std::hash::{Hash, Hasher};
+use std::vec::Vec;
 
 #[allow(dead_code)]
 struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
 }
 
 /// Get an ISA builder for creating ARM64 targets.
 pub fn isa_builder() -> IsaBuilder {
     IsaBuilder {
         setup: settings::builder(),
+        frame: FrameConfig::default(),
+        libcall_names: ir::LibCallNames::new(),
         constructor: isa_constructor,
     }
 }
@@ -33,10 +41,14 @@ pub fn isa_builder() -> IsaBuilder {
 fn isa_constructor(
     shared_flags: shared_settings::Flags,
     builder: &shared_settings::Builder,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
 ) -> Box<TargetIsa> {
     Box::new(Isa {
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        frame,
+        libcall_names,
     })
 }
 
@@ -49,6 +61,24 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn hash_flags(&self, mut state: &mut Hasher) {
+        self.name().hash(&mut state);
+        self.shared_flags.hash(&mut state);
+        self.isa_flags.hash(&mut state);
+    }
+
+    fn isa_settings(&self) -> Vec<shared_settings::Setting> {
+        self.isa_flags.settings()
+    }
+
+    fn frame_config(&self) -> &FrameConfig {
+        &self.frame
+    }
+
+    fn libcall_names(&self) -> &ir::LibCallNames {
+        &self.libcall_names
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -59,14 +89,14 @@ impl TargetIsa for Isa {
 
     fn legal_encodings<'a>(
         &'a self,
-        dfg: &'a ir::DataFlowGraph,
+        func: &'a ir::Function,
         inst: &'a ir::InstructionData,
         ctrl_typevar: ir::Type,
     ) -> Encodings<'a> {
         lookup_enclist(
             ctrl_typevar,
             inst,
-            dfg,
+            func,
             &enc_tables::LEVEL1_A64[..],
             &enc_tables::LEVEL2[..],
             &enc_tables::ENCLISTS[..],
@@ -86,7 +116,16 @@ impl TargetIsa for Isa {
     }
 
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::AllocatableSet {
-        abi::allocatable_registers(func)
+        abi::allocatable_registers(func, self)
+    }
+
+    fn callee_saved_registers(&self, _call_conv: ir::CallConv) -> Vec<RegUnit> {
+        self.frame.callee_saved_or(abi::callee_saved_registers())
+    }
+
+    fn prologue_epilogue(&self, func: &mut ir::Function) -> result::CtonResult {
+        let _tt = timing::prologue_epilogue();
+        abi::prologue_epilogue(func, self)
     }
 
     fn emit_inst(
@@ -99,8 +138,12 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    fn emit_padding(&self, bytes: CodeOffset, sink: &mut CodeSink) {
+        binemit::emit_padding(bytes, sink)
+    }
+
     fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
     }
 }
 
@@ -150,6 +150,26 @@ impl RecipeConstraints {
 
         true
     }
+
+    /// Check that any operands satisfying `other` also satisfy these constraints.
+    ///
+    /// This is the case when every operand constraint has the same kind as in `other`, or it is
+    /// a register constraint replacing a tied one, and its register class contains the one in
+    /// `other`.
+    pub fn subsumes(&self, other: &RecipeConstraints) -> bool {
+        fn covers(a: &OperandConstraint, b: &OperandConstraint) -> bool {
+            let kind_ok = match (a.kind, b.kind) {
+                (ConstraintKind::Reg, ConstraintKind::Tied(_)) => true,
+                (ak, bk) => ak == bk,
+            };
+            kind_ok && a.regclass.has_subclass(b.regclass)
+        }
+
+        self.ins.len() == other.ins.len() && self.outs.len() == other.outs.len() &&
+            self.ins.iter().zip(other.ins).all(|(a, b)| covers(a, b)) &&
+            self.outs.iter().zip(other.outs).all(|(a, b)| covers(a, b)) &&
+            (!self.clobbers_flags || other.clobbers_flags)
+    }
 }
 
 /// Constraints on the range of a branch instruction.
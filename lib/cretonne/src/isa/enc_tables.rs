@@ -4,10 +4,11 @@
 //! `lib/cretonne/meta/gen_encoding.py`.
 
 use constant_hash::{Table, probe};
-use ir::{Type, Opcode, DataFlowGraph, InstructionData};
-use isa::{Encoding, Legalize};
+use ir::{Type, Opcode, Function, InstructionData};
+use isa::{Encoding, EncodingCandidate, EncodingFailure, Legalize};
 use settings::PredicateView;
 use std::ops::Range;
+use std::vec::Vec;
 
 /// A recipe predicate.
 ///
@@ -20,7 +21,7 @@ pub type RecipePredicate = Option<fn(PredicateView, &InstructionData) -> bool>;
 ///
 /// This is a predicate function that needs to be tested in addition to the recipe predicate. It
 /// can't depend on ISA settings.
-pub type InstPredicate = fn(&DataFlowGraph, &InstructionData) -> bool;
+pub type InstPredicate = fn(&Function, &InstructionData) -> bool;
 
 /// Legalization action to perform when no encoding can be found for an instruction.
 ///
@@ -106,7 +107,7 @@ impl<OffT: Into<u32> + Copy> Table<Opcode> for [Level2Entry<OffT>] {
 pub fn lookup_enclist<'a, OffT1, OffT2>(
     ctrl_typevar: Type,
     inst: &'a InstructionData,
-    dfg: &'a DataFlowGraph,
+    func: &'a Function,
     level1_table: &'static [Level1Entry<OffT1>],
     level2_table: &'static [Level2Entry<OffT2>],
     enclist: &'static [EncListEntry],
@@ -150,7 +151,7 @@ where
         offset,
         legalize,
         inst,
-        dfg,
+        func,
         enclist,
         legalize_actions,
         recipe_preds,
@@ -177,7 +178,7 @@ pub struct Encodings<'a> {
     // Legalization code to use of no encoding is found.
     legalize: LegalizeCode,
     inst: &'a InstructionData,
-    dfg: &'a DataFlowGraph,
+    func: &'a Function,
     enclist: &'static [EncListEntry],
     legalize_actions: &'static [Legalize],
     recipe_preds: &'static [RecipePredicate],
@@ -195,7 +196,7 @@ impl<'a> Encodings<'a> {
         offset: usize,
         legalize: LegalizeCode,
         inst: &'a InstructionData,
-        dfg: &'a DataFlowGraph,
+        func: &'a Function,
         enclist: &'static [EncListEntry],
         legalize_actions: &'static [Legalize],
         recipe_preds: &'static [RecipePredicate],
@@ -205,7 +206,7 @@ impl<'a> Encodings<'a> {
         Encodings {
             offset,
             inst,
-            dfg,
+            func,
             legalize,
             isa_preds,
             recipe_preds,
@@ -225,6 +226,71 @@ impl<'a> Encodings<'a> {
         self.legalize_actions[self.legalize as usize]
     }
 
+    /// Get all the encodings in the encoding list along with the reason each one is rejected.
+    ///
+    /// Unlike the iterator, this doesn't stop at the first failed predicate, so it can be used to
+    /// explain why an instruction has no legal encodings. It must be called on a fresh iterator.
+    pub fn candidates(&self) -> Vec<EncodingCandidate> {
+        let mut candidates = Vec::new();
+        // Failures of the predicates guarding the current entry, along with the offset where each
+        // predicate stops applying.
+        let mut guards: Vec<(usize, Option<EncodingFailure>)> = Vec::new();
+        let mut offset = self.offset;
+
+        while let Some(entryref) = self.enclist.get(offset) {
+            let entry = *entryref as usize;
+            guards.retain(|&(end, _)| end > offset);
+
+            // Check for "recipe+bits".
+            let recipe = entry >> 1;
+            if let Some(&rpred) = self.recipe_preds.get(recipe) {
+                let failure = guards.iter().filter_map(|&(_, f)| f).next().or_else(|| {
+                    if self.check_recipe(rpred) {
+                        None
+                    } else {
+                        Some(EncodingFailure::RecipePredicate)
+                    }
+                });
+                candidates.push(EncodingCandidate {
+                    encoding: Encoding::new(recipe as u16, self.enclist[offset + 1]),
+                    failure,
+                });
+                if entry & 1 != 0 {
+                    break;
+                }
+                offset += 2;
+                continue;
+            }
+
+            // Check for "stop with legalize".
+            if entry < PRED_START {
+                break;
+            }
+
+            // This must be a predicate entry guarding the next `skip` entries, or the rest of the
+            // list when `skip` is 0.
+            let pred_entry = entry - PRED_START;
+            let skip = pred_entry >> PRED_BITS;
+            let pred = pred_entry & PRED_MASK;
+            let end = if skip == 0 {
+                self.enclist.len()
+            } else {
+                offset + 1 + skip
+            };
+            let failure = if self.check_pred(pred) {
+                None
+            } else if pred < self.inst_preds.len() {
+                Some(EncodingFailure::InstPredicate(pred))
+            } else {
+                Some(EncodingFailure::IsaPredicate(pred - self.inst_preds.len()))
+            };
+            guards.push((end, failure));
+            offset += 1;
+        }
+
+        candidates
+    }
+
     /// Check if the `rpred` recipe predicate is satisfied.
     fn check_recipe(&self, rpred: RecipePredicate) -> bool {
         match rpred {
@@ -236,7 +302,7 @@ impl<'a> Encodings<'a> {
     /// Check an instruction or isa predicate.
     fn check_pred(&self, pred: usize) -> bool {
         if let Some(&p) = self.inst_preds.get(pred) {
-            p(self.dfg, self.inst)
+            p(self.func, self.inst)
         } else {
             let pred = pred - self.inst_preds.len();
             self.isa_preds.test(pred)
@@ -1,8 +1,10 @@
 //! The `Encoding` struct.
 
 use binemit::CodeOffset;
+use ir::{Opcode, Type, types};
 use isa::constraints::{RecipeConstraints, BranchRange};
 use std::fmt;
+use std::vec::Vec;
 
 /// Bits needed to encode an instruction as binary machine code.
 ///
@@ -60,18 +62,13 @@ impl fmt::Display for Encoding {
 /// This is meant to be created by `EncInfo::display()`.
 pub struct DisplayEncoding {
     pub encoding: Encoding,
-    pub recipe_names: &'static [&'static str],
+    pub recipe_name: &'static str,
 }
 
 impl fmt::Display for DisplayEncoding {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         if self.encoding.is_legal() {
-            write!(
-                f,
-                "{}#{:02x}",
-                self.recipe_names[self.encoding.recipe()],
-                self.encoding.bits
-            )
+            write!(f, "{}#{:02x}", self.recipe_name, self.encoding.bits)
         } else {
             write!(f, "-")
         }
@@ -91,39 +88,126 @@ pub struct RecipeSizing {
     pub branch_range: Option<BranchRange>,
 }
 
+/// A range of bytes in `EncInfo::text` holding a name.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TextRange {
+    /// Offset of the first byte of the name.
+    pub start: u16,
+
+    /// Offset one past the last byte of the name.
+    pub end: u16,
+}
+
+/// Packed information about an encoding recipe.
+///
+/// Many recipes have the same operand constraints and code size, so the `EncInfo` tables only
+/// hold unique entries which are referenced by index from here.
+#[derive(Clone, Copy, Debug)]
+pub struct RecipeInfo {
+    /// Index into `EncInfo::constraints`.
+    pub constraints: u16,
+
+    /// Index into `EncInfo::sizing`.
+    pub sizing: u16,
+
+    /// Name of the recipe.
+    pub name: TextRange,
+
+    /// Name of the recipe predicate, or an empty range if the recipe has no predicate.
+    pub predicate_name: TextRange,
+}
+
 /// Information about all the encodings in this ISA.
+///
+/// The tables are generated by `meta/gen_encoding.py`. Use the accessor methods rather than
+/// indexing the tables directly.
 #[derive(Clone)]
 pub struct EncInfo {
-    /// Constraints on value operands per recipe.
+    /// Packed information per recipe.
+    pub recipes: &'static [RecipeInfo],
+
+    /// Unique value operand constraints referenced by `recipes`.
     pub constraints: &'static [RecipeConstraints],
 
-    /// Code size information per recipe.
+    /// Unique code size information referenced by `recipes`.
     pub sizing: &'static [RecipeSizing],
 
-    /// Names of encoding recipes.
-    pub names: &'static [&'static str],
+    /// All the recipe and predicate names, concatenated.
+    pub text: &'static str,
+
+    /// Names of instruction predicates.
+    pub inst_predicate_names: &'static [TextRange],
+
+    /// Names of ISA predicates.
+    pub isa_predicate_names: &'static [TextRange],
 }
 
 impl EncInfo {
+    /// Get the name in `range`.
+    fn name(&self, range: TextRange) -> &'static str {
+        &self.text[range.start as usize..range.end as usize]
+    }
+
     /// Get the value operand constraints for `enc` if it is a legal encoding.
     pub fn operand_constraints(&self, enc: Encoding) -> Option<&'static RecipeConstraints> {
-        self.constraints.get(enc.recipe())
+        self.recipes.get(enc.recipe()).map(|r| {
+            &self.constraints[r.constraints as usize]
+        })
+    }
+
+    /// Get the value operand constraints for `recipe`.
+    pub fn recipe_constraints(&self, recipe: usize) -> &'static RecipeConstraints {
+        &self.constraints[self.recipes[recipe].constraints as usize]
+    }
+
+    /// Get the name of `recipe`.
+    pub fn recipe_name(&self, recipe: usize) -> &'static str {
+        self.name(self.recipes[recipe].name)
+    }
+
+    /// Find the number of the recipe named `name`.
+    pub fn find_recipe(&self, name: &str) -> Option<usize> {
+        self.recipes.iter().position(|r| self.name(r.name) == name)
+    }
+
+    /// Get the name of the predicate of `recipe`, or the empty string.
+    pub fn recipe_predicate_name(&self, recipe: usize) -> &'static str {
+        self.name(self.recipes[recipe].predicate_name)
+    }
+
+    /// Get the name of instruction predicate number `n`.
+    pub fn inst_predicate_name(&self, n: usize) -> &'static str {
+        self.name(self.inst_predicate_names[n])
+    }
+
+    /// Get the name of ISA predicate number `n`.
+    pub fn isa_predicate_name(&self, n: usize) -> &'static str {
+        self.name(self.isa_predicate_names[n])
     }
 
     /// Create an object that can display an ISA-dependent encoding properly.
     pub fn display(&self, enc: Encoding) -> DisplayEncoding {
         DisplayEncoding {
             encoding: enc,
-            recipe_names: self.names,
+            recipe_name: self.recipes.get(enc.recipe()).map_or(
+                "",
+                |r| self.name(r.name),
+            ),
         }
     }
 
+    /// Get the code size information for `enc` if it is a legal encoding.
+    fn recipe_sizing(&self, enc: Encoding) -> Option<&'static RecipeSizing> {
+        self.recipes.get(enc.recipe()).map(
+            |r| &self.sizing[r.sizing as usize],
+        )
+    }
+
     /// Get the exact size in bytes of instructions encoded with `enc`.
     ///
     /// Returns 0 for illegal encodings.
     pub fn bytes(&self, enc: Encoding) -> CodeOffset {
-        self.sizing
-            .get(enc.recipe())
+        self.recipe_sizing(enc)
             .map(|s| CodeOffset::from(s.bytes))
             .unwrap_or(0)
     }
@@ -132,6 +216,110 @@ impl EncInfo {
     ///
     /// This will never return `None` for a legal branch encoding.
     pub fn branch_range(&self, enc: Encoding) -> Option<BranchRange> {
-        self.sizing.get(enc.recipe()).and_then(|s| s.branch_range)
+        self.recipe_sizing(enc).and_then(|s| s.branch_range)
+    }
+}
+
+/// The reason an encoding was rejected for an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EncodingFailure {
+    /// The instruction predicate with this number is not satisfied.
+    InstPredicate(usize),
+
+    /// The ISA predicate with this number is not satisfied by the ISA settings.
+    IsaPredicate(usize),
+
+    /// The predicate of the encoding recipe is not satisfied by the ISA settings or the
+    /// instruction's immediate operands.
+    RecipePredicate,
+}
+
+/// An encoding from the encoding tables that was considered for an instruction.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EncodingCandidate {
+    /// The candidate encoding.
+    pub encoding: Encoding,
+
+    /// Why the encoding can't be used, or `None` if it is a legal encoding.
+    pub failure: Option<EncodingFailure>,
+}
+
+/// An explanation of how an instruction was matched against the encoding tables.
+///
+/// This is produced by `TargetIsa::explain_encoding()`, typically after the legalizer failed to
+/// find an encoding for an instruction. The `Display` implementation lists the candidate encodings
+/// and the predicate that rejected each of them.
+pub struct EncodingDiagnosis {
+    /// The opcode of the instruction.
+    pub opcode: Opcode,
+
+    /// The controlling type variable of the instruction, or `VOID`.
+    pub ctrl_typevar: Type,
+
+    /// The encodings considered for the instruction, in table order.
+    ///
+    /// This is empty when the encoding tables have no entries for the opcode and controlling type
+    /// in the current CPU mode.
+    pub candidates: Vec<EncodingCandidate>,
+
+    encinfo: EncInfo,
+}
+
+impl EncodingDiagnosis {
+    /// Create a new diagnosis from the `candidates` found in the tables described by `encinfo`.
+    pub fn new(
+        opcode: Opcode,
+        ctrl_typevar: Type,
+        candidates: Vec<EncodingCandidate>,
+        encinfo: EncInfo,
+    ) -> Self {
+        Self {
+            opcode,
+            ctrl_typevar,
+            candidates,
+            encinfo,
+        }
+    }
+}
+
+impl fmt::Display for EncodingDiagnosis {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.opcode)?;
+        if self.ctrl_typevar != types::VOID {
+            write!(f, ".{}", self.ctrl_typevar)?;
+        }
+        if self.candidates.is_empty() {
+            return write!(f, " has no encodings in this CPU mode");
+        }
+        write!(f, " candidates:")?;
+        for (i, cand) in self.candidates.iter().enumerate() {
+            let sep = if i == 0 { "" } else { "," };
+            write!(f, "{} {}", sep, self.encinfo.display(cand.encoding))?;
+            match cand.failure {
+                None => write!(f, " is legal")?,
+                Some(EncodingFailure::InstPredicate(n)) => {
+                    write!(
+                        f,
+                        " fails instruction predicate {}",
+                        self.encinfo.inst_predicate_name(n)
+                    )?
+                }
+                Some(EncodingFailure::IsaPredicate(n)) => {
+                    write!(
+                        f,
+                        " fails ISA predicate {}",
+                        self.encinfo.isa_predicate_name(n)
+                    )?
+                }
+                Some(EncodingFailure::RecipePredicate) => {
+                    write!(
+                        f,
+                        " fails recipe predicate {}",
+                        self.encinfo.recipe_predicate_name(cand.encoding.recipe())
+                    )?
+                }
+            }
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,91 @@
+//! Embedder control over stack frames.
+//!
+//! By default, the callee-saved registers and the prologue and epilogue code of a compiled
+//! function are determined by its calling convention. Runtimes that maintain their own frame
+//! descriptors can override both with a `FrameConfig` passed to `isa::Builder::set_frame_config`.
+
+use isa::RegUnit;
+use std::vec::Vec;
+
+/// Embedder overrides for the stack frames generated by a `TargetIsa`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FrameConfig {
+    /// Registers that compiled functions must preserve.
+    ///
+    /// When set, this replaces the callee-saved registers of all calling conventions.
+    pub callee_saved: Option<Vec<RegUnit>>,
+
+    /// Let the embedder generate the prologue and epilogue code.
+    ///
+    /// When set, no prologue or epilogue code is inserted, and this many bytes between the return
+    /// address and the local stack frame are reserved for the embedder's own frame data. The
+    /// callee-saved registers are not available to the register allocator, so the embedder
+    /// doesn't need to save them.
+    pub external_prologue_bytes: Option<u32>,
+}
+
+impl FrameConfig {
+    /// Get the callee-saved registers, using `default` unless they have been overridden.
+    pub fn callee_saved_or<I>(&self, default: I) -> Vec<RegUnit>
+    where
+        I: IntoIterator<Item = RegUnit>,
+    {
+        match self.callee_saved {
+            Some(ref regs) => regs.clone(),
+            None => default.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(all(test, build_intel))]
+mod tests {
+    use super::FrameConfig;
+    use ir::{types, CallConv, Function};
+    use isa;
+    use settings::{self, Configurable};
+    use std::vec::Vec;
+
+    fn intel(frame: FrameConfig) -> ::std::boxed::Box<isa::TargetIsa> {
+        let mut shared_builder = settings::builder();
+        shared_builder.enable("is_64bit").unwrap();
+        let shared_flags = settings::Flags::new(&shared_builder);
+        let mut builder = isa::lookup("intel").unwrap();
+        builder.set_frame_config(frame);
+        builder.finish(shared_flags)
+    }
+
+    #[test]
+    fn custom_callee_saved() {
+        let isa = intel(FrameConfig::default());
+        let reginfo = isa.register_info();
+        let rbx = reginfo.parse_regunit("rbx").unwrap();
+        let r12 = reginfo.parse_regunit("r12").unwrap();
+        assert!(isa.callee_saved_registers(CallConv::Native).contains(&r12));
+
+        let isa = intel(FrameConfig {
+            callee_saved: Some(vec![rbx]),
+            external_prologue_bytes: None,
+        });
+        assert_eq!(isa.callee_saved_registers(CallConv::Native), vec![rbx]);
+        assert_eq!(
+            isa.callee_saved_registers(CallConv::WindowsFastcall),
+            vec![rbx]
+        );
+    }
+
+    #[test]
+    fn external_prologue() {
+        let isa = intel(FrameConfig {
+            callee_saved: None,
+            external_prologue_bytes: Some(32),
+        });
+        let gpr = isa.regclass_for_abi_type(types::I64);
+        let func = Function::new();
+        let regs = isa.allocatable_registers(&func);
+        let csrs: Vec<_> = isa.callee_saved_registers(CallConv::Native);
+        assert!(!csrs.is_empty());
+        for reg in csrs {
+            assert!(!regs.is_avail(gpr, reg));
+        }
+    }
+}
@@ -18,9 +18,16 @@ use result;
 /// Argument registers for x86-64
 static ARG_GPRS: [RU; 6] = [RU::rdi, RU::rsi, RU::rdx, RU::rcx, RU::r8, RU::r9];
 
+/// Argument registers for the Windows x64 calling convention.
+static WIN_ARG_GPRS: [RU; 4] = [RU::rcx, RU::rdx, RU::r8, RU::r9];
+
 /// Return value registers.
 static RET_GPRS: [RU; 3] = [RU::rax, RU::rdx, RU::rcx];
 
+/// Size of the shadow space that a Windows x64 caller reserves above the return address for the
+/// callee to spill its register arguments.
+const WIN_SHADOW_SPACE: u32 = 32;
+
 struct Args {
     pointer_bytes: u32,
     pointer_bits: u16,
@@ -90,10 +97,17 @@ impl ArgAssigner for Args {
             }
         }
 
+        // Windows x64 assigns registers by argument position, so an argument consumes a slot in
+        // both register banks.
+        let shared_slots = self.call_conv == CallConv::WindowsFastcall;
+
         // Try to use a GPR.
         if !ty.is_float() && self.gpr_used < self.gpr.len() {
             let reg = self.gpr[self.gpr_used] as RegUnit;
             self.gpr_used += 1;
+            if shared_slots {
+                self.fpr_used = self.gpr_used;
+            }
             return ArgumentLoc::Reg(reg).into();
         }
 
@@ -101,6 +115,9 @@ impl ArgAssigner for Args {
         if ty.is_float() && self.fpr_used < self.fpr_limit {
             let reg = FPR.unit(self.fpr_used);
             self.fpr_used += 1;
+            if shared_slots {
+                self.gpr_used = self.fpr_used;
+            }
             return ArgumentLoc::Reg(reg).into();
         }
 
@@ -117,7 +134,12 @@ pub fn legalize_signature(sig: &mut ir::Signature, flags: &shared_settings::Flag
     let bits;
     let mut args;
 
-    if flags.is_64bit() {
+    if flags.is_64bit() && sig.call_conv == CallConv::WindowsFastcall {
+        bits = 64;
+        args = Args::new(bits, &WIN_ARG_GPRS, 4, sig.call_conv);
+        // Stack arguments are passed above the shadow space.
+        args.offset = WIN_SHADOW_SPACE;
+    } else if flags.is_64bit() {
         bits = 64;
         args = Args::new(bits, &ARG_GPRS, 8, sig.call_conv);
     } else {
@@ -141,37 +163,59 @@ pub fn regclass_for_abi_type(ty: ir::Type) -> RegClass {
 }
 
 /// Get the set of allocatable registers for `func`.
-pub fn allocatable_registers(
-    _func: &ir::Function,
-    flags: &shared_settings::Flags,
-) -> AllocatableSet {
+pub fn allocatable_registers(func: &ir::Function, isa: &TargetIsa) -> AllocatableSet {
     let mut regs = AllocatableSet::new();
     regs.take(GPR, RU::rsp as RegUnit);
     regs.take(GPR, RU::rbp as RegUnit);
 
     // 32-bit arch only has 8 registers.
-    if !flags.is_64bit() {
+    if !isa.flags().is_64bit() {
         for i in 8..16 {
             regs.take(GPR, GPR.unit(i));
             regs.take(FPR, FPR.unit(i));
         }
+    } else if func.signature.call_conv == CallConv::WindowsFastcall {
+        // %xmm6-%xmm15 are callee-saved on Windows, but the prologue only saves integer
+        // registers, so keep them out of the register allocator's reach.
+        for i in 6..16 {
+            regs.take(FPR, FPR.unit(i));
+        }
+    }
+
+    // Without a prologue, nothing saves the callee-saved registers.
+    if isa.frame_config().external_prologue_bytes.is_some() {
+        for reg in isa.callee_saved_registers(func.signature.call_conv) {
+            let rc = if GPR.contains(reg) { GPR } else { FPR };
+            if regs.is_avail(rc, reg) {
+                regs.take(rc, reg);
+            }
+        }
     }
 
     regs
 }
 
 /// Get the set of callee-saved registers.
-pub fn callee_saved_registers(flags: &shared_settings::Flags) -> &'static [RU] {
-    if flags.is_64bit() {
-        &[RU::rbx, RU::r12, RU::r13, RU::r14, RU::r15]
-    } else {
+pub fn callee_saved_registers(
+    flags: &shared_settings::Flags,
+    call_conv: CallConv,
+) -> &'static [RU] {
+    if !flags.is_64bit() {
         &[RU::rbx, RU::rsi, RU::rdi]
+    } else if call_conv == CallConv::WindowsFastcall {
+        &[RU::rbx, RU::rsi, RU::rdi, RU::r12, RU::r13, RU::r14, RU::r15]
+    } else {
+        &[RU::rbx, RU::r12, RU::r13, RU::r14, RU::r15]
     }
 }
 
 pub fn prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> result::CtonResult {
+    if let Some(bytes) = isa.frame_config().external_prologue_bytes {
+        return external_prologue_epilogue(func, bytes);
+    }
     match func.signature.call_conv {
-        ir::CallConv::Native => native_prologue_epilogue(func, isa),
+        ir::CallConv::Native |
+        ir::CallConv::WindowsFastcall => native_prologue_epilogue(func, isa),
         ir::CallConv::SpiderWASM => spiderwasm_prologue_epilogue(func, isa),
     }
 }
@@ -193,7 +237,18 @@ pub fn spiderwasm_prologue_epilogue(
     Ok(())
 }
 
-/// Insert a System V-compatible prologue and epilogue.
+/// Lay out the stack frame for a function whose prologue and epilogue are generated by the
+/// embedder.
+fn external_prologue_epilogue(func: &mut ir::Function, bytes: StackSize) -> result::CtonResult {
+    let mut ss = ir::StackSlotData::new(ir::StackSlotKind::IncomingArg, bytes);
+    ss.offset = Some(-(bytes as StackOffset));
+    func.stack_slots.push(ss);
+
+    layout_stack(&mut func.stack_slots, 16)?;
+    Ok(())
+}
+
+/// Insert a System V or Windows x64-compatible prologue and epilogue.
 pub fn native_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> result::CtonResult {
     // The original 32-bit x86 ELF ABI had a 4-byte aligned stack pointer, but
     // newer versions use a 16-byte aligned stack pointer.
@@ -204,7 +259,12 @@ pub fn native_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> res
     } else {
         ir::types::I32
     };
-    let csrs = callee_saved_registers(isa.flags());
+    let csrs = isa.callee_saved_registers(func.signature.call_conv);
+
+    // Callee-saved registers are pushed, so they must be integer registers.
+    if csrs.iter().any(|&reg| !GPR.contains(reg)) {
+        return Err(result::CtonError::InvalidInput);
+    }
 
     // The reserved stack area is composed of:
     //   return address + frame pointer + all callee-saved registers
@@ -220,6 +280,20 @@ pub fn native_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> res
         offset: Some(-csr_stack_size),
     });
 
+    // Calls to Windows x64 functions need room for the callee's shadow space at the bottom of
+    // the outgoing argument area.
+    if isa.flags().is_64bit() &&
+        func.dfg.signatures.keys().any(|sig| {
+            func.dfg.signatures[sig].call_conv == CallConv::WindowsFastcall
+        })
+    {
+        func.stack_slots.push(ir::StackSlotData {
+            kind: ir::StackSlotKind::OutgoingArg,
+            size: WIN_SHADOW_SPACE,
+            offset: Some(0),
+        });
+    }
+
     let total_stack_size = layout_stack(&mut func.stack_slots, stack_align)? as i32;
     let local_stack_size = i64::from(total_stack_size - csr_stack_size);
 
@@ -232,9 +306,8 @@ pub fn native_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> res
     func.signature.params.push(fp_arg);
     func.signature.returns.push(fp_arg);
 
-    for csr in csrs.iter() {
-        let csr_arg =
-            ir::AbiParam::special_reg(csr_type, ir::ArgumentPurpose::CalleeSaved, *csr as RegUnit);
+    for &csr in &csrs {
+        let csr_arg = ir::AbiParam::special_reg(csr_type, ir::ArgumentPurpose::CalleeSaved, csr);
         func.signature.params.push(csr_arg);
         func.signature.returns.push(csr_arg);
     }
@@ -242,11 +315,11 @@ pub fn native_prologue_epilogue(func: &mut ir::Function, isa: &TargetIsa) -> res
     // Set up the cursor and insert the prologue
     let entry_ebb = func.layout.entry_block().expect("missing entry block");
     let mut pos = EncCursor::new(func, isa).at_first_insertion_point(entry_ebb);
-    insert_native_prologue(&mut pos, local_stack_size, csr_type, csrs);
+    insert_native_prologue(&mut pos, local_stack_size, csr_type, &csrs);
 
     // Reset the cursor and insert the epilogue
     let mut pos = pos.at_position(CursorPosition::Nowhere);
-    insert_native_epilogues(&mut pos, local_stack_size, csr_type, csrs);
+    insert_native_epilogues(&mut pos, local_stack_size, csr_type, &csrs);
 
     Ok(())
 }
@@ -256,7 +329,7 @@ fn insert_native_prologue(
     pos: &mut EncCursor,
     stack_size: i64,
     csr_type: ir::types::Type,
-    csrs: &'static [RU],
+    csrs: &[RegUnit],
 ) {
     // Append param to entry EBB
     let ebb = pos.current_ebb().expect("missing ebb under cursor");
@@ -269,12 +342,12 @@ fn insert_native_prologue(
         RU::rbp as RegUnit,
     );
 
-    for reg in csrs.iter() {
+    for &reg in csrs {
         // Append param to entry EBB
         let csr_arg = pos.func.dfg.append_ebb_param(ebb, csr_type);
 
         // Assign it a location
-        pos.func.locations[csr_arg] = ir::ValueLoc::Reg(*reg as RegUnit);
+        pos.func.locations[csr_arg] = ir::ValueLoc::Reg(reg);
 
         // Remember it so we can push it momentarily
         pos.ins().x86_push(csr_arg);
@@ -290,7 +363,7 @@ fn insert_native_epilogues(
     pos: &mut EncCursor,
     stack_size: i64,
     csr_type: ir::types::Type,
-    csrs: &'static [RU],
+    csrs: &[RegUnit],
 ) {
     while let Some(ebb) = pos.next_ebb() {
         pos.goto_last_inst(ebb);
@@ -308,7 +381,7 @@ fn insert_native_epilogue(
     stack_size: i64,
     pos: &mut EncCursor,
     csr_type: ir::types::Type,
-    csrs: &'static [RU],
+    csrs: &[RegUnit],
 ) {
     if stack_size > 0 {
         pos.ins().adjust_sp_imm(Imm64::new(stack_size));
@@ -322,11 +395,11 @@ fn insert_native_epilogue(
     pos.func.locations[fp_ret] = ir::ValueLoc::Reg(RU::rbp as RegUnit);
     pos.func.dfg.append_inst_arg(inst, fp_ret);
 
-    for reg in csrs.iter() {
+    for &reg in csrs {
         let csr_ret = pos.ins().x86_pop(csr_type);
         pos.prev_inst();
 
-        pos.func.locations[csr_ret] = ir::ValueLoc::Reg(*reg as RegUnit);
+        pos.func.locations[csr_ret] = ir::ValueLoc::Reg(reg);
         pos.func.dfg.append_inst_arg(inst, csr_ret);
     }
 }
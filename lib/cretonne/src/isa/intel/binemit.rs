@@ -1,7 +1,7 @@
 //! Emitting binary Intel machine code.
 
-use binemit::{CodeSink, Reloc, bad_encoding};
-use ir::{Function, Inst, Ebb, InstructionData, Opcode};
+use binemit::{CodeOffset, CodeSink, Reloc, bad_encoding};
+use ir::{Function, Inst, Ebb, GlobalVarData, InstructionData, Opcode, TlsModel, TrapCode};
 use ir::condcodes::{CondCode, IntCC, FloatCC};
 use isa::{RegUnit, StackRef, StackBase, StackBaseMask};
 use regalloc::RegDiversions;
@@ -9,6 +9,31 @@ use super::registers::RU;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-intel.rs"));
 
+/// The recommended multi-byte NOP sequences, indexed by length - 1.
+const NOPS: [&[u8]; 9] = [
+    &[0x90],
+    &[0x66, 0x90],
+    &[0x0f, 0x1f, 0x00],
+    &[0x0f, 0x1f, 0x40, 0x00],
+    &[0x0f, 0x1f, 0x44, 0x00, 0x00],
+    &[0x66, 0x0f, 0x1f, 0x44, 0x00, 0x00],
+    &[0x0f, 0x1f, 0x80, 0x00, 0x00, 0x00, 0x00],
+    &[0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+    &[0x66, 0x0f, 0x1f, 0x84, 0x00, 0x00, 0x00, 0x00, 0x00],
+];
+
+/// Emit `bytes` of padding as a sequence of the longest possible NOP instructions.
+pub fn emit_padding<CS: CodeSink + ?Sized>(bytes: CodeOffset, sink: &mut CS) {
+    let mut left = bytes as usize;
+    while left > 0 {
+        let nop = NOPS[left.min(NOPS.len()) - 1];
+        for &b in nop {
+            sink.put1(b);
+        }
+        left -= nop.len();
+    }
+}
+
 // Convert a stack base to the corresponding register.
 fn stk_base(base: StackBase) -> RegUnit {
     let ru = match base {
@@ -28,6 +53,9 @@ const OP3_BYTE2: [u8; 2] = [0x38, 0x3a];
 // A REX prefix with no bits set: 0b0100WRXB.
 const BASE_REX: u8 = 0b0100_0000;
 
+/// The W bit of a REX prefix, selecting a 64-bit operand size.
+const REX_W: u8 = 0b0000_1000;
+
 // Create a single-register REX prefix, setting the B bit to bit 3 of the register.
 // This is used for instructions that encode a register in the low 3 bits of the opcode and for
 // instructions that use the ModR/M `reg` field for something else.
@@ -46,6 +74,18 @@ fn rex2(rm: RegUnit, reg: RegUnit) -> u8 {
     BASE_REX | b | (r << 2)
 }
 
+// Create a three-register REX prefix, setting:
+//
+// REX.B = bit 3 of the SIB base register.
+// REX.R = bit 3 of reg register.
+// REX.X = bit 3 of the SIB index register.
+fn rex3(base: RegUnit, reg: RegUnit, index: RegUnit) -> u8 {
+    let b = ((base >> 3) & 1) as u8;
+    let r = ((reg >> 3) & 1) as u8;
+    let x = ((index >> 3) & 1) as u8;
+    BASE_REX | b | (x << 1) | (r << 2)
+}
+
 // Emit a REX prefix.
 //
 // The R, X, and B bits are computed from registers using the functions above. The W bit is
@@ -148,6 +188,26 @@ fn put_rexmp3<CS: CodeSink + ?Sized>(bits: u16, rex: u8, sink: &mut CS) {
     sink.put1(bits as u8);
 }
 
+// Emit an opcode with a three-byte VEX prefix: C4 RXBmmmmm WvvvvLpp.
+//
+// The R, X, and B bits are taken from a REX prefix computed by the functions above, and the extra
+// `vvvv` register operand is usually the first source operand of a non-destructive instruction.
+// VEX.L is always 0 since we only use 128-bit and scalar instructions.
+fn put_vex<CS: CodeSink + ?Sized>(bits: u16, rex: u8, vvvv: RegUnit, sink: &mut CS) {
+    debug_assert_ne!(bits & 0x0c00, 0, "Invalid encoding bits for Vex*");
+    debug_assert_eq!(rex & 0xf8, BASE_REX);
+    let pp = ((bits >> 8) & 3) as u8;
+    let mm = ((bits >> 10) & 3) as u8;
+    let w = ((bits >> 15) & 1) as u8;
+    // The R, X, B, and vvvv fields are all stored inverted.
+    let rxb = !rex & 0b111;
+    let vvvv = !(vvvv as u8) & 0xf;
+    sink.put1(0xc4);
+    sink.put1((rxb << 5) | mm);
+    sink.put1((w << 7) | (vvvv << 3) | pp);
+    sink.put1(bits as u8);
+}
+
 /// Emit a ModR/M byte for reg-reg operands.
 fn modrm_rr<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS) {
     let reg = reg as u8 & 7;
@@ -211,6 +271,16 @@ fn modrm_disp32<CS: CodeSink + ?Sized>(rm: RegUnit, reg: RegUnit, sink: &mut CS)
     sink.put1(b);
 }
 
+/// Emit a mode 00 ModR/M byte indicating that a SIB byte is present.
+fn modrm_sib<CS: CodeSink + ?Sized>(reg: RegUnit, sink: &mut CS) {
+    modrm_rm(0b100, reg, sink);
+}
+
+/// Emit a mode 01 ModR/M byte indicating that a SIB byte is present.
+fn modrm_sib_disp8<CS: CodeSink + ?Sized>(reg: RegUnit, sink: &mut CS) {
+    modrm_disp8(0b100, reg, sink);
+}
+
 /// Emit a mode 10 ModR/M byte indicating that a SIB byte is present.
 fn modrm_sib_disp32<CS: CodeSink + ?Sized>(reg: RegUnit, sink: &mut CS) {
     modrm_disp32(0b100, reg, sink);
@@ -225,6 +295,17 @@ fn sib_noindex<CS: CodeSink + ?Sized>(base: RegUnit, sink: &mut CS) {
     sink.put1(b);
 }
 
+/// Emit a SIB byte with a base register and an index register scaled by `1 << scale`.
+/// Register %rsp is invalid for `index`. It indicates that there is no index.
+fn sib<CS: CodeSink + ?Sized>(scale: u8, index: RegUnit, base: RegUnit, sink: &mut CS) {
+    debug_assert!(scale < 4, "Invalid SIB scale");
+    let index = index as u8 & 7;
+    let base = base as u8 & 7;
+    // SIB        SS_III_BBB.
+    let b = (scale << 6) | (index << 3) | base;
+    sink.put1(b);
+}
+
 /// Get the low 4 bits of an opcode for an integer condition code.
 ///
 /// Add this offset to a base opcode for:
@@ -0,0 +1,673 @@
+//! Checking emitted Intel machine code.
+//!
+//! The encoding recipes are hand-written snippets of Rust code that assemble ModR/M bytes, REX
+//! prefixes, and immediates. A recipe that puts a register in the wrong field still produces a
+//! valid instruction, so emitter bugs are normally only discovered by running the wrong code.
+//!
+//! This module decodes the first machine instruction emitted for an IR instruction and checks it
+//! against the encoding and operands of the IR instruction:
+//!
+//! - The mandatory prefix, opcode map, opcode byte, and REX.W bit must match the encoding bits.
+//! - Registers encoded in the opcode byte, the ModR/M and SIB bytes, and the VEX prefix must be
+//!   registers used by the instruction. Stack accesses may also use the stack and frame pointers.
+//! - An immediate operand must match the immediate of the IR instruction.
+//! - The displacement of a memory operand must match the offset of a load or store.
+//! - The destination of a branch must be the offset of the destination EBB.
+//!
+//! The decoder only understands the instructions produced by the Intel encoding recipes.
+
+use binemit::{Addend, CodeOffset, CodeSink, Reloc};
+use ir::{ExternalName, Function, Inst, InstructionData, JumpTable, Opcode, SourceLoc, TrapCode,
+         ValueLoc};
+use isa::RegUnit;
+use regalloc::RegDiversions;
+use std::result;
+use std::vec::Vec;
+use super::registers::RU;
+use verifier::{Error, ErrorCode, Result};
+
+/// A code sink that keeps a copy of the bytes it passes on to another sink.
+pub struct RecordingSink<'a, CS: 'a + CodeSink + ?Sized> {
+    sink: &'a mut CS,
+    /// The bytes emitted so far.
+    pub bytes: Vec<u8>,
+}
+
+impl<'a, CS: 'a + CodeSink + ?Sized> RecordingSink<'a, CS> {
+    /// Create a recording sink that forwards to `sink`.
+    pub fn new(sink: &'a mut CS) -> Self {
+        Self {
+            sink,
+            bytes: Vec::new(),
+        }
+    }
+}
+
+impl<'a, CS: 'a + CodeSink + ?Sized> CodeSink for RecordingSink<'a, CS> {
+    fn offset(&self) -> CodeOffset {
+        self.sink.offset()
+    }
+
+    fn put1(&mut self, x: u8) {
+        self.bytes.push(x);
+        self.sink.put1(x)
+    }
+
+    fn put2(&mut self, x: u16) {
+        self.put1(x as u8);
+        self.put1((x >> 8) as u8);
+    }
+
+    fn put4(&mut self, x: u32) {
+        self.put2(x as u16);
+        self.put2((x >> 16) as u16);
+    }
+
+    fn put8(&mut self, x: u64) {
+        self.put4(x as u32);
+        self.put4((x >> 32) as u32);
+    }
+
+    fn reloc_ebb(&mut self, reloc: Reloc, ebb_offset: CodeOffset) {
+        self.sink.reloc_ebb(reloc, ebb_offset)
+    }
+
+    fn reloc_external(&mut self, reloc: Reloc, name: &ExternalName, addend: Addend) {
+        self.sink.reloc_external(reloc, name, addend)
+    }
+
+    fn reloc_jt(&mut self, reloc: Reloc, jt: JumpTable) {
+        self.sink.reloc_jt(reloc, jt)
+    }
+
+    fn trap(&mut self, code: TrapCode, srcloc: SourceLoc) {
+        self.sink.trap(code, srcloc)
+    }
+
+    fn patchable_call(&mut self, name: &ExternalName) {
+        self.sink.patchable_call(name)
+    }
+
+    fn annotate_inst(&mut self, inst: Inst, start: CodeOffset) {
+        self.sink.annotate_inst(inst, start)
+    }
+}
+
+/// A decoded ModR/M byte, with the register numbers extended by the REX bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ModRM {
+    md: u8,
+    reg: u8,
+    rm: u8,
+}
+
+/// A decoded machine instruction.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+struct Decoded {
+    /// Total length of the instruction in bytes.
+    len: usize,
+    /// Mandatory prefix: 0 for none, 1 for 66, 2 for F3, and 3 for F2 like the encoding bits.
+    pp: u8,
+    /// Opcode map: 0 for one-byte opcodes, 1 for 0F, 2 for 0F 38, and 3 for 0F 3A.
+    mm: u8,
+    /// The REX.W or VEX.W bit.
+    w: bool,
+    /// The opcode byte.
+    opcode: u8,
+    /// Register encoded in the low bits of the opcode byte, if that is possible for the opcode.
+    opcode_reg: Option<u8>,
+    /// The ModR/M byte, if present.
+    modrm: Option<ModRM>,
+    /// The SIB base and index registers, if present.
+    base: Option<u8>,
+    index: Option<u8>,
+    /// Is the memory operand RIP-relative or absolute?
+    no_base: bool,
+    /// The VEX.vvvv register.
+    vvvv: Option<u8>,
+    /// Displacement of a memory operand or relative branch.
+    disp: i64,
+    /// Immediate operand bytes and their number.
+    imm: u64,
+    imm_size: usize,
+}
+
+/// Size of an immediate operand.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Imm {
+    None,
+    Byte,
+    Word,
+    /// 16 or 32 bits depending on the operand size.
+    Z,
+    /// 32 bits, or 64 bits with REX.W.
+    V,
+    /// Relative branch displacement.
+    Rel8,
+    Rel32,
+}
+
+/// Does a one-byte opcode have a ModR/M byte, and what immediate follows it?
+fn one_byte_opcode(op: u8, reg: u8) -> (bool, Imm) {
+    match op {
+        0x00..=0x3f if op & 7 < 4 => (true, Imm::None),
+        0x00..=0x3f if op & 7 == 4 => (false, Imm::Byte),
+        0x00..=0x3f if op & 7 == 5 => (false, Imm::Z),
+        0x63 => (true, Imm::None),
+        0x68 => (false, Imm::Z),
+        0x69 => (true, Imm::Z),
+        0x6a => (false, Imm::Byte),
+        0x6b => (true, Imm::Byte),
+        0x70..=0x7f | 0xeb => (false, Imm::Rel8),
+        0x80 | 0x82 | 0x83 | 0xc0 | 0xc1 | 0xc6 => (true, Imm::Byte),
+        0x81 | 0xc7 => (true, Imm::Z),
+        0x84..=0x8f | 0xd0..=0xd3 | 0xd8..=0xdf | 0xfe | 0xff => (true, Imm::None),
+        0xa8 | 0xb0..=0xb7 | 0xcd => (false, Imm::Byte),
+        0xa9 => (false, Imm::Z),
+        0xb8..=0xbf => (false, Imm::V),
+        0xc2 => (false, Imm::Word),
+        0xe8 | 0xe9 => (false, Imm::Rel32),
+        0xf6 if reg < 2 => (true, Imm::Byte),
+        0xf7 if reg < 2 => (true, Imm::Z),
+        0xf6 | 0xf7 => (true, Imm::None),
+        _ => (false, Imm::None),
+    }
+}
+
+/// Does a two-byte 0F opcode have a ModR/M byte, and what immediate follows it?
+fn two_byte_opcode(op: u8) -> (bool, Imm) {
+    match op {
+        0x05 | 0x06 | 0x07 | 0x0b | 0x30..=0x37 | 0x77 | 0xa0 | 0xa1 | 0xa2 | 0xa8 | 0xa9 |
+        0xc8..=0xcf => (false, Imm::None),
+        0x80..=0x8f => (false, Imm::Rel32),
+        0x70..=0x73 | 0xa4 | 0xac | 0xba | 0xc2 | 0xc4 | 0xc5 | 0xc6 => (true, Imm::Byte),
+        _ => (true, Imm::None),
+    }
+}
+
+/// Decode the machine instruction at the start of `bytes`.
+///
+/// Returns `None` if the bytes end before the instruction does.
+fn decode(bytes: &[u8]) -> Option<Decoded> {
+    let mut d = Decoded::default();
+    let mut pos = 0;
+    let mut rex = 0;
+    let mut opsize16 = false;
+
+    // Legacy prefixes: LOCK, segment overrides, and mandatory prefixes.
+    loop {
+        match *bytes.get(pos)? {
+            0xf0 | 0x2e | 0x3e | 0x26 | 0x36 | 0x64 | 0x65 => {}
+            0x66 => {
+                d.pp = 1;
+                opsize16 = true;
+            }
+            0xf3 => d.pp = 2,
+            0xf2 => d.pp = 3,
+            _ => break,
+        }
+        pos += 1;
+    }
+
+    let first = *bytes.get(pos)?;
+    if first == 0xc4 || first == 0xc5 {
+        // VEX prefix. The R, X, B, and vvvv fields are stored inverted.
+        let b1 = *bytes.get(pos + 1)?;
+        let (r, x, b, mmmmm, w, b2) = if first == 0xc4 {
+            let b2 = *bytes.get(pos + 2)?;
+            pos += 3;
+            (b1 >> 7, (b1 >> 6) & 1, (b1 >> 5) & 1, b1 & 0x1f, b2 >> 7, b2)
+        } else {
+            pos += 2;
+            (b1 >> 7, 1, 1, 1, 0, b1)
+        };
+        rex = ((r ^ 1) << 2) | ((x ^ 1) << 1) | (b ^ 1);
+        d.w = w != 0;
+        d.mm = mmmmm;
+        d.pp = b2 & 3;
+        d.vvvv = Some(!(b2 >> 3) & 0xf);
+    } else {
+        if first & 0xf0 == 0x40 {
+            rex = first & 0xf;
+            d.w = rex & 0b1000 != 0;
+            pos += 1;
+        }
+        if *bytes.get(pos)? == 0x0f {
+            pos += 1;
+            d.mm = match *bytes.get(pos)? {
+                0x38 => 2,
+                0x3a => 3,
+                _ => 1,
+            };
+            if d.mm > 1 {
+                pos += 1;
+            }
+        }
+    }
+    d.opcode = *bytes.get(pos)?;
+    pos += 1;
+
+    let reg_ext = |bits: u8, shift: u8| bits | (((rex >> shift) & 1) << 3);
+    let peek_reg = bytes.get(pos).map_or(0, |&m| (m >> 3) & 7);
+    let (has_modrm, imm) = match d.mm {
+        0 => one_byte_opcode(d.opcode, peek_reg),
+        1 => two_byte_opcode(d.opcode),
+        2 => (true, Imm::None),
+        _ => (true, Imm::Byte),
+    };
+
+    // Opcodes that encode a register in their low three bits.
+    d.opcode_reg = match (d.mm, d.opcode) {
+        (0, 0x50..=0x5f) |
+        (0, 0x90..=0x97) |
+        (0, 0xb0..=0xbf) |
+        (1, 0xc8..=0xcf) => Some(reg_ext(d.opcode & 7, 0)),
+        _ => None,
+    };
+
+    if has_modrm {
+        let m = *bytes.get(pos)?;
+        pos += 1;
+        let modrm = ModRM {
+            md: m >> 6,
+            reg: reg_ext((m >> 3) & 7, 2),
+            rm: reg_ext(m & 7, 0),
+        };
+        let mut disp_size = match modrm.md {
+            1 => 1,
+            2 => 4,
+            _ => 0,
+        };
+        if modrm.md != 3 {
+            if m & 7 == 4 {
+                // SIB byte.
+                let sib = *bytes.get(pos)?;
+                pos += 1;
+                let index = reg_ext((sib >> 3) & 7, 1);
+                if index != 4 {
+                    d.index = Some(index);
+                }
+                if modrm.md == 0 && sib & 7 == 5 {
+                    d.no_base = true;
+                    disp_size = 4;
+                } else {
+                    d.base = Some(reg_ext(sib & 7, 0));
+                }
+            } else if modrm.md == 0 && m & 7 == 5 {
+                d.no_base = true;
+                disp_size = 4;
+            } else {
+                d.base = Some(modrm.rm);
+            }
+        }
+        d.disp = read_signed(bytes.get(pos..pos + disp_size)?);
+        pos += disp_size;
+        d.modrm = Some(modrm);
+    }
+
+    let size = match imm {
+        Imm::None => 0,
+        Imm::Byte | Imm::Rel8 => 1,
+        Imm::Word => 2,
+        Imm::Z if opsize16 => 2,
+        Imm::V if d.w => 8,
+        Imm::Z | Imm::V | Imm::Rel32 => 4,
+    };
+    let field = bytes.get(pos..pos + size)?;
+    pos += size;
+    match imm {
+        Imm::Rel8 | Imm::Rel32 => d.disp = read_signed(field),
+        _ => {
+            d.imm_size = size;
+            d.imm = field.iter().rev().fold(0, |v, &b| (v << 8) | u64::from(b));
+        }
+    }
+
+    d.len = pos;
+    Some(d)
+}
+
+/// Read a little-endian signed integer.
+fn read_signed(bytes: &[u8]) -> i64 {
+    if bytes.is_empty() {
+        return 0;
+    }
+    let v = bytes.iter().rev().fold(0, |v, &b| (v << 8) | u64::from(b));
+    let shift = 64 - 8 * bytes.len();
+    ((v << shift) as i64) >> shift
+}
+
+/// Get the hardware register numbers of the registers used by `inst`.
+fn used_registers(func: &Function, inst: Inst, divert: &RegDiversions) -> (Vec<u8>, bool) {
+    let mut regs = Vec::new();
+    let mut stack = false;
+    {
+        let mut add_loc = |loc: ValueLoc| match loc {
+            ValueLoc::Reg(ru) => regs.push(hw_reg(ru)),
+            ValueLoc::Stack(_) => stack = true,
+            ValueLoc::Unassigned => {}
+        };
+        for &arg in func.dfg.inst_args(inst) {
+            add_loc(divert.get(arg, &func.locations));
+        }
+        for &res in func.dfg.inst_results(inst) {
+            add_loc(func.locations[res]);
+        }
+    }
+    match func.dfg[inst] {
+        InstructionData::RegMove { src, dst, .. } |
+        InstructionData::CopySpecial { src, dst, .. } => {
+            regs.push(hw_reg(src));
+            regs.push(hw_reg(dst));
+        }
+        InstructionData::RegSpill { src, .. } => {
+            regs.push(hw_reg(src));
+            stack = true;
+        }
+        InstructionData::RegFill { dst, .. } => {
+            regs.push(hw_reg(dst));
+            stack = true;
+        }
+        InstructionData::StackLoad { .. } |
+        InstructionData::StackStore { .. } => stack = true,
+        _ => {}
+    }
+    match func.dfg[inst].opcode() {
+        Opcode::AdjustSpImm | Opcode::IfcmpSp => regs.push(hw_reg(RU::rsp as RegUnit)),
+        _ => {}
+    }
+    (regs, stack)
+}
+
+/// Get the hardware number of a general purpose or XMM register unit.
+fn hw_reg(ru: RegUnit) -> u8 {
+    (ru & 15) as u8
+}
+
+/// Check the machine code `bytes` emitted for `inst` at `offset`.
+///
+/// Some recipes emit more than one machine instruction, like a `test` followed by a conditional
+/// branch. All of them must only use the registers of `inst`, and the first one matching the
+/// encoding bits is checked against the rest of the instruction. The `divert` register diversions
+/// must be the ones in effect before `inst`.
+pub fn check_emitted(
+    func: &Function,
+    inst: Inst,
+    divert: &RegDiversions,
+    offset: CodeOffset,
+    bytes: &[u8],
+) -> Result {
+    let enc = func.encodings[inst];
+    if bytes.is_empty() || !enc.is_legal() {
+        return Ok(());
+    }
+    let fail = |msg: String| Err(Error::new(inst, msg).classify(ErrorCode::MachineCode));
+
+    let mut decoded = Vec::new();
+    let mut pos = 0;
+    while pos < bytes.len() {
+        match decode(&bytes[pos..]) {
+            Some(d) => {
+                let len = d.len;
+                decoded.push((pos, d));
+                pos += len;
+            }
+            None => return fail(format!("can't decode {:02x?}", &bytes[pos..])),
+        }
+    }
+
+    let (regs, stack) = used_registers(func, inst, divert);
+    let bits = enc.bits();
+    let main = decoded.iter().position(|&(_, ref d)| matches_bits(d, bits));
+    for (i, &(_, ref d)) in decoded.iter().enumerate() {
+        let rrr = if Some(i) == main {
+            ((bits >> 12) & 7) as u8
+        } else {
+            0
+        };
+        if let Err(msg) = check_registers(d, rrr, &regs, stack) {
+            return fail(msg);
+        }
+    }
+
+    let (start, d) = match main {
+        Some(i) => (decoded[i].0, &decoded[i].1),
+        // Trap recipes don't use the encoding bits.
+        None if bits == 0 => return Ok(()),
+        None => {
+            let (_, ref first) = decoded[0];
+            return fail(format!(
+                "emitted opcode {:02x?} doesn't match encoding bits {:#06x}",
+                &bytes[..first.len],
+                bits
+            ));
+        }
+    };
+
+    // Check immediates, displacements, and branch destinations.
+    match func.dfg[inst] {
+        InstructionData::UnaryImm { imm, .. } |
+        InstructionData::BinaryImm { imm, .. } |
+        InstructionData::IntCompareImm { imm, .. } => {
+            if d.imm_size > 0 {
+                let mask = u64::max_value() >> (64 - 8 * d.imm_size);
+                let want: i64 = imm.into();
+                if d.imm != want as u64 & mask {
+                    return fail(format!("immediate {:#x} should be {}", d.imm, imm));
+                }
+            }
+        }
+        InstructionData::Load { offset, .. } |
+        InstructionData::Store { offset, .. } |
+        InstructionData::LoadComplex { offset, .. } |
+        InstructionData::StoreComplex { offset, .. } => {
+            let want: i64 = offset.into();
+            if d.base.is_some() && d.disp != want {
+                return fail(format!("displacement {} should be {}", d.disp, offset));
+            }
+        }
+        _ => {}
+    }
+    if let Some(dest) = func.dfg[inst].branch_destination() {
+        if is_branch_opcode(d.mm, d.opcode) {
+            let target = i64::from(offset) + (start + d.len) as i64 + d.disp;
+            if target != i64::from(func.offsets[dest]) {
+                return fail(format!(
+                    "branch target {} should be {} at {}",
+                    target,
+                    dest,
+                    func.offsets[dest]
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Does the decoded instruction `d` match the prefix, opcode, and REX.W in the encoding `bits`?
+fn matches_bits(d: &Decoded, bits: u16) -> bool {
+    let want_op = bits as u8;
+    let pp = ((bits >> 8) & 3) as u8;
+    let mm = ((bits >> 10) & 3) as u8;
+    let w = bits & 0x8000 != 0;
+    // Branch recipes keep the opcode of the branch in the low bits, and the remaining bits apply
+    // to a `test` instruction in front of the branch.
+    if is_branch_opcode(d.mm, d.opcode) && d.opcode == want_op {
+        return true;
+    }
+    let op_matches = d.opcode == want_op ||
+        (d.opcode_reg.is_some() && want_op & 7 == 0 && d.opcode & 0xf8 == want_op) ||
+        (is_condition_opcode(d.mm, want_op) && d.opcode & 0xf0 == want_op);
+    op_matches && d.pp == pp && d.mm == mm && d.w == w
+}
+
+/// Check that the registers encoded in `d` are among the `regs` used by the instruction.
+///
+/// When the ModR/M `reg` field holds an opcode extension, it must be `rrr`. Extension `/0` can't
+/// be distinguished from a register, so it is always accepted. Memory operands may also use the
+/// stack and frame pointers when the instruction accesses the `stack`.
+fn check_registers(d: &Decoded, rrr: u8, regs: &[u8], stack: bool) -> result::Result<(), String> {
+    // Fences and multi-byte NOPs don't have register operands.
+    if d.mm == 1 && (d.opcode == 0xae || d.opcode == 0x1f) {
+        return Ok(());
+    }
+    let is_used = |reg: u8| regs.contains(&reg);
+    let is_address = |reg: u8| {
+        is_used(reg) ||
+            (stack &&
+                 (reg == hw_reg(RU::rsp as RegUnit) || reg == hw_reg(RU::rbp as RegUnit)))
+    };
+    if let Some(modrm) = d.modrm {
+        let reg_ok = if rrr != 0 {
+            modrm.reg == rrr
+        } else {
+            modrm.reg == 0 || is_used(modrm.reg)
+        };
+        if !reg_ok {
+            return Err(format!(
+                "ModR/M reg field {} is not an operand of the instruction",
+                modrm.reg
+            ));
+        }
+        if modrm.md == 3 && !is_used(modrm.rm) {
+            return Err(format!(
+                "ModR/M r/m field {} is not an operand of the instruction",
+                modrm.rm
+            ));
+        }
+    }
+    for &(what, reg) in &[("base", d.base), ("index", d.index)] {
+        if let Some(reg) = reg {
+            if !is_address(reg) {
+                return Err(format!(
+                    "{} register {} is not an operand of the instruction",
+                    what,
+                    reg
+                ));
+            }
+        }
+    }
+    if let Some(reg) = d.opcode_reg {
+        if reg != 0 && !is_used(reg) {
+            return Err(format!(
+                "opcode register {} is not an operand of the instruction",
+                reg
+            ));
+        }
+    }
+    if let Some(reg) = d.vvvv {
+        if reg != 0 && !is_used(reg) {
+            return Err(format!(
+                "VEX.vvvv register {} is not an operand of the instruction",
+                reg
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Is this a relative jump or conditional branch?
+fn is_branch_opcode(mm: u8, op: u8) -> bool {
+    match (mm, op) {
+        (0, 0x70..=0x7f) | (0, 0xe9) | (0, 0xeb) | (1, 0x80..=0x8f) => true,
+        _ => false,
+    }
+}
+
+/// Is `op` the first of a group of 16 opcodes that encode a condition code in the low bits?
+fn is_condition_opcode(mm: u8, op: u8) -> bool {
+    match (mm, op) {
+        (0, 0x70) | (1, 0x40) | (1, 0x80) | (1, 0x90) => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_emitted, decode};
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder, ValueLoc};
+    use ir::types::I64;
+    use isa;
+    use isa::intel::registers::RU;
+    use regalloc::RegDiversions;
+    use settings::{self, Configurable};
+
+    #[test]
+    fn decode_instructions() {
+        // add %rcx, %rdx
+        let d = decode(&[0x48, 0x01, 0xca]).unwrap();
+        assert_eq!(d.len, 3);
+        assert!(d.w);
+        assert_eq!(d.opcode, 0x01);
+        let modrm = d.modrm.unwrap();
+        assert_eq!((modrm.md, modrm.reg, modrm.rm), (3, 1, 2));
+
+        // mov 0x10(%r12,%r13,4), %r9d
+        let d = decode(&[0x47, 0x8b, 0x4c, 0xac, 0x10]).unwrap();
+        assert_eq!(d.len, 5);
+        assert_eq!(d.modrm.unwrap().reg, 9);
+        assert_eq!(d.base, Some(12));
+        assert_eq!(d.index, Some(13));
+        assert_eq!(d.disp, 0x10);
+
+        // movabs $0x1122334455667788, %r10
+        let d = decode(&[0x49, 0xba, 0x88, 0x77, 0x66, 0x55, 0x44, 0x33, 0x22, 0x11]).unwrap();
+        assert_eq!(d.len, 10);
+        assert_eq!(d.opcode_reg, Some(10));
+        assert_eq!(d.imm, 0x1122334455667788);
+
+        // roundss $3, %xmm2, %xmm1
+        let d = decode(&[0x66, 0x0f, 0x3a, 0x0a, 0xca, 0x03]).unwrap();
+        assert_eq!((d.pp, d.mm, d.opcode), (1, 3, 0x0a));
+        assert_eq!(d.imm, 3);
+
+        // jne with a 32-bit displacement.
+        let d = decode(&[0x0f, 0x85, 0xfc, 0xff, 0xff, 0xff]).unwrap();
+        assert_eq!(d.len, 6);
+        assert_eq!(d.disp, -4);
+
+        // Truncated immediate.
+        assert_eq!(decode(&[0x81, 0xc1, 0x01]), None);
+    }
+
+    #[test]
+    fn check_registers() {
+        let mut shared = settings::builder();
+        shared.enable("is_64bit").unwrap();
+        let isa = isa::lookup("intel").unwrap().finish(
+            settings::Flags::new(&shared),
+        );
+
+        let mut func = Function::new();
+        let inst = {
+            let mut pos = FuncCursor::new(&mut func);
+            let ebb = pos.func.dfg.make_ebb();
+            pos.insert_ebb(ebb);
+            let v0 = pos.func.dfg.append_ebb_param(ebb, I64);
+            let v1 = pos.func.dfg.append_ebb_param(ebb, I64);
+            let v2 = pos.ins().iadd(v0, v1);
+            pos.func.locations[v0] = ValueLoc::Reg(RU::rax as u16);
+            pos.func.locations[v1] = ValueLoc::Reg(RU::rcx as u16);
+            pos.func.locations[v2] = ValueLoc::Reg(RU::rax as u16);
+            pos.func.dfg.value_def(v2).unwrap_inst()
+        };
+        func.encodings[inst] = isa.encode(&func, &func.dfg[inst], I64).unwrap();
+        let divert = RegDiversions::new();
+
+        // add %rcx, %rax
+        assert_eq!(
+            check_emitted(&func, inst, &divert, 0, &[0x48, 0x01, 0xc8]),
+            Ok(())
+        );
+        // add %rdx, %rax
+        let err = check_emitted(&func, inst, &divert, 0, &[0x48, 0x01, 0xd0]).unwrap_err();
+        assert_eq!(
+            err.message,
+            "ModR/M reg field 2 is not an operand of the instruction"
+        );
+        // add %ecx, %eax
+        assert!(check_emitted(&func, inst, &divert, 0, &[0x01, 0xc8]).is_err());
+    }
+}
@@ -7,10 +7,13 @@ use ir::{self, InstBuilder};
 use ir::condcodes::IntCC;
 use isa::constraints::*;
 use isa::enc_tables::*;
-use isa::encoding::RecipeSizing;
+use isa::encoding::{RecipeInfo, RecipeSizing, TextRange};
 use isa;
+use legalizer::{narrow_iconst, narrow_branch, narrow_load, narrow_store, narrow_divrem};
+use legalizer::split;
 use predicates;
 use super::registers::*;
+use std::vec::Vec;
 
 include!(concat!(env!("OUT_DIR"), "/encoding-intel.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-intel.rs"));
@@ -149,6 +152,77 @@ fn expand_udivrem(
     pos.remove_inst();
 }
 
+/// Narrow a 64-bit shift into operations on a 32-bit register pair.
+///
+/// The double precision shifts move bits between the two halves, but they only use the low 5
+/// bits of the shift amount like the other 32-bit shifts. When bit 5 of the amount is set, the
+/// shifted halves are moved to the other half of the register pair instead.
+fn narrow_shift(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &isa::TargetIsa,
+) {
+    use ir::types::I32;
+
+    let (opcode, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("Need ishl/ushr/sshr: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // The shift amount is taken modulo 64, so the low 32 bits are enough.
+    let amt = match pos.func.dfg.value_type(y) {
+        I32 => y,
+        ir::types::I64 => split::isplit(pos.func, cfg, pos.position(), pos.srcloc(), y).0,
+        _ => pos.ins().uextend(I32, y),
+    };
+    let (xl, xh) = split::isplit(pos.func, cfg, pos.position(), pos.srcloc(), x);
+
+    // Compute the results for amounts below 32, and the results for larger amounts.
+    let (small_lo, small_hi, large_lo, large_hi) = match opcode {
+        ir::Opcode::Ishl => {
+            let lo = pos.ins().ishl(xl, amt);
+            let hi = pos.ins().x86_shld(xh, xl, amt);
+            let zero = pos.ins().iconst(I32, 0);
+            (lo, hi, zero, lo)
+        }
+        ir::Opcode::Ushr => {
+            let lo = pos.ins().x86_shrd(xl, xh, amt);
+            let hi = pos.ins().ushr(xh, amt);
+            let zero = pos.ins().iconst(I32, 0);
+            (lo, hi, hi, zero)
+        }
+        ir::Opcode::Sshr => {
+            let lo = pos.ins().x86_shrd(xl, xh, amt);
+            let hi = pos.ins().sshr(xh, amt);
+            let sign = pos.ins().sshr_imm(xh, 31);
+            (lo, hi, hi, sign)
+        }
+        _ => panic!("Need ishl/ushr/sshr: {}", pos.func.dfg.display_inst(inst, None)),
+    };
+
+    let large = pos.ins().band_imm(amt, 32);
+    let flags = pos.ins().ifcmp_imm(large, 0);
+    let lo = pos.ins().selectif(
+        I32,
+        IntCC::NotEqual,
+        flags,
+        large_lo,
+        small_lo,
+    );
+    let hi = pos.ins().selectif(
+        I32,
+        IntCC::NotEqual,
+        flags,
+        large_hi,
+        small_hi,
+    );
+    pos.func.dfg.replace(inst).iconcat(lo, hi);
+}
+
 /// Expand the `fmin` and `fmax` instructions using the Intel `x86_fmin` and `x86_fmax`
 /// instructions.
 fn expand_minmax(
@@ -243,6 +317,57 @@ fn expand_minmax(
     cfg.recompute_ebb(pos.func, done);
 }
 
+/// Expand the `select` instruction.
+///
+/// Floating point selects use a variable blend when SSE4.1 or AVX is available. The blend mask is
+/// computed by negating the 0/1 integer value of the condition. Everything else uses the generic
+/// expansion with a branch.
+///
+/// Integer conditions are compared against zero first.
+fn expand_select(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &isa::TargetIsa,
+) {
+    let (ctrl, tval, fval) = match func.dfg[inst] {
+        ir::InstructionData::Ternary {
+            opcode: ir::Opcode::Select,
+            args,
+        } => (args[0], args[1], args[2]),
+        _ => panic!("Expected select: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+
+    // The mask needs an integer of the same size that can be moved into an XMM register.
+    let mask_ty = match ty {
+        ir::types::F32 => ir::types::I32,
+        ir::types::F64 if isa.flags().is_64bit() => ir::types::I64,
+        _ => return ::legalizer::expand_select(inst, func, cfg, isa),
+    };
+
+    // Check that the ISA flags enable a blend instruction.
+    let blend = ir::InstructionData::Ternary {
+        opcode: ir::Opcode::X86Blendv,
+        args: [tval, tval, fval],
+    };
+    if isa.encode(func, &blend, ty).is_err() {
+        return ::legalizer::expand_select(inst, func, cfg, isa);
+    }
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let ctrl = if pos.func.dfg.value_type(ctrl).is_int() {
+        pos.ins().icmp_imm(IntCC::NotEqual, ctrl, 0)
+    } else {
+        ctrl
+    };
+    let one = pos.ins().bint(mask_ty, ctrl);
+    let ones = pos.ins().irsub_imm(one, 0);
+    let mask = pos.ins().bitcast(ty, ones);
+    pos.func.dfg.replace(inst).x86_blendv(mask, tval, fval);
+}
+
 /// Intel has no unsigned-to-float conversions. We handle the easy case of zero-extending i32 to
 /// i64 with a pattern, the rest needs more code.
 fn expand_fcvt_from_uint(
@@ -509,3 +634,82 @@ fn expand_fcvt_to_uint(
     cfg.recompute_ebb(pos.func, large);
     cfg.recompute_ebb(pos.func, done);
 }
+
+/// Expand the `shuffle` and `swizzle` instructions on 32-bit lanes using `x86_shufps` and
+/// `x86_pshufd`.
+fn expand_shuffle(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &isa::TargetIsa,
+) {
+    let (x, y, mask) = match func.dfg[inst] {
+        ir::InstructionData::Shuffle { args, mask, .. } => (args[0], args[1], mask),
+        ir::InstructionData::Swizzle { arg, mask, .. } => (arg, arg, mask),
+        _ => panic!("Expected shuffle: {}", func.dfg.display_inst(inst, None)),
+    };
+    let lanes = func.dfg.lane_masks[mask].as_slice().to_vec();
+    debug_assert_eq!(lanes.len(), 4);
+
+    // Split the result lanes by the input they are selected from.
+    let (from_x, from_y): (Vec<usize>, Vec<usize>) = (0..4).partition(|&i| lanes[i] < 4);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Gather the selected lanes in a single vector, and compute the position of each result lane
+    // in that vector.
+    let mut order = [0; 4];
+    let gathered = if from_x.is_empty() || from_y.is_empty() {
+        for i in 0..4 {
+            order[i] = lanes[i] & 3;
+        }
+        if from_y.is_empty() { x } else { y }
+    } else {
+        // The two low lanes of a `x86_shufps` result come from its first operand, so use the
+        // input providing the most lanes as the first operand. On a tie, pick the input providing
+        // the first result lane.
+        let x_first = from_x.len() > from_y.len() || (from_x.len() == from_y.len() && lanes[0] < 4);
+        let (a, a_idx, b, b_idx) = if x_first {
+            (x, from_x, y, from_y)
+        } else {
+            (y, from_y, x, from_x)
+        };
+        let a_lane = |k: usize| lanes[a_idx[k]] & 3;
+        let b_lane = |k: usize| lanes[b_idx[k]] & 3;
+        let gathered = if a_idx.len() == 2 {
+            // Gather `[a0, a1, b0, b1]` directly.
+            let imm = lane_order([a_lane(0), a_lane(1), b_lane(0), b_lane(1)]);
+            pos.ins().x86_shufps(a, imm, b)
+        } else {
+            // Gather `[a0, a1, a2, b0]` by first combining `a2` and `b0` in one vector.
+            let imm = lane_order([a_lane(2), a_lane(2), b_lane(0), b_lane(0)]);
+            let tmp = pos.ins().x86_shufps(a, imm, b);
+            let imm = lane_order([a_lane(0), a_lane(1), 0, 2]);
+            pos.ins().x86_shufps(a, imm, tmp)
+        };
+        for (k, &i) in a_idx.iter().enumerate() {
+            order[i] = k as u8;
+        }
+        for (k, &i) in b_idx.iter().enumerate() {
+            order[i] = (a_idx.len() + k) as u8;
+        }
+        gathered
+    };
+
+    if order == [0, 1, 2, 3] {
+        // The gathered lanes are already in order.
+        pos.replace_with_multiple(inst, |_| [gathered]);
+    } else {
+        pos.func.dfg.replace(inst).x86_pshufd(gathered, lane_order(order));
+    }
+}
+
+/// Encode the source lane of each of the four 32-bit result lanes as the immediate operand of
+/// `x86_pshufd` or `x86_shufps`.
+fn lane_order(lanes: [u8; 4]) -> u8 {
+    lanes.iter().enumerate().fold(
+        0,
+        |imm, (i, &lane)| imm | (lane & 3) << (2 * i),
+    )
+}
@@ -3,25 +3,32 @@
 pub mod settings;
 mod abi;
 mod binemit;
+mod check;
 mod enc_tables;
 mod registers;
 
-use binemit::{CodeSink, MemoryCodeSink, emit_function};
+use binemit::{CodeOffset, CodeSink, MemoryCodeSink, emit_function};
+use self::check::RecordingSink;
 use super::super::settings as shared_settings;
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
-use isa::{TargetIsa, RegInfo, RegClass, EncInfo};
+use isa::{TargetIsa, RegInfo, RegClass, RegUnit, EncInfo, FrameConfig};
 use ir;
 use regalloc;
 use result;
 use timing;
+use verifier;
 use std::fmt;
 use std::boxed::Box;
+use std::hash::{Hash, Hasher};
+use std::vec::Vec;
 
 #[allow(dead_code)]
 struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
 }
 
@@ -29,6 +36,8 @@ struct Isa {
 pub fn isa_builder() -> IsaBuilder {
     IsaBuilder {
         setup: settings::builder(),
+        frame: FrameConfig::default(),
+        libcall_names: ir::LibCallNames::new(),
         constructor: isa_constructor,
     }
 }
@@ -36,6 +45,8 @@ pub fn isa_builder() -> IsaBuilder {
 fn isa_constructor(
     shared_flags: shared_settings::Flags,
     builder: &shared_settings::Builder,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
 ) -> Box<TargetIsa> {
     let level1 = if shared_flags.is_64bit() {
         &enc_tables::LEVEL1_I64[..]
@@ -45,6 +56,8 @@ fn isa_constructor(
     Box::new(Isa {
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        frame,
+        libcall_names,
         cpumode: level1,
     })
 }
@@ -58,6 +71,24 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn hash_flags(&self, mut state: &mut Hasher) {
+        self.name().hash(&mut state);
+        self.shared_flags.hash(&mut state);
+        self.isa_flags.hash(&mut state);
+    }
+
+    fn isa_settings(&self) -> Vec<shared_settings::Setting> {
+        self.isa_flags.settings()
+    }
+
+    fn frame_config(&self) -> &FrameConfig {
+        &self.frame
+    }
+
+    fn libcall_names(&self) -> &ir::LibCallNames {
+        &self.libcall_names
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -68,14 +99,14 @@ impl TargetIsa for Isa {
 
     fn legal_encodings<'a>(
         &'a self,
-        dfg: &'a ir::DataFlowGraph,
+        func: &'a ir::Function,
         inst: &'a ir::InstructionData,
         ctrl_typevar: ir::Type,
     ) -> Encodings<'a> {
         lookup_enclist(
             ctrl_typevar,
             inst,
-            dfg,
+            func,
             self.cpumode,
             &enc_tables::LEVEL2[..],
             &enc_tables::ENCLISTS[..],
@@ -95,7 +126,12 @@ impl TargetIsa for Isa {
     }
 
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::AllocatableSet {
-        abi::allocatable_registers(func, &self.shared_flags)
+        abi::allocatable_registers(func, self)
+    }
+
+    fn callee_saved_registers(&self, call_conv: ir::CallConv) -> Vec<RegUnit> {
+        let csrs = abi::callee_saved_registers(&self.shared_flags, call_conv);
+        self.frame.callee_saved_or(csrs.iter().map(|&ru| ru as RegUnit))
     }
 
     fn emit_inst(
@@ -108,8 +144,44 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    fn emit_padding(&self, bytes: CodeOffset, sink: &mut CodeSink) {
+        binemit::emit_padding(bytes, sink)
+    }
+
     fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        if cfg!(debug_assertions) && self.shared_flags.enable_verifier() {
+            let emit_checked = |func: &ir::Function,
+                                inst: ir::Inst,
+                                divert: &mut regalloc::RegDiversions,
+                                sink: &mut MemoryCodeSink| {
+                let before = divert.clone();
+                let offset = sink.offset();
+                let mut recorder = RecordingSink::new(sink);
+                binemit::emit_inst(func, inst, divert, &mut recorder);
+                if let Err(e) = check::check_emitted(func, inst, &before, offset, &recorder.bytes) {
+                    let isa: &TargetIsa = self;
+                    panic!(
+                        "Bad machine code for {}: {}",
+                        func.dfg.display_inst(inst, isa),
+                        e
+                    );
+                }
+            };
+            emit_function(func, emit_checked, binemit::emit_padding, sink)
+        } else {
+            emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
+        }
+    }
+
+    fn check_emitted(
+        &self,
+        func: &ir::Function,
+        inst: ir::Inst,
+        divert: &regalloc::RegDiversions,
+        offset: CodeOffset,
+        bytes: &[u8],
+    ) -> verifier::Result {
+        check::check_emitted(func, inst, divert, offset, bytes)
     }
 
     fn prologue_epilogue(&self, func: &mut ir::Function) -> result::CtonResult {
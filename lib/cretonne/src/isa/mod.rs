@@ -37,13 +37,20 @@
 //! }
 //! ```
 //!
+//! Embedders that describe their target with a triple like `x86_64-unknown-linux-gnu` can use
+//! `isa::lookup_by_triple()` instead, which also returns a shared settings builder configured for
+//! the architecture.
+//!
 //! The configured target ISA trait object is a `Box<TargetIsa>` which can be used for multiple
 //! concurrent function compilations.
 
 pub use isa::constraints::{RecipeConstraints, OperandConstraint, ConstraintKind, BranchRange};
-pub use isa::encoding::{Encoding, EncInfo};
+pub use isa::encoding::{Encoding, EncInfo, EncodingCandidate, EncodingDiagnosis,
+                        EncodingFailure};
+pub use isa::frame::FrameConfig;
 pub use isa::registers::{RegInfo, RegUnit, RegClass, RegClassIndex, regs_overlap};
 pub use isa::stack::{StackBase, StackBaseMask, StackRef};
+pub use isa::triple::lookup_by_triple;
 
 use binemit;
 use flowgraph;
@@ -52,9 +59,13 @@ use ir;
 use regalloc;
 use result;
 use timing;
+use verifier;
 use isa::enc_tables::Encodings;
 use std::fmt;
 use std::boxed::Box;
+use std::hash::Hasher;
+use std::sync::Arc;
+use std::vec::Vec;
 
 #[cfg(build_riscv)]
 mod riscv;
@@ -71,8 +82,10 @@ mod arm64;
 pub mod registers;
 mod encoding;
 mod enc_tables;
+mod frame;
 mod constraints;
 mod stack;
+mod triple;
 
 /// Returns a builder that can create a corresponding `TargetIsa`
 /// or `Err(LookupError::Unsupported)` if not enabled.
@@ -92,6 +105,20 @@ macro_rules! isa_builder {
     };
 }
 
+/// Names of all the ISAs known to Cretonne, including the ones that were left out of this build.
+pub const ALL_ISAS: &[&str] = &["riscv", "intel", "arm32", "arm64"];
+
+/// Get the names of the ISAs that are supported by this build of Cretonne.
+///
+/// All of these names can be passed to `lookup()`.
+pub fn supported_isas() -> Vec<&'static str> {
+    ALL_ISAS
+        .iter()
+        .cloned()
+        .filter(|name| lookup(name).is_ok())
+        .collect()
+}
+
 /// Look for a supported ISA with the given `name`.
 /// Return a builder that can create a corresponding `TargetIsa`.
 pub fn lookup(name: &str) -> Result<Builder, LookupError> {
@@ -118,14 +145,40 @@ pub enum LookupError {
 /// Modify the ISA-specific settings before creating the `TargetIsa` trait object with `finish`.
 pub struct Builder {
     setup: settings::Builder,
-    constructor: fn(settings::Flags, &settings::Builder) -> Box<TargetIsa>,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
+    constructor: fn(settings::Flags, &settings::Builder, FrameConfig, ir::LibCallNames)
+        -> Box<TargetIsa>,
 }
 
 impl Builder {
+    /// Get the ISA-specific settings and their current values in this builder.
+    ///
+    /// The boolean settings describe the CPU features that the ISA's encodings depend on.
+    pub fn settings(&self) -> Vec<settings::Setting> {
+        self.setup.settings()
+    }
+
+    /// Override the callee-saved registers and prologue generation of the `TargetIsa`.
+    pub fn set_frame_config(&mut self, frame: FrameConfig) {
+        self.frame = frame;
+    }
+
+    /// Override the symbol names and calling conventions used for runtime library calls.
+    pub fn set_libcall_names(&mut self, libcall_names: ir::LibCallNames) {
+        self.libcall_names = libcall_names;
+    }
+
     /// Combine the ISA-specific settings with the provided ISA-independent settings and allocate a
     /// fully configured `TargetIsa` trait object.
     pub fn finish(self, shared_flags: settings::Flags) -> Box<TargetIsa> {
-        (self.constructor)(shared_flags, &self.setup)
+        (self.constructor)(shared_flags, &self.setup, self.frame, self.libcall_names)
+    }
+
+    /// Like `finish`, but return a reference-counted `TargetIsa` that can be shared between
+    /// threads compiling different functions.
+    pub fn finish_shared(self, shared_flags: settings::Flags) -> Arc<TargetIsa> {
+        Arc::from(self.finish(shared_flags))
     }
 }
 
@@ -151,20 +204,46 @@ pub type Legalize = fn(ir::Inst,
 
 /// Methods that are specialized to a target ISA. Implies a Display trait that shows the
 /// shared flags, as well as any isa-specific flags.
-pub trait TargetIsa: fmt::Display {
+///
+/// A `TargetIsa` is immutable once created, so it can be shared between threads.
+pub trait TargetIsa: fmt::Display + Send + Sync {
     /// Get the name of this ISA.
     fn name(&self) -> &'static str;
 
     /// Get the ISA-independent flags that were used to make this trait object.
     fn flags(&self) -> &settings::Flags;
 
+    /// Feed the ISA name, the shared flags, and the ISA-specific flags into `state`.
+    ///
+    /// Two ISAs with the same name and flags hash the same, so the hash can be used as part of a
+    /// key for caching compiled code. The frame configuration and runtime library names are not
+    /// included.
+    fn hash_flags(&self, state: &mut Hasher);
+
+    /// Get the values of the ISA-specific settings that were used to make this trait object.
+    fn isa_settings(&self) -> Vec<settings::Setting>;
+
+    /// Does this ISA lower floating point values and instructions to integer code?
+    ///
+    /// The legalizer then replaces floating point arithmetic with runtime library calls. The
+    /// default implementation follows the `enable_float` setting.
+    fn soft_float(&self) -> bool {
+        !self.flags().enable_float()
+    }
+
+    /// Get the embedder's stack frame configuration.
+    fn frame_config(&self) -> &FrameConfig;
+
+    /// Get the symbol names and calling conventions used for runtime library calls.
+    fn libcall_names(&self) -> &ir::LibCallNames;
+
     /// Get a data structure describing the registers in this ISA.
     fn register_info(&self) -> RegInfo;
 
     /// Returns an iterartor over legal encodings for the instruction.
     fn legal_encodings<'a>(
         &'a self,
-        dfg: &'a ir::DataFlowGraph,
+        func: &'a ir::Function,
         inst: &'a ir::InstructionData,
         ctrl_typevar: ir::Type,
     ) -> Encodings<'a>;
@@ -177,17 +256,35 @@ pub trait TargetIsa: fmt::Display {
     /// This is also the main entry point for determining if an instruction is legal.
     fn encode(
         &self,
-        dfg: &ir::DataFlowGraph,
+        func: &ir::Function,
         inst: &ir::InstructionData,
         ctrl_typevar: ir::Type,
     ) -> Result<Encoding, Legalize> {
-        let mut iter = self.legal_encodings(dfg, inst, ctrl_typevar);
+        let mut iter = self.legal_encodings(func, inst, ctrl_typevar);
         iter.next().ok_or_else(|| iter.legalize())
     }
 
     /// Get a data structure describing the instruction encodings in this ISA.
     fn encoding_info(&self) -> EncInfo;
 
+    /// Explain how `inst` matches the encoding tables.
+    ///
+    /// This lists the candidate encodings for the instruction and the predicate that rejected each
+    /// of them. It is meant for diagnosing instructions that could not be legalized or encoded.
+    fn explain_encoding(
+        &self,
+        func: &ir::Function,
+        inst: &ir::InstructionData,
+        ctrl_typevar: ir::Type,
+    ) -> EncodingDiagnosis {
+        EncodingDiagnosis::new(
+            inst.opcode(),
+            ctrl_typevar,
+            self.legal_encodings(func, inst, ctrl_typevar).candidates(),
+            self.encoding_info(),
+        )
+    }
+
     /// Legalize a function signature.
     ///
     /// This is used to legalize both the signature of the function being compiled and any called
@@ -237,6 +334,14 @@ pub trait TargetIsa: fmt::Display {
     /// registers.
     fn allocatable_registers(&self, func: &ir::Function) -> regalloc::AllocatableSet;
 
+    /// Get the registers that a function with the calling convention `call_conv` must preserve.
+    ///
+    /// This takes the embedder's `FrameConfig` into account. The default implementation is for
+    /// ISAs that don't save any registers in their prologue.
+    fn callee_saved_registers(&self, _call_conv: ir::CallConv) -> Vec<RegUnit> {
+        self.frame_config().callee_saved_or(None)
+    }
+
     /// Compute the stack layout and insert prologue and epilogue code into `func`.
     ///
     /// Return an error if the stack frame is too large.
@@ -256,6 +361,13 @@ pub trait TargetIsa: fmt::Display {
             func.stack_slots.push(ss);
         }
 
+        // Account for the embedder's prologue.
+        if let Some(bytes) = self.frame_config().external_prologue_bytes {
+            let mut ss = ir::StackSlotData::new(ir::StackSlotKind::IncomingArg, bytes);
+            ss.offset = Some(-(bytes as StackOffset));
+            func.stack_slots.push(ss);
+        }
+
         layout_stack(&mut func.stack_slots, word_size)?;
         Ok(())
     }
@@ -272,6 +384,29 @@ pub trait TargetIsa: fmt::Display {
         sink: &mut binemit::CodeSink,
     );
 
+    /// Emit `bytes` of no-op instructions into the `sink` trait object.
+    ///
+    /// This is used to pad the code in front of aligned EBBs. The padding can be executed, so it
+    /// must consist of valid instructions.
+    fn emit_padding(&self, bytes: binemit::CodeOffset, sink: &mut binemit::CodeSink);
+
+    /// Decode the machine code `bytes` emitted for `inst` at `offset` and check it against the
+    /// encoding and operands of `inst`.
+    ///
+    /// This is a debugging aid for finding bugs in the encoding recipes. The `divert` register
+    /// diversions must be the ones in effect before `inst`. Not all ISAs can decode their machine
+    /// code, and the default implementation accepts anything.
+    fn check_emitted(
+        &self,
+        _func: &ir::Function,
+        _inst: ir::Inst,
+        _divert: &regalloc::RegDiversions,
+        _offset: binemit::CodeOffset,
+        _bytes: &[u8],
+    ) -> verifier::Result {
+        Ok(())
+    }
+
     /// Emit a whole function into memory.
     ///
     /// This is more performant than calling `emit_inst` for each instruction.
@@ -3,7 +3,8 @@
 //! This module implements the RISC-V calling convention through the primary `legalize_signature()`
 //! entry point.
 //!
-//! This doesn't support the soft-float ABI at the moment.
+//! Without the "F" and "D" extensions, the legalizer lowers floating point values to integers
+//! before legalizing signatures, which gives the soft-float ABI.
 
 use abi::{ArgAction, ValueConversion, ArgAssigner, legalize_args};
 use ir::{self, Type, AbiParam, ArgumentLoc, ArgumentExtension, ArgumentPurpose};
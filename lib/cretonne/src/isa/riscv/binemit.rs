@@ -1,7 +1,7 @@
 //! Emitting binary RISC-V machine code.
 
-use binemit::{CodeSink, Reloc, bad_encoding};
-use ir::{Function, Inst, InstructionData};
+use binemit::{CodeOffset, CodeSink, Reloc, bad_encoding};
+use ir::{AtomicOrdering, Function, Inst, InstructionData};
 use isa::{RegUnit, StackRef, StackBaseMask};
 use predicates::is_signed_int;
 use regalloc::RegDiversions;
@@ -9,6 +9,18 @@ use std::u32;
 
 include!(concat!(env!("OUT_DIR"), "/binemit-riscv.rs"));
 
+/// Emit `bytes` of padding as `addi x0, x0, 0` instructions, with a trailing `c.nop` if `bytes` is
+/// not a multiple of 4.
+pub fn emit_padding<CS: CodeSink + ?Sized>(bytes: CodeOffset, sink: &mut CS) {
+    debug_assert_eq!(bytes % 2, 0, "Misaligned RISC-V padding");
+    for _ in 0..bytes / 4 {
+        sink.put4(0x0000_0013);
+    }
+    if bytes % 4 != 0 {
+        sink.put2(0x0001);
+    }
+}
+
 /// R-type instructions.
 ///
 ///   31     24  19  14     11 6
@@ -37,6 +49,14 @@ fn put_r<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, rs2: RegUnit, rd: RegUn
     sink.put4(i);
 }
 
+/// Unary R-type OP-FP instructions with a fixed rs2 field and funct3 = 0.
+///
+/// Encoding bits: `opcode[6:2] | (rs2 << 5) | (funct7 << 8)`.
+fn put_rfu<CS: CodeSink + ?Sized>(bits: u16, rs1: RegUnit, rd: RegUnit, sink: &mut CS) {
+    let rs2 = (bits >> 5) & 0x7;
+    put_r(bits & !(0x7 << 5), rs1, rs2, rd, sink);
+}
+
 /// R-type instructions with a shift amount instead of rs2.
 ///
 ///   31     25    19  14     11 6
@@ -119,6 +139,35 @@ fn put_u<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS)
     sink.put4(i);
 }
 
+/// S-type store instructions.
+///
+///   31  24  19  14     11  6
+///   imm rs2 rs1 funct3 imm opcode
+///    25  20  15     12   7      0
+///
+/// Encoding bits: `opcode[6:2] | (funct3 << 5)`
+fn put_s<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rs1: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let bits = u32::from(bits);
+    let opcode5 = bits & 0x1f;
+    let funct3 = (bits >> 5) & 0x7;
+    let rs1 = u32::from(rs1) & 0x1f;
+    let rs2 = u32::from(rs2) & 0x1f;
+
+    debug_assert!(is_signed_int(imm, 12, 0), "S out of range {:#x}", imm);
+    let imm = imm as u32;
+
+    // 0-6: opcode
+    let mut i = 0x3;
+    i |= opcode5 << 2;
+    i |= (imm & 0x1f) << 7;
+    i |= funct3 << 12;
+    i |= rs1 << 15;
+    i |= rs2 << 20;
+    i |= ((imm >> 5) & 0x7f) << 25;
+
+    sink.put4(i);
+}
+
 /// SB-type branch instructions.
 ///
 ///   31  24  19  14     11  6
@@ -180,3 +229,163 @@ fn put_uj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rd: RegUnit, sink: &mut CS
 
     sink.put4(i);
 }
+
+/// Get the 3-bit register field of a compressed instruction for one of the registers `%x8-%x15`.
+fn creg(reg: RegUnit) -> u16 {
+    debug_assert!(reg >= 8 && reg < 16, "%x{} is not a compressed register", reg);
+    (reg - 8) & 0x7
+}
+
+/// CR-type compressed instructions.
+///
+///   15     11     6   1
+///   funct4 rd/rs1 rs2 op
+///       12      7   2  0
+///
+/// Encoding bits: `op | (funct4 << 12)`.
+fn put_cr<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let rd = rd & 0x1f;
+    let rs2 = rs2 & 0x1f;
+
+    let mut i = bits;
+    i |= rd << 7;
+    i |= rs2 << 2;
+
+    sink.put2(i);
+}
+
+/// CI-type compressed instructions.
+///
+///   15     12     11     6       1
+///   funct3 imm[5] rd/rs1 imm[4:0] op
+///       13     12      7        2  0
+///
+/// Encoding bits: `op | (funct3 << 13)`.
+fn put_ci<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, imm: i64, sink: &mut CS) {
+    let rd = rd & 0x1f;
+
+    debug_assert!(is_signed_int(imm, 6, 0), "CI out of range {:#x}", imm);
+    let imm = imm as u16;
+
+    let mut i = bits;
+    i |= ((imm >> 5) & 0x1) << 12;
+    i |= rd << 7;
+    i |= (imm & 0x1f) << 2;
+
+    sink.put2(i);
+}
+
+/// CA-type compressed instructions.
+///
+///   15     9        6      4    1
+///   funct6 rd'/rs1' funct2 rs2' op
+///       10        7      5    2  0
+///
+/// Encoding bits: `op | (funct2 << 5) | (funct6 << 10)`.
+fn put_ca<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, rs2: RegUnit, sink: &mut CS) {
+    let mut i = bits;
+    i |= creg(rd) << 7;
+    i |= creg(rs2) << 2;
+
+    sink.put2(i);
+}
+
+/// CB-type compressed instructions with a 6-bit immediate.
+///
+///   15     12     11     9        6        1
+///   funct3 imm[5] funct2 rd'/rs1' imm[4:0] op
+///       13     12     10        7        2  0
+///
+/// Encoding bits: `op | (funct2 << 10) | (funct3 << 13)`.
+fn put_cbi<CS: CodeSink + ?Sized>(bits: u16, rd: RegUnit, imm: i64, sink: &mut CS) {
+    debug_assert!(is_signed_int(imm, 6, 0), "CB out of range {:#x}", imm);
+    let imm = imm as u16;
+
+    let mut i = bits;
+    i |= ((imm >> 5) & 0x1) << 12;
+    i |= creg(rd) << 7;
+    i |= (imm & 0x1f) << 2;
+
+    sink.put2(i);
+}
+
+/// CB-type compressed branch instructions.
+///
+///   15     12  9    6   1
+///   funct3 imm rs1' imm op
+///       13  10    7   2  0
+///
+/// Encoding bits: `op | (funct3 << 13)`.
+fn put_cb<CS: CodeSink + ?Sized>(bits: u16, imm: i64, rs1: RegUnit, sink: &mut CS) {
+    debug_assert!(is_signed_int(imm, 9, 1), "CB out of range {:#x}", imm);
+    let imm = imm as u16;
+
+    let mut i = bits;
+    i |= creg(rs1) << 7;
+
+    // The displacement is completely hashed up.
+    i |= ((imm >> 8) & 0x1) << 12;
+    i |= ((imm >> 3) & 0x3) << 10;
+    i |= ((imm >> 6) & 0x3) << 5;
+    i |= ((imm >> 1) & 0x3) << 3;
+    i |= ((imm >> 5) & 0x1) << 2;
+
+    sink.put2(i);
+}
+
+/// CJ-type compressed jump instructions.
+///
+///   15     12  1
+///   funct3 imm op
+///       13   2  0
+///
+/// Encoding bits: `op | (funct3 << 13)`.
+fn put_cj<CS: CodeSink + ?Sized>(bits: u16, imm: i64, sink: &mut CS) {
+    debug_assert!(is_signed_int(imm, 12, 1), "CJ out of range {:#x}", imm);
+    let imm = imm as u16;
+
+    let mut i = bits;
+
+    // The displacement is completely hashed up.
+    i |= ((imm >> 11) & 0x1) << 12;
+    i |= ((imm >> 4) & 0x1) << 11;
+    i |= ((imm >> 8) & 0x3) << 9;
+    i |= ((imm >> 10) & 0x1) << 8;
+    i |= ((imm >> 6) & 0x1) << 7;
+    i |= ((imm >> 7) & 0x1) << 6;
+    i |= ((imm >> 1) & 0x7) << 3;
+    i |= ((imm >> 5) & 0x1) << 2;
+
+    sink.put2(i);
+}
+
+/// Add the `aq` and `rl` bits for `ordering` to the encoding bits of an AMO instruction.
+///
+/// The bits are the two low bits of funct7 in an R-type instruction.
+fn amo_bits(bits: u16, ordering: AtomicOrdering) -> u16 {
+    let mut bits = bits;
+    if ordering.is_acquire() {
+        bits |= 1 << 9;
+    }
+    if ordering.is_release() {
+        bits |= 1 << 8;
+    }
+    bits
+}
+
+/// Get the immediate operand of a `fence` instruction implementing `ordering`.
+///
+/// The immediate is `fm[3:0] | pred[3:0] | succ[3:0]` where the predecessor and successor sets
+/// are `i=8 o=4 r=2 w=1`. Device I/O doesn't need to be ordered.
+fn fence_imm(ordering: AtomicOrdering) -> i64 {
+    match ordering {
+        // fence r,rw
+        AtomicOrdering::Acquire => 0x023,
+        // fence rw,w
+        AtomicOrdering::Release => 0x031,
+        // fence.tso orders everything except earlier stores with later loads.
+        AtomicOrdering::AcqRel => 0x833,
+        // fence rw,rw
+        AtomicOrdering::SeqCst | AtomicOrdering::Relaxed => 0x033,
+    }
+}
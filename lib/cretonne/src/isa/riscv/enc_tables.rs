@@ -1,10 +1,12 @@
 //! Encoding tables for RISC-V.
 
-use ir;
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::{self, InstBuilderBase};
 use isa;
 use isa::constraints::*;
 use isa::enc_tables::*;
-use isa::encoding::RecipeSizing;
+use isa::encoding::{RecipeInfo, RecipeSizing, TextRange};
 use predicates;
 use super::registers::*;
 
@@ -16,3 +18,45 @@ use super::registers::*;
 // - `INFO`
 include!(concat!(env!("OUT_DIR"), "/encoding-riscv.rs"));
 include!(concat!(env!("OUT_DIR"), "/legalize-riscv.rs"));
+
+/// Expand the `sdiv`, `udiv`, `srem`, and `urem` instructions.
+///
+/// The RISC-V division instructions don't trap, so the division by zero and the `sdiv` overflow
+/// are checked explicitly. Without the "M" extension, the division becomes a library call.
+fn expand_divrem(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &isa::TargetIsa,
+) {
+    let (opcode, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("Need sdiv/udiv/srem/urem: {}", func.dfg.display_inst(inst, None)),
+    };
+    let riscv_opcode = match opcode {
+        ir::Opcode::Sdiv => ir::Opcode::RiscvDiv,
+        ir::Opcode::Udiv => ir::Opcode::RiscvDivu,
+        ir::Opcode::Srem => ir::Opcode::RiscvRem,
+        ir::Opcode::Urem => ir::Opcode::RiscvRemu,
+        _ => panic!("Need sdiv/udiv/srem/urem: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    ::legalizer::insert_divrem_traps(&mut pos, opcode, x, y);
+
+    // Check that the ISA flags enable the "M" extension.
+    let native = ir::InstructionData::Binary {
+        opcode: riscv_opcode,
+        args: [x, y],
+    };
+    if isa.encode(pos.func, &native, ty).is_err() {
+        let converted = ::legalizer::expand_as_libcall(inst, pos.func, isa);
+        debug_assert!(converted, "No library call for {}", opcode);
+        return;
+    }
+
+    pos.func.dfg.replace(inst).build(native, ty);
+}
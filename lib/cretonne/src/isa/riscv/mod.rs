@@ -7,19 +7,23 @@ mod enc_tables;
 mod registers;
 
 use super::super::settings as shared_settings;
-use binemit::{CodeSink, MemoryCodeSink, emit_function};
+use binemit::{CodeOffset, CodeSink, MemoryCodeSink, emit_function};
 use isa::enc_tables::{self as shared_enc_tables, lookup_enclist, Encodings};
 use isa::Builder as IsaBuilder;
-use isa::{TargetIsa, RegInfo, RegClass, EncInfo};
+use isa::{TargetIsa, RegInfo, RegClass, EncInfo, FrameConfig};
 use ir;
 use regalloc;
 use std::fmt;
 use std::boxed::Box;
+use std::hash::{Hash, Hasher};
+use std::vec::Vec;
 
 #[allow(dead_code)]
 struct Isa {
     shared_flags: shared_settings::Flags,
     isa_flags: settings::Flags,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
     cpumode: &'static [shared_enc_tables::Level1Entry<u16>],
 }
 
@@ -27,6 +31,8 @@ struct Isa {
 pub fn isa_builder() -> IsaBuilder {
     IsaBuilder {
         setup: settings::builder(),
+        frame: FrameConfig::default(),
+        libcall_names: ir::LibCallNames::new(),
         constructor: isa_constructor,
     }
 }
@@ -34,6 +40,8 @@ pub fn isa_builder() -> IsaBuilder {
 fn isa_constructor(
     shared_flags: shared_settings::Flags,
     builder: &shared_settings::Builder,
+    frame: FrameConfig,
+    libcall_names: ir::LibCallNames,
 ) -> Box<TargetIsa> {
     let level1 = if shared_flags.is_64bit() {
         &enc_tables::LEVEL1_RV64[..]
@@ -43,6 +51,8 @@ fn isa_constructor(
     Box::new(Isa {
         isa_flags: settings::Flags::new(&shared_flags, builder),
         shared_flags,
+        frame,
+        libcall_names,
         cpumode: level1,
     })
 }
@@ -56,6 +66,29 @@ impl TargetIsa for Isa {
         &self.shared_flags
     }
 
+    fn hash_flags(&self, mut state: &mut Hasher) {
+        self.name().hash(&mut state);
+        self.shared_flags.hash(&mut state);
+        self.isa_flags.hash(&mut state);
+    }
+
+    fn isa_settings(&self) -> Vec<shared_settings::Setting> {
+        self.isa_flags.settings()
+    }
+
+    fn soft_float(&self) -> bool {
+        // The ABI passes both `f32` and `f64` values in float registers.
+        !(self.isa_flags.use_f() && self.isa_flags.use_d())
+    }
+
+    fn frame_config(&self) -> &FrameConfig {
+        &self.frame
+    }
+
+    fn libcall_names(&self) -> &ir::LibCallNames {
+        &self.libcall_names
+    }
+
     fn register_info(&self) -> RegInfo {
         registers::INFO.clone()
     }
@@ -66,14 +99,14 @@ impl TargetIsa for Isa {
 
     fn legal_encodings<'a>(
         &'a self,
-        dfg: &'a ir::DataFlowGraph,
+        func: &'a ir::Function,
         inst: &'a ir::InstructionData,
         ctrl_typevar: ir::Type,
     ) -> Encodings<'a> {
         lookup_enclist(
             ctrl_typevar,
             inst,
-            dfg,
+            func,
             self.cpumode,
             &enc_tables::LEVEL2[..],
             &enc_tables::ENCLISTS[..],
@@ -106,16 +139,21 @@ impl TargetIsa for Isa {
         binemit::emit_inst(func, inst, divert, sink)
     }
 
+    fn emit_padding(&self, bytes: CodeOffset, sink: &mut CodeSink) {
+        binemit::emit_padding(bytes, sink)
+    }
+
     fn emit_function(&self, func: &ir::Function, sink: &mut MemoryCodeSink) {
-        emit_function(func, binemit::emit_inst, sink)
+        emit_function(func, binemit::emit_inst, binemit::emit_padding, sink)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use settings::{self, Configurable};
+    use cursor::{Cursor, EncCursor};
     use isa;
-    use ir::{DataFlowGraph, InstructionData, Opcode};
+    use ir::{Function, InstBuilder, InstructionData, Opcode};
     use ir::{types, immediates};
     use std::string::{String, ToString};
 
@@ -133,10 +171,10 @@ mod tests {
         let shared_flags = settings::Flags::new(&shared_builder);
         let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
 
-        let mut dfg = DataFlowGraph::new();
-        let ebb = dfg.make_ebb();
-        let arg64 = dfg.append_ebb_param(ebb, types::I64);
-        let arg32 = dfg.append_ebb_param(ebb, types::I32);
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let arg64 = func.dfg.append_ebb_param(ebb, types::I64);
+        let arg32 = func.dfg.append_ebb_param(ebb, types::I32);
 
         // Try to encode iadd_imm.i64 v1, -10.
         let inst64 = InstructionData::BinaryImm {
@@ -147,7 +185,7 @@ mod tests {
 
         // ADDI is I/0b00100
         assert_eq!(
-            encstr(&*isa, isa.encode(&dfg, &inst64, types::I64)),
+            encstr(&*isa, isa.encode(&func, &inst64, types::I64)),
             "Ii#04"
         );
 
@@ -159,7 +197,7 @@ mod tests {
         };
 
         // Immediate is out of range for ADDI.
-        assert!(isa.encode(&dfg, &inst64_large, types::I64).is_err());
+        assert!(isa.encode(&func, &inst64_large, types::I64).is_err());
 
         // Create an iadd_imm.i32 which is encodable in RV64.
         let inst32 = InstructionData::BinaryImm {
@@ -170,7 +208,7 @@ mod tests {
 
         // ADDIW is I/0b00110
         assert_eq!(
-            encstr(&*isa, isa.encode(&dfg, &inst32, types::I32)),
+            encstr(&*isa, isa.encode(&func, &inst32, types::I32)),
             "Ii#06"
         );
     }
@@ -183,10 +221,10 @@ mod tests {
         let shared_flags = settings::Flags::new(&shared_builder);
         let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
 
-        let mut dfg = DataFlowGraph::new();
-        let ebb = dfg.make_ebb();
-        let arg64 = dfg.append_ebb_param(ebb, types::I64);
-        let arg32 = dfg.append_ebb_param(ebb, types::I32);
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let arg64 = func.dfg.append_ebb_param(ebb, types::I64);
+        let arg32 = func.dfg.append_ebb_param(ebb, types::I32);
 
         // Try to encode iadd_imm.i64 v1, -10.
         let inst64 = InstructionData::BinaryImm {
@@ -196,7 +234,7 @@ mod tests {
         };
 
         // In 32-bit mode, an i64 bit add should be narrowed.
-        assert!(isa.encode(&dfg, &inst64, types::I64).is_err());
+        assert!(isa.encode(&func, &inst64, types::I64).is_err());
 
         // Try to encode iadd_imm.i64 v1, -10000.
         let inst64_large = InstructionData::BinaryImm {
@@ -206,7 +244,7 @@ mod tests {
         };
 
         // In 32-bit mode, an i64 bit add should be narrowed.
-        assert!(isa.encode(&dfg, &inst64_large, types::I64).is_err());
+        assert!(isa.encode(&func, &inst64_large, types::I64).is_err());
 
         // Create an iadd_imm.i32 which is encodable in RV32.
         let inst32 = InstructionData::BinaryImm {
@@ -217,7 +255,7 @@ mod tests {
 
         // ADDI is I/0b00100
         assert_eq!(
-            encstr(&*isa, isa.encode(&dfg, &inst32, types::I32)),
+            encstr(&*isa, isa.encode(&func, &inst32, types::I32)),
             "Ii#04"
         );
 
@@ -227,7 +265,11 @@ mod tests {
             args: [arg32, arg32],
         };
 
-        assert!(isa.encode(&dfg, &mul32, types::I32).is_err());
+        assert!(isa.encode(&func, &mul32, types::I32).is_err());
+        assert_eq!(
+            isa.explain_encoding(&func, &mul32, types::I32).to_string(),
+            "imul.i32 candidates: R#10c fails ISA predicate riscv.use_m"
+        );
     }
 
     #[test]
@@ -243,16 +285,103 @@ mod tests {
 
         let isa = isa_builder.finish(shared_flags);
 
-        let mut dfg = DataFlowGraph::new();
-        let ebb = dfg.make_ebb();
-        let arg32 = dfg.append_ebb_param(ebb, types::I32);
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let arg32 = func.dfg.append_ebb_param(ebb, types::I32);
 
         // Create an imul.i32 which is encodable in RV32M.
         let mul32 = InstructionData::Binary {
             opcode: Opcode::Imul,
             args: [arg32, arg32],
         };
-        assert_eq!(encstr(&*isa, isa.encode(&dfg, &mul32, types::I32)), "R#10c");
+        assert_eq!(encstr(&*isa, isa.encode(&func, &mul32, types::I32)), "R#10c");
+    }
+
+    #[test]
+    fn encode_batch() {
+        let shared_builder = settings::builder();
+        let shared_flags = settings::Flags::new(&shared_builder);
+        let isa = isa::lookup("riscv").unwrap().finish(shared_flags);
+
+        let mut func = Function::new();
+        let ebb = func.dfg.make_ebb();
+        let arg32 = func.dfg.append_ebb_param(ebb, types::I32);
+        func.layout.append_ebb(ebb);
+
+        let mut pos = EncCursor::new(&mut func, &*isa).at_bottom(ebb);
+        let v1 = pos.encode_batch(|pos| {
+            let v0 = pos.ins().iadd_imm(arg32, 10);
+            pos.ins().iadd_imm(v0, 20)
+        }).unwrap();
+        let i1 = pos.func.dfg.value_def(v1).unwrap_inst();
+        assert_eq!(pos.func.layout.last_inst(ebb), Some(i1));
+        assert_eq!(encstr(&*isa, Ok(pos.func.encodings[i1])), "Ii#04");
+
+        // The large immediate can't be encoded, so nothing is inserted.
+        assert!(
+            pos.encode_batch(|pos| {
+                let v2 = pos.ins().iadd_imm(v1, 1);
+                pos.ins().iadd_imm(v2, -10000);
+            }).is_none()
+        );
+        assert_eq!(pos.func.layout.last_inst(ebb), Some(i1));
+        assert_eq!(pos.func.layout.ebb_insts(ebb).count(), 2);
+
+        // Replaced instructions are restored.
+        assert!(
+            pos.encode_batch(|pos| { pos.batch_replace(i1).iadd_imm(arg32, -10000); })
+                .is_none()
+        );
+        assert_eq!(pos.display_inst(i1).to_string(), "v2 = iadd_imm.i32 v1, 20");
+    }
+
+    #[test]
+    fn isa_settings() {
+        assert!(isa::supported_isas().contains(&"riscv"));
+
+        let mut isa_builder = isa::lookup("riscv").unwrap();
+        isa_builder.enable("supports_m").unwrap();
+        let supports_m = |settings: &[settings::Setting]| {
+            settings
+                .iter()
+                .find(|s| s.name == "supports_m")
+                .cloned()
+                .unwrap()
+        };
+        let setting = supports_m(&isa_builder.settings());
+        assert_eq!(setting.kind, settings::SettingKind::Bool);
+        assert_eq!(setting.default, "false");
+        assert_eq!(setting.value, "true");
+
+        let isa = isa_builder.finish(settings::Flags::new(&settings::builder()));
+        assert_eq!(supports_m(&isa.isa_settings()).value, "true");
+    }
+
+    #[test]
+    fn shared_isa() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::hash::Hasher;
+        use std::thread;
+
+        let hash = |isa: &isa::TargetIsa| {
+            let mut state = DefaultHasher::new();
+            isa.hash_flags(&mut state);
+            state.finish()
+        };
+
+        let flags = settings::Flags::new(&settings::builder());
+        let isa = isa::lookup("riscv").unwrap().finish_shared(flags.clone());
+        let same = isa::lookup("riscv").unwrap().finish(flags.clone());
+        assert_eq!(hash(&*isa), hash(&*same));
+
+        let mut isa_builder = isa::lookup("riscv").unwrap();
+        isa_builder.enable("supports_m").unwrap();
+        let with_m = isa_builder.finish(flags);
+        assert_ne!(hash(&*isa), hash(&*with_m));
+
+        let shared = isa.clone();
+        let worker = thread::spawn(move || shared.flags().is_64bit());
+        assert_eq!(worker.join().unwrap(), isa.flags().is_64bit());
     }
 }
 
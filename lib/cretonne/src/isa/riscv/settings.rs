@@ -26,7 +26,9 @@ mod tests {
                     supports_a = false\n\
                     supports_f = false\n\
                     supports_d = false\n\
+                    supports_c = false\n\
                     enable_m = true\n\
+                    enable_c = true\n\
                     enable_e = false\n"
         );
         // Predicates are not part of the Display output.
@@ -50,5 +52,11 @@ mod tests {
         b.enable("supports_d").unwrap();
         let f = Flags::new(&shared, &b);
         assert_eq!(f.full_float(), false);
+
+        let mut b = builder();
+        b.enable("supports_c").unwrap();
+        assert_eq!(Flags::new(&shared, &b).use_c(), true);
+        b.set("enable_c", "false").unwrap();
+        assert_eq!(Flags::new(&shared, &b).use_c(), false);
     }
 }
@@ -0,0 +1,160 @@
+//! Target triples.
+//!
+//! Embedders often describe the target they are compiling for with a target triple like
+//! `x86_64-unknown-linux-gnu`, the format used by LLVM and rustc. Cretonne only needs the
+//! architecture part of the triple. It selects the ISA and a few settings that are implied by the
+//! architecture name.
+
+use isa::{lookup, Builder, LookupError};
+use settings::{self, Configurable};
+
+/// The ISA and settings implied by an architecture name.
+#[derive(Debug, PartialEq, Eq)]
+struct Arch<'a> {
+    /// Name of the ISA, as accepted by `isa::lookup()`.
+    isa: &'static str,
+    /// Is this a 64-bit architecture?
+    is_64bit: bool,
+    /// RISC-V extension letters following the base ISA letter.
+    extensions: &'a str,
+}
+
+/// Parse the architecture part of a target triple.
+fn parse_arch(arch: &str) -> Option<Arch> {
+    let simple = |isa, is_64bit| {
+        Some(Arch {
+            isa,
+            is_64bit,
+            extensions: "",
+        })
+    };
+    match arch {
+        "x86_64" | "amd64" => simple("intel", true),
+        "x86" | "i386" | "i486" | "i586" | "i686" => simple("intel", false),
+        "aarch64" | "arm64" => simple("arm64", true),
+        _ if arch.starts_with("arm") || arch.starts_with("thumb") => simple("arm32", false),
+        _ if arch.starts_with("riscv32") || arch.starts_with("riscv64") => {
+            Some(Arch {
+                isa: "riscv",
+                is_64bit: arch.starts_with("riscv64"),
+                extensions: &arch["riscv32".len()..],
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Apply the RISC-V extension letters in `extensions` to the settings builders.
+///
+/// Extensions that Cretonne doesn't model are ignored.
+fn riscv_extensions(
+    extensions: &str,
+    flag_builder: &mut settings::Builder,
+    isa_builder: &mut Builder,
+) -> settings::Result<()> {
+    for ext in extensions.chars() {
+        match ext {
+            'g' => {
+                for &setting in &["supports_m", "supports_a", "supports_f", "supports_d"] {
+                    isa_builder.enable(setting)?;
+                }
+            }
+            'm' => isa_builder.enable("supports_m")?,
+            'a' => isa_builder.enable("supports_a")?,
+            'f' => isa_builder.enable("supports_f")?,
+            'd' => isa_builder.enable("supports_d")?,
+            'e' => isa_builder.enable("enable_e")?,
+            'c' => flag_builder.enable("is_compressed")?,
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Look for a supported ISA that generates code for the target `triple`.
+///
+/// Only the architecture part of the triple is used. Besides selecting the ISA, it determines the
+/// `is_64bit` setting, and the extensions named in a RISC-V architecture like `riscv64gc`.
+///
+/// Return the shared settings builder along with the ISA builder so the settings can be refined
+/// before creating the `TargetIsa`.
+pub fn lookup_by_triple(triple: &str) -> Result<(settings::Builder, Builder), LookupError> {
+    let arch = triple.split('-').next().unwrap_or("");
+    let arch = parse_arch(arch).ok_or(LookupError::Unknown)?;
+
+    let mut flag_builder = settings::builder();
+    let mut isa_builder = lookup(arch.isa)?;
+    if arch.is_64bit {
+        flag_builder.enable("is_64bit").expect(
+            "shared settings have is_64bit",
+        );
+    }
+    if arch.isa == "riscv" {
+        riscv_extensions(arch.extensions, &mut flag_builder, &mut isa_builder)
+            .expect("RISC-V extension settings");
+    }
+    Ok((flag_builder, isa_builder))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_arch, lookup_by_triple, Arch};
+    use isa::LookupError;
+    use settings;
+
+    #[test]
+    fn arch() {
+        assert_eq!(
+            parse_arch("x86_64"),
+            Some(Arch {
+                isa: "intel",
+                is_64bit: true,
+                extensions: "",
+            })
+        );
+        assert_eq!(parse_arch("i686").map(|a| a.is_64bit), Some(false));
+        assert_eq!(parse_arch("armv7").map(|a| a.isa), Some("arm32"));
+        assert_eq!(parse_arch("thumbv7em").map(|a| a.isa), Some("arm32"));
+        assert_eq!(parse_arch("aarch64").map(|a| a.isa), Some("arm64"));
+        assert_eq!(
+            parse_arch("riscv64gc"),
+            Some(Arch {
+                isa: "riscv",
+                is_64bit: true,
+                extensions: "gc",
+            })
+        );
+        assert_eq!(parse_arch("riscv"), None);
+        assert_eq!(parse_arch("sparc"), None);
+    }
+
+    #[test]
+    fn unknown() {
+        assert_eq!(
+            lookup_by_triple("mips-unknown-linux-gnu").err(),
+            Some(LookupError::Unknown)
+        );
+        assert_eq!(lookup_by_triple("").err(), Some(LookupError::Unknown));
+    }
+
+    #[test]
+    #[cfg(build_riscv)]
+    fn riscv() {
+        let (flag_builder, isa_builder) = lookup_by_triple("riscv32imc-unknown-none-elf").unwrap();
+        let value = |settings: &[settings::Setting], name| {
+            settings
+                .iter()
+                .find(|s| s.name == name)
+                .map(|s| s.value.clone())
+                .unwrap()
+        };
+
+        let isa_settings = isa_builder.settings();
+        assert_eq!(value(&isa_settings, "supports_m"), "true");
+        assert_eq!(value(&isa_settings, "supports_a"), "false");
+
+        let flags = settings::Flags::new(&flag_builder);
+        assert!(!flags.is_64bit());
+        assert!(flags.is_compressed());
+    }
+}
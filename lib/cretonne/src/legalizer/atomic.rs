@@ -0,0 +1,147 @@
+//! Legalization of atomic memory operations.
+//!
+//! Targets provide encodings for the atomic instructions that map directly to their hardware, and
+//! the expansions here cover the rest:
+//!
+//! - Memory orderings that the target can't encode on a load or store are implemented with
+//!   explicit `fence` instructions around a relaxed access. This uses the leading-fence mapping
+//!   where sequentially consistent loads are preceded by a full fence.
+//! - A relaxed access without an encoding becomes a normal load or store. Aligned accesses that
+//!   fit in a register are single-copy atomic on all supported targets.
+//! - Read-modify-write operations without an encoding become a compare-and-swap loop.
+
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::{self, AtomicOrdering, AtomicRmwOp, InstBuilder};
+use ir::condcodes::IntCC;
+use isa::TargetIsa;
+
+/// Expand an `atomic_load` instruction.
+pub fn expand_atomic_load(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let (ordering, flags, addr) = match func.dfg[inst] {
+        ir::InstructionData::AtomicLoad {
+            opcode: ir::Opcode::AtomicLoad,
+            ordering,
+            flags,
+            arg,
+        } => (ordering, flags, arg),
+        _ => panic!("Expected atomic_load: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    if ordering == AtomicOrdering::Relaxed {
+        pos.func.dfg.replace(inst).load(ty, flags, addr, 0);
+        return;
+    }
+
+    if ordering == AtomicOrdering::SeqCst {
+        pos.ins().fence(AtomicOrdering::SeqCst);
+    }
+    pos.func.dfg.replace(inst).atomic_load(ty, AtomicOrdering::Relaxed, flags, addr);
+    pos.goto_after_inst(inst);
+    pos.ins().fence(AtomicOrdering::Acquire);
+}
+
+/// Expand an `atomic_store` instruction.
+pub fn expand_atomic_store(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let (ordering, flags, arg, addr) = match func.dfg[inst] {
+        ir::InstructionData::AtomicStore {
+            opcode: ir::Opcode::AtomicStore,
+            ordering,
+            flags,
+            args,
+        } => (ordering, flags, args[0], args[1]),
+        _ => panic!("Expected atomic_store: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    if ordering == AtomicOrdering::Relaxed {
+        pos.func.dfg.replace(inst).store(flags, arg, addr, 0);
+        return;
+    }
+
+    // Sequentially consistent loads have a leading full fence, so a release fence is enough for
+    // both orderings here.
+    pos.ins().fence(AtomicOrdering::Release);
+    pos.func.dfg.replace(inst).atomic_store(AtomicOrdering::Relaxed, flags, arg, addr);
+}
+
+/// Expand an `atomic_rmw` instruction.
+pub fn expand_atomic_rmw(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let (op, ordering, flags, addr, arg) = match func.dfg[inst] {
+        ir::InstructionData::AtomicRmw {
+            opcode: ir::Opcode::AtomicRmw,
+            op,
+            ordering,
+            flags,
+            args,
+        } => (op, ordering, flags, args[0], args[1]),
+        _ => panic!("Expected atomic_rmw: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+
+    let old_ebb = func.layout.pp_ebb(inst);
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Subtraction is addition of the negated operand.
+    if op == AtomicRmwOp::Sub {
+        let neg = pos.ins().irsub_imm(arg, 0);
+        pos.func.dfg.replace(inst).atomic_rmw(AtomicRmwOp::Add, ordering, flags, addr, neg);
+        return;
+    }
+
+    // Replace `a = atomic_rmw op ordering p, x` with a compare-and-swap loop:
+    //
+    //     v0 = atomic_load relaxed p
+    //     jump loop_ebb(v0)
+    //   loop_ebb(old):
+    //     new = op old, x
+    //     a = atomic_cas ordering p, old, new
+    //     retry = icmp ne a, old
+    //     brnz retry, loop_ebb(a)
+    //
+    // The initial load doesn't need to be ordered since the `atomic_cas` validates it.
+    let loop_ebb = pos.func.dfg.make_ebb();
+    let old = pos.func.dfg.append_ebb_param(loop_ebb, ty);
+    let initial = pos.ins().atomic_load(ty, AtomicOrdering::Relaxed, flags, addr);
+    pos.ins().jump(loop_ebb, &[initial]);
+    pos.insert_ebb(loop_ebb);
+
+    let new = match op {
+        AtomicRmwOp::Add => pos.ins().iadd(old, arg),
+        AtomicRmwOp::And => pos.ins().band(old, arg),
+        AtomicRmwOp::Or => pos.ins().bor(old, arg),
+        AtomicRmwOp::Xor => pos.ins().bxor(old, arg),
+        AtomicRmwOp::Xchg => arg,
+        AtomicRmwOp::Sub => unreachable!(),
+    };
+    pos.func.dfg.replace(inst).atomic_cas(ordering, flags, addr, old, new);
+    let result = pos.func.dfg.first_result(inst);
+    pos.goto_after_inst(inst);
+    let retry = pos.ins().icmp(IntCC::NotEqual, result, old);
+    pos.ins().brnz(retry, loop_ebb, &[result]);
+
+    cfg.recompute_ebb(pos.func, old_ebb);
+    cfg.recompute_ebb(pos.func, loop_ebb);
+}
@@ -46,6 +46,18 @@ pub fn legalize_signatures(func: &mut Function, isa: &TargetIsa) {
     }
 }
 
+/// Legalize the signatures that were imported into `func` after `legalize_signatures` ran.
+///
+/// Signatures that haven't been legalized are recognized by their missing `argument_bytes`.
+pub fn legalize_new_signatures(func: &mut Function, isa: &TargetIsa) {
+    for sig in func.dfg.signatures.keys() {
+        if func.dfg.signatures[sig].argument_bytes.is_none() {
+            isa.legalize_signature(&mut func.dfg.signatures[sig], false);
+            func.dfg.signatures[sig].compute_argument_bytes();
+        }
+    }
+}
+
 /// Legalize the entry block parameters after `func`'s signature has been legalized.
 ///
 /// The legalized signature may contain more parameters than the original signature, and the
@@ -0,0 +1,225 @@
+//! Legalization of bulk memory operations.
+//!
+//! The `memcpy`, `memmove`, and `memset` instructions with a small constant length are expanded
+//! into a sequence of loads and stores using the widest integer accesses available. All the
+//! loads of a copy are emitted before the stores, so the same expansion works for overlapping
+//! regions. Other lengths are expanded as calls to the corresponding library routines.
+//!
+//! When `opt_level` is `min_size`, only expansions that are smaller than a call are inlined.
+
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::{self, InstBuilder, MemFlags};
+use ir::types::{I32, I64};
+use isa::TargetIsa;
+use settings::OptLevel;
+use std::vec::Vec;
+use super::libcall::get_libcall_funcref;
+
+/// The largest number of stores in an inline expansion.
+const MAX_INLINE_STORES: usize = 8;
+
+/// The largest number of stores in an inline expansion when optimizing for size.
+const MAX_INLINE_STORES_MIN_SIZE: usize = 2;
+
+/// Expand a `memcpy`, `memmove`, or `memset` instruction.
+pub fn expand_bulk_memory(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    let (opcode, flags, dest, arg, len) = match func.dfg[inst] {
+        ir::InstructionData::BulkMemory {
+            opcode,
+            flags,
+            args,
+        } => (opcode, flags, args[0], args[1], args[2]),
+        _ => panic!("Expected bulk memory op: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let word = if isa.flags().is_64bit() { 8 } else { 4 };
+    let max_stores = if isa.flags().opt_level() == OptLevel::MinSize {
+        MAX_INLINE_STORES_MIN_SIZE
+    } else {
+        MAX_INLINE_STORES
+    };
+    let chunks = get_const(len, func).and_then(|len| chunk_sizes(len as u64, word, max_stores));
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let chunks = match chunks {
+        Some(chunks) => chunks,
+        None => return expand_as_call(inst, opcode, dest, arg, len, &mut pos, isa),
+    };
+
+    // The expanded accesses have no alignment guarantees.
+    let mut access_flags = MemFlags::new();
+    if flags.notrap() {
+        access_flags.set_notrap();
+    }
+
+    if opcode == ir::Opcode::Memset {
+        let mut fill = Fill::new(arg, pos.func);
+        let mut offset = 0;
+        for size in chunks {
+            let val = fill.value(size, &mut pos);
+            store(size, access_flags, val, dest, offset, &mut pos);
+            offset += size as i32;
+        }
+    } else {
+        let mut values = Vec::with_capacity(chunks.len());
+        let mut offset = 0;
+        for &size in &chunks {
+            values.push((size, offset, load(size, access_flags, arg, offset, &mut pos)));
+            offset += size as i32;
+        }
+        for (size, offset, val) in values {
+            store(size, access_flags, val, dest, offset, &mut pos);
+        }
+    }
+
+    pos.remove_inst();
+}
+
+/// Replace `inst` with a call to the library routine implementing it.
+fn expand_as_call(
+    inst: ir::Inst,
+    opcode: ir::Opcode,
+    dest: ir::Value,
+    arg: ir::Value,
+    len: ir::Value,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+) {
+    let addr_ty = pos.func.dfg.value_type(dest);
+    let (libcall, arg, arg_ty) = match opcode {
+        ir::Opcode::Memcpy => (ir::LibCall::Memcpy, arg, addr_ty),
+        ir::Opcode::Memmove => (ir::LibCall::Memmove, arg, addr_ty),
+        ir::Opcode::Memset => (ir::LibCall::Memset, pos.ins().uextend(I32, arg), I32),
+        _ => panic!("Unexpected bulk memory op {}", opcode),
+    };
+    let funcref = get_libcall_funcref(libcall, &[addr_ty, arg_ty, addr_ty], &[], pos.func, isa);
+    pos.func.dfg.replace(inst).call(funcref, &[dest, arg, len]);
+}
+
+/// Get the value of `value` if it is defined by an `iconst` instruction.
+fn get_const(value: ir::Value, func: &ir::Function) -> Option<i64> {
+    if let ir::ValueDef::Result(inst, 0) = func.dfg.value_def(value) {
+        if let ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } = func.dfg[inst]
+        {
+            return Some(imm.into());
+        }
+    }
+    None
+}
+
+/// Split `len` bytes into access sizes of at most `word` bytes, largest first.
+///
+/// Returns `None` if that would take more than `max_stores` accesses.
+fn chunk_sizes(len: u64, word: u64, max_stores: usize) -> Option<Vec<u64>> {
+    let mut chunks = Vec::new();
+    let mut remaining = len;
+    let mut size = word;
+    while remaining > 0 {
+        if chunks.len() == max_stores {
+            return None;
+        }
+        while size > remaining {
+            size /= 2;
+        }
+        chunks.push(size);
+        remaining -= size;
+    }
+    Some(chunks)
+}
+
+/// Load `size` bytes from `addr + offset`.
+fn load(
+    size: u64,
+    flags: MemFlags,
+    addr: ir::Value,
+    offset: i32,
+    pos: &mut FuncCursor,
+) -> ir::Value {
+    match size {
+        8 => pos.ins().load(I64, flags, addr, offset),
+        4 => pos.ins().load(I32, flags, addr, offset),
+        2 => pos.ins().uload16(I32, flags, addr, offset),
+        _ => pos.ins().uload8(I32, flags, addr, offset),
+    }
+}
+
+/// Store the low `size` bytes of `val` to `addr + offset`.
+fn store(
+    size: u64,
+    flags: MemFlags,
+    val: ir::Value,
+    addr: ir::Value,
+    offset: i32,
+    pos: &mut FuncCursor,
+) {
+    match size {
+        8 | 4 => pos.ins().store(flags, val, addr, offset),
+        2 => pos.ins().istore16(flags, val, addr, offset),
+        _ => pos.ins().istore8(flags, val, addr, offset),
+    };
+}
+
+/// The values stored by an expanded `memset`.
+///
+/// The values repeat the fill byte in every byte. They are computed when the first store of each
+/// width needs them.
+struct Fill {
+    byte: ir::Value,
+    constant: Option<u8>,
+    narrow: Option<ir::Value>,
+    wide: Option<ir::Value>,
+}
+
+impl Fill {
+    fn new(byte: ir::Value, func: &ir::Function) -> Self {
+        Self {
+            byte,
+            constant: get_const(byte, func).map(|c| c as u8),
+            narrow: None,
+            wide: None,
+        }
+    }
+
+    /// Get the value to store for an access of `size` bytes.
+    fn value(&mut self, size: u64, pos: &mut FuncCursor) -> ir::Value {
+        let byte = self.byte;
+        let constant = self.constant;
+        let (ty, cache) = if size == 8 {
+            (I64, &mut self.wide)
+        } else {
+            (I32, &mut self.narrow)
+        };
+        if let Some(val) = *cache {
+            return val;
+        }
+        let val = match constant {
+            Some(c) => pos.ins().iconst(ty, splat(c, ty)),
+            None => {
+                let ext = pos.ins().uextend(ty, byte);
+                pos.ins().imul_imm(ext, splat(1, ty))
+            }
+        };
+        *cache = Some(val);
+        val
+    }
+}
+
+/// Repeat `byte` in every byte of an integer of type `ty`, as an immediate operand.
+fn splat(byte: u8, ty: ir::Type) -> i64 {
+    if ty == I64 {
+        (u64::from(byte) * 0x0101_0101_0101_0101) as i64
+    } else {
+        i64::from((u32::from(byte) * 0x0101_0101) as i32)
+    }
+}
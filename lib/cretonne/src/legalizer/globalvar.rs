@@ -27,7 +27,12 @@ pub fn expand_global_addr(
     match func.global_vars[gv] {
         ir::GlobalVarData::VmCtx { offset } => vmctx_addr(inst, func, offset.into()),
         ir::GlobalVarData::Deref { base, offset } => deref_addr(inst, func, base, offset.into()),
+        ir::GlobalVarData::HeapBase { heap, offset } => {
+            heap_base_addr(inst, func, heap, offset.into())
+        }
         ir::GlobalVarData::Sym { .. } => globalsym(inst, func, gv),
+        ir::GlobalVarData::GotSym { .. } => gotsym(inst, func, gv),
+        ir::GlobalVarData::TlsSym { .. } => tlssym(inst, func, gv),
     }
 }
 
@@ -57,8 +62,30 @@ fn deref_addr(inst: ir::Inst, func: &mut ir::Function, base: ir::GlobalVar, offs
     pos.func.dfg.replace(inst).iadd_imm(base_ptr, offset);
 }
 
+/// Expand a `global_addr` instruction for a global at an offset from a heap base.
+fn heap_base_addr(inst: ir::Inst, func: &mut ir::Function, heap: ir::Heap, offset: i64) {
+    // The heap base address is loaded from the heap's base global variable, just like a `deref`
+    // global.
+    match func.heaps[heap].base {
+        ir::HeapBase::ReservedReg => unimplemented!(),
+        ir::HeapBase::GlobalVar(base) => deref_addr(inst, func, base, offset),
+    }
+}
+
 /// Expand a `global_addr` instruction for a symbolic name global.
 fn globalsym(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalVar) {
     let ptr_ty = func.dfg.value_type(func.dfg.first_result(inst));
     func.dfg.replace(inst).globalsym_addr(ptr_ty, gv);
 }
+
+/// Expand a `global_addr` instruction for a global offset table symbol.
+fn gotsym(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalVar) {
+    let ptr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+    func.dfg.replace(inst).gotsym_addr(ptr_ty, gv);
+}
+
+/// Expand a `global_addr` instruction for a thread-local symbol.
+fn tlssym(inst: ir::Inst, func: &mut ir::Function, gv: ir::GlobalVar) {
+    let ptr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+    func.dfg.replace(inst).tlssym_addr(ptr_ty, gv);
+}
@@ -14,7 +14,7 @@ pub fn expand_heap_addr(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
-    _isa: &TargetIsa,
+    isa: &TargetIsa,
 ) {
     // Unpack the instruction.
     let (heap, offset, size) = match func.dfg[inst] {
@@ -30,12 +30,13 @@ pub fn expand_heap_addr(
         _ => panic!("Wanted heap_addr: {}", func.dfg.display_inst(inst, None)),
     };
 
+    let spectre = isa.flags().enable_heap_access_spectre_mitigation();
     match func.heaps[heap].style {
         ir::HeapStyle::Dynamic { bound_gv } => {
-            dynamic_addr(inst, heap, offset, size, bound_gv, func)
+            dynamic_addr(inst, heap, offset, size, bound_gv, spectre, func)
         }
         ir::HeapStyle::Static { bound } => {
-            static_addr(inst, heap, offset, size, bound.into(), func, cfg)
+            static_addr(inst, heap, offset, size, bound.into(), spectre, func, cfg)
         }
     }
 }
@@ -47,6 +48,7 @@ fn dynamic_addr(
     offset: ir::Value,
     size: u32,
     bound_gv: ir::GlobalVar,
+    spectre: bool,
     func: &mut ir::Function,
 ) {
     let size = i64::from(size);
@@ -60,37 +62,28 @@ fn dynamic_addr(
     let bound_addr = pos.ins().global_addr(addr_ty, bound_gv);
     let bound = pos.ins().load(offset_ty, MemFlags::new(), bound_addr, 0);
 
-    let oob;
+    let oob_cmp;
     if size == 1 {
         // `offset > bound - 1` is the same as `offset >= bound`.
-        oob = pos.ins().icmp(
-            IntCC::UnsignedGreaterThanOrEqual,
-            offset,
-            bound,
-        );
+        oob_cmp = (IntCC::UnsignedGreaterThanOrEqual, offset, bound);
     } else if size <= min_size {
         // We know that bound >= min_size, so here we can compare `offset > bound - size` without
         // wrapping.
         let adj_bound = pos.ins().iadd_imm(bound, -size);
-        oob = pos.ins().icmp(
-            IntCC::UnsignedGreaterThan,
-            offset,
-            adj_bound,
-        );
+        oob_cmp = (IntCC::UnsignedGreaterThan, offset, adj_bound);
     } else {
         // We need an overflow check for the adjusted offset.
         let size_val = pos.ins().iconst(offset_ty, size);
         let (adj_offset, overflow) = pos.ins().iadd_cout(offset, size_val);
         pos.ins().trapnz(overflow, ir::TrapCode::HeapOutOfBounds);
-        oob = pos.ins().icmp(
-            IntCC::UnsignedGreaterThan,
-            adj_offset,
-            bound,
-        );
+        oob_cmp = (IntCC::UnsignedGreaterThan, adj_offset, bound);
     }
+    let (cc, lhs, rhs) = oob_cmp;
+    let oob = pos.ins().icmp(cc, lhs, rhs);
     pos.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
 
-    offset_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+    let clamp = if spectre { Some(oob_cmp) } else { None };
+    offset_addr(inst, heap, addr_ty, offset, offset_ty, clamp, pos.func);
 }
 
 /// Expand a `heap_addr` for a static heap.
@@ -100,6 +93,7 @@ fn static_addr(
     offset: ir::Value,
     size: u32,
     bound: i64,
+    spectre: bool,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
 ) {
@@ -129,37 +123,41 @@ fn static_addr(
 
     // We may be able to omit the check entirely for 32-bit offsets if the heap bound is 4 GB or
     // more.
+    let mut clamp = None;
     if offset_ty != ir::types::I32 || limit < 0xffff_ffff {
-        let oob = if limit & 1 == 1 {
+        let (cc, imm) = if limit & 1 == 1 {
             // Prefer testing `offset >= limit - 1` when limit is odd because an even number is
             // likely to be a convenient constant on ARM and other RISC architectures.
-            pos.ins().icmp_imm(
-                IntCC::UnsignedGreaterThanOrEqual,
-                offset,
-                limit - 1,
-            )
+            (IntCC::UnsignedGreaterThanOrEqual, limit - 1)
         } else {
-            pos.ins().icmp_imm(
-                IntCC::UnsignedGreaterThan,
-                offset,
-                limit,
-            )
+            (IntCC::UnsignedGreaterThan, limit)
+        };
+        let oob = if spectre {
+            // Materialize the limit once for both comparisons.
+            let limit_val = pos.ins().iconst(offset_ty, imm);
+            clamp = Some((cc, offset, limit_val));
+            pos.ins().icmp(cc, offset, limit_val)
+        } else {
+            pos.ins().icmp_imm(cc, offset, imm)
         };
         pos.ins().trapnz(oob, ir::TrapCode::HeapOutOfBounds);
     }
 
-    offset_addr(inst, heap, addr_ty, offset, offset_ty, pos.func);
+    offset_addr(inst, heap, addr_ty, offset, offset_ty, clamp, pos.func);
 }
 
 /// Emit code for the base address computation of a `heap_addr` instruction.
 ///
-///
+/// If `clamp` is a comparison `(cc, lhs, rhs)` that is true when the access is out of bounds,
+/// the address is replaced by null with a conditional move when the comparison holds. The bounds
+/// check has already trapped in that case, but the CPU may speculate past the trapping branch.
 fn offset_addr(
     inst: ir::Inst,
     heap: ir::Heap,
     addr_ty: ir::Type,
     mut offset: ir::Value,
     offset_ty: ir::Type,
+    clamp: Option<(IntCC, ir::Value, ir::Value)>,
     func: &mut ir::Function,
 ) {
     let mut pos = FuncCursor::new(func).at_inst(inst);
@@ -171,12 +169,29 @@ fn offset_addr(
     }
 
     // Add the heap base address base
-    match pos.func.heaps[heap].base {
+    let base = match pos.func.heaps[heap].base {
         ir::HeapBase::ReservedReg => unimplemented!(),
         ir::HeapBase::GlobalVar(base_gv) => {
             let base_addr = pos.ins().global_addr(addr_ty, base_gv);
-            let base = pos.ins().load(addr_ty, MemFlags::new(), base_addr, 0);
+            pos.ins().load(addr_ty, MemFlags::new(), base_addr, 0)
+        }
+    };
+
+    match clamp {
+        None => {
             pos.func.dfg.replace(inst).iadd(base, offset);
         }
+        Some((cc, lhs, rhs)) => {
+            let addr = pos.ins().iadd(base, offset);
+            let null = pos.ins().iconst(addr_ty, 0);
+            let flags = pos.ins().ifcmp(lhs, rhs);
+            pos.func.dfg.replace(inst).selectif(
+                addr_ty,
+                cc,
+                flags,
+                null,
+                addr,
+            );
+        }
     }
 }
@@ -2,9 +2,14 @@
 
 use ir;
 use ir::InstBuilder;
+use isa::TargetIsa;
+use std::vec::Vec;
 
 /// Try to expand `inst` as a library call, returning true is successful.
-pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function) -> bool {
+///
+/// The symbol name and calling convention of the library routine are determined by the ISA's
+/// `LibCallNames`.
+pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function, isa: &TargetIsa) -> bool {
     // Does the opcode/ctrl_type combo even have a well-known runtime library name.
     let libcall =
         match ir::LibCall::for_inst(func.dfg[inst].opcode(), func.dfg.ctrl_typevar(inst)) {
@@ -12,50 +17,79 @@ pub fn expand_as_libcall(inst: ir::Inst, func: &mut ir::Function) -> bool {
             None => return false,
         };
 
-    let funcref = find_funcref(libcall, func).unwrap_or_else(|| make_funcref(libcall, inst, func));
+    let params: Vec<_> = func.dfg
+        .inst_args(inst)
+        .iter()
+        .map(|&v| func.dfg.value_type(v))
+        .collect();
+    let returns: Vec<_> = func.dfg
+        .inst_results(inst)
+        .iter()
+        .map(|&v| func.dfg.value_type(v))
+        .collect();
+    let funcref = get_libcall_funcref(libcall, &params, &returns, func, isa);
 
     // Now we convert `inst` to a call. First save the arguments.
     let mut args = Vec::new();
     args.extend_from_slice(func.dfg.inst_args(inst));
-    // The replace builder will preserve the instruction result values.
+    // The replace builder will preserve the instruction result values. The arguments and return
+    // values are converted to the legalized signature when the legalizer revisits the call.
     func.dfg.replace(inst).call(funcref, &args);
 
-    // TODO: ask the ISA to legalize the signature.
-
     true
 }
 
-/// Get the existing function reference for `libcall` in `func` if it exists.
-fn find_funcref(libcall: ir::LibCall, func: &ir::Function) -> Option<ir::FuncRef> {
-    // We're assuming that all libcall function decls are at the end.
-    // If we get this wrong, worst case we'll have duplicate libcall decls which is harmless.
-    for fref in func.dfg.ext_funcs.keys().rev() {
-        match func.dfg.ext_funcs[fref].name {
-            ir::ExternalName::LibCall(lc) => {
-                if lc == libcall {
-                    return Some(fref);
-                }
-            }
-            _ => break,
-        }
-    }
-    None
+/// Get the existing function reference for `name` in `func` if it exists.
+fn find_funcref(name: &ir::ExternalName, func: &ir::Function) -> Option<ir::FuncRef> {
+    // Library call declarations are usually at the end, so search backwards.
+    func.dfg.ext_funcs.keys().rev().find(|&fref| {
+        func.dfg.ext_funcs[fref].name == *name
+    })
+}
+
+/// Get a function reference for calling `libcall` with the given parameter and return types.
+///
+/// The function is imported with a signature legalized for `isa` if `func` doesn't already refer
+/// to it.
+pub fn get_libcall_funcref(
+    libcall: ir::LibCall,
+    params: &[ir::Type],
+    returns: &[ir::Type],
+    func: &mut ir::Function,
+    isa: &TargetIsa,
+) -> ir::FuncRef {
+    let names = isa.libcall_names();
+    let name = names.name(libcall);
+    find_funcref(&name, func).unwrap_or_else(|| {
+        make_funcref(name, names.call_conv(libcall), params, returns, func, isa)
+    })
 }
 
-/// Create a funcref for `libcall` with a signature matching `inst`.
-fn make_funcref(libcall: ir::LibCall, inst: ir::Inst, func: &mut ir::Function) -> ir::FuncRef {
-    // Start with a native calling convention. We'll give the ISA a chance to change it.
-    let mut sig = ir::Signature::new(ir::CallConv::Native);
-    for &v in func.dfg.inst_args(inst) {
-        sig.params.push(ir::AbiParam::new(func.dfg.value_type(v)));
+/// Create a funcref for `name` with the given parameter and return types, legalized for `isa`.
+fn make_funcref(
+    name: ir::ExternalName,
+    call_conv: ir::CallConv,
+    params: &[ir::Type],
+    returns: &[ir::Type],
+    func: &mut ir::Function,
+    isa: &TargetIsa,
+) -> ir::FuncRef {
+    let mut sig = ir::Signature::new(call_conv);
+    for &ty in params {
+        sig.params.push(ir::AbiParam::new(ty));
     }
-    for &v in func.dfg.inst_results(inst) {
-        sig.returns.push(ir::AbiParam::new(func.dfg.value_type(v)));
+    for &ty in returns {
+        sig.returns.push(ir::AbiParam::new(ty));
     }
+    isa.legalize_signature(&mut sig, false);
+    sig.compute_argument_bytes();
     let sigref = func.import_signature(sig);
 
     func.import_function(ir::ExtFuncData {
-        name: ir::ExternalName::LibCall(libcall),
+        name,
         signature: sigref,
+        noreturn: false,
+        cold: false,
+        colocated: false,
     })
 }
@@ -16,19 +16,74 @@
 use cursor::{Cursor, FuncCursor};
 use flowgraph::ControlFlowGraph;
 use ir::{self, InstBuilder};
-use isa::TargetIsa;
+use isa::{Legalize, TargetIsa};
 use bitset::BitSet;
+use predicates;
 use timing;
+use std::vec::Vec;
 
+mod atomic;
 mod boundary;
+mod bulkmem;
 mod globalvar;
 mod heap;
 mod libcall;
-mod split;
+mod narrow;
+mod softfloat;
+pub mod split;
+mod table;
 
+use self::atomic::{expand_atomic_load, expand_atomic_store, expand_atomic_rmw};
+use self::bulkmem::expand_bulk_memory;
 use self::globalvar::expand_global_addr;
 use self::heap::expand_heap_addr;
-use self::libcall::expand_as_libcall;
+use self::table::expand_table_addr;
+pub use self::libcall::expand_as_libcall;
+pub use self::narrow::{narrow_iconst, narrow_branch, narrow_load, narrow_store, narrow_divrem};
+
+/// Legalization actions registered by the embedder for specific opcodes.
+///
+/// A custom action is tried before the ISA's encodings and built-in legalization actions, so it
+/// can also replace instructions that would otherwise be legal. This makes it possible to lower
+/// instructions like `fma` to calls to runtime-specific helpers without changing the generated
+/// legalizer tables.
+///
+/// Like the built-in actions, a custom action returns `true` if it changed the function, and the
+/// legalizer then revisits the replacement instructions. It must not produce instructions that it
+/// would expand again. Signatures imported by the action for new calls are legalized for the ISA.
+/// An action that returns `false` declines to handle the instruction, which is then legalized as
+/// usual.
+#[derive(Clone, Default)]
+pub struct CustomLegalizations {
+    actions: Vec<(ir::Opcode, Legalize)>,
+}
+
+impl CustomLegalizations {
+    /// Create an empty set of custom legalizations.
+    pub fn new() -> Self {
+        Self { actions: Vec::new() }
+    }
+
+    /// Use `action` to legalize instructions with `opcode`.
+    ///
+    /// This replaces any action registered for `opcode` before.
+    pub fn register(&mut self, opcode: ir::Opcode, action: Legalize) {
+        self.actions.retain(|&(op, _)| op != opcode);
+        self.actions.push((opcode, action));
+    }
+
+    /// Get the action registered for `opcode`, if any.
+    pub fn get(&self, opcode: ir::Opcode) -> Option<Legalize> {
+        self.actions.iter().find(|&&(op, _)| op == opcode).map(
+            |&(_, action)| action,
+        )
+    }
+
+    /// Are there no custom legalizations?
+    pub fn is_empty(&self) -> bool {
+        self.actions.is_empty()
+    }
+}
 
 /// Legalize `func` for `isa`.
 ///
@@ -36,9 +91,24 @@ use self::libcall::expand_as_libcall;
 /// - Fill out `func.encodings`.
 ///
 pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, isa: &TargetIsa) {
+    legalize_function_with(func, cfg, isa, &CustomLegalizations::new())
+}
+
+/// Legalize `func` for `isa`, using the `custom` legalizations in preference to the built-in
+/// ones.
+pub fn legalize_function_with(
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+    custom: &CustomLegalizations,
+) {
     let _tt = timing::legalize();
     debug_assert!(cfg.is_valid());
 
+    if isa.soft_float() {
+        softfloat::lower_floats(func, isa);
+    }
+
     boundary::legalize_signatures(func, isa);
 
     func.encodings.resize(func.dfg.num_insts());
@@ -55,6 +125,10 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
         while let Some(inst) = pos.next_inst() {
             let opcode = pos.func.dfg[inst].opcode();
 
+            if opcode.is_call() {
+                terminate_noreturn_call(inst, &mut pos, cfg);
+            }
+
             // Check for ABI boundaries that need to be converted to the legalized signature.
             if opcode.is_call() && boundary::handle_call_abi(inst, pos.func, cfg) {
                 // Go back and legalize the inserted argument conversion instructions.
@@ -72,8 +146,17 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
                 split::simplify_branch_arguments(&mut pos.func.dfg, inst);
             }
 
+            if let Some(action) = custom.get(opcode) {
+                if action(inst, pos.func, cfg, isa) {
+                    // The action may have imported signatures for new calls.
+                    boundary::legalize_new_signatures(pos.func, isa);
+                    pos.set_position(prev_pos);
+                    continue;
+                }
+            }
+
             match isa.encode(
-                &pos.func.dfg,
+                &pos.func,
                 &pos.func.dfg[inst],
                 pos.func.dfg.ctrl_typevar(inst),
             ) {
@@ -93,7 +176,7 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
 
                     // We don't have any pattern expansion for this instruction either.
                     // Try converting it to a library call as a last resort.
-                    if expand_as_libcall(inst, pos.func) {
+                    if expand_as_libcall(inst, pos.func, isa) {
                         pos.set_position(prev_pos);
                         continue;
                     }
@@ -106,6 +189,36 @@ pub fn legalize_function(func: &mut ir::Function, cfg: &mut ControlFlowGraph, is
     }
 }
 
+/// Terminate the EBB after a call to a function that never returns.
+///
+/// The CFG doesn't consider any branches after such a call. The instructions following the call
+/// are moved to a new unreachable EBB, and a trap is inserted after the call.
+fn terminate_noreturn_call(
+    inst: ir::Inst,
+    pos: &mut FuncCursor,
+    cfg: &mut ControlFlowGraph,
+) {
+    if !pos.func.dfg.is_noreturn_call(inst) {
+        return;
+    }
+    let ebb = pos.func.layout.pp_ebb(inst);
+
+    if let Some(next) = pos.func.layout.next_inst(inst) {
+        // Leave the EBB alone if the call is already followed by a trap.
+        if pos.func.dfg[next].opcode() == ir::Opcode::Trap {
+            return;
+        }
+        let tail = pos.func.dfg.make_ebb();
+        pos.func.layout.split_ebb(tail, next);
+        cfg.recompute_ebb(pos.func, tail);
+    }
+
+    pos.goto_bottom(ebb);
+    pos.ins().trap(ir::TrapCode::UnreachableCodeReached);
+    pos.goto_inst(inst);
+    cfg.recompute_ebb(pos.func, ebb);
+}
+
 // Include legalization patterns that were generated by `gen_legalizer.py` from the `XForms` in
 // `lib/cretonne/meta/base/legalize.py`.
 //
@@ -184,19 +297,39 @@ fn expand_br_table(
     // This is a poor man's jump table using just a sequence of conditional branches.
     // TODO: Lower into a jump table load and indirect branch.
     let table_size = func.jump_tables[table].len();
+    let min_index = func.jump_tables[table].min_index();
+    let default = func.jump_tables[table].default_dest();
     let mut pos = FuncCursor::new(func).at_inst(inst);
     pos.use_srcloc(inst);
 
     for i in 0..table_size {
         if let Some(dest) = pos.func.jump_tables[table].get_entry(i) {
-            let t = pos.ins().icmp_imm(IntCC::Equal, arg, i as i64);
+            let t = pos.ins().icmp_imm(
+                IntCC::Equal,
+                arg,
+                min_index.wrapping_add(i as i64),
+            );
             pos.ins().brnz(t, dest, &[]);
         }
     }
 
-    // `br_table` falls through when nothing matches.
     let ebb = pos.current_ebb().unwrap();
-    pos.remove_inst();
+    match default {
+        Some(dest) => {
+            // The instructions following the `br_table` are unreachable now, but they still need
+            // an EBB of their own after the `jump`.
+            pos.func.dfg.replace(inst).jump(dest, &[]);
+            if let Some(next) = pos.func.layout.next_inst(inst) {
+                let tail = pos.func.dfg.make_ebb();
+                pos.func.layout.split_ebb(tail, next);
+                cfg.recompute_ebb(pos.func, tail);
+            }
+        }
+        None => {
+            // `br_table` falls through when nothing matches.
+            pos.remove_inst();
+        }
+    }
     cfg.recompute_ebb(pos.func, ebb);
 }
 
@@ -204,7 +337,7 @@ fn expand_br_table(
 ///
 /// Conditional moves are available in some ISAs for some register classes. The remaining selects
 /// are handled by a branch.
-fn expand_select(
+pub fn expand_select(
     inst: ir::Inst,
     func: &mut ir::Function,
     cfg: &mut ControlFlowGraph,
@@ -239,6 +372,28 @@ fn expand_select(
     cfg.recompute_ebb(pos.func, old_ebb);
 }
 
+/// Insert the traps of the division `opcode` of `x` by `y` at `pos`.
+///
+/// This is for divisions that are expanded into code that doesn't trap by itself. All divisions
+/// trap when `y` is zero, and `sdiv` also traps when its quotient overflows.
+pub fn insert_divrem_traps(
+    pos: &mut FuncCursor,
+    opcode: ir::Opcode,
+    x: ir::Value,
+    y: ir::Value,
+) {
+    pos.ins().trapz(y, ir::TrapCode::IntegerDivisionByZero);
+
+    // Trap when x == INT_MIN and y == -1. `y + 1` is zero only when y == -1, and `x ^ INT_MIN` is
+    // zero only when x == INT_MIN, so their bitwise or is zero only when both hold.
+    if opcode == ir::Opcode::Sdiv {
+        let bits = pos.func.dfg.value_type(x).lane_bits();
+        let yp1 = pos.ins().iadd_imm(y, 1);
+        let xmin = pos.ins().bxor_imm(x, -1 << (bits - 1));
+        let ovf = pos.ins().bor(yp1, xmin);
+        pos.ins().trapz(ovf, ir::TrapCode::IntegerOverflow);
+    }
+}
 
 /// Expand illegal `f32const` and `f64const` instructions.
 fn expand_fconst(
@@ -268,6 +423,60 @@ fn expand_fconst(
     pos.func.dfg.replace(inst).bitcast(ty, ival);
 }
 
+/// Expand `load_complex` and `store_complex` into an explicit address computation followed by a
+/// plain load or store.
+fn expand_complex_addr(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let (flags, base, index, scale, offset) = match func.dfg[inst] {
+        ir::InstructionData::LoadComplex {
+            flags,
+            args,
+            scale,
+            offset,
+            ..
+        } => (flags, args[0], args[1], scale, offset),
+        ir::InstructionData::StoreComplex {
+            flags,
+            ref args,
+            scale,
+            offset,
+            ..
+        } => {
+            let args = args.as_slice(&func.dfg.value_lists);
+            (flags, args[1], args[2], scale, offset)
+        }
+        _ => panic!("Expected complex address: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let addr_ty = pos.func.dfg.value_type(base);
+    let mut index = index;
+    if pos.func.dfg.value_type(index) != addr_ty {
+        index = pos.ins().uextend(addr_ty, index);
+    }
+    if scale > 0 {
+        index = pos.ins().ishl_imm(index, i64::from(scale));
+    }
+    let addr = pos.ins().iadd(base, index);
+
+    match pos.func.dfg[inst].opcode() {
+        ir::Opcode::LoadComplex => {
+            let ty = pos.func.dfg.value_type(pos.func.dfg.first_result(inst));
+            pos.func.dfg.replace(inst).load(ty, flags, addr, offset);
+        }
+        _ => {
+            let data = pos.func.dfg.inst_args(inst)[0];
+            pos.func.dfg.replace(inst).store(flags, data, addr, offset);
+        }
+    }
+}
+
 /// Expand the stack check instruction.
 pub fn expand_stack_check(
     inst: ir::Inst,
@@ -0,0 +1,225 @@
+//! Narrowing of wide integer instructions.
+//!
+//! Most integer instructions are narrowed by the patterns in `meta/base/legalize.py`, which split
+//! the operands into halves with `isplit` and join the results with `iconcat`. The instructions
+//! here have immediates, variable arguments, or memory operands that the patterns can't express:
+//!
+//! - Constants are split into two half-width constants.
+//! - Conditional branches test both halves of their arguments.
+//! - Loads and stores access the two halves separately. The low half is at the lower address, so
+//!   this assumes a little-endian target.
+//! - Division and remainder become library calls after explicit checks for the trapping
+//!   conditions, since the runtime library functions don't trap.
+//!
+//! Targets register these functions as custom legalizations for the integer types they narrow.
+//! They can't handle types without a half-width type, so they aren't part of the shared `narrow`
+//! group.
+
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::{self, InstBuilder};
+use isa::TargetIsa;
+use std::vec::Vec;
+use super::split::isplit;
+
+/// Get the half-width type used to narrow `ty`.
+fn half_type(ty: ir::Type, func: &ir::Function, inst: ir::Inst) -> ir::Type {
+    ty.half_width().unwrap_or_else(|| {
+        panic!("Can't narrow {}: {}", ty, func.dfg.display_inst(inst, None))
+    })
+}
+
+/// Narrow an `iconst` instruction into two half-width constants.
+pub fn narrow_iconst(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let imm: i64 = match func.dfg[inst] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => imm.into(),
+        _ => panic!("Expected iconst: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.ctrl_typevar(inst);
+    let half = half_type(ty, func, inst);
+    let bits = half.bits();
+    let mask = (1 << bits) - 1;
+    let lo = imm & mask;
+    let hi = (imm >> bits) & mask;
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+    let al = pos.ins().iconst(half, lo);
+    let ah = pos.ins().iconst(half, hi);
+    pos.func.dfg.replace(inst).iconcat(al, ah);
+}
+
+/// Narrow a conditional branch on a wide integer.
+///
+/// The `brz` and `brnz` instructions test the bitwise or of the two halves, and `br_icmp` becomes
+/// an `icmp` followed by a `brnz`. The comparison is then narrowed by the patterns.
+pub fn narrow_branch(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let dest = func.dfg[inst].branch_destination().unwrap_or_else(|| {
+        panic!("Expected branch: {}", func.dfg.display_inst(inst, None))
+    });
+    let mut args = Vec::new();
+    args.extend_from_slice(func.dfg.inst_variable_args(inst));
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    match pos.func.dfg[inst] {
+        ir::InstructionData::Branch { opcode, .. } => {
+            let x = pos.func.dfg.inst_fixed_args(inst)[0];
+            let (xl, xh) = isplit(pos.func, cfg, pos.position(), pos.srcloc(), x);
+            let a = pos.ins().bor(xl, xh);
+            if opcode == ir::Opcode::Brz {
+                pos.func.dfg.replace(inst).brz(a, dest, &args);
+            } else {
+                pos.func.dfg.replace(inst).brnz(a, dest, &args);
+            }
+        }
+        ir::InstructionData::BranchIcmp { cond, .. } => {
+            let (x, y) = {
+                let fixed = pos.func.dfg.inst_fixed_args(inst);
+                (fixed[0], fixed[1])
+            };
+            let c = pos.ins().icmp(cond, x, y);
+            pos.func.dfg.replace(inst).brnz(c, dest, &args);
+        }
+        _ => panic!("Expected brz/brnz/br_icmp: {}", pos.func.dfg.display_inst(inst, None)),
+    }
+}
+
+/// Narrow a load of a wide integer into loads of the halves.
+///
+/// Extending loads load the low half only, and extend it into the high half.
+pub fn narrow_load(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let (opcode, flags, addr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Load {
+            opcode,
+            flags,
+            arg,
+            offset,
+        } => (opcode, flags, arg, offset),
+        _ => panic!("Expected load: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.value_type(func.dfg.first_result(inst));
+    let half = half_type(ty, func, inst);
+    let offset: i32 = offset.into();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let (al, ah) = match opcode {
+        ir::Opcode::Load => {
+            let al = pos.ins().load(half, flags, addr, offset);
+            let ah = pos.ins().load(half, flags, addr, offset + half.bytes() as i32);
+            (al, ah)
+        }
+        ir::Opcode::Uload8 | ir::Opcode::Uload16 | ir::Opcode::Uload32 => {
+            let al = match opcode {
+                ir::Opcode::Uload8 => pos.ins().uload8(half, flags, addr, offset),
+                ir::Opcode::Uload16 => pos.ins().uload16(half, flags, addr, offset),
+                _ => pos.ins().load(half, flags, addr, offset),
+            };
+            let ah = pos.ins().iconst(half, 0);
+            (al, ah)
+        }
+        ir::Opcode::Sload8 | ir::Opcode::Sload16 | ir::Opcode::Sload32 => {
+            let al = match opcode {
+                ir::Opcode::Sload8 => pos.ins().sload8(half, flags, addr, offset),
+                ir::Opcode::Sload16 => pos.ins().sload16(half, flags, addr, offset),
+                _ => pos.ins().load(half, flags, addr, offset),
+            };
+            let ah = pos.ins().sshr_imm(al, i64::from(half.bits() - 1));
+            (al, ah)
+        }
+        _ => panic!("Expected load: {}", pos.func.dfg.display_inst(inst, None)),
+    };
+    pos.func.dfg.replace(inst).iconcat(al, ah);
+}
+
+/// Narrow a store of a wide integer into stores of the halves.
+///
+/// Truncating stores only store the low half.
+pub fn narrow_store(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    let (opcode, flags, x, addr, offset) = match func.dfg[inst] {
+        ir::InstructionData::Store {
+            opcode,
+            flags,
+            args,
+            offset,
+        } => (opcode, flags, args[0], args[1], offset),
+        _ => panic!("Expected store: {}", func.dfg.display_inst(inst, None)),
+    };
+    let ty = func.dfg.value_type(x);
+    let half = half_type(ty, func, inst);
+    let offset: i32 = offset.into();
+
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    let (xl, xh) = isplit(pos.func, cfg, pos.position(), pos.srcloc(), x);
+    match opcode {
+        ir::Opcode::Store => {
+            pos.ins().store(flags, xl, addr, offset);
+            pos.func.dfg.replace(inst).store(
+                flags,
+                xh,
+                addr,
+                offset + half.bytes() as i32,
+            );
+        }
+        ir::Opcode::Istore8 => {
+            pos.func.dfg.replace(inst).istore8(flags, xl, addr, offset);
+        }
+        ir::Opcode::Istore16 => {
+            pos.func.dfg.replace(inst).istore16(flags, xl, addr, offset);
+        }
+        ir::Opcode::Istore32 => {
+            pos.func.dfg.replace(inst).store(flags, xl, addr, offset);
+        }
+        _ => panic!("Expected store: {}", pos.func.dfg.display_inst(inst, None)),
+    }
+}
+
+/// Narrow a wide division or remainder into a library call.
+///
+/// The library functions don't trap, so the trapping conditions are checked explicitly first.
+pub fn narrow_divrem(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    isa: &TargetIsa,
+) {
+    let (opcode, x, y) = match func.dfg[inst] {
+        ir::InstructionData::Binary { opcode, args } => (opcode, args[0], args[1]),
+        _ => panic!("Need sdiv/udiv/srem/urem: {}", func.dfg.display_inst(inst, None)),
+    };
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    super::insert_divrem_traps(&mut pos, opcode, x, y);
+
+    let converted = super::expand_as_libcall(inst, pos.func, isa);
+    debug_assert!(converted, "No library call for {}", opcode);
+}
@@ -0,0 +1,306 @@
+//! Soft-float legalization.
+//!
+//! When the `enable_float` setting is disabled, or the target ISA lacks the floating point
+//! extensions it needs, the target can't use any floating point instructions or registers. The
+//! `lower_floats()` function then rewrites the function to work on integers only:
+//!
+//! - `f32` and `f64` values are changed to `i32` and `i64` values holding the same bits. This
+//!   includes the parameters and return values of the function and of the functions it calls.
+//! - Arithmetic, comparisons, and conversions are replaced with runtime library calls.
+//! - `fneg`, `fabs`, and `fcopysign` manipulate the sign bit with integer instructions.
+//! - Constants become integer constants, and `bitcast` instructions disappear.
+//!
+//! Floating point vectors and the flags-based `ffcmp` comparisons are not supported.
+
+use cursor::{Cursor, FuncCursor};
+use entity::EntityRef;
+use ir::{self, InstBuilder, InstructionData, LibCall, Opcode, Type, Value};
+use ir::condcodes::{FloatCC, IntCC};
+use ir::immediates::{Ieee32, Ieee64};
+use ir::types::{F32, F64, I32, I64};
+use isa::TargetIsa;
+use legalizer::libcall::get_libcall_funcref;
+use std::vec::Vec;
+
+/// Get the integer type used to represent values of type `ty`.
+fn soft_type(ty: Type) -> Type {
+    match ty {
+        F32 => I32,
+        F64 => I64,
+        _ => ty,
+    }
+}
+
+/// Change the floating point parameters and return values of `sig` to integers.
+fn soft_signature(sig: &mut ir::Signature) {
+    for param in sig.params.iter_mut().chain(sig.returns.iter_mut()) {
+        param.value_type = soft_type(param.value_type);
+    }
+}
+
+/// Rewrite `func` so it doesn't use any floating point values or instructions.
+///
+/// This must run before the signatures are legalized, so the floating point parameters and
+/// return values are passed like integers.
+pub fn lower_floats(func: &mut ir::Function, isa: &TargetIsa) {
+    soft_signature(&mut func.signature);
+    for sig in func.dfg.signatures.keys() {
+        soft_signature(&mut func.dfg.signatures[sig]);
+    }
+
+    // The `bitcast` instructions to remove once the values have been changed to integers.
+    let mut bitcasts = Vec::new();
+
+    {
+        let mut pos = FuncCursor::new(func);
+        while let Some(_ebb) = pos.next_ebb() {
+            let mut prev_pos = pos.position();
+            while let Some(inst) = pos.next_inst() {
+                if lower_inst(inst, &mut pos, isa, &mut bitcasts) {
+                    // The replacement instructions may need to be lowered too.
+                    pos.set_position(prev_pos);
+                    continue;
+                }
+                prev_pos = pos.position();
+            }
+        }
+    }
+
+    // All the floating point instructions are gone, so the values can be changed to integers.
+    for v in 0..func.dfg.num_values() {
+        let v = Value::new(v);
+        let ty = func.dfg.value_type(v);
+        if soft_type(ty) != ty {
+            func.dfg.change_value_type(v, soft_type(ty));
+        }
+    }
+
+    for inst in bitcasts {
+        let arg = func.dfg.inst_args(inst)[0];
+        let result = func.dfg.first_result(inst);
+        func.dfg.clear_results(inst);
+        func.dfg.change_to_alias(result, arg);
+        func.layout.remove_inst(inst);
+    }
+}
+
+/// Lower the floating point instruction `inst`, returning true if it was replaced.
+///
+/// The cursor `pos` points at `inst`. Instructions inserted by the lowering can still use
+/// floating point values, so the caller must revisit them.
+fn lower_inst(
+    inst: ir::Inst,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+    bitcasts: &mut Vec<ir::Inst>,
+) -> bool {
+    pos.use_srcloc(inst);
+    let opcode = pos.func.dfg[inst].opcode();
+    let ctrl_type = pos.func.dfg.ctrl_typevar(inst);
+
+    match pos.func.dfg[inst] {
+        InstructionData::UnaryIeee32 { imm, .. } => {
+            // `i32` immediates are sign-extended.
+            pos.func.dfg.replace(inst).iconst(
+                I32,
+                i64::from(imm.bits() as i32),
+            );
+            return true;
+        }
+        InstructionData::UnaryIeee64 { imm, .. } => {
+            pos.func.dfg.replace(inst).iconst(I64, imm.bits() as i64);
+            return true;
+        }
+        InstructionData::FloatCompare { cond, args, .. } if soft_type(ctrl_type) != ctrl_type => {
+            lower_fcmp(inst, cond, args[0], args[1], pos, isa);
+            return true;
+        }
+        _ => {}
+    }
+
+    match opcode {
+        Opcode::Fneg | Opcode::Fabs | Opcode::Fcopysign if soft_type(ctrl_type) != ctrl_type => {
+            let sign = -1i64 << (ctrl_type.bits() - 1);
+            let x = pos.func.dfg.inst_args(inst)[0];
+            match opcode {
+                Opcode::Fneg => {
+                    pos.func.dfg.replace(inst).bxor_imm(x, sign);
+                }
+                Opcode::Fabs => {
+                    pos.func.dfg.replace(inst).band_imm(x, !sign);
+                }
+                _ => {
+                    let y = pos.func.dfg.inst_args(inst)[1];
+                    let magnitude = pos.ins().band_imm(x, !sign);
+                    let sign_bit = pos.ins().band_imm(y, sign);
+                    pos.func.dfg.replace(inst).bor(magnitude, sign_bit);
+                }
+            }
+            true
+        }
+        Opcode::FcvtFromSint | Opcode::FcvtFromUint | Opcode::FcvtToSint |
+        Opcode::FcvtToUint | Opcode::Fpromote | Opcode::Fdemote => {
+            let x = pos.func.dfg.inst_args(inst)[0];
+            let from = pos.func.dfg.value_type(x);
+            let to = pos.func.dfg.value_type(pos.func.dfg.first_result(inst));
+            let libcall = match LibCall::for_conversion(opcode, from, to) {
+                Some(libcall) => libcall,
+                None => return false,
+            };
+            match opcode {
+                Opcode::FcvtToSint => insert_range_checks(x, to, true, pos),
+                Opcode::FcvtToUint => insert_range_checks(x, to, false, pos),
+                _ => {}
+            }
+            replace_with_libcall(inst, libcall, pos, isa);
+            true
+        }
+        Opcode::Bitcast => {
+            let x = pos.func.dfg.inst_args(inst)[0];
+            let from = pos.func.dfg.value_type(x);
+            if soft_type(from) != from || soft_type(ctrl_type) != ctrl_type {
+                bitcasts.push(inst);
+            }
+            false
+        }
+        _ if soft_type(ctrl_type) != ctrl_type => {
+            match LibCall::for_inst(opcode, ctrl_type) {
+                Some(libcall) => {
+                    replace_with_libcall(inst, libcall, pos, isa);
+                    true
+                }
+                None => false,
+            }
+        }
+        _ => false,
+    }
+}
+
+/// Replace `inst` with a call to `libcall` taking the same arguments.
+fn replace_with_libcall(inst: ir::Inst, libcall: LibCall, pos: &mut FuncCursor, isa: &TargetIsa) {
+    let args = pos.func.dfg.inst_args(inst).to_vec();
+    let params: Vec<_> = args.iter()
+        .map(|&v| soft_type(pos.func.dfg.value_type(v)))
+        .collect();
+    let returns: Vec<_> = pos.func
+        .dfg
+        .inst_results(inst)
+        .iter()
+        .map(|&v| soft_type(pos.func.dfg.value_type(v)))
+        .collect();
+    let funcref = get_libcall_funcref(libcall, &params, &returns, pos.func, isa);
+    pos.func.dfg.replace(inst).call(funcref, &args);
+}
+
+/// Replace the `fcmp` instruction `inst` with library calls.
+fn lower_fcmp(
+    inst: ir::Inst,
+    cond: FloatCC,
+    x: Value,
+    y: Value,
+    pos: &mut FuncCursor,
+    isa: &TargetIsa,
+) {
+    // Each comparison routine returns an integer that is compared against zero.
+    let (call32, call64, icc) = match cond {
+        FloatCC::Ordered => (LibCall::UnordF32, LibCall::UnordF64, IntCC::Equal),
+        FloatCC::Unordered => (LibCall::UnordF32, LibCall::UnordF64, IntCC::NotEqual),
+        FloatCC::Equal => (LibCall::EqF32, LibCall::EqF64, IntCC::Equal),
+        FloatCC::NotEqual => (LibCall::EqF32, LibCall::EqF64, IntCC::NotEqual),
+        FloatCC::LessThan => (LibCall::LtF32, LibCall::LtF64, IntCC::SignedLessThan),
+        FloatCC::LessThanOrEqual => {
+            (LibCall::LeF32, LibCall::LeF64, IntCC::SignedLessThanOrEqual)
+        }
+        FloatCC::GreaterThan => (LibCall::GtF32, LibCall::GtF64, IntCC::SignedGreaterThan),
+        FloatCC::GreaterThanOrEqual => {
+            (LibCall::GeF32, LibCall::GeF64, IntCC::SignedGreaterThanOrEqual)
+        }
+        // The unordered conditions are the inverse of the ordered ones.
+        FloatCC::UnorderedOrLessThan => {
+            (LibCall::GeF32, LibCall::GeF64, IntCC::SignedLessThan)
+        }
+        FloatCC::UnorderedOrLessThanOrEqual => {
+            (LibCall::GtF32, LibCall::GtF64, IntCC::SignedLessThanOrEqual)
+        }
+        FloatCC::UnorderedOrGreaterThan => {
+            (LibCall::LeF32, LibCall::LeF64, IntCC::SignedGreaterThan)
+        }
+        FloatCC::UnorderedOrGreaterThanOrEqual => {
+            (LibCall::LtF32, LibCall::LtF64, IntCC::SignedGreaterThanOrEqual)
+        }
+        // There are no routines for these conditions, so combine two comparisons that are
+        // lowered when they are revisited.
+        FloatCC::OrderedNotEqual => {
+            let lt = pos.ins().fcmp(FloatCC::LessThan, x, y);
+            let gt = pos.ins().fcmp(FloatCC::GreaterThan, x, y);
+            pos.func.dfg.replace(inst).bor(lt, gt);
+            return;
+        }
+        FloatCC::UnorderedOrEqual => {
+            let uno = pos.ins().fcmp(FloatCC::Unordered, x, y);
+            let eq = pos.ins().fcmp(FloatCC::Equal, x, y);
+            pos.func.dfg.replace(inst).bor(uno, eq);
+            return;
+        }
+    };
+
+    let ty = pos.func.dfg.value_type(x);
+    let libcall = if ty == F32 { call32 } else { call64 };
+    let funcref = get_libcall_funcref(libcall, &[soft_type(ty); 2], &[I32], pos.func, isa);
+    let call = pos.ins().call(funcref, &[x, y]);
+    let result = pos.func.dfg.first_result(call);
+    pos.func.dfg.replace(inst).icmp_imm(icc, result, 0);
+}
+
+/// Insert the checks that make a float-to-integer conversion of `x` to `ty` trap on NaN and
+/// out-of-range inputs, like `fcvt_to_sint` and `fcvt_to_uint` require.
+///
+/// The library routines don't check their input.
+fn insert_range_checks(x: Value, ty: Type, signed: bool, pos: &mut FuncCursor) {
+    let xty = pos.func.dfg.value_type(x);
+    let bits = ty.bits();
+
+    let is_nan = pos.ins().fcmp(FloatCC::Unordered, x, x);
+    pos.ins().trapnz(is_nan, ir::TrapCode::BadConversionToInteger);
+
+    // Find the range of the input values that can be converted, after truncation towards zero.
+    // When -2^(N-1) - 1 can be represented exactly, all the values above it can be converted.
+    let (low_cc, low, high) = match (xty, signed) {
+        (F32, true) if bits < 32 => (
+            FloatCC::LessThanOrEqual,
+            pos.ins().f32const(Ieee32::fcvt_to_sint_negative_overflow(bits)),
+            pos.ins().f32const(Ieee32::pow2(bits - 1)),
+        ),
+        (F32, true) => (
+            FloatCC::LessThan,
+            pos.ins().f32const(Ieee32::pow2(bits - 1).neg()),
+            pos.ins().f32const(Ieee32::pow2(bits - 1)),
+        ),
+        (F32, false) => (
+            FloatCC::LessThanOrEqual,
+            pos.ins().f32const(Ieee32::with_float(-1.0)),
+            pos.ins().f32const(Ieee32::pow2(bits)),
+        ),
+        (F64, true) if bits < 64 => (
+            FloatCC::LessThanOrEqual,
+            pos.ins().f64const(Ieee64::fcvt_to_sint_negative_overflow(bits)),
+            pos.ins().f64const(Ieee64::pow2(bits - 1)),
+        ),
+        (F64, true) => (
+            FloatCC::LessThan,
+            pos.ins().f64const(Ieee64::pow2(bits - 1).neg()),
+            pos.ins().f64const(Ieee64::pow2(bits - 1)),
+        ),
+        (F64, false) => (
+            FloatCC::LessThanOrEqual,
+            pos.ins().f64const(Ieee64::with_float(-1.0)),
+            pos.ins().f64const(Ieee64::pow2(bits)),
+        ),
+        _ => panic!("Can't convert {}", xty),
+    };
+
+    let underflow = pos.ins().fcmp(low_cc, x, low);
+    pos.ins().trapnz(underflow, ir::TrapCode::IntegerOverflow);
+    let overflow = pos.ins().fcmp(FloatCC::GreaterThanOrEqual, x, high);
+    pos.ins().trapnz(overflow, ir::TrapCode::IntegerOverflow);
+}
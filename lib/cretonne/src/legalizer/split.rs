@@ -332,12 +332,8 @@ fn resolve_splits(dfg: &ir::DataFlowGraph, value: Value) -> Value {
 /// After legalizing the instructions computing the value that was split, it is likely that we can
 /// avoid depending on the split instruction. Its input probably comes from a concatenation.
 pub fn simplify_branch_arguments(dfg: &mut ir::DataFlowGraph, branch: Inst) {
-    let mut new_args = Vec::new();
-
-    for &arg in dfg.inst_args(branch) {
-        let new_arg = resolve_splits(dfg, arg);
-        new_args.push(new_arg);
+    for i in 0..dfg.inst_args(branch).len() {
+        let new_arg = resolve_splits(dfg, dfg.inst_args(branch)[i]);
+        dfg.inst_args_mut(branch)[i] = new_arg;
     }
-
-    dfg.inst_args_mut(branch).copy_from_slice(&new_args);
 }
@@ -0,0 +1,115 @@
+//! Legalization of tables.
+//!
+//! This module exports the `expand_table_addr` function which transforms a `table_addr`
+//! instruction into a bounds check followed by the address computation of the table element.
+
+use cursor::{Cursor, FuncCursor};
+use flowgraph::ControlFlowGraph;
+use ir::{self, InstBuilder, MemFlags};
+use ir::condcodes::IntCC;
+use isa::TargetIsa;
+
+/// Expand a `table_addr` instruction according to the definition of the table.
+pub fn expand_table_addr(
+    inst: ir::Inst,
+    func: &mut ir::Function,
+    _cfg: &mut ControlFlowGraph,
+    _isa: &TargetIsa,
+) {
+    // Unpack the instruction.
+    let (table, index, element_offset) = match func.dfg[inst] {
+        ir::InstructionData::TableAddr {
+            opcode,
+            table,
+            arg,
+            offset,
+        } => {
+            debug_assert_eq!(opcode, ir::Opcode::TableAddr);
+            (table, arg, offset.into())
+        }
+        _ => panic!("Wanted table_addr: {}", func.dfg.display_inst(inst, None)),
+    };
+
+    let index_ty = func.dfg.value_type(index);
+    let addr_ty = func.dfg.value_type(func.dfg.first_result(inst));
+    let min_size: i64 = func.tables[table].min_size.into();
+    let bound_gv = func.tables[table].bound_gv;
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Start with the bounds check. Trap if `index >= bound`. Constant indexes below the
+    // guaranteed minimum size don't need the check.
+    if constant_index(pos.func, index).map_or(true, |idx| idx >= min_size) {
+        let bound_addr = pos.ins().global_addr(addr_ty, bound_gv);
+        let bound = pos.ins().load(index_ty, MemFlags::new(), bound_addr, 0);
+        let oob = pos.ins().icmp(IntCC::UnsignedGreaterThanOrEqual, index, bound);
+        pos.ins().trapnz(oob, ir::TrapCode::TableOutOfBounds);
+    }
+
+    compute_addr(inst, table, addr_ty, index, index_ty, element_offset, pos.func);
+}
+
+/// Get the value of `index` if it is an unsigned integer constant.
+fn constant_index(func: &ir::Function, index: ir::Value) -> Option<i64> {
+    let inst = match func.dfg.value_def(index) {
+        ir::ValueDef::Result(inst, _) => inst,
+        ir::ValueDef::Param(..) => return None,
+    };
+    match func.dfg[inst] {
+        ir::InstructionData::UnaryImm {
+            opcode: ir::Opcode::Iconst,
+            imm,
+        } => {
+            let imm: i64 = imm.into();
+            if imm >= 0 { Some(imm) } else { None }
+        }
+        _ => None,
+    }
+}
+
+/// Emit code for the base address computation of a `table_addr` instruction.
+fn compute_addr(
+    inst: ir::Inst,
+    table: ir::Table,
+    addr_ty: ir::Type,
+    mut index: ir::Value,
+    index_ty: ir::Type,
+    element_offset: i64,
+    func: &mut ir::Function,
+) {
+    let mut pos = FuncCursor::new(func).at_inst(inst);
+    pos.use_srcloc(inst);
+
+    // Convert `index` to `addr_ty`.
+    if index_ty != addr_ty {
+        index = pos.ins().uextend(addr_ty, index);
+    }
+
+    // Add the table base address base
+    let base_gv = pos.func.tables[table].base_gv;
+    let base_addr = pos.ins().global_addr(addr_ty, base_gv);
+    let base = pos.ins().load(addr_ty, MemFlags::new(), base_addr, 0);
+
+    // Scale the index by the element size.
+    let element_size: i64 = pos.func.tables[table].element_size.into();
+    let offset = if element_size == 1 {
+        index
+    } else if element_size > 0 && element_size & (element_size - 1) == 0 {
+        pos.ins().ishl_imm(
+            index,
+            i64::from(element_size.trailing_zeros()),
+        )
+    } else {
+        pos.ins().imul_imm(index, element_size)
+    };
+
+    if element_offset == 0 {
+        pos.func.dfg.replace(inst).iadd(base, offset);
+    } else {
+        let elem_addr = pos.ins().iadd(base, offset);
+        pos.func.dfg.replace(inst).iadd_imm(
+            elem_addr,
+            element_offset,
+        );
+    }
+}
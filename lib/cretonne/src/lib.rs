@@ -35,10 +35,15 @@
                 useless_let_if_seq,
                 len_without_is_empty))]
 
-pub use context::Context;
-pub use legalizer::legalize_function;
+pub use context::{Context, CompiledFunctionInfo};
+pub use copy_propagation::propagate_copies;
+pub use debug_values::DebugValueLoc;
+pub use deopt::DeoptPoint;
+pub use stack_layout::{FrameLayout, FrameSlot};
+pub use legalizer::{legalize_function, legalize_function_with, CustomLegalizations};
+pub use regalloc::{SpillSlotStats, TiedCopyStats};
 pub use verifier::verify_function;
-pub use write::write_function;
+pub use write::{write_function, write_function_with, WriteOptions};
 
 /// Version number of the cretonne crate.
 pub const VERSION: &str = env!("CARGO_PKG_VERSION");
@@ -48,10 +53,14 @@ pub mod dbg;
 #[macro_use]
 pub mod entity;
 
+pub mod alias_analysis;
 pub mod bforest;
 pub mod binemit;
 pub mod cfg_printer;
+pub mod critical_edges;
 pub mod cursor;
+pub mod diff;
+pub mod dump;
 pub mod dominator_tree;
 pub mod flowgraph;
 pub mod ir;
@@ -66,20 +75,32 @@ pub mod verifier;
 
 mod abi;
 mod bitset;
+mod bounds_checks;
+mod cold_ebbs;
+mod constant_folding;
 mod constant_hash;
 mod context;
+mod copy_propagation;
+mod debug_values;
+mod deopt;
 mod divconst_magic_numbers;
+mod ebb_counters;
 mod iterators;
 mod legalizer;
 mod licm;
 mod partition_slice;
+mod postopt;
 mod predicates;
 mod preopt;
 mod ref_slice;
 mod regalloc;
+mod sccp;
 mod scoped_hash_map;
 mod simple_gvn;
+mod slp;
 mod stack_layout;
+mod strength_reduction;
 mod topo_order;
 mod unreachable_code;
+mod unroll;
 mod write;
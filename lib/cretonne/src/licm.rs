@@ -1,18 +1,18 @@
 //! A Loop Invariant Code Motion optimization pass
 
+use alias_analysis::{may_conflict, memory_access};
 use cursor::{Cursor, FuncCursor};
-use ir::{Function, Ebb, Inst, Value, Type, InstBuilder, Layout};
+use ir::{Function, Ebb, Inst, Value, InstructionData};
 use flowgraph::ControlFlowGraph;
 use std::collections::HashSet;
 use dominator_tree::DominatorTree;
-use entity::{EntityList, ListPool};
 use loop_analysis::{Loop, LoopAnalysis};
 use timing;
 use std::vec::Vec;
 
 /// Performs the LICM pass by detecting loops within the CFG and moving
 /// loop-invariant instructions out of them.
-/// Changes the CFG and domtree in-place during the operation.
+/// Changes the CFG, domtree, and loop analysis in-place during the operation.
 pub fn do_licm(
     func: &mut Function,
     cfg: &mut ControlFlowGraph,
@@ -31,104 +31,16 @@ pub fn do_licm(
         // Then we create the loop's pre-header and fill it with the invariant instructions
         // Then we remove the invariant instructions from the loop body
         if !invariant_inst.is_empty() {
-            // If the loop has a natural pre-header we use it, otherwise we create it.
-            let mut pos;
-            match has_pre_header(&func.layout, cfg, domtree, loop_analysis.loop_header(lp)) {
-                None => {
-                    let pre_header =
-                        create_pre_header(loop_analysis.loop_header(lp), func, cfg, domtree);
-                    pos = FuncCursor::new(func).at_last_inst(pre_header);
-                }
-                // If there is a natural pre-header we insert new instructions just before the
-                // related jumping instruction (which is not necessarily at the end).
-                Some((_, last_inst)) => {
-                    pos = FuncCursor::new(func).at_inst(last_inst);
-                }
-            };
-            // The last instruction of the pre-header is the termination instruction (usually
-            // a jump) so we need to insert just before this.
+            // If the loop has a natural pre-header we use it, otherwise we create it. We insert
+            // new instructions just before the related jumping instruction (which is not
+            // necessarily at the end).
+            let (_, last_inst) = loop_analysis.ensure_preheader(lp, func, cfg, domtree);
+            let mut pos = FuncCursor::new(func).at_inst(last_inst);
             for inst in invariant_inst {
                 pos.insert_inst(inst);
             }
         }
     }
-    // We have to recompute the domtree to account for the changes
-    cfg.compute(func);
-    domtree.compute(func, cfg);
-}
-
-// Insert a pre-header before the header, modifying the function layout and CFG to reflect it.
-// A jump instruction to the header is placed at the end of the pre-header.
-fn create_pre_header(
-    header: Ebb,
-    func: &mut Function,
-    cfg: &mut ControlFlowGraph,
-    domtree: &DominatorTree,
-) -> Ebb {
-    let pool = &mut ListPool::<Value>::new();
-    let header_args_values: Vec<Value> = func.dfg.ebb_params(header).into_iter().cloned().collect();
-    let header_args_types: Vec<Type> = header_args_values
-        .clone()
-        .into_iter()
-        .map(|val| func.dfg.value_type(val))
-        .collect();
-    let pre_header = func.dfg.make_ebb();
-    let mut pre_header_args_value: EntityList<Value> = EntityList::new();
-    for typ in header_args_types {
-        pre_header_args_value.push(func.dfg.append_ebb_param(pre_header, typ), pool);
-    }
-    for (_, last_inst) in cfg.pred_iter(header) {
-        // We only follow normal edges (not the back edges)
-        if !domtree.dominates(header, last_inst, &func.layout) {
-            change_branch_jump_destination(last_inst, pre_header, func);
-        }
-    }
-    {
-        let mut pos = FuncCursor::new(func).at_top(header);
-        // Inserts the pre-header at the right place in the layout.
-        pos.insert_ebb(pre_header);
-        pos.next_inst();
-        pos.ins().jump(header, pre_header_args_value.as_slice(pool));
-    }
-    pre_header
-}
-
-// Detects if a loop header has a natural pre-header.
-//
-// A loop header has a pre-header if there is only one predecessor that the header doesn't
-// dominate.
-// Returns the pre-header Ebb and the instruction jumping to the header.
-fn has_pre_header(
-    layout: &Layout,
-    cfg: &ControlFlowGraph,
-    domtree: &DominatorTree,
-    header: Ebb,
-) -> Option<(Ebb, Inst)> {
-    let mut result = None;
-    let mut found = false;
-    for (pred_ebb, last_inst) in cfg.pred_iter(header) {
-        // We only count normal edges (not the back edges)
-        if !domtree.dominates(header, last_inst, layout) {
-            if found {
-                // We have already found one, there are more than one
-                return None;
-            } else {
-                result = Some((pred_ebb, last_inst));
-                found = true;
-            }
-        }
-    }
-    result
-}
-
-
-// Change the destination of a jump or branch instruction. Does nothing if called with a non-jump
-// or non-branch instruction.
-fn change_branch_jump_destination(inst: Inst, new_ebb: Ebb, func: &mut Function) {
-    match func.dfg[inst].branch_destination_mut() {
-        None => (),
-        Some(instruction_dest) => *instruction_dest = new_ebb,
-    }
 }
 
 // Traverses a loop in reverse post-order from a header EBB and identify loop-invariant
@@ -142,9 +54,11 @@ fn remove_loop_invariant_instructions(
 ) -> Vec<Inst> {
     let mut loop_values: HashSet<Value> = HashSet::new();
     let mut invariant_inst: Vec<Inst> = Vec::new();
+    let loop_ebbs = postorder_ebbs_loop(loop_analysis, cfg, lp);
+    let loop_writes = memory_writes(func, &loop_ebbs);
     let mut pos = FuncCursor::new(func);
     // We traverse the loop EBB in reverse post-order.
-    for ebb in postorder_ebbs_loop(loop_analysis, cfg, lp).iter().rev() {
+    for ebb in loop_ebbs.iter().rev() {
         // Arguments of the EBB are loop values
         for val in pos.func.dfg.ebb_params(*ebb) {
             loop_values.insert(*val);
@@ -152,7 +66,7 @@ fn remove_loop_invariant_instructions(
         pos.goto_top(*ebb);
         #[cfg_attr(feature = "cargo-clippy", allow(block_in_if_condition_stmt))]
         while let Some(inst) = pos.next_inst() {
-            if pos.func.dfg.has_results(inst) &&
+            if pos.func.dfg.has_results(inst) && is_safe_to_hoist(pos.func, inst, &loop_writes) &&
                 pos.func.dfg.inst_args(inst).into_iter().all(|arg| {
                     !loop_values.contains(arg)
                 })
@@ -174,6 +88,39 @@ fn remove_loop_invariant_instructions(
     invariant_inst
 }
 
+/// Get the instructions in `ebbs` that can write to memory.
+fn memory_writes(func: &Function, ebbs: &[Ebb]) -> Vec<Inst> {
+    ebbs.iter()
+        .flat_map(|&ebb| func.layout.ebb_insts(ebb))
+        .filter(|&inst| {
+            memory_access(func, inst).map_or(false, |access| access.writes)
+        })
+        .collect()
+}
+
+/// Test whether `inst` can be moved out of a loop, given the instructions in the loop that can
+/// write to memory.
+///
+/// Loads can only be moved if they can't trap, and no write in the loop can change the memory
+/// they read.
+fn is_safe_to_hoist(func: &Function, inst: Inst, loop_writes: &[Inst]) -> bool {
+    let opcode = func.dfg[inst].opcode();
+    if opcode.is_call() || opcode.is_branch() || opcode.is_terminator() || opcode.is_return() ||
+        opcode.can_trap() || opcode.other_side_effects() || opcode.can_store() ||
+        opcode.writes_cpu_flags()
+    {
+        return false;
+    }
+    if opcode.can_load() {
+        let notrap = match func.dfg[inst] {
+            InstructionData::Load { flags, .. } => flags.notrap(),
+            _ => false,
+        };
+        return notrap && loop_writes.iter().all(|&write| !may_conflict(func, write, inst));
+    }
+    true
+}
+
 /// Return ebbs from a loop in post-order, starting from an entry point in the block.
 fn postorder_ebbs_loop(loop_analysis: &LoopAnalysis, cfg: &ControlFlowGraph, lp: Loop) -> Vec<Ebb> {
     let mut grey = HashSet::new();
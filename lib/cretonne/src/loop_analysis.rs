@@ -1,11 +1,18 @@
 //! A loop analysis represented as mappings of loops to their header Ebb
 //! and parent in the loop tree.
+//!
+//! Besides the loop forest itself, the analysis provides the nesting depth and back edges of
+//! each loop, and it can create loop preheaders for the passes that need a place to put code
+//! executed once before entering a loop.
 
+use critical_edges::{insert_forwarding_ebb, unshare_jump_table};
 use dominator_tree::DominatorTree;
 use entity::{PrimaryMap, Keys};
 use entity::EntityMap;
-use flowgraph::ControlFlowGraph;
-use ir::{Function, Ebb, Layout};
+use flowgraph::{BasicBlock, ControlFlowGraph};
+use ir::{Function, Ebb, Layout, Opcode};
+use ir::instructions::BranchInfo;
+use ir::layout::Ebbs;
 use packed_option::PackedOption;
 use timing;
 use std::vec::Vec;
@@ -22,12 +29,20 @@ entity_impl!(Loop, "loop");
 pub struct LoopAnalysis {
     loops: PrimaryMap<Loop, LoopData>,
     ebb_loop_map: EntityMap<Ebb, PackedOption<Loop>>,
+    // Back edges of all the loops. The back edges of a loop are contiguous.
+    back_edges: Vec<BasicBlock>,
     valid: bool,
+
+    // Scratch memory used by `discover_loop_blocks()`.
+    stack: Vec<Ebb>,
 }
 
 struct LoopData {
     header: Ebb,
     parent: PackedOption<Loop>,
+    depth: u32,
+    // Range of `LoopAnalysis::back_edges` holding the back edges of this loop.
+    back_edges: (u32, u32),
 }
 
 impl LoopData {
@@ -36,6 +51,8 @@ impl LoopData {
         LoopData {
             header: header,
             parent: parent.into(),
+            depth: 0,
+            back_edges: (0, 0),
         }
     }
 }
@@ -49,6 +66,8 @@ impl LoopAnalysis {
             valid: false,
             loops: PrimaryMap::new(),
             ebb_loop_map: EntityMap::new(),
+            back_edges: Vec::new(),
+            stack: Vec::new(),
         }
     }
 
@@ -70,6 +89,67 @@ impl LoopAnalysis {
         self.loops[lp].parent.expand()
     }
 
+    /// Return the innermost loop containing `ebb`, if any.
+    pub fn innermost_loop(&self, ebb: Ebb) -> Option<Loop> {
+        self.ebb_loop_map[ebb].expand()
+    }
+
+    /// Return the nesting depth of a loop.
+    ///
+    /// Outermost loops have depth 1.
+    pub fn loop_depth(&self, lp: Loop) -> u32 {
+        self.loops[lp].depth
+    }
+
+    /// Return the nesting depth of the innermost loop containing `ebb`, or 0 if `ebb` is not in
+    /// a loop.
+    pub fn ebb_loop_depth(&self, ebb: Ebb) -> u32 {
+        self.innermost_loop(ebb).map_or(0, |lp| self.loop_depth(lp))
+    }
+
+    /// Return the back edges of a loop, that is the branches to its header from inside the loop.
+    pub fn back_edges(&self, lp: Loop) -> &[BasicBlock] {
+        let (start, end) = self.loops[lp].back_edges;
+        &self.back_edges[start as usize..end as usize]
+    }
+
+    /// Iterate over the EBBs belonging to a loop and its inner loops, in layout order.
+    pub fn loop_ebbs<'a>(&'a self, lp: Loop, layout: &'a Layout) -> LoopEbbs<'a> {
+        LoopEbbs {
+            loop_analysis: self,
+            lp,
+            ebbs: layout.ebbs(),
+        }
+    }
+
+    /// Return the natural preheader of a loop, if it has one.
+    ///
+    /// A loop has a natural preheader when a single branch from outside the loop goes to its
+    /// header, and that branch is a `jump` ending an EBB whose only successor is the header.
+    /// Code inserted before the jump is then executed exactly when the loop is entered. Returns
+    /// the EBB containing that jump and the jump instruction itself.
+    pub fn preheader(
+        &self,
+        lp: Loop,
+        func: &Function,
+        cfg: &ControlFlowGraph,
+    ) -> Option<BasicBlock> {
+        let header = self.loops[lp].header;
+        let mut entries = cfg.pred_iter(header).filter(
+            |&(ebb, _)| !self.is_in_loop(ebb, lp),
+        );
+        let (ebb, inst) = match (entries.next(), entries.next()) {
+            (Some(entry), None) => entry,
+            _ => return None,
+        };
+        if func.dfg[inst].opcode() != Opcode::Jump || func.layout.last_inst(ebb) != Some(inst) ||
+            cfg.succ_iter(ebb).any(|succ| succ != header)
+        {
+            return None;
+        }
+        Some((ebb, inst))
+    }
+
     /// Determine if an Ebb belongs to a loop by running a finger along the loop tree.
     ///
     /// Returns `true` if `ebb` is in loop `lp`.
@@ -104,8 +184,10 @@ impl LoopAnalysis {
         self.loops.clear();
         self.ebb_loop_map.clear();
         self.ebb_loop_map.resize(func.dfg.num_ebbs());
+        self.back_edges.clear();
         self.find_loop_headers(cfg, domtree, &func.layout);
         self.discover_loop_blocks(cfg, domtree, &func.layout);
+        self.compute_depths();
         self.valid = true;
     }
 
@@ -124,9 +206,59 @@ impl LoopAnalysis {
     pub fn clear(&mut self) {
         self.loops.clear();
         self.ebb_loop_map.clear();
+        self.back_edges.clear();
         self.valid = false;
     }
 
+    /// Get a preheader for a loop, creating one if the loop doesn't have a natural preheader.
+    ///
+    /// Returns the preheader EBB and the `jump` to the loop header that ends it. Instructions
+    /// inserted before that jump are executed every time the loop is entered, and only then.
+    ///
+    /// A loop entered by a conditional branch, or from an EBB with other successors, doesn't have
+    /// a natural preheader. A new preheader is inserted in the layout just before the loop header,
+    /// with the same EBB parameters, and all the branches entering the loop are redirected to it.
+    /// The control flow graph is updated incrementally, but the dominator tree is recomputed
+    /// because the loop header now has a new immediate dominator. The new preheader is added to
+    /// the parent loop of `lp`, so this analysis remains valid.
+    pub fn ensure_preheader(
+        &mut self,
+        lp: Loop,
+        func: &mut Function,
+        cfg: &mut ControlFlowGraph,
+        domtree: &mut DominatorTree,
+    ) -> BasicBlock {
+        if let Some(preheader) = self.preheader(lp, func, cfg) {
+            return preheader;
+        }
+
+        let header = self.loops[lp].header;
+        let entries: Vec<BasicBlock> = cfg.pred_iter(header)
+            .filter(|&(ebb, _)| !self.is_in_loop(ebb, lp))
+            .collect();
+        let (preheader, jump) = insert_forwarding_ebb(func, header);
+        for &(_, inst) in &entries {
+            // Jump tables are rewritten in place, so a table shared with branches that stay in
+            // place must be copied first.
+            if let BranchInfo::Table(jt) = func.dfg.analyze_branch(inst) {
+                unshare_jump_table(func, inst, jt);
+            }
+            func.change_branch_destination(inst, header, preheader);
+        }
+
+        cfg.insert_ebb(func, preheader);
+        for (idx, &(ebb, _)) in entries.iter().enumerate() {
+            // The same EBB can have several branches to the header.
+            if entries[..idx].iter().all(|&(prev, _)| prev != ebb) {
+                cfg.recompute_ebb(func, ebb);
+            }
+        }
+        domtree.compute(func, cfg);
+        self.ebb_loop_map[preheader] = self.loops[lp].parent;
+
+        (preheader, jump)
+    }
+
     // Traverses the CFG in reverse postorder and create a loop object for every EBB having a
     // back edge.
     fn find_loop_headers(
@@ -137,16 +269,32 @@ impl LoopAnalysis {
     ) {
         // We traverse the CFG in reverse postorder
         for &ebb in domtree.cfg_postorder().iter().rev() {
-            for (_, pred_inst) in cfg.pred_iter(ebb) {
+            let start = self.back_edges.len() as u32;
+            for (pred, pred_inst) in cfg.pred_iter(ebb) {
                 // If the ebb dominates one of its predecessors it is a back edge
                 if domtree.dominates(ebb, pred_inst, layout) {
-                    // This ebb is a loop header, so we create its associated loop
-                    let lp = self.loops.push(LoopData::new(ebb, None));
-                    self.ebb_loop_map[ebb] = lp.into();
-                    break;
-                    // We break because we only need one back edge to identify a loop header.
+                    self.back_edges.push((pred, pred_inst));
                 }
             }
+            let end = self.back_edges.len() as u32;
+            if end > start {
+                // This ebb is a loop header, so we create its associated loop
+                let mut data = LoopData::new(ebb, None);
+                data.back_edges = (start, end);
+                let lp = self.loops.push(data);
+                self.ebb_loop_map[ebb] = lp.into();
+            }
+        }
+    }
+
+    // Intended to be called after `discover_loop_blocks`. Loops are created in reverse postorder
+    // of their headers, so a parent loop always comes before its children.
+    fn compute_depths(&mut self) {
+        for lp in self.loops() {
+            self.loops[lp].depth = match self.loops[lp].parent.expand() {
+                Some(parent) => self.loops[parent].depth + 1,
+                None => 1,
+            };
         }
     }
 
@@ -159,17 +307,17 @@ impl LoopAnalysis {
         domtree: &DominatorTree,
         layout: &Layout,
     ) {
-        let mut stack: Vec<Ebb> = Vec::new();
+        debug_assert!(self.stack.is_empty());
         // We handle each loop header in reverse order, corresponding to a pseudo postorder
         // traversal of the graph.
         for lp in self.loops().rev() {
             for (pred, pred_inst) in cfg.pred_iter(self.loops[lp].header) {
                 // We follow the back edges
                 if domtree.dominates(self.loops[lp].header, pred_inst, layout) {
-                    stack.push(pred);
+                    self.stack.push(pred);
                 }
             }
-            while let Some(node) = stack.pop() {
+            while let Some(node) = self.stack.pop() {
                 let continue_dfs: Option<Ebb>;
                 match self.ebb_loop_map[node].expand() {
                     None => {
@@ -214,7 +362,7 @@ impl LoopAnalysis {
                 // predecessors of that node
                 if let Some(continue_dfs) = continue_dfs {
                     for (pred, _) in cfg.pred_iter(continue_dfs) {
-                        stack.push(pred)
+                        self.stack.push(pred)
                     }
                 }
             }
@@ -223,11 +371,28 @@ impl LoopAnalysis {
     }
 }
 
+/// Iterate over the EBBs in a loop. See `LoopAnalysis::loop_ebbs()`.
+pub struct LoopEbbs<'a> {
+    loop_analysis: &'a LoopAnalysis,
+    lp: Loop,
+    ebbs: Ebbs<'a>,
+}
+
+impl<'a> Iterator for LoopEbbs<'a> {
+    type Item = Ebb;
+
+    fn next(&mut self) -> Option<Ebb> {
+        let loop_analysis = self.loop_analysis;
+        let lp = self.lp;
+        self.ebbs.find(|&ebb| loop_analysis.is_in_loop(ebb, lp))
+    }
+}
+
 #[cfg(test)]
 mod test {
 
     use cursor::{Cursor, FuncCursor};
-    use ir::{Function, InstBuilder, types};
+    use ir::{Ebb, Function, InstBuilder, types};
     use loop_analysis::{Loop, LoopAnalysis};
     use flowgraph::ControlFlowGraph;
     use dominator_tree::DominatorTree;
@@ -281,6 +446,21 @@ mod test {
         assert_eq!(loop_analysis.is_in_loop(ebb2, loops[0]), true);
         assert_eq!(loop_analysis.is_in_loop(ebb3, loops[0]), true);
         assert_eq!(loop_analysis.is_in_loop(ebb0, loops[1]), false);
+        assert_eq!(loop_analysis.loop_depth(loops[0]), 1);
+        assert_eq!(loop_analysis.loop_depth(loops[1]), 2);
+        assert_eq!(loop_analysis.ebb_loop_depth(ebb2), 2);
+        assert_eq!(loop_analysis.ebb_loop_depth(ebb3), 1);
+        assert_eq!(loop_analysis.back_edges(loops[0]).len(), 1);
+        assert_eq!(loop_analysis.back_edges(loops[0])[0].0, ebb3);
+        assert_eq!(loop_analysis.back_edges(loops[1])[0].0, ebb2);
+        assert_eq!(
+            loop_analysis
+                .loop_ebbs(loops[1], &func.layout)
+                .collect::<Vec<Ebb>>(),
+            [ebb1, ebb2]
+        );
+        assert_eq!(loop_analysis.preheader(loops[1], &func, &cfg).map(|p| p.0), Some(ebb0));
+        assert_eq!(loop_analysis.preheader(loops[0], &func, &cfg), None);
     }
 
     #[test]
@@ -342,4 +522,104 @@ mod test {
         assert_eq!(loop_analysis.is_in_loop(ebb4, loops[2]), true);
         assert_eq!(loop_analysis.is_in_loop(ebb5, loops[0]), true);
     }
+
+    #[test]
+    fn preheader_creation() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let ebb3 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        let arg = func.dfg.append_ebb_param(ebb2, types::I32);
+
+        let back_edge = {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            cur.ins().brnz(cond, ebb2, &[cond]);
+            cur.ins().jump(ebb1, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().jump(ebb2, &[cond]);
+
+            cur.insert_ebb(ebb2);
+            let back_edge = cur.ins().brnz(arg, ebb2, &[arg]);
+            cur.ins().jump(ebb3, &[]);
+
+            cur.insert_ebb(ebb3);
+            cur.ins().return_(&[]);
+            back_edge
+        };
+
+        let mut loop_analysis = LoopAnalysis::new();
+        let mut cfg = ControlFlowGraph::new();
+        let mut domtree = DominatorTree::new();
+        cfg.compute(&func);
+        domtree.compute(&func, &cfg);
+        loop_analysis.compute(&func, &cfg, &domtree);
+
+        let lp = loop_analysis.loops().next().unwrap();
+        assert_eq!(loop_analysis.back_edges(lp), &[(ebb2, back_edge)]);
+        assert_eq!(loop_analysis.preheader(lp, &func, &cfg), None);
+
+        let (preheader, jump) =
+            loop_analysis.ensure_preheader(lp, &mut func, &mut cfg, &mut domtree);
+        assert_eq!(func.layout.prev_ebb(ebb2), Some(preheader));
+        assert_eq!(func.dfg.ebb_params(preheader).len(), 1);
+        assert_eq!(func.layout.last_inst(preheader), Some(jump));
+        assert_eq!(loop_analysis.preheader(lp, &func, &cfg), Some((preheader, jump)));
+        assert_eq!(loop_analysis.ebb_loop_depth(preheader), 0);
+        assert_eq!(domtree.idom(ebb2), Some(jump));
+        assert_eq!(cfg.pred_iter(preheader).count(), 2);
+        assert_eq!(
+            loop_analysis.ensure_preheader(lp, &mut func, &mut cfg, &mut domtree),
+            (preheader, jump)
+        );
+    }
+
+    #[test]
+    fn conditional_entry_preheader() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let ebb2 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+
+        let entry = {
+            let mut cur = FuncCursor::new(&mut func);
+
+            cur.insert_ebb(ebb0);
+            let entry = cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().jump(ebb2, &[]);
+
+            cur.insert_ebb(ebb1);
+            cur.ins().brnz(cond, ebb1, &[]);
+            cur.ins().jump(ebb2, &[]);
+
+            cur.insert_ebb(ebb2);
+            cur.ins().return_(&[]);
+            entry
+        };
+
+        let mut loop_analysis = LoopAnalysis::new();
+        let mut cfg = ControlFlowGraph::new();
+        let mut domtree = DominatorTree::new();
+        cfg.compute(&func);
+        domtree.compute(&func, &cfg);
+        loop_analysis.compute(&func, &cfg, &domtree);
+
+        // The single entry branch is conditional, so code inserted before it would also be
+        // executed when the loop isn't entered.
+        let lp = loop_analysis.loops().next().unwrap();
+        assert_eq!(loop_analysis.preheader(lp, &func, &cfg), None);
+
+        let (preheader, jump) =
+            loop_analysis.ensure_preheader(lp, &mut func, &mut cfg, &mut domtree);
+        assert_ne!(preheader, ebb0);
+        assert_eq!(func.layout.last_inst(preheader), Some(jump));
+        assert_eq!(func.dfg[entry].branch_destination(), Some(preheader));
+        assert_eq!(cfg.pred_iter(preheader).collect::<Vec<_>>(), [(ebb0, entry)]);
+        assert_eq!(loop_analysis.preheader(lp, &func, &cfg), Some((preheader, jump)));
+    }
 }
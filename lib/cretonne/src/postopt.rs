@@ -0,0 +1,625 @@
+//! A post-legalization rewriting pass.
+//!
+//! After legalization, every instruction has an encoding, so it is possible to exploit target
+//! instruction forms that don't have a direct counterpart in the target-independent IL.
+//!
+//! This pass folds address arithmetic into the addressing modes of loads and stores:
+//!
+//! - An `iadd_imm` computing the address is folded into the offset of the memory access.
+//! - An `iadd` computing the address is turned into the base and index operands of a
+//!   `load_complex` or `store_complex`. An `ishl_imm` by 1-3 bits computing the index becomes the
+//!   scale of the index.
+//!
+//! The folded instructions are removed, so they are only folded when the memory access is the only
+//! user of their result. They must also be in the same EBB as the memory access, so folding never
+//! extends the live ranges of their operands across EBB boundaries or into loops. A rewritten
+//! instruction is only kept if the target ISA can encode it.
+//!
+//! It also fuses conditional branches with the comparisons computing their condition. A `brz` or
+//! `brnz` testing the result of an `icmp`, `icmp_imm`, or `fcmp` is replaced by a flags comparison
+//! followed by a `brif` or `brff`, so the boolean is never materialized in a register:
+//!
+//! - The comparison is repeated right in front of the branch, so nothing can clobber the flags in
+//!   between. The original comparison is removed once it has no other users.
+//! - When the condition is an EBB parameter and all the predecessors pass the result of the same
+//!   kind of comparison, the EBB gets parameters for the compared operands instead, and the
+//!   comparison is repeated in the EBB.
+
+use cursor::{Cursor, EncCursor};
+use entity::EntityMap;
+use flowgraph::ControlFlowGraph;
+use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value, ValueDef};
+use ir::condcodes::{CondCode, FloatCC, IntCC};
+use ir::immediates::{Imm64, Offset32};
+use isa::TargetIsa;
+use std::vec::Vec;
+use timing;
+
+/// Fold address computations into the loads and stores in `func`, and fuse its conditional
+/// branches with comparisons.
+pub fn do_postopt(func: &mut Function, cfg: &ControlFlowGraph, isa: &TargetIsa) {
+    let _tt = timing::postopt();
+    // Use counts of all values, computed the first time they are needed.
+    let mut use_counts = None;
+    // EBB parameters that have been replaced with the operands of the comparisons computing them.
+    let mut params = Vec::new();
+
+    let mut ebb = func.layout.entry_block();
+    while let Some(e) = ebb {
+        let mut cur = func.layout.first_inst(e);
+        while let Some(inst) = cur {
+            cur = func.layout.next_inst(inst);
+            if let Some(addr) = mem_address(func, inst) {
+                if is_foldable(func, inst, addr).is_some() {
+                    let counts = use_counts.get_or_insert_with(|| count_uses(func));
+                    optimize_address(func, inst, addr, counts, isa);
+                }
+            } else if let Some(cond) = branch_condition(func, inst) {
+                if Compare::of(func, cond).is_some() || is_param(func, cond) {
+                    let counts = use_counts.get_or_insert_with(|| count_uses(func));
+                    fuse_branch(func, cfg, inst, cond, &mut params, counts, isa);
+                }
+            }
+        }
+        ebb = func.layout.next_ebb(e);
+    }
+
+    // Remove the EBB parameters that were only used by fused branches.
+    if let Some(mut counts) = use_counts {
+        for (param, cmp) in params {
+            if cmp.is_some() && counts[param] == 0 {
+                remove_ebb_param(func, cfg, param, &mut counts);
+            }
+        }
+    }
+}
+
+/// Get the address operand of a load or store.
+fn mem_address(func: &Function, inst: Inst) -> Option<Value> {
+    match func.dfg[inst] {
+        InstructionData::Load { arg, .. } => Some(arg),
+        InstructionData::Store { args, .. } => Some(args[1]),
+        _ => None,
+    }
+}
+
+/// Get the instruction defining `value` if it is an address computation that could be folded
+/// into the memory access `inst`.
+fn is_foldable(func: &Function, inst: Inst, value: Value) -> Option<Inst> {
+    let def = match func.dfg.value_def(func.dfg.resolve_aliases(value)) {
+        ValueDef::Result(def, 0) => def,
+        _ => return None,
+    };
+    if func.layout.inst_ebb(def) != func.layout.inst_ebb(inst) {
+        return None;
+    }
+    match func.dfg[def].opcode() {
+        Opcode::IaddImm | Opcode::Iadd | Opcode::IshlImm | Opcode::Ishl => Some(def),
+        _ => None,
+    }
+}
+
+/// Get the defining instruction of `value` if it can be folded into `inst` and `inst` is its
+/// only user.
+fn single_use_def(
+    func: &Function,
+    inst: Inst,
+    value: Value,
+    counts: &EntityMap<Value, u32>,
+) -> Option<Inst> {
+    let def = is_foldable(func, inst, value)?;
+    if counts[func.dfg.resolve_aliases(value)] == 1 {
+        Some(def)
+    } else {
+        None
+    }
+}
+
+/// Count the uses of every value in `func`, with aliases resolved.
+fn count_uses(func: &Function) -> EntityMap<Value, u32> {
+    let mut counts = EntityMap::new();
+    counts.resize(func.dfg.num_values());
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                counts[func.dfg.resolve_aliases(arg)] += 1;
+            }
+        }
+    }
+    counts
+}
+
+/// Fold the computation of `addr` into the load or store `inst`.
+fn optimize_address(
+    func: &mut Function,
+    inst: Inst,
+    addr: Value,
+    counts: &EntityMap<Value, u32>,
+    isa: &TargetIsa,
+) {
+    let mut offset: i64 = match func.dfg[inst] {
+        InstructionData::Load { offset, .. } |
+        InstructionData::Store { offset, .. } => offset.into(),
+        _ => return,
+    };
+    // Instructions that become dead when the address is folded.
+    let mut dead = Vec::new();
+
+    // Fold an immediate addend into the offset.
+    let mut base = addr;
+    if let Some(def) = single_use_def(func, inst, base, counts) {
+        if let InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } = func.dfg[def]
+        {
+            let imm: i64 = imm.into();
+            offset += imm;
+            base = arg;
+            dead.push(def);
+        }
+    }
+    if offset != i64::from(offset as i32) {
+        return;
+    }
+
+    // Fold an `iadd` into a base and an index register, but only for loads and stores of full
+    // values. The extending and truncating accesses don't have complex forms.
+    let mut index = None;
+    let mut scale = 0;
+    let opcode = func.dfg[inst].opcode();
+    if opcode == Opcode::Load || opcode == Opcode::Store {
+        if let Some(def) = single_use_def(func, inst, base, counts) {
+            if let InstructionData::Binary {
+                opcode: Opcode::Iadd,
+                args,
+            } = func.dfg[def]
+            {
+                dead.push(def);
+                // Fold a shift of either operand into the scale of the index.
+                let (lhs, rhs) = (args[0], args[1]);
+                if let Some((shifted, amount)) = shift_amount(func, inst, rhs, counts, &mut dead) {
+                    base = lhs;
+                    index = Some(shifted);
+                    scale = amount;
+                } else if let Some((shifted, amount)) =
+                    shift_amount(func, inst, lhs, counts, &mut dead)
+                {
+                    base = rhs;
+                    index = Some(shifted);
+                    scale = amount;
+                } else {
+                    base = lhs;
+                    index = Some(rhs);
+                }
+            }
+        }
+    }
+
+    if dead.is_empty() {
+        return;
+    }
+
+    // Rewrite the instruction, keeping the original around in case the target can't encode the
+    // new form.
+    let original = func.dfg[inst].clone();
+    let offset = Offset32::new(offset as i32);
+    match index {
+        Some(index) => {
+            if opcode == Opcode::Load {
+                let flags = match original {
+                    InstructionData::Load { flags, .. } => flags,
+                    _ => unreachable!(),
+                };
+                let ty = func.dfg.ctrl_typevar(inst);
+                func.dfg.replace(inst).load_complex(
+                    ty,
+                    flags,
+                    base,
+                    index,
+                    scale,
+                    offset,
+                );
+            } else {
+                let (flags, data) = match original {
+                    InstructionData::Store { flags, args, .. } => (flags, args[0]),
+                    _ => unreachable!(),
+                };
+                func.dfg.replace(inst).store_complex(
+                    flags,
+                    data,
+                    base,
+                    index,
+                    scale,
+                    offset,
+                );
+            }
+        }
+        None => {
+            match func.dfg[inst] {
+                InstructionData::Load {
+                    ref mut arg,
+                    offset: ref mut off,
+                    ..
+                } => {
+                    *arg = base;
+                    *off = offset;
+                }
+                InstructionData::Store {
+                    ref mut args,
+                    offset: ref mut off,
+                    ..
+                } => {
+                    args[1] = base;
+                    *off = offset;
+                }
+                _ => unreachable!(),
+            }
+        }
+    }
+
+    if !encode(func, inst, isa) {
+        func.dfg[inst] = original;
+        return;
+    }
+
+    for def in dead {
+        func.layout.remove_inst(def);
+    }
+}
+
+/// If `value` is computed by a left shift by 1-3 bits that can be folded into `inst`, get the
+/// shifted value and the shift amount.
+///
+/// The shift instruction, and the `iconst` defining its amount if it has no other uses, are
+/// appended to `dead`.
+fn shift_amount(
+    func: &Function,
+    inst: Inst,
+    value: Value,
+    counts: &EntityMap<Value, u32>,
+    dead: &mut Vec<Inst>,
+) -> Option<(Value, u8)> {
+    let def = single_use_def(func, inst, value, counts)?;
+    let (arg, amount, amount_def): (Value, i64, Option<Inst>) = match func.dfg[def] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IshlImm,
+            arg,
+            imm,
+        } => (arg, imm.into(), None),
+        // The legalizer expands `ishl_imm` into an `ishl` by a constant on some targets.
+        InstructionData::Binary {
+            opcode: Opcode::Ishl,
+            args,
+        } => {
+            let amount = func.dfg.resolve_aliases(args[1]);
+            match func.dfg.value_def(amount) {
+                ValueDef::Result(amount_def, _) => {
+                    match func.dfg[amount_def] {
+                        InstructionData::UnaryImm {
+                            opcode: Opcode::Iconst,
+                            imm,
+                        } => {
+                            let unused = if counts[amount] == 1 {
+                                Some(amount_def)
+                            } else {
+                                None
+                            };
+                            (args[0], imm.into(), unused)
+                        }
+                        _ => return None,
+                    }
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+    if amount >= 1 && amount <= 3 {
+        dead.push(def);
+        dead.extend(amount_def);
+        Some((arg, amount as u8))
+    } else {
+        None
+    }
+}
+
+/// Set the encoding of `inst`, and return `false` if the target ISA can't encode it.
+fn encode(func: &mut Function, inst: Inst, isa: &TargetIsa) -> bool {
+    let ctrl_type = func.dfg.ctrl_typevar(inst);
+    match isa.encode(func, &func.dfg[inst], ctrl_type) {
+        Ok(enc) => {
+            func.encodings[inst] = enc;
+            true
+        }
+        Err(_) => false,
+    }
+}
+
+/// A condition that can be tested on the CPU flags.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Cond {
+    Int(IntCC),
+    IntImm(IntCC, Imm64),
+    Float(FloatCC),
+}
+
+/// A comparison computing a boolean branch condition.
+#[derive(Clone, Copy)]
+struct Compare {
+    cond: Cond,
+    lhs: Value,
+    /// The right-hand side operand, or `None` when it is an immediate.
+    rhs: Option<Value>,
+}
+
+impl Compare {
+    /// Get the comparison computing `value`, if any.
+    fn of(func: &Function, value: Value) -> Option<Compare> {
+        let inst = match func.dfg.value_def(value) {
+            ValueDef::Result(inst, 0) => inst,
+            _ => return None,
+        };
+        match func.dfg[inst] {
+            InstructionData::IntCompare {
+                opcode: Opcode::Icmp,
+                cond,
+                args,
+            } => Some(Compare {
+                cond: Cond::Int(cond),
+                lhs: args[0],
+                rhs: Some(args[1]),
+            }),
+            InstructionData::IntCompareImm {
+                opcode: Opcode::IcmpImm,
+                cond,
+                arg,
+                imm,
+            } => Some(Compare {
+                cond: Cond::IntImm(cond, imm),
+                lhs: arg,
+                rhs: None,
+            }),
+            InstructionData::FloatCompare {
+                opcode: Opcode::Fcmp,
+                cond,
+                args,
+            } => Some(Compare {
+                cond: Cond::Float(cond),
+                lhs: args[0],
+                rhs: Some(args[1]),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Does `other` test the same condition on operands of the same type?
+    fn same_shape(&self, other: &Compare, func: &Function) -> bool {
+        self.cond == other.cond &&
+            func.dfg.value_type(self.lhs) == func.dfg.value_type(other.lhs)
+    }
+}
+
+/// Get the condition tested by a `brz` or `brnz` instruction.
+fn branch_condition(func: &Function, inst: Inst) -> Option<Value> {
+    match func.dfg[inst] {
+        InstructionData::Branch {
+            opcode: Opcode::Brz,
+            ref args,
+            ..
+        } |
+        InstructionData::Branch {
+            opcode: Opcode::Brnz,
+            ref args,
+            ..
+        } => args.first(&func.dfg.value_lists).map(
+            |arg| func.dfg.resolve_aliases(arg),
+        ),
+        _ => None,
+    }
+}
+
+/// Is `value` a parameter of an EBB other than the entry block?
+fn is_param(func: &Function, value: Value) -> bool {
+    match func.dfg.value_def(value) {
+        ValueDef::Param(ebb, _) => Some(ebb) != func.layout.entry_block(),
+        _ => false,
+    }
+}
+
+/// Get the branch argument passed to `ebb`'s parameter number `num` by `branch`.
+fn branch_arg(func: &Function, branch: Inst, num: usize) -> Option<Value> {
+    func.dfg.inst_variable_args(branch).get(num).map(|&arg| {
+        func.dfg.resolve_aliases(arg)
+    })
+}
+
+/// Record a use of `value`.
+fn add_use(value: Value, counts: &mut EntityMap<Value, u32>) {
+    counts[value] += 1;
+}
+
+/// Drop a use of `value`, and remove the comparison computing it if it has no more users.
+fn release_use(func: &mut Function, value: Value, counts: &mut EntityMap<Value, u32>) {
+    let value = func.dfg.resolve_aliases(value);
+    counts[value] -= 1;
+    if counts[value] == 0 && Compare::of(func, value).is_some() {
+        let inst = func.dfg.value_def(value).unwrap_inst();
+        for i in 0..func.dfg.inst_args(inst).len() {
+            let arg = func.dfg.inst_args(inst)[i];
+            release_use(func, arg, counts);
+        }
+        func.layout.remove_inst(inst);
+    }
+}
+
+/// Fuse the `brz` or `brnz` instruction `inst` testing `cond` with the comparison computing it.
+///
+/// The `params` list remembers the EBB parameters that have already been considered, along with
+/// the comparison that replaces them.
+fn fuse_branch(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    inst: Inst,
+    cond: Value,
+    params: &mut Vec<(Value, Option<Compare>)>,
+    counts: &mut EntityMap<Value, u32>,
+    isa: &TargetIsa,
+) {
+    if let Some(cmp) = Compare::of(func, cond) {
+        if rewrite_branch(func, inst, cmp, counts, isa) {
+            release_use(func, cond, counts);
+        }
+        return;
+    }
+
+    // The condition is an EBB parameter.
+    if let Some(&(_, cmp)) = params.iter().find(|&&(p, _)| p == cond) {
+        if let Some(cmp) = cmp {
+            if rewrite_branch(func, inst, cmp, counts, isa) {
+                counts[cond] -= 1;
+            }
+        }
+        return;
+    }
+    let cmp = param_compare(func, cfg, cond, counts).and_then(|cmp| {
+        if rewrite_branch(func, inst, cmp, counts, isa) {
+            counts[cond] -= 1;
+            Some(cmp)
+        } else {
+            // The target can't test this condition on the flags, so drop the new parameters
+            // again.
+            remove_ebb_param(func, cfg, cmp.lhs, counts);
+            if let Some(rhs) = cmp.rhs {
+                remove_ebb_param(func, cfg, rhs, counts);
+            }
+            None
+        }
+    });
+    params.push((cond, cmp));
+}
+
+/// If all the predecessors of the EBB defining `param` pass it the result of the same kind of
+/// comparison, add EBB parameters for the compared operands and return the comparison of those
+/// parameters.
+fn param_compare(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    param: Value,
+    counts: &mut EntityMap<Value, u32>,
+) -> Option<Compare> {
+    let (ebb, num) = match func.dfg.value_def(param) {
+        ValueDef::Param(ebb, num) => (ebb, num),
+        _ => return None,
+    };
+
+    let mut shape: Option<Compare> = None;
+    for (_, branch) in cfg.pred_iter(ebb) {
+        let cmp = Compare::of(func, branch_arg(func, branch, num)?)?;
+        match shape {
+            Some(ref s) if !s.same_shape(&cmp, func) => return None,
+            Some(_) => {}
+            None => shape = Some(cmp),
+        }
+    }
+    let shape = shape?;
+
+    let lhs = func.dfg.append_ebb_param(ebb, func.dfg.value_type(shape.lhs));
+    let rhs = shape.rhs.map(|rhs| {
+        func.dfg.append_ebb_param(ebb, func.dfg.value_type(rhs))
+    });
+    for (_, branch) in cfg.pred_iter(ebb) {
+        let cmp = Compare::of(func, branch_arg(func, branch, num).unwrap()).unwrap();
+        func.dfg.append_inst_arg(branch, cmp.lhs);
+        add_use(cmp.lhs, counts);
+        if let Some(arg) = cmp.rhs {
+            func.dfg.append_inst_arg(branch, arg);
+            add_use(arg, counts);
+        }
+    }
+    Some(Compare {
+        cond: shape.cond,
+        lhs,
+        rhs,
+    })
+}
+
+/// Remove the EBB parameter `param` along with the corresponding branch arguments.
+fn remove_ebb_param(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    param: Value,
+    counts: &mut EntityMap<Value, u32>,
+) {
+    let (ebb, num): (Ebb, usize) = match func.dfg.value_def(param) {
+        ValueDef::Param(ebb, num) => (ebb, num),
+        _ => panic!("{} must be an EBB parameter", param),
+    };
+    for (_, branch) in cfg.pred_iter(ebb) {
+        let index = func.dfg.inst_fixed_args(branch).len() + num;
+        let mut args = func.dfg[branch].take_value_list().expect(
+            "branch without arguments",
+        );
+        let arg = args.get(index, &func.dfg.value_lists).unwrap();
+        args.remove(index, &mut func.dfg.value_lists);
+        func.dfg[branch].put_value_list(args);
+        release_use(func, arg, counts);
+    }
+    func.dfg.remove_ebb_param(param);
+}
+
+/// Replace the `brz` or `brnz` instruction `inst` with a flags comparison and a branch on the
+/// flags. Return `false` if the target ISA can't encode the new instructions.
+fn rewrite_branch(
+    func: &mut Function,
+    inst: Inst,
+    cmp: Compare,
+    counts: &mut EntityMap<Value, u32>,
+    isa: &TargetIsa,
+) -> bool {
+    let (opcode, destination, varargs) = match func.dfg[inst] {
+        InstructionData::Branch {
+            opcode,
+            destination,
+            ref args,
+        } => (
+            opcode,
+            destination,
+            args.as_slice(&func.dfg.value_lists)[1..].to_vec(),
+        ),
+        _ => return false,
+    };
+
+    // A `brz` branches when the condition is false.
+    let invert = opcode == Opcode::Brz;
+    let mut pos = EncCursor::new(func, isa).at_inst(inst);
+    pos.use_srcloc(inst);
+    let encoded = pos.encode_batch(|pos| {
+        let flags = match (cmp.cond, cmp.rhs) {
+            (Cond::Int(_), Some(rhs)) => pos.ins().ifcmp(cmp.lhs, rhs),
+            (Cond::IntImm(_, imm), None) => pos.ins().ifcmp_imm(cmp.lhs, imm),
+            (Cond::Float(_), Some(rhs)) => pos.ins().ffcmp(cmp.lhs, rhs),
+            _ => panic!("malformed comparison"),
+        };
+        match cmp.cond {
+            Cond::Int(cc) |
+            Cond::IntImm(cc, _) => {
+                let cc = if invert { cc.inverse() } else { cc };
+                pos.batch_replace(inst).brif(cc, flags, destination, &varargs);
+            }
+            Cond::Float(cc) => {
+                let cc = if invert { cc.inverse() } else { cc };
+                pos.batch_replace(inst).brff(cc, flags, destination, &varargs);
+            }
+        }
+    });
+    if encoded.is_none() {
+        return false;
+    }
+    add_use(cmp.lhs, counts);
+    if let Some(rhs) = cmp.rhs {
+        add_use(rhs, counts);
+    }
+    true
+}
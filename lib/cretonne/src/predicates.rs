@@ -6,9 +6,14 @@
 //! The predicates the operate on integer fields use `Into<i64>` as a shared trait bound. This
 //! bound is implemented by all the native integer types as well as `Imm64`.
 //!
+//! The predicates that operate on entity references also get the function being compiled so they
+//! can look up the referenced entity.
+//!
 //! Some of these predicates may be unused in certain ISA configurations, so we suppress the
 //! dead code warning.
 
+use ir::{FuncRef, Function, GlobalVar, GlobalVarData};
+
 /// Check that `x` is the same as `y`.
 #[allow(dead_code)]
 pub fn is_equal<T: Eq + Copy, O: Into<T> + Copy>(x: T, y: O) -> bool {
@@ -31,6 +36,21 @@ pub fn is_unsigned_int<T: Into<i64>>(x: T, wd: u8, sc: u8) -> bool {
     u == (u & m)
 }
 
+/// Check that the external function `func_ref` is colocated with `func`.
+#[allow(dead_code)]
+pub fn is_colocated_func(func_ref: FuncRef, func: &Function) -> bool {
+    func.dfg.ext_funcs[func_ref].colocated
+}
+
+/// Check that the global variable `global_var` is a symbol colocated with `func`.
+#[allow(dead_code)]
+pub fn is_colocated_data(global_var: GlobalVar, func: &Function) -> bool {
+    match func.global_vars[global_var] {
+        GlobalVarData::Sym { colocated, .. } => colocated,
+        _ => false,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
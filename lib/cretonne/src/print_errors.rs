@@ -1,25 +1,44 @@
 //! Utility routines for pretty-printing error messages.
 
 use ir;
+use ir::entities::AnyEntity;
 use verifier;
 use result::CtonError;
 use isa::TargetIsa;
-use std::fmt::Write;
+use std::fmt::{self, Write};
+use std::string::String;
+use std::vec::Vec;
+use write::write_ebb_header;
+
+/// Number of instructions to show before and after the offending one in an excerpt.
+const CONTEXT_INSTS: usize = 3;
 
 /// Pretty-print a verifier error.
+///
+/// The error is followed by an excerpt of the function around the offending entity, and excerpts
+/// around any related entities.
 pub fn pretty_verifier_error(
     func: &ir::Function,
     isa: Option<&TargetIsa>,
     err: &verifier::Error,
 ) -> String {
-    let mut msg = err.to_string();
-    match err.location {
-        ir::entities::AnyEntity::Inst(inst) => {
-            write!(msg, "\n{}: {}\n\n", inst, func.dfg.display_inst(inst, isa)).unwrap()
-        }
-        _ => msg.push('\n'),
+    let mut msg = String::new();
+    write_error(&mut msg, func, isa, err).unwrap();
+    msg
+}
+
+/// Pretty-print all the issues reported by `verifier::verify_function_collect`.
+pub fn pretty_verifier_issues(
+    func: &ir::Function,
+    isa: Option<&TargetIsa>,
+    issues: &[verifier::VerifierIssue],
+) -> String {
+    let mut msg = String::new();
+    for issue in issues {
+        write!(msg, "{}: ", issue.severity).unwrap();
+        write_error(&mut msg, func, isa, &issue.error).unwrap();
     }
-    write!(msg, "{}", func.display(isa)).unwrap();
+    write!(msg, "\n{}", func.display(isa)).unwrap();
     msg
 }
 
@@ -31,3 +50,128 @@ pub fn pretty_error(func: &ir::Function, isa: Option<&TargetIsa>, err: CtonError
         err.to_string()
     }
 }
+
+fn write_error(
+    w: &mut Write,
+    func: &ir::Function,
+    isa: Option<&TargetIsa>,
+    err: &verifier::Error,
+) -> fmt::Result {
+    writeln!(w, "{} [{}]", err, err.code)?;
+    write_excerpt(w, func, isa, err.location)?;
+    for &entity in &err.related {
+        writeln!(w, "note: related to {}", entity)?;
+        write_excerpt(w, func, isa, entity)?;
+    }
+    Ok(())
+}
+
+/// Write the part of `func` around `entity`, marking the line that defines it with `>`.
+fn write_excerpt(
+    w: &mut Write,
+    func: &ir::Function,
+    isa: Option<&TargetIsa>,
+    entity: AnyEntity,
+) -> fmt::Result {
+    let regs = isa.map(TargetIsa::register_info);
+    let regs = regs.as_ref();
+
+    match entity {
+        AnyEntity::Function => {
+            writeln!(w, "> function {}{}", func.name, func.signature.display(regs))
+        }
+        AnyEntity::Ebb(ebb) => write_ebb_excerpt(w, func, isa, ebb, None),
+        AnyEntity::Inst(inst) => {
+            match func.layout.inst_ebb(inst) {
+                Some(ebb) => write_ebb_excerpt(w, func, isa, ebb, Some(inst)),
+                None => writeln!(w, ">   {}", func.dfg.display_inst(inst, isa)),
+            }
+        }
+        AnyEntity::Value(value) => {
+            match func.dfg.value_def(value) {
+                ir::ValueDef::Result(inst, _) => write_excerpt(w, func, isa, inst.into()),
+                ir::ValueDef::Param(ebb, _) => write_excerpt(w, func, isa, ebb.into()),
+            }
+        }
+        AnyEntity::StackSlot(ss) => {
+            if func.stack_slots.is_valid(ss) {
+                writeln!(w, ">   {} = {}", ss, func.stack_slots[ss])
+            } else {
+                Ok(())
+            }
+        }
+        AnyEntity::GlobalVar(gv) => {
+            match func.global_vars.get(gv) {
+                Some(data) => writeln!(w, ">   {} = {}", gv, data),
+                None => Ok(()),
+            }
+        }
+        AnyEntity::Heap(heap) => {
+            match func.heaps.get(heap) {
+                Some(data) => writeln!(w, ">   {} = {}", heap, data),
+                None => Ok(()),
+            }
+        }
+        AnyEntity::Table(table) => {
+            match func.tables.get(table) {
+                Some(data) => writeln!(w, ">   {} = {}", table, data),
+                None => Ok(()),
+            }
+        }
+        AnyEntity::SigRef(sig) => {
+            match func.dfg.signatures.get(sig) {
+                Some(data) => writeln!(w, ">   {} = {}", sig, data.display(regs)),
+                None => Ok(()),
+            }
+        }
+        AnyEntity::FuncRef(fnref) => {
+            match func.dfg.ext_funcs.get(fnref) {
+                Some(data) => writeln!(w, ">   {} = {}", fnref, data),
+                None => Ok(()),
+            }
+        }
+        AnyEntity::JumpTable(jt) => {
+            match func.jump_tables.get(jt) {
+                Some(data) => writeln!(w, ">   {} = {}", jt, data),
+                None => Ok(()),
+            }
+        }
+    }
+}
+
+/// Write the header of `ebb` and the instructions around `inst`, or the first instructions of the
+/// EBB if `inst` is `None`.
+fn write_ebb_excerpt(
+    w: &mut Write,
+    func: &ir::Function,
+    isa: Option<&TargetIsa>,
+    ebb: ir::Ebb,
+    inst: Option<ir::Inst>,
+) -> fmt::Result {
+    if !func.layout.is_ebb_inserted(ebb) {
+        return writeln!(w, ">   {} is not in the layout", ebb);
+    }
+
+    w.write_str(if inst.is_none() { ">" } else { " " })?;
+    write_ebb_header(w, func, isa, ebb, 8)?;
+
+    let insts: Vec<ir::Inst> = func.layout.ebb_insts(ebb).collect();
+    let pos = inst.and_then(|inst| insts.iter().position(|&i| i == inst));
+    let (start, end) = match pos {
+        Some(pos) => (pos.saturating_sub(CONTEXT_INSTS), pos + CONTEXT_INSTS + 1),
+        None => (0, CONTEXT_INSTS),
+    };
+    let end = end.min(insts.len());
+
+    if start > 0 {
+        writeln!(w, "        ...")?;
+    }
+    for &i in &insts[start..end] {
+        let marker = if Some(i) == inst { ">" } else { " " };
+        writeln!(w, "{}       {}", marker, func.dfg.display_inst(i, isa))?;
+    }
+    if end < insts.len() {
+        writeln!(w, "        ...")?;
+    }
+    Ok(())
+}
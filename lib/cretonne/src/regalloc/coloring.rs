@@ -45,7 +45,7 @@
 use cursor::{Cursor, EncCursor};
 use dominator_tree::DominatorTree;
 use ir::{Ebb, Inst, Value, Function, Layout, ValueLoc, SigRef};
-use ir::{InstBuilder, AbiParam, ArgumentLoc, ValueDef};
+use ir::{InstBuilder, AbiParam, ArgumentLoc, ValueDef, ExpandedProgramPoint};
 use isa::{RegUnit, RegClass, RegInfo, regs_overlap};
 use isa::{TargetIsa, EncInfo, RecipeConstraints, OperandConstraint, ConstraintKind};
 use packed_option::PackedOption;
@@ -319,6 +319,10 @@ impl<'a> Context<'a> {
                 &self.reginfo,
                 &self.divert,
             );
+            // A `try_call` is also a branch to its landing pad, which takes no arguments.
+            if let Some(pad) = self.cur.func.dfg[inst].branch_destination() {
+                self.undivert_regs(|lr, ctx| lr.is_livein(pad, ctx));
+            }
         } else if self.cur.func.dfg[inst].opcode().is_return() {
             program_input_abi(
                 &mut self.solver,
@@ -809,7 +813,12 @@ impl<'a> Context<'a> {
                 ConstraintKind::FixedTied(_) |
                 ConstraintKind::Stack => continue,
                 ConstraintKind::Reg => {
-                    self.solver.add_def(lv.value, op.regclass, !lv.is_local);
+                    let hint = if lv.is_local {
+                        self.fixed_use_hint(lv.value)
+                    } else {
+                        None
+                    };
+                    self.solver.add_def(lv.value, op.regclass, !lv.is_local, hint);
                 }
                 ConstraintKind::Tied(num) => {
                     // Find the input operand we're tied to.
@@ -840,6 +849,48 @@ impl<'a> Context<'a> {
         }
     }
 
+    /// Find the fixed register required by the last use of the local value `value`, if any.
+    ///
+    /// Values that are consumed by a fixed register operand or an ABI register argument are
+    /// best defined in that register, so the use doesn't need a `regmove`. This is common for
+    /// the copies inserted before instructions like Intel's `div` which read and clobber fixed
+    /// registers.
+    fn fixed_use_hint(&self, value: Value) -> Option<RegUnit> {
+        let user = match self.liveness[value].def_local_end().into() {
+            ExpandedProgramPoint::Inst(user) => user,
+            ExpandedProgramPoint::Ebb(_) => return None,
+        };
+        let dfg = &self.cur.func.dfg;
+
+        let enc = self.cur.func.encodings[user];
+        if let Some(constraints) = self.encinfo.operand_constraints(enc) {
+            for (op, &arg) in constraints.ins.iter().zip(dfg.inst_args(user)) {
+                match op.kind {
+                    ConstraintKind::FixedReg(reg) |
+                    ConstraintKind::FixedTied(reg) if arg == value => return Some(reg),
+                    _ => {}
+                }
+            }
+        }
+
+        let abi_types = if let Some(sig) = dfg.call_signature(user) {
+            &dfg.signatures[sig].params
+        } else if dfg[user].opcode().is_return() {
+            &self.cur.func.signature.returns
+        } else {
+            return None;
+        };
+        abi_types
+            .iter()
+            .zip(dfg.inst_variable_args(user))
+            .filter(|&(_, &arg)| arg == value)
+            .filter_map(|(abi, _)| match abi.location {
+                ArgumentLoc::Reg(reg) => Some(reg),
+                _ => None,
+            })
+            .next()
+    }
+
     /// Try harder to find a solution to the constraint problem since `quick_solve()` failed.
     ///
     /// We may need to move more registers around before a solution is possible. Use an iterative
@@ -914,7 +965,7 @@ impl<'a> Context<'a> {
             Table(jt) => {
                 let lr = &self.liveness[value];
                 !lr.is_local() &&
-                    self.cur.func.jump_tables[jt].entries().any(|(_, ebb)| {
+                    self.cur.func.jump_tables[jt].destinations().any(|ebb| {
                         lr.is_livein(ebb, ctx)
                     })
             }
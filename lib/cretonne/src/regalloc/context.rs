@@ -13,9 +13,11 @@ use regalloc::coloring::Coloring;
 use regalloc::live_value_tracker::LiveValueTracker;
 use regalloc::liveness::Liveness;
 use regalloc::reload::Reload;
-use regalloc::spilling::Spilling;
+use regalloc::spill_slots::{SpillSlots, SpillSlotStats};
+use regalloc::spilling::{Spilling, TiedCopyStats};
 use regalloc::virtregs::VirtRegs;
 use result::CtonResult;
+use settings::OptLevel;
 use timing;
 use topo_order::TopoOrder;
 use verifier::{verify_context, verify_liveness, verify_cssa, verify_locations};
@@ -28,6 +30,7 @@ pub struct Context {
     topo: TopoOrder,
     tracker: LiveValueTracker,
     spilling: Spilling,
+    spill_slots: SpillSlots,
     reload: Reload,
     coloring: Coloring,
 }
@@ -45,6 +48,7 @@ impl Context {
             topo: TopoOrder::new(),
             tracker: LiveValueTracker::new(),
             spilling: Spilling::new(),
+            spill_slots: SpillSlots::new(),
             reload: Reload::new(),
             coloring: Coloring::new(),
         }
@@ -58,10 +62,21 @@ impl Context {
         self.topo.clear();
         self.tracker.clear();
         self.spilling.clear();
+        self.spill_slots.clear();
         self.reload.clear();
         self.coloring.clear();
     }
 
+    /// Get statistics about the copies inserted for tied operands by the last call to `run()`.
+    pub fn tied_copy_stats(&self) -> TiedCopyStats {
+        self.spilling.tied_copy_stats()
+    }
+
+    /// Get statistics about the spill slots created by the last call to `run()`.
+    pub fn spill_slot_stats(&self) -> SpillSlotStats {
+        self.spill_slots.stats()
+    }
+
     /// Allocate registers in `func`.
     ///
     /// After register allocation, all values in `func` have been assigned to a register or stack
@@ -108,6 +123,7 @@ impl Context {
 
 
         // Pass: Spilling.
+        let first_spill_slot = func.stack_slots.next_key();
         self.spilling.run(
             isa,
             func,
@@ -124,6 +140,18 @@ impl Context {
             verify_cssa(func, cfg, domtree, &self.liveness, &self.virtregs)?;
         }
 
+        // Pass: Share spill slots between values that don't interfere.
+        if isa.flags().opt_level() != OptLevel::Fastest {
+            self.spill_slots.run(
+                func,
+                &self.liveness,
+                domtree,
+                first_spill_slot,
+            );
+        } else {
+            self.spill_slots.clear();
+        }
+
         // Pass: Reload.
         self.reload.run(
             isa,
@@ -43,6 +43,17 @@ pub struct RegDiversions {
     current: Vec<Diversion>,
 }
 
+impl Clone for RegDiversions {
+    fn clone(&self) -> Self {
+        Self { current: self.current.clone() }
+    }
+
+    // Reuse the allocation in `self`.
+    fn clone_from(&mut self, source: &Self) {
+        self.current.clone_from(&source.current)
+    }
+}
+
 impl RegDiversions {
     /// Create a new empty diversion tracker.
     pub fn new() -> Self {
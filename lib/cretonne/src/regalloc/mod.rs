@@ -8,16 +8,19 @@ pub mod allocatable_set;
 pub mod live_value_tracker;
 pub mod coloring;
 pub mod virtregs;
+pub mod affinity;
 
-mod affinity;
 mod coalescing;
 mod context;
 mod diversion;
 mod pressure;
 mod reload;
 mod solver;
+mod spill_slots;
 mod spilling;
 
 pub use self::allocatable_set::AllocatableSet;
 pub use self::context::Context;
 pub use self::diversion::RegDiversions;
+pub use self::spill_slots::SpillSlotStats;
+pub use self::spilling::TiedCopyStats;
@@ -146,6 +146,9 @@ pub struct Variable {
     /// The assigned register unit after a full solution was found.
     pub solution: RegUnit,
 
+    /// Preferred register for a defined value, used when it is available.
+    hint: Option<RegUnit>,
+
     /// Any solution must belong to the constraint register class.
     constraint: RegClass,
 }
@@ -161,10 +164,16 @@ impl Variable {
             is_global: false,
             domain: 0,
             solution: !0,
+            hint: None,
         }
     }
 
-    fn new_def(value: Value, constraint: RegClass, is_global: bool) -> Variable {
+    fn new_def(
+        value: Value,
+        constraint: RegClass,
+        is_global: bool,
+        hint: Option<RegUnit>,
+    ) -> Variable {
         Variable {
             value,
             constraint,
@@ -174,6 +183,7 @@ impl Variable {
             is_global,
             domain: 0,
             solution: !0,
+            hint,
         }
     }
 
@@ -205,6 +215,25 @@ impl Variable {
         }
         r.iter(self.constraint)
     }
+
+    /// Get the hinted register if it is one of the possible register choices.
+    fn available_hint(
+        &self,
+        iregs: &AllocatableSet,
+        oregs: &AllocatableSet,
+        gregs: &AllocatableSet,
+    ) -> Option<RegUnit> {
+        let reg = self.hint?;
+        let rc = self.constraint;
+        if rc.contains(reg) && oregs.is_avail(rc, reg) &&
+            (!self.is_input || iregs.is_avail(rc, reg)) &&
+            (!self.is_global || gregs.is_avail(rc, reg))
+        {
+            Some(reg)
+        } else {
+            None
+        }
+    }
 }
 
 impl fmt::Display for Variable {
@@ -828,10 +857,17 @@ impl Solver {
     /// Add a defined output value.
     ///
     /// This is similar to `add_var`, except the value doesn't have a prior register assignment.
-    pub fn add_def(&mut self, value: Value, constraint: RegClass, is_global: bool) {
+    /// The solver prefers the `hint` register when it is available.
+    pub fn add_def(
+        &mut self,
+        value: Value,
+        constraint: RegClass,
+        is_global: bool,
+        hint: Option<RegUnit>,
+    ) {
         debug_assert!(self.inputs_done);
         self.vars.push(
-            Variable::new_def(value, constraint, is_global),
+            Variable::new_def(value, constraint, is_global, hint),
         );
     }
 
@@ -945,7 +981,10 @@ impl Solver {
 
         for v in &mut self.vars {
             let rc = v.constraint;
-            let reg = match v.iter(&iregs, &oregs, &gregs).next() {
+            let choice = v.available_hint(&iregs, &oregs, &gregs).or_else(|| {
+                v.iter(&iregs, &oregs, &gregs).next()
+            });
+            let reg = match choice {
                 Some(reg) => reg,
                 None => {
                     // If `v` must avoid global interference, there is not point in requesting
@@ -1265,6 +1304,39 @@ mod tests {
         );
     }
 
+    #[test]
+    fn def_hints() {
+        let isa = arm32().expect("This test requires arm32 support");
+        let reginfo = isa.register_info();
+        let gpr = rc_by_name(&reginfo, "GPR");
+        let r0 = gpr.unit(0);
+        let r1 = gpr.unit(1);
+        let r2 = gpr.unit(2);
+        let gregs = AllocatableSet::new();
+        let mut regs = AllocatableSet::new();
+        let mut solver = Solver::new();
+        let v10 = Value::new(10);
+        let v11 = Value::new(11);
+
+        // The hinted register is used when it is available.
+        solver.reset(&regs);
+        solver.inputs_done();
+        solver.add_def(v10, gpr, false, Some(r2));
+        solver.add_def(v11, gpr, false, None);
+        assert!(solver.quick_solve(&gregs).is_ok());
+        assert_eq!(solver.vars()[0].solution, r2);
+        assert_eq!(solver.vars()[1].solution, r0);
+
+        // Otherwise it is ignored.
+        regs.take(gpr, r2);
+        solver.reset(&regs);
+        solver.inputs_done();
+        solver.add_fixed_output(gpr, r0);
+        solver.add_def(v10, gpr, false, Some(r2));
+        assert!(solver.quick_solve(&gregs).is_ok());
+        assert_eq!(solver.vars()[0].solution, r1);
+    }
+
     #[test]
     fn harder_move_cycles() {
         let isa = arm32().expect("This test requires arm32 support");
@@ -0,0 +1,288 @@
+//! Spill slot sharing.
+//!
+//! The spilling pass creates a new spill slot for every virtual register it spills. Many of
+//! those values are never live at the same time, so they can share a stack slot.
+//!
+//! This pass runs right after spilling. The spill slots created by the spilling pass are visited
+//! in the order of their first definition, and each slot is given the first color of the same size
+//! whose values don't interfere with the values in the slot. The slots are renumbered so each
+//! color gets its own spill slot, and the slots left over are removed from the function.
+//!
+//! Checking a slot against a color compares the live ranges of their values pairwise, so the
+//! total number of checks is limited to keep the compile time of large functions in check. Slots
+//! visited after the limit is reached get a color of their own.
+//!
+//! The live ranges of spilled values are only shortened by the following passes, so the shared
+//! slots remain valid through reloading and coloring.
+
+use dominator_tree::DominatorTree;
+use entity::EntityRef;
+use ir::{Function, StackSlot, Value, ValueLoc};
+use ir::stackslot::StackSize;
+use regalloc::liveness::Liveness;
+use std::vec::Vec;
+use timing;
+
+/// Maximum number of value pairs checked for interference in one function.
+const MAX_CHECKS: usize = 100_000;
+
+/// Persistent data structures for the spill slot sharing pass.
+pub struct SpillSlots {
+    /// Spilled values and the index of their spill slot, relative to the first slot created by
+    /// the spilling pass.
+    values: Vec<(usize, Value)>,
+
+    /// The order of each spill slot's first value definition, or `None` for unused slots.
+    first_def: Vec<Option<usize>>,
+
+    /// The color assigned to each spill slot.
+    colors: Vec<Option<usize>>,
+
+    /// The size of the slots assigned each color.
+    color_sizes: Vec<StackSize>,
+
+    /// The last value given each color, as an index into `members`.
+    color_last: Vec<Option<usize>>,
+
+    /// The values given a color, each linked to the previous value given the same color.
+    members: Vec<(Value, Option<usize>)>,
+
+    /// Statistics about the last function processed.
+    stats: SpillSlotStats,
+}
+
+/// Statistics about the spill slots created by the register allocator in a function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct SpillSlotStats {
+    /// Number of spill slots created by the spilling pass.
+    pub slots_before: usize,
+
+    /// Number of spill slots left after sharing slots between values that don't interfere.
+    pub slots_after: usize,
+
+    /// Total size in bytes of the spill slots created by the spilling pass.
+    pub size_before: StackSize,
+
+    /// Total size in bytes of the spill slots left after sharing.
+    pub size_after: StackSize,
+}
+
+impl SpillSlots {
+    /// Create a new spill slot sharing data structure.
+    pub fn new() -> Self {
+        Self {
+            values: Vec::new(),
+            first_def: Vec::new(),
+            colors: Vec::new(),
+            color_sizes: Vec::new(),
+            color_last: Vec::new(),
+            members: Vec::new(),
+            stats: SpillSlotStats::default(),
+        }
+    }
+
+    /// Clear all data structures in this spill slot sharing pass.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.first_def.clear();
+        self.colors.clear();
+        self.color_sizes.clear();
+        self.color_last.clear();
+        self.members.clear();
+        self.stats = SpillSlotStats::default();
+    }
+
+    /// Get statistics about the spill slots in the last function processed.
+    pub fn stats(&self) -> SpillSlotStats {
+        self.stats
+    }
+
+    /// Share the spill slots from `first` onwards between values that don't interfere.
+    ///
+    /// All the stack slots from `first` onwards must be spill slots that are only referenced by
+    /// value locations.
+    pub fn run(
+        &mut self,
+        func: &mut Function,
+        liveness: &Liveness,
+        domtree: &DominatorTree,
+        first: StackSlot,
+    ) {
+        let _tt = timing::ra_spill_slots();
+        self.clear();
+
+        let num_slots = func.stack_slots.next_key().index() - first.index();
+        self.stats.slots_before = num_slots;
+        self.stats.size_before = (0..num_slots)
+            .map(|slot| func.stack_slots[StackSlot::new(first.index() + slot)].size)
+            .sum();
+
+        self.collect_values(func, domtree, first, num_slots);
+        self.color_slots(func, liveness, first, num_slots);
+
+        // Move the values into the slot for their color, and drop the slots that are left over.
+        for &(slot, value) in &self.values {
+            let color = self.colors[slot].expect("uncolored spill slot");
+            func.locations[value] = ValueLoc::Stack(StackSlot::new(first.index() + color));
+        }
+        for (color, &size) in self.color_sizes.iter().enumerate() {
+            func.stack_slots[StackSlot::new(first.index() + color)].size = size;
+        }
+        func.stack_slots.truncate(
+            StackSlot::new(first.index() + self.color_sizes.len()),
+        );
+
+        self.stats.slots_after = self.color_sizes.len();
+        self.stats.size_after = self.color_sizes.iter().sum();
+        dbg!(
+            "Shared {} spill slots of {} bytes in {} slots of {} bytes",
+            self.stats.slots_before,
+            self.stats.size_before,
+            self.stats.slots_after,
+            self.stats.size_after
+        );
+    }
+
+    /// Collect the values assigned to the spill slots from `first` onwards.
+    ///
+    /// The values are grouped by spill slot, and the groups are ordered by their first value
+    /// definition in the dominator tree reverse post-order.
+    fn collect_values(
+        &mut self,
+        func: &Function,
+        domtree: &DominatorTree,
+        first: StackSlot,
+        num_slots: usize,
+    ) {
+        for value in func.locations.keys() {
+            if let ValueLoc::Stack(ss) = func.locations[value] {
+                if ss.index() >= first.index() && func.dfg.value_is_attached(value) {
+                    self.values.push((ss.index() - first.index(), value));
+                }
+            }
+        }
+        self.values.sort_by(|&(_, a), &(_, b)| {
+            domtree.rpo_cmp(func.dfg.value_def(a), func.dfg.value_def(b), &func.layout)
+        });
+
+        self.first_def.resize(num_slots, None);
+        for (idx, &(slot, _)) in self.values.iter().enumerate() {
+            if self.first_def[slot].is_none() {
+                self.first_def[slot] = Some(idx);
+            }
+        }
+        // The sort is stable, so the values in each slot stay in definition order.
+        let first_def = &self.first_def;
+        self.values.sort_by_key(|&(slot, _)| first_def[slot]);
+    }
+
+    /// Assign colors to the spill slots such that slots with interfering values get different
+    /// colors.
+    fn color_slots(
+        &mut self,
+        func: &Function,
+        liveness: &Liveness,
+        first: StackSlot,
+        num_slots: usize,
+    ) {
+        self.colors.resize(num_slots, None);
+        let mut budget = MAX_CHECKS;
+
+        let mut start = 0;
+        while start < self.values.len() {
+            let slot = self.values[start].0;
+            let end = start +
+                self.values[start..]
+                    .iter()
+                    .take_while(|&&(s, _)| s == slot)
+                    .count();
+            let size = func.stack_slots[StackSlot::new(first.index() + slot)].size;
+            let color = self.find_color(func, liveness, start, end, size, &mut budget);
+            self.colors[slot] = Some(color);
+            for idx in start..end {
+                let value = self.values[idx].1;
+                self.members.push((value, self.color_last[color]));
+                self.color_last[color] = Some(self.members.len() - 1);
+            }
+            start = end;
+        }
+
+        // Slots without any values can share any color of the same size.
+        for slot in 0..num_slots {
+            if self.colors[slot].is_none() {
+                let size = func.stack_slots[StackSlot::new(first.index() + slot)].size;
+                let color = self.find_color(func, liveness, 0, 0, size, &mut budget);
+                self.colors[slot] = Some(color);
+            }
+        }
+    }
+
+    /// Find a color of `size` bytes for the values in `self.values[start..end]`, creating a new
+    /// color if none of the existing ones can be used.
+    ///
+    /// The colors are numbered in the order they are created.
+    fn find_color(
+        &mut self,
+        func: &Function,
+        liveness: &Liveness,
+        start: usize,
+        end: usize,
+        size: StackSize,
+        budget: &mut usize,
+    ) -> usize {
+        for color in 0..self.color_sizes.len() {
+            if *budget == 0 {
+                break;
+            }
+            if self.color_sizes[color] == size &&
+                !self.interferes(func, liveness, start, end, color, budget)
+            {
+                return color;
+            }
+        }
+        self.color_sizes.push(size);
+        self.color_last.push(None);
+        self.color_sizes.len() - 1
+    }
+
+    /// Do any of the values in `self.values[start..end]` interfere with the values given `color`?
+    ///
+    /// Each pair of values checked uses up one unit of `budget`. Once the budget is exhausted, all
+    /// colors are considered to interfere.
+    fn interferes(
+        &self,
+        func: &Function,
+        liveness: &Liveness,
+        start: usize,
+        end: usize,
+        color: usize,
+        budget: &mut usize,
+    ) -> bool {
+        let ctx = liveness.context(&func.layout);
+        for &(_, value) in &self.values[start..end] {
+            let def = func.dfg.value_def(value).into();
+            let def_ebb = func.layout.pp_ebb(def);
+
+            // Values defined recently are the most likely to interfere, so start from the last
+            // value given the color.
+            let mut next = self.color_last[color];
+            while let Some(idx) = next {
+                if *budget == 0 {
+                    return true;
+                }
+                *budget -= 1;
+
+                let (other, prev) = self.members[idx];
+                let other_def = func.dfg.value_def(other).into();
+                let other_ebb = func.layout.pp_ebb(other_def);
+                if liveness[value].overlaps_def(other_def, other_ebb, ctx) ||
+                    liveness[other].overlaps_def(def, def_ebb, ctx)
+                {
+                    return true;
+                }
+                next = prev;
+            }
+        }
+        false
+    }
+}
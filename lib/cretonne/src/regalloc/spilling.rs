@@ -10,13 +10,18 @@
 //! inserting copies. The extra constraints are:
 //!
 //! 1. A value used by a tied operand must be killed by the instruction. This is resolved by
-//!    inserting a copy to a temporary value when necessary.
+//!    inserting a copy to a temporary value when necessary. The operands of a commutative
+//!    instruction are swapped instead when that makes the tied operand use a killed value.
 //! 2. When the same value is used more than once by an instruction, the operand constraints must
 //!    be compatible. Otherwise, the value must be copied into a new register for some of the
 //!    operands.
+//!
+//! When `opt_level` is `min_size`, the spilling pass prefers to spill the values with the fewest
+//! uses since every use of a spilled value needs a `fill` instruction.
 
 use cursor::{Cursor, EncCursor};
 use dominator_tree::DominatorTree;
+use entity::EntityMap;
 use ir::{InstBuilder, Function, Ebb, Inst, Value, ValueLoc, SigRef};
 use isa::registers::{RegClassMask, RegClassIndex};
 use isa::{TargetIsa, RegInfo, EncInfo, RecipeConstraints, ConstraintKind};
@@ -25,6 +30,8 @@ use regalloc::live_value_tracker::{LiveValue, LiveValueTracker};
 use regalloc::liveness::Liveness;
 use regalloc::pressure::Pressure;
 use regalloc::virtregs::VirtRegs;
+use settings::OptLevel;
+use std::cmp::Ordering;
 use std::fmt;
 use std::vec::Vec;
 use timing;
@@ -34,6 +41,18 @@ use topo_order::TopoOrder;
 pub struct Spilling {
     spills: Vec<Value>,
     reg_uses: Vec<RegUse>,
+    use_counts: EntityMap<Value, u32>,
+    stats: TiedCopyStats,
+}
+
+/// Statistics about the copies needed for tied operands in a function.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TiedCopyStats {
+    /// Number of copies inserted because a tied operand's value is live after the instruction.
+    pub inserted: usize,
+
+    /// Number of copies avoided by swapping the operands of a commutative instruction.
+    pub eliminated: usize,
 }
 
 /// Context data structure that gets instantiated once per pass.
@@ -61,6 +80,12 @@ struct Context<'a> {
 
     // Uses of register values in the current instruction.
     reg_uses: &'a mut Vec<RegUse>,
+
+    // Number of uses of each value when optimizing for size.
+    use_counts: Option<&'a EntityMap<Value, u32>>,
+
+    // Tied operand copies in the current function.
+    stats: &'a mut TiedCopyStats,
 }
 
 impl Spilling {
@@ -69,6 +94,8 @@ impl Spilling {
         Self {
             spills: Vec::new(),
             reg_uses: Vec::new(),
+            use_counts: EntityMap::new(),
+            stats: TiedCopyStats::default(),
         }
     }
 
@@ -76,6 +103,13 @@ impl Spilling {
     pub fn clear(&mut self) {
         self.spills.clear();
         self.reg_uses.clear();
+        self.use_counts.clear();
+        self.stats = TiedCopyStats::default();
+    }
+
+    /// Get the tied operand statistics for the last function processed by `run()`.
+    pub fn tied_copy_stats(&self) -> TiedCopyStats {
+        self.stats
     }
 
     /// Run the spilling algorithm over `func`.
@@ -93,6 +127,13 @@ impl Spilling {
         dbg!("Spilling for:\n{}", func.display(isa));
         let reginfo = isa.register_info();
         let usable_regs = isa.allocatable_registers(func);
+        self.stats = TiedCopyStats::default();
+        let use_counts = if isa.flags().opt_level() == OptLevel::MinSize {
+            count_uses(func, &mut self.use_counts);
+            Some(&self.use_counts)
+        } else {
+            None
+        };
         let mut ctx = Context {
             cur: EncCursor::new(func, isa),
             reginfo: isa.register_info(),
@@ -104,6 +145,8 @@ impl Spilling {
             pressure: Pressure::new(&reginfo, &usable_regs),
             spills: &mut self.spills,
             reg_uses: &mut self.reg_uses,
+            use_counts,
+            stats: &mut self.stats,
         };
         ctx.run(tracker)
     }
@@ -259,6 +302,24 @@ impl<'a> Context<'a> {
         // Update the live value tracker with this instruction.
         let (throughs, kills, defs) = tracker.process_inst(inst, &self.cur.func.dfg, self.liveness);
 
+        // A `try_call` can unwind to its landing pad without restoring any registers, so the
+        // values that are live in to the landing pad must be spilled too, even if the call is
+        // their last use on the normal path.
+        if call_sig.is_some() {
+            if let Some(pad) = self.cur.func.dfg[inst].branch_destination() {
+                for lv in kills {
+                    if lv.affinity.is_reg() && !self.spills.contains(&lv.value) &&
+                        self.liveness[lv.value].is_livein(
+                            pad,
+                            self.liveness.context(&self.cur.func.layout),
+                        )
+                    {
+                        self.spill_reg(lv.value);
+                    }
+                }
+            }
+        }
+
         // Remove kills from the pressure tracker.
         self.free_regs(kills);
 
@@ -312,6 +373,10 @@ impl<'a> Context<'a> {
     // class operand, they two are compatible. We are also assuming that two register class
     // operands are always compatible.
     fn collect_reg_uses(&mut self, inst: Inst, ebb: Ebb, constraints: &RecipeConstraints) {
+        if constraints.tied_ops && self.cur.func.dfg[inst].opcode().is_commutative() {
+            self.commute_tied_operands(inst, ebb, constraints);
+        }
+
         let args = self.cur.func.dfg.inst_args(inst);
         for (idx, (op, &arg)) in constraints.ins.iter().zip(args).enumerate() {
             let mut reguse = RegUse::new(arg, idx, op.regclass.into());
@@ -342,6 +407,33 @@ impl<'a> Context<'a> {
         }
     }
 
+    // Swap the operands of a commutative instruction if the first operand is tied to an output and
+    // its value lives on, while the second operand's value is killed by the instruction. The tied
+    // operand then doesn't need a copy.
+    fn commute_tied_operands(&mut self, inst: Inst, ebb: Ebb, constraints: &RecipeConstraints) {
+        if constraints.ins.len() != 2 {
+            return;
+        }
+        let (op0, op1) = (&constraints.ins[0], &constraints.ins[1]);
+        match (op0.kind, op1.kind) {
+            (ConstraintKind::Tied(_), ConstraintKind::Reg) if op0.regclass == op1.regclass => {}
+            _ => return,
+        }
+
+        let (arg0, arg1) = {
+            let args = self.cur.func.dfg.inst_args(inst);
+            (args[0], args[1])
+        };
+        let ctx = self.liveness.context(&self.cur.func.layout);
+        if arg0 != arg1 && !self.liveness[arg0].killed_at(inst, ebb, ctx) &&
+            self.liveness[arg1].killed_at(inst, ebb, ctx)
+        {
+            dbg!("Commuting {} to avoid a tied copy", self.cur.display_inst(inst));
+            self.cur.func.dfg.inst_args_mut(inst).swap(0, 1);
+            self.stats.eliminated += 1;
+        }
+    }
+
     // Collect register uses from the ABI input constraints.
     fn collect_abi_reg_uses(&mut self, inst: Inst, sig: SigRef) {
         let fixed_args = self.cur.func.dfg[inst]
@@ -404,6 +496,9 @@ impl<'a> Context<'a> {
             };
 
             if need_copy {
+                if ru.tied {
+                    self.stats.inserted += 1;
+                }
                 let copy = self.insert_copy(ru.value, ru.rci);
                 self.cur.func.dfg.inst_args_mut(inst)[ru.opidx as usize] = copy;
             }
@@ -422,7 +517,8 @@ impl<'a> Context<'a> {
                     // at the branch destination. It is also necessary since there can be
                     // arbitrarily many EBB arguments.
                     match {
-                        let args = if self.cur.func.dfg[inst].opcode().is_branch() {
+                        let opcode = self.cur.func.dfg[inst].opcode();
+                        let args = if opcode.is_branch() && !opcode.is_call() {
                             self.cur.func.dfg.inst_fixed_args(inst)
                         } else {
                             self.cur.func.dfg.inst_args(inst)
@@ -461,6 +557,8 @@ impl<'a> Context<'a> {
         //
         // We know that all candidate defs dominate the current instruction, so one of them will
         // dominate the others. That is the earliest def.
+        //
+        // When optimizing for size, the values with the fewest uses are spilled first.
         candidates
             .into_iter()
             .filter_map(|lv| {
@@ -476,12 +574,18 @@ impl<'a> Context<'a> {
                 None
             })
             .min_by(|&a, &b| {
+                let uses = match self.use_counts {
+                    Some(counts) => counts[a].cmp(&counts[b]),
+                    None => Ordering::Equal,
+                };
                 // Find the minimum candidate according to the RPO of their defs.
-                self.domtree.rpo_cmp(
-                    self.cur.func.dfg.value_def(a),
-                    self.cur.func.dfg.value_def(b),
-                    &self.cur.func.layout,
-                )
+                uses.then_with(|| {
+                    self.domtree.rpo_cmp(
+                        self.cur.func.dfg.value_def(a),
+                        self.cur.func.dfg.value_def(b),
+                        &self.cur.func.layout,
+                    )
+                })
             })
     }
 
@@ -596,3 +700,16 @@ impl fmt::Display for RegUse {
         Ok(())
     }
 }
+
+/// Count the number of uses of every value in `func` into `counts`.
+fn count_uses(func: &Function, counts: &mut EntityMap<Value, u32>) {
+    counts.clear();
+    counts.resize(func.dfg.num_values());
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                counts[arg] += 1;
+            }
+        }
+    }
+}
@@ -0,0 +1,467 @@
+//! Sparse conditional constant propagation.
+//!
+//! This pass finds the values that are constant on all the executable paths through a function,
+//! and the EBBs that can be executed at all. It is optimistic: an EBB is assumed to be
+//! unreachable until a branch to it is found to be executable, and an EBB parameter is assumed to
+//! be constant until two different values flow into it along executable edges. This way,
+//! constants propagate through EBB parameters and loops where a simple constant folder would give
+//! up.
+//!
+//! When the analysis is done, the function is rewritten:
+//!
+//! - Instructions computing a constant are replaced with `iconst` or `bconst`, and constant EBB
+//!   parameters are replaced with a constant materialized at the top of the EBB.
+//! - Conditional branches on a known condition are turned into jumps or removed.
+//! - EBBs that can't be executed are removed.
+//!
+//! Only scalar integer and boolean values are tracked, and only instructions without side effects
+//! are evaluated.
+
+use constant_folding::{fold_binary, icmp, sign_extend, zero_extend};
+use cursor::{Cursor, FuncCursor};
+use entity::{EntityMap, EntitySet};
+use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Type, Value};
+use ir::instructions::BranchInfo;
+use std::vec::Vec;
+use timing;
+
+/// Abstract value of an SSA value.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum LatticeValue {
+    /// No definition of the value has been executed yet.
+    Top,
+    /// The value is always this constant, sign-extended from its type.
+    Const(i64),
+    /// The value is not known to be a constant.
+    Bottom,
+}
+
+impl Default for LatticeValue {
+    fn default() -> Self {
+        LatticeValue::Top
+    }
+}
+
+impl LatticeValue {
+    /// Combine the values flowing in from two different paths.
+    fn meet(self, other: Self) -> Self {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Top, x) | (x, Top) => x,
+            (Const(x), Const(y)) if x == y => Const(x),
+            _ => Bottom,
+        }
+    }
+
+    /// Combine two operands with `f` when both are constant.
+    fn binary<F>(self, other: Self, f: F) -> Self
+    where
+        F: FnOnce(i64, i64) -> Option<i64>,
+    {
+        use self::LatticeValue::*;
+        match (self, other) {
+            (Const(x), Const(y)) => f(x, y).map_or(Bottom, Const),
+            (Bottom, _) | (_, Bottom) => Bottom,
+            _ => Top,
+        }
+    }
+}
+
+/// Persistent data structures for the sparse conditional constant propagation pass.
+pub struct Sccp {
+    /// The lattice value of every SSA value.
+    values: EntityMap<Value, LatticeValue>,
+    /// The EBBs that can be executed.
+    executable: EntitySet<Ebb>,
+    /// Replacements for the constant EBB parameters.
+    replacements: EntityMap<Value, Option<Value>>,
+    /// Did the last pass over the function change anything?
+    changed: bool,
+}
+
+impl Sccp {
+    /// Create a new context for the SCCP pass.
+    pub fn new() -> Self {
+        Self {
+            values: EntityMap::new(),
+            executable: EntitySet::new(),
+            replacements: EntityMap::new(),
+            changed: false,
+        }
+    }
+
+    /// Clear all data structures in this context.
+    pub fn clear(&mut self) {
+        self.values.clear();
+        self.executable.clear();
+        self.replacements.clear();
+        self.changed = false;
+    }
+
+    /// Propagate constants through `func`, fold conditional branches, and remove unreachable EBBs.
+    ///
+    /// Returns true if the control flow of the function changed. The control flow graph,
+    /// dominator tree, and loop analysis must be recomputed in that case.
+    pub fn run(&mut self, func: &mut Function) -> bool {
+        let _tt = timing::sccp();
+        self.clear();
+        if !self.analyze(func) {
+            return false;
+        }
+        self.rewrite(func)
+    }
+
+    /// Compute the lattice values and the executable EBBs. Returns false for an empty function.
+    fn analyze(&mut self, func: &Function) -> bool {
+        let entry = match func.layout.entry_block() {
+            Some(ebb) => ebb,
+            None => return false,
+        };
+        self.executable.insert(entry);
+        for &param in func.dfg.ebb_params(entry) {
+            self.values[param] = LatticeValue::Bottom;
+        }
+
+        // Lattice values can only move down, so this terminates after a few passes.
+        self.changed = true;
+        while self.changed {
+            self.changed = false;
+            for ebb in func.layout.ebbs() {
+                if !self.executable.contains(ebb) {
+                    continue;
+                }
+                for inst in func.layout.ebb_insts(ebb) {
+                    if !self.visit_inst(func, inst) {
+                        break;
+                    }
+                }
+            }
+        }
+        true
+    }
+
+    /// Update the lattice value of `value`.
+    fn set_value(&mut self, value: Value, lattice: LatticeValue) {
+        let new = self.values[value].meet(lattice);
+        if new != self.values[value] {
+            self.values[value] = new;
+            self.changed = true;
+        }
+    }
+
+    /// Mark the edge to `dest` as executable, passing `args` to its parameters.
+    fn visit_edge(&mut self, func: &Function, dest: Ebb, args: &[Value]) {
+        if self.executable.insert(dest) {
+            self.changed = true;
+        }
+        for (&param, &arg) in func.dfg.ebb_params(dest).iter().zip(args) {
+            let lattice = self.values[arg];
+            self.set_value(param, lattice);
+        }
+    }
+
+    /// Evaluate `inst`. Returns false if execution can't continue with the next instruction.
+    fn visit_inst(&mut self, func: &Function, inst: Inst) -> bool {
+        let opcode = func.dfg[inst].opcode();
+        match func.dfg.analyze_branch(inst) {
+            BranchInfo::NotABranch => {
+                if opcode.is_terminator() {
+                    return false;
+                }
+                let results = func.dfg.inst_results(inst);
+                if results.len() == 1 {
+                    let lattice = self.eval(func, inst, func.dfg.value_type(results[0]));
+                    self.set_value(results[0], lattice);
+                } else {
+                    for &result in results {
+                        self.set_value(result, LatticeValue::Bottom);
+                    }
+                }
+                true
+            }
+            BranchInfo::SingleDest(dest, _) if opcode == Opcode::TryCall => {
+                // The arguments of a `try_call` go to the callee.
+                self.visit_edge(func, dest, &[]);
+                for &result in func.dfg.inst_results(inst) {
+                    self.set_value(result, LatticeValue::Bottom);
+                }
+                true
+            }
+            BranchInfo::SingleDest(dest, args) => {
+                match self.branch_taken(func, inst) {
+                    LatticeValue::Top => false,
+                    LatticeValue::Const(0) => true,
+                    LatticeValue::Const(_) => {
+                        self.visit_edge(func, dest, args);
+                        false
+                    }
+                    LatticeValue::Bottom => {
+                        self.visit_edge(func, dest, args);
+                        !opcode.is_terminator()
+                    }
+                }
+            }
+            BranchInfo::Table(jt) => {
+                match self.values[func.dfg.inst_args(inst)[0]] {
+                    LatticeValue::Top => false,
+                    LatticeValue::Const(index) => {
+                        match table_dest(func, inst, index) {
+                            Some(dest) => {
+                                self.visit_edge(func, dest, &[]);
+                                false
+                            }
+                            None => true,
+                        }
+                    }
+                    LatticeValue::Bottom => {
+                        for dest in func.jump_tables[jt].destinations() {
+                            self.visit_edge(func, dest, &[]);
+                        }
+                        true
+                    }
+                }
+            }
+        }
+    }
+
+    /// Determine if the single-destination branch `inst` is taken, as a lattice value where
+    /// `Const(0)` means not taken and any other constant means taken.
+    fn branch_taken(&self, func: &Function, inst: Inst) -> LatticeValue {
+        match func.dfg[inst] {
+            InstructionData::Jump { .. } => LatticeValue::Const(1),
+            InstructionData::Branch { opcode, ref args, .. } => {
+                let arg = args.as_slice(&func.dfg.value_lists)[0];
+                let taken_if_zero = opcode == Opcode::Brz;
+                match self.values[arg] {
+                    LatticeValue::Const(x) => {
+                        LatticeValue::Const(((x == 0) == taken_if_zero) as i64)
+                    }
+                    lattice => lattice,
+                }
+            }
+            InstructionData::BranchIcmp { cond, ref args, .. } => {
+                let args = args.as_slice(&func.dfg.value_lists);
+                let bits = func.dfg.value_type(args[0]).bits();
+                self.values[args[0]].binary(self.values[args[1]], |x, y| {
+                    Some(icmp(cond, x, y, bits) as i64)
+                })
+            }
+            _ => LatticeValue::Bottom,
+        }
+    }
+
+    /// Evaluate the single result of `inst` which has type `ty`.
+    fn eval(&self, func: &Function, inst: Inst, ty: Type) -> LatticeValue {
+        if ty.is_vector() || !(ty.is_int() || ty.is_bool()) {
+            return LatticeValue::Bottom;
+        }
+        let value = |v: Value| self.values[v];
+        let lattice = match func.dfg[inst] {
+            InstructionData::UnaryImm { opcode: Opcode::Iconst, imm } => {
+                LatticeValue::Const(imm.into())
+            }
+            InstructionData::UnaryBool { opcode: Opcode::Bconst, imm } => {
+                LatticeValue::Const(imm as i64)
+            }
+            InstructionData::Unary { opcode, arg } => {
+                let arg_bits = func.dfg.value_type(arg).bits();
+                value(arg).binary(LatticeValue::Const(0), |x, _| match opcode {
+                    Opcode::Copy | Opcode::Bint | Opcode::Sextend | Opcode::Ireduce => Some(x),
+                    Opcode::Uextend => Some(zero_extend(x, arg_bits)),
+                    _ => None,
+                })
+            }
+            InstructionData::Binary { opcode, args } => {
+                let bits = ty.bits();
+                value(args[0]).binary(value(args[1]), |x, y| fold_binary(opcode, bits, x, y))
+            }
+            InstructionData::BinaryImm { opcode, arg, imm } => {
+                let bits = ty.bits();
+                value(arg).binary(LatticeValue::Const(imm.into()), |x, y| {
+                    fold_binary(opcode, bits, x, y)
+                })
+            }
+            InstructionData::IntCompare { cond, args, .. } => {
+                let bits = func.dfg.value_type(args[0]).bits();
+                value(args[0]).binary(value(args[1]), |x, y| Some(icmp(cond, x, y, bits) as i64))
+            }
+            InstructionData::IntCompareImm { cond, arg, imm, .. } => {
+                let bits = func.dfg.value_type(arg).bits();
+                value(arg).binary(LatticeValue::Const(imm.into()), |x, y| {
+                    Some(icmp(cond, x, y, bits) as i64)
+                })
+            }
+            InstructionData::Ternary { opcode: Opcode::Select, args } => {
+                match value(args[0]) {
+                    LatticeValue::Top => LatticeValue::Top,
+                    LatticeValue::Const(0) => value(args[2]),
+                    LatticeValue::Const(_) => value(args[1]),
+                    LatticeValue::Bottom => value(args[1]).meet(value(args[2])),
+                }
+            }
+            _ => LatticeValue::Bottom,
+        };
+        match lattice {
+            LatticeValue::Const(x) if ty.is_int() => LatticeValue::Const(sign_extend(x, ty.bits())),
+            lattice => lattice,
+        }
+    }
+
+    /// Rewrite `func` according to the analysis. Returns true if the control flow changed.
+    fn rewrite(&mut self, func: &mut Function) -> bool {
+        let mut cfg_changed = false;
+
+        let mut pos = FuncCursor::new(func);
+        while let Some(ebb) = pos.next_ebb() {
+            if !self.executable.contains(ebb) {
+                continue;
+            }
+
+            for i in 0..pos.func.dfg.num_ebb_params(ebb) {
+                let param = pos.func.dfg.ebb_params(ebb)[i];
+                if let LatticeValue::Const(x) = self.values[param] {
+                    let ty = pos.func.dfg.value_type(param);
+                    pos.goto_first_inst(ebb);
+                    let value = if ty.is_bool() {
+                        pos.ins().bconst(ty, x != 0)
+                    } else {
+                        pos.ins().iconst(ty, x)
+                    };
+                    self.replacements[param] = Some(value);
+                }
+            }
+
+            pos.goto_top(ebb);
+            while let Some(inst) = pos.next_inst() {
+                if pos.func.dfg[inst].opcode().is_branch() {
+                    if self.fold_branch(&mut pos, inst) {
+                        cfg_changed = true;
+                    }
+                    continue;
+                }
+                self.fold_inst(pos.func, inst);
+            }
+        }
+
+        // Uses of constant EBB parameters are dominated by the new constants.
+        let mut pos = FuncCursor::new(func);
+        while let Some(_) = pos.next_ebb() {
+            while let Some(inst) = pos.next_inst() {
+                for arg in pos.func.dfg.inst_args_mut(inst) {
+                    if let Some(value) = self.replacements[*arg] {
+                        *arg = value;
+                    }
+                }
+            }
+        }
+
+        // Remove the EBBs that can't be executed, and the jump table entries referring to them.
+        let mut pos = FuncCursor::new(func);
+        while let Some(ebb) = pos.next_ebb() {
+            if self.executable.contains(ebb) {
+                continue;
+            }
+            dbg!("Removing unexecutable {}", ebb);
+            cfg_changed = true;
+            pos.prev_ebb();
+            while let Some(inst) = pos.func.layout.first_inst(ebb) {
+                pos.func.layout.remove_inst(inst);
+            }
+            pos.func.layout.remove_ebb(ebb);
+        }
+        for jt in func.jump_tables.keys() {
+            let table = &mut func.jump_tables[jt];
+            for entry in table.as_mut_slice() {
+                if entry.expand().map_or(false, |ebb| !self.executable.contains(ebb)) {
+                    *entry = None.into();
+                }
+            }
+            if let Some(ebb) = table.default_dest() {
+                if !self.executable.contains(ebb) {
+                    table.set_default_dest(None);
+                }
+            }
+        }
+
+        cfg_changed
+    }
+
+    /// Replace `inst` with a constant if its result is known. Instructions with side effects are
+    /// never evaluated, so they don't have constant results.
+    fn fold_inst(&self, func: &mut Function, inst: Inst) {
+        match func.dfg[inst].opcode() {
+            Opcode::Iconst | Opcode::Bconst => return,
+            _ => {}
+        }
+        if func.dfg.inst_results(inst).len() != 1 {
+            return;
+        }
+        let result = func.dfg.first_result(inst);
+        if let LatticeValue::Const(x) = self.values[result] {
+            let ty = func.dfg.value_type(result);
+            if ty.is_bool() {
+                func.dfg.replace(inst).bconst(ty, x != 0);
+            } else {
+                func.dfg.replace(inst).iconst(ty, x);
+            }
+        }
+    }
+
+    /// Fold the branch `inst` at the cursor position if its outcome is known.
+    ///
+    /// A branch which is always taken becomes a jump, and the following instructions are removed.
+    /// A branch which is never taken is removed. Returns true if the branch was changed.
+    fn fold_branch(&self, pos: &mut FuncCursor, inst: Inst) -> bool {
+        let dest = match pos.func.dfg[inst] {
+            InstructionData::Jump { .. } => return false,
+            InstructionData::BranchTable { arg, .. } => {
+                match self.values[arg] {
+                    LatticeValue::Const(index) => table_dest(pos.func, inst, index),
+                    _ => return false,
+                }
+            }
+            _ => {
+                match self.branch_taken(pos.func, inst) {
+                    LatticeValue::Const(0) => None,
+                    LatticeValue::Const(_) => pos.func.dfg[inst].branch_destination(),
+                    _ => return false,
+                }
+            }
+        };
+
+        match dest {
+            Some(dest) => {
+                let args = match pos.func.dfg.analyze_branch(inst) {
+                    BranchInfo::SingleDest(_, args) => args.to_vec(),
+                    _ => Vec::new(),
+                };
+                pos.func.dfg.replace(inst).jump(dest, &args);
+                while let Some(next) = pos.func.layout.next_inst(inst) {
+                    pos.func.layout.remove_inst(next);
+                }
+            }
+            None => {
+                pos.remove_inst_and_step_back();
+            }
+        }
+        true
+    }
+}
+
+/// Find the destination of the `br_table` instruction `inst` for a constant index, or `None` if it
+/// falls through.
+fn table_dest(func: &Function, inst: Inst, index: i64) -> Option<Ebb> {
+    let (arg, jt) = match func.dfg[inst] {
+        InstructionData::BranchTable { arg, table, .. } => (arg, table),
+        _ => panic!("not a br_table"),
+    };
+    let table = &func.jump_tables[jt];
+    let bits = func.dfg.value_type(arg).bits();
+    let offset = zero_extend(index.wrapping_sub(table.min_index()), bits) as u64;
+    if offset < table.len() as u64 {
+        if let Some(dest) = table.get_entry(offset as usize) {
+            return Some(dest);
+        }
+    }
+    table.default_dest()
+}
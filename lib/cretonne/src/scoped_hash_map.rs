@@ -76,6 +76,13 @@ where
         }
     }
 
+    /// Remove all entries and exit all scopes, keeping the allocated memory for reuse.
+    pub fn clear(&mut self) {
+        self.map.clear();
+        self.last_insert = None;
+        self.current_depth = 0;
+    }
+
     /// Similar to `HashMap::entry`, gets the given key's corresponding entry in the map for
     /// in-place manipulation.
     pub fn entry(&mut self, key: K) -> Entry<K, V> {
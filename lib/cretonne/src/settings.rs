@@ -7,7 +7,8 @@
 //! settings as well as computed predicate flags.
 //!
 //! The `Flags` struct is immutable once it has been created. A `Builder` instance is used to
-//! create it.
+//! create it. The flags are stored in a few bytes, so they are cheap to clone and send between
+//! threads, and they can be compared and hashed.
 //!
 //! # Example
 //! ```
@@ -24,6 +25,7 @@ use constant_hash::{probe, simple_hash};
 use isa::TargetIsa;
 use std::fmt;
 use std::result;
+use std::string::String;
 use std::vec::Vec;
 
 /// A string-based configurator for settings groups.
@@ -57,6 +59,14 @@ impl Builder {
         }
     }
 
+    /// Get the name, kind, default and current value of all the settings in this builder.
+    ///
+    /// This makes it possible to build a configuration layer on top of `Configurable` without
+    /// hard-coding the list of settings.
+    pub fn settings(&self) -> Vec<Setting> {
+        self.template.settings(&self.bytes)
+    }
+
     /// Extract contents of builder once everything is configured.
     pub fn state_for(&self, name: &str) -> &[u8] {
         assert_eq!(name, self.template.name);
@@ -163,6 +173,44 @@ pub enum Error {
 /// A result returned when changing a setting.
 pub type Result<T> = result::Result<T, Error>;
 
+/// The kind of a setting, which determines the values it accepts.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SettingKind {
+    /// A boolean setting accepts `true` or `false`, and it can be enabled by name.
+    Bool,
+
+    /// A numerical setting accepts a number from 0 to 255.
+    Num,
+
+    /// An enumerated setting accepts one of the names listed in `Setting::values`.
+    Enum,
+
+    /// A preset can only be enabled, which applies a collection of other settings at once.
+    Preset,
+}
+
+/// Description of a setting and its value.
+///
+/// The values are formatted the way `Configurable::set` accepts them. Presets don't have a value,
+/// so their values are empty strings.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Setting {
+    /// Lower snake-case name of the setting.
+    pub name: &'static str,
+
+    /// The kind of setting.
+    pub kind: SettingKind,
+
+    /// The accepted values of an enumerated setting.
+    pub values: Option<&'static [&'static str]>,
+
+    /// The default value.
+    pub default: String,
+
+    /// The current value.
+    pub value: String,
+}
+
 /// A reference to just the boolean predicates of a settings object.
 ///
 /// The settings objects themselves are generated and appear in the `isa/*/settings.rs` modules.
@@ -191,7 +239,10 @@ impl<'a> PredicateView<'a> {
 /// code in other modules.
 pub mod detail {
     use std::fmt;
+    use std::string::{String, ToString};
+    use std::vec::Vec;
     use constant_hash;
+    use super::{Setting, SettingKind};
 
     /// An instruction group template.
     pub struct Template {
@@ -211,12 +262,56 @@ pub mod detail {
 
     impl Template {
         /// Get enumerators corresponding to a `Details::Enum`.
-        pub fn enums(&self, last: u8, enumerators: u16) -> &[&'static str] {
+        pub fn enums(&self, last: u8, enumerators: u16) -> &'static [&'static str] {
             let from = enumerators as usize;
             let len = usize::from(last) + 1;
             &self.enumerators[from..from + len]
         }
 
+        /// Describe all the settings in this group along with their values in `bytes`.
+        pub fn settings(&self, bytes: &[u8]) -> Vec<Setting> {
+            self.descriptors
+                .iter()
+                .map(|d| {
+                    let offset = d.offset as usize;
+                    let (kind, values) = match d.detail {
+                        Detail::Bool { .. } => (SettingKind::Bool, None),
+                        Detail::Num => (SettingKind::Num, None),
+                        Detail::Enum { last, enumerators } => {
+                            (SettingKind::Enum, Some(self.enums(last, enumerators)))
+                        }
+                        Detail::Preset => (SettingKind::Preset, None),
+                    };
+                    let (default, value) = if d.detail.is_preset() {
+                        (String::new(), String::new())
+                    } else {
+                        (
+                            self.format_value(d.detail, self.defaults[offset]),
+                            self.format_value(d.detail, bytes[offset]),
+                        )
+                    };
+                    Setting {
+                        name: d.name,
+                        kind,
+                        values,
+                        default,
+                        value,
+                    }
+                })
+                .collect()
+        }
+
+        /// Format a setting value the way it is accepted by `Configurable::set`.
+        fn format_value(&self, detail: Detail, byte: u8) -> String {
+            match detail {
+                Detail::Bool { bit } => ((byte & (1 << bit)) != 0).to_string(),
+                Detail::Enum { last, enumerators } if byte <= last => {
+                    self.enums(last, enumerators)[usize::from(byte)].to_string()
+                }
+                _ => byte.to_string(),
+            }
+        }
+
         /// Format a setting value as a TOML string. This is mostly for use by the generated
         /// `Display` implementation.
         pub fn format_toml_value(
@@ -345,7 +440,7 @@ impl<'a> From<&'a TargetIsa> for FlagsOrIsa<'a> {
 
 #[cfg(test)]
 mod tests {
-    use super::{builder, Flags};
+    use super::{builder, Flags, SettingKind};
     use super::Error::*;
     use super::Configurable;
     use std::string::ToString;
@@ -363,10 +458,16 @@ mod tests {
                     is_pic = false\n\
                     return_at_end = false\n\
                     avoid_div_traps = false\n\
+                    enable_heap_access_spectre_mitigation = false\n\
                     is_compressed = false\n\
                     enable_float = true\n\
                     enable_simd = true\n\
                     enable_atomics = true\n\
+                    enable_slp = false\n\
+                    unroll_budget = 64\n\
+                    enable_ebb_counters = false\n\
+                    function_align = 0\n\
+                    loop_align = 0\n\
                     spiderwasm_prologue_words = 0\n\
                     allones_funcaddrs = false\n"
         );
@@ -375,6 +476,44 @@ mod tests {
         assert_eq!(f.spiderwasm_prologue_words(), 0);
     }
 
+    #[test]
+    fn introspection() {
+        let mut b = builder();
+        b.set("opt_level", "best").unwrap();
+        let settings = b.settings();
+
+        let opt_level = settings.iter().find(|s| s.name == "opt_level").unwrap();
+        assert_eq!(opt_level.kind, SettingKind::Enum);
+        assert_eq!(
+            opt_level.values,
+            Some(&["default", "best", "fastest", "min_size"][..])
+        );
+        assert_eq!(opt_level.default, "default");
+        assert_eq!(opt_level.value, "best");
+
+        let f = Flags::new(&b);
+        let settings = f.settings();
+        let verifier = settings.iter().find(|s| s.name == "enable_verifier").unwrap();
+        assert_eq!(verifier.kind, SettingKind::Bool);
+        assert_eq!(verifier.values, None);
+        assert_eq!(verifier.default, "true");
+        assert_eq!(verifier.value, "true");
+
+        let align = settings.iter().find(|s| s.name == "loop_align").unwrap();
+        assert_eq!(align.kind, SettingKind::Num);
+        assert_eq!(align.value, "0");
+    }
+
+    #[test]
+    fn compare_flags() {
+        let mut b = builder();
+        let f1 = Flags::new(&b);
+        assert!(f1 == Flags::new(&b));
+
+        b.set("opt_level", "best").unwrap();
+        assert!(f1 != Flags::new(&b));
+    }
+
     #[test]
     fn modify_bool() {
         let mut b = builder();
@@ -1,5 +1,6 @@
 //! A simple GVN pass.
 
+use alias_analysis::is_readonly_load;
 use cursor::{Cursor, FuncCursor};
 use flowgraph::ControlFlowGraph;
 use dominator_tree::DominatorTree;
@@ -15,70 +16,100 @@ fn trivially_unsafe_for_gvn(opcode: Opcode) -> bool {
         opcode.can_store() || opcode.can_load() || opcode.writes_cpu_flags()
 }
 
-/// Perform simple GVN on `func`.
-///
-pub fn do_simple_gvn(func: &mut Function, cfg: &mut ControlFlowGraph, domtree: &mut DominatorTree) {
-    let _tt = timing::gvn();
-    debug_assert!(cfg.is_valid());
-    debug_assert!(domtree.is_valid());
+/// Persistent data structures for the simple GVN pass.
+pub struct SimpleGvn {
+    /// The instructions that are available in the current scope, keyed by their contents.
+    visible_values: ScopedHashMap<(InstructionData, Type), Inst>,
+    /// The first instruction of every scope that is currently open.
+    scope_stack: Vec<Inst>,
+}
+
+impl SimpleGvn {
+    /// Create a new context for the GVN pass.
+    pub fn new() -> Self {
+        Self {
+            visible_values: ScopedHashMap::new(),
+            scope_stack: Vec::new(),
+        }
+    }
 
-    let mut visible_values: ScopedHashMap<(InstructionData, Type), Inst> = ScopedHashMap::new();
-    let mut scope_stack: Vec<Inst> = Vec::new();
+    /// Clear all data structures in this context.
+    pub fn clear(&mut self) {
+        self.visible_values.clear();
+        self.scope_stack.clear();
+    }
+
+    /// Perform simple GVN on `func`.
+    pub fn run(
+        &mut self,
+        func: &mut Function,
+        cfg: &mut ControlFlowGraph,
+        domtree: &mut DominatorTree,
+    ) {
+        let _tt = timing::gvn();
+        debug_assert!(cfg.is_valid());
+        debug_assert!(domtree.is_valid());
 
-    // Visit EBBs in a reverse post-order.
-    let mut pos = FuncCursor::new(func);
+        self.clear();
+        let visible_values = &mut self.visible_values;
+        let scope_stack = &mut self.scope_stack;
 
-    for &ebb in domtree.cfg_postorder().iter().rev() {
-        // Pop any scopes that we just exited.
-        loop {
-            if let Some(current) = scope_stack.last() {
-                if domtree.dominates(*current, ebb, &pos.func.layout) {
+        // Visit EBBs in a reverse post-order.
+        let mut pos = FuncCursor::new(func);
+
+        for &ebb in domtree.cfg_postorder().iter().rev() {
+            // Pop any scopes that we just exited.
+            loop {
+                if let Some(current) = scope_stack.last() {
+                    if domtree.dominates(*current, ebb, &pos.func.layout) {
+                        break;
+                    }
+                } else {
                     break;
                 }
-            } else {
-                break;
+                scope_stack.pop();
+                visible_values.decrement_depth();
             }
-            scope_stack.pop();
-            visible_values.decrement_depth();
-        }
 
-        // Push a scope for the current block.
-        scope_stack.push(pos.func.layout.first_inst(ebb).unwrap());
-        visible_values.increment_depth();
+            // Push a scope for the current block.
+            scope_stack.push(pos.func.layout.first_inst(ebb).unwrap());
+            visible_values.increment_depth();
 
-        pos.goto_top(ebb);
-        while let Some(inst) = pos.next_inst() {
-            // Resolve aliases, particularly aliases we created earlier.
-            pos.func.dfg.resolve_aliases_in_arguments(inst);
+            pos.goto_top(ebb);
+            while let Some(inst) = pos.next_inst() {
+                // Resolve aliases, particularly aliases we created earlier.
+                pos.func.dfg.resolve_aliases_in_arguments(inst);
 
-            let opcode = pos.func.dfg[inst].opcode();
-            if opcode.is_branch() && !opcode.is_terminator() {
-                scope_stack.push(pos.func.layout.next_inst(inst).unwrap());
-                visible_values.increment_depth();
-            }
-            if trivially_unsafe_for_gvn(opcode) {
-                continue;
-            }
+                let opcode = pos.func.dfg[inst].opcode();
+                if opcode.is_branch() && !opcode.is_terminator() {
+                    scope_stack.push(pos.func.layout.next_inst(inst).unwrap());
+                    visible_values.increment_depth();
+                }
+                // Loads from read-only memory always produce the same value for the same address.
+                if trivially_unsafe_for_gvn(opcode) && !is_readonly_load(pos.func, inst) {
+                    continue;
+                }
 
-            let ctrl_typevar = pos.func.dfg.ctrl_typevar(inst);
-            let key = (pos.func.dfg[inst].clone(), ctrl_typevar);
-            let entry = visible_values.entry(key);
-            use scoped_hash_map::Entry::*;
-            match entry {
-                Occupied(entry) => {
-                    debug_assert!(domtree.dominates(*entry.get(), inst, &pos.func.layout));
-                    // If the redundant instruction is representing the current
-                    // scope, pick a new representative.
-                    let old = scope_stack.last_mut().unwrap();
-                    if *old == inst {
-                        *old = pos.func.layout.next_inst(inst).unwrap();
+                let ctrl_typevar = pos.func.dfg.ctrl_typevar(inst);
+                let key = (pos.func.dfg[inst].clone(), ctrl_typevar);
+                let entry = visible_values.entry(key);
+                use scoped_hash_map::Entry::*;
+                match entry {
+                    Occupied(entry) => {
+                        debug_assert!(domtree.dominates(*entry.get(), inst, &pos.func.layout));
+                        // If the redundant instruction is representing the current
+                        // scope, pick a new representative.
+                        let old = scope_stack.last_mut().unwrap();
+                        if *old == inst {
+                            *old = pos.func.layout.next_inst(inst).unwrap();
+                        }
+                        // Replace the redundant instruction and remove it.
+                        pos.func.dfg.replace_with_aliases(inst, *entry.get());
+                        pos.remove_inst_and_step_back();
+                    }
+                    Vacant(entry) => {
+                        entry.insert(inst);
                     }
-                    // Replace the redundant instruction and remove it.
-                    pos.func.dfg.replace_with_aliases(inst, *entry.get());
-                    pos.remove_inst_and_step_back();
-                }
-                Vacant(entry) => {
-                    entry.insert(inst);
                 }
             }
         }
@@ -0,0 +1,383 @@
+//! Superword-level parallelism (SLP) vectorization.
+//!
+//! This experimental pass packs groups of isomorphic scalar operations into vector operations.
+//!
+//! The seeds are groups of scalar stores to adjacent addresses from the same base pointer, one
+//! store per vector lane. The stored values are followed up the data flow graph as long as all
+//! the lanes are computed by the same opcode, and the lanes of each operand are packed in turn.
+//! The packing must end in groups of loads from adjacent addresses.
+//!
+//! A group is only vectorized when the target ISA can encode all the vector instructions, and
+//! when the scalar instructions can be moved down to the last store without reordering them
+//! across other memory accesses.
+
+use alias_analysis::may_conflict;
+use cursor::{Cursor, FuncCursor};
+use entity::EntityMap;
+use ir::dfg::ValueDef;
+use ir::immediates::Offset32;
+use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Layout, MemFlags, Opcode, ProgramOrder,
+         Type, Value};
+use isa::TargetIsa;
+use std::cmp::Ordering;
+use std::vec::Vec;
+use timing;
+
+/// Vectors produced by this pass are 128 bits wide.
+const VECTOR_BITS: u16 = 128;
+
+/// Vectorize adjacent scalar stores and the computations of the stored values in `func`.
+pub fn do_slp(func: &mut Function, isa: &TargetIsa) {
+    let _tt = timing::slp();
+    let uses = count_uses(func);
+    let ebbs: Vec<Ebb> = func.layout.ebbs().collect();
+    for ebb in ebbs {
+        for group in store_groups(func, ebb) {
+            let mut tree = Tree::new();
+            if tree.build(func, isa, &uses, ebb, &group) {
+                dbg!("SLP vectorizing stores {:?}", group);
+                tree.emit(func, &group);
+            }
+        }
+    }
+}
+
+/// Count the number of uses of each value in `func`.
+fn count_uses(func: &Function) -> EntityMap<Value, u32> {
+    let mut uses = EntityMap::new();
+    for ebb in func.layout.ebbs() {
+        for inst in func.layout.ebb_insts(ebb) {
+            for &arg in func.dfg.inst_args(inst) {
+                uses[func.dfg.resolve_aliases(arg)] += 1;
+            }
+        }
+    }
+    uses
+}
+
+/// Get the vector type with one lane per scalar `ty`, if there is one.
+fn vector_type(ty: Type) -> Option<Type> {
+    if ty.is_vector() || !(ty.is_int() || ty.is_float()) || ty.bits() >= VECTOR_BITS {
+        return None;
+    }
+    ty.by(VECTOR_BITS / ty.bits())
+}
+
+/// Stores that may be packed together have the same base address, flags, and stored type.
+type StoreKey = (Value, MemFlags, Type);
+
+/// Find groups of stores to adjacent addresses in `ebb` that could fill a vector.
+///
+/// Each returned group contains one store per vector lane, ordered by address.
+fn store_groups(func: &Function, ebb: Ebb) -> Vec<Vec<Inst>> {
+    let mut candidates: Vec<(StoreKey, Vec<(i32, Inst)>)> = Vec::new();
+    for inst in func.layout.ebb_insts(ebb) {
+        if let InstructionData::Store {
+            opcode: Opcode::Store,
+            flags,
+            args,
+            offset,
+        } = func.dfg[inst]
+        {
+            let key = (args[1], flags, func.dfg.value_type(args[0]));
+            let offset: i32 = offset.into();
+            match candidates.iter().position(|c| c.0 == key) {
+                Some(idx) => candidates[idx].1.push((offset, inst)),
+                None => candidates.push((key, vec![(offset, inst)])),
+            }
+        }
+    }
+
+    let mut groups = Vec::new();
+    for ((_, _, ty), mut stores) in candidates {
+        let lanes = match vector_type(ty) {
+            Some(vty) => vty.lane_count() as usize,
+            None => continue,
+        };
+        stores.sort_by_key(|&(offset, _)| offset);
+
+        // Pick non-overlapping runs of `lanes` stores with consecutive addresses.
+        let mut i = 0;
+        while i + lanes <= stores.len() {
+            let run = &stores[i..i + lanes];
+            let adjacent = run.windows(2).all(|w| {
+                i64::from(w[1].0) - i64::from(w[0].0) == i64::from(ty.bytes())
+            });
+            if adjacent {
+                groups.push(run.iter().map(|&(_, inst)| inst).collect());
+                i += lanes;
+            } else {
+                i += 1;
+            }
+        }
+    }
+    groups
+}
+
+/// A group of isomorphic scalar instructions, one per vector lane.
+enum Pack {
+    /// Loads from adjacent addresses.
+    Load {
+        base: Value,
+        flags: MemFlags,
+        offset: Offset32,
+    },
+    /// A binary operation on two other packs.
+    Binary { opcode: Opcode, args: [usize; 2] },
+}
+
+/// A tree of packs computing the values stored by a group of stores.
+struct Tree {
+    /// The packs in the tree. Operands always appear before their users.
+    packs: Vec<Pack>,
+    /// All the scalar instructions in the packs.
+    insts: Vec<Inst>,
+    /// The scalar loads in the packs.
+    loads: Vec<Inst>,
+    /// The vector type being built.
+    vty: Type,
+}
+
+impl Tree {
+    fn new() -> Self {
+        Self {
+            packs: Vec::new(),
+            insts: Vec::new(),
+            loads: Vec::new(),
+            vty: Type::default(),
+        }
+    }
+
+    /// Build the tree of packs for the values stored by `stores`.
+    ///
+    /// Returns true if the whole group can be vectorized.
+    fn build(
+        &mut self,
+        func: &Function,
+        isa: &TargetIsa,
+        uses: &EntityMap<Value, u32>,
+        ebb: Ebb,
+        stores: &[Inst],
+    ) -> bool {
+        let values: Vec<Value> = stores
+            .iter()
+            .map(|&inst| func.dfg.resolve_aliases(func.dfg.inst_args(inst)[0]))
+            .collect();
+        self.vty = match vector_type(func.dfg.value_type(values[0])) {
+            Some(vty) => vty,
+            None => return false,
+        };
+        self.pack(func, uses, ebb, &values).is_some() && self.is_legal(func, stores) &&
+            self.is_encodable(func, isa, stores[0])
+    }
+
+    /// Pack the instructions computing `values`, and return the index of the new pack.
+    fn pack(
+        &mut self,
+        func: &Function,
+        uses: &EntityMap<Value, u32>,
+        ebb: Ebb,
+        values: &[Value],
+    ) -> Option<usize> {
+        let mut insts = Vec::with_capacity(values.len());
+        for &value in values {
+            let inst = match func.dfg.value_def(value) {
+                ValueDef::Result(inst, 0) => inst,
+                _ => return None,
+            };
+            // The scalar instructions are deleted, so their results can't be used anywhere else.
+            if uses[value] != 1 || func.layout.inst_ebb(inst) != Some(ebb) ||
+                func.dfg.inst_results(inst).len() != 1
+            {
+                return None;
+            }
+            insts.push(inst);
+        }
+
+        let opcode = func.dfg[insts[0]].opcode();
+        if insts.iter().any(|&inst| func.dfg[inst].opcode() != opcode) {
+            return None;
+        }
+
+        let pack = match func.dfg[insts[0]] {
+            InstructionData::Load {
+                opcode: Opcode::Load,
+                flags,
+                arg,
+                offset,
+            } => {
+                let lane_bytes = i64::from(self.vty.lane_type().bytes());
+                let start: i64 = offset.into();
+                for (lane, &inst) in insts.iter().enumerate() {
+                    match func.dfg[inst] {
+                        InstructionData::Load {
+                            flags: f,
+                            arg: a,
+                            offset: o,
+                            ..
+                        } if f == flags && a == arg &&
+                                 Into::<i64>::into(o) == start + lane as i64 * lane_bytes => {}
+                        _ => return None,
+                    }
+                }
+                self.loads.extend_from_slice(&insts);
+                Pack::Load {
+                    base: arg,
+                    flags,
+                    offset,
+                }
+            }
+            InstructionData::Binary { opcode, .. } if is_packable_binary(opcode) => {
+                let mut args = [0; 2];
+                for (i, arg) in args.iter_mut().enumerate() {
+                    let lanes: Vec<Value> = insts
+                        .iter()
+                        .map(|&inst| func.dfg.resolve_aliases(func.dfg.inst_args(inst)[i]))
+                        .collect();
+                    *arg = self.pack(func, uses, ebb, &lanes)?;
+                }
+                Pack::Binary { opcode, args }
+            }
+            _ => return None,
+        };
+
+        self.insts.extend_from_slice(&insts);
+        self.packs.push(pack);
+        Some(self.packs.len() - 1)
+    }
+
+    /// Check that the scalar instructions can be moved down to the last store.
+    ///
+    /// A load can only follow a store in the group if they can't conflict, and there
+    /// can't be any other memory accesses or side effects between the first scalar instruction
+    /// and the last store.
+    fn is_legal(&self, func: &Function, stores: &[Inst]) -> bool {
+        let layout = &func.layout;
+        let last_store = last_inst(layout, stores.iter().cloned());
+        for &load in &self.loads {
+            for &store in stores {
+                if layout.cmp(store, load) == Ordering::Less && may_conflict(func, store, load) {
+                    return false;
+                }
+            }
+        }
+
+        let mut cur = Some(first_inst(layout, self.insts.iter().chain(stores).cloned()));
+        while let Some(inst) = cur {
+            if !self.insts.contains(&inst) && !stores.contains(&inst) {
+                let opcode = func.dfg[inst].opcode();
+                if opcode.can_load() || opcode.can_store() || opcode.can_trap() ||
+                    opcode.is_call() || opcode.other_side_effects()
+                {
+                    return false;
+                }
+            }
+            if inst == last_store {
+                return true;
+            }
+            cur = layout.next_inst(inst);
+        }
+        false
+    }
+
+    /// Check that the target ISA can encode all the vector instructions.
+    fn is_encodable(&self, func: &Function, isa: &TargetIsa, store: Inst) -> bool {
+        let mut datas: Vec<InstructionData> = self.packs
+            .iter()
+            .map(|pack| match *pack {
+                Pack::Load {
+                    base,
+                    flags,
+                    offset,
+                } => InstructionData::Load {
+                    opcode: Opcode::Load,
+                    flags,
+                    arg: base,
+                    offset,
+                },
+                Pack::Binary { opcode, .. } => {
+                    // The arguments don't affect the encoding, so any value will do.
+                    InstructionData::Binary {
+                        opcode,
+                        args: [func.dfg.first_result(self.insts[0]); 2],
+                    }
+                }
+            })
+            .collect();
+        datas.push(func.dfg[store].clone());
+        datas.iter().all(|data| {
+            isa.encode(func, data, self.vty).is_ok()
+        })
+    }
+
+    /// Replace the scalar instructions with vector instructions inserted before the last store.
+    fn emit(&self, func: &mut Function, stores: &[Inst]) {
+        let last_store = last_inst(&func.layout, stores.iter().cloned());
+        let (flags, base, offset) = match func.dfg[stores[0]] {
+            InstructionData::Store {
+                flags,
+                args,
+                offset,
+                ..
+            } => (flags, args[1], offset),
+            _ => panic!("Expected store"),
+        };
+
+        let mut pos = FuncCursor::new(func).at_inst(last_store);
+        pos.use_srcloc(last_store);
+        let mut vectors = Vec::with_capacity(self.packs.len());
+        for pack in &self.packs {
+            let vector = match *pack {
+                Pack::Load {
+                    base,
+                    flags,
+                    offset,
+                } => pos.ins().load(self.vty, vector_flags(flags), base, offset),
+                Pack::Binary { opcode, args } => {
+                    let (inst, dfg) = pos.ins().Binary(
+                        opcode,
+                        self.vty,
+                        vectors[args[0]],
+                        vectors[args[1]],
+                    );
+                    dfg.first_result(inst)
+                }
+            };
+            vectors.push(vector);
+        }
+        let root = *vectors.last().expect("Empty tree");
+        pos.ins().store(vector_flags(flags), root, base, offset);
+
+        for &inst in self.insts.iter().chain(stores) {
+            pos.func.layout.remove_inst(inst);
+        }
+    }
+}
+
+/// Get the flags of a vector access replacing scalar accesses with `flags`.
+///
+/// An `aligned` scalar access is only aligned to the size of a lane, so the vector access can't
+/// claim to be aligned.
+fn vector_flags(mut flags: MemFlags) -> MemFlags {
+    flags.clear_aligned();
+    flags
+}
+
+/// Get the first instruction of `insts` in layout order.
+fn first_inst<I: Iterator<Item = Inst>>(layout: &Layout, insts: I) -> Inst {
+    insts.min_by(|&a, &b| layout.cmp(a, b)).expect("Empty group")
+}
+
+/// Get the last instruction of `insts` in layout order.
+fn last_inst<I: Iterator<Item = Inst>>(layout: &Layout, insts: I) -> Inst {
+    insts.max_by(|&a, &b| layout.cmp(a, b)).expect("Empty group")
+}
+
+/// Can the binary instruction `opcode` be applied lane-wise to vectors?
+fn is_packable_binary(opcode: Opcode) -> bool {
+    match opcode {
+        Opcode::Iadd | Opcode::Isub | Opcode::Imul | Opcode::Band | Opcode::Bor |
+        Opcode::Bxor | Opcode::Fadd | Opcode::Fsub | Opcode::Fmul | Opcode::Fdiv => true,
+        _ => false,
+    }
+}
@@ -1,9 +1,11 @@
 //! Computing stack layout.
 
-use ir::StackSlots;
+use ir::{ArgumentLoc, ArgumentPurpose, Function, StackSlot, StackSlots};
 use ir::stackslot::{StackSize, StackOffset, StackSlotKind};
+use isa::RegUnit;
 use result::CtonError;
 use std::cmp::{min, max};
+use std::vec::Vec;
 
 /// Compute the stack frame layout.
 ///
@@ -112,11 +114,120 @@ pub fn layout_stack(frame: &mut StackSlots, alignment: StackSize) -> Result<Stac
     Ok(frame_size)
 }
 
+/// The final layout of a function's stack frame.
+///
+/// All offsets are relative to the stack pointer in the calling function, like
+/// `StackSlotData::offset`. The stack pointer in the function itself is at `-frame_size`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FrameLayout {
+    /// Total size of the stack frame, as computed by `layout_stack`.
+    pub frame_size: StackSize,
+
+    /// Size of the area at the top of the frame holding the return address and the registers
+    /// saved by the prologue.
+    pub saved_area_size: StackSize,
+
+    /// Registers saved by the prologue, including the frame pointer.
+    pub saved_registers: Vec<RegUnit>,
+
+    /// Size of the incoming arguments passed on the stack by the caller, above the frame.
+    pub incoming_args_size: StackSize,
+
+    /// Size of the outgoing argument area at the bottom of the frame.
+    pub outgoing_args_size: StackSize,
+
+    /// All the stack slots in the function.
+    pub slots: Vec<FrameSlot>,
+}
+
+/// A stack slot in a `FrameLayout`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FrameSlot {
+    /// The stack slot.
+    pub slot: StackSlot,
+
+    /// The kind of stack slot.
+    pub kind: StackSlotKind,
+
+    /// Offset of the slot relative to the stack pointer in the calling function.
+    ///
+    /// Outgoing argument slots are converted to the same base as the other slots.
+    pub offset: StackOffset,
+
+    /// Size of the slot in bytes.
+    pub size: StackSize,
+}
+
+impl FrameLayout {
+    /// Collect the frame layout of `func`.
+    ///
+    /// Returns `None` if the stack frame hasn't been laid out by `layout_stack` yet.
+    pub fn new(func: &Function) -> Option<Self> {
+        let frame_size = func.stack_slots.frame_size?;
+        let mut layout = Self {
+            frame_size,
+            saved_area_size: 0,
+            saved_registers: Vec::new(),
+            incoming_args_size: 0,
+            outgoing_args_size: 0,
+            slots: Vec::new(),
+        };
+
+        for ss in func.stack_slots.keys() {
+            let data = &func.stack_slots[ss];
+            let mut offset = match data.offset {
+                Some(offset) => offset,
+                None => continue,
+            };
+            let end = offset.wrapping_add(data.size as StackOffset);
+            match data.kind {
+                StackSlotKind::IncomingArg if offset < 0 => {
+                    let size = offset.wrapping_neg() as StackSize;
+                    layout.saved_area_size = max(layout.saved_area_size, size);
+                }
+                StackSlotKind::IncomingArg => {
+                    layout.incoming_args_size = max(layout.incoming_args_size, end as StackSize);
+                }
+                StackSlotKind::OutgoingArg => {
+                    layout.outgoing_args_size = max(layout.outgoing_args_size, end as StackSize);
+                    offset = offset.wrapping_sub(frame_size as StackOffset);
+                }
+                StackSlotKind::SpillSlot |
+                StackSlotKind::ExplicitSlot |
+                StackSlotKind::EmergencySlot => {}
+            }
+            layout.slots.push(FrameSlot {
+                slot: ss,
+                kind: data.kind,
+                offset,
+                size: data.size,
+            });
+        }
+
+        layout.saved_registers.extend(
+            func.signature
+                .params
+                .iter()
+                .filter(|arg| match arg.purpose {
+                    ArgumentPurpose::FramePointer |
+                    ArgumentPurpose::CalleeSaved => true,
+                    _ => false,
+                })
+                .filter_map(|arg| match arg.location {
+                    ArgumentLoc::Reg(reg) => Some(reg),
+                    _ => None,
+                }),
+        );
+
+        Some(layout)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use ir::{StackSlots, StackSlotData, StackSlotKind};
+    use ir::{AbiParam, ArgumentPurpose, Function, StackSlots, StackSlotData, StackSlotKind};
     use ir::types;
-    use super::layout_stack;
+    use super::{layout_stack, FrameLayout, FrameSlot};
     use ir::stackslot::StackOffset;
     use result::CtonError;
 
@@ -212,4 +323,63 @@ mod tests {
         assert_eq!(sss[ss1].offset, Some(-8));
         assert_eq!(sss[ss2].offset, Some(-12));
     }
+
+    #[test]
+    fn frame_layout() {
+        let mut func = Function::new();
+        assert_eq!(FrameLayout::new(&func), None);
+
+        let in0 = func.stack_slots.make_incoming_arg(types::I64, 0);
+        let saved = func.stack_slots.make_incoming_arg(types::I64, -16);
+        let ss0 = func.stack_slots.make_spill_slot(types::I64);
+        let out0 = func.stack_slots.get_outgoing_arg(types::I32, 0);
+        func.signature.params.push(AbiParam::special_reg(
+            types::I64,
+            ArgumentPurpose::FramePointer,
+            5,
+        ));
+        func.signature.params.push(AbiParam::new(types::I64));
+        func.signature.params.push(AbiParam::special_reg(
+            types::I64,
+            ArgumentPurpose::CalleeSaved,
+            3,
+        ));
+        assert_eq!(layout_stack(&mut func.stack_slots, 16), Ok(32));
+
+        let layout = FrameLayout::new(&func).unwrap();
+        assert_eq!(layout.frame_size, 32);
+        assert_eq!(layout.saved_area_size, 16);
+        assert_eq!(layout.saved_registers, [5, 3]);
+        assert_eq!(layout.incoming_args_size, 8);
+        assert_eq!(layout.outgoing_args_size, 4);
+        assert_eq!(
+            layout.slots,
+            [
+                FrameSlot {
+                    slot: in0,
+                    kind: StackSlotKind::IncomingArg,
+                    offset: 0,
+                    size: 8,
+                },
+                FrameSlot {
+                    slot: saved,
+                    kind: StackSlotKind::IncomingArg,
+                    offset: -16,
+                    size: 8,
+                },
+                FrameSlot {
+                    slot: ss0,
+                    kind: StackSlotKind::SpillSlot,
+                    offset: -24,
+                    size: 8,
+                },
+                FrameSlot {
+                    slot: out0,
+                    kind: StackSlotKind::OutgoingArg,
+                    offset: -32,
+                    size: 4,
+                },
+            ]
+        );
+    }
 }
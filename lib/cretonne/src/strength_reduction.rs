@@ -0,0 +1,476 @@
+//! Induction variable strength reduction.
+//!
+//! Array indexing loops compute an address like `base + i*stride` in every iteration, where `i` is
+//! an induction variable. This pass replaces such computations with a new induction variable
+//! that is initialized in the loop pre-header and incremented along with `i`:
+//!
+//! ```cton
+//! ebb1(v1: i64):
+//!     v2 = imul_imm v1, 4
+//!     v3 = iadd v0, v2
+//!     ...
+//!     v9 = iadd_imm v1, 1
+//!     brnz v8, ebb1(v9)
+//! ```
+//!
+//! Becomes a loop with a pointer that is incremented by 4 every iteration:
+//!
+//! ```cton
+//! ebb1(v1: i64, v3: i64):
+//!     ...
+//!     v9 = iadd_imm v1, 1
+//!     v11 = iadd_imm v3, 4
+//!     brnz v8, ebb1(v9, v11)
+//! ```
+//!
+//! A basic induction variable is a loop header parameter that is incremented by the same
+//! `iadd_imm` instruction on all the back edges. It can be scaled by `imul` with a loop-invariant
+//! value, `imul_imm`, or `ishl_imm`, and the scaled value can be added to a loop-invariant base.
+//!
+//! Loop-invariant values are the values defined outside the loop, so LICM should run before this
+//! pass to hoist the computations of bases and strides out of the loop. Like LICM, this pass
+//! inserts its initialization code in the loop pre-header, but loops without a natural pre-header
+//! are left alone.
+//!
+//! Every new induction variable is live across the whole loop, so a loop gets at most
+//! `MAX_NEW_IVS` of them. The remaining candidates are left alone unless they can share an
+//! induction variable that was already added.
+
+use cursor::{Cursor, FuncCursor};
+use dominator_tree::DominatorTree;
+use entity::EntityMap;
+use flowgraph::ControlFlowGraph;
+use ir::dfg::ValueDef;
+use ir::instructions::BranchInfo;
+use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value};
+use loop_analysis::{Loop, LoopAnalysis};
+use packed_option::PackedOption;
+use std::vec::Vec;
+use timing;
+
+/// Maximum number of induction variables added to a single loop.
+const MAX_NEW_IVS: usize = 4;
+
+/// A loop-invariant factor or increment.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scale {
+    /// A value defined outside the loop.
+    Value(Value),
+    /// A constant.
+    Imm(i64),
+}
+
+/// A basic induction variable.
+#[derive(Clone, Copy, PartialEq, Eq)]
+struct BasicIv {
+    /// Index of the induction variable in the loop header parameters.
+    index: usize,
+    /// The `iadd_imm` instruction computing its value in the next iteration.
+    increment: Inst,
+    /// The amount added by `increment`.
+    step: i64,
+}
+
+/// A computation of `base + iv*scale` that can be replaced with an induction variable.
+struct Candidate {
+    /// The instruction computing the value to replace.
+    inst: Inst,
+    /// The multiplication, if it is not `inst` itself.
+    mul: Option<Inst>,
+    iv: BasicIv,
+    scale: Scale,
+    base: Option<Value>,
+}
+
+/// Replace multiplications of induction variables with new induction variables in all the loops
+/// of `func`.
+///
+/// The CFG, dominator tree, and loop analysis remain valid.
+pub fn do_strength_reduction(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+) {
+    let _tt = timing::strength_reduction();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    // Uses of all values, counted the first time a candidate is found.
+    let mut use_counts = None;
+    for lp in loop_analysis.loops() {
+        let header = loop_analysis.loop_header(lp);
+        let entry = match loop_analysis.preheader(lp, func, cfg) {
+            Some((_, inst)) => inst,
+            None => continue,
+        };
+        match func.dfg.analyze_branch(entry) {
+            BranchInfo::SingleDest(..) => {}
+            _ => continue,
+        }
+
+        let candidates = find_candidates(func, cfg, domtree, loop_analysis, lp, &mut use_counts);
+        let uses = match use_counts {
+            Some(ref mut uses) => uses,
+            None => continue,
+        };
+        // Identical candidates share the same new induction variable.
+        let mut reduced: Vec<(BasicIv, Scale, Option<Value>, Value)> = Vec::new();
+        for cand in candidates {
+            let result = func.dfg.first_result(cand.inst);
+            let existing = reduced
+                .iter()
+                .find(|r| r.0 == cand.iv && r.1 == cand.scale && r.2 == cand.base)
+                .map(|r| r.3);
+            let new_iv = match existing {
+                Some(v) => v,
+                None if reduced.len() < MAX_NEW_IVS => {
+                    let v = insert_iv(func, cfg, domtree, header, entry, &cand, uses);
+                    reduced.push((cand.iv, cand.scale, cand.base, v));
+                    v
+                }
+                None => continue,
+            };
+
+            dbg!("Strength reducing {} to {}", result, new_iv);
+            func.dfg.clear_results(cand.inst);
+            func.dfg.change_to_alias(result, new_iv);
+            uses.change_to_alias(result, new_iv);
+            uses.remove_inst(func, cand.inst);
+            func.layout.remove_inst(cand.inst);
+            if let Some(mul) = cand.mul {
+                let product = func.dfg.first_result(mul);
+                if uses.counts[product] == 0 {
+                    uses.remove_inst(func, mul);
+                    func.layout.remove_inst(mul);
+                }
+            }
+        }
+    }
+}
+
+/// Uses of the values in a function, with aliases resolved.
+///
+/// The uses are counted once, and kept up to date as instructions are added and removed.
+struct Uses {
+    /// Number of uses of each value.
+    counts: EntityMap<Value, u32>,
+    /// The instruction that last added a use of each value.
+    users: EntityMap<Value, PackedOption<Inst>>,
+}
+
+impl Uses {
+    /// Count the uses of every value in `func`.
+    fn new(func: &Function) -> Self {
+        let mut uses = Uses {
+            counts: EntityMap::new(),
+            users: EntityMap::new(),
+        };
+        uses.counts.resize(func.dfg.num_values());
+        uses.users.resize(func.dfg.num_values());
+        for ebb in func.layout.ebbs() {
+            for inst in func.layout.ebb_insts(ebb) {
+                uses.add_inst(func, inst);
+            }
+        }
+        uses
+    }
+
+    /// Add a use of `value` by `inst`.
+    fn add(&mut self, func: &Function, value: Value, inst: Inst) {
+        let value = func.dfg.resolve_aliases(value);
+        self.counts[value] += 1;
+        self.users[value] = inst.into();
+    }
+
+    /// Add the uses of the arguments of `inst`.
+    fn add_inst(&mut self, func: &Function, inst: Inst) {
+        for &arg in func.dfg.inst_args(inst) {
+            self.add(func, arg, inst);
+        }
+    }
+
+    /// Remove the uses of the arguments of `inst`.
+    fn remove_inst(&mut self, func: &Function, inst: Inst) {
+        for &arg in func.dfg.inst_args(inst) {
+            let arg = func.dfg.resolve_aliases(arg);
+            self.counts[arg] -= 1;
+            if self.users[arg] == inst.into() {
+                self.users[arg] = None.into();
+            }
+        }
+    }
+
+    /// Move the uses of `value` to `dest`, which `value` has become an alias of.
+    fn change_to_alias(&mut self, value: Value, dest: Value) {
+        if self.counts[dest] != 0 {
+            self.users[dest] = None.into();
+        } else {
+            self.users[dest] = self.users[value];
+        }
+        self.counts[dest] += self.counts[value];
+        self.counts[value] = 0;
+        self.users[value] = None.into();
+    }
+
+    /// Get the only instruction using `value`, if it is used exactly once.
+    ///
+    /// Returns `None` when the single user is no longer known.
+    fn single_user(&self, value: Value) -> Option<Inst> {
+        if self.counts[value] == 1 {
+            self.users[value].expand()
+        } else {
+            None
+        }
+    }
+}
+
+/// Find the candidates for strength reduction in the loop `lp`.
+fn find_candidates(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+    use_counts: &mut Option<Uses>,
+) -> Vec<Candidate> {
+    let header = loop_analysis.loop_header(lp);
+    let mut candidates = Vec::new();
+    for ebb in func.layout.ebbs() {
+        if !loop_analysis.is_in_loop(ebb, lp) {
+            continue;
+        }
+        for inst in func.layout.ebb_insts(ebb) {
+            let (x, scale) = match scaled_value(func, loop_analysis, lp, inst) {
+                Some(s) => s,
+                None => continue,
+            };
+            let iv = match basic_iv(func, cfg, domtree, header, x) {
+                Some(iv) => iv,
+                None => continue,
+            };
+
+            // Fold the addition of a loop-invariant base if the product isn't used elsewhere.
+            let product = func.dfg.first_result(inst);
+            let uses = use_counts.get_or_insert_with(|| Uses::new(func));
+            let add = uses.single_user(product).and_then(|user| match func.dfg[user] {
+                InstructionData::Binary {
+                    opcode: Opcode::Iadd,
+                    args,
+                } if func.layout.inst_ebb(user).map_or(false, |e| {
+                    loop_analysis.is_in_loop(e, lp)
+                }) =>
+                {
+                    let base = if func.dfg.resolve_aliases(args[0]) == product {
+                        args[1]
+                    } else {
+                        args[0]
+                    };
+                    if is_invariant(func, loop_analysis, lp, base) {
+                        Some((user, base))
+                    } else {
+                        None
+                    }
+                }
+                _ => None,
+            });
+
+            candidates.push(match add {
+                Some((user, base)) => Candidate {
+                    inst: user,
+                    mul: Some(inst),
+                    iv,
+                    scale,
+                    base: Some(base),
+                },
+                None => Candidate {
+                    inst,
+                    mul: None,
+                    iv,
+                    scale,
+                    base: None,
+                },
+            });
+        }
+    }
+    candidates
+}
+
+/// If `inst` multiplies a value by a loop-invariant scale, return the value and the scale.
+fn scaled_value(
+    func: &Function,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+    inst: Inst,
+) -> Option<(Value, Scale)> {
+    let (x, scale) = match func.dfg[inst] {
+        InstructionData::Binary {
+            opcode: Opcode::Imul,
+            args,
+        } => {
+            if is_invariant(func, loop_analysis, lp, args[1]) {
+                (args[0], Scale::Value(args[1]))
+            } else if is_invariant(func, loop_analysis, lp, args[0]) {
+                (args[1], Scale::Value(args[0]))
+            } else {
+                return None;
+            }
+        }
+        InstructionData::BinaryImm {
+            opcode: Opcode::ImulImm,
+            arg,
+            imm,
+        } => (arg, Scale::Imm(imm.into())),
+        InstructionData::BinaryImm {
+            opcode: Opcode::IshlImm,
+            arg,
+            imm,
+        } => {
+            let amount: i64 = imm.into();
+            if amount < 0 || amount >= i64::from(func.dfg.value_type(arg).bits()) {
+                return None;
+            }
+            (arg, Scale::Imm(1 << amount))
+        }
+        _ => return None,
+    };
+    if func.dfg.value_type(x).is_int() {
+        Some((func.dfg.resolve_aliases(x), scale))
+    } else {
+        None
+    }
+}
+
+/// Check if `value` is a basic induction variable of the loop with header `header`.
+fn basic_iv(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    header: Ebb,
+    value: Value,
+) -> Option<BasicIv> {
+    let index = match func.dfg.value_def(value) {
+        ValueDef::Param(ebb, index) if ebb == header => index,
+        _ => return None,
+    };
+
+    let mut increment = None;
+    for (_, inst) in cfg.pred_iter(header) {
+        if !domtree.dominates(header, inst, &func.layout) {
+            continue;
+        }
+        let next = match func.dfg.analyze_branch(inst) {
+            BranchInfo::SingleDest(dest, args) if dest == header => args[index],
+            _ => return None,
+        };
+        let def = match func.dfg.value_def(func.dfg.resolve_aliases(next)) {
+            ValueDef::Result(def, _) => def,
+            ValueDef::Param(..) => return None,
+        };
+        if increment.is_some() && increment != Some(def) {
+            return None;
+        }
+        increment = Some(def);
+    }
+
+    let increment = increment?;
+    match func.dfg[increment] {
+        InstructionData::BinaryImm {
+            opcode: Opcode::IaddImm,
+            arg,
+            imm,
+        } if func.dfg.resolve_aliases(arg) == value => {
+            Some(BasicIv {
+                index,
+                increment,
+                step: imm.into(),
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Is `value` defined outside the loop `lp`?
+fn is_invariant(func: &Function, loop_analysis: &LoopAnalysis, lp: Loop, value: Value) -> bool {
+    let ebb = match func.dfg.value_def(func.dfg.resolve_aliases(value)) {
+        ValueDef::Result(inst, _) => func.layout.inst_ebb(inst),
+        ValueDef::Param(ebb, _) => Some(ebb),
+    };
+    ebb.map_or(false, |ebb| !loop_analysis.is_in_loop(ebb, lp))
+}
+
+/// Create a new induction variable for `cand` in the loop with header `header`, which is entered
+/// through the branch `entry`.
+///
+/// Returns the new loop header parameter. The uses of the inserted instructions are added to
+/// `uses`.
+fn insert_iv(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    header: Ebb,
+    entry: Inst,
+    cand: &Candidate,
+    uses: &mut Uses,
+) -> Value {
+    let ty = func.dfg.value_type(func.dfg.first_result(cand.inst));
+    let iv = cand.iv;
+
+    // Compute the initial value and the increment in the pre-header.
+    let initial = func.dfg.inst_variable_args(entry)[iv.index];
+    let before = func.layout.prev_inst(entry);
+    let (start, stride) = {
+        let mut pos = FuncCursor::new(func).at_inst(entry);
+        pos.use_srcloc(entry);
+        let (product, stride) = match cand.scale {
+            Scale::Value(k) => {
+                let product = pos.ins().imul(initial, k);
+                let stride = if iv.step == 1 {
+                    k
+                } else {
+                    pos.ins().imul_imm(k, iv.step)
+                };
+                (product, Scale::Value(stride))
+            }
+            Scale::Imm(c) => (
+                pos.ins().imul_imm(initial, c),
+                Scale::Imm(c.wrapping_mul(iv.step)),
+            ),
+        };
+        let start = match cand.base {
+            Some(base) => pos.ins().iadd(base, product),
+            None => product,
+        };
+        (start, stride)
+    };
+    let mut inst = func.layout.prev_inst(entry);
+    while inst != before {
+        let new_inst = inst.expect("Instructions inserted before the entry branch");
+        uses.add_inst(func, new_inst);
+        inst = func.layout.prev_inst(new_inst);
+    }
+    func.dfg.append_inst_arg(entry, start);
+    uses.add(func, start, entry);
+    let param = func.dfg.append_ebb_param(header, ty);
+
+    // Increment the new induction variable along with the basic induction variable.
+    let next = {
+        let mut pos = FuncCursor::new(func).at_inst(iv.increment);
+        pos.use_srcloc(iv.increment);
+        pos.next_inst();
+        match stride {
+            Scale::Value(stride) => pos.ins().iadd(param, stride),
+            Scale::Imm(stride) => pos.ins().iadd_imm(param, stride),
+        }
+    };
+    let increment = func.dfg.value_def(next).unwrap_inst();
+    uses.add_inst(func, increment);
+    for (_, inst) in cfg.pred_iter(header) {
+        if domtree.dominates(header, inst, &func.layout) {
+            func.dfg.append_inst_arg(inst, next);
+            uses.add(func, next, inst);
+        }
+    }
+    param
+}
@@ -4,7 +4,7 @@
 
 use std::fmt;
 
-pub use self::details::{TimingToken, PassTimes, take_current, add_to_current};
+pub use self::details::{TimingToken, PassTimes, PassTiming, take_current, add_to_current};
 
 // Each pass that can be timed is predefined with the `define_passes!` macro. Each pass has a
 // snake_case name and a plain text description used when printing out the timing report.
@@ -13,10 +13,11 @@ pub use self::details::{TimingToken, PassTimes, take_current, add_to_current};
 //
 // - A C-style enum containing all the pass names and a `None` variant.
 // - A usize constant with the number of defined passes.
+// - A const array of pass names.
 // - A const array of pass descriptions.
 // - A public function per pass used to start the timing of that pass.
 macro_rules! define_passes {
-    { $enum:ident, $num_passes:ident, $descriptions:ident;
+    { $enum:ident, $num_passes:ident, $names:ident, $descriptions:ident;
       $($pass:ident: $desc:expr,)+
     } => {
         #[allow(non_camel_case_types)]
@@ -25,6 +26,8 @@ macro_rules! define_passes {
 
         const $num_passes: usize = $enum::None as usize;
 
+        const $names: [&str; $num_passes] = [ $(stringify!($pass)),+ ];
+
         const $descriptions: [&str; $num_passes] = [ $($desc),+ ];
 
         $(
@@ -38,7 +41,7 @@ macro_rules! define_passes {
 
 // Pass definitions.
 define_passes!{
-    Pass, NUM_PASSES, DESCRIPTIONS;
+    Pass, NUM_PASSES, NAMES, DESCRIPTIONS;
 
     process_file: "Processing test file",
     parse_text: "Parsing textual Cretonne IL",
@@ -55,20 +58,34 @@ define_passes!{
     flowgraph: "Control flow graph",
     domtree: "Dominator tree",
     loop_analysis: "Loop analysis",
+    ebb_counters: "Insert EBB execution counters",
     preopt: "Pre-legalization rewriting",
     legalize: "Legalization",
     gvn: "Global value numbering",
+    sccp: "Sparse conditional constant propagation",
+    postopt: "Post-legalization rewriting",
     licm: "Loop invariant code motion",
+    slp: "SLP vectorization",
+    unroll: "Loop unrolling",
+    strength_reduction: "Induction variable strength reduction",
+    bounds_checks: "Heap bounds check optimization",
     unreachable_code: "Remove unreachable blocks",
+    cold_ebbs: "Move cold EBBs out of line",
+    critical_edges: "Split critical edges",
 
     regalloc: "Register allocation",
     ra_liveness: "RA liveness analysis",
     ra_cssa: "RA coalescing CSSA",
     ra_spilling: "RA spilling",
+    ra_spill_slots: "RA spill slot sharing",
     ra_reload: "RA reloading",
     ra_coloring: "RA coloring",
 
     prologue_epilogue: "Prologue/epilogue insertion",
+    copy_propagation: "Post-regalloc copy propagation",
+    shrink_instructions: "Instruction shrinking",
+    debug_values: "Resolve debug value locations",
+    deopt_points: "Resolve deoptimization point locations",
     binemit: "Binary machine code emission",
     layout_renumber: "Layout full renumbering",
 }
@@ -95,11 +112,12 @@ impl fmt::Display for Pass {
 /// performance-sensitive builds or restricted environments. The dummy implementation must provide
 /// `TimingToken` and `PassTimings` types and a `take_current` function.
 mod details {
-    use super::{Pass, NUM_PASSES, DESCRIPTIONS};
+    use super::{Pass, NUM_PASSES, NAMES, DESCRIPTIONS};
     use std::cell::{Cell, RefCell};
     use std::fmt;
     use std::mem;
     use std::time::{Instant, Duration};
+    use std::vec::Vec;
 
     /// A timing token is responsible for timing the currently running pass. Timing starts when it
     /// is created and ends when it is dropped.
@@ -118,7 +136,7 @@ mod details {
     }
 
     /// Accumulated timing information for a single pass.
-    #[derive(Default)]
+    #[derive(Default, Clone, Copy)]
     struct PassTime {
         /// Total time spent running this pas including children.
         total: Duration,
@@ -128,11 +146,51 @@ mod details {
     }
 
     /// Accumulated timing for all passes.
-    #[derive(Default)]
     pub struct PassTimes {
         pass: [PassTime; NUM_PASSES],
     }
 
+    impl Default for PassTimes {
+        fn default() -> Self {
+            Self { pass: [PassTime::default(); NUM_PASSES] }
+        }
+    }
+
+    /// The accumulated time of a single pass, as returned by `PassTimes::passes()`.
+    #[derive(Clone, Copy, Debug)]
+    pub struct PassTiming {
+        /// The snake_case name of the pass.
+        pub name: &'static str,
+
+        /// Plain text description of the pass.
+        pub description: &'static str,
+
+        /// Total time spent running the pass including child passes.
+        pub total: Duration,
+
+        /// Time spent running the pass itself, excluding child passes.
+        pub self_time: Duration,
+    }
+
+    impl PassTimes {
+        /// Get the timings of all the passes that have run, in the order they are defined.
+        pub fn passes(&self) -> Vec<PassTiming> {
+            self.pass
+                .iter()
+                .zip(NAMES.iter().zip(&DESCRIPTIONS))
+                .filter(|&(time, _)| time.total != Duration::default())
+                .map(|(time, (&name, &description))| {
+                    PassTiming {
+                        name,
+                        description,
+                        total: time.total,
+                        self_time: time.total.checked_sub(time.child).unwrap_or_default(),
+                    }
+                })
+                .collect()
+        }
+    }
+
     impl fmt::Display for PassTimes {
         fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
             writeln!(f, "======== ========  ==================================")?;
@@ -218,10 +276,22 @@ mod details {
 #[cfg(test)]
 mod test {
     use super::*;
+    use std::vec::Vec;
 
     #[test]
     fn display() {
         assert_eq!(Pass::None.to_string(), "<no pass>");
         assert_eq!(Pass::regalloc.to_string(), "Register allocation");
     }
+
+    #[test]
+    fn passes() {
+        take_current();
+        {
+            let _tt = regalloc();
+            let _child = ra_coloring();
+        }
+        let names: Vec<_> = take_current().passes().iter().map(|p| p.name).collect();
+        assert_eq!(names, ["regalloc", "ra_coloring"]);
+    }
 }
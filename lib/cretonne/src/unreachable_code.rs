@@ -35,9 +35,8 @@ pub fn eliminate_unreachable_code(
             pos.func.layout.remove_inst(inst);
         }
 
-        // Once the EBB is completely empty, we can update the CFG which removes it from any
-        // predecessor lists.
-        cfg.recompute_ebb(pos.func, ebb);
+        // Remove the edges to and from the EBB. Any EBB branching to it is unreachable too.
+        cfg.remove_ebb(ebb);
 
         // Finally, remove the EBB from the layout.
         pos.func.layout.remove_ebb(ebb);
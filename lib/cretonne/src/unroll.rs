@@ -0,0 +1,343 @@
+//! Loop unrolling.
+//!
+//! This pass completely unrolls small loops with a constant trip count. Only loops consisting of
+//! a single EBB are considered. The EBB must end with a conditional branch and a jump, one of
+//! them going back to the loop header and the other one leaving the loop:
+//!
+//! ```cton
+//! ebb1(v1: i32):
+//!     ...
+//!     v2 = iadd_imm v1, 1
+//!     v3 = icmp_imm slt v2, 4
+//!     brnz v3, ebb1(v2)
+//!     jump ebb2
+//! ```
+//!
+//! The trip count is computed by evaluating the induction variable `v1`, which must be initialized
+//! with a constant when entering the loop and incremented by a constant on the back edge. The
+//! loop condition must compare the induction variable, before or after the increment, against a
+//! constant.
+//!
+//! The unrolled loop executes the body once for each iteration, and then falls into the exit.
+
+use constant_folding::icmp;
+use dominator_tree::DominatorTree;
+use flowgraph::ControlFlowGraph;
+use ir::dfg::ValueDef;
+use ir::{Ebb, Function, Inst, InstBuilder, InstructionData, Opcode, Value, ValueList};
+use loop_analysis::{Loop, LoopAnalysis};
+use std::collections::HashMap;
+use std::vec::Vec;
+use timing;
+
+/// A single-EBB loop with a constant trip count.
+struct CountedLoop {
+    /// The loop header, which is also the whole loop body.
+    header: Ebb,
+    /// Number of times the loop body is executed.
+    trip_count: u32,
+    /// The conditional branch at the end of the loop.
+    branch: Inst,
+    /// The jump terminating the loop header.
+    jump: Inst,
+    /// Is the loop continued by `branch` rather than by `jump`?
+    branch_continues: bool,
+    /// Instruction computing the loop condition, if the condition has no other uses.
+    cond_inst: Option<Inst>,
+}
+
+/// Completely unroll the small counted loops in `func`.
+///
+/// A loop is only unrolled if the unrolled body has no more than `budget` instructions.
+///
+/// Returns true if any loops were unrolled. The control flow graph, dominator tree, and loop
+/// analysis must be recomputed in that case.
+pub fn do_unroll(
+    func: &mut Function,
+    cfg: &ControlFlowGraph,
+    domtree: &DominatorTree,
+    loop_analysis: &LoopAnalysis,
+    budget: u32,
+) -> bool {
+    let _tt = timing::unroll();
+    debug_assert!(cfg.is_valid());
+    debug_assert!(domtree.is_valid());
+    debug_assert!(loop_analysis.is_valid());
+
+    let counted: Vec<CountedLoop> = loop_analysis
+        .loops()
+        .filter_map(|lp| {
+            counted_loop(func, cfg, loop_analysis, lp, budget)
+        })
+        .collect();
+
+    for lp in &counted {
+        dbg!("Unrolling {} {} times", lp.header, lp.trip_count);
+        unroll(func, lp);
+    }
+    !counted.is_empty()
+}
+
+/// Count the number of uses of `value` in `func`.
+fn count_uses(func: &Function, value: Value) -> usize {
+    let value = func.dfg.resolve_aliases(value);
+    func.layout
+        .ebbs()
+        .flat_map(|ebb| func.layout.ebb_insts(ebb))
+        .map(|inst| {
+            func.dfg
+                .inst_args(inst)
+                .iter()
+                .filter(|&&arg| func.dfg.resolve_aliases(arg) == value)
+                .count()
+        })
+        .sum()
+}
+
+/// Get the value of the integer constant `value`, if it is one.
+fn iconst_value(func: &Function, value: Value) -> Option<i64> {
+    match func.dfg.value_def(func.dfg.resolve_aliases(value)) {
+        ValueDef::Result(inst, _) => {
+            match func.dfg[inst] {
+                InstructionData::UnaryImm {
+                    opcode: Opcode::Iconst,
+                    imm,
+                } => Some(imm.into()),
+                _ => None,
+            }
+        }
+        ValueDef::Param(..) => None,
+    }
+}
+
+/// Get the instruction defining `value` if it is in `ebb`.
+fn def_in_ebb(func: &Function, value: Value, ebb: Ebb) -> Option<Inst> {
+    match func.dfg.value_def(func.dfg.resolve_aliases(value)) {
+        ValueDef::Result(inst, _) if func.layout.inst_ebb(inst) == Some(ebb) => Some(inst),
+        _ => None,
+    }
+}
+
+/// Analyze the loop `lp`, and determine if it is a counted loop whose body can be unrolled within
+/// `budget` instructions.
+fn counted_loop(
+    func: &Function,
+    cfg: &ControlFlowGraph,
+    loop_analysis: &LoopAnalysis,
+    lp: Loop,
+    budget: u32,
+) -> Option<CountedLoop> {
+    // The header must have a single entry edge, and all back edges must come from the header
+    // itself.
+    let header = loop_analysis.loop_header(lp);
+    if loop_analysis.back_edges(lp).iter().any(
+        |&(pred, _)| pred != header,
+    )
+    {
+        return None;
+    }
+    let (_, entry) = loop_analysis.preheader(lp, func, cfg)?;
+
+    // The header ends in a conditional branch followed by a jump. No other instruction branches.
+    let jump = func.layout.last_inst(header)?;
+    let branch = func.layout.prev_inst(jump)?;
+    let jump_dest = match func.dfg[jump] {
+        InstructionData::Jump { destination, .. } => destination,
+        _ => return None,
+    };
+    let (branch_dest, cond) = match func.dfg[branch] {
+        InstructionData::Branch {
+            opcode,
+            destination,
+            ..
+        } if opcode == Opcode::Brz || opcode == Opcode::Brnz => {
+            (destination, func.dfg.inst_args(branch)[0])
+        }
+        _ => return None,
+    };
+    let branch_continues = match (branch_dest == header, jump_dest == header) {
+        (true, false) => true,
+        (false, true) => false,
+        _ => return None,
+    };
+    let body_size = func.layout.ebb_insts(header).count() as u32 - 2;
+    if func.layout.ebb_insts(header).take(body_size as usize).any(|inst| {
+        func.dfg[inst].opcode().is_branch()
+    })
+    {
+        return None;
+    }
+
+    // The loop condition compares an induction variable with a constant.
+    let cond_inst = def_in_ebb(func, cond, header)?;
+    let (cc, x, bound) = match func.dfg[cond_inst] {
+        InstructionData::IntCompareImm { cond, arg, imm, .. } => (cond, arg, imm.into()),
+        InstructionData::IntCompare { cond, args, .. } => {
+            (cond, args[0], iconst_value(func, args[1])?)
+        }
+        _ => return None,
+    };
+    let bits = func.dfg.value_type(x).bits();
+
+    // Find the induction variable, and whether it is compared before or after the increment.
+    let back_edge = if branch_continues { branch } else { jump };
+    let back_args = func.dfg.inst_variable_args(back_edge);
+    let params = func.dfg.ebb_params(header);
+    let x = func.dfg.resolve_aliases(x);
+    let (param_idx, compare_next) = match params.iter().position(|&p| p == x) {
+        Some(idx) => (idx, false),
+        None => (back_args.iter().position(|&a| func.dfg.resolve_aliases(a) == x)?, true),
+    };
+    let step = match def_in_ebb(func, back_args[param_idx], header).map(|inst| &func.dfg[inst]) {
+        Some(&InstructionData::BinaryImm {
+                 opcode: Opcode::IaddImm,
+                 arg,
+                 imm,
+             }) if func.dfg.resolve_aliases(arg) == params[param_idx] => imm.into(),
+        _ => return None,
+    };
+    let init = iconst_value(func, func.dfg.inst_variable_args(entry)[param_idx])?;
+
+    // Drop the condition from the unrolled body if nothing else uses it.
+    let (cond_inst, body_size) = if count_uses(func, cond) == 1 {
+        (Some(cond_inst), body_size - 1)
+    } else {
+        (None, body_size)
+    };
+
+    // Simulate the loop to find the trip count.
+    let max_trips = budget / body_size.max(1);
+    let continue_if = branch_continues == (func.dfg[branch].opcode() == Opcode::Brnz);
+    let mut i: i64 = init;
+    let mut trip_count = 0;
+    loop {
+        trip_count += 1;
+        if trip_count > max_trips {
+            return None;
+        }
+        let next = i.wrapping_add(step);
+        let value = if compare_next { next } else { i };
+        if icmp(cc, value, bound, bits) != continue_if {
+            break;
+        }
+        i = next;
+    }
+
+    Some(CountedLoop {
+        header,
+        trip_count,
+        branch,
+        jump,
+        branch_continues,
+        cond_inst,
+    })
+}
+
+/// Completely unroll the counted loop `lp`.
+///
+/// The original loop body becomes the first iteration, and copies of it for the remaining
+/// iterations are inserted after it. Uses of the loop values after the loop are changed to refer
+/// to the last iteration.
+fn unroll(func: &mut Function, lp: &CountedLoop) {
+    let header = lp.header;
+    let params: Vec<Value> = func.dfg.ebb_params(header).to_vec();
+    let body: Vec<Inst> = func.layout
+        .ebb_insts(header)
+        .filter(|&inst| {
+            inst != lp.branch && inst != lp.jump && Some(inst) != lp.cond_inst
+        })
+        .collect();
+    let back_edge = if lp.branch_continues { lp.branch } else { lp.jump };
+    let exit = if lp.branch_continues { lp.jump } else { lp.branch };
+
+    // Map from the original values to the values of the current iteration. The first iteration
+    // uses the original values.
+    let mut map: HashMap<Value, Value> = HashMap::new();
+    for _ in 1..lp.trip_count {
+        let next_params: Vec<Value> = func.dfg
+            .inst_variable_args(back_edge)
+            .iter()
+            .map(|&arg| lookup(func, &map, arg))
+            .collect();
+        for (&param, &value) in params.iter().zip(&next_params) {
+            map.insert(param, value);
+        }
+        for &inst in &body {
+            let copy = clone_inst(func, inst, &map);
+            func.layout.insert_inst(copy, lp.branch);
+            for (&orig, &new) in func.dfg.inst_results(inst).iter().zip(
+                func.dfg.inst_results(copy),
+            )
+            {
+                map.insert(orig, new);
+            }
+        }
+    }
+
+    // Leave through the exit edge with the values of the last iteration.
+    let (exit_dest, exit_args) = match func.dfg[exit] {
+        InstructionData::Jump { destination, .. } |
+        InstructionData::Branch { destination, .. } => {
+            let args: Vec<Value> = func.dfg
+                .inst_variable_args(exit)
+                .iter()
+                .map(|&arg| lookup(func, &map, arg))
+                .collect();
+            (destination, args)
+        }
+        _ => panic!("Unexpected loop exit"),
+    };
+    func.layout.remove_inst(lp.branch);
+    if let Some(cond_inst) = lp.cond_inst {
+        func.layout.remove_inst(cond_inst);
+    }
+    if func.dfg[lp.jump].opcode() == Opcode::Fallthrough && exit == lp.jump {
+        func.dfg.replace(lp.jump).fallthrough(exit_dest, &exit_args);
+    } else {
+        func.dfg.replace(lp.jump).jump(exit_dest, &exit_args);
+    }
+
+    // The loop header dominates all uses of its values outside the loop. Those uses see the
+    // values from the last iteration.
+    let ebbs: Vec<Ebb> = func.layout.ebbs().filter(|&ebb| ebb != header).collect();
+    for ebb in ebbs {
+        let mut next = func.layout.first_inst(ebb);
+        while let Some(inst) = next {
+            for i in 0..func.dfg.inst_args(inst).len() {
+                let arg = func.dfg.inst_args(inst)[i];
+                let new = lookup(func, &map, arg);
+                func.dfg.inst_args_mut(inst)[i] = new;
+            }
+            next = func.layout.next_inst(inst);
+        }
+    }
+}
+
+/// Get the value corresponding to `value` in the current iteration.
+fn lookup(func: &Function, map: &HashMap<Value, Value>, value: Value) -> Value {
+    let value = func.dfg.resolve_aliases(value);
+    *map.get(&value).unwrap_or(&value)
+}
+
+/// Create a copy of `inst` with its arguments mapped through `map`, and new result values.
+///
+/// The copy is not inserted in the layout.
+fn clone_inst(func: &mut Function, inst: Inst, map: &HashMap<Value, Value>) -> Inst {
+    let mut data = func.dfg[inst].clone();
+    // The value list is shared with the original instruction, so make a new one.
+    if let Some(list) = data.take_value_list() {
+        let args = list.as_slice(&func.dfg.value_lists).to_vec();
+        let mut copy = ValueList::new();
+        copy.extend(args, &mut func.dfg.value_lists);
+        data.put_value_list(copy);
+    }
+    let copy = func.dfg.make_inst(data);
+    for i in 0..func.dfg.inst_args(copy).len() {
+        let arg = func.dfg.inst_args(copy)[i];
+        func.dfg.inst_args_mut(copy)[i] = lookup(func, map, arg);
+    }
+    let ctrl_typevar = func.dfg.ctrl_typevar(inst);
+    func.dfg.make_inst_results(copy, ctrl_typevar);
+    func.srclocs[copy] = func.srclocs[inst];
+    copy
+}
@@ -0,0 +1,194 @@
+//! Verify the control flow graph and dominator tree.
+//!
+//! The `ControlFlowGraph` and `DominatorTree` are side structures that passes are expected to keep
+//! up to date when they mutate the function layout. These checks compare the side structures
+//! against freshly computed ones so a pass that forgets to update them is caught immediately.
+//! They are run by `verify_context` when the analyses are valid.
+
+use dominator_tree::DominatorTree;
+use flowgraph::ControlFlowGraph;
+use ir::entities::AnyEntity;
+use ir::{Function, Ebb, Inst};
+use iterators::IteratorExtras;
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::vec::Vec;
+use verifier::{Error, Result};
+
+/// Compare `cfg` against `expected_cfg` which was computed from `func`.
+pub fn cfg_integrity(
+    func: &Function,
+    expected_cfg: &ControlFlowGraph,
+    cfg: &ControlFlowGraph,
+) -> Result {
+    let mut expected_succs = BTreeSet::<Ebb>::new();
+    let mut got_succs = BTreeSet::<Ebb>::new();
+    let mut expected_preds = BTreeSet::<Inst>::new();
+    let mut got_preds = BTreeSet::<Inst>::new();
+
+    for ebb in func.layout.ebbs() {
+        expected_succs.extend(expected_cfg.succ_iter(ebb));
+        got_succs.extend(cfg.succ_iter(ebb));
+
+        let missing_succs: Vec<Ebb> = expected_succs.difference(&got_succs).cloned().collect();
+        if !missing_succs.is_empty() {
+            return err!(
+                ebb,
+                "cfg lacked the following successor(s) {:?}",
+                missing_succs
+            );
+        }
+
+        let excess_succs: Vec<Ebb> = got_succs.difference(&expected_succs).cloned().collect();
+        if !excess_succs.is_empty() {
+            return err!(ebb, "cfg had unexpected successor(s) {:?}", excess_succs);
+        }
+
+        expected_preds.extend(expected_cfg.pred_iter(ebb).map(|(_, inst)| inst));
+        for (pred_ebb, inst) in cfg.pred_iter(ebb) {
+            // The redundant EBB stored with each predecessor must agree with the layout.
+            let inst_ebb = func.layout.inst_ebb(inst);
+            if inst_ebb != Some(pred_ebb) {
+                let message = format!(
+                    "cfg predecessor {} recorded in {} but the layout has it in {:?}",
+                    inst,
+                    pred_ebb,
+                    inst_ebb
+                );
+                return Err(Error::new(ebb, message).with_related(inst));
+            }
+            got_preds.insert(inst);
+        }
+
+        let missing_preds: Vec<Inst> = expected_preds.difference(&got_preds).cloned().collect();
+        if !missing_preds.is_empty() {
+            return err!(
+                ebb,
+                "cfg lacked the following predecessor(s) {:?}",
+                missing_preds
+            );
+        }
+
+        let excess_preds: Vec<Inst> = got_preds.difference(&expected_preds).cloned().collect();
+        if !excess_preds.is_empty() {
+            return err!(ebb, "cfg had unexpected predecessor(s) {:?}", excess_preds);
+        }
+
+        expected_succs.clear();
+        got_succs.clear();
+        expected_preds.clear();
+        got_preds.clear();
+    }
+    Ok(())
+}
+
+/// Compare `domtree` against `expected_domtree` which was computed from `func`.
+pub fn domtree_integrity(
+    func: &Function,
+    expected_domtree: &DominatorTree,
+    domtree: &DominatorTree,
+) -> Result {
+    // We consider two `DominatorTree`s to be equal if they return the same immediate
+    // dominator for each EBB. Therefore the current domtree is valid if it matches the freshly
+    // computed one.
+    for ebb in func.layout.ebbs() {
+        let expected = expected_domtree.idom(ebb);
+        let got = domtree.idom(ebb);
+        if got != expected {
+            return err!(
+                ebb,
+                "invalid domtree, expected idom({}) = {:?}, got {:?}",
+                ebb,
+                expected,
+                got
+            );
+        }
+    }
+    // We also verify if the postorder defined by `DominatorTree` is sane
+    if domtree.cfg_postorder().len() != expected_domtree.cfg_postorder().len() {
+        return err!(
+            AnyEntity::Function,
+            "incorrect number of Ebbs in postorder traversal"
+        );
+    }
+    for (index, (&test_ebb, &true_ebb)) in
+        domtree
+            .cfg_postorder()
+            .iter()
+            .zip(expected_domtree.cfg_postorder().iter())
+            .enumerate()
+    {
+        if !func.layout.is_ebb_inserted(test_ebb) {
+            return err!(
+                test_ebb,
+                "invalid domtree, postorder ebb number {} is not in the layout",
+                index
+            );
+        }
+        if test_ebb != true_ebb {
+            return err!(
+                test_ebb,
+                "invalid domtree, postorder ebb number {} should be {}, got {}",
+                index,
+                true_ebb,
+                test_ebb
+            );
+        }
+    }
+    // We verify rpo_cmp on pairs of adjacent ebbs in the postorder
+    for (&prev_ebb, &next_ebb) in domtree.cfg_postorder().iter().adjacent_pairs() {
+        if expected_domtree.rpo_cmp(prev_ebb, next_ebb, &func.layout) != Ordering::Greater {
+            return err!(
+                next_ebb,
+                "invalid domtree, rpo_cmp does not says {} is greater than {}",
+                prev_ebb,
+                next_ebb
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use cursor::{Cursor, FuncCursor};
+    use dominator_tree::DominatorTree;
+    use flowgraph::ControlFlowGraph;
+    use ir::{AbiParam, Function, InstBuilder, types};
+    use settings;
+    use verifier::{ErrorCode, verify_context};
+
+    #[test]
+    fn stale_analyses() {
+        let mut func = Function::new();
+        func.signature.params.push(AbiParam::new(types::I32));
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let cond = func.dfg.append_ebb_param(ebb0, types::I32);
+        {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            cur.ins().return_(&[]);
+            cur.insert_ebb(ebb1);
+            cur.ins().return_(&[]);
+        }
+
+        let cfg = ControlFlowGraph::with_function(&func);
+        let domtree = DominatorTree::with_function(&func, &cfg);
+        let flags = settings::Flags::new(&settings::builder());
+        assert_eq!(verify_context(&func, &cfg, &domtree, &flags), Ok(()));
+
+        // Add a branch without updating the analyses.
+        {
+            let mut cur = FuncCursor::new(&mut func).at_first_insertion_point(ebb0);
+            cur.ins().brnz(cond, ebb1, &[]);
+        }
+        let err = verify_context(&func, &cfg, &domtree, &flags).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Cfg);
+
+        // The dominator tree is checked on its own when the CFG is recomputed.
+        let cfg = ControlFlowGraph::with_function(&func);
+        let err = verify_context(&func, &cfg, &domtree, &flags).unwrap_err();
+        assert_eq!(err.code, ErrorCode::DomTree);
+    }
+}
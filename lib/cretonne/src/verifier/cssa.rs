@@ -4,10 +4,11 @@ use dbg::DisplayList;
 use dominator_tree::{DominatorTree, DominatorTreePreorder};
 use flowgraph::ControlFlowGraph;
 use ir::{Function, ExpandedProgramPoint};
+use ir::instructions::BranchInfo;
 use regalloc::liveness::Liveness;
 use regalloc::virtregs::VirtRegs;
 use timing;
-use verifier::Result;
+use verifier::{Error, ErrorCode, Result};
 
 /// Verify conventional SSA form for `func`.
 ///
@@ -43,9 +44,10 @@ pub fn verify_cssa(
         liveness,
         preorder,
     };
-    verifier.check_virtregs()?;
-    verifier.check_cssa()?;
-    Ok(())
+    verifier
+        .check_virtregs()
+        .and_then(|()| verifier.check_cssa())
+        .map_err(|e| e.classify(ErrorCode::Cssa))
 }
 
 struct CssaVerifier<'a> {
@@ -117,13 +119,13 @@ impl<'a> CssaVerifier<'a> {
                     {
                         let ctx = self.liveness.context(&self.func.layout);
                         if self.liveness[prev_val].overlaps_def(def, def_ebb, ctx) {
-                            return err!(
-                                val,
+                            let message = format!(
                                 "Value def in {} = {} interferes with {}",
                                 vreg,
                                 DisplayList(values),
                                 prev_val
                             );
+                            return Err(Error::new(val, message).with_related(prev_val));
                         } else {
                             break;
                         }
@@ -139,7 +141,11 @@ impl<'a> CssaVerifier<'a> {
         for ebb in self.func.layout.ebbs() {
             let ebb_params = self.func.dfg.ebb_params(ebb);
             for (_, pred) in self.cfg.pred_iter(ebb) {
-                let pred_args = self.func.dfg.inst_variable_args(pred);
+                // The variable arguments of a `try_call` are passed to the callee, not the EBB.
+                let pred_args = match self.func.dfg.analyze_branch(pred) {
+                    BranchInfo::SingleDest(_, args) => args,
+                    _ => self.func.dfg.inst_variable_args(pred),
+                };
                 // This should have been caught by an earlier verifier pass.
                 assert_eq!(
                     ebb_params.len(),
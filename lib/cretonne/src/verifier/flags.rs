@@ -7,7 +7,7 @@ use ir;
 use isa;
 use packed_option::PackedOption;
 use std::result;
-use verifier::{Result, Error};
+use verifier::{Result, Error, ErrorCode};
 use timing;
 
 /// Verify that CPU flags are used correctly.
@@ -34,7 +34,7 @@ pub fn verify_flags(
         encinfo: isa.map(|isa| isa.encoding_info()),
         livein: EntityMap::new(),
     };
-    verifier.check()
+    verifier.check().map_err(|e| e.classify(ErrorCode::Flags))
 }
 
 struct FlagsVerifier<'a> {
@@ -125,7 +125,7 @@ impl<'a> FlagsVerifier<'a> {
                     }
                 }
                 BranchInfo::Table(jt) => {
-                    for (_, dest) in self.func.jump_tables[jt].entries() {
+                    for dest in self.func.jump_tables[jt].destinations() {
                         if let Some(val) = self.livein[dest].expand() {
                             merge(&mut live_val, val, inst)?;
                         }
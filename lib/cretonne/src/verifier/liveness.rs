@@ -7,7 +7,7 @@ use isa::TargetIsa;
 use regalloc::liveness::Liveness;
 use regalloc::liverange::LiveRange;
 use std::cmp::Ordering;
-use verifier::Result;
+use verifier::{ErrorCode, Result};
 use timing;
 
 /// Verify liveness information for `func`.
@@ -35,9 +35,10 @@ pub fn verify_liveness(
         cfg,
         liveness,
     };
-    verifier.check_ebbs()?;
-    verifier.check_insts()?;
-    Ok(())
+    verifier
+        .check_ebbs()
+        .and_then(|()| verifier.check_insts())
+        .map_err(|e| e.classify(ErrorCode::Liveness))
 }
 
 struct LivenessVerifier<'a> {
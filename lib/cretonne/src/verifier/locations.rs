@@ -1,10 +1,16 @@
 //! Verify value locations.
 
+use dominator_tree::{DominatorTree, DominatorTreePreorder};
+use entity::{EntityRef, SparseSet};
+use flowgraph::ControlFlowGraph;
 use ir;
-use isa;
+use ir::entities::AnyEntity;
+use isa::{self, ConstraintKind};
 use regalloc::RegDiversions;
+use regalloc::affinity::Affinity;
 use regalloc::liveness::Liveness;
-use verifier::Result;
+use std::vec::Vec;
+use verifier::{Error, ErrorCode, Result};
 use timing;
 
 /// Verify value locations for `func`.
@@ -17,7 +23,8 @@ use timing;
 /// and `regfill` instructions, but only inside an EBB.
 ///
 /// If a liveness analysis is provided, it is used to verify that there are no active register
-/// diversions across control flow edges.
+/// diversions across control flow edges, and that no two simultaneously live values are assigned
+/// to overlapping registers.
 pub fn verify_locations(
     isa: &isa::TargetIsa,
     func: &ir::Function,
@@ -31,7 +38,14 @@ pub fn verify_locations(
         encinfo: isa.encoding_info(),
         liveness,
     };
-    verifier.check_constraints()?;
+    verifier.check_constraints().map_err(
+        |e| e.classify(ErrorCode::Locations),
+    )?;
+    if let Some(liveness) = liveness {
+        verifier.check_interference(liveness).map_err(|e| {
+            e.classify(ErrorCode::Locations)
+        })?;
+    }
     Ok(())
 }
 
@@ -97,7 +111,40 @@ impl<'a> LocationVerifier<'a> {
             return Ok(());
         }
 
-        // TODO: We could give a better error message here.
+        // Find the operand that violates its constraint so we can give a precise error message.
+        let dfg = &self.func.dfg;
+        for (num, (&arg, constraint)) in dfg.inst_args(inst)
+            .iter()
+            .zip(constraints.ins)
+            .enumerate()
+        {
+            let loc = divert.get(arg, &self.func.locations);
+
+            if let ConstraintKind::Tied(out_index) = constraint.kind {
+                let out_val = dfg.inst_results(inst)[out_index as usize];
+                let out_loc = self.func.locations[out_val];
+                if loc != out_loc {
+                    return err!(
+                        inst,
+                        "{} operand {} ({}) in {} must be tied to result {} in {}",
+                        self.encinfo.display(enc),
+                        num,
+                        arg,
+                        loc.display(&self.reginfo),
+                        out_val,
+                        out_loc.display(&self.reginfo)
+                    );
+                }
+            }
+
+            self.check_operand_constraint(inst, enc, arg, loc, constraint)?;
+        }
+
+        for (&res, constraint) in dfg.inst_results(inst).iter().zip(constraints.outs) {
+            let loc = divert.get(res, &self.func.locations);
+            self.check_operand_constraint(inst, enc, res, loc, constraint)?;
+        }
+
         err!(
             inst,
             "{} constraints not satisfied",
@@ -105,6 +152,65 @@ impl<'a> LocationVerifier<'a> {
         )
     }
 
+    /// Check a single operand or result location against its constraint.
+    fn check_operand_constraint(
+        &self,
+        inst: ir::Inst,
+        enc: isa::Encoding,
+        value: ir::Value,
+        loc: ir::ValueLoc,
+        constraint: &isa::OperandConstraint,
+    ) -> Result {
+        if constraint.satisfied(loc) {
+            return Ok(());
+        }
+
+        match constraint.kind {
+            ConstraintKind::Reg |
+            ConstraintKind::Tied(_) => {
+                if let ir::ValueLoc::Reg(reg) = loc {
+                    err!(
+                        inst,
+                        "{} operand {} is in {} which is not in register class {}",
+                        self.encinfo.display(enc),
+                        value,
+                        self.reginfo.display_regunit(reg),
+                        constraint.regclass
+                    )
+                } else {
+                    err!(
+                        inst,
+                        "{} operand {} must be in a {} register, got {}",
+                        self.encinfo.display(enc),
+                        value,
+                        constraint.regclass,
+                        loc.display(&self.reginfo)
+                    )
+                }
+            }
+            ConstraintKind::FixedReg(reg) |
+            ConstraintKind::FixedTied(reg) => {
+                err!(
+                    inst,
+                    "{} operand {} must be in fixed register {}, got {}",
+                    self.encinfo.display(enc),
+                    value,
+                    self.reginfo.display_regunit(reg),
+                    loc.display(&self.reginfo)
+                )
+            }
+            ConstraintKind::Stack => {
+                err!(
+                    inst,
+                    "{} operand {} must be in a stack slot, got {}",
+                    self.encinfo.display(enc),
+                    value,
+                    loc.display(&self.reginfo)
+                )
+            }
+        }
+    }
+
     /// Check that the result values produced by a ghost instruction are not assigned a value
     /// location.
     fn check_ghost_results(&self, inst: ir::Inst) -> Result {
@@ -262,6 +368,191 @@ impl<'a> LocationVerifier<'a> {
         Ok(())
     }
 
+    /// Check that no two values assigned to overlapping registers or the same spill slot are live
+    /// at the same time.
+    ///
+    /// Values that are temporarily diverted anywhere in the function are skipped since their
+    /// global register may legitimately be reused while they are diverted.
+    fn check_interference(&self, liveness: &Liveness) -> Result {
+        let dfg = &self.func.dfg;
+
+        // Collect all values that are ever diverted.
+        let mut diverted = SparseSet::new();
+        for ebb in self.func.layout.ebbs() {
+            for inst in self.func.layout.ebb_insts(ebb) {
+                match dfg[inst] {
+                    ir::InstructionData::RegMove { arg, .. } |
+                    ir::InstructionData::RegSpill { arg, .. } |
+                    ir::InstructionData::RegFill { arg, .. } => {
+                        diverted.insert(arg);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        let cfg = ControlFlowGraph::with_function(self.func);
+        let domtree = DominatorTree::with_function(self.func, &cfg);
+        let mut preorder = DominatorTreePreorder::new();
+        preorder.compute(&domtree, &self.func.layout);
+        let mut stack = Vec::new();
+
+        // Collect `(unit, value)` for every register unit covered by a register value, sorted by
+        // unit and then by the dominator tree pre-order of the value definitions.
+        let mut assigned = Vec::new();
+        for value in self.func.locations.keys() {
+            let reg = match self.func.locations[value] {
+                ir::ValueLoc::Reg(reg) => reg,
+                _ => continue,
+            };
+            if !dfg.value_is_attached(value) || diverted.contains_key(value) ||
+                !self.is_reachable_def(value, &domtree)
+            {
+                continue;
+            }
+            if let Some(lr) = liveness.get(value) {
+                if let Affinity::Reg(rci) = lr.affinity {
+                    let width = isa::RegUnit::from(self.reginfo.rc(rci).width);
+                    assigned.extend((reg..reg + width).map(|unit| (unit, value)));
+                }
+            }
+        }
+        assigned.sort_by(|&(unit_a, a), &(unit_b, b)| {
+            unit_a.cmp(&unit_b).then_with(
+                || preorder.pre_cmp_def(a, b, self.func),
+            )
+        });
+
+        let mut start = 0;
+        while start < assigned.len() {
+            let unit = assigned[start].0;
+            let group = assigned[start..].iter().take_while(|&&(u, _)| u == unit);
+            if let Some((value, other)) = self.find_interference(
+                group.clone().map(|&(_, value)| value),
+                liveness,
+                &domtree,
+                &preorder,
+                &mut stack,
+            )
+            {
+                let message = format!(
+                    "{} in {} interferes with {} in {}",
+                    value,
+                    self.func.locations[value].display(&self.reginfo),
+                    other,
+                    self.func.locations[other].display(&self.reginfo)
+                );
+                return Err(Error::new(self.def_entity(value), message).with_related(other));
+            }
+            start += group.count();
+        }
+
+        // Collect `(slot, value)` for all values in spill slots, sorted by slot and then by the
+        // dominator tree pre-order of their definitions.
+        let mut spilled = Vec::new();
+        for value in self.func.locations.keys() {
+            let ss = match self.func.locations[value] {
+                ir::ValueLoc::Stack(ss) => ss,
+                _ => continue,
+            };
+            if self.func.stack_slots[ss].kind != ir::StackSlotKind::SpillSlot ||
+                !dfg.value_is_attached(value) || diverted.contains_key(value)
+            {
+                continue;
+            }
+            if liveness.get(value).is_some() && self.is_reachable_def(value, &domtree) {
+                spilled.push((ss, value));
+            }
+        }
+        spilled.sort_by(|&(ss_a, a), &(ss_b, b)| {
+            ss_a.index().cmp(&ss_b.index()).then_with(
+                || preorder.pre_cmp_def(a, b, self.func),
+            )
+        });
+
+        let mut start = 0;
+        while start < spilled.len() {
+            let ss = spilled[start].0;
+            let group = spilled[start..].iter().take_while(|&&(s, _)| s == ss);
+            if let Some((value, other)) = self.find_interference(
+                group.clone().map(|&(_, value)| value),
+                liveness,
+                &domtree,
+                &preorder,
+                &mut stack,
+            )
+            {
+                let message = format!("{} interferes with {} in {}", value, other, ss);
+                return Err(Error::new(self.def_entity(value), message).with_related(other));
+            }
+            start += group.count();
+        }
+
+        Ok(())
+    }
+
+    /// Get the instruction or EBB defining `value`.
+    fn def_entity(&self, value: ir::Value) -> AnyEntity {
+        match self.func.dfg.value_def(value) {
+            ir::ValueDef::Result(inst, _) => inst.into(),
+            ir::ValueDef::Param(ebb, _) => ebb.into(),
+        }
+    }
+
+    /// Is `value` defined in a reachable EBB?
+    fn is_reachable_def(&self, value: ir::Value, domtree: &DominatorTree) -> bool {
+        let def = self.func.dfg.value_def(value).pp();
+        domtree.is_reachable(self.func.layout.pp_ebb(def))
+    }
+
+    /// Find a pair of interfering values among `values` which are all assigned to the same
+    /// location.
+    ///
+    /// The values must be defined in reachable EBBs and sorted by the dominator tree pre-order of
+    /// their definitions. Two SSA values can only interfere if the definition of one dominates the
+    /// other. If the values seen so far don't interfere, only the closest dominating definition
+    /// can be live at the next one, so each value is only checked against one other value.
+    ///
+    /// The stack of dominating values is kept in `stack` which is cleared first.
+    fn find_interference<I: Iterator<Item = ir::Value>>(
+        &self,
+        values: I,
+        liveness: &Liveness,
+        domtree: &DominatorTree,
+        preorder: &DominatorTreePreorder,
+        stack: &mut Vec<ir::Value>,
+    ) -> Option<(ir::Value, ir::Value)> {
+        let dfg = &self.func.dfg;
+        let layout = &self.func.layout;
+        let ctx = liveness.context(layout);
+        stack.clear();
+
+        for value in values {
+            let def = dfg.value_def(value).pp();
+            let def_ebb = layout.pp_ebb(def);
+
+            // Values in EBBs that don't dominate `def_ebb` can't dominate any of the following
+            // values either.
+            while let Some(&top) = stack.last() {
+                if preorder.dominates(layout.pp_ebb(dfg.value_def(top).pp()), def_ebb) {
+                    break;
+                }
+                stack.pop();
+            }
+
+            let dominator = stack.iter().rev().cloned().find(|&other| {
+                domtree.dominates(dfg.value_def(other).pp(), def, layout)
+            });
+            if let Some(other) = dominator {
+                if liveness[other].overlaps_def(def, def_ebb, ctx) {
+                    return Some((value, other));
+                }
+            }
+            stack.push(value);
+        }
+        None
+    }
+
     /// We have active diversions before a branch. Make sure none of the diverted values are live
     /// on the outgoing CFG edges.
     fn check_cfg_edges(&self, inst: ir::Inst, divert: &RegDiversions) -> Result {
@@ -298,7 +589,7 @@ impl<'a> LocationVerifier<'a> {
             Table(jt) => {
                 for d in divert.all() {
                     let lr = &liveness[d.value];
-                    for (_, ebb) in self.func.jump_tables[jt].entries() {
+                    for ebb in self.func.jump_tables[jt].destinations() {
                         if lr.is_livein(ebb, liveness.context(&self.func.layout)) {
                             return err!(
                                 inst,
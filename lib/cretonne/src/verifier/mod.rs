@@ -15,16 +15,20 @@
 //! - All result values must be created for multi-valued instructions.
 //! - All referenced entities must exist. (Values, EBBs, stack slots, ...)
 //! - Instructions must not reference (eg. branch to) the entry block.
+//! - Jump table destinations must be EBBs in the layout other than the entry block, and a
+//!   `br_table` instruction must use a jump table with at least one destination.
 //!
 //! SSA form
 //!
 //! - Values must be defined by an instruction that exists and that is inserted in
 //!   an EBB, or be an argument of an existing EBB.
-//! - Values used by an instruction must dominate the instruction.
+//! - Values used by an instruction must dominate the instruction. A violation is reported with
+//!   the shortest CFG path from the entry block to the use that doesn't pass the definition.
 //!
 //! Control flow graph and dominator tree integrity:
 //!
 //! - All predecessors in the CFG must be branches to the EBB.
+//! - All predecessors in the CFG must be recorded with the EBB containing the branch.
 //! - All branches to an EBB must be present in the CFG.
 //! - A recomputed dominator tree is identical to the existing one.
 //!
@@ -34,8 +38,12 @@
 //!   For polymorphic opcodes, determine the controlling type variable first.
 //! - Branches and jumps must pass arguments to destination EBBs that match the
 //!   expected types exactly. The number of arguments must match.
-//! - All EBBs in a jump table must take no arguments.
+//! - All EBBs in a jump table must take no arguments, whether or not the table is used.
+//! - Vector lane masks must have a valid source lane index for every lane in the result.
 //! - Function calls are type checked against their signature.
+//! - Call signatures can't have frame-related special-purpose parameters (link, fp, csr).
+//! - Signatures have at most one of each unique special-purpose parameter, and legalized
+//!   signatures assign every parameter to a distinct location.
 //! - The entry block must take arguments that match the signature of the current
 //!   function.
 //! - All return instructions must have return value operands matching the current
@@ -44,6 +52,16 @@
 //! Global variables
 //!
 //! - Detect cycles in deref(base) declarations.
+//! - Tables must have valid base and bound global variables and a non-zero element size.
+//!
+//! Memory flags
+//!
+//! - An `aligned` load or store addressing a stack slot via `stack_addr` must have an effective
+//!   offset that is a multiple of the access size in a slot that is at least that aligned.
+//! - A `notrap` load or store addressing a heap via `heap_addr` must stay inside the range that
+//!   `heap_addr` bounds checked. Accesses beyond that range rely on the guard pages to trap.
+//! - A `notrap` load or store addressing a table via `table_addr` must stay inside the table
+//!   element. Tables have no guard pages.
 //!
 //! TODO:
 //! Ad hoc checking
@@ -57,24 +75,25 @@
 
 use dbg::DisplayList;
 use dominator_tree::DominatorTree;
-use entity::SparseSet;
+use entity::{EntityMap, EntitySet, SparseSet};
 use flowgraph::ControlFlowGraph;
 use ir::entities::AnyEntity;
 use ir::instructions::{InstructionFormat, BranchInfo, ResolvedConstraint, CallInfo};
 use ir::{types, Function, ValueDef, Ebb, Inst, SigRef, FuncRef, ValueList, JumpTable, StackSlot,
-         StackSlotKind, GlobalVar, Value, Type, Opcode, ValueLoc, ArgumentLoc};
+         StackSlotKind, GlobalVar, Value, Type, Opcode, ValueLoc, ArgumentLoc, ArgumentPurpose,
+         ProgramOrder};
+use packed_option::PackedOption;
 use ir;
 use isa::TargetIsa;
-use iterators::IteratorExtras;
+use self::cfg::{cfg_integrity, domtree_integrity};
 use self::flags::verify_flags;
 use settings::{Flags, FlagsOrIsa};
 use std::cmp::Ordering;
-use std::collections::BTreeSet;
 use std::error as std_error;
 use std::fmt::{self, Display, Formatter, Write};
 use std::result;
-use std::vec::Vec;
 use std::string::String;
+use std::vec::Vec;
 use timing;
 
 pub use self::cssa::verify_cssa;
@@ -84,34 +103,129 @@ pub use self::locations::verify_locations;
 // Create an `Err` variant of `Result<X>` from a location and `format!` arguments.
 macro_rules! err {
     ( $loc:expr, $msg:expr ) => {
-        Err(::verifier::Error {
-            location: $loc.into(),
-            message: String::from($msg),
-        })
+        Err(::verifier::Error::new($loc, String::from($msg)))
     };
 
     ( $loc:expr, $fmt:expr, $( $arg:expr ),+ ) => {
-        Err(::verifier::Error {
-            location: $loc.into(),
-            message: format!( $fmt, $( $arg ),+ ),
-        })
+        Err(::verifier::Error::new($loc, format!( $fmt, $( $arg ),+ )))
     };
 }
 
+mod cfg;
 mod cssa;
 mod flags;
 mod liveness;
 mod locations;
 
+/// The kind of check that produced a verifier error.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ErrorCode {
+    /// The error hasn't been attributed to a specific check.
+    Unclassified,
+    /// EBB integrity: terminators and instruction / parameter membership.
+    EbbIntegrity,
+    /// Instruction integrity: formats, result counts, and entity references.
+    InstIntegrity,
+    /// SSA form: values must be defined before use by a dominating definition.
+    Ssa,
+    /// The control flow graph doesn't match the function layout.
+    Cfg,
+    /// The dominator tree doesn't match the function layout.
+    DomTree,
+    /// Type checking of instruction operands, results, and branch arguments.
+    Typecheck,
+    /// Signature and call site ABI checks.
+    Abi,
+    /// Global variable declarations.
+    GlobalVar,
+    /// Memory flags on loads and stores.
+    MemFlags,
+    /// Memory orderings on atomic instructions.
+    Ordering,
+    /// Instruction encodings.
+    Encoding,
+    /// The `return_at_end` setting.
+    ReturnAtEnd,
+    /// CPU flags values.
+    Flags,
+    /// Value locations assigned by the register allocator.
+    Locations,
+    /// Live ranges computed for register allocation.
+    Liveness,
+    /// Conventional SSA form.
+    Cssa,
+    /// Unreachable code.
+    Unreachable,
+    /// Machine code emitted for an instruction doesn't match its encoding and operands.
+    MachineCode,
+}
+
+impl Display for ErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            ErrorCode::Unclassified => "unclassified",
+            ErrorCode::EbbIntegrity => "ebb-integrity",
+            ErrorCode::InstIntegrity => "inst-integrity",
+            ErrorCode::Ssa => "ssa",
+            ErrorCode::Cfg => "cfg",
+            ErrorCode::DomTree => "domtree",
+            ErrorCode::Typecheck => "typecheck",
+            ErrorCode::Abi => "abi",
+            ErrorCode::GlobalVar => "global-var",
+            ErrorCode::MemFlags => "memflags",
+            ErrorCode::Ordering => "ordering",
+            ErrorCode::Encoding => "encoding",
+            ErrorCode::ReturnAtEnd => "return-at-end",
+            ErrorCode::Flags => "flags",
+            ErrorCode::Locations => "locations",
+            ErrorCode::Liveness => "liveness",
+            ErrorCode::Cssa => "cssa",
+            ErrorCode::Unreachable => "unreachable",
+            ErrorCode::MachineCode => "machine-code",
+        })
+    }
+}
+
 /// A verifier error.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Error {
     /// The entity causing the verifier error.
     pub location: AnyEntity,
+    /// Other entities involved in the error, like the definition of a value that doesn't dominate
+    /// its use.
+    pub related: Vec<AnyEntity>,
+    /// The kind of check that failed.
+    pub code: ErrorCode,
     /// Error message.
     pub message: String,
 }
 
+impl Error {
+    /// Create a new unclassified error at `location`.
+    pub fn new<L: Into<AnyEntity>>(location: L, message: String) -> Error {
+        Error {
+            location: location.into(),
+            related: Vec::new(),
+            code: ErrorCode::Unclassified,
+            message,
+        }
+    }
+
+    /// Add a related entity to this error.
+    pub fn with_related<E: Into<AnyEntity>>(mut self, entity: E) -> Error {
+        self.related.push(entity.into());
+        self
+    }
+
+    /// Attribute this error to `code` unless it has already been classified.
+    pub fn classify(mut self, code: ErrorCode) -> Error {
+        if self.code == ErrorCode::Unclassified {
+            self.code = code;
+        }
+        self
+    }
+}
+
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(f, "{}: {}", self.location, self.message)
@@ -127,12 +241,61 @@ impl std_error::Error for Error {
 /// Verifier result.
 pub type Result = result::Result<(), Error>;
 
+/// The severity of a verifier issue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Suspicious, but valid IL. Compilation can proceed.
+    Warning,
+    /// Invalid IL. Compiling the function is likely to fail or produce wrong code.
+    Error,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        })
+    }
+}
+
+/// A verifier error or warning reported by `verify_function_collect`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct VerifierIssue {
+    /// How serious is this issue?
+    pub severity: Severity,
+    /// The entity and message describing the issue.
+    pub error: Error,
+}
+
+impl Display for VerifierIssue {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.severity, self.error)
+    }
+}
+
 /// Verify `func`.
 pub fn verify_function<'a, FOI: Into<FlagsOrIsa<'a>>>(func: &Function, fisa: FOI) -> Result {
     let _tt = timing::verifier();
     Verifier::new(func, fisa.into()).run()
 }
 
+/// Verify `func` and return all the issues found instead of stopping at the first error.
+///
+/// Each instruction reports at most one error since later checks on an instruction assume that
+/// the earlier ones passed. Function-wide checks that depend on every instruction being well
+/// formed are skipped if any errors were found. The returned issues are in layout order, with
+/// warnings included.
+pub fn verify_function_collect<'a, FOI: Into<FlagsOrIsa<'a>>>(
+    func: &Function,
+    fisa: FOI,
+) -> Vec<VerifierIssue> {
+    let _tt = timing::verifier();
+    let mut issues = Vec::new();
+    Verifier::new(func, fisa.into()).collect(&mut issues);
+    issues
+}
+
 /// Verify `func` after checking the integrity of associated context data structures `cfg` and
 /// `domtree`.
 pub fn verify_context<'a, FOI: Into<FlagsOrIsa<'a>>>(
@@ -144,10 +307,13 @@ pub fn verify_context<'a, FOI: Into<FlagsOrIsa<'a>>>(
     let _tt = timing::verifier();
     let verifier = Verifier::new(func, fisa.into());
     if cfg.is_valid() {
-        verifier.cfg_integrity(cfg)?;
+        cfg_integrity(func, &verifier.expected_cfg, cfg).map_err(
+            |e| e.classify(ErrorCode::Cfg),
+        )?;
     }
     if domtree.is_valid() {
-        verifier.domtree_integrity(domtree)?;
+        domtree_integrity(func, &verifier.expected_domtree, domtree)
+            .map_err(|e| e.classify(ErrorCode::DomTree))?;
     }
     verifier.run()
 }
@@ -182,7 +348,21 @@ impl<'a> Verifier<'a> {
             seen.insert(gv);
 
             let mut cur = gv;
-            while let ir::GlobalVarData::Deref { base, .. } = self.func.global_vars[cur] {
+            loop {
+                let base = match self.func.global_vars[cur] {
+                    ir::GlobalVarData::Deref { base, .. } => base,
+                    ir::GlobalVarData::HeapBase { heap, .. } => {
+                        if !self.func.heaps.is_valid(heap) {
+                            return err!(cur, "invalid heap {}", heap);
+                        }
+                        // The heap base address is loaded from the heap's base global.
+                        match self.func.heaps[heap].base {
+                            ir::HeapBase::GlobalVar(base) => base,
+                            ir::HeapBase::ReservedReg => break,
+                        }
+                    }
+                    _ => break,
+                };
                 if seen.insert(base).is_some() {
                     return err!(gv, "deref cycle: {}", DisplayList(seen.as_slice()));
                 }
@@ -194,6 +374,51 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    // Check the global variables and element sizes of the table declarations.
+    fn verify_tables(&self) -> Result {
+        for table in self.func.tables.keys() {
+            let data = &self.func.tables[table];
+            for &gv in &[data.base_gv, data.bound_gv] {
+                if !self.func.global_vars.is_valid(gv) {
+                    return err!(table, "invalid global variable {}", gv);
+                }
+            }
+            let element_size: i64 = data.element_size.into();
+            if element_size <= 0 {
+                return err!(table, "element size must be positive");
+            }
+        }
+
+        Ok(())
+    }
+
+    // Check that the jump table destinations are EBBs in the layout that take no arguments.
+    //
+    // This applies to all jump tables, even those that aren't used by a `br_table` instruction.
+    fn verify_jump_tables(&self) -> Result {
+        for jt in self.func.jump_tables.keys() {
+            for dest in self.func.jump_tables[jt].destinations() {
+                if !self.func.dfg.ebb_is_valid(dest) || !self.func.layout.is_ebb_inserted(dest) {
+                    return err!(jt, "invalid destination {}", dest);
+                }
+                if self.func.layout.entry_block() == Some(dest) {
+                    return err!(jt, "invalid reference to entry ebb {}", dest);
+                }
+                let arg_count = self.func.dfg.num_ebb_params(dest);
+                if arg_count != 0 {
+                    let message = format!(
+                        "destination {} can't take arguments, but it has {}",
+                        dest,
+                        arg_count
+                    );
+                    return Err(Error::new(jt, message).classify(ErrorCode::Typecheck));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     fn ebb_integrity(&self, ebb: Ebb, inst: Inst) -> Result {
 
         let is_terminator = self.func.dfg[inst].opcode().is_terminator();
@@ -201,11 +426,11 @@ impl<'a> Verifier<'a> {
 
         if is_terminator && !is_last_inst {
             // Terminating instructions only occur at the end of blocks.
-            return err!(
-                inst,
+            let message = format!(
                 "a terminator instruction was encountered before the end of {}",
                 ebb
             );
+            return Err(Error::new(inst, message).with_related(ebb));
         }
         if is_last_inst && !is_terminator {
             return err!(ebb, "block does not end in a terminator instruction!");
@@ -315,11 +540,25 @@ impl<'a> Verifier<'a> {
             }
             BranchTable { table, .. } => {
                 self.verify_jump_table(inst, table)?;
+                // An empty table has nowhere to branch to.
+                if self.func.jump_tables[table].destinations().next().is_none() {
+                    return err!(inst, "{} has no destinations", table);
+                }
             }
             Call { func_ref, ref args, .. } => {
                 self.verify_func_ref(inst, func_ref)?;
                 self.verify_value_list(inst, args)?;
             }
+            TryCall {
+                func_ref,
+                destination,
+                ref args,
+                ..
+            } => {
+                self.verify_func_ref(inst, func_ref)?;
+                self.verify_ebb(inst, destination)?;
+                self.verify_value_list(inst, args)?;
+            }
             IndirectCall { sig_ref, ref args, .. } => {
                 self.verify_sig_ref(inst, sig_ref)?;
                 self.verify_value_list(inst, args)?;
@@ -337,12 +576,30 @@ impl<'a> Verifier<'a> {
             HeapAddr { heap, .. } => {
                 self.verify_heap(inst, heap)?;
             }
+            TableAddr { table, offset, .. } => {
+                self.verify_table(inst, table)?;
+                let element_size: i64 = self.func.tables[table].element_size.into();
+                let offset: i64 = offset.into();
+                if offset < 0 || offset >= element_size {
+                    return err!(
+                        inst,
+                        "offset {} is outside the {}-byte elements of {}",
+                        offset,
+                        element_size,
+                        table
+                    );
+                }
+            }
             RegSpill { dst, .. } => {
                 self.verify_stack_slot(inst, dst)?;
             }
             RegFill { src, .. } => {
                 self.verify_stack_slot(inst, src)?;
             }
+            Shuffle { mask, .. } |
+            Swizzle { mask, .. } => {
+                self.verify_lane_mask(inst, mask)?;
+            }
 
             // Exhaustive list so we can't forget to add new formats
             Unary { .. } |
@@ -363,8 +620,18 @@ impl<'a> Verifier<'a> {
             IntSelect { .. } |
             Load { .. } |
             Store { .. } |
+            LoadComplex { .. } |
+            StoreComplex { .. } |
+            AtomicLoad { .. } |
+            AtomicStore { .. } |
+            AtomicRmw { .. } |
+            AtomicCas { .. } |
+            Fence { .. } |
+            BulkMemory { .. } |
             RegMove { .. } |
             CopySpecial { .. } |
+            DebugValue { .. } |
+            DeoptPoint { .. } |
             Trap { .. } |
             CondTrap { .. } |
             IntCondTrap { .. } |
@@ -427,6 +694,14 @@ impl<'a> Verifier<'a> {
         }
     }
 
+    fn verify_table(&self, inst: Inst, table: ir::Table) -> Result {
+        if !self.func.tables.is_valid(table) {
+            err!(inst, "invalid table {}", table)
+        } else {
+            Ok(())
+        }
+    }
+
     fn verify_value_list(&self, inst: Inst, l: &ValueList) -> Result {
         if !l.is_valid(&self.func.dfg.value_lists) {
             err!(inst, "invalid value list reference {:?}", l)
@@ -443,6 +718,14 @@ impl<'a> Verifier<'a> {
         }
     }
 
+    fn verify_lane_mask(&self, inst: Inst, mask: ir::LaneMask) -> Result {
+        if !self.func.dfg.lane_masks.is_valid(mask) {
+            err!(inst, "invalid lane mask reference {}", mask)
+        } else {
+            Ok(())
+        }
+    }
+
     fn verify_value(&self, loc_inst: Inst, v: Value) -> Result {
         let dfg = &self.func.dfg;
         if !dfg.value_is_valid(v) {
@@ -480,7 +763,34 @@ impl<'a> Verifier<'a> {
                         &self.func.layout,
                     )
                 {
-                    return err!(loc_inst, "uses value from non-dominating {}", def_inst);
+                    let message = format!(
+                        "uses {} from non-dominating {} (path from entry: {})",
+                        v,
+                        def_inst,
+                        self.display_path(dfg.value_def(v), loc_inst)
+                    );
+                    return Err(Error::new(loc_inst, message).with_related(def_inst).classify(
+                        ErrorCode::Ssa,
+                    ));
+                }
+                // The results of a `try_call` are not available in its landing pad.
+                if let ir::InstructionData::TryCall { destination, .. } = dfg[def_inst] {
+                    if is_reachable &&
+                        self.expected_domtree.dominates(
+                            destination,
+                            loc_inst,
+                            &self.func.layout,
+                        )
+                    {
+                        let message = format!(
+                            "uses {} which is unavailable in landing pad {}",
+                            v,
+                            destination
+                        );
+                        return Err(Error::new(loc_inst, message).with_related(def_inst).classify(
+                            ErrorCode::Ssa,
+                        ));
+                    }
                 }
             }
             ValueDef::Param(ebb, _) => {
@@ -505,71 +815,79 @@ impl<'a> Verifier<'a> {
                         &self.func.layout,
                     )
                 {
-                    return err!(loc_inst, "uses value arg from non-dominating {}", ebb);
+                    let message = format!(
+                        "uses {} from non-dominating {} (path from entry: {})",
+                        v,
+                        ebb,
+                        self.display_path(dfg.value_def(v), loc_inst)
+                    );
+                    return Err(Error::new(loc_inst, message).with_related(ebb).classify(
+                        ErrorCode::Ssa,
+                    ));
                 }
             }
         }
         Ok(())
     }
 
-    fn domtree_integrity(&self, domtree: &DominatorTree) -> Result {
-        // We consider two `DominatorTree`s to be equal if they return the same immediate
-        // dominator for each EBB. Therefore the current domtree is valid if it matches the freshly
-        // computed one.
-        for ebb in self.func.layout.ebbs() {
-            let expected = self.expected_domtree.idom(ebb);
-            let got = domtree.idom(ebb);
-            if got != expected {
-                return err!(
-                    ebb,
-                    "invalid domtree, expected idom({}) = {:?}, got {:?}",
-                    ebb,
-                    expected,
-                    got
-                );
+    /// Find a shortest CFG path from the entry block to `use_inst` that doesn't pass `def`.
+    ///
+    /// Return the EBBs on the path, starting with the entry block and ending with the EBB
+    /// containing `use_inst`. Such a path explains why `def` doesn't dominate `use_inst`, so an
+    /// empty vector is only returned when it does.
+    fn path_avoiding(&self, def: ValueDef, use_inst: Inst) -> Vec<Ebb> {
+        let layout = &self.func.layout;
+        let entry = match layout.entry_block() {
+            Some(entry) => entry,
+            None => return Vec::new(),
+        };
+
+        // Can control leave `ebb` through `branch` without executing `def`?
+        let avoids_def = |ebb: Ebb, branch: Inst| match def {
+            ValueDef::Result(def_inst, _) => {
+                layout.inst_ebb(def_inst) != Some(ebb) ||
+                    layout.cmp(branch, def_inst) == Ordering::Less
             }
-        }
-        // We also verify if the postorder defined by `DominatorTree` is sane
-        if domtree.cfg_postorder().len() != self.expected_domtree.cfg_postorder().len() {
-            return err!(
-                AnyEntity::Function,
-                "incorrect number of Ebbs in postorder traversal"
-            );
-        }
-        for (index, (&test_ebb, &true_ebb)) in
-            domtree
-                .cfg_postorder()
-                .iter()
-                .zip(self.expected_domtree.cfg_postorder().iter())
-                .enumerate()
-        {
-            if test_ebb != true_ebb {
-                return err!(
-                    test_ebb,
-                    "invalid domtree, postorder ebb number {} should be {}, got {}",
-                    index,
-                    true_ebb,
-                    test_ebb
-                );
+            ValueDef::Param(def_ebb, _) => def_ebb != ebb,
+        };
+
+        // Search backwards from the use, recording the next EBB on the path for each EBB found.
+        let use_ebb = layout.pp_ebb(use_inst);
+        let mut next = EntityMap::<Ebb, PackedOption<Ebb>>::new();
+        let mut visited = EntitySet::new();
+        visited.insert(use_ebb);
+        let mut worklist = vec![use_ebb];
+        let mut i = 0;
+        while i < worklist.len() {
+            let ebb = worklist[i];
+            i += 1;
+            if ebb == entry {
+                let mut path = vec![entry];
+                while let Some(ebb) = next[*path.last().unwrap()].expand() {
+                    path.push(ebb);
+                }
+                return path;
+            }
+            for (pred, branch) in self.expected_cfg.pred_iter(ebb) {
+                if avoids_def(pred, branch) && visited.insert(pred) {
+                    next[pred] = ebb.into();
+                    worklist.push(pred);
+                }
             }
         }
-        // We verify rpo_cmp on pairs of adjacent ebbs in the postorder
-        for (&prev_ebb, &next_ebb) in domtree.cfg_postorder().iter().adjacent_pairs() {
-            if self.expected_domtree.rpo_cmp(
-                prev_ebb,
-                next_ebb,
-                &self.func.layout,
-            ) != Ordering::Greater
-            {
-                return err!(
-                    next_ebb,
-                    "invalid domtree, rpo_cmp does not says {} is greater than {}",
-                    prev_ebb,
-                    next_ebb
-                );
+        Vec::new()
+    }
+
+    /// Format the path found by `path_avoiding` like `ebb0 -> ebb3`.
+    fn display_path(&self, def: ValueDef, use_inst: Inst) -> String {
+        let mut text = String::new();
+        for (i, ebb) in self.path_avoiding(def, use_inst).into_iter().enumerate() {
+            if i > 0 {
+                text.push_str(" -> ");
             }
+            write!(text, "{}", ebb).unwrap();
         }
-        Ok(())
+        text
     }
 
     fn typecheck_entry_block_params(&self) -> Result {
@@ -626,6 +944,139 @@ impl<'a> Verifier<'a> {
         self.typecheck_variable_args(inst)?;
         self.typecheck_return(inst)?;
         self.typecheck_special(inst, ctrl_type)?;
+        self.typecheck_lane_mask(inst, ctrl_type)?;
+
+        Ok(())
+    }
+
+    /// Get the memory flags, address and offset of a load or store instruction, along with the
+    /// number of bytes it accesses.
+    fn mem_access(&self, inst: Inst) -> Option<(ir::MemFlags, Value, i32, u32)> {
+        let (flags, addr, offset) = match self.func.dfg[inst] {
+            ir::InstructionData::Load { flags, arg, offset, .. } => (flags, arg, offset.into()),
+            ir::InstructionData::Store { flags, args, offset, .. } => {
+                (flags, args[1], offset.into())
+            }
+            ir::InstructionData::AtomicLoad { flags, arg, .. } => (flags, arg, 0),
+            ir::InstructionData::AtomicStore { flags, args, .. } => (flags, args[1], 0),
+            ir::InstructionData::AtomicRmw { flags, args, .. } => (flags, args[0], 0),
+            ir::InstructionData::AtomicCas { flags, args, .. } => (flags, args[0], 0),
+            _ => return None,
+        };
+        let bytes = match self.func.dfg[inst].opcode() {
+            Opcode::Uload8 | Opcode::Sload8 | Opcode::Istore8 => 1,
+            Opcode::Uload16 | Opcode::Sload16 | Opcode::Istore16 => 2,
+            Opcode::Uload32 | Opcode::Sload32 | Opcode::Istore32 => 4,
+            _ => self.func.dfg.ctrl_typevar(inst).bytes(),
+        };
+        Some((flags, addr, offset, bytes))
+    }
+
+    /// Check that the memory ordering of an atomic instruction is meaningful for the operation.
+    fn verify_ordering(&self, inst: Inst) -> Result {
+        use ir::AtomicOrdering::{Relaxed, SeqCst};
+
+        let (ordering, valid) = match self.func.dfg[inst] {
+            // A load can't release earlier stores.
+            ir::InstructionData::AtomicLoad { ordering, .. } => {
+                (ordering, ordering == SeqCst || !ordering.is_release())
+            }
+            // A store can't acquire the value of later loads.
+            ir::InstructionData::AtomicStore { ordering, .. } => {
+                (ordering, ordering == SeqCst || !ordering.is_acquire())
+            }
+            ir::InstructionData::Fence { ordering, .. } => (ordering, ordering != Relaxed),
+            _ => return Ok(()),
+        };
+
+        if !valid {
+            return err!(
+                inst,
+                "{} ordering is not valid for {}",
+                ordering,
+                self.func.dfg[inst].opcode()
+            );
+        }
+        Ok(())
+    }
+
+    /// Check that the memory flags on a load or store are consistent with what can be derived
+    /// about the address it accesses.
+    fn verify_memflags(&self, inst: Inst) -> Result {
+        let (flags, addr, offset, bytes) = match self.mem_access(inst) {
+            Some(access) => access,
+            None => return Ok(()),
+        };
+
+        let opcode = self.func.dfg[inst].opcode();
+        if flags.readonly() && opcode.can_store() {
+            return err!(inst, "readonly flag on {}, which writes memory", opcode);
+        }
+
+        let def_inst = match self.func.dfg.value_def(addr) {
+            ValueDef::Result(def_inst, _) => def_inst,
+            ValueDef::Param(..) => return Ok(()),
+        };
+
+        match self.func.dfg[def_inst] {
+            ir::InstructionData::StackLoad {
+                opcode: Opcode::StackAddr,
+                stack_slot,
+                offset: ss_offset,
+            } if flags.aligned() => {
+                let slot = &self.func.stack_slots[stack_slot];
+                let ss_offset: i64 = ss_offset.into();
+                let eff_offset = ss_offset + i64::from(offset);
+                if slot.alignment(bytes) < bytes || eff_offset % i64::from(bytes) != 0 {
+                    return err!(
+                        inst,
+                        "aligned {}-byte access at offset {} of {} can't be aligned",
+                        bytes,
+                        eff_offset,
+                        stack_slot
+                    );
+                }
+            }
+            ir::InstructionData::HeapAddr {
+                opcode: Opcode::HeapAddr,
+                heap,
+                imm,
+                ..
+            } if flags.notrap() => {
+                let checked: i64 = imm.into();
+                if offset < 0 || i64::from(offset) + i64::from(bytes) > checked {
+                    return err!(
+                        inst,
+                        "notrap {}-byte access at offset {} is outside the {} bytes checked for {}",
+                        bytes,
+                        offset,
+                        checked,
+                        heap
+                    );
+                }
+            }
+            ir::InstructionData::TableAddr {
+                opcode: Opcode::TableAddr,
+                table,
+                offset: elem_offset,
+                ..
+            } if flags.notrap() => {
+                let element_size: i64 = self.func.tables[table].element_size.into();
+                let elem_offset: i64 = elem_offset.into();
+                let eff_offset = elem_offset + i64::from(offset);
+                if eff_offset < 0 || eff_offset + i64::from(bytes) > element_size {
+                    return err!(
+                        inst,
+                        "notrap {}-byte access at offset {} is outside the {}-byte elements of {}",
+                        bytes,
+                        eff_offset,
+                        element_size,
+                        table
+                    );
+                }
+            }
+            _ => {}
+        }
 
         Ok(())
     }
@@ -696,25 +1147,26 @@ impl<'a> Verifier<'a> {
 
     fn typecheck_variable_args(&self, inst: Inst) -> Result {
         match self.func.dfg.analyze_branch(inst) {
+            // The variable arguments of a `try_call` belong to the callee.
+            BranchInfo::SingleDest(ebb, _) if self.func.dfg[inst].opcode() == Opcode::TryCall => {
+                let arg_count = self.func.dfg.num_ebb_params(ebb);
+                if arg_count != 0 {
+                    return err!(
+                        inst,
+                        "landing pad {} can't take arguments, but it has {}",
+                        ebb,
+                        arg_count
+                    );
+                }
+            }
             BranchInfo::SingleDest(ebb, _) => {
                 let iter = self.func.dfg.ebb_params(ebb).iter().map(|&v| {
                     self.func.dfg.value_type(v)
                 });
                 self.typecheck_variable_args_iterator(inst, iter)?;
             }
-            BranchInfo::Table(table) => {
-                for (_, ebb) in self.func.jump_tables[table].entries() {
-                    let arg_count = self.func.dfg.num_ebb_params(ebb);
-                    if arg_count != 0 {
-                        return err!(
-                            inst,
-                            "takes no arguments, but had target {} with {} arguments",
-                            ebb,
-                            arg_count
-                        );
-                    }
-                }
-            }
+            // The jump table destinations are checked by `verify_jump_tables`.
+            BranchInfo::Table(_) |
             BranchInfo::NotABranch => {}
         }
 
@@ -725,6 +1177,7 @@ impl<'a> Verifier<'a> {
                     a.value_type
                 });
                 self.typecheck_variable_args_iterator(inst, arg_types)?;
+                self.check_call_signature(inst, sig_ref)?;
                 self.check_outgoing_args(inst, sig_ref)?;
             }
             CallInfo::Indirect(sig_ref, _) => {
@@ -732,6 +1185,7 @@ impl<'a> Verifier<'a> {
                     a.value_type
                 });
                 self.typecheck_variable_args_iterator(inst, arg_types)?;
+                self.check_call_signature(inst, sig_ref)?;
                 self.check_outgoing_args(inst, sig_ref)?;
             }
             CallInfo::NotACall => {}
@@ -778,6 +1232,149 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
+    /// Check that the signature of a call is a valid callee signature.
+    ///
+    /// Special-purpose parameters that describe the frame of the function being compiled can't
+    /// appear in a callee signature.
+    fn check_call_signature(&self, inst: Inst, sig_ref: SigRef) -> Result {
+        let sig = &self.func.dfg.signatures[sig_ref];
+
+        for abi in sig.params.iter().chain(&sig.returns) {
+            match abi.purpose {
+                ArgumentPurpose::Link |
+                ArgumentPurpose::FramePointer |
+                ArgumentPurpose::CalleeSaved => {
+                    return err!(
+                        inst,
+                        "{} parameters are not allowed in call signature {}",
+                        abi.purpose,
+                        sig_ref
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check that the ABI information in a signature is self-consistent.
+    ///
+    /// A signature can have at most one of each of the unique special-purpose parameters, and no
+    /// two parameters or return values can be assigned to the same register. Once legalized, all
+    /// parameters must have a location within the argument array.
+    fn verify_signature<E: Into<AnyEntity> + Copy>(&self, loc: E, sig: &ir::Signature) -> Result {
+        for &purpose in &[
+            ArgumentPurpose::StructReturn,
+            ArgumentPurpose::Link,
+            ArgumentPurpose::FramePointer,
+            ArgumentPurpose::VMContext,
+            ArgumentPurpose::SignatureId,
+        ]
+        {
+            if sig.params.iter().filter(|p| p.purpose == purpose).count() > 1 {
+                return err!(loc, "duplicate {} parameter in signature {}", purpose, sig);
+            }
+        }
+
+        // Return values are not included in `argument_bytes`, and they may still be unassigned
+        // in a signature whose parameters have all been legalized.
+        for (kind, abis) in &[("parameter", &sig.params), ("return value", &sig.returns)] {
+            for (i, abi) in abis.iter().enumerate() {
+                if let ArgumentLoc::Reg(reg) = abi.location {
+                    let dup = abis[..i].iter().position(|a| match a.location {
+                        ArgumentLoc::Reg(r) => r == reg,
+                        _ => false,
+                    });
+                    if let Some(j) = dup {
+                        let reginfo = self.isa.map(|isa| isa.register_info());
+                        return err!(
+                            loc,
+                            "{}s {} and {} are both assigned to {}",
+                            kind,
+                            j,
+                            i,
+                            abi.location.display(reginfo.as_ref())
+                        );
+                    }
+                }
+            }
+        }
+
+        let argument_bytes = match sig.argument_bytes {
+            Some(bytes) => bytes,
+            None => return Ok(()),
+        };
+
+        for (i, abi) in sig.params.iter().enumerate() {
+            match abi.location {
+                ArgumentLoc::Unassigned => {
+                    return err!(loc, "legalized parameter {} has no ABI location", i);
+                }
+                // Incoming stack arguments of the current function can be at negative offsets,
+                // see `compute_argument_bytes`.
+                ArgumentLoc::Stack(offset)
+                    if offset >= 0 && offset as u32 + abi.value_type.bytes() > argument_bytes => {
+                    return err!(
+                        loc,
+                        "stack parameter {} at offset {} exceeds the {} argument bytes",
+                        i,
+                        offset,
+                        argument_bytes
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Verify the current function's signature and all the signatures it references.
+    fn verify_signatures(&self) -> Result {
+        self.verify_signature(AnyEntity::Function, &self.func.signature)?;
+        for sig_ref in self.func.dfg.signatures.keys() {
+            self.verify_signature(sig_ref, &self.func.dfg.signatures[sig_ref])?;
+        }
+        self.verify_callee_saved()
+    }
+
+    /// Check that the `CalleeSaved` parameters of the current function are registers that the
+    /// ISA's frame configuration actually requires to be preserved.
+    fn verify_callee_saved(&self) -> Result {
+        let isa = match self.isa {
+            Some(isa) => isa,
+            None => return Ok(()),
+        };
+        let csrs = isa.callee_saved_registers(self.func.signature.call_conv);
+        let external = isa.frame_config().external_prologue_bytes.is_some();
+
+        for (i, abi) in self.func.signature.params.iter().enumerate() {
+            if abi.purpose != ArgumentPurpose::CalleeSaved {
+                continue;
+            }
+            if external {
+                return err!(
+                    AnyEntity::Function,
+                    "csr parameter {} with an embedder-generated prologue",
+                    i
+                );
+            }
+            if let ArgumentLoc::Reg(reg) = abi.location {
+                if !csrs.contains(&reg) {
+                    return err!(
+                        AnyEntity::Function,
+                        "csr parameter {} is assigned to {}, which is not callee-saved",
+                        i,
+                        isa.register_info().display_regunit(reg)
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
     /// Check the locations assigned to outgoing call arguments.
     ///
     /// When a signature has been legalized, all values passed as outgoing arguments on the stack
@@ -916,51 +1513,27 @@ impl<'a> Verifier<'a> {
         Ok(())
     }
 
-    fn cfg_integrity(&self, cfg: &ControlFlowGraph) -> Result {
-        let mut expected_succs = BTreeSet::<Ebb>::new();
-        let mut got_succs = BTreeSet::<Ebb>::new();
-        let mut expected_preds = BTreeSet::<Inst>::new();
-        let mut got_preds = BTreeSet::<Inst>::new();
-
-        for ebb in self.func.layout.ebbs() {
-            expected_succs.extend(self.expected_cfg.succ_iter(ebb));
-            got_succs.extend(cfg.succ_iter(ebb));
-
-            let missing_succs: Vec<Ebb> = expected_succs.difference(&got_succs).cloned().collect();
-            if !missing_succs.is_empty() {
-                return err!(
-                    ebb,
-                    "cfg lacked the following successor(s) {:?}",
-                    missing_succs
-                );
-            }
-
-            let excess_succs: Vec<Ebb> = got_succs.difference(&expected_succs).cloned().collect();
-            if !excess_succs.is_empty() {
-                return err!(ebb, "cfg had unexpected successor(s) {:?}", excess_succs);
-            }
-
-            expected_preds.extend(self.expected_cfg.pred_iter(ebb).map(|(_, inst)| inst));
-            got_preds.extend(cfg.pred_iter(ebb).map(|(_, inst)| inst));
-
-            let missing_preds: Vec<Inst> = expected_preds.difference(&got_preds).cloned().collect();
-            if !missing_preds.is_empty() {
-                return err!(
-                    ebb,
-                    "cfg lacked the following predecessor(s) {:?}",
-                    missing_preds
-                );
-            }
-
-            let excess_preds: Vec<Inst> = got_preds.difference(&expected_preds).cloned().collect();
-            if !excess_preds.is_empty() {
-                return err!(ebb, "cfg had unexpected predecessor(s) {:?}", excess_preds);
-            }
-
-            expected_succs.clear();
-            got_succs.clear();
-            expected_preds.clear();
-            got_preds.clear();
+    /// Check that the lane mask of a shuffle instruction selects a valid lane for every lane in
+    /// the result.
+    fn typecheck_lane_mask(&self, inst: Inst, ctrl_type: Type) -> Result {
+        let (mask, inputs) = match self.func.dfg[inst] {
+            ir::InstructionData::Shuffle { mask, .. } => (mask, 2),
+            ir::InstructionData::Swizzle { mask, .. } => (mask, 1),
+            _ => return Ok(()),
+        };
+        let lanes = ctrl_type.lane_count();
+        let data = &self.func.dfg.lane_masks[mask];
+        if data.len() != usize::from(lanes) {
+            return err!(
+                inst,
+                "lane mask {} must have {} lanes for {}",
+                data,
+                lanes,
+                ctrl_type
+            );
+        }
+        if let Some(&lane) = data.iter().find(|&&lane| u16::from(lane) >= inputs * lanes) {
+            return err!(inst, "lane index {} out of range for {}", lane, ctrl_type);
         }
         Ok(())
     }
@@ -984,7 +1557,7 @@ impl<'a> Verifier<'a> {
         let encoding = self.func.encodings[inst];
         if encoding.is_legal() {
             let mut encodings = isa.legal_encodings(
-                &self.func.dfg,
+                self.func,
                 &self.func.dfg[inst],
                 self.func.dfg.ctrl_typevar(inst),
             ).peekable();
@@ -992,8 +1565,13 @@ impl<'a> Verifier<'a> {
             if encodings.peek().is_none() {
                 return err!(
                     inst,
-                    "Instruction failed to re-encode {}",
-                    isa.encoding_info().display(encoding)
+                    "Instruction failed to re-encode {}: {}",
+                    isa.encoding_info().display(encoding),
+                    isa.explain_encoding(
+                        self.func,
+                        &self.func.dfg[inst],
+                        self.func.dfg.ctrl_typevar(inst),
+                    )
                 );
             }
 
@@ -1004,7 +1582,7 @@ impl<'a> Verifier<'a> {
                 let mut multiple_encodings = false;
 
                 for enc in isa.legal_encodings(
-                    &self.func.dfg,
+                    self.func,
                     &self.func.dfg[inst],
                     self.func.dfg.ctrl_typevar(inst),
                 )
@@ -1039,6 +1617,12 @@ impl<'a> Verifier<'a> {
             return Ok(());
         }
 
+        // The `debug_value` and `deopt_point` instructions have side effects to keep optimizations
+        // from removing them, but they never generate any code.
+        if opcode == Opcode::DebugValue || opcode == Opcode::DeoptPoint {
+            return Ok(());
+        }
+
         // Check if this opcode must be encoded.
         let mut needs_enc = None;
         if opcode.is_branch() {
@@ -1059,7 +1643,7 @@ impl<'a> Verifier<'a> {
             // This instruction needs an encoding, so generate an error.
             // Provide the ISA default encoding as a hint.
             match isa.encode(
-                &self.func.dfg,
+                self.func,
                 &self.func.dfg[inst],
                 self.func.dfg.ctrl_typevar(inst),
             ) {
@@ -1071,7 +1655,18 @@ impl<'a> Verifier<'a> {
                         isa.encoding_info().display(enc)
                     )
                 }
-                Err(_) => return err!(inst, "{} must have an encoding", text),
+                Err(_) => {
+                    return err!(
+                        inst,
+                        "{} must have an encoding: {}",
+                        text,
+                        isa.explain_encoding(
+                            self.func,
+                            &self.func.dfg[inst],
+                            self.func.dfg.ctrl_typevar(inst),
+                        )
+                    )
+                }
             }
         }
 
@@ -1094,33 +1689,125 @@ impl<'a> Verifier<'a> {
     }
 
     pub fn run(&self) -> Result {
-        self.verify_global_vars()?;
-        self.typecheck_entry_block_params()?;
+        self.verify_preamble()?;
         for ebb in self.func.layout.ebbs() {
             for inst in self.func.layout.ebb_insts(ebb) {
-                self.ebb_integrity(ebb, inst)?;
-                self.instruction_integrity(inst)?;
-                self.typecheck(inst)?;
-                self.verify_encoding(inst)?;
+                self.verify_inst(ebb, inst)?;
             }
         }
 
         if self.flags.return_at_end() {
-            self.verify_return_at_end()?;
+            self.verify_return_at_end().map_err(
+                |e| e.classify(ErrorCode::ReturnAtEnd),
+            )?;
         }
 
         verify_flags(self.func, &self.expected_cfg, self.isa)?;
 
         Ok(())
     }
+
+    /// Verify the function-wide declarations that instructions refer to.
+    fn verify_preamble(&self) -> Result {
+        self.verify_global_vars().map_err(
+            |e| e.classify(ErrorCode::GlobalVar),
+        )?;
+        self.verify_tables().map_err(
+            |e| e.classify(ErrorCode::GlobalVar),
+        )?;
+        self.verify_jump_tables().map_err(|e| {
+            e.classify(ErrorCode::InstIntegrity)
+        })?;
+        self.verify_signatures().map_err(
+            |e| e.classify(ErrorCode::Abi),
+        )?;
+        self.typecheck_entry_block_params().map_err(|e| {
+            e.classify(ErrorCode::Typecheck)
+        })
+    }
+
+    /// Run all the checks on a single instruction, stopping at the first error since later checks
+    /// assume that the earlier ones passed.
+    fn verify_inst(&self, ebb: Ebb, inst: Inst) -> Result {
+        self.ebb_integrity(ebb, inst).map_err(|e| {
+            e.classify(ErrorCode::EbbIntegrity)
+        })?;
+        self.instruction_integrity(inst).map_err(|e| {
+            e.classify(ErrorCode::InstIntegrity)
+        })?;
+        self.typecheck(inst).map_err(
+            |e| e.classify(ErrorCode::Typecheck),
+        )?;
+        self.verify_memflags(inst).map_err(
+            |e| e.classify(ErrorCode::MemFlags),
+        )?;
+        self.verify_ordering(inst).map_err(
+            |e| e.classify(ErrorCode::Ordering),
+        )?;
+        self.verify_encoding(inst).map_err(
+            |e| e.classify(ErrorCode::Encoding),
+        )
+    }
+
+    /// Run all the checks in `run`, but collect all the issues in `issues` instead of stopping
+    /// at the first one. Also report warnings.
+    pub fn collect(&self, issues: &mut Vec<VerifierIssue>) {
+        report(issues, self.verify_preamble());
+        for ebb in self.func.layout.ebbs() {
+            for inst in self.func.layout.ebb_insts(ebb) {
+                report(issues, self.verify_inst(ebb, inst));
+            }
+        }
+
+        if self.flags.return_at_end() {
+            report(
+                issues,
+                self.verify_return_at_end().map_err(|e| {
+                    e.classify(ErrorCode::ReturnAtEnd)
+                }),
+            );
+        }
+
+        // The flags verifier assumes that all instructions are well formed.
+        if issues.iter().all(|i| i.severity != Severity::Error) {
+            report(issues, verify_flags(self.func, &self.expected_cfg, self.isa));
+        }
+
+        self.warn_unreachable_ebbs(issues);
+    }
+
+    /// Warn about EBBs that can't be reached from the entry block.
+    fn warn_unreachable_ebbs(&self, issues: &mut Vec<VerifierIssue>) {
+        for ebb in self.func.layout.ebbs() {
+            if !self.expected_domtree.is_reachable(ebb) {
+                issues.push(VerifierIssue {
+                    severity: Severity::Warning,
+                    error: Error::new(ebb, String::from("unreachable from the entry block"))
+                        .classify(ErrorCode::Unreachable),
+                });
+            }
+        }
+    }
+}
+
+// Record a failed check as an error in `issues`.
+fn report(issues: &mut Vec<VerifierIssue>, result: Result) {
+    if let Err(error) = result {
+        issues.push(VerifierIssue {
+            severity: Severity::Error,
+            error,
+        });
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Verifier, Error};
-    use ir::Function;
+    use super::{Verifier, Error, ErrorCode, Severity, verify_function, verify_function_collect};
+    use cursor::{Cursor, FuncCursor};
+    use ir::{Function, InstBuilder, types};
     use ir::instructions::{InstructionData, Opcode};
     use entity::EntityList;
+    use print_errors::pretty_verifier_error;
     use settings;
 
     macro_rules! assert_err_with_msg {
@@ -1166,4 +1853,69 @@ mod tests {
         let verifier = Verifier::new(&func, flags.into());
         assert_err_with_msg!(verifier.run(), "instruction format");
     }
+
+    #[test]
+    fn collect_all_issues() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        func.layout.append_ebb(ebb0);
+        func.layout.append_ebb(ebb1);
+        for &ebb in &[ebb0, ebb1] {
+            let bad = func.dfg.make_inst(InstructionData::UnaryImm {
+                opcode: Opcode::F32const,
+                imm: 0.into(),
+            });
+            func.layout.append_inst(bad, ebb);
+            let ret = func.dfg.make_inst(InstructionData::MultiAry {
+                opcode: Opcode::Return,
+                args: EntityList::default(),
+            });
+            func.layout.append_inst(ret, ebb);
+        }
+        let flags = &settings::Flags::new(&settings::builder());
+        let issues = verify_function_collect(&func, flags);
+        let severities: Vec<_> = issues.iter().map(|i| i.severity).collect();
+        assert_eq!(
+            severities,
+            [Severity::Error, Severity::Error, Severity::Warning]
+        );
+        assert!(issues[0].error.message.contains("instruction format"));
+        assert_eq!(issues[2].error.location, ebb1.into());
+    }
+
+    #[test]
+    fn error_context() {
+        let mut func = Function::new();
+        let ebb0 = func.dfg.make_ebb();
+        let ebb1 = func.dfg.make_ebb();
+        let (def, bad_use) = {
+            let mut cur = FuncCursor::new(&mut func);
+            cur.insert_ebb(ebb0);
+            let cond = cur.ins().iconst(types::I32, 0);
+            cur.ins().brnz(cond, ebb1, &[]);
+            let v1 = cur.ins().iconst(types::I32, 1);
+            cur.ins().return_(&[]);
+            cur.insert_ebb(ebb1);
+            // `v1` is defined after the branch to `ebb1`, so it doesn't dominate this use.
+            let v2 = cur.ins().bnot(v1);
+            cur.ins().return_(&[]);
+            let dfg = &cur.func.dfg;
+            (dfg.value_def(v1).unwrap_inst(), dfg.value_def(v2).unwrap_inst())
+        };
+
+        let flags = &settings::Flags::new(&settings::builder());
+        let err = verify_function(&func, flags).unwrap_err();
+        assert_eq!(err.code, ErrorCode::Ssa);
+        assert_eq!(err.location, bad_use.into());
+        assert_eq!(err.related, [def.into()]);
+
+        let text = pretty_verifier_error(&func, None, &err);
+        assert!(text.starts_with(
+            "inst4: uses v1 from non-dominating inst2 (path from entry: ebb0 -> ebb1) [ssa]\n",
+        ));
+        assert!(text.contains(">       v2 = bnot.i32 v1\n"));
+        assert!(text.contains("note: related to inst2\n"));
+        assert!(text.contains(">       v1 = iconst.i32 1\n"));
+    }
 }
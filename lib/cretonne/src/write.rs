@@ -3,33 +3,173 @@
 //! The `write` module provides the `write_function` function which converts an IL `Function` to an
 //! equivalent textual representation. This textual representation can be read back by the
 //! `cretonne-reader` crate.
+//!
+//! The `write_function_with` variant takes `WriteOptions` that can renumber the EBBs and values
+//! of the function in the text, which makes it easier to compare two compilations of the same
+//! function.
 
-use ir::{Function, DataFlowGraph, Ebb, Inst, Value, ValueDef, Type, SigRef};
+use entity::EntityMap;
+use ir::{Function, DataFlowGraph, Ebb, Inst, Value, ValueDef, Type, SigRef, JumpTableData};
 use isa::{TargetIsa, RegInfo};
 use std::fmt::{self, Result, Error, Write};
 use std::result;
-use packed_option::ReservedValue;
+use packed_option::{PackedOption, ReservedValue};
 use std::string::String;
 
+/// Options controlling how a function is written as text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WriteOptions {
+    /// Renumber EBBs and values densely in layout order.
+    ///
+    /// The function itself is not changed, only the numbers appearing in the text. Functions that
+    /// differ only in the numbering of their EBBs and values are written identically.
+    pub canonical_numbering: bool,
+}
+
 /// Write `func` to `w` as equivalent text.
 /// Use `isa` to emit ISA-dependent annotations.
 pub fn write_function(w: &mut Write, func: &Function, isa: Option<&TargetIsa>) -> Result {
+    write_function_with(w, func, isa, WriteOptions::default())
+}
+
+/// Write `func` to `w` as equivalent text, using `options`.
+/// Use `isa` to emit ISA-dependent annotations.
+pub fn write_function_with(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    options: WriteOptions,
+) -> Result {
     let regs = isa.map(TargetIsa::register_info);
     let regs = regs.as_ref();
+    let names = if options.canonical_numbering {
+        Numbering::canonical(func)
+    } else {
+        Numbering::identity()
+    };
 
     write_spec(w, func, regs)?;
     writeln!(w, " {{")?;
-    let mut any = write_preamble(w, func, regs)?;
+    let mut any = write_preamble(w, func, regs, &names)?;
     for ebb in &func.layout {
         if any {
             writeln!(w, "")?;
         }
-        write_ebb(w, func, isa, ebb)?;
+        ebb_with_names(w, func, isa, ebb, &names)?;
         any = true;
     }
     writeln!(w, "}}")
 }
 
+// ====--------------------------------------------------------------------------------------====//
+//
+// Numbering
+//
+// ====--------------------------------------------------------------------------------------====//
+
+/// The numbers used for the EBBs and values of a function in the text.
+struct Numbering {
+    ebbs: EntityMap<Ebb, PackedOption<Ebb>>,
+    values: EntityMap<Value, PackedOption<Value>>,
+}
+
+impl Numbering {
+    /// Use the entity numbers of the function.
+    fn identity() -> Self {
+        Self {
+            ebbs: EntityMap::new(),
+            values: EntityMap::new(),
+        }
+    }
+
+    /// Number EBBs and values in the order they appear in the text.
+    fn canonical(func: &Function) -> Self {
+        let mut names = Self::identity();
+        let mut next_ebb = 0;
+        let mut next_value = 0;
+        {
+            let mut name_value = |names: &mut Self, v: Value| if names.values[v].is_none() {
+                names.values[v] = Value::with_number(next_value).into();
+                next_value += 1;
+            };
+
+            // Definitions come first, along with the aliases that are written before the
+            // instruction using them.
+            for ebb in &func.layout {
+                names.ebbs[ebb] = Ebb::with_number(next_ebb).into();
+                next_ebb += 1;
+                for &arg in func.dfg.ebb_params(ebb) {
+                    name_value(&mut names, arg);
+                }
+                for inst in func.layout.ebb_insts(ebb) {
+                    for &arg in func.dfg.inst_args(inst) {
+                        if func.dfg.resolve_aliases(arg) != arg {
+                            name_value(&mut names, arg);
+                        }
+                    }
+                    for &res in func.dfg.inst_results(inst) {
+                        name_value(&mut names, res);
+                    }
+                }
+            }
+
+            // Then the remaining values that are used without being defined in the layout.
+            for ebb in &func.layout {
+                for inst in func.layout.ebb_insts(ebb) {
+                    for &arg in func.dfg.inst_args(inst) {
+                        name_value(&mut names, arg);
+                        name_value(&mut names, func.dfg.resolve_aliases(arg));
+                    }
+                }
+            }
+        }
+
+        // EBBs that are only referenced by branches and jump tables.
+        let mut name_ebb = |names: &mut Self, ebb: Ebb| if names.ebbs[ebb].is_none() {
+            names.ebbs[ebb] = Ebb::with_number(next_ebb).into();
+            next_ebb += 1;
+        };
+        for ebb in &func.layout {
+            for inst in func.layout.ebb_insts(ebb) {
+                if let Some(dest) = func.dfg[inst].branch_destination() {
+                    name_ebb(&mut names, dest);
+                }
+            }
+        }
+        for jt in func.jump_tables.keys() {
+            for (_, dest) in func.jump_tables[jt].entries() {
+                name_ebb(&mut names, dest);
+            }
+            if let Some(dest) = func.jump_tables[jt].default_dest() {
+                name_ebb(&mut names, dest);
+            }
+        }
+
+        names
+    }
+
+    /// Get the EBB to write in place of `ebb`.
+    fn ebb(&self, ebb: Ebb) -> Ebb {
+        self.ebbs.get(ebb).and_then(|e| e.expand()).unwrap_or(ebb)
+    }
+
+    /// Get the value to write in place of `v`.
+    fn value(&self, v: Value) -> Value {
+        self.values.get(v).and_then(|v| v.expand()).unwrap_or(v)
+    }
+
+    /// Get a jump table with renumbered destinations.
+    fn jump_table(&self, jt: &JumpTableData) -> JumpTableData {
+        let mut jt = jt.clone();
+        for entry in jt.as_mut_slice() {
+            *entry = entry.map(|ebb| self.ebb(ebb)).into();
+        }
+        let default = jt.default_dest().map(|ebb| self.ebb(ebb));
+        jt.set_default_dest(default);
+        jt
+    }
+}
+
 // ====--------------------------------------------------------------------------------------====//
 //
 // Function spec.
@@ -44,6 +184,7 @@ fn write_preamble(
     w: &mut Write,
     func: &Function,
     regs: Option<&RegInfo>,
+    names: &Numbering,
 ) -> result::Result<bool, Error> {
     let mut any = false;
 
@@ -62,6 +203,11 @@ fn write_preamble(
         writeln!(w, "    {} = {}", heap, func.heaps[heap])?;
     }
 
+    for table in func.tables.keys() {
+        any = true;
+        writeln!(w, "    {} = {}", table, func.tables[table])?;
+    }
+
     // Write out all signatures before functions since function declarations can refer to
     // signatures.
     for sig in func.dfg.signatures.keys() {
@@ -84,7 +230,7 @@ fn write_preamble(
 
     for jt in func.jump_tables.keys() {
         any = true;
-        writeln!(w, "    {} = {}", jt, func.jump_tables[jt])?;
+        writeln!(w, "    {} = {}", jt, names.jump_table(&func.jump_tables[jt]))?;
     }
 
     Ok(any)
@@ -96,8 +242,14 @@ fn write_preamble(
 //
 // ====--------------------------------------------------------------------------------------====//
 
-pub fn write_arg(w: &mut Write, func: &Function, regs: Option<&RegInfo>, arg: Value) -> Result {
-    write!(w, "{}: {}", arg, func.dfg.value_type(arg))?;
+fn arg_with_names(
+    w: &mut Write,
+    func: &Function,
+    regs: Option<&RegInfo>,
+    arg: Value,
+    names: &Numbering,
+) -> Result {
+    write!(w, "{}: {}", names.value(arg), func.dfg.value_type(arg))?;
     let loc = func.locations[arg];
     if loc.is_assigned() {
         write!(w, " [{}]", loc.display(regs))?
@@ -112,6 +264,17 @@ pub fn write_ebb_header(
     isa: Option<&TargetIsa>,
     ebb: Ebb,
     indent: usize,
+) -> Result {
+    ebb_header_with_names(w, func, isa, ebb, indent, &Numbering::identity())
+}
+
+fn ebb_header_with_names(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    ebb: Ebb,
+    indent: usize,
+    names: &Numbering,
 ) -> Result {
     // Write out the basic block header, outdented:
     //
@@ -121,7 +284,7 @@ pub fn write_ebb_header(
     //
 
     // The `indent` is the instruction indentation. EBB headers are 4 spaces out from that.
-    write!(w, "{1:0$}{2}", indent - 4, "", ebb)?;
+    write!(w, "{1:0$}{2}", indent - 4, "", names.ebb(ebb))?;
 
     let regs = isa.map(TargetIsa::register_info);
     let regs = regs.as_ref();
@@ -131,18 +294,24 @@ pub fn write_ebb_header(
         None => return writeln!(w, ":"),
         Some(arg) => {
             write!(w, "(")?;
-            write_arg(w, func, regs, arg)?;
+            arg_with_names(w, func, regs, arg, names)?;
         }
     }
     // Remaining arguments.
     for arg in args {
         write!(w, ", ")?;
-        write_arg(w, func, regs, arg)?;
+        arg_with_names(w, func, regs, arg, names)?;
     }
     writeln!(w, "):")
 }
 
-pub fn write_ebb(w: &mut Write, func: &Function, isa: Option<&TargetIsa>, ebb: Ebb) -> Result {
+fn ebb_with_names(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    ebb: Ebb,
+    names: &Numbering,
+) -> Result {
     // Indent all instructions if any encodings are present.
     let indent = if func.encodings.is_empty() && func.srclocs.is_empty() {
         4
@@ -150,9 +319,9 @@ pub fn write_ebb(w: &mut Write, func: &Function, isa: Option<&TargetIsa>, ebb: E
         36
     };
 
-    write_ebb_header(w, func, isa, ebb, indent)?;
+    ebb_header_with_names(w, func, isa, ebb, indent, names)?;
     for inst in func.layout.ebb_insts(ebb) {
-        write_instruction(w, func, isa, inst, indent)?;
+        instruction_with_names(w, func, isa, inst, indent, names)?;
     }
     Ok(())
 }
@@ -199,25 +368,50 @@ fn type_suffix(func: &Function, inst: Inst) -> Option<Type> {
 }
 
 // Write out any value aliases appearing in `inst`.
-fn write_value_aliases(w: &mut Write, func: &Function, inst: Inst, indent: usize) -> Result {
+fn write_value_aliases(
+    w: &mut Write,
+    func: &Function,
+    inst: Inst,
+    indent: usize,
+    names: &Numbering,
+) -> Result {
     for &arg in func.dfg.inst_args(inst) {
         let resolved = func.dfg.resolve_aliases(arg);
         if resolved != arg {
-            writeln!(w, "{1:0$}{2} -> {3}", indent, "", arg, resolved)?;
+            writeln!(
+                w,
+                "{1:0$}{2} -> {3}",
+                indent,
+                "",
+                names.value(arg),
+                names.value(resolved)
+            )?;
         }
     }
     Ok(())
 }
 
-fn write_instruction(
+/// Write `inst` and any value aliases it uses, prefixed with its encoding and value locations.
+pub fn write_instruction(
+    w: &mut Write,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    inst: Inst,
+    indent: usize,
+) -> Result {
+    instruction_with_names(w, func, isa, inst, indent, &Numbering::identity())
+}
+
+fn instruction_with_names(
     w: &mut Write,
     func: &Function,
     isa: Option<&TargetIsa>,
     inst: Inst,
     indent: usize,
+    names: &Numbering,
 ) -> Result {
     // Value aliases come out on lines before the instruction using them.
-    write_value_aliases(w, func, inst, indent)?;
+    write_value_aliases(w, func, inst, indent, names)?;
 
     // Prefix containing source location, encoding, and value locations.
     let mut s = String::with_capacity(16);
@@ -253,9 +447,9 @@ fn write_instruction(
     for r in func.dfg.inst_results(inst) {
         if !has_results {
             has_results = true;
-            write!(w, "{}", r)?;
+            write!(w, "{}", names.value(*r))?;
         } else {
-            write!(w, ", {}", r)?;
+            write!(w, ", {}", names.value(*r))?;
         }
     }
     if has_results {
@@ -270,7 +464,7 @@ fn write_instruction(
         None => write!(w, "{}", opcode)?,
     }
 
-    write_operands(w, &func.dfg, isa, inst)?;
+    operands_with_names(w, &func.dfg, isa, inst, names)?;
     writeln!(w, "")
 }
 
@@ -280,44 +474,66 @@ pub fn write_operands(
     dfg: &DataFlowGraph,
     isa: Option<&TargetIsa>,
     inst: Inst,
+) -> Result {
+    operands_with_names(w, dfg, isa, inst, &Numbering::identity())
+}
+
+fn operands_with_names(
+    w: &mut Write,
+    dfg: &DataFlowGraph,
+    isa: Option<&TargetIsa>,
+    inst: Inst,
+    n: &Numbering,
 ) -> Result {
     let pool = &dfg.value_lists;
     use ir::instructions::InstructionData::*;
     match dfg[inst] {
-        Unary { arg, .. } => write!(w, " {}", arg),
+        Unary { arg, .. } => write!(w, " {}", n.value(arg)),
         UnaryImm { imm, .. } => write!(w, " {}", imm),
         UnaryIeee32 { imm, .. } => write!(w, " {}", imm),
         UnaryIeee64 { imm, .. } => write!(w, " {}", imm),
         UnaryBool { imm, .. } => write!(w, " {}", imm),
         UnaryGlobalVar { global_var, .. } => write!(w, " {}", global_var),
-        Binary { args, .. } => write!(w, " {}, {}", args[0], args[1]),
-        BinaryImm { arg, imm, .. } => write!(w, " {}, {}", arg, imm),
-        Ternary { args, .. } => write!(w, " {}, {}, {}", args[0], args[1], args[2]),
+        Binary { args, .. } => write!(w, " {}, {}", n.value(args[0]), n.value(args[1])),
+        BinaryImm { arg, imm, .. } => write!(w, " {}, {}", n.value(arg), imm),
+        Ternary { args, .. } => {
+            write!(w, " {}, {}, {}", n.value(args[0]), n.value(args[1]), n.value(args[2]))
+        }
         MultiAry { ref args, .. } => {
             if args.is_empty() {
                 write!(w, "")
             } else {
-                write!(w, " {}", DisplayValues(args.as_slice(pool)))
+                write!(w, " {}", DisplayValues(args.as_slice(pool), n))
             }
         }
         NullAry { .. } => write!(w, " "),
-        InsertLane { lane, args, .. } => write!(w, " {}, {}, {}", args[0], lane, args[1]),
-        ExtractLane { lane, arg, .. } => write!(w, " {}, {}", arg, lane),
-        IntCompare { cond, args, .. } => write!(w, " {} {}, {}", cond, args[0], args[1]),
-        IntCompareImm { cond, arg, imm, .. } => write!(w, " {} {}, {}", cond, arg, imm),
-        IntCond { cond, arg, .. } => write!(w, " {} {}", cond, arg),
-        FloatCompare { cond, args, .. } => write!(w, " {} {}, {}", cond, args[0], args[1]),
-        FloatCond { cond, arg, .. } => write!(w, " {} {}", cond, arg),
+        InsertLane { lane, args, .. } => {
+            write!(w, " {}, {}, {}", n.value(args[0]), lane, n.value(args[1]))
+        }
+        ExtractLane { lane, arg, .. } => write!(w, " {}, {}", n.value(arg), lane),
+        Shuffle { mask, args, .. } => {
+            write!(w, " {}, {}, {}", n.value(args[0]), n.value(args[1]), dfg.lane_masks[mask])
+        }
+        Swizzle { mask, arg, .. } => write!(w, " {}, {}", n.value(arg), dfg.lane_masks[mask]),
+        IntCompare { cond, args, .. } => {
+            write!(w, " {} {}, {}", cond, n.value(args[0]), n.value(args[1]))
+        }
+        IntCompareImm { cond, arg, imm, .. } => write!(w, " {} {}, {}", cond, n.value(arg), imm),
+        IntCond { cond, arg, .. } => write!(w, " {} {}", cond, n.value(arg)),
+        FloatCompare { cond, args, .. } => {
+            write!(w, " {} {}, {}", cond, n.value(args[0]), n.value(args[1]))
+        }
+        FloatCond { cond, arg, .. } => write!(w, " {} {}", cond, n.value(arg)),
         IntSelect { cond, args, .. } => {
-            write!(w, " {} {}, {}, {}", cond, args[0], args[1], args[2])
+            write!(w, " {} {}, {}, {}", cond, n.value(args[0]), n.value(args[1]), n.value(args[2]))
         }
         Jump {
             destination,
             ref args,
             ..
         } => {
-            write!(w, " {}", destination)?;
-            write_ebb_args(w, args.as_slice(pool))
+            write!(w, " {}", n.ebb(destination))?;
+            write_ebb_args(w, args.as_slice(pool), n)
         }
         Branch {
             destination,
@@ -325,8 +541,8 @@ pub fn write_operands(
             ..
         } => {
             let args = args.as_slice(pool);
-            write!(w, " {}, {}", args[0], destination)?;
-            write_ebb_args(w, &args[1..])
+            write!(w, " {}, {}", n.value(args[0]), n.ebb(destination))?;
+            write_ebb_args(w, &args[1..], n)
         }
         BranchInt {
             cond,
@@ -335,8 +551,8 @@ pub fn write_operands(
             ..
         } => {
             let args = args.as_slice(pool);
-            write!(w, " {} {}, {}", cond, args[0], destination)?;
-            write_ebb_args(w, &args[1..])
+            write!(w, " {} {}, {}", cond, n.value(args[0]), n.ebb(destination))?;
+            write_ebb_args(w, &args[1..], n)
         }
         BranchFloat {
             cond,
@@ -345,8 +561,8 @@ pub fn write_operands(
             ..
         } => {
             let args = args.as_slice(pool);
-            write!(w, " {} {}, {}", cond, args[0], destination)?;
-            write_ebb_args(w, &args[1..])
+            write!(w, " {} {}, {}", cond, n.value(args[0]), n.ebb(destination))?;
+            write_ebb_args(w, &args[1..], n)
         }
         BranchIcmp {
             cond,
@@ -355,12 +571,33 @@ pub fn write_operands(
             ..
         } => {
             let args = args.as_slice(pool);
-            write!(w, " {} {}, {}, {}", cond, args[0], args[1], destination)?;
-            write_ebb_args(w, &args[2..])
+            write!(
+                w,
+                " {} {}, {}, {}",
+                cond,
+                n.value(args[0]),
+                n.value(args[1]),
+                n.ebb(destination)
+            )?;
+            write_ebb_args(w, &args[2..], n)
         }
-        BranchTable { arg, table, .. } => write!(w, " {}, {}", arg, table),
+        BranchTable { arg, table, .. } => write!(w, " {}, {}", n.value(arg), table),
         Call { func_ref, ref args, .. } => {
-            write!(w, " {}({})", func_ref, DisplayValues(args.as_slice(pool)))
+            write!(w, " {}({})", func_ref, DisplayValues(args.as_slice(pool), n))
+        }
+        TryCall {
+            func_ref,
+            destination,
+            ref args,
+            ..
+        } => {
+            write!(
+                w,
+                " {}({}), {}",
+                func_ref,
+                DisplayValues(args.as_slice(pool), n),
+                n.ebb(destination)
+            )
         }
         IndirectCall { sig_ref, ref args, .. } => {
             let args = args.as_slice(pool);
@@ -368,8 +605,8 @@ pub fn write_operands(
                 w,
                 " {}, {}({})",
                 sig_ref,
-                args[0],
-                DisplayValues(&args[1..])
+                n.value(args[0]),
+                DisplayValues(&args[1..], n)
             )
         }
         FuncAddr { func_ref, .. } => write!(w, " {}", func_ref),
@@ -379,27 +616,97 @@ pub fn write_operands(
             stack_slot,
             offset,
             ..
-        } => write!(w, " {}, {}{}", arg, stack_slot, offset),
-        HeapAddr { heap, arg, imm, .. } => write!(w, " {}, {}, {}", heap, arg, imm),
-        Load { flags, arg, offset, .. } => write!(w, "{} {}{}", flags, arg, offset),
+        } => write!(w, " {}, {}{}", n.value(arg), stack_slot, offset),
+        HeapAddr { heap, arg, imm, .. } => write!(w, " {}, {}, {}", heap, n.value(arg), imm),
+        TableAddr { table, arg, offset, .. } => write!(w, " {}, {}{}", table, n.value(arg), offset),
+        DebugValue { arg, var, .. } => write!(w, " {}, {}", n.value(arg), var),
+        DeoptPoint { id, ref args, .. } => {
+            write!(w, " {}({})", id, DisplayValues(args.as_slice(pool), n))
+        }
+        Load { flags, arg, offset, .. } => write!(w, "{} {}{}", flags, n.value(arg), offset),
         Store {
             flags,
             args,
             offset,
             ..
-        } => write!(w, "{} {}, {}{}", flags, args[0], args[1], offset),
+        } => write!(w, "{} {}, {}{}", flags, n.value(args[0]), n.value(args[1]), offset),
+        LoadComplex {
+            flags,
+            args,
+            scale,
+            offset,
+            ..
+        } => write!(w, "{} {}{}, {}, {}", flags, n.value(args[0]), offset, n.value(args[1]), scale),
+        StoreComplex {
+            flags,
+            ref args,
+            scale,
+            offset,
+            ..
+        } => {
+            let args = args.as_slice(pool);
+            write!(
+                w,
+                "{} {}, {}{}, {}, {}",
+                flags,
+                n.value(args[0]),
+                n.value(args[1]),
+                offset,
+                n.value(args[2]),
+                scale
+            )
+        }
+        AtomicLoad {
+            ordering,
+            flags,
+            arg,
+            ..
+        } => write!(w, " {}{} {}", ordering, flags, n.value(arg)),
+        AtomicStore {
+            ordering,
+            flags,
+            args,
+            ..
+        } => write!(w, " {}{} {}, {}", ordering, flags, n.value(args[0]), n.value(args[1])),
+        AtomicRmw {
+            op,
+            ordering,
+            flags,
+            args,
+            ..
+        } => write!(w, " {} {}{} {}, {}", op, ordering, flags, n.value(args[0]), n.value(args[1])),
+        AtomicCas {
+            ordering,
+            flags,
+            args,
+            ..
+        } => {
+            write!(
+                w,
+                " {}{} {}, {}, {}",
+                ordering,
+                flags,
+                n.value(args[0]),
+                n.value(args[1]),
+                n.value(args[2])
+            )
+        }
+        Fence { ordering, .. } => write!(w, " {}", ordering),
+        BulkMemory { flags, args, .. } => {
+            write!(w, "{} {}, {}, {}", flags, n.value(args[0]), n.value(args[1]), n.value(args[2]))
+        }
         RegMove { arg, src, dst, .. } => {
             if let Some(isa) = isa {
                 let regs = isa.register_info();
                 write!(
                     w,
                     " {}, {} -> {}",
-                    arg,
+                    n.value(arg),
                     regs.display_regunit(src),
                     regs.display_regunit(dst)
                 )
             } else {
-                write!(w, " {}, %{} -> %{}", arg, src, dst)
+                write!(w, " {}, %{} -> %{}", n.value(arg), src, dst)
             }
         }
         CopySpecial { src, dst, .. } => {
@@ -418,45 +725,45 @@ pub fn write_operands(
         RegSpill { arg, src, dst, .. } => {
             if let Some(isa) = isa {
                 let regs = isa.register_info();
-                write!(w, " {}, {} -> {}", arg, regs.display_regunit(src), dst)
+                write!(w, " {}, {} -> {}", n.value(arg), regs.display_regunit(src), dst)
             } else {
-                write!(w, " {}, %{} -> {}", arg, src, dst)
+                write!(w, " {}, %{} -> {}", n.value(arg), src, dst)
             }
         }
         RegFill { arg, src, dst, .. } => {
             if let Some(isa) = isa {
                 let regs = isa.register_info();
-                write!(w, " {}, {} -> {}", arg, src, regs.display_regunit(dst))
+                write!(w, " {}, {} -> {}", n.value(arg), src, regs.display_regunit(dst))
             } else {
-                write!(w, " {}, {} -> %{}", arg, src, dst)
+                write!(w, " {}, {} -> %{}", n.value(arg), src, dst)
             }
         }
         Trap { code, .. } => write!(w, " {}", code),
-        CondTrap { arg, code, .. } => write!(w, " {}, {}", arg, code),
-        IntCondTrap { cond, arg, code, .. } => write!(w, " {} {}, {}", cond, arg, code),
-        FloatCondTrap { cond, arg, code, .. } => write!(w, " {} {}, {}", cond, arg, code),
+        CondTrap { arg, code, .. } => write!(w, " {}, {}", n.value(arg), code),
+        IntCondTrap { cond, arg, code, .. } => write!(w, " {} {}, {}", cond, n.value(arg), code),
+        FloatCondTrap { cond, arg, code, .. } => write!(w, " {} {}, {}", cond, n.value(arg), code),
     }
 }
 
 /// Write EBB args using optional parantheses.
-fn write_ebb_args(w: &mut Write, args: &[Value]) -> Result {
+fn write_ebb_args(w: &mut Write, args: &[Value], names: &Numbering) -> Result {
     if args.is_empty() {
         Ok(())
     } else {
-        write!(w, "({})", DisplayValues(args))
+        write!(w, "({})", DisplayValues(args, names))
     }
 }
 
 /// Displayable slice of values.
-struct DisplayValues<'a>(&'a [Value]);
+struct DisplayValues<'a>(&'a [Value], &'a Numbering);
 
 impl<'a> fmt::Display for DisplayValues<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> Result {
-        for (i, val) in self.0.iter().enumerate() {
+        for (i, &val) in self.0.iter().enumerate() {
             if i == 0 {
-                write!(f, "{}", val)?;
+                write!(f, "{}", self.1.value(val))?;
             } else {
-                write!(f, ", {}", val)?;
+                write!(f, ", {}", self.1.value(val))?;
             }
         }
         Ok(())
@@ -502,4 +809,48 @@ mod tests {
             "function %foo() native {\n    ss0 = explicit_slot 4\n\nebb0(v0: i8, v1: f32x4):\n}\n"
         );
     }
+
+    #[test]
+    fn canonical() {
+        use cursor::{Cursor, FuncCursor};
+        use ir::InstBuilder;
+        use write::WriteOptions;
+
+        // Build the same function twice, creating the entities in a different order.
+        let build = |shuffle: bool| {
+            let mut func = Function::new();
+            {
+                let mut pos = FuncCursor::new(&mut func);
+                if shuffle {
+                    pos.func.dfg.make_ebb();
+                    pos.func.dfg.make_ebb();
+                }
+                let ebb0 = pos.func.dfg.make_ebb();
+                let ebb1 = pos.func.dfg.make_ebb();
+                pos.insert_ebb(ebb0);
+                if shuffle {
+                    pos.ins().iconst(types::I32, 99);
+                }
+                let x = pos.func.dfg.append_ebb_param(ebb0, types::I32);
+                let y = pos.ins().iadd_imm(x, 1);
+                pos.ins().jump(ebb1, &[y]);
+                pos.insert_ebb(ebb1);
+                let z = pos.func.dfg.append_ebb_param(ebb1, types::I32);
+                pos.ins().return_(&[z]);
+                if shuffle {
+                    let dead = pos.func.layout.first_inst(ebb0).unwrap();
+                    pos.func.layout.remove_inst(dead);
+                }
+            }
+            func
+        };
+        let plain = build(false);
+        let shuffled = build(true);
+        assert_ne!(plain.to_string(), shuffled.to_string());
+
+        let options = WriteOptions { canonical_numbering: true };
+        let text = plain.display_with(None, options).to_string();
+        assert_eq!(text, plain.to_string());
+        assert_eq!(text, shuffled.display_with(None, options).to_string());
+    }
 }
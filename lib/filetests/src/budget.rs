@@ -0,0 +1,156 @@
+//! Timing budgets for test functions.
+//!
+//! A function can be annotated with a comment giving upper limits on the time spent parsing and
+//! compiling it:
+//!
+//! ```text
+//! ; budget: parse<5ms compile<50ms
+//! ```
+//!
+//! Budgets are always parsed so malformed annotations are reported, but they are only enforced
+//! when the test runner is asked to check them. Timings are noisy, so a budget is only exceeded
+//! when the measured time is more than `TOLERANCE` times the annotated limit.
+
+use std::time::Duration;
+use cretonne::ir::entities::AnyEntity;
+use cton_reader::Comment;
+use subtest::Result;
+
+/// Factor applied to a budget before comparing it with the measured time.
+const TOLERANCE: u32 = 4;
+
+/// Time limits for a single function.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Budget {
+    /// Limit on the time spent parsing the function.
+    pub parse: Option<Duration>,
+    /// Limit on the time spent in `Context::compile()` for each ISA.
+    pub compile: Option<Duration>,
+}
+
+impl Budget {
+    /// Collect the `budget:` annotations on a function.
+    ///
+    /// Only comments attached to the function itself are considered, like `targets:`.
+    pub fn from_comments(comments: &[Comment]) -> Result<Self> {
+        let mut budget = Self::default();
+        for comment in comments {
+            if comment.entity != AnyEntity::Function {
+                continue;
+            }
+            let text = comment.text.trim_left_matches(';').trim();
+            if !text.starts_with("budget:") {
+                continue;
+            }
+            for item in text["budget:".len()..].split_whitespace() {
+                let mut parts = item.splitn(2, '<');
+                let phase = parts.next().unwrap_or_default();
+                let limit = match parts.next() {
+                    Some(limit) => parse_duration(limit)?,
+                    None => {
+                        return Err(format!("expected 'phase<time' in budget, got '{}'", item))
+                    }
+                };
+                match phase {
+                    "parse" => budget.parse = Some(limit),
+                    "compile" => budget.compile = Some(limit),
+                    _ => return Err(format!("unknown budget phase '{}'", phase)),
+                }
+            }
+        }
+        Ok(budget)
+    }
+
+    /// Check the time spent parsing the function.
+    pub fn check_parse(&self, time: Duration) -> Result<()> {
+        check("parse", self.parse, time)
+    }
+
+    /// Check the time spent compiling the function.
+    pub fn check_compile(&self, time: Duration) -> Result<()> {
+        check("compile", self.compile, time)
+    }
+}
+
+/// Check `time` against an optional `limit`.
+fn check(phase: &str, limit: Option<Duration>, time: Duration) -> Result<()> {
+    match limit {
+        Some(limit) if time > limit * TOLERANCE => {
+            Err(format!(
+                "{} took {}, over the budget of {} (tolerance {}x)",
+                phase,
+                display_duration(time),
+                display_duration(limit),
+                TOLERANCE
+            ))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Parse a duration like `50ms`. The units `us`, `ms`, and `s` are supported.
+fn parse_duration(text: &str) -> Result<Duration> {
+    let digits = text.find(|c: char| !c.is_digit(10)).unwrap_or(text.len());
+    let value = text[..digits].parse::<u64>().map_err(|_| {
+        format!("expected a number in budget, got '{}'", text)
+    })?;
+    match &text[digits..] {
+        "us" => Ok(Duration::new(value / 1_000_000, (value % 1_000_000) as u32 * 1000)),
+        "ms" => Ok(Duration::from_millis(value)),
+        "s" => Ok(Duration::from_secs(value)),
+        unit => Err(format!("unknown time unit '{}' in budget", unit)),
+    }
+}
+
+/// Format a duration in milliseconds.
+fn display_duration(dur: Duration) -> String {
+    format!(
+        "{}.{:03}ms",
+        dur.as_secs() * 1000 + u64::from(dur.subsec_nanos() / 1_000_000),
+        dur.subsec_nanos() / 1000 % 1000
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Budget;
+    use std::time::Duration;
+    use cretonne::ir::entities::AnyEntity;
+    use cton_reader::Comment;
+
+    fn comment(text: &str) -> Comment {
+        Comment {
+            entity: AnyEntity::Function,
+            text,
+        }
+    }
+
+    #[test]
+    fn annotations() {
+        let budget = Budget::from_comments(&[comment("; budget: parse<5ms compile<2s")]).unwrap();
+        assert_eq!(budget.parse, Some(Duration::from_millis(5)));
+        assert_eq!(budget.compile, Some(Duration::from_secs(2)));
+        assert!(budget.check_parse(Duration::from_millis(20)).is_ok());
+        assert_eq!(
+            budget.check_parse(Duration::new(0, 20_500_000)),
+            Err("parse took 20.500ms, over the budget of 5.000ms (tolerance 4x)".to_string())
+        );
+
+        let budget = Budget::from_comments(&[comment("; check: compile<1ms")]).unwrap();
+        assert_eq!(budget, Budget::default());
+        assert!(budget.check_compile(Duration::from_secs(10)).is_ok());
+
+        assert_eq!(
+            Budget::from_comments(&[comment("; budget: link<1ms")]),
+            Err("unknown budget phase 'link'".to_string())
+        );
+        assert_eq!(
+            Budget::from_comments(&[comment("; budget: parse<5min")]),
+            Err("unknown time unit 'min' in budget".to_string())
+        );
+        assert_eq!(
+            Budget::from_comments(&[comment("; budget: parse")]),
+            Err("expected 'phase<time' in budget, got 'parse'".to_string())
+        );
+    }
+}
@@ -11,31 +11,45 @@
 #[macro_use(dbg)]
 extern crate cretonne;
 extern crate cton_reader;
+extern crate cton_wasm;
 extern crate filecheck;
 extern crate num_cpus;
+extern crate tempdir;
 
 use std::path::Path;
 use std::time;
 use cton_reader::TestCommand;
 use runner::TestRunner;
 
+mod budget;
 mod concurrent;
 mod runner;
 mod runone;
+mod runwasm;
 mod subtest;
 mod match_directive;
 
 mod test_binemit;
+mod test_bounds_checks;
 mod test_cat;
 mod test_compile;
+mod test_copy_propagation;
 mod test_domtree;
 mod test_legalizer;
 mod test_licm;
+mod test_postopt;
 mod test_preopt;
 mod test_print_cfg;
 mod test_regalloc;
+mod test_sccp;
 mod test_simple_gvn;
+mod test_slp;
+mod test_split_critical_edges;
+mod test_stats;
+mod test_strength_reduction;
+mod test_unroll;
 mod test_verifier;
+mod test_wasm_compile;
 
 /// The result of running the test in a file.
 type TestResult = Result<time::Duration, String>;
@@ -46,11 +60,14 @@ type TestResult = Result<time::Duration, String>;
 ///
 /// Files are interpreted as test cases and executed immediately.
 ///
-/// Directories are scanned recursively for test cases ending in `.cton`. These test cases are
-/// executed on background threads.
+/// Directories are scanned recursively for test cases ending in `.cton`, `.wat`, or `.wasm`. These
+/// test cases are executed on background threads.
 ///
-pub fn run(verbose: bool, files: &[String]) -> TestResult {
-    let mut runner = TestRunner::new(verbose);
+/// The `; budget:` timing annotations on test functions are only enforced when `check_budgets` is
+/// set.
+///
+pub fn run(verbose: bool, check_budgets: bool, files: &[String]) -> TestResult {
+    let mut runner = TestRunner::new(verbose, check_budgets);
 
     for path in files.iter().map(Path::new) {
         if path.is_file() {
@@ -71,16 +88,26 @@ pub fn run(verbose: bool, files: &[String]) -> TestResult {
 fn new_subtest(parsed: &TestCommand) -> subtest::Result<Box<subtest::SubTest>> {
     match parsed.command {
         "binemit" => test_binemit::subtest(parsed),
+        "bounds-checks" => test_bounds_checks::subtest(parsed),
         "cat" => test_cat::subtest(parsed),
         "compile" => test_compile::subtest(parsed),
+        "copy-propagation" => test_copy_propagation::subtest(parsed),
         "domtree" => test_domtree::subtest(parsed),
         "legalizer" => test_legalizer::subtest(parsed),
         "licm" => test_licm::subtest(parsed),
+        "postopt" => test_postopt::subtest(parsed),
         "preopt" => test_preopt::subtest(parsed),
         "print-cfg" => test_print_cfg::subtest(parsed),
         "regalloc" => test_regalloc::subtest(parsed),
+        "sccp" => test_sccp::subtest(parsed),
         "simple-gvn" => test_simple_gvn::subtest(parsed),
+        "slp" => test_slp::subtest(parsed),
+        "split-critical-edges" => test_split_critical_edges::subtest(parsed),
+        "stats" => test_stats::subtest(parsed),
+        "strength-reduction" => test_strength_reduction::subtest(parsed),
+        "unroll" => test_unroll::subtest(parsed),
         "verifier" => test_verifier::subtest(parsed),
+        "wasm-compile" => test_wasm_compile::subtest(parsed),
         _ => Err(format!("unknown test command '{}'", parsed.command)),
     }
 }
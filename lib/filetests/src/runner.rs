@@ -58,6 +58,9 @@ impl Display for QueueEntry {
 pub struct TestRunner {
     verbose: bool,
 
+    // Enforce the timing budgets annotated on test functions.
+    check_budgets: bool,
+
     // Directories that have not yet been scanned.
     dir_stack: Vec<PathBuf>,
 
@@ -81,9 +84,10 @@ pub struct TestRunner {
 
 impl TestRunner {
     /// Create a new blank TrstRunner.
-    pub fn new(verbose: bool) -> Self {
+    pub fn new(verbose: bool, check_budgets: bool) -> Self {
         Self {
             verbose,
+            check_budgets,
             dir_stack: Vec::new(),
             tests: Vec::new(),
             new_tests: 0,
@@ -115,7 +119,7 @@ impl TestRunner {
     /// Begin running tests concurrently.
     pub fn start_threads(&mut self) {
         assert!(self.threads.is_none());
-        self.threads = Some(ConcurrentRunner::new());
+        self.threads = Some(ConcurrentRunner::new(self.check_budgets));
     }
 
     /// Scan any directories pushed so far.
@@ -124,7 +128,8 @@ impl TestRunner {
         // This recursive search tries to minimize statting in a directory hierarchy containing
         // mostly test cases.
         //
-        // - Directory entries with a "cton" extension are presumed to be test case files.
+        // - Directory entries with a "cton", "wat", or "wasm" extension are presumed to be test
+        //   case files.
         // - Directory entries with no extension are presumed to be subdirectories.
         // - Anything else is ignored.
         //
@@ -157,7 +162,9 @@ impl TestRunner {
                                 // Recognize directories and tests by extension.
                                 // Yes, this means we ignore directories with '.' in their name.
                                 match path.extension().and_then(OsStr::to_str) {
-                                    Some("cton") => self.push_test(path),
+                                    Some("cton") | Some("wat") | Some("wasm") => {
+                                        self.push_test(path)
+                                    }
                                     Some(_) => {}
                                     None => self.push_dir(path),
                                 }
@@ -201,7 +208,7 @@ impl TestRunner {
             } else {
                 // Run test synchronously.
                 self.tests[jobid].state = State::Running;
-                let result = runone::run(self.tests[jobid].path());
+                let result = runone::run(self.tests[jobid].path(), self.check_budgets);
                 self.finish_job(jobid, result);
             }
             self.new_tests = jobid + 1;
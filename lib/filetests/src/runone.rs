@@ -1,8 +1,9 @@
 //! Run the tests in a single test file.
 
 use std::borrow::Cow;
+use std::ffi::OsStr;
 use std::path::Path;
-use std::time;
+use std::time::{self, Duration};
 use std::io::{self, Read};
 use std::fs;
 use cretonne::ir::Function;
@@ -10,11 +11,13 @@ use cretonne::isa::TargetIsa;
 use cretonne::settings::Flags;
 use cretonne::timing;
 use cretonne::verify_function;
-use cretonne::print_errors::pretty_verifier_error;
-use cton_reader::parse_test;
+use cretonne::verifier::verify_function_collect;
+use cretonne::print_errors::pretty_verifier_issues;
+use cton_reader::{parse_test_with_includes, Details, IncludeOptions, TestFile};
 use cton_reader::IsaSpec;
-use {TestResult, new_subtest};
-use subtest::{SubTest, Context, Result};
+use {TestResult, new_subtest, runwasm};
+use budget::Budget;
+use subtest::{SubTest, Context, FileContext, Result};
 
 /// Read an entire file into a string.
 fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
@@ -26,13 +29,29 @@ fn read_to_string<P: AsRef<Path>>(path: P) -> io::Result<String> {
 
 /// Load `path` and run the test in it.
 ///
+/// Enforce the `budget:` annotations on the functions if `check_budgets` is set.
+///
 /// If running this test causes a panic, it will propagate as normal.
-pub fn run(path: &Path) -> TestResult {
+pub fn run(path: &Path, check_budgets: bool) -> TestResult {
     let _tt = timing::process_file();
     dbg!("---\nFile: {}", path.to_string_lossy());
     let started = time::Instant::now();
+    match path.extension().and_then(OsStr::to_str) {
+        Some("wat") | Some("wasm") => return runwasm::run(path, started, check_budgets),
+        _ => {}
+    }
     let buffer = read_to_string(path).map_err(|e| e.to_string())?;
-    let testfile = parse_test(&buffer).map_err(|e| e.to_string())?;
+    let testfile = parse_test_with_includes(&buffer, &IncludeOptions::for_file(path))
+        .map_err(|e| e.to_string())?;
+    run_test_file(testfile, started, check_budgets)
+}
+
+/// Run the tests in a parsed test file, timing the run from `started`.
+pub fn run_test_file(
+    testfile: TestFile,
+    started: time::Instant,
+    check_budgets: bool,
+) -> TestResult {
     if testfile.functions.is_empty() {
         return Err("no functions found".to_string());
     }
@@ -65,25 +84,71 @@ pub fn run(path: &Path) -> TestResult {
         Some(t) => t,
     };
 
-    for (func, details) in testfile.functions {
+    // Let the tests build the state they share between functions.
+    let mut file = FileContext::new(&testfile.preamble_comments, &testfile.isa_spec);
+    for test in &tests {
+        test.setup_file(&mut file)?;
+    }
+
+    let result = run_functions(
+        testfile.functions,
+        &tuples,
+        last_tuple,
+        &file,
+        flags,
+        check_budgets,
+    );
+
+    // Tear down the shared state even if a function failed, but report the first error.
+    for test in &tests {
+        let teardown = test.teardown_file(&mut file);
+        if result.is_ok() {
+            teardown?;
+        }
+    }
+    result?;
+
+    Ok(started.elapsed())
+}
+
+/// Run the expanded test tuples on each function in `functions`.
+fn run_functions<'a>(
+    functions: Vec<(Function, Details<'a>)>,
+    tuples: &[(&'a SubTest, &'a Flags, Option<&'a TargetIsa>)],
+    last_tuple: (&'a SubTest, &'a Flags, Option<&'a TargetIsa>),
+    file: &'a FileContext<'a>,
+    flags: &'a Flags,
+    check_budgets: bool,
+) -> Result<()> {
+    for (func, details) in functions {
+        let budget = Budget::from_comments(&details.comments).map_err(|e| {
+            format!("{}: {}", func.name, e)
+        })?;
+        let budget = if check_budgets {
+            budget.check_parse(details.parse_time).map_err(|e| {
+                format!("{}: {}", func.name, e)
+            })?;
+            Some(budget)
+        } else {
+            None
+        };
+
         let mut context = Context {
-            preamble_comments: &testfile.preamble_comments,
+            file,
             details,
             verified: false,
             flags,
             isa: None,
         };
 
-        for tuple in &tuples {
-            run_one_test(*tuple, Cow::Borrowed(&func), &mut context)?;
+        for tuple in tuples {
+            run_one_test(*tuple, Cow::Borrowed(&func), &mut context, budget)?;
         }
         // Run the last test with an owned function which means it won't need to clone it before
         // mutating.
-        run_one_test(last_tuple, Cow::Owned(func), &mut context)?;
+        run_one_test(last_tuple, Cow::Owned(func), &mut context, budget)?;
     }
-
-
-    Ok(started.elapsed())
+    Ok(())
 }
 
 // Given a slice of tests, generate a vector of (test, flags, isa) tuples.
@@ -120,8 +185,17 @@ fn run_one_test<'a>(
     tuple: (&'a SubTest, &'a Flags, Option<&'a TargetIsa>),
     func: Cow<Function>,
     context: &mut Context<'a>,
+    budget: Option<Budget>,
 ) -> Result<()> {
     let (test, flags, isa) = tuple;
+
+    // Skip functions annotated with a list of targets that doesn't include this ISA.
+    if let Some(isa) = isa {
+        if test.needs_isa() && !context.details.has_target(isa.name()) {
+            return Ok(());
+        }
+    }
+
     let name = format!("{}({})", test.name(), func.name);
     dbg!("Test: {} {}", name, isa.map(TargetIsa::name).unwrap_or("-"));
 
@@ -130,15 +204,31 @@ fn run_one_test<'a>(
 
     // Should we run the verifier before this test?
     if !context.verified && test.needs_verifier() {
-        verify_function(&func, context.flags_or_isa()).map_err(
-            |e| {
-                pretty_verifier_error(&func, isa, &e)
-            },
-        )?;
+        if verify_function(&func, context.flags_or_isa()).is_err() {
+            // Report every problem in the function, not just the first one.
+            let issues = verify_function_collect(&func, context.flags_or_isa());
+            return Err(pretty_verifier_issues(&func, isa, &issues));
+        }
         context.verified = true;
     }
 
-    test.run(func, context).map_err(
-        |e| format!("{}: {}", name, e),
-    )
+    let result = match budget {
+        Some(budget) if budget.compile.is_some() => {
+            // Measure the compile time of this test alone, and then restore the pass timings
+            // accumulated so far.
+            let before = timing::take_current();
+            let result = test.run(func, context);
+            let times = timing::take_current();
+            timing::add_to_current(&before);
+            timing::add_to_current(&times);
+            let compile_time = times
+                .passes()
+                .iter()
+                .find(|pass| pass.name == "compile")
+                .map_or(Duration::default(), |pass| pass.total);
+            result.and_then(|()| budget.check_compile(compile_time))
+        }
+        _ => test.run(func, context),
+    };
+    result.map_err(|e| format!("{}: {}", name, e))
 }
@@ -0,0 +1,222 @@
+//! Run the tests in a single WebAssembly file.
+//!
+//! A `.wat` or `.wasm` test file is translated to Cretonne IL with the dummy environment from
+//! `cton_wasm`, and the translated functions are tested like the functions in a `.cton` file.
+//!
+//! The test commands and filecheck directives that would be in the preamble of a `.cton` file
+//! come from:
+//!
+//! - The `;;` comments of a `.wat` file. Only the leading comment lines can contain `test`,
+//!   `set`, and `isa` commands. The filecheck directives can appear anywhere, and they apply to
+//!   all functions in the module.
+//! - The custom section named `filetest` of a `.wasm` file. It contains the header in the same
+//!   syntax as a `.cton` file.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::io::{self, Read};
+use std::path::Path;
+use std::process::Command;
+use std::str;
+use std::time;
+use cton_reader::{parse_test, Details, IsaSpec, Location, SourceMap};
+use cton_wasm::{translate_module, DummyEnvironment};
+use tempdir::TempDir;
+use TestResult;
+use runone::run_test_file;
+
+/// Name of the custom section holding the test header of a `.wasm` file.
+const HEADER_SECTION: &[u8] = b"filetest";
+
+/// Read an entire file into a vector.
+fn read_to_end<P: AsRef<Path>>(path: P) -> io::Result<Vec<u8>> {
+    let mut file = fs::File::open(path)?;
+    let mut buffer = Vec::new();
+    file.read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Load the WebAssembly file at `path` and run the tests in it.
+pub fn run(path: &Path, started: time::Instant, check_budgets: bool) -> TestResult {
+    let (header, data) = match path.extension().and_then(OsStr::to_str) {
+        Some("wat") => {
+            let text = String::from_utf8(read_to_end(path).map_err(|e| e.to_string())?)
+                .map_err(|e| e.to_string())?;
+            let data = match wat2wasm(path)? {
+                Some(data) => data,
+                None => {
+                    println!("wat2wasm not found; skipped {}", path.display());
+                    return Ok(started.elapsed());
+                }
+            };
+            (wat_header(&text), data)
+        }
+        _ => {
+            let data = read_to_end(path).map_err(|e| e.to_string())?;
+            let header = wasm_header(&data).ok_or_else(|| {
+                "no filetest custom section found".to_string()
+            })?;
+            (header, data)
+        }
+    };
+
+    let mut testfile = parse_test(&header).map_err(|e| e.to_string())?;
+    if !testfile.functions.is_empty() {
+        return Err("functions are not allowed in the header of a wasm test".to_string());
+    }
+
+    // Translate the module with the flags of the last ISA, so the pointer size matches.
+    let flags = match testfile.isa_spec {
+        IsaSpec::None(ref f) => f.clone(),
+        IsaSpec::Some(ref v) => v.last().expect("Empty ISA list").flags().clone(),
+    };
+    let mut environ = DummyEnvironment::with_flags(flags);
+    translate_module(&data, &mut environ)?;
+
+    testfile.functions = environ
+        .info
+        .function_bodies
+        .into_iter()
+        .map(|func| {
+            let details = Details {
+                location: Location::default(),
+                comments: Vec::new(),
+                map: SourceMap::new(),
+                targets: Vec::new(),
+                parse_time: time::Duration::default(),
+            };
+            (func, details)
+        })
+        .collect();
+
+    run_test_file(testfile, started, check_budgets)
+}
+
+/// Convert the `.wat` file at `path` to binary with the external `wat2wasm` tool.
+///
+/// Return `None` if `wat2wasm` is not installed.
+fn wat2wasm(path: &Path) -> Result<Option<Vec<u8>>, String> {
+    let tmp_dir = TempDir::new("cretonne-filetests").map_err(|e| e.to_string())?;
+    let out_path = tmp_dir.path().join("module.wasm");
+    let output = match Command::new("wat2wasm")
+        .arg(path)
+        .arg("-o")
+        .arg(&out_path)
+        .output() {
+        Ok(output) => output,
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(format!("wat2wasm: {}", e)),
+    };
+    if !output.status.success() {
+        return Err(format!(
+            "wat2wasm failed:\n{}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+    read_to_end(&out_path).map(Some).map_err(|e| e.to_string())
+}
+
+/// Build the text of a test file header from the `;;` comments in a `.wat` file.
+fn wat_header(text: &str) -> String {
+    let mut header = String::new();
+    // Are we still in the leading comment lines?
+    let mut leading = true;
+    for line in text.lines() {
+        let line = line.trim();
+        if !line.starts_with(";;") {
+            if !line.is_empty() {
+                leading = false;
+            }
+            continue;
+        }
+        let comment = line[2..].trim();
+        let is_command = ["test ", "set ", "isa "].iter().any(
+            |cmd| comment.starts_with(cmd),
+        );
+        if !(leading && is_command) {
+            header.push_str("; ");
+        }
+        header.push_str(comment);
+        header.push('\n');
+    }
+    header
+}
+
+/// Find the test file header in the `filetest` custom section of a `.wasm` file.
+fn wasm_header(data: &[u8]) -> Option<String> {
+    if data.len() < 8 || &data[0..4] != b"\0asm" {
+        return None;
+    }
+    let mut pos = 8;
+    while pos < data.len() {
+        let id = data[pos];
+        pos += 1;
+        let size = read_leb128(data, &mut pos)? as usize;
+        let end = pos + size;
+        if end > data.len() {
+            return None;
+        }
+        if id == 0 {
+            let name_len = read_leb128(data, &mut pos)? as usize;
+            let name_end = pos + name_len;
+            if name_end > end {
+                return None;
+            }
+            if &data[pos..name_end] == HEADER_SECTION {
+                return str::from_utf8(&data[name_end..end]).ok().map(String::from);
+            }
+        }
+        pos = end;
+    }
+    None
+}
+
+/// Read an unsigned LEB128 number at `*pos`, and advance `*pos` past it.
+fn read_leb128(data: &[u8], pos: &mut usize) -> Option<u32> {
+    let mut result = 0u32;
+    for shift in 0..5 {
+        let byte = *data.get(*pos)?;
+        *pos += 1;
+        result |= u32::from(byte & 0x7f) << (shift * 7);
+        if byte & 0x80 == 0 {
+            return Some(result);
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{wat_header, wasm_header};
+
+    #[test]
+    fn header_from_wat() {
+        let text = ";; Test translation.
+                    ;; test wasm-compile
+                    ;; isa intel
+                    (module
+                      ;; test not a command here
+                      (func))
+                    ;; check: return";
+        assert_eq!(
+            wat_header(text),
+            "; Test translation.\ntest wasm-compile\nisa intel\n; test not a command here\n\
+             ; check: return\n"
+        );
+    }
+
+    #[test]
+    fn header_from_wasm() {
+        let mut data = b"\0asm\x01\0\0\0".to_vec();
+        // An unrelated custom section followed by the header.
+        data.extend_from_slice(b"\0\x04\x03abc");
+        data.extend_from_slice(b"\0\x0f\x08filetesttest x");
+        assert_eq!(wasm_header(&data), Some("test x".to_string()));
+
+        // Truncated section.
+        data.pop();
+        assert_eq!(wasm_header(&data), None);
+        assert_eq!(wasm_header(b"\0asm\x01\0\0\0"), None);
+        assert_eq!(wasm_header(b"(module)"), None);
+    }
+}
@@ -1,19 +1,68 @@
 //! `SubTest` trait.
 
+use std::any::{Any, TypeId};
 use std::result;
 use std::borrow::Cow;
+use std::collections::HashMap;
+use cretonne::diff::FunctionDiff;
 use cretonne::ir::Function;
 use cretonne::isa::TargetIsa;
 use cretonne::settings::{Flags, FlagsOrIsa};
-use cton_reader::{Details, Comment};
+use cton_reader::{Details, Comment, IsaSpec};
 use filecheck::{CheckerBuilder, Checker, NO_VARIABLES};
 
 pub type Result<T> = result::Result<T, String>;
 
+/// Context shared by the tests of all the functions in a single test file.
+///
+/// Sub-tests that need expensive state can build it once per file in `SubTest::setup_file()` and
+/// store it here, instead of rebuilding it for every function. The state is keyed by its type, so
+/// tests that want to share state with each other agree on a type.
+pub struct FileContext<'a> {
+    /// Comments from the preamble of the test file. These apply to all functions.
+    pub preamble_comments: &'a [Comment<'a>],
+
+    /// The ISAs the test file is run against.
+    pub isa_spec: &'a IsaSpec,
+
+    /// Shared state, at most one value of each type.
+    state: HashMap<TypeId, Box<Any>>,
+}
+
+impl<'a> FileContext<'a> {
+    /// Create a file context with no shared state.
+    pub fn new(preamble_comments: &'a [Comment<'a>], isa_spec: &'a IsaSpec) -> Self {
+        Self {
+            preamble_comments,
+            isa_spec,
+            state: HashMap::new(),
+        }
+    }
+
+    /// Get the shared state of type `T`, if any.
+    pub fn get<T: Any>(&self) -> Option<&T> {
+        self.state.get(&TypeId::of::<T>()).and_then(
+            |b| b.downcast_ref(),
+        )
+    }
+
+    /// Store `value` as the shared state of its type, replacing any previous value.
+    pub fn insert<T: Any>(&mut self, value: T) {
+        self.state.insert(TypeId::of::<T>(), Box::new(value));
+    }
+
+    /// Remove the shared state of type `T`, and return it.
+    pub fn remove<T: Any>(&mut self) -> Option<T> {
+        self.state.remove(&TypeId::of::<T>()).and_then(|b| {
+            b.downcast().ok().map(|b| *b)
+        })
+    }
+}
+
 /// Context for running a test on a single function.
 pub struct Context<'a> {
-    /// Comments from the preamble f the test file. These apply to all functions.
-    pub preamble_comments: &'a [Comment<'a>],
+    /// Context shared by all the functions in the test file.
+    pub file: &'a FileContext<'a>,
 
     /// Additional details about the function from the parser.
     pub details: Details<'a>,
@@ -63,12 +112,50 @@ pub trait SubTest {
         false
     }
 
+    /// Prepare for running this test on the functions in a file.
+    ///
+    /// This is called once per test file before any function is tested. Any state stored in
+    /// `file` is available to all the functions through `Context::file`.
+    fn setup_file(&self, _file: &mut FileContext) -> Result<()> {
+        Ok(())
+    }
+
+    /// Clean up after running this test on the functions in a file.
+    ///
+    /// This is called once per test file after all the functions are tested, even if one of them
+    /// failed.
+    fn teardown_file(&self, _file: &mut FileContext) -> Result<()> {
+        Ok(())
+    }
+
     /// Run this test on `func`.
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()>;
 }
 
 /// Run filecheck on `text`, using directives extracted from `context`.
 pub fn run_filecheck(text: &str, context: &Context) -> Result<()> {
+    check_text(text, context, String::new)
+}
+
+/// Run filecheck on `text`, the output of a pass that transformed `input` into `func`.
+///
+/// When the check fails, the report includes the changes made by the pass, so they don't have to
+/// be found by comparing the test input with the output. Use `isa` to show the encodings and
+/// value locations.
+pub fn run_filecheck_diff(
+    text: &str,
+    input: &Function,
+    func: &Function,
+    isa: Option<&TargetIsa>,
+    context: &Context,
+) -> Result<()> {
+    check_text(text, context, || {
+        format!("changes:\n{}", FunctionDiff::new(input, func, isa))
+    })
+}
+
+/// Run filecheck on `text`, adding the output of `details` to the report when it fails.
+fn check_text<F: FnOnce() -> String>(text: &str, context: &Context, details: F) -> Result<()> {
     let checker = build_filechecker(context)?;
     if checker.check(text, NO_VARIABLES).map_err(|e| {
         format!("filecheck: {}", e)
@@ -80,7 +167,12 @@ pub fn run_filecheck(text: &str, context: &Context) -> Result<()> {
         let (_, explain) = checker.explain(text, NO_VARIABLES).map_err(|e| {
             format!("explain: {}", e)
         })?;
-        Err(format!("filecheck failed:\n{}{}", checker, explain))
+        Err(format!(
+            "filecheck failed:\n{}{}{}",
+            checker,
+            details(),
+            explain
+        ))
     }
 }
 
@@ -88,7 +180,7 @@ pub fn run_filecheck(text: &str, context: &Context) -> Result<()> {
 pub fn build_filechecker(context: &Context) -> Result<Checker> {
     let mut builder = CheckerBuilder::new();
     // Preamble comments apply to all functions.
-    for comment in context.preamble_comments {
+    for comment in context.file.preamble_comments {
         builder.directive(comment.text).map_err(|e| {
             format!("filecheck: {}", e)
         })?;
@@ -100,3 +192,27 @@ pub fn build_filechecker(context: &Context) -> Result<Checker> {
     }
     Ok(builder.finish())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::FileContext;
+    use cretonne::settings::{self, Flags};
+    use cton_reader::IsaSpec;
+
+    #[test]
+    fn shared_state() {
+        let isa_spec = IsaSpec::None(Flags::new(&settings::builder()));
+        let mut file = FileContext::new(&[], &isa_spec);
+        assert_eq!(file.get::<u32>(), None);
+
+        file.insert(7u32);
+        file.insert("seven");
+        assert_eq!(file.get::<u32>(), Some(&7));
+        assert_eq!(file.get::<&str>(), Some(&"seven"));
+
+        file.insert(8u32);
+        assert_eq!(file.remove::<u32>(), Some(8));
+        assert_eq!(file.get::<u32>(), None);
+        assert_eq!(file.get::<&str>(), Some(&"seven"));
+    }
+}
@@ -31,6 +31,7 @@ pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
 struct TextSink {
     offset: binemit::CodeOffset,
     text: String,
+    bytes: Vec<u8>,
 }
 
 impl TextSink {
@@ -39,6 +40,7 @@ impl TextSink {
         Self {
             offset: 0,
             text: String::new(),
+            bytes: Vec::new(),
         }
     }
 }
@@ -52,21 +54,31 @@ impl binemit::CodeSink for TextSink {
 
     fn put1(&mut self, x: u8) {
         write!(self.text, "{:02x} ", x).unwrap();
+        self.bytes.push(x);
         self.offset += 1;
     }
 
     fn put2(&mut self, x: u16) {
         write!(self.text, "{:04x} ", x).unwrap();
+        for i in 0..2 {
+            self.bytes.push((x >> (8 * i)) as u8);
+        }
         self.offset += 2;
     }
 
     fn put4(&mut self, x: u32) {
         write!(self.text, "{:08x} ", x).unwrap();
+        for i in 0..4 {
+            self.bytes.push((x >> (8 * i)) as u8);
+        }
         self.offset += 4;
     }
 
     fn put8(&mut self, x: u64) {
         write!(self.text, "{:016x} ", x).unwrap();
+        for i in 0..8 {
+            self.bytes.push((x >> (8 * i)) as u8);
+        }
         self.offset += 8;
     }
 
@@ -102,6 +114,14 @@ impl binemit::CodeSink for TextSink {
     fn reloc_jt(&mut self, reloc: binemit::Reloc, jt: ir::JumpTable) {
         write!(self.text, "{}({}) ", reloc, jt).unwrap();
     }
+
+    fn trap(&mut self, code: ir::TrapCode, _srcloc: ir::SourceLoc) {
+        write!(self.text, "{} ", code).unwrap();
+    }
+
+    fn patchable_call(&mut self, name: &ir::ExternalName) {
+        write!(self.text, "patchable({}) ", name).unwrap();
+    }
 }
 
 impl SubTest for TestBinEmit {
@@ -143,11 +163,11 @@ impl SubTest for TestBinEmit {
                     // constraints.
                     if let Some(enc) = {
                         let mut legal_encodings = isa.legal_encodings(
-                            &func.dfg,
+                            &func,
                             &func.dfg[inst],
                             func.dfg.ctrl_typevar(inst),
                         ).filter(|e| {
-                                let recipe_constraints = &encinfo.constraints[e.recipe()];
+                                let recipe_constraints = encinfo.recipe_constraints(e.recipe());
                                 recipe_constraints.satisfied(inst, &divert, &func)
                             });
 
@@ -205,6 +225,11 @@ impl SubTest for TestBinEmit {
         let mut sink = TextSink::new();
         for ebb in func.layout.ebbs() {
             divert.clear();
+            // Fill the gap in front of aligned EBBs.
+            if sink.offset < func.offsets[ebb] {
+                let padding = func.offsets[ebb] - sink.offset;
+                isa.emit_padding(padding, &mut sink);
+            }
             // Correct header offsets should have been computed by `relax_branches()`.
             assert_eq!(
                 sink.offset,
@@ -215,6 +240,7 @@ impl SubTest for TestBinEmit {
             for (offset, inst, enc_bytes) in func.inst_offsets(ebb, &encinfo) {
                 assert_eq!(sink.offset, offset);
                 sink.text.clear();
+                sink.bytes.clear();
                 let enc = func.encodings[inst];
 
                 // Send legal encodings into the emitter.
@@ -231,6 +257,7 @@ impl SubTest for TestBinEmit {
                         ));
                     }
                     let before = sink.offset;
+                    let divert_before = divert.clone();
                     isa.emit_inst(&func, inst, &mut divert, &mut sink);
                     let emitted = sink.offset - before;
                     // Verify the encoding recipe sizes against the ISAs emit_inst implementation.
@@ -241,6 +268,15 @@ impl SubTest for TestBinEmit {
                         encinfo.display(enc),
                         func.dfg.display_inst(inst, isa)
                     );
+                    // Decode the machine code and check it against the instruction.
+                    isa.check_emitted(&func, inst, &divert_before, before, &sink.bytes)
+                        .map_err(|e| {
+                            format!(
+                                "Bad machine code for {}: {}",
+                                func.dfg.display_inst(inst, isa),
+                                e
+                            )
+                        })?;
                 }
 
                 // Check against bin: directives.
@@ -261,7 +297,7 @@ impl SubTest for TestBinEmit {
 
                         // Do any encodings exist?
                         let encodings = isa.legal_encodings(
-                            &func.dfg,
+                            &func,
                             &func.dfg[inst],
                             func.dfg.ctrl_typevar(inst),
                         ).map(|e| encinfo.display(e))
@@ -0,0 +1,54 @@
+//! Test command for testing the heap bounds check optimization pass.
+//!
+//! The `bounds-checks` test command runs each function through the pass that combines and hoists
+//! heap bounds checks.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use cretonne::ir::Function;
+use cretonne;
+use cretonne::print_errors::pretty_error;
+use cton_reader::TestCommand;
+use subtest::{SubTest, Context, Result, run_filecheck_diff};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+struct TestBoundsChecks;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "bounds-checks");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestBoundsChecks))
+    }
+}
+
+impl SubTest for TestBoundsChecks {
+    fn name(&self) -> Cow<str> {
+        Cow::from("bounds-checks")
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
+        // Create a compilation context, and drop in the function.
+        let mut comp_ctx = cretonne::Context::new();
+        let input = func.into_owned();
+        comp_ctx.func = input.clone();
+
+        comp_ctx.flowgraph();
+        comp_ctx.compute_loop_analysis();
+        comp_ctx.bounds_checks(context.flags_or_isa()).map_err(|e| {
+            pretty_error(&comp_ctx.func, context.isa, Into::into(e))
+        })?;
+
+        let mut text = String::new();
+        write!(&mut text, "{}", &comp_ctx.func).map_err(
+            |e| e.to_string(),
+        )?;
+        run_filecheck_diff(&text, &input, &comp_ctx.func, None, context)
+    }
+}
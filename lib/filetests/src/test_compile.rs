@@ -5,14 +5,29 @@
 use cretonne::binemit;
 use cretonne::ir;
 use cretonne;
+use cretonne::isa::TargetIsa;
 use cretonne::print_errors::pretty_error;
 use cton_reader::TestCommand;
-use subtest::{SubTest, Context, Result, run_filecheck};
+use subtest::{SubTest, Context, FileContext, Result, run_filecheck};
 use std::borrow::Cow;
+use std::cell::RefCell;
 use std::fmt::Write;
 
 struct TestCompile;
 
+/// Compilation context shared by the functions in a test file.
+///
+/// Reusing a single `cretonne::Context` avoids reallocating its data structures for every
+/// function, the same way an embedder compiling many functions would.
+struct SharedContext(RefCell<cretonne::Context>);
+
+/// Store a shared compilation context in `file`, unless another test already did.
+pub fn setup_shared_context(file: &mut FileContext) {
+    if file.get::<SharedContext>().is_none() {
+        file.insert(SharedContext(RefCell::new(cretonne::Context::new())));
+    }
+}
+
 pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
     assert_eq!(parsed.command, "compile");
     if !parsed.options.is_empty() {
@@ -35,45 +50,66 @@ impl SubTest for TestCompile {
         true
     }
 
+    fn setup_file(&self, file: &mut FileContext) -> Result<()> {
+        setup_shared_context(file);
+        Ok(())
+    }
+
     fn run(&self, func: Cow<ir::Function>, context: &Context) -> Result<()> {
         let isa = context.isa.expect("compile needs an ISA");
+        let text = compile(func.into_owned(), isa, context)?;
+        run_filecheck(&text, context)
+    }
+}
 
-        // Create a compilation context, and drop in the function.
-        let mut comp_ctx = cretonne::Context::new();
-        comp_ctx.func = func.into_owned();
-
-        let code_size = comp_ctx.compile(isa).map_err(|e| {
-            pretty_error(&comp_ctx.func, context.isa, e)
-        })?;
-
-        dbg!(
-            "Generated {} bytes of code:\n{}",
+/// Compile `func` for `isa`, and return the text of the compiled function followed by a size
+/// report.
+///
+/// The compilation context set up by `setup_shared_context()` is reused.
+pub fn compile(func: ir::Function, isa: &TargetIsa, context: &Context) -> Result<String> {
+    // Clear the shared compilation context, and drop in the function.
+    let shared = context.file.get::<SharedContext>().ok_or_else(|| {
+        "no shared compilation context".to_string()
+    })?;
+    let mut comp_ctx = shared.0.borrow_mut();
+    comp_ctx.clear();
+    comp_ctx.func = func;
+
+    let code_size = comp_ctx.compile(isa).map_err(|e| {
+        pretty_error(&comp_ctx.func, context.isa, e)
+    })?;
+
+    dbg!(
+        "Generated {} bytes of code:\n{}",
+        code_size,
+        comp_ctx.func.display(isa)
+    );
+
+    // Verify that the returned code size matches the emitted bytes.
+    let mut sink = SizeSink { offset: 0 };
+    binemit::emit_function(
+        &comp_ctx.func,
+        |func, inst, div, sink| isa.emit_inst(func, inst, div, sink),
+        |bytes, sink| isa.emit_padding(bytes, sink),
+        &mut sink,
+    );
+
+    if sink.offset != code_size {
+        return Err(format!(
+            "Expected code size {}, got {}",
             code_size,
-            comp_ctx.func.display(isa)
-        );
-
-        // Verify that the returned code size matches the emitted bytes.
-        let mut sink = SizeSink { offset: 0 };
-        binemit::emit_function(
-            &comp_ctx.func,
-            |func, inst, div, sink| isa.emit_inst(func, inst, div, sink),
-            &mut sink,
-        );
-
-        if sink.offset != code_size {
-            return Err(format!(
-                "Expected code size {}, got {}",
-                code_size,
-                sink.offset
-            ));
-        }
-
-        // Run final code through filecheck.
-        let mut text = String::new();
-        write!(&mut text, "{}", &comp_ctx.func.display(Some(isa)))
-            .map_err(|e| e.to_string())?;
-        run_filecheck(&text, context)
+            sink.offset
+        ));
     }
+
+    // Print the final code, followed by a size report.
+    let mut text = String::new();
+    write!(&mut text, "{}", &comp_ctx.func.display(Some(isa)))
+        .map_err(|e| e.to_string())?;
+    writeln!(&mut text, "; code size: {} bytes", code_size).map_err(
+        |e| e.to_string(),
+    )?;
+    Ok(text)
 }
 
 // Code sink that simply counts bytes.
@@ -111,4 +147,6 @@ impl binemit::CodeSink for SizeSink {
     ) {
     }
     fn reloc_jt(&mut self, _reloc: binemit::Reloc, _jt: ir::JumpTable) {}
+    fn trap(&mut self, _code: ir::TrapCode, _srcloc: ir::SourceLoc) {}
+    fn patchable_call(&mut self, _name: &ir::ExternalName) {}
 }
@@ -0,0 +1,64 @@
+//! Test command for testing the post-regalloc copy propagation pass.
+//!
+//! The `copy-propagation` test command runs each function through the register allocator and then
+//! removes the copies made redundant by the register assignment.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use cretonne::ir::Function;
+use cretonne;
+use cretonne::print_errors::pretty_error;
+use cton_reader::TestCommand;
+use subtest::{SubTest, Context, Result, run_filecheck_diff};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+struct TestCopyPropagation;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "copy-propagation");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestCopyPropagation))
+    }
+}
+
+impl SubTest for TestCopyPropagation {
+    fn name(&self) -> Cow<str> {
+        Cow::from("copy-propagation")
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn needs_isa(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
+        let isa = context.isa.expect("copy propagation needs an ISA");
+
+        let mut comp_ctx = cretonne::Context::new();
+        let input = func.into_owned();
+        comp_ctx.func = input.clone();
+
+        comp_ctx.compute_cfg();
+        comp_ctx.legalize(isa).map_err(|e| {
+            pretty_error(&comp_ctx.func, context.isa, e)
+        })?;
+        comp_ctx.compute_domtree();
+        comp_ctx.regalloc(isa).map_err(|e| {
+            pretty_error(&comp_ctx.func, context.isa, e)
+        })?;
+        comp_ctx.propagate_copies(isa).map_err(|e| {
+            pretty_error(&comp_ctx.func, context.isa, e)
+        })?;
+
+        let mut text = String::new();
+        write!(&mut text, "{}", &comp_ctx.func.display(Some(isa)))
+            .map_err(|e| e.to_string())?;
+        run_filecheck_diff(&text, &input, &comp_ctx.func, Some(isa), context)
+    }
+}
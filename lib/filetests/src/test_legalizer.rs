@@ -8,7 +8,7 @@ use cretonne;
 use cretonne::ir::Function;
 use cretonne::print_errors::pretty_error;
 use cton_reader::TestCommand;
-use subtest::{SubTest, Context, Result, run_filecheck};
+use subtest::{SubTest, Context, Result, run_filecheck_diff};
 use std::fmt::Write;
 
 struct TestLegalizer;
@@ -37,7 +37,8 @@ impl SubTest for TestLegalizer {
 
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
         let mut comp_ctx = cretonne::Context::new();
-        comp_ctx.func = func.into_owned();
+        let input = func.into_owned();
+        comp_ctx.func = input.clone();
         let isa = context.isa.expect("legalizer needs an ISA");
 
         comp_ctx.compute_cfg();
@@ -48,6 +49,6 @@ impl SubTest for TestLegalizer {
         let mut text = String::new();
         write!(&mut text, "{}", &comp_ctx.func.display(Some(isa)))
             .map_err(|e| e.to_string())?;
-        run_filecheck(&text, context)
+        run_filecheck_diff(&text, &input, &comp_ctx.func, Some(isa), context)
     }
 }
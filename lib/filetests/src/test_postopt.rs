@@ -0,0 +1,60 @@
+//! Test command for testing the postopt pass.
+//!
+//! The `postopt` test command legalizes each function for the target ISA and then runs it through
+//! the post-legalization rewriting pass.
+//!
+//! The resulting function is sent to `filecheck`.
+
+use cretonne::ir::Function;
+use cretonne;
+use cretonne::print_errors::pretty_error;
+use cton_reader::TestCommand;
+use subtest::{SubTest, Context, Result, run_filecheck_diff};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+struct TestPostopt;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "postopt");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestPostopt))
+    }
+}
+
+impl SubTest for TestPostopt {
+    fn name(&self) -> Cow<str> {
+        Cow::from("postopt")
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn needs_isa(&self) -> bool {
+        true
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
+        // Create a compilation context, and drop in the function.
+        let mut comp_ctx = cretonne::Context::new();
+        let input = func.into_owned();
+        comp_ctx.func = input.clone();
+        let isa = context.isa.expect("postopt needs an ISA");
+
+        comp_ctx.compute_cfg();
+        comp_ctx.legalize(isa).map_err(|e| {
+            pretty_error(&comp_ctx.func, context.isa, e)
+        })?;
+        comp_ctx.postopt(isa).map_err(|e| {
+            pretty_error(&comp_ctx.func, context.isa, e)
+        })?;
+
+        let mut text = String::new();
+        write!(&mut text, "{}", &comp_ctx.func.display(Some(isa)))
+            .map_err(|e| e.to_string())?;
+        run_filecheck_diff(&text, &input, &comp_ctx.func, Some(isa), context)
+    }
+}
@@ -7,19 +7,34 @@ use std::borrow::Cow;
 
 use cretonne::ir::Function;
 use cretonne::cfg_printer::CFGPrinter;
-use cton_reader::TestCommand;
+use cton_reader::{TestCommand, TestOption};
 use subtest::{self, SubTest, Context, Result as STResult};
 
 /// Object implementing the `test print-cfg` sub-test.
-struct TestPrintCfg;
+#[derive(Default)]
+struct TestPrintCfg {
+    /// Number of instructions to show in each EBB, or `None` to only show branches.
+    insts: Option<usize>,
+    domtree: bool,
+    loops: bool,
+}
 
 pub fn subtest(parsed: &TestCommand) -> STResult<Box<SubTest>> {
     assert_eq!(parsed.command, "print-cfg");
-    if !parsed.options.is_empty() {
-        Err(format!("No options allowed on {}", parsed))
-    } else {
-        Ok(Box::new(TestPrintCfg))
+    let mut test = TestPrintCfg::default();
+    for option in &parsed.options {
+        match *option {
+            TestOption::Value("insts", value) => {
+                test.insts = Some(value.parse().map_err(|_| {
+                    format!("Invalid instruction count '{}' on {}", value, parsed)
+                })?)
+            }
+            TestOption::Flag("domtree") => test.domtree = true,
+            TestOption::Flag("loops") => test.loops = true,
+            _ => return Err(format!("Unknown option on {}", parsed)),
+        }
     }
+    Ok(Box::new(test))
 }
 
 impl SubTest for TestPrintCfg {
@@ -32,6 +47,16 @@ impl SubTest for TestPrintCfg {
     }
 
     fn run(&self, func: Cow<Function>, context: &Context) -> STResult<()> {
-        subtest::run_filecheck(&CFGPrinter::new(&func).to_string(), context)
+        let mut printer = CFGPrinter::new(&func);
+        if let Some(insts) = self.insts {
+            printer = printer.with_insts(insts);
+        }
+        if self.domtree {
+            printer = printer.with_domtree();
+        }
+        if self.loops {
+            printer = printer.with_loops();
+        }
+        subtest::run_filecheck(&printer.to_string(), context)
     }
 }
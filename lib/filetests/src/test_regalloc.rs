@@ -9,7 +9,7 @@ use cretonne::ir::Function;
 use cretonne;
 use cretonne::print_errors::pretty_error;
 use cton_reader::TestCommand;
-use subtest::{SubTest, Context, Result, run_filecheck};
+use subtest::{SubTest, Context, Result, run_filecheck_diff};
 use std::borrow::Cow;
 use std::fmt::Write;
 
@@ -42,7 +42,8 @@ impl SubTest for TestRegalloc {
 
         // Create a compilation context, and drop in the function.
         let mut comp_ctx = cretonne::Context::new();
-        comp_ctx.func = func.into_owned();
+        let input = func.into_owned();
+        comp_ctx.func = input.clone();
 
         comp_ctx.compute_cfg();
         // TODO: Should we have an option to skip legalization?
@@ -57,6 +58,6 @@ impl SubTest for TestRegalloc {
         let mut text = String::new();
         write!(&mut text, "{}", &comp_ctx.func.display(Some(isa)))
             .map_err(|e| e.to_string())?;
-        run_filecheck(&text, context)
+        run_filecheck_diff(&text, &input, &comp_ctx.func, Some(isa), context)
     }
 }
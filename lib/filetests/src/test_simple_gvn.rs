@@ -9,7 +9,7 @@ use cretonne::ir::Function;
 use cretonne;
 use cretonne::print_errors::pretty_error;
 use cton_reader::TestCommand;
-use subtest::{SubTest, Context, Result, run_filecheck};
+use subtest::{SubTest, Context, Result, run_filecheck_diff};
 use std::borrow::Cow;
 use std::fmt::Write;
 
@@ -36,7 +36,8 @@ impl SubTest for TestSimpleGVN {
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
         // Create a compilation context, and drop in the function.
         let mut comp_ctx = cretonne::Context::new();
-        comp_ctx.func = func.into_owned();
+        let input = func.into_owned();
+        comp_ctx.func = input.clone();
 
         comp_ctx.flowgraph();
         comp_ctx.simple_gvn(context.flags_or_isa()).map_err(|e| {
@@ -47,6 +48,6 @@ impl SubTest for TestSimpleGVN {
         write!(&mut text, "{}", &comp_ctx.func).map_err(
             |e| e.to_string(),
         )?;
-        run_filecheck(&text, context)
+        run_filecheck_diff(&text, &input, &comp_ctx.func, None, context)
     }
 }
@@ -0,0 +1,60 @@
+//! Test command for checking function statistics.
+//!
+//! The `stats` test command prints the size statistics of each function and runs them through
+//! filecheck. When the test file specifies an ISA, the function is compiled first so the
+//! statistics include the frame size and code size, followed by the number and total size of the
+//! spill slots before and after the register allocator shares them.
+
+use cretonne;
+use cretonne::ir::Function;
+use cretonne::print_errors::pretty_error;
+use cton_reader::TestCommand;
+use subtest::{SubTest, Context, Result, run_filecheck};
+use std::borrow::Cow;
+use std::fmt::Write;
+
+struct TestStats;
+
+pub fn subtest(parsed: &TestCommand) -> Result<Box<SubTest>> {
+    assert_eq!(parsed.command, "stats");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestStats))
+    }
+}
+
+impl SubTest for TestStats {
+    fn name(&self) -> Cow<str> {
+        Cow::from("stats")
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
+        let text = match context.isa {
+            Some(isa) => {
+                let mut comp_ctx = cretonne::Context::new();
+                comp_ctx.func = func.into_owned();
+                comp_ctx.compile(isa).map_err(|e| {
+                    pretty_error(&comp_ctx.func, context.isa, e)
+                })?;
+                let spills = comp_ctx.regalloc.spill_slot_stats();
+                let mut text = comp_ctx.func.stats(isa).to_string();
+                writeln!(
+                    text,
+                    "spill_slots: {} -> {}",
+                    spills.slots_before,
+                    spills.slots_after
+                ).map_err(|e| e.to_string())?;
+                writeln!(
+                    text,
+                    "spill_size: {} -> {}",
+                    spills.size_before,
+                    spills.size_after
+                ).map_err(|e| e.to_string())?;
+                text
+            }
+            None => func.stats(None).to_string(),
+        };
+        run_filecheck(&text, context)
+    }
+}
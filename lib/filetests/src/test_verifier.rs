@@ -8,10 +8,19 @@
 //!
 //! This annotation means that the verifier is expected to given an error for the jump instruction
 //! containing the substring "jump to non-existent EBB".
+//!
+//! All problems in a function are reported at once, so a function can have multiple `error:`
+//! annotations. Verifier warnings can be checked with `warning:` annotations, but they are not
+//! required.
+//!
+//! When the test has an ISA and the function has value locations, the locations are also verified
+//! with a liveness analysis, provided that the function has no other errors.
 
 use std::borrow::{Borrow, Cow};
-use cretonne::verify_function;
+use cretonne;
 use cretonne::ir::Function;
+use cretonne::isa::TargetIsa;
+use cretonne::verifier::{verify_function_collect, Severity, VerifierIssue};
 use cton_reader::TestCommand;
 use subtest::{SubTest, Context, Result};
 use match_directive::match_directive;
@@ -40,43 +49,70 @@ impl SubTest for TestVerifier {
     fn run(&self, func: Cow<Function>, context: &Context) -> Result<()> {
         let func = func.borrow();
 
-        // Scan source annotations for "error:" directives.
-        let mut expected = None;
+        // Scan source annotations for "error:" and "warning:" directives.
+        let mut expected = Vec::new();
         for comment in &context.details.comments {
             if let Some(tail) = match_directive(comment.text, "error:") {
-                // Currently, the verifier can only report one problem at a time.
-                // Reject more than one `error:` directives.
-                if expected.is_some() {
-                    return Err("cannot handle multiple error: directives".to_string());
-                }
-                expected = Some((comment.entity, tail));
+                expected.push((Severity::Error, comment.entity, tail));
+            } else if let Some(tail) = match_directive(comment.text, "warning:") {
+                expected.push((Severity::Warning, comment.entity, tail));
             }
         }
 
-        match verify_function(func, context.flags_or_isa()) {
-            Ok(_) => {
-                match expected {
-                    None => Ok(()),
-                    Some((_, msg)) => Err(format!("passed, expected error: {}", msg)),
-                }
+        let mut issues = verify_function_collect(func, context.flags_or_isa());
+        if let Some(isa) = context.isa {
+            if !issues.iter().any(|i| i.severity == Severity::Error) {
+                issues.extend(check_locations(func, isa));
             }
-            Err(got) => {
-                match expected {
-                    None => Err(format!("verifier pass, got {}", got)),
-                    Some((want_loc, want_msg)) if got.message.contains(want_msg) => {
-                        if want_loc == got.location {
-                            Ok(())
-                        } else {
-                            Err(format!(
-                                "correct error reported on {}, but wanted {}",
-                                got.location,
+        }
+
+        // Every expected issue must be reported on the annotated entity.
+        for &(severity, want_loc, want_msg) in &expected {
+            let found = issues.iter().position(|i| {
+                i.severity == severity && i.error.location == want_loc &&
+                    i.error.message.contains(want_msg)
+            });
+            match found {
+                Some(idx) => {
+                    issues.remove(idx);
+                }
+                None => {
+                    return Err(match issues.iter().find(|i| i.severity == severity) {
+                        None => format!("passed, expected {}: {}", severity, want_msg),
+                        Some(got) if got.error.message.contains(want_msg) => {
+                            format!(
+                                "correct {} reported on {}, but wanted {}",
+                                severity,
+                                got.error.location,
                                 want_loc
-                            ))
+                            )
                         }
-                    }
-                    Some(_) => Err(format!("mismatching error: {}", got)),
+                        Some(got) => format!("mismatching {}", got),
+                    });
                 }
             }
         }
+
+        // Unexpected warnings are fine, but all errors must be annotated.
+        match issues.iter().find(|i| i.severity == Severity::Error) {
+            None => Ok(()),
+            Some(got) if expected.is_empty() => Err(format!("verifier pass, got {}", got.error)),
+            Some(got) => Err(format!("unexpected {}", got)),
+        }
     }
 }
+
+/// Verify the value locations in `func` if it has any.
+fn check_locations(func: &Function, isa: &TargetIsa) -> Option<VerifierIssue> {
+    if !func.locations.keys().any(|v| func.locations[v].is_assigned()) {
+        return None;
+    }
+
+    let mut ctx = cretonne::Context::for_function(func.clone());
+    ctx.verify_locations_with_liveness(isa).err().map(|error| {
+        VerifierIssue {
+            severity: Severity::Error,
+            error,
+        }
+    })
+}
@@ -0,0 +1,48 @@
+//! The `wasm-compile` subtest.
+
+use std::borrow::Cow;
+use cretonne::ir::Function;
+use cton_reader::TestCommand;
+use subtest::{self, SubTest, Context, FileContext, Result as STResult};
+use test_compile::{compile, setup_shared_context};
+
+/// Object implementing the `test wasm-compile` sub-test.
+///
+/// This command is used in `.wat` and `.wasm` test files for testing the WebAssembly translator.
+/// Each function translated from the module is verified and printed. If the test file has a
+/// unique ISA, the function is compiled for it first, like `test compile` does.
+///
+/// The result is verified by filecheck.
+struct TestWasmCompile;
+
+pub fn subtest(parsed: &TestCommand) -> STResult<Box<SubTest>> {
+    assert_eq!(parsed.command, "wasm-compile");
+    if !parsed.options.is_empty() {
+        Err(format!("No options allowed on {}", parsed))
+    } else {
+        Ok(Box::new(TestWasmCompile))
+    }
+}
+
+impl SubTest for TestWasmCompile {
+    fn name(&self) -> Cow<str> {
+        Cow::from("wasm-compile")
+    }
+
+    fn is_mutating(&self) -> bool {
+        true
+    }
+
+    fn setup_file(&self, file: &mut FileContext) -> STResult<()> {
+        setup_shared_context(file);
+        Ok(())
+    }
+
+    fn run(&self, func: Cow<Function>, context: &Context) -> STResult<()> {
+        let text = match context.isa {
+            Some(isa) => compile(func.into_owned(), isa, context)?,
+            None => func.display(None).to_string(),
+        };
+        subtest::run_filecheck(&text, context)
+    }
+}
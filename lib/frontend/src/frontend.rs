@@ -3,12 +3,16 @@ use cretonne::cursor::{Cursor, FuncCursor};
 use cretonne::ir;
 use cretonne::ir::{Ebb, Type, Value, Function, Inst, JumpTable, StackSlot, JumpTableData,
                    StackSlotData, DataFlowGraph, InstructionData, ExtFuncData, FuncRef, SigRef,
-                   Signature, InstBuilderBase, GlobalVarData, GlobalVar, HeapData, Heap};
+                   Signature, InstBuilderBase, GlobalVarData, GlobalVar, HeapData, Heap,
+                   TableData, Table, StackSlotKind, MemFlags, InstBuilder, ExternalName, LibCall,
+                   AbiParam, CallConv};
 use cretonne::ir::function::DisplayFunction;
 use cretonne::isa::TargetIsa;
 use ssa::{SSABuilder, SideEffects, Block};
 use cretonne::entity::{EntityRef, EntityMap, EntitySet};
 use cretonne::packed_option::PackedOption;
+use std::fmt;
+use std::vec::Vec;
 
 /// Structure used for translating a series of functions into Cretonne IL.
 ///
@@ -50,6 +54,43 @@ struct EbbData {
     filled: bool,
     pristine: bool,
     user_param_count: usize,
+    /// Source locations that were current when the EBB was created, filled, and sealed. These are
+    /// only used for diagnostics.
+    created_at: ir::SourceLoc,
+    filled_at: ir::SourceLoc,
+    sealed_at: ir::SourceLoc,
+}
+
+/// An EBB that was left incomplete when the function was finalized.
+///
+/// Every EBB that is used must be filled with a terminator instruction and sealed before the
+/// function is finalized. The source locations are the ones that were set with
+/// `FunctionBuilder::set_srcloc` when the EBB was created, filled, and sealed, to help locate the
+/// construct in the source language that forgot to complete the EBB.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct IncompleteEbb {
+    /// The incomplete EBB.
+    pub ebb: Ebb,
+    /// Source location current when the EBB was created.
+    pub created_at: ir::SourceLoc,
+    /// Source location current when the EBB was filled, or `None` if it wasn't filled.
+    pub filled_at: Option<ir::SourceLoc>,
+    /// Source location current when the EBB was sealed, or `None` if it wasn't sealed.
+    pub sealed_at: Option<ir::SourceLoc>,
+}
+
+impl fmt::Display for IncompleteEbb {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} created at {}", self.ebb, self.created_at)?;
+        match self.filled_at {
+            Some(loc) => write!(f, ", filled at {}", loc)?,
+            None => write!(f, ", never filled")?,
+        }
+        match self.sealed_at {
+            Some(loc) => write!(f, ", sealed at {}", loc),
+            None => write!(f, ", never sealed"),
+        }
+    }
 }
 
 struct Position {
@@ -169,8 +210,7 @@ impl<'short, 'long, Variable> InstBuilderBase<'short> for FuncInstBuilder<'short
                                     .jump_tables
                                     .get(table)
                                     .expect("you are referencing an undeclared jump table")
-                                    .entries()
-                                    .map(|(_, ebb)| ebb)
+                                    .destinations()
                                     .filter(|dest_ebb| unique.insert(*dest_ebb)) {
                             self.builder.func_ctx.ssa.declare_ebb_predecessor(
                                 dest_ebb,
@@ -256,6 +296,9 @@ where
             filled: false,
             pristine: true,
             user_param_count: 0,
+            created_at: self.srcloc,
+            filled_at: Default::default(),
+            sealed_at: Default::default(),
         };
         ebb
     }
@@ -291,6 +334,14 @@ where
     /// created. Forgetting to call this method on every block will cause inconsistencies in the
     /// produced functions.
     pub fn seal_block(&mut self, ebb: Ebb) {
+        debug_assert!(
+            !self.func_ctx.ssa.is_sealed(ebb),
+            "{} is already sealed (sealed at {}, current location {})",
+            ebb,
+            self.func_ctx.ebbs[ebb].sealed_at,
+            self.srcloc
+        );
+        self.func_ctx.ebbs[ebb].sealed_at = self.srcloc;
         let side_effects = self.func_ctx.ssa.seal_ebb_header_block(ebb, self.func);
         self.handle_ssa_side_effects(side_effects);
     }
@@ -302,6 +353,11 @@ where
     /// function can be used at the end of translating all blocks to ensure
     /// that everything is sealed.
     pub fn seal_all_blocks(&mut self) {
+        for ebb in self.func_ctx.ebbs.keys() {
+            if !self.func_ctx.ssa.is_sealed(ebb) {
+                self.func_ctx.ebbs[ebb].sealed_at = self.srcloc;
+            }
+        }
         let side_effects = self.func_ctx.ssa.seal_all_ebb_header_blocks(self.func);
         self.handle_ssa_side_effects(side_effects);
     }
@@ -373,6 +429,11 @@ where
         self.func.create_heap(data)
     }
 
+    /// Declares a table accessible to the function.
+    pub fn create_table(&mut self, data: TableData) -> Table {
+        self.func.create_table(data)
+    }
+
     /// Returns an object with the [`InstBuilder`](../cretonne/ir/builder/trait.InstBuilder.html)
     /// trait that allows to conveniently append an instruction to the current `Ebb` being built.
     pub fn ins<'short>(&'short mut self) -> FuncInstBuilder<'short, 'a, Variable> {
@@ -436,20 +497,23 @@ where
     /// Declare that translation of the current function is complete. This
     /// resets the state of the `FunctionBuilder` in preparation to be used
     /// for another function.
+    ///
+    /// In debug builds, this panics with a list of the incomplete EBBs if any EBB was left
+    /// unfilled or unsealed. Use `incomplete_ebbs` to check the function without panicking.
     pub fn finalize(&mut self) {
         // Check that all the `Ebb`s are filled and sealed.
-        debug_assert!(
-            self.func_ctx.ebbs.keys().all(|ebb| {
-                self.func_ctx.ebbs[ebb].pristine || self.func_ctx.ssa.is_sealed(ebb)
-            }),
-            "all blocks should be sealed before dropping a FunctionBuilder"
-        );
-        debug_assert!(
-            self.func_ctx.ebbs.keys().all(|ebb| {
-                self.func_ctx.ebbs[ebb].pristine || self.func_ctx.ebbs[ebb].filled
-            }),
-            "all blocks should be filled before dropping a FunctionBuilder"
-        );
+        if cfg!(debug_assertions) {
+            let incomplete = self.incomplete_ebbs();
+            if !incomplete.is_empty() {
+                let mut msg = String::from(
+                    "all blocks should be filled and sealed before dropping a FunctionBuilder:",
+                );
+                for ebb in incomplete {
+                    msg.push_str(&format!("\n    {}", ebb));
+                }
+                panic!("{}", msg);
+            }
+        }
 
         // Clear the state (but preserve the allocated buffers) in preparation
         // for translation another function.
@@ -545,6 +609,32 @@ where
         self.func_ctx.ebbs[self.position.ebb.unwrap()].filled
     }
 
+    /// Returns the EBBs that are not yet filled or sealed.
+    ///
+    /// An EBB is incomplete if instructions have been added to it or branches to it have been
+    /// created, and it either has no terminator instruction or hasn't been sealed. The function
+    /// can only be finalized when this list is empty.
+    pub fn incomplete_ebbs(&self) -> Vec<IncompleteEbb> {
+        let mut incomplete = Vec::new();
+        for ebb in self.func_ctx.ebbs.keys() {
+            let data = &self.func_ctx.ebbs[ebb];
+            if data.pristine && self.func_ctx.ssa.predecessors(ebb).is_empty() {
+                continue;
+            }
+            let sealed = self.func_ctx.ssa.is_sealed(ebb);
+            if data.filled && sealed {
+                continue;
+            }
+            incomplete.push(IncompleteEbb {
+                ebb,
+                created_at: data.created_at,
+                filled_at: if data.filled { Some(data.filled_at) } else { None },
+                sealed_at: if sealed { Some(data.sealed_at) } else { None },
+            });
+        }
+        incomplete
+    }
+
     /// Returns a displayable object for the function as it is.
     ///
     /// Useful for debug purposes. Use it with `None` for standard printing.
@@ -555,6 +645,97 @@ where
     }
 }
 
+/// Helpers for lowering aggregates like structs and arrays to memory operations.
+///
+/// An aggregate lives in memory and is accessed through its base address. The field accessors
+/// take the known alignment of the base address so they can mark naturally aligned accesses as
+/// `aligned`.
+impl<'a, Variable> FunctionBuilder<'a, Variable>
+where
+    Variable: EntityRef,
+{
+    /// Creates an explicit stack slot for an aggregate of `size` bytes and returns it along with
+    /// its address of type `addr_ty`.
+    ///
+    /// The address computation is inserted at the current position.
+    pub fn create_aggregate_slot(&mut self, size: u32, addr_ty: Type) -> (StackSlot, Value) {
+        let ss = self.create_stack_slot(
+            StackSlotData::new(StackSlotKind::ExplicitSlot, size),
+        );
+        let addr = self.ins().stack_addr(addr_ty, ss, 0);
+        (ss, addr)
+    }
+
+    /// Loads a field of type `ty` at `offset` bytes into the aggregate at `base`.
+    ///
+    /// The address `base` is known to be aligned to `base_align` bytes.
+    pub fn load_field(&mut self, ty: Type, base: Value, offset: i32, base_align: u32) -> Value {
+        let flags = field_flags(ty, offset, base_align);
+        self.ins().load(ty, flags, base, offset)
+    }
+
+    /// Stores `val` to the field at `offset` bytes into the aggregate at `base`.
+    ///
+    /// The address `base` is known to be aligned to `base_align` bytes.
+    pub fn store_field(&mut self, val: Value, base: Value, offset: i32, base_align: u32) -> Inst {
+        let ty = self.func.dfg.value_type(val);
+        let flags = field_flags(ty, offset, base_align);
+        self.ins().store(flags, val, base, offset)
+    }
+
+    /// Copies an aggregate of `size` bytes from `src` to `dest` by calling the `Memcpy` library
+    /// routine.
+    ///
+    /// The addresses and the size are passed as values of type `addr_ty`. The two aggregates must
+    /// not overlap.
+    pub fn emit_aggregate_copy(&mut self, dest: Value, src: Value, size: u64, addr_ty: Type) {
+        let memcpy = self.memcpy_funcref(addr_ty);
+        let size = self.ins().iconst(addr_ty, size as i64);
+        self.ins().call(memcpy, &[dest, src, size]);
+    }
+
+    /// Get a function reference for the `Memcpy` library routine, importing it if needed.
+    fn memcpy_funcref(&mut self, addr_ty: Type) -> FuncRef {
+        let name = ExternalName::LibCall(LibCall::Memcpy);
+        if let Some(fref) = self.func.dfg.ext_funcs.keys().find(|&fref| {
+            self.func.dfg.ext_funcs[fref].name == name
+        })
+        {
+            return fref;
+        }
+
+        let mut sig = Signature::new(CallConv::Native);
+        for _ in 0..3 {
+            sig.params.push(AbiParam::new(addr_ty));
+        }
+        let signature = self.import_signature(sig);
+        self.import_function(ExtFuncData {
+            name,
+            signature,
+            noreturn: false,
+            cold: false,
+            colocated: false,
+        })
+    }
+}
+
+/// Get the memory flags for accessing a field of type `ty` at `offset` bytes from a base address
+/// aligned to `base_align` bytes.
+fn field_flags(ty: Type, offset: i32, base_align: u32) -> MemFlags {
+    let mut flags = MemFlags::new();
+    // The alignment of the field address is the largest power of two dividing both the base
+    // alignment and the offset.
+    let align = if offset == 0 {
+        base_align
+    } else {
+        base_align.min(1 << offset.trailing_zeros())
+    };
+    if align >= ty.bytes() {
+        flags.set_aligned();
+    }
+    flags
+}
+
 // Helper functions
 impl<'a, Variable> FunctionBuilder<'a, Variable>
 where
@@ -567,7 +748,9 @@ where
     }
 
     fn fill_current_block(&mut self) {
-        self.func_ctx.ebbs[self.position.ebb.unwrap()].filled = true;
+        let data = &mut self.func_ctx.ebbs[self.position.ebb.unwrap()];
+        data.filled = true;
+        data.filled_at = self.srcloc;
     }
 
     fn declare_successor(&mut self, dest_ebb: Ebb, jump_inst: Inst) {
@@ -592,11 +775,14 @@ where
 mod tests {
 
     use cretonne::entity::EntityRef;
-    use cretonne::ir::{ExternalName, Function, CallConv, Signature, AbiParam, InstBuilder};
+    use cretonne::ir::{ExternalName, Function, CallConv, Signature, AbiParam, InstBuilder,
+                       SourceLoc};
     use cretonne::ir::types::*;
     use frontend::{FunctionBuilderContext, FunctionBuilder};
     use cretonne::verifier::verify_function;
     use cretonne::settings;
+    use std::string::ToString;
+    use std::vec::Vec;
     use Variable;
 
     fn sample_function(lazy_seal: bool) {
@@ -703,4 +889,88 @@ mod tests {
     fn sample_with_lazy_seal() {
         sample_function(true)
     }
+
+    #[test]
+    fn incomplete_ebbs() {
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(
+            ExternalName::testcase("incomplete"),
+            Signature::new(CallConv::Native),
+        );
+        let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+
+        builder.set_srcloc(SourceLoc::new(1));
+        let block0 = builder.create_ebb();
+        let block1 = builder.create_ebb();
+        let block2 = builder.create_ebb();
+
+        builder.switch_to_block(block0);
+        builder.seal_block(block0);
+        builder.set_srcloc(SourceLoc::new(2));
+        let cond = builder.ins().iconst(I32, 0);
+        builder.ins().brz(cond, block1, &[]);
+        builder.ins().jump(block2, &[]);
+
+        builder.switch_to_block(block1);
+        builder.set_srcloc(SourceLoc::new(3));
+        builder.ins().return_(&[]);
+
+        let incomplete = builder.incomplete_ebbs();
+        assert_eq!(
+            incomplete
+                .iter()
+                .map(|ebb| ebb.to_string())
+                .collect::<Vec<_>>(),
+            [
+                "ebb1 created at @0001, filled at @0003, never sealed",
+                "ebb2 created at @0001, never filled, never sealed",
+            ]
+        );
+
+        builder.seal_block(block1);
+        builder.switch_to_block(block2);
+        builder.seal_block(block2);
+        builder.ins().return_(&[]);
+        assert!(builder.incomplete_ebbs().is_empty());
+        builder.finalize();
+    }
+
+    #[test]
+    fn aggregates() {
+        let mut sig = Signature::new(CallConv::Native);
+        sig.params.push(AbiParam::new(I64));
+        sig.returns.push(AbiParam::new(I32));
+
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("aggregates"), sig);
+        {
+            let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+
+            let block0 = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(block0);
+            builder.switch_to_block(block0);
+            builder.seal_block(block0);
+
+            let src = builder.ebb_params(block0)[0];
+            let (_, dest) = builder.create_aggregate_slot(16, I64);
+            builder.emit_aggregate_copy(dest, src, 16, I64);
+            builder.emit_aggregate_copy(src, dest, 16, I64);
+            let field = builder.load_field(I32, dest, 4, 8);
+            let misaligned = builder.load_field(I32, dest, 2, 8);
+            builder.store_field(misaligned, src, 8, 1);
+            builder.ins().return_(&[field]);
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(&settings::builder());
+        if let Err(err) = verify_function(&func, &flags) {
+            panic!("{}{}", func.display(None), err);
+        }
+
+        let text = func.display(None).to_string();
+        assert_eq!(text.matches("%Memcpy").count(), 1);
+        assert!(text.contains("load.i32 aligned v1+4"));
+        assert!(text.contains("load.i32 v1+2"));
+        assert!(text.contains("store v5, v0+8"));
+    }
 }
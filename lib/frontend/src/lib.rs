@@ -136,9 +136,11 @@
 
 extern crate cretonne;
 
-pub use frontend::{FunctionBuilderContext, FunctionBuilder};
+pub use frontend::{FunctionBuilderContext, FunctionBuilder, IncompleteEbb};
+pub use switch::Switch;
 pub use variable::Variable;
 
 mod frontend;
 mod ssa;
+mod switch;
 mod variable;
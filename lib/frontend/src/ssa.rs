@@ -641,10 +641,13 @@ where
                 self.blocks[middle_block].add_predecessor(jump_inst_block, jump_inst);
                 self.mark_ebb_header_block_sealed(middle_block);
                 for old_dest in func.jump_tables[jt].as_mut_slice() {
-                    if old_dest.unwrap() == dest_ebb {
+                    if old_dest.expand() == Some(dest_ebb) {
                         *old_dest = PackedOption::from(middle_ebb);
                     }
                 }
+                if func.jump_tables[jt].default_dest() == Some(dest_ebb) {
+                    func.jump_tables[jt].set_default_dest(Some(middle_ebb));
+                }
                 let mut cur = FuncCursor::new(func).at_bottom(middle_ebb);
                 let middle_jump_inst = cur.ins().jump(dest_ebb, &[val]);
                 self.def_var(var, val, middle_block);
@@ -0,0 +1,294 @@
+//! Lowering of `switch` statements.
+//!
+//! The `br_table` instruction only handles dense, zero-based indices. A `Switch` accepts arbitrary
+//! case values and lowers them to a mix of jump tables for the dense clusters of cases and
+//! comparisons for the sparse ones. The clusters are searched with a binary tree of unsigned
+//! comparisons.
+
+use cretonne::entity::EntityRef;
+use cretonne::ir::{Ebb, InstBuilder, JumpTableData, Value};
+use cretonne::ir::condcodes::IntCC;
+use frontend::FunctionBuilder;
+use std::collections::BTreeMap;
+use std::vec::Vec;
+
+/// Minimum number of cases in a cluster before it is lowered as a jump table.
+const MIN_JUMP_TABLE_CASES: usize = 4;
+
+/// Maximum number of clusters that are tested in sequence instead of with a binary search.
+const MAX_LINEAR_CLUSTERS: usize = 3;
+
+/// Builder for the lowering of a `switch` statement.
+///
+/// ```rust
+/// # extern crate cretonne;
+/// # extern crate cton_frontend;
+/// # use cretonne::ir::{Ebb, Value};
+/// # use cretonne::entity::EntityRef;
+/// # use cton_frontend::{FunctionBuilder, Switch};
+/// # fn lower<V: EntityRef>(builder: &mut FunctionBuilder<V>, val: Value, ebbs: &[Ebb; 3]) {
+/// let mut switch = Switch::new();
+/// switch.set_entry(1, ebbs[0]);
+/// switch.set_entry(1000, ebbs[1]);
+/// switch.emit(builder, val, ebbs[2]);
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Debug, Default)]
+pub struct Switch {
+    cases: BTreeMap<u64, Ebb>,
+}
+
+/// A sequence of cases that are lowered together.
+struct Cluster {
+    /// The cases in increasing order.
+    cases: Vec<(u64, Ebb)>,
+}
+
+impl Cluster {
+    fn first(&self) -> u64 {
+        self.cases[0].0
+    }
+
+    fn last(&self) -> u64 {
+        self.cases[self.cases.len() - 1].0
+    }
+
+    /// Can `value` be added to this cluster while keeping at least half of the table entries
+    /// used?
+    fn accepts(&self, value: u64) -> bool {
+        let count = self.cases.len() as u64 + 1;
+        value - self.first() < count.saturating_mul(2)
+    }
+
+    fn is_jump_table(&self) -> bool {
+        self.cases.len() >= MIN_JUMP_TABLE_CASES
+    }
+}
+
+impl Switch {
+    /// Create a new empty switch.
+    pub fn new() -> Self {
+        Self { cases: BTreeMap::new() }
+    }
+
+    /// Set the destination of the case `index`.
+    ///
+    /// Case values are interpreted as unsigned integers of the type of the value being switched
+    /// on.
+    pub fn set_entry(&mut self, index: u64, ebb: Ebb) {
+        let prev = self.cases.insert(index, ebb);
+        debug_assert!(
+            prev.is_none() || prev == Some(ebb),
+            "switch case {} set to both {} and {}",
+            index,
+            prev.unwrap(),
+            ebb
+        );
+    }
+
+    /// Get the cases that have been set so far, in increasing order.
+    pub fn entries(&self) -> &BTreeMap<u64, Ebb> {
+        &self.cases
+    }
+
+    /// Emit the code for the switch at the current position of `builder`.
+    ///
+    /// Control is transferred to the EBB of the case matching `val`, or to `otherwise` if no case
+    /// matches. The current EBB is filled when this returns, and the EBBs created for the search
+    /// tree are sealed. The case EBBs and `otherwise` must be sealed by the caller as usual.
+    pub fn emit<Variable>(self, builder: &mut FunctionBuilder<Variable>, val: Value, otherwise: Ebb)
+    where
+        Variable: EntityRef,
+    {
+        let clusters = self.build_clusters();
+        emit_clusters(builder, val, otherwise, &clusters);
+    }
+
+    /// Group the cases into clusters that are dense enough for a jump table.
+    fn build_clusters(self) -> Vec<Cluster> {
+        let mut clusters: Vec<Cluster> = Vec::new();
+        for (value, ebb) in self.cases {
+            if let Some(cluster) = clusters.last_mut() {
+                if cluster.accepts(value) {
+                    cluster.cases.push((value, ebb));
+                    continue;
+                }
+            }
+            clusters.push(Cluster { cases: vec![(value, ebb)] });
+        }
+
+        // Clusters that are too small for a jump table are tested case by case, so split them up
+        // to give the search tree more freedom.
+        let mut split = Vec::with_capacity(clusters.len());
+        for cluster in clusters {
+            if cluster.is_jump_table() {
+                split.push(cluster);
+            } else {
+                split.extend(cluster.cases.into_iter().map(
+                    |case| Cluster { cases: vec![case] },
+                ));
+            }
+        }
+        split
+    }
+}
+
+/// Emit a search for `val` among `clusters`, branching to `otherwise` when it isn't found.
+fn emit_clusters<Variable>(
+    builder: &mut FunctionBuilder<Variable>,
+    val: Value,
+    otherwise: Ebb,
+    clusters: &[Cluster],
+) where
+    Variable: EntityRef,
+{
+    if clusters.len() > MAX_LINEAR_CLUSTERS {
+        // Split the clusters in two and branch to the upper half when `val` is large enough.
+        let mid = clusters.len() / 2;
+        let upper = builder.create_ebb();
+        let cond = builder.ins().icmp_imm(
+            IntCC::UnsignedGreaterThanOrEqual,
+            val,
+            clusters[mid].first() as i64,
+        );
+        builder.ins().brnz(cond, upper, &[]);
+        emit_clusters(builder, val, otherwise, &clusters[..mid]);
+
+        builder.switch_to_block(upper);
+        builder.seal_block(upper);
+        emit_clusters(builder, val, otherwise, &clusters[mid..]);
+        return;
+    }
+
+    for cluster in clusters {
+        if cluster.is_jump_table() {
+            emit_jump_table(builder, val, otherwise, cluster);
+        } else {
+            for &(value, ebb) in &cluster.cases {
+                let cond = builder.ins().icmp_imm(IntCC::Equal, val, value as i64);
+                builder.ins().brnz(cond, ebb, &[]);
+            }
+        }
+    }
+    builder.ins().jump(otherwise, &[]);
+}
+
+/// Emit a `br_table` for the cases in `cluster`.
+///
+/// Holes in the table branch to `otherwise`, and values outside the table fall through.
+fn emit_jump_table<Variable>(
+    builder: &mut FunctionBuilder<Variable>,
+    val: Value,
+    otherwise: Ebb,
+    cluster: &Cluster,
+) where
+    Variable: EntityRef,
+{
+    let first = cluster.first();
+    let span = cluster.last() - first + 1;
+    let mut data = JumpTableData::with_capacity(span as usize);
+    let mut cases = cluster.cases.iter().peekable();
+    for offset in 0..span {
+        let index = first.wrapping_add(offset);
+        match cases.peek() {
+            Some(&&(value, ebb)) if value == index => {
+                data.push_entry(ebb);
+                cases.next();
+            }
+            _ => data.push_entry(otherwise),
+        }
+    }
+    let jt = builder.create_jump_table(data);
+
+    let index = if first == 0 {
+        val
+    } else {
+        builder.ins().iadd_imm(val, (first as i64).wrapping_neg())
+    };
+    builder.ins().br_table(index, jt);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Switch;
+    use cretonne::ir::{ExternalName, Function, CallConv, Signature, AbiParam, InstBuilder};
+    use cretonne::ir::types::*;
+    use cretonne::settings;
+    use cretonne::verifier::verify_function;
+    use frontend::{FunctionBuilderContext, FunctionBuilder};
+    use std::string::{String, ToString};
+    use Variable;
+
+    /// Build a function switching on its `i64` argument with the given case values, each going to
+    /// its own EBB. Return the text of the function.
+    fn switch_function(values: &[u64]) -> String {
+        let mut sig = Signature::new(CallConv::Native);
+        sig.params.push(AbiParam::new(I64));
+
+        let mut fn_ctx = FunctionBuilderContext::<Variable>::new();
+        let mut func = Function::with_name_signature(ExternalName::testcase("switch"), sig);
+        {
+            let mut builder = FunctionBuilder::<Variable>::new(&mut func, &mut fn_ctx);
+            let entry = builder.create_ebb();
+            let otherwise = builder.create_ebb();
+            builder.append_ebb_params_for_function_params(entry);
+            builder.switch_to_block(entry);
+            builder.seal_block(entry);
+            let val = builder.ebb_params(entry)[0];
+
+            let mut switch = Switch::new();
+            let mut dests = Vec::new();
+            for &value in values {
+                let ebb = builder.create_ebb();
+                switch.set_entry(value, ebb);
+                dests.push(ebb);
+            }
+            switch.emit(&mut builder, val, otherwise);
+
+            for ebb in dests.into_iter().chain(Some(otherwise)) {
+                builder.switch_to_block(ebb);
+                builder.seal_block(ebb);
+                builder.ins().return_(&[]);
+            }
+            builder.finalize();
+        }
+
+        let flags = settings::Flags::new(&settings::builder());
+        if let Err(err) = verify_function(&func, &flags) {
+            panic!("{}{}", func.display(None), err);
+        }
+        func.display(None).to_string()
+    }
+
+    #[test]
+    fn sparse() {
+        let text = switch_function(&[7, 1000]);
+        assert!(!text.contains("br_table"));
+        assert!(text.contains("icmp_imm eq v0, 7"));
+        assert!(text.contains("icmp_imm eq v0, 1000"));
+    }
+
+    #[test]
+    fn dense() {
+        let text = switch_function(&[10, 11, 13, 14]);
+        assert!(text.contains("jt0 = jump_table ebb2, ebb3, ebb1, ebb4, ebb5"));
+        assert!(text.contains("iadd_imm v0, -10"));
+        assert!(!text.contains("icmp_imm"));
+    }
+
+    #[test]
+    fn mixed() {
+        let text = switch_function(&[0, 1, 2, 3, 100, 200, 300, 1 << 40]);
+        assert!(text.contains("jt0 = jump_table ebb2, ebb3, ebb4, ebb5"));
+        assert!(text.contains("br_table v0, jt0"));
+        assert!(text.contains("icmp_imm uge v0, 200"));
+        assert!(text.contains("eq v0, 0x0100_0000_0000"));
+    }
+
+    #[test]
+    fn extremes() {
+        let text = switch_function(&[0, u64::max_value()]);
+        assert!(text.contains("eq v0, -1"));
+    }
+}
@@ -12,6 +12,7 @@ extern crate raw_cpuid;
 
 use cretonne::isa;
 use cretonne::settings::{self, Configurable};
+use std::env;
 
 #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
 use raw_cpuid::CpuId;
@@ -20,28 +21,12 @@ use raw_cpuid::CpuId;
 /// machine, or `Err(())` if the host machine is not supported
 /// in the current configuration.
 pub fn builders() -> Result<(settings::Builder, isa::Builder), &'static str> {
-    let mut flag_builder = settings::builder();
-
     // TODO: Add RISC-V support once Rust supports it.
-
-    if cfg!(target_pointer_width = "64") {
-        flag_builder.enable("is_64bit").unwrap();
-    }
-
-    let name = if cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
-        "intel"
-    } else if cfg!(target_arch = "arm") {
-        "arm32"
-    } else if cfg!(target_arch = "aarch64") {
-        "arm64"
-    } else {
-        return Err("unrecognized architecture");
-    };
-
-    let mut isa_builder = isa::lookup(name).map_err(|err| match err {
-        isa::LookupError::Unknown => panic!(),
-        isa::LookupError::Unsupported => "unsupported architecture",
-    })?;
+    let (flag_builder, mut isa_builder) =
+        isa::lookup_by_triple(env::consts::ARCH).map_err(|err| match err {
+            isa::LookupError::Unknown => "unrecognized architecture",
+            isa::LookupError::Unsupported => "unsupported architecture",
+        })?;
 
     if cfg!(any(target_arch = "x86", target_arch = "x86_64")) {
         parse_x86_cpuid(&mut isa_builder)?;
@@ -61,6 +46,9 @@ fn parse_x86_cpuid(isa_builder: &mut isa::Builder) -> Result<(), &'static str> {
         if info.has_sse3() {
             isa_builder.enable("has_sse3").unwrap();
         }
+        if info.has_ssse3() {
+            isa_builder.enable("has_ssse3").unwrap();
+        }
         if info.has_sse41() {
             isa_builder.enable("has_sse41").unwrap();
         }
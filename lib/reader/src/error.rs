@@ -11,6 +11,9 @@ pub struct Location {
     /// Line number. Command-line arguments are line 0 and source file
     /// lines start from 1.
     pub line_number: usize,
+    /// The file containing the location when it isn't the file being parsed. This is an index
+    /// into `TestFile::includes`, the list of files read by `include` directives.
+    pub include: Option<usize>,
 }
 
 /// A parse error is returned when the parse failed.
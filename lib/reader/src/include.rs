@@ -0,0 +1,58 @@
+//! Support for the `include` directive.
+//!
+//! A function preamble can contain an `include "file"` directive which reads more preamble
+//! declarations from another file. This lets many test cases share the same signature, global
+//! variable, and heap declarations.
+//!
+//! The included file can contain declarations, comments, and more `include` directives, but no
+//! functions or test commands. An included file is not a test case by itself, so it should not
+//! have a `.cton` extension when it lives in a directory of test cases.
+
+use std::path::{Path, PathBuf};
+
+/// Configuration for finding the files named by `include` directives.
+#[derive(Clone, Debug, Default)]
+pub struct IncludeOptions {
+    /// Directory of the file being parsed. Relative file names are looked up here first.
+    ///
+    /// When this is `None`, relative file names are resolved from the current directory instead.
+    pub dir: Option<PathBuf>,
+
+    /// Additional directories to search for relative file names, in order.
+    pub paths: Vec<PathBuf>,
+}
+
+impl IncludeOptions {
+    /// Create include options for parsing the file at `path`.
+    pub fn for_file<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            dir: path.as_ref().parent().map(Path::to_path_buf),
+            paths: Vec::new(),
+        }
+    }
+
+    /// Find the file named `name` by an `include` directive in a file in `dir`.
+    ///
+    /// Return `None` if no such file exists in the search path.
+    pub fn find(&self, name: &str, dir: Option<&Path>) -> Option<PathBuf> {
+        let name = Path::new(name);
+        if name.is_absolute() {
+            return if name.is_file() {
+                Some(name.to_path_buf())
+            } else {
+                None
+            };
+        }
+
+        let first = match dir {
+            Some(dir) => dir.join(name),
+            None => name.to_path_buf(),
+        };
+        if first.is_file() {
+            return Some(first);
+        }
+        self.paths.iter().map(|p| p.join(name)).find(
+            |p| p.is_file(),
+        )
+    }
+}
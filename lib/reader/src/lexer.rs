@@ -35,11 +35,14 @@ pub enum Token<'a> {
     StackSlot(u32), // ss3
     GlobalVar(u32), // gv3
     Heap(u32), // heap2
+    Table(u32), // table2
     JumpTable(u32), // jt2
     FuncRef(u32), // fn2
     SigRef(u32), // sig2
     UserRef(u32), // u345
     Name(&'a str), // %9arbitrary_alphanum, %x3, %0, %function ...
+    QuotedName(&'a str), // %"_ZN3foo3barE", %"a \"quoted\" name\0a" with escapes unprocessed
+    String(&'a str), // "common.inc" with escapes unprocessed
     HexSequence(&'a str), // #89AF
     Identifier(&'a str), // Unrecognized identifier (opcode, enumerator, ...)
     SourceLoc(&'a str), // @00c7
@@ -64,6 +67,7 @@ fn token(token: Token, loc: Location) -> Result<LocatedToken, LocatedError> {
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Error {
     InvalidChar,
+    UnterminatedString,
 }
 
 /// An `Error` with an associated Location.
@@ -161,7 +165,10 @@ impl<'a> Lexer<'a> {
 
     // Get the location corresponding to `lookahead`.
     fn loc(&self) -> Location {
-        Location { line_number: self.line_number }
+        Location {
+            line_number: self.line_number,
+            include: None,
+        }
     }
 
     // Starting from `lookahead`, are we looking at `prefix`?
@@ -318,6 +325,7 @@ impl<'a> Lexer<'a> {
             "ss" => Some(Token::StackSlot(number)),
             "gv" => Some(Token::GlobalVar(number)),
             "heap" => Some(Token::Heap(number)),
+            "table" => Some(Token::Table(number)),
             "jt" => Some(Token::JumpTable(number)),
             "fn" => Some(Token::FuncRef(number)),
             "sig" => Some(Token::SigRef(number)),
@@ -365,16 +373,57 @@ impl<'a> Lexer<'a> {
 
         assert_eq!(self.lookahead, Some('%'));
 
-        while let Some(c) = self.next_ch() {
+        if self.next_ch() == Some('"') {
+            return match self.scan_quoted() {
+                Some(text) => token(Token::QuotedName(text), loc),
+                None => error(Error::UnterminatedString, loc),
+            };
+        }
+
+        while let Some(c) = self.lookahead {
             if !(c.is_ascii() && c.is_alphanumeric() || c == '_') {
                 break;
             }
+            self.next_ch();
         }
 
         let end = self.pos;
         token(Token::Name(&self.source[begin..end]), loc)
     }
 
+    // Scan a quoted string starting at the opening quote, and return the text between the quotes.
+    // A backslash escapes the following character, so `\"` doesn't end the string. Return `None`
+    // if the string doesn't end on the same line.
+    fn scan_quoted(&mut self) -> Option<&'a str> {
+        assert_eq!(self.lookahead, Some('"'));
+        let begin = self.pos + 1;
+
+        loop {
+            match self.next_ch() {
+                Some('"') => break,
+                Some('\\') => {
+                    if let None | Some('\n') = self.next_ch() {
+                        return None;
+                    }
+                }
+                None | Some('\n') => return None,
+                Some(_) => {}
+            }
+        }
+
+        let end = self.pos;
+        self.next_ch();
+        Some(&self.source[begin..end])
+    }
+
+    fn scan_string(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
+        let loc = self.loc();
+        match self.scan_quoted() {
+            Some(text) => token(Token::String(text), loc),
+            None => error(Error::UnterminatedString, loc),
+        }
+    }
+
     fn scan_hex_sequence(&mut self) -> Result<LocatedToken<'a>, LocatedError> {
         let loc = self.loc();
         let begin = self.pos + 1;
@@ -437,6 +486,7 @@ impl<'a> Lexer<'a> {
                 Some(ch) if ch.is_digit(10) => Some(self.scan_number()),
                 Some(ch) if ch.is_alphabetic() => Some(self.scan_word()),
                 Some('%') => Some(self.scan_name()),
+                Some('"') => Some(self.scan_string()),
                 Some('#') => Some(self.scan_hex_sequence()),
                 Some('@') => Some(self.scan_srcloc()),
                 Some(ch) if ch.is_whitespace() => {
@@ -486,11 +536,23 @@ mod tests {
     }
 
     fn token<'a>(token: Token<'a>, line: usize) -> Option<Result<LocatedToken<'a>, LocatedError>> {
-        Some(super::token(token, Location { line_number: line }))
+        Some(super::token(
+            token,
+            Location {
+                line_number: line,
+                include: None,
+            },
+        ))
     }
 
     fn error<'a>(error: Error, line: usize) -> Option<Result<LocatedToken<'a>, LocatedError>> {
-        Some(super::error(error, Location { line_number: line }))
+        Some(super::error(
+            error,
+            Location {
+                line_number: line,
+                include: None,
+            },
+        ))
     }
 
     #[test]
@@ -607,6 +669,35 @@ mod tests {
         assert_eq!(lex.next(), token(Token::Name("_"), 1));
     }
 
+    #[test]
+    fn lex_quoted_names() {
+        let mut lex = Lexer::new(
+            r#"%"" %"_ZN3foo3barE"(%"a \"b\" \\"
+               %"\0a" %"open
+               %"trailing\"#,
+        );
+
+        assert_eq!(lex.next(), token(Token::QuotedName(""), 1));
+        assert_eq!(lex.next(), token(Token::QuotedName("_ZN3foo3barE"), 1));
+        assert_eq!(lex.next(), token(Token::LPar, 1));
+        assert_eq!(lex.next(), token(Token::QuotedName(r#"a \"b\" \\"#), 1));
+        assert_eq!(lex.next(), token(Token::QuotedName(r"\0a"), 2));
+        assert_eq!(lex.next(), error(Error::UnterminatedString, 2));
+        assert_eq!(lex.next(), error(Error::UnterminatedString, 3));
+        assert_eq!(lex.next(), None);
+    }
+
+    #[test]
+    fn lex_strings() {
+        let mut lex = Lexer::new(r#"include "common.inc" "a\"b" ""#);
+
+        assert_eq!(lex.next(), token(Token::Identifier("include"), 1));
+        assert_eq!(lex.next(), token(Token::String("common.inc"), 1));
+        assert_eq!(lex.next(), token(Token::String(r#"a\"b"#), 1));
+        assert_eq!(lex.next(), error(Error::UnterminatedString, 1));
+        assert_eq!(lex.next(), None);
+    }
+
     #[test]
     fn lex_userrefs() {
         let mut lex = Lexer::new("u0 u1 u234567890 u9:8765");
@@ -10,13 +10,15 @@
 extern crate cretonne;
 
 pub use error::{Location, Result, Error};
-pub use parser::{parse_functions, parse_test};
+pub use parser::{parse_functions, parse_test, parse_test_with_includes};
 pub use testcommand::{TestCommand, TestOption};
 pub use testfile::{TestFile, Details, Comment};
 pub use isaspec::{IsaSpec, parse_options};
 pub use sourcemap::SourceMap;
+pub use include::IncludeOptions;
 
 mod error;
+mod include;
 mod lexer;
 mod parser;
 mod testcommand;
@@ -2,12 +2,16 @@
 
 use std::str::FromStr;
 use std::{u16, u32};
+use std::fs::File;
+use std::io::Read;
 use std::mem;
+use std::time::Instant;
+use std::path::{Path, PathBuf};
 use cretonne::ir::{Function, Ebb, Opcode, Value, Type, ExternalName, CallConv, StackSlotData,
                    StackSlotKind, JumpTable, JumpTableData, Signature, AbiParam,
                    ArgumentExtension, ExtFuncData, SigRef, FuncRef, StackSlot, ValueLoc,
                    ArgumentLoc, MemFlags, GlobalVar, GlobalVarData, Heap, HeapData, HeapStyle,
-                   HeapBase};
+                   HeapBase, Table, TableData, LaneMaskData};
 use cretonne::ir;
 use cretonne::ir::types::VOID;
 use cretonne::ir::immediates::{Imm64, Uimm32, Offset32, Ieee32, Ieee64};
@@ -19,6 +23,7 @@ use cretonne::entity::EntityRef;
 use cretonne::packed_option::ReservedValue;
 use testfile::{TestFile, Details, Comment};
 use error::{Location, Error, Result};
+use include::IncludeOptions;
 use lexer::{self, Lexer, Token};
 use testcommand::TestCommand;
 use isaspec;
@@ -38,8 +43,21 @@ pub fn parse_functions(text: &str) -> Result<Vec<Function>> {
 ///
 /// The returned `TestFile` contains direct references to substrings of `text`.
 pub fn parse_test(text: &str) -> Result<TestFile> {
+    parse_test_with_includes(text, &IncludeOptions::default())
+}
+
+/// Parse the entire `text` as a test case file, reading the files named by `include` directives
+/// as configured by `options`.
+///
+/// The returned `TestFile` contains direct references to substrings of `text`.
+pub fn parse_test_with_includes<'a>(
+    text: &'a str,
+    options: &IncludeOptions,
+) -> Result<TestFile<'a>> {
     let _tt = timing::parse_text();
     let mut parser = Parser::new(text);
+    parser.includes.options = options.clone();
+    parser.dir = options.dir.clone();
     // Gather the preamble comments.
     parser.start_gathering_comments();
 
@@ -57,9 +75,60 @@ pub fn parse_test(text: &str) -> Result<TestFile> {
         isa_spec,
         preamble_comments,
         functions,
+        includes: parser.includes.files,
     })
 }
 
+/// Find the ISA names in the `; targets: isa...` annotations among the comments of a function.
+///
+/// Only the comments attached to the function itself count, that is the comments before the
+/// preamble and after the closing brace. This is where the filecheck directives go too. Errors are
+/// reported at `loc`, the location of the function.
+fn parse_targets<'a>(comments: &[Comment<'a>], loc: &Location) -> Result<Vec<&'a str>> {
+    let mut targets = Vec::new();
+    for comment in comments {
+        if comment.entity != AnyEntity::Function {
+            continue;
+        }
+        let text = comment.text.trim_left_matches(';').trim();
+        if !text.starts_with("targets:") {
+            continue;
+        }
+        for name in text["targets:".len()..].split_whitespace() {
+            if let Err(isa::LookupError::Unknown) = isa::lookup(name) {
+                return err!(loc, "unknown ISA '{}' in targets", name);
+            }
+            targets.push(name);
+        }
+    }
+    Ok(targets)
+}
+
+/// Decode the escapes in the text of a quoted name or string.
+///
+/// A backslash is followed by either `"` or `\` standing for itself, or by two hex digits giving a
+/// byte value. Return `None` for any other escape.
+fn unescape(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len());
+    let mut iter = text.bytes();
+    while let Some(b) = iter.next() {
+        if b != b'\\' {
+            bytes.push(b);
+            continue;
+        }
+        match iter.next() {
+            Some(e @ b'"') | Some(e @ b'\\') => bytes.push(e),
+            Some(hi) => {
+                let hi = char::from(hi).to_digit(16)?;
+                let lo = char::from(iter.next()?).to_digit(16)?;
+                bytes.push((hi * 16 + lo) as u8);
+            }
+            None => return None,
+        }
+    }
+    Some(bytes)
+}
+
 pub struct Parser<'a> {
     lex: Lexer<'a>,
 
@@ -79,6 +148,28 @@ pub struct Parser<'a> {
 
     // Comments collected so far.
     comments: Vec<Comment<'a>>,
+
+    // Files read by `include` directives, shared with the parsers of the included files.
+    includes: IncludeState,
+
+    // Index of the file being parsed in `includes.files`, or `None` for the main file.
+    include: Option<usize>,
+
+    // Directory of the file being parsed, if known.
+    dir: Option<PathBuf>,
+}
+
+// State of the `include` directives seen while parsing a file and the files it includes.
+#[derive(Default)]
+struct IncludeState {
+    options: IncludeOptions,
+
+    // Files read so far. This list is indexed by `Location::include`.
+    files: Vec<PathBuf>,
+
+    // Canonical paths of the files currently being included, outermost first. Used to detect
+    // cycles.
+    stack: Vec<PathBuf>,
 }
 
 // Context for resolving references when parsing a single function.
@@ -107,9 +198,7 @@ impl<'a> Context<'a> {
         if let Some(unique_isa) = self.unique_isa {
             unique_isa
                 .encoding_info()
-                .names
-                .iter()
-                .position(|&name| name == recipe_name)
+                .find_recipe(recipe_name)
                 .map(|idx| idx as u16)
         } else {
             None
@@ -141,6 +230,7 @@ impl<'a> Context<'a> {
         while self.function.global_vars.next_key().index() <= gv.index() {
             self.function.create_global_var(GlobalVarData::Sym {
                 name: ExternalName::testcase(""),
+                colocated: false,
             });
         }
         self.function.global_vars[gv] = data;
@@ -179,6 +269,29 @@ impl<'a> Context<'a> {
         }
     }
 
+    // Allocate a table slot.
+    fn add_table(&mut self, table: Table, data: TableData, loc: &Location) -> Result<()> {
+        while self.function.tables.next_key().index() <= table.index() {
+            self.function.create_table(TableData {
+                base_gv: GlobalVar::reserved_value(),
+                min_size: Imm64::new(0),
+                bound_gv: GlobalVar::reserved_value(),
+                element_size: Imm64::new(0),
+            });
+        }
+        self.function.tables[table] = data;
+        self.map.def_table(table, loc)
+    }
+
+    // Resolve a reference to a table.
+    fn check_table(&self, table: Table, loc: &Location) -> Result<()> {
+        if !self.map.contains_table(table) {
+            err!(loc, "undefined table {}", table)
+        } else {
+            Ok(())
+        }
+    }
+
     // Allocate a new signature.
     fn add_sig(&mut self, sig: SigRef, data: Signature, loc: &Location) -> Result<()> {
         while self.function.dfg.signatures.next_key().index() <= sig.index() {
@@ -205,6 +318,9 @@ impl<'a> Context<'a> {
             self.function.import_function(ExtFuncData {
                 name: ExternalName::testcase(""),
                 signature: SigRef::reserved_value(),
+                noreturn: false,
+                cold: false,
+                colocated: false,
             });
         }
         self.function.dfg.ext_funcs[fn_] = data;
@@ -255,10 +371,13 @@ impl<'a> Parser<'a> {
             lex: Lexer::new(text),
             lex_error: None,
             lookahead: None,
-            loc: Location { line_number: 0 },
+            loc: Location::default(),
             gathering_comments: false,
             gathered_comments: Vec::new(),
             comments: Vec::new(),
+            includes: IncludeState::default(),
+            include: None,
+            dir: None,
         }
     }
 
@@ -292,11 +411,11 @@ impl<'a> Parser<'a> {
                         }
                         _ => self.lookahead = Some(token),
                     }
-                    self.loc = location;
+                    self.loc = Location { include: self.include, ..location };
                 }
                 Some(Err(lexer::LocatedError { error, location })) => {
                     self.lex_error = Some(error);
-                    self.loc = location;
+                    self.loc = Location { include: self.include, ..location };
                     break;
                 }
                 None => break,
@@ -425,6 +544,17 @@ impl<'a> Parser<'a> {
         err!(self.loc, err_msg)
     }
 
+    // Match and consume a table reference.
+    fn match_table(&mut self, err_msg: &str) -> Result<Table> {
+        if let Some(Token::Table(table)) = self.token() {
+            self.consume();
+            if let Some(table) = Table::with_number(table) {
+                return Ok(table);
+            }
+        }
+        err!(self.loc, err_msg)
+    }
+
     // Match and consume a jump table reference.
     fn match_jt(&mut self) -> Result<JumpTable> {
         if let Some(Token::JumpTable(jt)) = self.token() {
@@ -502,6 +632,30 @@ impl<'a> Parser<'a> {
         }
     }
 
+    // Parse a lane mask for a vector shuffle.
+    //
+    // lane-mask ::= "[" [uimm8 {"," uimm8}] "]"
+    fn parse_lane_mask(&mut self) -> Result<LaneMaskData> {
+        self.match_token(
+            Token::LBracket,
+            "expected '[' before lane mask",
+        )?;
+        let mut mask = LaneMaskData::new();
+        if !self.optional(Token::RBracket) {
+            loop {
+                mask.push(self.match_uimm8("expected lane index")?);
+                if !self.optional(Token::Comma) {
+                    break;
+                }
+            }
+            self.match_token(
+                Token::RBracket,
+                "expected ']' after lane mask",
+            )?;
+        }
+        Ok(mask)
+    }
+
     // Match and consume an i32 immediate.
     // This is used for stack argument byte offsets.
     fn match_imm32(&mut self, err_msg: &str) -> Result<i32> {
@@ -747,14 +901,19 @@ impl<'a> Parser<'a> {
         while self.token().is_some() {
             list.push(self.parse_function(unique_isa)?);
         }
-        if let Some(err) = self.lex_error {
-            return match err {
-                lexer::Error::InvalidChar => err!(self.loc, "invalid character"),
-            };
-        }
+        self.check_lex_error()?;
         Ok(list)
     }
 
+    // Report the lexical error that ended the token stream, if any.
+    fn check_lex_error(&self) -> Result<()> {
+        match self.lex_error {
+            None => Ok(()),
+            Some(lexer::Error::InvalidChar) => err!(self.loc, "invalid character"),
+            Some(lexer::Error::UnterminatedString) => err!(self.loc, "unterminated string"),
+        }
+    }
+
     // Parse a whole function definition.
     //
     // function ::= * function-spec "{" preamble function-body "}"
@@ -763,6 +922,8 @@ impl<'a> Parser<'a> {
         &mut self,
         unique_isa: Option<&TargetIsa>,
     ) -> Result<(Function, Details<'a>)> {
+        let started = Instant::now();
+
         // Begin gathering comments.
         // Make sure we don't include any comments before the `function` keyword.
         self.token();
@@ -796,10 +957,14 @@ impl<'a> Parser<'a> {
         self.token();
         self.claim_gathered_comments(AnyEntity::Function);
 
+        let comments = self.take_comments();
+        let targets = parse_targets(&comments, &location)?;
         let details = Details {
             location,
-            comments: self.take_comments(),
+            comments,
             map: ctx.map,
+            targets,
+            parse_time: started.elapsed(),
         };
 
         Ok((ctx.function, details))
@@ -839,6 +1004,13 @@ impl<'a> Parser<'a> {
                     |_| self.error("invalid test case or libcall name"),
                 )
             }
+            Some(Token::QuotedName(s)) => {
+                let bytes = unescape(s).ok_or_else(
+                    || self.error("invalid escape in quoted name"),
+                )?;
+                self.consume();
+                Ok(ExternalName::Symbol(bytes))
+            }
             Some(Token::UserRef(namespace)) => {
                 self.consume();
                 match self.token() {
@@ -1019,6 +1191,12 @@ impl<'a> Parser<'a> {
                         ctx.add_heap(heap, dat, &self.loc)
                     })
                 }
+                Some(Token::Table(..)) => {
+                    self.start_gathering_comments();
+                    self.parse_table_decl().and_then(|(table, dat)| {
+                        ctx.add_table(table, dat, &self.loc)
+                    })
+                }
                 Some(Token::SigRef(..)) => {
                     self.start_gathering_comments();
                     self.parse_signature_decl(ctx.unique_isa).and_then(
@@ -1039,12 +1217,80 @@ impl<'a> Parser<'a> {
                         ctx.add_jt(jt, dat, &self.loc)
                     })
                 }
+                Some(Token::Identifier("include")) => self.parse_include(ctx),
                 // More to come..
                 _ => return Ok(()),
             }?;
         }
     }
 
+    // Parse an include directive, and add the declarations in the included file to `ctx`.
+    //
+    // include ::= * "include" String(file)
+    fn parse_include(&mut self, ctx: &mut Context) -> Result<()> {
+        self.match_identifier("include", "expected 'include'")?;
+        let loc = self.loc;
+        let name = match self.token() {
+            Some(Token::String(s)) => {
+                let bytes = unescape(s).ok_or_else(
+                    || self.error("invalid escape in file name"),
+                )?;
+                String::from_utf8(bytes).map_err(
+                    |_| self.error("file name is not valid UTF-8"),
+                )?
+            }
+            _ => return err!(self.loc, "expected quoted file name after 'include'"),
+        };
+        self.consume();
+
+        let path = match self.includes.options.find(
+            &name,
+            self.dir.as_ref().map(PathBuf::as_path),
+        ) {
+            Some(path) => path,
+            None => return err!(loc, "can't find included file \"{}\"", name),
+        };
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.clone());
+        if self.includes.stack.contains(&canonical) {
+            return err!(loc, "{} is included recursively", path.display());
+        }
+        let mut text = String::new();
+        File::open(&path)
+            .and_then(|mut file| file.read_to_string(&mut text))
+            .or_else(|e| err!(loc, "{}: {}", path.display(), e))?;
+
+        // Parse the included file with its own parser, sharing the include state.
+        let index = self.includes.files.len();
+        self.includes.files.push(path.clone());
+        self.includes.stack.push(canonical);
+        let mut parser = Parser::new(&text);
+        parser.includes = mem::replace(&mut self.includes, IncludeState::default());
+        parser.include = Some(index);
+        parser.dir = path.parent().map(Path::to_path_buf);
+        let result = parser.parse_included_preamble(ctx);
+        self.includes = parser.includes;
+        self.includes.stack.pop();
+
+        result.or_else(|e| {
+            err!(
+                loc,
+                "{}:{}: {}",
+                path.display(),
+                e.location.line_number,
+                e.message
+            )
+        })
+    }
+
+    // Parse the contents of an included file. It can only contain preamble declarations.
+    fn parse_included_preamble(&mut self, ctx: &mut Context) -> Result<()> {
+        self.parse_preamble(ctx)?;
+        if self.token().is_some() {
+            return err!(self.loc, "expected preamble declaration in included file");
+        }
+        self.check_lex_error()
+    }
+
     // Parse a stack slot decl.
     //
     // stack-slot-decl ::= * StackSlot(ss) "=" stack-slot-kind Bytes {"," stack-slot-flag}
@@ -1092,7 +1338,10 @@ impl<'a> Parser<'a> {
     // global-var-decl ::= * GlobalVar(gv) "=" global-var-desc
     // global-var-desc ::= "vmctx" offset32
     //                   | "deref" "(" GlobalVar(base) ")" offset32
-    //                   | "globalsym" name
+    //                   | "heap_base" "(" Heap(heap) ")" offset32
+    //                   | "globalsym" ["colocated"] name
+    //                   | "gotsym" name
+    //                   | "tlssym" tls-model name
     //
     fn parse_global_var_decl(&mut self) -> Result<(GlobalVar, GlobalVarData)> {
         let gv = self.match_gv("expected global variable number: gv«n»")?;
@@ -1120,9 +1369,34 @@ impl<'a> Parser<'a> {
                 let offset = self.optional_offset32()?;
                 GlobalVarData::Deref { base, offset }
             }
+            "heap_base" => {
+                self.match_token(
+                    Token::LPar,
+                    "expected '(' in 'heap_base' global variable decl",
+                )?;
+                let heap = self.match_heap("expected heap: heap«n»")?;
+                self.match_token(
+                    Token::RPar,
+                    "expected ')' in 'heap_base' global variable decl",
+                )?;
+                let offset = self.optional_offset32()?;
+                GlobalVarData::HeapBase { heap, offset }
+            }
             "globalsym" => {
+                let colocated = self.optional(Token::Identifier("colocated"));
+                let name = self.parse_external_name()?;
+                GlobalVarData::Sym { name, colocated }
+            }
+            "gotsym" => {
+                let name = self.parse_external_name()?;
+                GlobalVarData::GotSym { name }
+            }
+            "tlssym" => {
+                let model = self.match_any_identifier("expected TLS model")?
+                    .parse()
+                    .map_err(|_| self.error("unknown TLS model"))?;
                 let name = self.parse_external_name()?;
-                GlobalVarData::Sym { name }
+                GlobalVarData::TlsSym { name, model }
             }
             other => return err!(self.loc, "Unknown global variable kind '{}'", other),
         };
@@ -1208,6 +1482,63 @@ impl<'a> Parser<'a> {
         Ok((heap, data))
     }
 
+    // Parse a table decl.
+    //
+    // table-decl ::= * Table(table) "=" table-desc
+    // table-desc ::= "dynamic" GlobalVar(base) { "," table-attr }
+    // table-attr ::= "min" Imm64(elements)
+    //              | "bound" GlobalVar(elements)
+    //              | "element_size" Imm64(bytes)
+    //
+    fn parse_table_decl(&mut self) -> Result<(Table, TableData)> {
+        let table = self.match_table("expected table number: table«n»")?;
+        self.match_token(
+            Token::Equal,
+            "expected '=' in table declaration",
+        )?;
+
+        match self.match_any_identifier("expected 'dynamic'")? {
+            "dynamic" => {}
+            t => return err!(self.loc, "unknown table style '{}'", t),
+        }
+
+        // table-desc ::= "dynamic" * GlobalVar(base) { "," table-attr }
+        let base_gv = self.match_gv("expected table base global variable")?;
+        let mut bound_gv = None;
+        let mut data = TableData {
+            base_gv,
+            min_size: 0.into(),
+            bound_gv: GlobalVar::reserved_value(),
+            element_size: 0.into(),
+        };
+
+        // table-desc ::= "dynamic" GlobalVar(base) * { "," table-attr }
+        while self.optional(Token::Comma) {
+            match self.match_any_identifier("expected table attribute name")? {
+                "min" => {
+                    data.min_size = self.match_imm64("expected integer min size")?;
+                }
+                "bound" => {
+                    bound_gv = Some(self.match_gv("expected gv bound")?);
+                }
+                "element_size" => {
+                    data.element_size = self.match_imm64("expected integer element size")?;
+                }
+                t => return err!(self.loc, "unknown table attribute '{}'", t),
+            }
+        }
+        data.bound_gv = match bound_gv {
+            Some(gv) => gv,
+            None => return err!(self.loc, "missing bound in declaration of {}", table),
+        };
+
+        // Collect any trailing comments.
+        self.token();
+        self.claim_gathered_comments(table);
+
+        Ok((table, data))
+    }
+
     // Parse a signature decl.
     //
     // signature-decl ::= SigRef(sigref) "=" signature
@@ -1234,8 +1565,9 @@ impl<'a> Parser<'a> {
     //
     // Two variants:
     //
-    // function-decl ::= FuncRef(fnref) "=" function-spec
-    //                   FuncRef(fnref) "=" SigRef(sig) name
+    // function-decl ::= FuncRef(fnref) "=" {fn-attr} function-spec
+    //                   FuncRef(fnref) "=" {fn-attr} SigRef(sig) name
+    // fn-attr       ::= "noreturn" | "cold" | "colocated"
     //
     // The first variant allocates a new signature reference. The second references an existing
     // signature which must be declared first.
@@ -1247,6 +1579,19 @@ impl<'a> Parser<'a> {
             "expected '=' in function decl",
         )?;
 
+        let mut noreturn = false;
+        let mut cold = false;
+        let mut colocated = false;
+        loop {
+            match self.token() {
+                Some(Token::Identifier("noreturn")) => noreturn = true,
+                Some(Token::Identifier("cold")) => cold = true,
+                Some(Token::Identifier("colocated")) => colocated = true,
+                _ => break,
+            }
+            self.consume();
+        }
+
         let data = match self.token() {
             Some(Token::Identifier("function")) => {
                 let (loc, name, sig) = self.parse_function_spec(ctx.unique_isa)?;
@@ -1257,6 +1602,9 @@ impl<'a> Parser<'a> {
                 ExtFuncData {
                     name,
                     signature: sigref,
+                    noreturn,
+                    cold,
+                    colocated,
                 }
             }
             Some(Token::SigRef(sig_src)) => {
@@ -1272,6 +1620,9 @@ impl<'a> Parser<'a> {
                 ExtFuncData {
                     name,
                     signature: sig,
+                    noreturn,
+                    cold,
+                    colocated,
                 }
             }
             _ => return err!(self.loc, "expected 'function' or sig«n» in function decl"),
@@ -1287,6 +1638,8 @@ impl<'a> Parser<'a> {
     // Parse a jump table decl.
     //
     // jump-table-decl ::= * JumpTable(jt) "=" "jump_table" jt-entry {"," jt-entry}
+    //                     {"," jt-attr}
+    // jt-attr ::= "min" Imm64(n) | "default" Ebb(dest)
     fn parse_jump_table_decl(&mut self) -> Result<(JumpTable, JumpTableData)> {
         let jt = self.match_jt()?;
         self.match_token(
@@ -1298,9 +1651,25 @@ impl<'a> Parser<'a> {
         let mut data = JumpTableData::new();
 
         // jump-table-decl ::= JumpTable(jt) "=" "jump_table" * jt-entry {"," jt-entry}
-        for idx in 0_usize.. {
-            if let Some(dest) = self.parse_jump_table_entry()? {
-                data.set_entry(idx, dest);
+        let mut idx = 0_usize;
+        loop {
+            match self.token() {
+                Some(Token::Identifier("min")) => {
+                    self.consume();
+                    let min: i64 = self.match_imm64("expected minimum index")?.into();
+                    data.set_min_index(min);
+                }
+                Some(Token::Identifier("default")) => {
+                    self.consume();
+                    let dest = self.match_ebb("expected default EBB")?;
+                    data.set_default_dest(Some(dest));
+                }
+                _ => {
+                    if let Some(dest) = self.parse_jump_table_entry()? {
+                        data.set_entry(idx, dest);
+                    }
+                    idx += 1;
+                }
             }
             if !self.optional(Token::Comma) {
                 // Collect any trailing comments.
@@ -1310,8 +1679,6 @@ impl<'a> Parser<'a> {
                 return Ok((jt, data));
             }
         }
-
-        err!(self.loc, "jump_table too long")
     }
 
     // jt-entry ::= * Ebb(dest) | "0"
@@ -2047,6 +2414,37 @@ impl<'a> Parser<'a> {
                 let lane = self.match_uimm8("expected lane number")?;
                 InstructionData::ExtractLane { opcode, lane, arg }
             }
+            InstructionFormat::Shuffle => {
+                let lhs = self.match_value("expected SSA value first operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let rhs = self.match_value("expected SSA value second operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let mask = self.parse_lane_mask()?;
+                InstructionData::Shuffle {
+                    opcode,
+                    args: [lhs, rhs],
+                    mask: ctx.function.dfg.lane_masks.push(mask),
+                }
+            }
+            InstructionFormat::Swizzle => {
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let mask = self.parse_lane_mask()?;
+                InstructionData::Swizzle {
+                    opcode,
+                    arg,
+                    mask: ctx.function.dfg.lane_masks.push(mask),
+                }
+            }
             InstructionFormat::IntCompare => {
                 let cond = self.match_enum("expected intcc condition code")?;
                 let lhs = self.match_value("expected SSA value first operand")?;
@@ -2137,6 +2535,30 @@ impl<'a> Parser<'a> {
                     args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
                 }
             }
+            InstructionFormat::TryCall => {
+                let func_ref = self.match_fn("expected function reference")?;
+                ctx.check_fn(func_ref, &self.loc)?;
+                self.match_token(
+                    Token::LPar,
+                    "expected '(' before arguments",
+                )?;
+                let args = self.parse_value_list()?;
+                self.match_token(
+                    Token::RPar,
+                    "expected ')' after arguments",
+                )?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let ebb_num = self.match_ebb("expected landing pad EBB")?;
+                InstructionData::TryCall {
+                    opcode,
+                    func_ref,
+                    destination: ebb_num,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::IndirectCall => {
                 let sig_ref = self.match_sig("expected signature reference")?;
                 ctx.check_sig(sig_ref, &self.loc)?;
@@ -2211,6 +2633,48 @@ impl<'a> Parser<'a> {
                     imm,
                 }
             }
+            InstructionFormat::TableAddr => {
+                let table = self.match_table("expected table identifier")?;
+                ctx.check_table(table, &self.loc)?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let arg = self.match_value("expected SSA value table index")?;
+                let offset = self.optional_offset32()?;
+                InstructionData::TableAddr {
+                    opcode,
+                    table,
+                    arg,
+                    offset,
+                }
+            }
+            InstructionFormat::DebugValue => {
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let var = self.match_uimm32("expected variable number")?;
+                InstructionData::DebugValue { opcode, arg, var }
+            }
+            InstructionFormat::DeoptPoint => {
+                let id = self.match_uimm32("expected deoptimization point number")?;
+                self.match_token(
+                    Token::LPar,
+                    "expected '(' before arguments",
+                )?;
+                let args = self.parse_value_list()?;
+                self.match_token(
+                    Token::RPar,
+                    "expected ')' after arguments",
+                )?;
+                InstructionData::DeoptPoint {
+                    opcode,
+                    id,
+                    args: args.into_value_list(&[], &mut ctx.function.dfg.value_lists),
+                }
+            }
             InstructionFormat::Load => {
                 let flags = self.optional_memflags();
                 let addr = self.match_value("expected SSA value address")?;
@@ -2238,6 +2702,147 @@ impl<'a> Parser<'a> {
                     offset,
                 }
             }
+            InstructionFormat::LoadComplex => {
+                let flags = self.optional_memflags();
+                let base = self.match_value("expected SSA value address")?;
+                let offset = self.optional_offset32()?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let index = self.match_value("expected SSA value index")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let scale = self.match_uimm8("expected index scale")?;
+                InstructionData::LoadComplex {
+                    opcode,
+                    flags,
+                    args: [base, index],
+                    scale,
+                    offset,
+                }
+            }
+            InstructionFormat::StoreComplex => {
+                let flags = self.optional_memflags();
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let base = self.match_value("expected SSA value address")?;
+                let offset = self.optional_offset32()?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let index = self.match_value("expected SSA value index")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let scale = self.match_uimm8("expected index scale")?;
+                InstructionData::StoreComplex {
+                    opcode,
+                    flags,
+                    args: VariableArgs::new().into_value_list(
+                        &[arg, base, index],
+                        &mut ctx.function.dfg.value_lists,
+                    ),
+                    scale,
+                    offset,
+                }
+            }
+            InstructionFormat::AtomicLoad => {
+                let ordering = self.match_enum("expected memory ordering")?;
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                InstructionData::AtomicLoad {
+                    opcode,
+                    ordering,
+                    flags,
+                    arg: addr,
+                }
+            }
+            InstructionFormat::AtomicStore => {
+                let ordering = self.match_enum("expected memory ordering")?;
+                let flags = self.optional_memflags();
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let addr = self.match_value("expected SSA value address")?;
+                InstructionData::AtomicStore {
+                    opcode,
+                    ordering,
+                    flags,
+                    args: [arg, addr],
+                }
+            }
+            InstructionFormat::AtomicRmw => {
+                let op = self.match_enum("expected atomic operation")?;
+                let ordering = self.match_enum("expected memory ordering")?;
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let arg = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicRmw {
+                    opcode,
+                    op,
+                    ordering,
+                    flags,
+                    args: [addr, arg],
+                }
+            }
+            InstructionFormat::AtomicCas => {
+                let ordering = self.match_enum("expected memory ordering")?;
+                let flags = self.optional_memflags();
+                let addr = self.match_value("expected SSA value address")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let expected = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let replacement = self.match_value("expected SSA value operand")?;
+                InstructionData::AtomicCas {
+                    opcode,
+                    ordering,
+                    flags,
+                    args: [addr, expected, replacement],
+                }
+            }
+            InstructionFormat::Fence => {
+                let ordering = self.match_enum("expected memory ordering")?;
+                InstructionData::Fence { opcode, ordering }
+            }
+            InstructionFormat::BulkMemory => {
+                let flags = self.optional_memflags();
+                let dest = self.match_value("expected SSA value address")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let arg = self.match_value("expected SSA value operand")?;
+                self.match_token(
+                    Token::Comma,
+                    "expected ',' between operands",
+                )?;
+                let len = self.match_value("expected SSA value length")?;
+                InstructionData::BulkMemory {
+                    opcode,
+                    flags,
+                    args: [dest, arg, len],
+                }
+            }
             InstructionFormat::RegMove => {
                 let arg = self.match_value("expected SSA value operand")?;
                 self.match_token(
@@ -2418,6 +3023,11 @@ mod tests {
         );
         assert_eq!(sig2.call_conv, CallConv::SpiderWASM);
 
+        let sig3 = Parser::new("(i64, f64) windows_fastcall")
+            .parse_signature(None)
+            .unwrap();
+        assert_eq!(sig3.call_conv, CallConv::WindowsFastcall);
+
         // Old-style signature without a calling convention.
         assert_eq!(
             Parser::new("()").parse_signature(None).unwrap().to_string(),
@@ -2514,6 +3124,34 @@ mod tests {
         assert_eq!(func.dfg.value_type(ebb4_args[0]), types::I32);
     }
 
+    #[test]
+    fn targets() {
+        let (_, details) = Parser::new(
+            "function %t() native {
+                             ; targets: intel
+                         ebb0: ; targets: arm32
+                             trap user0
+                         }
+                         ; targets: riscv",
+        ).parse_function(None)
+            .unwrap();
+        // The annotation on the EBB doesn't count.
+        assert_eq!(details.targets, ["intel", "riscv"]);
+        assert!(details.has_target("riscv"));
+        assert!(!details.has_target("arm32"));
+
+        let error = Parser::new(
+            "function %t() native {
+                             ; targets: vax
+                         ebb0:
+                             trap user0
+                         }",
+        ).parse_function(None)
+            .unwrap_err();
+        assert_eq!(error.location.line_number, 1);
+        assert_eq!(error.message, "unknown ISA 'vax' in targets");
+    }
+
     #[test]
     fn comments() {
         let (func, Details { comments, .. }) = Parser::new(
@@ -2659,4 +3297,99 @@ mod tests {
         );
         assert!(parser.parse_function(None).is_err());
     }
+
+    #[test]
+    fn quoted_function_name() {
+        let func = Parser::new(
+            r#"function %"_ZN3foo3bar17h0123456789abcdefE"() native {
+                                           ebb0:
+                                             trap user0
+                                           }"#,
+        ).parse_function(None)
+            .unwrap()
+            .0;
+        assert_eq!(
+            func.name,
+            ExternalName::symbol("_ZN3foo3bar17h0123456789abcdefE")
+        );
+
+        // Escapes are decoded, and printed again the same way.
+        let func = Parser::new(
+            r#"function %"a \"b\"\\c\0a\FF"() native {
+                                           ebb0:
+                                             trap user0
+                                           }"#,
+        ).parse_function(None)
+            .unwrap()
+            .0;
+        assert_eq!(func.name, ExternalName::symbol(b"a \"b\"\\c\n\xff"));
+        assert_eq!(func.name.to_string(), r#"%"a \"b\"\\c\0a\ff""#);
+
+        // Invalid escapes.
+        for name in &[r#"%"\q""#, r#"%"\0""#, r#"%"\0g""#] {
+            let text = format!("function {}() native {{ ebb0: trap user0 }}", name);
+            assert!(Parser::new(&text).parse_function(None).is_err());
+        }
+    }
+
+    #[test]
+    fn include() {
+        use std::env;
+        use std::fs;
+        use std::process;
+
+        let dir = env::temp_dir().join(format!("cton-reader-include-{}", process::id()));
+        fs::create_dir_all(dir.join("sub")).unwrap();
+        let write = |name: &str, text: &str| fs::write(dir.join(name), text).unwrap();
+        write(
+            "decls.inc",
+            "; Shared declarations.\n gv0 = vmctx\n include \"sub/sigs.inc\"\n",
+        );
+        write("sub/sigs.inc", "sig0 = (i32) -> i32\n");
+        write("self.inc", "gv0 = vmctx\ninclude \"self.inc\"\n");
+        write("bad.inc", "gv0 = vmctx\nebb0:\n");
+        let options = IncludeOptions {
+            dir: Some(dir.clone()),
+            paths: Vec::new(),
+        };
+        let parse = |decl: &str| {
+            let text = format!(
+                "function %f() {{\n  {}\n  ss0 = explicit_slot 4\nebb0:\n  return\n}}",
+                decl
+            );
+            parse_test_with_includes(&text, &options).map(|tf| {
+                let (ref func, ref details) = tf.functions[0];
+                (
+                    func.to_string(),
+                    details.map.location(GlobalVar::new(0).into()),
+                    details.map.location(SigRef::new(0).into()),
+                    tf.includes.clone(),
+                )
+            })
+        };
+
+        let (text, gv_loc, sig_loc, includes) = parse("include \"decls.inc\"").unwrap();
+        assert!(text.contains("gv0 = vmctx"));
+        assert!(text.contains("sig0 = (i32) -> i32 native"));
+        assert!(text.contains("ss0 = explicit_slot 4"));
+        assert_eq!(gv_loc.unwrap().include, Some(0));
+        assert_eq!(sig_loc.unwrap().include, Some(1));
+        assert_eq!(includes, vec![dir.join("decls.inc"), dir.join("sub/sigs.inc")]);
+
+        let error = parse("include \"missing.inc\"").unwrap_err();
+        assert_eq!(error.location.line_number, 2);
+        assert_eq!(error.message, "can't find included file \"missing.inc\"");
+
+        let error = parse("include \"self.inc\"").unwrap_err().to_string();
+        assert!(error.ends_with("self.inc is included recursively"), "{}", error);
+
+        let error = parse("include \"bad.inc\"").unwrap_err().to_string();
+        assert!(
+            error.ends_with("bad.inc:2: expected preamble declaration in included file"),
+            "{}",
+            error
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
 }
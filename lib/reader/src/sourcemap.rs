@@ -7,7 +7,7 @@
 //! to parser clients.
 
 use cretonne::ir::entities::AnyEntity;
-use cretonne::ir::{StackSlot, GlobalVar, Heap, JumpTable, Ebb, Value, SigRef, FuncRef};
+use cretonne::ir::{StackSlot, GlobalVar, Heap, Table, JumpTable, Ebb, Value, SigRef, FuncRef};
 use error::{Result, Location};
 use lexer::split_entity_name;
 use std::collections::HashMap;
@@ -46,6 +46,11 @@ impl SourceMap {
         self.locations.contains_key(&heap.into())
     }
 
+    /// Look up a table entity.
+    pub fn contains_table(&self, table: Table) -> bool {
+        self.locations.contains_key(&table.into())
+    }
+
     /// Look up a signature entity.
     pub fn contains_sig(&self, sig: SigRef) -> bool {
         self.locations.contains_key(&sig.into())
@@ -100,6 +105,13 @@ impl SourceMap {
                     Some(heap.into())
                 })
             }
+            "table" => {
+                Table::with_number(num).and_then(|table| if !self.contains_table(table) {
+                    None
+                } else {
+                    Some(table.into())
+                })
+            }
             "sig" => {
                 SigRef::with_number(num).and_then(|sig| if !self.contains_sig(sig) {
                     None
@@ -162,6 +174,11 @@ impl SourceMap {
         self.def_entity(entity.into(), loc)
     }
 
+    /// Define the table `entity`.
+    pub fn def_table(&mut self, entity: Table, loc: &Location) -> Result<()> {
+        self.def_entity(entity.into(), loc)
+    }
+
     /// Define the signature `entity`.
     pub fn def_sig(&mut self, entity: SigRef, loc: &Location) -> Result<()> {
         self.def_entity(entity.into(), loc)
@@ -4,6 +4,8 @@
 //! file-based test case.
 //!
 
+use std::path::PathBuf;
+use std::time::Duration;
 use cretonne::ir::Function;
 use cretonne::ir::entities::AnyEntity;
 use testcommand::TestCommand;
@@ -25,6 +27,8 @@ pub struct TestFile<'a> {
     pub preamble_comments: Vec<Comment<'a>>,
     /// Parsed functions and additional details about each function.
     pub functions: Vec<(Function, Details<'a>)>,
+    /// Files read by `include` directives, indexed by `Location::include`.
+    pub includes: Vec<PathBuf>,
 }
 
 /// Additional details about a function parsed from a text string.
@@ -38,6 +42,18 @@ pub struct Details<'a> {
     pub comments: Vec<Comment<'a>>,
     /// Mapping of entity numbers to source locations.
     pub map: SourceMap,
+    /// ISA names from `; targets: isa...` annotations. When this is not empty, tests that need an
+    /// ISA are only run on this function for the listed ISAs.
+    pub targets: Vec<&'a str>,
+    /// Time spent parsing the function.
+    pub parse_time: Duration,
+}
+
+impl<'a> Details<'a> {
+    /// Should tests using `isa_name` be run on this function?
+    pub fn has_target(&self, isa_name: &str) -> bool {
+        self.targets.is_empty() || self.targets.contains(&isa_name)
+    }
 }
 
 /// A comment in a parsed function.
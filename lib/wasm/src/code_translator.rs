@@ -368,10 +368,12 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
             // `index` is the index of the function's signature and `table_index` is the index of
             // the table to search the function in.
             let (sigref, num_args) = state.get_indirect_sig(builder.func, index, environ);
+            let table = state.get_table(builder.func, table_index, environ);
             let callee = state.pop1();
             let call = environ.translate_call_indirect(
                 builder.cursor(),
                 table_index as TableIndex,
+                table,
                 index as SignatureIndex,
                 sigref,
                 callee,
@@ -385,8 +387,8 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          * special functions.
          ************************************************************************************/
         Operator::GrowMemory { reserved } => {
-            // The WebAssembly MVP only supports one linear memory, but we expect the reserved
-            // argument to be a memory index.
+            // The WebAssembly MVP only supports one linear memory, and the reserved argument is
+            // the memory index in the multi-memory proposal.
             let heap_index = reserved as MemoryIndex;
             let heap = state.get_heap(builder.func, reserved, environ);
             let val = state.pop1();
@@ -411,69 +413,69 @@ pub fn translate_operator<FE: FuncEnvironment + ?Sized>(
          * The memory base address is provided by the environment.
          * TODO: differentiate between 32 bit and 64 bit architecture, to put the uextend or not
          ************************************************************************************/
-        Operator::I32Load8U { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Uload8, I32, builder, state, environ);
+        Operator::I32Load8U { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Uload8, I32, builder, state, environ);
         }
-        Operator::I32Load16U { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Uload16, I32, builder, state, environ);
+        Operator::I32Load16U { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Uload16, I32, builder, state, environ);
         }
-        Operator::I32Load8S { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Sload8, I32, builder, state, environ);
+        Operator::I32Load8S { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Sload8, I32, builder, state, environ);
         }
-        Operator::I32Load16S { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Sload16, I32, builder, state, environ);
+        Operator::I32Load16S { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Sload16, I32, builder, state, environ);
         }
-        Operator::I64Load8U { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Uload8, I64, builder, state, environ);
+        Operator::I64Load8U { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Uload8, I64, builder, state, environ);
         }
-        Operator::I64Load16U { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Uload16, I64, builder, state, environ);
+        Operator::I64Load16U { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Uload16, I64, builder, state, environ);
         }
-        Operator::I64Load8S { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Sload8, I64, builder, state, environ);
+        Operator::I64Load8S { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Sload8, I64, builder, state, environ);
         }
-        Operator::I64Load16S { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Sload16, I64, builder, state, environ);
+        Operator::I64Load16S { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Sload16, I64, builder, state, environ);
         }
-        Operator::I64Load32S { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Sload32, I64, builder, state, environ);
+        Operator::I64Load32S { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Sload32, I64, builder, state, environ);
         }
-        Operator::I64Load32U { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Uload32, I64, builder, state, environ);
+        Operator::I64Load32U { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Uload32, I64, builder, state, environ);
         }
-        Operator::I32Load { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Load, I32, builder, state, environ);
+        Operator::I32Load { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Load, I32, builder, state, environ);
         }
-        Operator::F32Load { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Load, F32, builder, state, environ);
+        Operator::F32Load { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Load, F32, builder, state, environ);
         }
-        Operator::I64Load { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Load, I64, builder, state, environ);
+        Operator::I64Load { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Load, I64, builder, state, environ);
         }
-        Operator::F64Load { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_load(offset, ir::Opcode::Load, F64, builder, state, environ);
+        Operator::F64Load { ref memarg } => {
+            translate_load(memarg, ir::Opcode::Load, F64, builder, state, environ);
         }
         /****************************** Store instructions ***********************************
          * Wasm specifies an integer alignment flag but we drop it in Cretonne.
          * The memory base address is provided by the environment.
          * TODO: differentiate between 32 bit and 64 bit architecture, to put the uextend or not
          ************************************************************************************/
-        Operator::I32Store { memarg: MemoryImmediate { flags: _, offset } } |
-        Operator::I64Store { memarg: MemoryImmediate { flags: _, offset } } |
-        Operator::F32Store { memarg: MemoryImmediate { flags: _, offset } } |
-        Operator::F64Store { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_store(offset, ir::Opcode::Store, builder, state, environ);
+        Operator::I32Store { ref memarg } |
+        Operator::I64Store { ref memarg } |
+        Operator::F32Store { ref memarg } |
+        Operator::F64Store { ref memarg } => {
+            translate_store(memarg, ir::Opcode::Store, builder, state, environ);
         }
-        Operator::I32Store8 { memarg: MemoryImmediate { flags: _, offset } } |
-        Operator::I64Store8 { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_store(offset, ir::Opcode::Istore8, builder, state, environ);
+        Operator::I32Store8 { ref memarg } |
+        Operator::I64Store8 { ref memarg } => {
+            translate_store(memarg, ir::Opcode::Istore8, builder, state, environ);
         }
-        Operator::I32Store16 { memarg: MemoryImmediate { flags: _, offset } } |
-        Operator::I64Store16 { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_store(offset, ir::Opcode::Istore16, builder, state, environ);
+        Operator::I32Store16 { ref memarg } |
+        Operator::I64Store16 { ref memarg } => {
+            translate_store(memarg, ir::Opcode::Istore16, builder, state, environ);
         }
-        Operator::I64Store32 { memarg: MemoryImmediate { flags: _, offset } } => {
-            translate_store(offset, ir::Opcode::Istore32, builder, state, environ);
+        Operator::I64Store32 { ref memarg } => {
+            translate_store(memarg, ir::Opcode::Istore32, builder, state, environ);
         }
         /****************************** Nullary Operators ************************************/
         Operator::I32Const { value } => state.push1(builder.ins().iconst(I32, i64::from(value))),
@@ -979,9 +981,21 @@ fn get_heap_addr(
     }
 }
 
+/// Get the index of the linear memory accessed by a load or store.
+///
+/// The multi-memory proposal stores a memory index in the memory immediate when bit 6 of the
+/// alignment flags is set. The parser doesn't decode the index yet, so all other accesses are to
+/// memory 0.
+fn memarg_memory(memarg: &MemoryImmediate) -> u32 {
+    if memarg.flags & (1 << 6) != 0 {
+        panic!("proposed multi-memory immediates not yet supported");
+    }
+    0
+}
+
 // Translate a load instruction.
 fn translate_load<FE: FuncEnvironment + ?Sized>(
-    offset: u32,
+    memarg: &MemoryImmediate,
     opcode: ir::Opcode,
     result_ty: ir::Type,
     builder: &mut FunctionBuilder<Variable>,
@@ -989,9 +1003,9 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
     environ: &mut FE,
 ) {
     let addr32 = state.pop1();
-    // We don't yet support multiple linear memories.
-    let heap = state.get_heap(builder.func, 0, environ);
-    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.native_pointer(), builder);
+    let heap = state.get_heap(builder.func, memarg_memory(memarg), environ);
+    let (base, offset) =
+        get_heap_addr(heap, addr32, memarg.offset, environ.native_pointer(), builder);
     let flags = MemFlags::new();
     let (load, dfg) = builder.ins().Load(
         opcode,
@@ -1005,7 +1019,7 @@ fn translate_load<FE: FuncEnvironment + ?Sized>(
 
 // Translate a store instruction.
 fn translate_store<FE: FuncEnvironment + ?Sized>(
-    offset: u32,
+    memarg: &MemoryImmediate,
     opcode: ir::Opcode,
     builder: &mut FunctionBuilder<Variable>,
     state: &mut TranslationState,
@@ -1014,9 +1028,9 @@ fn translate_store<FE: FuncEnvironment + ?Sized>(
     let (addr32, val) = state.pop2();
     let val_ty = builder.func.dfg.value_type(val);
 
-    // We don't yet support multiple linear memories.
-    let heap = state.get_heap(builder.func, 0, environ);
-    let (base, offset) = get_heap_addr(heap, addr32, offset, environ.native_pointer(), builder);
+    let heap = state.get_heap(builder.func, memarg_memory(memarg), environ);
+    let (base, offset) =
+        get_heap_addr(heap, addr32, memarg.offset, environ.native_pointer(), builder);
     let flags = MemFlags::new();
     builder.ins().Store(
         opcode,
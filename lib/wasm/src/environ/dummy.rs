@@ -158,9 +158,17 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         }
     }
 
-    fn make_heap(&mut self, func: &mut ir::Function, _index: MemoryIndex) -> ir::Heap {
-        // Create a static heap whose base address is stored at `vmctx+0`.
-        let gv = func.create_global_var(ir::GlobalVarData::VmCtx { offset: 0.into() });
+    fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> ir::Heap {
+        // Create a static heap whose base address is stored at `vmctx+0` for the first memory.
+        // The base addresses of additional memories follow the globals.
+        let offset = if index == 0 {
+            0
+        } else {
+            (self.mod_info.globals.len() + index) * 8
+        };
+        let gv = func.create_global_var(ir::GlobalVarData::VmCtx {
+            offset: (offset as i32).into(),
+        });
 
         func.create_heap(ir::HeapData {
             base: ir::HeapBase::GlobalVar(gv),
@@ -170,6 +178,28 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         })
     }
 
+    fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> ir::Table {
+        // Create a table whose base address and current number of elements are stored in `vmctx`
+        // after the globals and memory bases.
+        let first = self.mod_info.globals.len() + self.mod_info.memories.len() + 1;
+        let offset = (first + 2 * index) * 8;
+        let base_gv = func.create_global_var(ir::GlobalVarData::VmCtx {
+            offset: (offset as i32).into(),
+        });
+        let bound_gv = func.create_global_var(ir::GlobalVarData::VmCtx {
+            offset: (offset as i32 + 8).into(),
+        });
+        let min_size = self.mod_info.tables.get(index).map_or(0, |t| t.entity.size);
+
+        // Each element is a function pointer.
+        func.create_table(ir::TableData {
+            base_gv,
+            min_size: (min_size as i64).into(),
+            bound_gv,
+            element_size: i64::from(self.native_pointer().bytes()).into(),
+        })
+    }
+
     fn make_indirect_sig(&mut self, func: &mut ir::Function, index: SignatureIndex) -> ir::SigRef {
         // A real implementation would probably change the calling convention and add `vmctx` and
         // signature index arguments.
@@ -182,13 +212,20 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
         // And maybe attempt some signature de-duplication.
         let signature = func.import_signature(self.vmctx_sig(sigidx));
         let name = get_func_name(index);
-        func.import_function(ir::ExtFuncData { name, signature })
+        func.import_function(ir::ExtFuncData {
+            name,
+            signature,
+            noreturn: false,
+            cold: false,
+            colocated: false,
+        })
     }
 
     fn translate_call_indirect(
         &mut self,
         mut pos: FuncCursor,
         _table_index: TableIndex,
+        table: ir::Table,
         _sig_index: SignatureIndex,
         sig_ref: ir::SigRef,
         callee: ir::Value,
@@ -199,17 +236,14 @@ impl<'dummy_environment> FuncEnvironment for DummyFuncEnvironment<'dummy_environ
             .special_param(ir::ArgumentPurpose::VMContext)
             .expect("Missing vmctx parameter");
 
-        // The `callee` value is an index into a table of function pointers.
-        // Apparently, that table is stored at absolute address 0 in this dummy environment.
-        // TODO: Generate bounds checking code.
+        // The `callee` value is an index into a table of function pointers. A real
+        // implementation would also check the signature of the callee.
         let ptr = self.native_pointer();
-        let callee_offset = if ptr == I32 {
-            pos.ins().imul_imm(callee, 4)
-        } else {
-            let ext = pos.ins().uextend(I64, callee);
-            pos.ins().imul_imm(ext, 4)
-        };
-        let func_ptr = pos.ins().load(ptr, ir::MemFlags::new(), callee_offset, 0);
+        let callee_addr = pos.ins().table_addr(ptr, table, callee, 0);
+        let mut flags = ir::MemFlags::new();
+        flags.set_notrap();
+        flags.set_aligned();
+        let func_ptr = pos.ins().load(ptr, flags, callee_addr, 0);
 
         // Build a value list for the indirect call instruction containing the callee, call_args,
         // and the vmctx parameter.
@@ -58,6 +58,12 @@ pub trait FuncEnvironment {
     /// The index space covers both imported and locally declared memories.
     fn make_heap(&mut self, func: &mut ir::Function, index: MemoryIndex) -> ir::Heap;
 
+    /// Set up the necessary preamble definitions in `func` to access the table identified
+    /// by `index`.
+    ///
+    /// The index space covers both imported and locally declared tables.
+    fn make_table(&mut self, func: &mut ir::Function, index: TableIndex) -> ir::Table;
+
     /// Set up a signature definition in the preamble of `func` that can be used for an indirect
     /// call with signature `index`.
     ///
@@ -88,13 +94,15 @@ pub trait FuncEnvironment {
     /// `table_index` with WebAssembly signature `sig_index`. The `callee` value will have type
     /// `i32`.
     ///
-    /// The signature `sig_ref` was previously created by `make_indirect_sig()`.
+    /// The table reference `table` was previously created by `make_table()` for `table_index`,
+    /// and the signature `sig_ref` was previously created by `make_indirect_sig()`.
     ///
     /// Return the call instruction whose results are the WebAssembly return values.
     fn translate_call_indirect(
         &mut self,
         pos: FuncCursor,
         table_index: TableIndex,
+        table: ir::Table,
         sig_index: SignatureIndex,
         sig_ref: ir::SigRef,
         callee: ir::Value,
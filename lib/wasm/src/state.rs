@@ -6,7 +6,7 @@
 use cretonne::ir::{self, Ebb, Inst, Value};
 use environ::{FuncEnvironment, GlobalValue};
 use std::collections::HashMap;
-use translation_utils::{GlobalIndex, MemoryIndex, SignatureIndex, FunctionIndex};
+use translation_utils::{GlobalIndex, MemoryIndex, TableIndex, SignatureIndex, FunctionIndex};
 use std::vec::Vec;
 
 /// A control stack frame can be an `if`, a `block` or a `loop`, each one having the following
@@ -116,6 +116,9 @@ pub struct TranslationState {
     // Map of heaps that have been created by `FuncEnvironment::make_heap`.
     heaps: HashMap<MemoryIndex, ir::Heap>,
 
+    // Map of tables that have been created by `FuncEnvironment::make_table`.
+    tables: HashMap<TableIndex, ir::Table>,
+
     // Map of indirect call signatures that have been created by
     // `FuncEnvironment::make_indirect_sig()`.
     // Stores both the signature reference and the number of WebAssembly arguments
@@ -135,6 +138,7 @@ impl TranslationState {
             reachable: true,
             globals: HashMap::new(),
             heaps: HashMap::new(),
+            tables: HashMap::new(),
             signatures: HashMap::new(),
             functions: HashMap::new(),
         }
@@ -146,6 +150,7 @@ impl TranslationState {
         self.reachable = true;
         self.globals.clear();
         self.heaps.clear();
+        self.tables.clear();
         self.signatures.clear();
         self.functions.clear();
     }
@@ -277,6 +282,20 @@ impl TranslationState {
         )
     }
 
+    /// Get the `Table` reference that should be used to access table `index`.
+    /// Create the reference if necessary.
+    pub fn get_table<FE: FuncEnvironment + ?Sized>(
+        &mut self,
+        func: &mut ir::Function,
+        index: u32,
+        environ: &mut FE,
+    ) -> ir::Table {
+        let index = index as TableIndex;
+        *self.tables.entry(index).or_insert_with(
+            || environ.make_table(func, index),
+        )
+    }
+
     /// Get the `SigRef` reference that should be used to make an indirect call with signature
     /// `index`. Also return the number of WebAssembly arguments in the signature.
     ///
@@ -0,0 +1,273 @@
+//! CLI tool to benchmark the compilation of a corpus of Cretonne IL functions.
+//!
+//! Every function in the corpus is compiled once per iteration, and the time spent in each pass
+//! is collected with the `timing` module. The report shows the distribution of the pass times
+//! over the iterations. It can be written as JSON and compared against a previous run.
+//!
+//! The corpus can also include a generated function with a large number of values live across
+//! many EBBs, which is the shape of machine-generated code that stresses the liveness analysis.
+
+use compile::collect_files;
+use cretonne::Context;
+use cretonne::ir::Function;
+use cretonne::print_errors::pretty_error;
+use cretonne::timing;
+use cton_reader::{parse_functions, parse_test_with_includes, IncludeOptions};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+use utils::{read_to_string, parse_sets_and_isa};
+
+/// The distribution of the time spent in one pass over all the iterations.
+struct PassStats {
+    name: &'static str,
+    description: &'static str,
+    min: f64,
+    median: f64,
+    max: f64,
+}
+
+pub fn run(
+    files: Vec<String>,
+    iterations: usize,
+    flag_json: bool,
+    flag_baseline: &str,
+    flag_synthetic: usize,
+    flag_set: &[String],
+    flag_isa: &str,
+) -> Result<(), String> {
+    let parsed = parse_sets_and_isa(flag_set, flag_isa)?;
+    let isa = match parsed.as_fisa().isa {
+        Some(isa) => isa,
+        None => return Err(String::from("benchmarking requires a target isa")),
+    };
+    if iterations == 0 {
+        return Err(String::from("benchmarking requires at least one iteration"));
+    }
+
+    let mut paths = Vec::new();
+    for filename in files {
+        collect_files(PathBuf::from(filename), &mut paths)?;
+    }
+    let mut corpus = read_corpus(&paths)?;
+    if flag_synthetic > 0 {
+        corpus.extend(parse_functions(&synthetic_function(flag_synthetic)).map_err(
+            |e| format!("synthetic function: {}", e),
+        )?);
+    }
+    if corpus.is_empty() {
+        return Err(String::from("the benchmark corpus is empty"));
+    }
+
+    // Discard any timings collected while reading the corpus.
+    timing::take_current();
+
+    let mut samples: HashMap<&'static str, (&'static str, Vec<f64>)> = HashMap::new();
+    let mut order = Vec::new();
+    let mut context = Context::new();
+    for _ in 0..iterations {
+        for func in &corpus {
+            context.clear();
+            context.func = func.clone();
+            context.compile(isa).map_err(|err| {
+                pretty_error(&context.func, Some(isa), err)
+            })?;
+        }
+        for pass in timing::take_current().passes() {
+            let entry = samples.entry(pass.name).or_insert_with(|| {
+                order.push(pass.name);
+                (pass.description, Vec::new())
+            });
+            entry.1.push(millis(pass.self_time));
+        }
+    }
+
+    let stats = order
+        .into_iter()
+        .map(|name| {
+            let (description, ref mut times) = *samples.get_mut(name).unwrap();
+            // Passes that didn't run in an iteration took no time.
+            times.resize(iterations, 0.0);
+            times.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            PassStats {
+                name,
+                description,
+                min: times[0],
+                median: times[times.len() / 2],
+                max: times[times.len() - 1],
+            }
+        })
+        .collect::<Vec<_>>();
+
+    let baseline = if flag_baseline.is_empty() {
+        None
+    } else {
+        let text = read_to_string(flag_baseline).map_err(
+            |e| format!("{}: {}", flag_baseline, e),
+        )?;
+        Some(parse_baseline(&text))
+    };
+
+    if flag_json {
+        print!("{}", format_json(&stats, corpus.len(), iterations));
+    } else {
+        print!(
+            "{}",
+            format_table(&stats, corpus.len(), iterations, baseline.as_ref())
+        );
+    }
+    Ok(())
+}
+
+/// Read all the functions in `paths`.
+///
+/// The functions are compiled for the ISA given on the command line, and any ISA specified in
+/// the files is ignored.
+fn read_corpus(paths: &[PathBuf]) -> Result<Vec<Function>, String> {
+    let mut corpus = Vec::new();
+    for path in paths {
+        let name = path.to_string_lossy();
+        let buffer = read_to_string(path).map_err(|e| format!("{}: {}", name, e))?;
+        let test_file = parse_test_with_includes(&buffer, &IncludeOptions::for_file(path))
+            .map_err(|e| format!("{}: {}", name, e))?;
+        corpus.extend(test_file.functions.into_iter().map(|(func, _)| func));
+    }
+    Ok(corpus)
+}
+
+/// Generate the IL text of a function with about `values` values.
+///
+/// The function is a chain of diamonds with a loop back-edge every 16 EBBs. The values defined in
+/// each diamond head are used by heads up to 256 diamonds later, so most values are live-in to a
+/// large number of EBBs.
+fn synthetic_function(values: usize) -> String {
+    const PER_EBB: usize = 4;
+    let heads = values / (2 * PER_EBB) + 1;
+    let mut out = String::new();
+    writeln!(out, "function %synthetic(i32) -> i32 {{").unwrap();
+    writeln!(out, "ebb0(v0: i32):").unwrap();
+    writeln!(out, "    jump ebb2").unwrap();
+    // The number of the next value, and the first value defined in each head.
+    let mut next = 1;
+    let mut head_values = Vec::with_capacity(heads);
+    for k in 0..heads {
+        let head = 2 * k + 2;
+        head_values.push(next);
+        writeln!(out, "\nebb{}:", head).unwrap();
+        for i in 0..PER_EBB {
+            // Use a value from an earlier head, chosen by a cheap hash of the head number.
+            let far_value = if k == 0 {
+                0
+            } else {
+                head_values[k - 1 - (k * 31 + i * 7) % 64 * 4 % k] + i
+            };
+            let prev = if i == 0 { 0 } else { next - 1 };
+            writeln!(out, "    v{} = iadd v{}, v{}", next, prev, far_value).unwrap();
+            next += 1;
+        }
+        if k % 8 == 7 && k >= 15 {
+            writeln!(out, "    brnz v{}, ebb{}", next - 1, head - 14).unwrap();
+        }
+        if k + 1 == heads {
+            writeln!(out, "    return v{}", next - 1).unwrap();
+            break;
+        }
+        writeln!(out, "    brz v{}, ebb{}", next - 2, head + 1).unwrap();
+        writeln!(out, "    jump ebb{}", head + 2).unwrap();
+        writeln!(out, "\nebb{}:", head + 1).unwrap();
+        for i in 0..PER_EBB {
+            writeln!(out, "    v{} = iadd_imm v{}, {}", next, next - 1, i).unwrap();
+            next += 1;
+        }
+        writeln!(out, "    jump ebb{}", head + 2).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Convert `dur` to milliseconds.
+fn millis(dur: Duration) -> f64 {
+    dur.as_secs() as f64 * 1000.0 + f64::from(dur.subsec_nanos()) / 1e6
+}
+
+/// Format `stats` as JSON, one pass per line.
+fn format_json(stats: &[PassStats], functions: usize, iterations: usize) -> String {
+    let mut out = String::new();
+    writeln!(out, "{{").unwrap();
+    writeln!(out, "  \"functions\": {},", functions).unwrap();
+    writeln!(out, "  \"iterations\": {},", iterations).unwrap();
+    writeln!(out, "  \"passes\": [").unwrap();
+    for (i, pass) in stats.iter().enumerate() {
+        writeln!(
+            out,
+            concat!(
+                "    {{\"pass\": \"{}\", ",
+                "\"min_ms\": {:.6}, \"median_ms\": {:.6}, \"max_ms\": {:.6}}}{}"
+            ),
+            pass.name,
+            pass.min,
+            pass.median,
+            pass.max,
+            if i + 1 < stats.len() { "," } else { "" }
+        ).unwrap();
+    }
+    writeln!(out, "  ]").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Get the median times per pass from the JSON written by `format_json`.
+fn parse_baseline(text: &str) -> HashMap<String, f64> {
+    text.lines()
+        .filter_map(|line| {
+            let name = json_field(line, "pass")?.trim_matches('"');
+            let median = json_field(line, "median_ms")?.parse().ok()?;
+            Some((name.to_string(), median))
+        })
+        .collect()
+}
+
+/// Get the text of the value of `key` in a single line JSON object.
+fn json_field<'a>(line: &'a str, key: &str) -> Option<&'a str> {
+    let pattern = format!("\"{}\": ", key);
+    let start = line.find(&pattern)? + pattern.len();
+    let rest = &line[start..];
+    let end = rest.find(|c| c == ',' || c == '}').unwrap_or(rest.len());
+    Some(rest[..end].trim())
+}
+
+/// Format `stats` as a table, comparing the medians against `baseline` if given.
+fn format_table(
+    stats: &[PassStats],
+    functions: usize,
+    iterations: usize,
+    baseline: Option<&HashMap<String, f64>>,
+) -> String {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{} functions, {} iterations, self time per iteration in ms",
+        functions,
+        iterations
+    ).unwrap();
+    write!(out, "     min   median      max").unwrap();
+    if baseline.is_some() {
+        write!(out, "   change").unwrap();
+    }
+    writeln!(out, "  pass").unwrap();
+    for pass in stats {
+        write!(out, "{:8.3} {:8.3} {:8.3}", pass.min, pass.median, pass.max).unwrap();
+        if let Some(baseline) = baseline {
+            match baseline.get(pass.name) {
+                Some(&base) if base > 0.0 => {
+                    write!(out, " {:+7.1}%", (pass.median - base) / base * 100.0).unwrap()
+                }
+                _ => write!(out, "      new").unwrap(),
+            }
+        }
+        writeln!(out, "  {}", pass.description).unwrap();
+    }
+    out
+}
+
@@ -1,35 +1,37 @@
 //! The `cat` sub-command.
 //!
 //! Read a sequence of Cretonne IL files and print them again to stdout. This has the effect of
-//! normalizing formatting and removing comments.
+//! normalizing formatting and removing comments. With `--canonical`, the EBBs and values are also
+//! renumbered in layout order so functions can be compared with `diff`.
 
-use cton_reader::parse_functions;
+use cretonne::WriteOptions;
+use cton_reader::{parse_test_with_includes, IncludeOptions};
 use CommandResult;
 use utils::read_to_string;
 
-pub fn run(files: &[String]) -> CommandResult {
+pub fn run(files: &[String], canonical: bool) -> CommandResult {
+    let options = WriteOptions { canonical_numbering: canonical };
     for (i, f) in files.into_iter().enumerate() {
         if i != 0 {
             println!();
         }
-        cat_one(f)?
+        cat_one(f, options)?
     }
     Ok(())
 }
 
-fn cat_one(filename: &str) -> CommandResult {
+fn cat_one(filename: &str, options: WriteOptions) -> CommandResult {
     let buffer = read_to_string(&filename).map_err(
         |e| format!("{}: {}", filename, e),
     )?;
-    let items = parse_functions(&buffer).map_err(
-        |e| format!("{}: {}", filename, e),
-    )?;
+    let test_file = parse_test_with_includes(&buffer, &IncludeOptions::for_file(filename))
+        .map_err(|e| format!("{}: {}", filename, e))?;
 
-    for (idx, func) in items.into_iter().enumerate() {
+    for (idx, &(ref func, _)) in test_file.functions.iter().enumerate() {
         if idx != 0 {
             println!();
         }
-        print!("{}", func);
+        print!("{}", func.display_with(None, options));
     }
 
     Ok(())
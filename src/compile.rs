@@ -1,15 +1,23 @@
 //! CLI tool to compile cretonne IL into native code.
 //!
-//! Reads IR files into Cretonne IL and compiles it.
+//! Reads IR files into Cretonne IL and compiles it. Directories are searched for `.cton` files.
+//! The machine code of all the compiled functions can be written to a single file, where each
+//! function is aligned as it requires.
 
-use cton_reader::parse_test;
+use cton_reader::{parse_test_with_includes, IncludeOptions};
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::Write;
 use std::path::PathBuf;
+use std::process::Command;
+use std::time::Instant;
 use cretonne::Context;
+use cretonne::isa::TargetIsa;
 use cretonne::settings::FlagsOrIsa;
 use cretonne::{binemit, ir};
 use cretonne::print_errors::pretty_error;
-use std::path::Path;
-use utils::{read_to_string, parse_sets_and_isa};
+use tempdir::TempDir;
+use utils::{read_to_string, parse_sets_and_isa, parse_dump, check_dump};
 
 struct PrintRelocs {
     flag_print: bool,
@@ -46,32 +54,108 @@ impl binemit::RelocSink for PrintRelocs {
     }
 }
 
+struct PrintPatches {
+    flag_print: bool,
+}
+
+impl binemit::PatchSink for PrintPatches {
+    fn patchable_call(&mut self, offset: binemit::CodeOffset, name: &ir::ExternalName) {
+        if self.flag_print {
+            println!("patchable_call: {} at {}", name, offset);
+        }
+    }
+}
+
 pub fn run(
     files: Vec<String>,
     flag_print: bool,
+    flag_disasm: bool,
+    flag_print_size: bool,
+    flag_output: &str,
     flag_set: &[String],
     flag_isa: &str,
 ) -> Result<(), String> {
     let parsed = parse_sets_and_isa(flag_set, flag_isa)?;
 
+    let mut paths = Vec::new();
     for filename in files {
-        let path = Path::new(&filename);
+        collect_files(PathBuf::from(filename), &mut paths)?;
+    }
+
+    let mut output = Output {
+        code: Vec::new(),
+        keep: !flag_output.is_empty(),
+        print_size: flag_print_size,
+    };
+    let mut context = Context::new();
+    context.dump = parse_dump(flag_set)?;
+    for path in paths {
         let name = String::from(path.as_os_str().to_string_lossy());
-        handle_module(flag_print, &path.to_path_buf(), &name, parsed.as_fisa())?;
+        handle_module(
+            flag_print,
+            flag_disasm,
+            &path,
+            &name,
+            parsed.as_fisa(),
+            &mut context,
+            &mut output,
+        )?;
+    }
+    check_dump(&mut context)?;
+
+    if output.keep {
+        File::create(flag_output)
+            .and_then(|mut file| file.write_all(&output.code))
+            .map_err(|e| format!("{}: {}", flag_output, e))?;
     }
     Ok(())
 }
 
+/// Add `path` to `paths` if it is a file, or all the `.cton` files below it if it is a directory.
+pub fn collect_files(path: PathBuf, paths: &mut Vec<PathBuf>) -> Result<(), String> {
+    if !path.is_dir() {
+        paths.push(path);
+        return Ok(());
+    }
+
+    let mut entries: Vec<PathBuf> = path.read_dir()
+        .and_then(|entries| entries.map(|e| e.map(|e| e.path())).collect())
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    entries.sort();
+    for entry in entries {
+        if entry.is_dir() {
+            collect_files(entry, paths)?;
+        } else if entry.extension().and_then(OsStr::to_str) == Some("cton") {
+            paths.push(entry);
+        }
+    }
+    Ok(())
+}
+
+/// The machine code of all the compiled functions.
+struct Output {
+    /// The machine code of the functions, each aligned as required.
+    code: Vec<u8>,
+    /// Should the code be kept, or is it discarded after each function?
+    keep: bool,
+    /// Print the size and compile time of every function.
+    print_size: bool,
+}
+
 fn handle_module(
     flag_print: bool,
+    flag_disasm: bool,
     path: &PathBuf,
     name: &str,
     fisa: FlagsOrIsa,
+    context: &mut Context,
+    output: &mut Output,
 ) -> Result<(), String> {
     let buffer = read_to_string(&path).map_err(
         |e| format!("{}: {}", name, e),
     )?;
-    let test_file = parse_test(&buffer).map_err(|e| format!("{}: {}", name, e))?;
+    let test_file = parse_test_with_includes(&buffer, &IncludeOptions::for_file(path))
+        .map_err(|e| format!("{}: {}", name, e))?;
 
     // If we have an isa from the command-line, use that. Otherwise if the
     // file contains a unique isa, use that.
@@ -84,25 +168,58 @@ fn handle_module(
     };
 
     for (func, _) in test_file.functions {
-        let mut context = Context::new();
+        context.clear();
         context.func = func;
+        let start = Instant::now();
         let size = context.compile(isa).map_err(|err| {
             pretty_error(&context.func, Some(isa), err)
         })?;
+        let elapsed = start.elapsed();
         if flag_print {
             println!("{}", context.func.display(isa));
         }
 
-        // Encode the result as machine code.
-        let mut mem = Vec::new();
+        // Encode the result as machine code, aligned as required in the output.
+        if !output.keep {
+            output.code.clear();
+        }
+        let align = binemit::function_alignment(&context.func, isa) as usize;
+        let padded = (output.code.len() + align - 1) / align * align;
+        output.code.resize(padded, 0);
+        output.code.resize(padded + size as usize, 0);
+        let mem = &mut output.code[padded..];
         let mut relocs = PrintRelocs { flag_print };
-        mem.resize(size as usize, 0);
-        context.emit_to_memory(mem.as_mut_ptr(), &mut relocs, &*isa);
+        let mut traps = binemit::TrapTableSink::new();
+        let mut patches = PrintPatches { flag_print };
+        let mut ranges = binemit::InstRanges::new();
+        context.emit_to_memory_annotated(
+            mem.as_mut_ptr(),
+            &mut relocs,
+            &mut traps,
+            &mut patches,
+            &mut ranges,
+            &*isa,
+        );
+
+        if flag_print {
+            for site in traps.sites() {
+                println!("trap: {} at {}", site.code, site.offset);
+            }
+        }
+
+        if output.print_size {
+            let ms = elapsed.as_secs() as f64 * 1000.0 + f64::from(elapsed.subsec_nanos()) / 1e6;
+            print!("{}: {} bytes in {:.3} ms", context.func.name, size, ms);
+            if output.keep {
+                print!(" at offset {:#x}", padded);
+            }
+            println!();
+        }
 
         if flag_print {
             print!(".byte ");
             let mut first = true;
-            for byte in &mem {
+            for byte in mem.iter() {
                 if first {
                     first = false;
                 } else {
@@ -112,7 +229,105 @@ fn handle_module(
             }
             println!();
         }
+
+        if flag_disasm {
+            let disasm = match disassemble(isa, mem) {
+                Ok(lines) => lines,
+                Err(msg) => {
+                    println!("{}, printing raw bytes", msg);
+                    raw_bytes(mem, &ranges.ranges)
+                }
+            };
+            let mut text = String::new();
+            binemit::write_annotated_disassembly(
+                &mut text,
+                &context.func,
+                Some(isa),
+                &ranges.ranges,
+                &disasm,
+            ).map_err(|e| e.to_string())?;
+            print!("{}", text);
+        }
     }
 
     Ok(())
 }
+
+/// Get the `objdump` machine and disassembler options for `isa`.
+fn objdump_machine(isa: &TargetIsa) -> Option<(&'static str, Option<&'static str>)> {
+    let flags = isa.flags();
+    match isa.name() {
+        "intel" if flags.is_64bit() => Some(("i386:x86-64", None)),
+        "intel" => Some(("i386", None)),
+        "riscv" => Some(("riscv", None)),
+        "arm32" if flags.is_compressed() => Some(("arm", Some("force-thumb"))),
+        "arm32" => Some(("arm", None)),
+        "arm64" => Some(("aarch64", None)),
+        _ => None,
+    }
+}
+
+/// Disassemble `mem` with `objdump`, returning the code offset and text of every instruction.
+fn disassemble(isa: &TargetIsa, mem: &[u8]) -> Result<Vec<(binemit::CodeOffset, String)>, String> {
+    let (machine, options) = objdump_machine(isa).ok_or_else(|| {
+        format!("no disassembler for {}", isa.name())
+    })?;
+
+    let tmp_dir = TempDir::new("cton-util").map_err(|e| e.to_string())?;
+    let file_path = tmp_dir.path().join("code.bin");
+    File::create(&file_path)
+        .and_then(|mut file| file.write_all(mem))
+        .map_err(|e| e.to_string())?;
+
+    let mut cmd = Command::new("objdump");
+    cmd.args(&["-D", "-b", "binary", "-m", machine]);
+    if let Some(options) = options {
+        cmd.arg("-M").arg(options);
+    }
+    let output = cmd.arg(&file_path).output().map_err(
+        |e| format!("objdump failed: {}", e),
+    )?;
+    if !output.status.success() {
+        return Err(format!(
+            "objdump failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        ));
+    }
+
+    // Instruction lines look like "  1c:\t48 85 ff             \ttest   %rdi,%rdi".
+    let mut lines = Vec::new();
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        let mut fields = line.splitn(3, '\t');
+        let offset = match fields.next().map(|f| f.trim()) {
+            Some(f) if f.ends_with(':') => {
+                match binemit::CodeOffset::from_str_radix(&f[..f.len() - 1], 16) {
+                    Ok(offset) => offset,
+                    Err(_) => continue,
+                }
+            }
+            _ => continue,
+        };
+        let bytes = fields.next().unwrap_or("").trim();
+        let asm = fields.next().unwrap_or("").trim();
+        lines.push((offset, format!("{:24}{}", bytes, asm)));
+    }
+    Ok(lines)
+}
+
+/// Format the machine code of every instruction as hexadecimal bytes.
+fn raw_bytes(
+    mem: &[u8],
+    ranges: &[(ir::Inst, binemit::CodeOffset, binemit::CodeOffset)],
+) -> Vec<(binemit::CodeOffset, String)> {
+    ranges
+        .iter()
+        .filter(|&&(_, start, end)| start < end)
+        .map(|&(_, start, end)| {
+            let bytes: Vec<String> = mem[start as usize..end as usize]
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            (start, bytes.join(" "))
+        })
+        .collect()
+}
@@ -20,17 +20,19 @@ mod print_cfg;
 mod rsfilecheck;
 mod wasm;
 mod compile;
+mod bench;
 
 const USAGE: &str = "
 Cretonne code generator utility
 
 Usage:
-    cton-util test [-vT] <file>...
-    cton-util cat <file>...
+    cton-util test [-vT] [--budgets] <file>...
+    cton-util cat [--canonical] <file>...
     cton-util filecheck [-v] <file>
-    cton-util print-cfg <file>...
-    cton-util compile [-vpT] [--set <set>]... [--isa <isa>] <file>...
+    cton-util print-cfg [--insts <n>] [--domtree] [--loops] <file>...
+    cton-util compile [-vpTDs] [--set <set>]... [--isa <isa>] [-o <output>] <file>...
     cton-util wasm [-ctvpTs] [--set <set>]... [--isa <isa>] <file>...
+    cton-util bench [--iterations <n>] [--json] [--baseline <file>] [--synthetic <n>] [--set <set>]... [--isa <isa>] [<file>...]
     cton-util --help | --version
 
 Options:
@@ -40,12 +42,29 @@ Options:
     -t, --just-decode
                     just decode WebAssembly to Cretonne IL
     -s, --print-size
-                    prints generated code size
+                    prints generated code size, and compile time for compile
+    -o, --output=<output>
+                    write the machine code of all compiled functions to a file
     -c, --check-translation
                     just checks the correctness of Cretonne IL translated from WebAssembly
     -p, --print     print the resulting Cretonne IL
+    -D, --disasm    print the disassembled machine code interleaved with the IL
     -h, --help      print this help message
-    --set=<set>     configure Cretonne settings
+    --iterations=<n>
+                    number of times to compile the benchmark corpus [default: 10]
+    --json          print the benchmark results as JSON
+    --insts=<n>     show the first <n> instructions of each EBB in the CFG
+    --domtree       draw the dominator tree edges in the CFG
+    --loops         group the EBBs of each loop in a cluster in the CFG
+    --canonical     renumber EBBs and values in layout order when printing
+    --budgets       fail tests that exceed the timing budgets annotated on their functions
+    --baseline=<file>
+                    compare the benchmark results with JSON from a previous run
+    --synthetic=<n>
+                    add a generated function with about <n> values to the benchmark corpus
+                    [default: 0]
+    --set=<set>     configure Cretonne settings, or IL dumps with dump_ir, dump_filter
+                    and dump_dir
     --isa=<isa>     specify the Cretonne ISA
     --version       print the Cretonne version
 
@@ -59,15 +78,27 @@ struct Args {
     cmd_print_cfg: bool,
     cmd_compile: bool,
     cmd_wasm: bool,
+    cmd_bench: bool,
     arg_file: Vec<String>,
     flag_just_decode: bool,
     flag_check_translation: bool,
     flag_print: bool,
+    flag_disasm: bool,
     flag_verbose: bool,
     flag_set: Vec<String>,
     flag_isa: String,
     flag_time_passes: bool,
     flag_print_size: bool,
+    flag_output: String,
+    flag_iterations: usize,
+    flag_json: bool,
+    flag_baseline: String,
+    flag_synthetic: usize,
+    flag_insts: Option<usize>,
+    flag_domtree: bool,
+    flag_loops: bool,
+    flag_canonical: bool,
+    flag_budgets: bool,
 }
 
 /// A command either succeeds or fails with an error message.
@@ -86,17 +117,26 @@ fn cton_util() -> CommandResult {
 
     // Find the sub-command to execute.
     let result = if args.cmd_test {
-        cton_filetests::run(args.flag_verbose, &args.arg_file).map(|_time| ())
+        cton_filetests::run(args.flag_verbose, args.flag_budgets, &args.arg_file)
+            .map(|_time| ())
     } else if args.cmd_cat {
-        cat::run(&args.arg_file)
+        cat::run(&args.arg_file, args.flag_canonical)
     } else if args.cmd_filecheck {
         rsfilecheck::run(&args.arg_file, args.flag_verbose)
     } else if args.cmd_print_cfg {
-        print_cfg::run(&args.arg_file)
+        print_cfg::run(
+            &args.arg_file,
+            args.flag_insts,
+            args.flag_domtree,
+            args.flag_loops,
+        )
     } else if args.cmd_compile {
         compile::run(
             args.arg_file,
             args.flag_print,
+            args.flag_disasm,
+            args.flag_print_size,
+            &args.flag_output,
             &args.flag_set,
             &args.flag_isa,
         )
@@ -111,6 +151,16 @@ fn cton_util() -> CommandResult {
             &args.flag_isa,
             args.flag_print_size,
         )
+    } else if args.cmd_bench {
+        bench::run(
+            args.arg_file,
+            args.flag_iterations,
+            args.flag_json,
+            &args.flag_baseline,
+            args.flag_synthetic,
+            &args.flag_set,
+            &args.flag_isa,
+        )
     } else {
         // Debugging / shouldn't happen with proper command line handling above.
         Err(format!("Unhandled args: {:?}", args))
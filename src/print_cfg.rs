@@ -8,17 +8,27 @@ use cretonne::cfg_printer::CFGPrinter;
 use cton_reader::parse_functions;
 use utils::read_to_string;
 
-pub fn run(files: &[String]) -> CommandResult {
+pub fn run(
+    files: &[String],
+    flag_insts: Option<usize>,
+    flag_domtree: bool,
+    flag_loops: bool,
+) -> CommandResult {
     for (i, f) in files.into_iter().enumerate() {
         if i != 0 {
             println!();
         }
-        print_cfg(f)?
+        print_cfg(f, flag_insts, flag_domtree, flag_loops)?
     }
     Ok(())
 }
 
-fn print_cfg(filename: &str) -> CommandResult {
+fn print_cfg(
+    filename: &str,
+    flag_insts: Option<usize>,
+    flag_domtree: bool,
+    flag_loops: bool,
+) -> CommandResult {
     let buffer = read_to_string(filename).map_err(
         |e| format!("{}: {}", filename, e),
     )?;
@@ -30,7 +40,17 @@ fn print_cfg(filename: &str) -> CommandResult {
         if idx != 0 {
             println!();
         }
-        print!("{}", CFGPrinter::new(&func));
+        let mut printer = CFGPrinter::new(&func);
+        if let Some(insts) = flag_insts {
+            printer = printer.with_insts(insts);
+        }
+        if flag_domtree {
+            printer = printer.with_domtree();
+        }
+        if flag_loops {
+            printer = printer.with_loops();
+        }
+        print!("{}", printer);
     }
 
     Ok(())
@@ -1,5 +1,7 @@
 //! Utility functions.
 
+use cretonne::Context;
+use cretonne::dump::IrDump;
 use cretonne::isa::TargetIsa;
 use cretonne::settings::{self, FlagsOrIsa};
 use cretonne::isa;
@@ -44,9 +46,11 @@ impl OwnedFlagsOrIsa {
 pub fn parse_sets_and_isa(flag_set: &[String], flag_isa: &str) -> Result<OwnedFlagsOrIsa, String> {
     let mut flag_builder = settings::builder();
     parse_options(
-        flag_set.iter().map(|x| x.as_str()),
+        flag_set.iter().map(|x| x.as_str()).filter(
+            |x| !is_dump_option(x),
+        ),
         &mut flag_builder,
-        &Location { line_number: 0 },
+        &Location::default(),
     ).map_err(|err| err.to_string())?;
 
     let mut words = flag_isa.trim().split_whitespace();
@@ -57,7 +61,7 @@ pub fn parse_sets_and_isa(flag_set: &[String], flag_isa: &str) -> Result<OwnedFl
             isa::LookupError::Unsupported => format!("support for ISA '{}' not enabled", isa_name),
         })?;
         // Apply the ISA-specific settings to `isa_builder`.
-        parse_options(words, &mut isa_builder, &Location { line_number: 0 })
+        parse_options(words, &mut isa_builder, &Location::default())
             .map_err(|err| err.to_string())?;
 
         Ok(OwnedFlagsOrIsa::Isa(
@@ -67,3 +71,34 @@ pub fn parse_sets_and_isa(flag_set: &[String], flag_isa: &str) -> Result<OwnedFl
         Ok(OwnedFlagsOrIsa::Flags(settings::Flags::new(&flag_builder)))
     }
 }
+
+/// Is `option` in a "set" command one of the `dump_*` options configuring an `IrDump`?
+fn is_dump_option(option: &str) -> bool {
+    option.starts_with("dump_")
+}
+
+/// Parse the `dump_*` options in "set" commands.
+///
+/// Return `None` if there are no such options.
+pub fn parse_dump(flag_set: &[String]) -> Result<Option<IrDump>, String> {
+    let mut options = flag_set
+        .iter()
+        .map(|x| x.as_str())
+        .filter(|x| is_dump_option(x))
+        .peekable();
+    if options.peek().is_none() {
+        return Ok(None);
+    }
+    let mut dump = IrDump::new();
+    parse_options(options, &mut dump, &Location::default())
+        .map_err(|err| err.to_string())?;
+    Ok(Some(dump))
+}
+
+/// Report the first error that happened while writing the dumps of `context`.
+pub fn check_dump(context: &mut Context) -> Result<(), String> {
+    match context.dump.as_mut().and_then(IrDump::take_error) {
+        Some(err) => Err(format!("dump_dir: {}", err)),
+        None => Ok(()),
+    }
+}
@@ -15,7 +15,7 @@ use std::path::Path;
 use std::process::Command;
 use tempdir::TempDir;
 use term;
-use utils::{parse_sets_and_isa, read_to_end};
+use utils::{parse_sets_and_isa, parse_dump, check_dump, read_to_end};
 
 macro_rules! vprintln {
     ($x: expr, $($tts:tt)*) => {
@@ -44,6 +44,8 @@ pub fn run(
     flag_print_size: bool,
 ) -> Result<(), String> {
     let parsed = parse_sets_and_isa(flag_set, flag_isa)?;
+    let mut context = Context::new();
+    context.dump = parse_dump(flag_set)?;
 
     for filename in files {
         let path = Path::new(&filename);
@@ -57,9 +59,10 @@ pub fn run(
             &path.to_path_buf(),
             &name,
             parsed.as_fisa(),
+            &mut context,
         )?;
     }
-    Ok(())
+    check_dump(&mut context)
 }
 
 fn handle_module(
@@ -71,6 +74,7 @@ fn handle_module(
     path: &PathBuf,
     name: &str,
     fisa: FlagsOrIsa,
+    context: &mut Context,
 ) -> Result<(), String> {
     let mut terminal = term::stdout().unwrap();
     terminal.fg(term::color::YELLOW).unwrap();
@@ -150,7 +154,7 @@ fn handle_module(
     let mut total_module_code_size = 0;
     for (def_index, func) in dummy_environ.info.function_bodies.iter().enumerate() {
         let func_index = num_func_imports + def_index;
-        let mut context = Context::new();
+        context.clear();
         context.func = func.clone();
         if flag_check_translation {
             context.verify(fisa).map_err(|err| {
@@ -0,0 +1,106 @@
+//! Check that a reused compilation context recycles its memory.
+//!
+//! Once the data structures in a `Context` have grown to fit a function, compiling more functions
+//! of the same size should only perform the same handful of allocations every time. The verifier
+//! computes its own analyses, so it is disabled here. This binary has a counting global
+//! allocator, so it must only contain this one test.
+
+extern crate cretonne;
+
+mod common;
+
+use common::IgnoreRelocs;
+use cretonne::binemit::{NullPatchSink, NullTrapSink};
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, Function, InstBuilder};
+use cretonne::ir::types::I32;
+use cretonne::Context;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::SeqCst);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+/// Build a small loop into `func`.
+fn build_function(func: &mut Function) {
+    func.signature.params.push(AbiParam::new(I32));
+    func.signature.returns.push(AbiParam::new(I32));
+
+    let mut pos = FuncCursor::new(func);
+    let ebb0 = pos.func.dfg.make_ebb();
+    let ebb1 = pos.func.dfg.make_ebb();
+    let ebb2 = pos.func.dfg.make_ebb();
+
+    pos.insert_ebb(ebb0);
+    let arg = pos.func.dfg.append_ebb_param(ebb0, I32);
+    let zero = pos.ins().iconst(I32, 0);
+    pos.ins().jump(ebb1, &[arg, zero]);
+
+    pos.insert_ebb(ebb1);
+    let count = pos.func.dfg.append_ebb_param(ebb1, I32);
+    let acc = pos.func.dfg.append_ebb_param(ebb1, I32);
+    let sum = pos.ins().iadd(acc, count);
+    let next = pos.ins().iadd_imm(count, -1);
+    pos.ins().brnz(next, ebb1, &[next, sum]);
+    pos.ins().jump(ebb2, &[]);
+
+    pos.insert_ebb(ebb2);
+    pos.ins().return_(&[sum]);
+}
+
+#[test]
+fn reused_context_allocations() {
+    let isa = match common::intel_64bit(&[("opt_level", "best"), ("enable_verifier", "false")]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    let mut code: Vec<u8> = Vec::new();
+    let mut counts = Vec::with_capacity(10);
+    for _ in 0..10 {
+        let before = ALLOCATIONS.load(Ordering::SeqCst);
+        ctx.clear();
+        code.clear();
+        build_function(&mut ctx.func);
+        ctx.compile_and_emit(
+            &*isa,
+            &mut code,
+            &mut IgnoreRelocs,
+            &mut NullTrapSink {},
+            &mut NullPatchSink {},
+        ).unwrap();
+        counts.push(ALLOCATIONS.load(Ordering::SeqCst) - before);
+    }
+
+    // The first function grows the data structures, and the rest must reuse them.
+    assert!(counts[1] < counts[0], "allocations per function: {:?}", counts);
+    assert!(
+        counts[1..].iter().all(|&n| n == counts[1]),
+        "allocations per function: {:?}",
+        counts
+    );
+
+    // The only remaining allocation is the list of callee-saved registers returned by the ISA.
+    assert!(counts[1] <= 1, "allocations per function: {:?}", counts);
+}
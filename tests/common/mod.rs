@@ -0,0 +1,55 @@
+//! Helpers shared by the integration tests.
+//!
+//! Every test binary includes this module with `mod common;` and only uses parts of it.
+
+#![allow(dead_code)]
+
+use cretonne::binemit::{Addend, CodeOffset, Reloc, RelocSink};
+use cretonne::ir::{ExternalName, JumpTable};
+use cretonne::isa::{self, TargetIsa};
+use cretonne::settings::{self, Configurable};
+use std::thread;
+
+/// Look up the Intel ISA.
+///
+/// If Cretonne was built without it, print a message naming the running test and return `None`
+/// so the test can skip itself.
+pub fn intel_builder() -> Option<isa::Builder> {
+    match isa::lookup("intel") {
+        Ok(isa_builder) => Some(isa_builder),
+        Err(err) => {
+            println!(
+                "intel ISA not available ({:?}); skipped {}",
+                err,
+                thread::current().name().unwrap_or("test")
+            );
+            None
+        }
+    }
+}
+
+/// Create the shared flags for a 64-bit target, with additional `(name, value)` settings.
+pub fn flags_64bit(extra: &[(&str, &str)]) -> settings::Flags {
+    let mut flag_builder = settings::builder();
+    flag_builder.enable("is_64bit").unwrap();
+    for &(name, value) in extra {
+        flag_builder.set(name, value).unwrap();
+    }
+    settings::Flags::new(&flag_builder)
+}
+
+/// Build the 64-bit Intel ISA with additional `(name, value)` settings.
+///
+/// Returns `None` if the Intel ISA is not available, see `intel_builder`.
+pub fn intel_64bit(extra: &[(&str, &str)]) -> Option<Box<TargetIsa>> {
+    intel_builder().map(|isa_builder| isa_builder.finish(flags_64bit(extra)))
+}
+
+/// A relocation sink that ignores all relocations.
+pub struct IgnoreRelocs;
+
+impl RelocSink for IgnoreRelocs {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {}
+    fn reloc_external(&mut self, _: CodeOffset, _: Reloc, _: &ExternalName, _: Addend) {}
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, _: JumpTable) {}
+}
@@ -0,0 +1,94 @@
+//! Check that embedders can customize the legalization of instructions.
+
+extern crate cretonne;
+
+mod common;
+
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::flowgraph::ControlFlowGraph;
+use cretonne::ir::{AbiParam, CallConv, ExtFuncData, ExternalName, Function, Inst, InstBuilder,
+                   LibCall, LibCallNames, Opcode, Signature};
+use cretonne::ir::types::{F32, F64};
+use cretonne::isa::TargetIsa;
+use cretonne::Context;
+
+/// Replace an `fma` instruction with a call to a runtime helper.
+fn fma_to_call(inst: Inst, func: &mut Function, _: &mut ControlFlowGraph, _: &TargetIsa) -> bool {
+    let mut sig = Signature::new(CallConv::Native);
+    sig.params.extend(vec![AbiParam::new(F64); 3]);
+    sig.returns.push(AbiParam::new(F64));
+    let signature = func.import_signature(sig);
+    let callee = func.import_function(ExtFuncData {
+        name: ExternalName::testcase("fma_helper"),
+        signature,
+        noreturn: false,
+        cold: false,
+        colocated: false,
+    });
+
+    let args = func.dfg.inst_args(inst).to_vec();
+    func.dfg.replace(inst).call(callee, &args);
+    true
+}
+
+#[test]
+fn fma_helper_call() {
+    let isa = match common::intel_64bit(&[]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    ctx.custom_legalizations.register(Opcode::Fma, fma_to_call);
+    for _ in 0..3 {
+        ctx.func.signature.params.push(AbiParam::new(F64));
+    }
+    ctx.func.signature.returns.push(AbiParam::new(F64));
+    {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, F64);
+        let y = pos.func.dfg.append_ebb_param(ebb0, F64);
+        let z = pos.func.dfg.append_ebb_param(ebb0, F64);
+        let v = pos.ins().fma(x, y, z);
+        pos.ins().return_(&[v]);
+    }
+
+    ctx.compile(&*isa).unwrap();
+    let text = ctx.func.display(&*isa).to_string();
+    assert!(text.contains("call fn0("), "{}", text);
+    assert!(!text.contains("= fma "), "{}", text);
+}
+
+#[test]
+fn libcall_names() {
+    let mut isa_builder = match common::intel_builder() {
+        Some(isa_builder) => isa_builder,
+        None => return,
+    };
+    let mut names = LibCallNames::new();
+    names.set(
+        LibCall::FloorF32,
+        ExternalName::testcase("floorf"),
+        CallConv::Native,
+    );
+    isa_builder.set_libcall_names(names);
+    let isa = isa_builder.finish(common::flags_64bit(&[]));
+
+    let mut ctx = Context::new();
+    ctx.func.signature.params.push(AbiParam::new(F32));
+    ctx.func.signature.returns.push(AbiParam::new(F32));
+    {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, F32);
+        let v = pos.ins().floor(x);
+        pos.ins().return_(&[v]);
+    }
+
+    ctx.compile(&*isa).unwrap();
+    let text = ctx.func.display(&*isa).to_string();
+    assert!(text.contains("fn0 = sig0 %floorf"), "{}", text);
+}
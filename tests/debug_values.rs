@@ -0,0 +1,52 @@
+//! Check that the locations of source-level variables are resolved after compilation.
+
+extern crate cretonne;
+
+mod common;
+
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, InstBuilder, ValueLoc};
+use cretonne::ir::types::I32;
+use cretonne::Context;
+
+#[test]
+fn resolve_locations() {
+    let isa = match common::intel_64bit(&[("opt_level", "best")]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    ctx.func.signature.params.push(AbiParam::new(I32));
+    ctx.func.signature.returns.push(AbiParam::new(I32));
+    let (x, y) = {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, I32);
+        pos.ins().debug_value(x, 0);
+        let y = pos.ins().iadd_imm(x, 1);
+        pos.ins().debug_value(y, 1);
+        let z = pos.ins().imul(y, x);
+        pos.ins().return_(&[z]);
+        (x, y)
+    };
+
+    // The instructions themselves are checked by `filetests/regalloc/debug-value.cton`.
+    let code_size = ctx.compile(&*isa).unwrap();
+    assert_eq!(ctx.debug_values.len(), 2);
+    let (first, second) = (ctx.debug_values[0], ctx.debug_values[1]);
+    assert_eq!((first.var, first.value), (0, x));
+    assert_eq!((second.var, second.value), (1, y));
+    assert!(first.offset < second.offset);
+    assert!(second.offset < code_size);
+    for dv in &ctx.debug_values {
+        match dv.loc {
+            ValueLoc::Reg(_) | ValueLoc::Stack(_) => {}
+            ValueLoc::Unassigned => panic!("{} has no location", dv.value),
+        }
+    }
+
+    ctx.clear();
+    assert!(ctx.debug_values.is_empty());
+}
@@ -0,0 +1,53 @@
+//! Check that the value locations at deoptimization points are resolved after compilation.
+
+extern crate cretonne;
+
+mod common;
+
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, InstBuilder, ValueLoc};
+use cretonne::ir::types::I32;
+use cretonne::Context;
+
+#[test]
+fn resolve_locations() {
+    let isa = match common::intel_64bit(&[]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    ctx.func.signature.params.push(AbiParam::new(I32));
+    ctx.func.signature.params.push(AbiParam::new(I32));
+    ctx.func.signature.returns.push(AbiParam::new(I32));
+    {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, I32);
+        let y = pos.func.dfg.append_ebb_param(ebb0, I32);
+        pos.ins().deopt_point(1, &[]);
+        let z = pos.ins().iadd(x, y);
+        pos.ins().deopt_point(2, &[x, z]);
+        let w = pos.ins().imul(z, y);
+        pos.ins().return_(&[w]);
+    }
+
+    // The instructions themselves are checked by `filetests/regalloc/deopt-point.cton`.
+    let code_size = ctx.compile(&*isa).unwrap();
+    assert_eq!(ctx.deopt_points.len(), 2);
+    let (first, second) = (&ctx.deopt_points[0], &ctx.deopt_points[1]);
+    assert_eq!((first.id, first.locations.len()), (1, 0));
+    assert_eq!((second.id, second.locations.len()), (2, 2));
+    assert!(first.offset < second.offset);
+    assert!(second.offset < code_size);
+    for &loc in &second.locations {
+        match loc {
+            ValueLoc::Reg(_) | ValueLoc::Stack(_) => {}
+            ValueLoc::Unassigned => panic!("unassigned location in {:?}", second),
+        }
+    }
+
+    ctx.clear();
+    assert!(ctx.deopt_points.is_empty());
+}
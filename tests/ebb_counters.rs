@@ -0,0 +1,61 @@
+//! Check that the EBB counter instrumentation pass increments one counter per EBB.
+
+extern crate cretonne;
+
+mod common;
+
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, Ebb, ExternalName, Function, GlobalVarData, InstBuilder};
+use cretonne::ir::types::I32;
+use cretonne::result::CtonError;
+use cretonne::Context;
+
+fn build_function(func: &mut Function) -> Vec<Ebb> {
+    func.signature.params.push(AbiParam::new(I32));
+    func.signature.returns.push(AbiParam::new(I32));
+    let mut pos = FuncCursor::new(func);
+    let ebb0 = pos.func.dfg.make_ebb();
+    let ebb1 = pos.func.dfg.make_ebb();
+    pos.insert_ebb(ebb0);
+    let x = pos.func.dfg.append_ebb_param(ebb0, I32);
+    pos.ins().brz(x, ebb1, &[]);
+    let y = pos.ins().iadd_imm(x, 1);
+    pos.ins().return_(&[y]);
+    pos.insert_ebb(ebb1);
+    pos.ins().return_(&[x]);
+    vec![ebb0, ebb1]
+}
+
+#[test]
+fn counter_layout() {
+    let isa = match common::intel_64bit(&[("enable_ebb_counters", "true")]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    // The embedder must supply a counter table when the pass is enabled.
+    let mut ctx = Context::new();
+    build_function(&mut ctx.func);
+    match ctx.compile(&*isa) {
+        Err(CtonError::InvalidInput) => {}
+        res => panic!("unexpected result: {:?}", res),
+    }
+
+    ctx.clear();
+    let ebbs = build_function(&mut ctx.func);
+    let table = ctx.func.create_global_var(GlobalVarData::Sym {
+        name: ExternalName::testcase("counters"),
+        colocated: false,
+    });
+    ctx.ebb_counter_table = Some(table);
+    ctx.compile(&*isa).unwrap();
+    assert_eq!(ctx.ebb_counters, ebbs);
+
+    let text = ctx.func.display(&*isa).to_string();
+    assert!(text.contains("load.i64 notrap aligned"), "{}", text);
+    assert!(text.contains("+8"), "{}", text);
+
+    ctx.clear();
+    assert!(ctx.ebb_counter_table.is_none());
+    assert!(ctx.ebb_counters.is_empty());
+}
@@ -0,0 +1,70 @@
+//! Check that compiled `br_table` instructions don't leave jump tables in the machine code.
+
+extern crate cretonne;
+
+mod common;
+
+use cretonne::binemit::{Addend, CodeOffset, NullPatchSink, NullTrapSink, Reloc, RelocSink};
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, ExternalName, InstBuilder, JumpTable, JumpTableData};
+use cretonne::ir::types::I32;
+use cretonne::Context;
+
+struct CollectJumpTables(Vec<JumpTable>);
+
+impl RelocSink for CollectJumpTables {
+    fn reloc_ebb(&mut self, _: CodeOffset, _: Reloc, _: CodeOffset) {}
+    fn reloc_external(&mut self, _: CodeOffset, _: Reloc, _: &ExternalName, _: Addend) {}
+    fn reloc_jt(&mut self, _: CodeOffset, _: Reloc, jt: JumpTable) {
+        self.0.push(jt);
+    }
+}
+
+#[test]
+fn br_table_without_jump_tables() {
+    let isa = match common::intel_64bit(&[]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    ctx.func.signature.params.push(AbiParam::new(I32));
+    ctx.func.signature.returns.push(AbiParam::new(I32));
+    {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        let ebb1 = pos.func.dfg.make_ebb();
+        let ebb2 = pos.func.dfg.make_ebb();
+        let mut jt_data = JumpTableData::new();
+        jt_data.set_entry(0, ebb1);
+        jt_data.set_entry(2, ebb2);
+        let jt = pos.func.create_jump_table(jt_data);
+
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, I32);
+        pos.ins().br_table(x, jt);
+        let zero = pos.ins().iconst(I32, 0);
+        pos.ins().return_(&[zero]);
+        pos.insert_ebb(ebb1);
+        let one = pos.ins().iconst(I32, 1);
+        pos.ins().return_(&[one]);
+        pos.insert_ebb(ebb2);
+        let two = pos.ins().iconst(I32, 2);
+        pos.ins().return_(&[two]);
+    }
+
+    let mut code = Vec::new();
+    let mut relocs = CollectJumpTables(Vec::new());
+    let info = ctx.compile_and_emit(
+        &*isa,
+        &mut code,
+        &mut relocs,
+        &mut NullTrapSink {},
+        &mut NullPatchSink {},
+    ).unwrap();
+
+    // All of the code is covered by `code_size`, and nothing refers to a jump table placed after
+    // it.
+    assert_eq!(code.len(), info.code_size as usize);
+    assert_eq!(relocs.0, []);
+}
@@ -0,0 +1,74 @@
+//! Check that patchable calls are aligned and reported to the patch sink.
+
+extern crate cretonne;
+
+mod common;
+
+use common::IgnoreRelocs;
+use cretonne::binemit::{self, CodeOffset, NullTrapSink, PatchSink};
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, CallConv, ExtFuncData, ExternalName, InstBuilder, Signature};
+use cretonne::ir::types::I64;
+use cretonne::Context;
+
+struct CollectPatches(Vec<(CodeOffset, ExternalName)>);
+
+impl PatchSink for CollectPatches {
+    fn patchable_call(&mut self, offset: CodeOffset, name: &ExternalName) {
+        self.0.push((offset, name.clone()));
+    }
+}
+
+#[test]
+fn aligned_call_sites() {
+    let isa = match common::intel_64bit(&[]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    ctx.func.signature.params.push(AbiParam::new(I64));
+    ctx.func.signature.returns.push(AbiParam::new(I64));
+    let mut sig = Signature::new(CallConv::Native);
+    sig.params.push(AbiParam::new(I64));
+    sig.returns.push(AbiParam::new(I64));
+    let signature = ctx.func.import_signature(sig);
+    let callee = ctx.func.import_function(ExtFuncData {
+        name: ExternalName::testcase("tier_up"),
+        signature,
+        noreturn: false,
+        cold: false,
+        colocated: false,
+    });
+    {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, I64);
+        let call = pos.ins().call_patchable(callee, &[x]);
+        let y = pos.func.dfg.first_result(call);
+        let z = pos.ins().iadd_imm(y, 1);
+        let call = pos.ins().call_patchable(callee, &[z]);
+        let w = pos.func.dfg.first_result(call);
+        pos.ins().return_(&[w]);
+    }
+
+    let mut code = Vec::new();
+    let mut patches = CollectPatches(Vec::new());
+    let info = ctx.compile_and_emit(
+        &*isa,
+        &mut code,
+        &mut IgnoreRelocs,
+        &mut NullTrapSink {},
+        &mut patches,
+    ).unwrap();
+    assert!(info.alignment >= 4);
+    assert_eq!(info.alignment, binemit::function_alignment(&ctx.func, &*isa));
+
+    assert_eq!(patches.0.len(), 2);
+    for &(offset, ref name) in &patches.0 {
+        assert_eq!(*name, ExternalName::testcase("tier_up"));
+        assert_eq!(code[offset as usize], 0xe8);
+        assert_eq!((offset + 1) % 4, 0, "unaligned displacement at {}", offset);
+    }
+}
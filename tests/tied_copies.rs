@@ -0,0 +1,62 @@
+//! Check the statistics for copies inserted before instructions with tied operands.
+//!
+//! The copies themselves are checked by `filetests/regalloc/constraints.cton`.
+
+extern crate cretonne;
+extern crate cton_reader;
+
+mod common;
+
+use cretonne::{Context, TiedCopyStats};
+use cton_reader::parse_functions;
+
+fn tied_copy_stats(code: &str) -> Option<TiedCopyStats> {
+    let isa = common::intel_64bit(&[])?;
+
+    let func = parse_functions(code).unwrap().into_iter().next().unwrap();
+    let mut ctx = Context::for_function(func);
+    ctx.compile(&*isa).unwrap();
+    Some(ctx.regalloc.tied_copy_stats())
+}
+
+#[test]
+fn commuted_operands() {
+    let stats = tied_copy_stats(
+        "function %f(i64, i64) -> i64 {
+         ebb0(v0: i64, v1: i64):
+             v2 = iadd v0, v1
+             v3 = isub v2, v0
+             return v3
+         }",
+    );
+    if let Some(stats) = stats {
+        assert_eq!(
+            stats,
+            TiedCopyStats {
+                inserted: 0,
+                eliminated: 1,
+            }
+        );
+    }
+}
+
+#[test]
+fn non_commutative_operands() {
+    let stats = tied_copy_stats(
+        "function %f(i64, i64) -> i64 {
+         ebb0(v0: i64, v1: i64):
+             v2 = isub v0, v1
+             v3 = iadd v2, v0
+             return v3
+         }",
+    );
+    if let Some(stats) = stats {
+        assert_eq!(
+            stats,
+            TiedCopyStats {
+                inserted: 1,
+                eliminated: 0,
+            }
+        );
+    }
+}
@@ -0,0 +1,61 @@
+//! Check that compiling a `try_call` produces a call-site table entry for its landing pad.
+
+extern crate cretonne;
+
+mod common;
+
+use cretonne::binemit::CallSite;
+use cretonne::cursor::{Cursor, FuncCursor};
+use cretonne::ir::{AbiParam, CallConv, ExtFuncData, ExternalName, InstBuilder, Signature};
+use cretonne::ir::types::I64;
+use cretonne::Context;
+
+#[test]
+fn call_site_table() {
+    let isa = match common::intel_64bit(&[]) {
+        Some(isa) => isa,
+        None => return,
+    };
+
+    let mut ctx = Context::new();
+    ctx.func.signature.params.push(AbiParam::new(I64));
+    ctx.func.signature.returns.push(AbiParam::new(I64));
+    let mut sig = Signature::new(CallConv::Native);
+    sig.params.push(AbiParam::new(I64));
+    sig.returns.push(AbiParam::new(I64));
+    let signature = ctx.func.import_signature(sig);
+    let callee = ctx.func.import_function(ExtFuncData {
+        name: ExternalName::testcase("may_throw"),
+        signature,
+        noreturn: false,
+        cold: false,
+        colocated: false,
+    });
+    {
+        let mut pos = FuncCursor::new(&mut ctx.func);
+        let ebb0 = pos.func.dfg.make_ebb();
+        let pad = pos.func.dfg.make_ebb();
+        pos.insert_ebb(ebb0);
+        let x = pos.func.dfg.append_ebb_param(ebb0, I64);
+        let call = pos.ins().try_call(callee, pad, &[x]);
+        let y = pos.func.dfg.first_result(call);
+        pos.ins().return_(&[y]);
+        pos.insert_ebb(pad);
+        let zero = pos.ins().iconst(I64, 0);
+        pos.ins().return_(&[zero]);
+    }
+
+    let code_size = ctx.compile(&*isa).unwrap();
+    assert_eq!(ctx.call_sites.len(), 1);
+    let CallSite {
+        offset,
+        size,
+        landing_pad,
+    } = ctx.call_sites[0];
+    assert_eq!(size, 5);
+    assert!(offset + size <= landing_pad);
+    assert!(landing_pad < code_size);
+
+    ctx.clear();
+    assert!(ctx.call_sites.is_empty());
+}